@@ -14,6 +14,19 @@ export default class CrateSidebar extends Component {
     return homepage && (!repository || simplifyUrl(repository) !== simplifyUrl(homepage));
   }
 
+  get repositoryWarning() {
+    let status = this.args.crate.repository_status;
+    if (!status) return;
+
+    if (status.status === 'archived') {
+      return 'The repository of this crate is archived, so the crate might be unmaintained.';
+    } else if (status.status === 'not_found') {
+      return 'The repository of this crate no longer exists, so the crate might be unmaintained.';
+    } else if (status.redirected_to) {
+      return `The repository of this crate has moved to ${status.redirected_to}.`;
+    }
+  }
+
   get cargoAddCommand() {
     return this.args.requestedVersion
       ? `cargo add ${this.args.crate.name}@${this.args.requestedVersion}`
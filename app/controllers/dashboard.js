@@ -10,6 +10,7 @@ import ajax from '../utils/ajax';
 const TO_SHOW = 5;
 
 export default class DashboardController extends Controller {
+  @service session;
   @service store;
 
   hasMore = false;
@@ -35,6 +36,10 @@ export default class DashboardController extends Controller {
     return this.myFollowing.length > TO_SHOW;
   }
 
+  get singleOwnerCrates() {
+    return (this.session.ownedCrates ?? []).filter(ownedCrate => ownedCrate.needs_backup_owner);
+  }
+
   loadMoreTask = task(async () => {
     let page = this.myFeed.length / 10 + 1;
 
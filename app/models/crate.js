@@ -17,6 +17,11 @@ export default class Crate extends Model {
   @attr homepage;
   @attr documentation;
   @attr repository;
+  /**
+   * The result of the latest check whether the repository still exists,
+   * only present on the crate page.
+   */
+  @attr repository_status;
 
   @hasMany('version', { async: true, inverse: 'crate' }) versions;
   @hasMany('team', { async: true, inverse: null }) owner_team;
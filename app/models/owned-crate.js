@@ -3,4 +3,5 @@ import Model, { attr } from '@ember-data/model';
 export default class OwnedCrate extends Model {
   @attr name;
   @attr email_notifications;
+  @attr needs_backup_owner;
 }
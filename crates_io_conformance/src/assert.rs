@@ -0,0 +1,86 @@
+//! Assertions on the status codes and JSON shapes of API responses.
+//!
+//! JSON values are addressed by JSON pointers, e.g. `/crate/max_version`.
+
+use crate::client::Response;
+use anyhow::{anyhow, bail};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+pub fn status(response: &Response, expected: StatusCode) -> anyhow::Result<()> {
+    if response.status != expected {
+        bail!(
+            "expected status {expected}, found {}: {}",
+            response.status,
+            response.json
+        );
+    }
+
+    Ok(())
+}
+
+fn value<'a>(json: &'a Value, pointer: &str) -> anyhow::Result<&'a Value> {
+    json.pointer(pointer)
+        .ok_or_else(|| anyhow!("missing `{pointer}` in {json}"))
+}
+
+pub fn str<'a>(json: &'a Value, pointer: &str) -> anyhow::Result<&'a str> {
+    value(json, pointer)?
+        .as_str()
+        .ok_or_else(|| anyhow!("expected `{pointer}` to be a string in {json}"))
+}
+
+pub fn str_eq(json: &Value, pointer: &str, expected: &str) -> anyhow::Result<()> {
+    let actual = str(json, pointer)?;
+    if actual != expected {
+        bail!("expected `{pointer}` to be `{expected}`, found `{actual}`");
+    }
+
+    Ok(())
+}
+
+pub fn bool_eq(json: &Value, pointer: &str, expected: bool) -> anyhow::Result<()> {
+    let actual = value(json, pointer)?
+        .as_bool()
+        .ok_or_else(|| anyhow!("expected `{pointer}` to be a boolean in {json}"))?;
+
+    if actual != expected {
+        bail!("expected `{pointer}` to be `{expected}`, found `{actual}`");
+    }
+
+    Ok(())
+}
+
+pub fn array<'a>(json: &'a Value, pointer: &str) -> anyhow::Result<&'a Vec<Value>> {
+    value(json, pointer)?
+        .as_array()
+        .ok_or_else(|| anyhow!("expected `{pointer}` to be an array in {json}"))
+}
+
+pub fn ok_true(response: &Response) -> anyhow::Result<()> {
+    status(response, StatusCode::OK)?;
+    bool_eq(&response.json, "/ok", true)
+}
+
+/// Asserts that the response contains a list of errors with a `detail`
+/// message each, which is what cargo displays to its users.
+pub fn errors(response: &Response, expected_status: &[StatusCode]) -> anyhow::Result<()> {
+    if !expected_status.contains(&response.status) {
+        bail!(
+            "expected one of the status codes {expected_status:?}, found {}: {}",
+            response.status,
+            response.json
+        );
+    }
+
+    let errors = array(&response.json, "/errors")?;
+    if errors.is_empty() {
+        bail!("expected at least one error in {}", response.json);
+    }
+
+    for error in errors {
+        str(error, "/detail")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Context};
+use crates_io_index::Repository;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, StatusCode};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::Value;
+
+/// A response of the web API, whose body is expected to be JSON.
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub json: Value,
+}
+
+pub struct ApiClient {
+    http_client: Client,
+    api_url: String,
+    index_url: Option<String>,
+    token: SecretString,
+}
+
+impl ApiClient {
+    pub fn new(
+        api_url: &str,
+        index_url: Option<&str>,
+        token: SecretString,
+    ) -> anyhow::Result<Self> {
+        let http_client = Client::builder()
+            .user_agent("crates.io conformance test")
+            .build()?;
+
+        Ok(Self {
+            http_client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            index_url: index_url.map(|url| url.trim_end_matches('/').to_string()),
+            token,
+        })
+    }
+
+    pub fn has_index_url(&self) -> bool {
+        self.index_url.is_some()
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = format!("{}{path}", self.api_url);
+        self.http_client.request(method, url)
+    }
+
+    fn authenticated(&self, method: Method, path: &str) -> RequestBuilder {
+        self.request(method, path)
+            .header(AUTHORIZATION, self.token.expose_secret())
+    }
+
+    /// Sends an unauthenticated `GET` request.
+    pub fn get(&self, path: &str) -> anyhow::Result<Response> {
+        send(self.request(Method::GET, path))
+    }
+
+    /// Sends an authenticated `PUT` request without a body.
+    pub fn put(&self, path: &str) -> anyhow::Result<Response> {
+        send(self.authenticated(Method::PUT, path))
+    }
+
+    /// Sends an authenticated `DELETE` request.
+    pub fn delete(&self, path: &str) -> anyhow::Result<Response> {
+        send(self.authenticated(Method::DELETE, path))
+    }
+
+    /// Sends a publish request like `cargo publish` does.
+    pub fn publish(&self, body: Vec<u8>, authenticated: bool) -> anyhow::Result<Response> {
+        let request = if authenticated {
+            self.authenticated(Method::PUT, "/api/v1/crates/new")
+        } else {
+            self.request(Method::PUT, "/api/v1/crates/new")
+        };
+
+        send(request.body(body))
+    }
+
+    /// Downloads a crate file, following the redirect to the storage
+    /// location.
+    pub fn download(&self, name: &str, version: &str) -> anyhow::Result<Vec<u8>> {
+        let path = format!("/api/v1/crates/{name}/{version}/download");
+        let bytes = self
+            .request(Method::GET, &path)
+            .send()?
+            .error_for_status()?
+            .bytes()?;
+
+        Ok(bytes.to_vec())
+    }
+
+    /// Loads the sparse index file of a crate.
+    ///
+    /// Returns `None` if the crate is not in the sparse index (yet).
+    pub fn load_from_sparse_index(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<Option<Vec<crates_io_index::Crate>>> {
+        let index_url = self
+            .index_url
+            .as_ref()
+            .ok_or_else(|| anyhow!("no sparse index URL configured"))?;
+
+        let path = Repository::relative_index_file_for_url(name);
+        let url = format!("{index_url}/{path}");
+
+        let response = self.http_client.get(url).send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let text = response.error_for_status()?.text()?;
+        let records = text
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .context("Failed to parse sparse index file")?;
+
+        Ok(Some(records))
+    }
+}
+
+fn send(request: RequestBuilder) -> anyhow::Result<Response> {
+    let response = request.send()?;
+    let status = response.status();
+    let text = response.text()?;
+
+    let json = serde_json::from_str(&text)
+        .with_context(|| format!("Response with status {status} is not JSON: {text}"))?;
+
+    Ok(Response { status, json })
+}
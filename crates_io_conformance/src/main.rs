@@ -0,0 +1,80 @@
+mod assert;
+mod client;
+mod suite;
+
+#[macro_use]
+extern crate tracing;
+
+use crate::client::ApiClient;
+use crate::suite::Suite;
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use secrecy::SecretString;
+use std::time::Duration;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Runs a black-box conformance test suite against a running registry
+/// instance, covering publishing, yanking, owners, downloads and the sparse
+/// index.
+#[derive(clap::Parser, Debug)]
+struct Options {
+    /// base URL of the registry web API
+    #[arg(long, default_value = "http://localhost:8888")]
+    api_url: String,
+
+    /// base URL of the sparse index; the sparse index checks are skipped if
+    /// this is not set
+    #[arg(long)]
+    index_url: Option<String>,
+
+    /// name of the test crate that will be published to the registry
+    #[arg(long, default_value = "crates-io-conformance-test")]
+    crate_name: String,
+
+    /// API token that will be used to publish, yank and unyank a new version
+    #[arg(long, env = "CARGO_REGISTRY_TOKEN", hide_env_values = true)]
+    token: SecretString,
+
+    /// number of seconds to wait for changes to show up in the sparse index
+    #[arg(long, default_value_t = 60)]
+    index_timeout: u64,
+}
+
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+
+    let options = Options::parse();
+    debug!(?options);
+
+    let client = ApiClient::new(
+        &options.api_url,
+        options.index_url.as_deref(),
+        options.token,
+    )
+    .context("Failed to initialize API client")?;
+
+    let index_timeout = Duration::from_secs(options.index_timeout);
+    let suite = Suite::new(&client, &options.crate_name, index_timeout);
+    if !suite.run()? {
+        return Err(anyhow!("Some conformance checks have failed"));
+    }
+
+    info!("All conformance checks have passed");
+
+    Ok(())
+}
+
+fn init_tracing() {
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+
+    let log_layer = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_filter(env_filter);
+
+    tracing_subscriber::registry().with(log_layer).init();
+}
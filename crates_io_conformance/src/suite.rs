@@ -0,0 +1,353 @@
+use crate::assert;
+use crate::client::ApiClient;
+use anyhow::{anyhow, bail, Context};
+use crates_io_tarball::TarballBuilder;
+use reqwest::StatusCode;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+enum Outcome {
+    Passed,
+    Failed(anyhow::Error),
+    Skipped(&'static str),
+}
+
+pub struct Suite<'a> {
+    client: &'a ApiClient,
+    crate_name: &'a str,
+    index_timeout: Duration,
+    results: Vec<(&'static str, Outcome)>,
+}
+
+impl<'a> Suite<'a> {
+    pub fn new(client: &'a ApiClient, crate_name: &'a str, index_timeout: Duration) -> Self {
+        Self {
+            client,
+            crate_name,
+            index_timeout,
+            results: vec![],
+        }
+    }
+
+    /// Runs a single check and records its outcome. Returns `true` if the
+    /// check passed.
+    fn check<F>(&mut self, name: &'static str, f: F) -> bool
+    where
+        F: FnOnce(&Self) -> anyhow::Result<()>,
+    {
+        info!("Checking {name}…");
+
+        let outcome = match f(self) {
+            Ok(()) => Outcome::Passed,
+            Err(error) => {
+                error!("Check failed: {name}: {error:#}");
+                Outcome::Failed(error)
+            }
+        };
+
+        let passed = matches!(outcome, Outcome::Passed);
+        self.results.push((name, outcome));
+        passed
+    }
+
+    fn skip(&mut self, name: &'static str, reason: &'static str) {
+        info!("Skipping {name}: {reason}");
+        self.results.push((name, Outcome::Skipped(reason)));
+    }
+
+    /// Runs all checks of the suite, and returns `true` if none of them
+    /// failed.
+    pub fn run(mut self) -> anyhow::Result<bool> {
+        let name = self.crate_name;
+        let version = self.next_version()?;
+        info!(%name, %version, "Calculated new version number");
+
+        let tarball = build_tarball(name, &version);
+        let cksum = hex::encode(Sha256::digest(&tarball));
+        let body = publish_body(name, &version, &tarball);
+
+        self.check("publish without authentication is rejected", |suite| {
+            let response = suite.client.publish(body.clone(), false)?;
+            assert::errors(
+                &response,
+                &[StatusCode::FORBIDDEN, StatusCode::UNAUTHORIZED],
+            )
+        });
+
+        let published = self.check("publish of a new version", |suite| {
+            let response = suite.client.publish(body.clone(), true)?;
+            assert::status(&response, StatusCode::OK)?;
+            assert::str_eq(&response.json, "/crate/name", name)?;
+            assert::array(&response.json, "/warnings/invalid_categories")?;
+            assert::array(&response.json, "/warnings/invalid_badges")?;
+            assert::array(&response.json, "/warnings/other")?;
+            Ok(())
+        });
+
+        if !published {
+            self.skip_remaining();
+            return Ok(self.report());
+        }
+
+        self.check("publish of an existing version is rejected", |suite| {
+            let response = suite.client.publish(body.clone(), true)?;
+            assert::errors(&response, &[StatusCode::OK, StatusCode::BAD_REQUEST])
+        });
+
+        self.check("crate metadata", |suite| {
+            let response = suite.client.get(&format!("/api/v1/crates/{name}"))?;
+            assert::status(&response, StatusCode::OK)?;
+            assert::str_eq(&response.json, "/crate/id", name)?;
+            assert::str_eq(&response.json, "/crate/name", name)?;
+            let versions = assert::array(&response.json, "/versions")?;
+            if !versions.iter().any(|v| v["num"] == version.as_str()) {
+                bail!("version {version} is missing in the versions of the crate");
+            }
+            Ok(())
+        });
+
+        self.check("version metadata", |suite| {
+            let response = suite
+                .client
+                .get(&format!("/api/v1/crates/{name}/{version}"))?;
+            assert::status(&response, StatusCode::OK)?;
+            assert::str_eq(&response.json, "/version/crate", name)?;
+            assert::str_eq(&response.json, "/version/num", &version)?;
+            assert::str_eq(&response.json, "/version/checksum", &cksum)?;
+            assert::bool_eq(&response.json, "/version/yanked", false)?;
+            Ok(())
+        });
+
+        self.check("crate file download", |suite| {
+            let bytes = suite.client.download(name, &version)?;
+            if bytes != tarball {
+                bail!("downloaded crate file differs from the published crate file");
+            }
+            Ok(())
+        });
+
+        self.check_sparse_index(
+            "sparse index contains the new version",
+            &version,
+            |record| {
+                if record.cksum != cksum {
+                    bail!("expected checksum {cksum}, found {}", record.cksum);
+                }
+                if record.yanked == Some(true) {
+                    bail!("version is yanked");
+                }
+                Ok(())
+            },
+        );
+
+        self.check("owners", |suite| {
+            let response = suite.client.get(&format!("/api/v1/crates/{name}/owners"))?;
+            assert::status(&response, StatusCode::OK)?;
+            let owners = assert::array(&response.json, "/users")?;
+            if owners.is_empty() {
+                bail!("crate has no owners");
+            }
+            for owner in owners {
+                assert::str(owner, "/login")?;
+                assert::str(owner, "/kind")?;
+            }
+
+            let response = suite
+                .client
+                .get(&format!("/api/v1/crates/{name}/owner_user"))?;
+            assert::status(&response, StatusCode::OK)?;
+            assert::array(&response.json, "/users")?;
+
+            let response = suite
+                .client
+                .get(&format!("/api/v1/crates/{name}/owner_team"))?;
+            assert::status(&response, StatusCode::OK)?;
+            assert::array(&response.json, "/teams")?;
+            Ok(())
+        });
+
+        let yanked = self.check("yank", |suite| {
+            let path = format!("/api/v1/crates/{name}/{version}/yank");
+            assert::ok_true(&suite.client.delete(&path)?)?;
+
+            let response = suite
+                .client
+                .get(&format!("/api/v1/crates/{name}/{version}"))?;
+            assert::bool_eq(&response.json, "/version/yanked", true)
+        });
+
+        if yanked {
+            self.check_sparse_index("sparse index contains the yank", &version, |record| {
+                match record.yanked {
+                    Some(true) => Ok(()),
+                    _ => Err(anyhow!("version is not yanked")),
+                }
+            });
+        } else {
+            self.skip("sparse index contains the yank", "yank failed");
+        }
+
+        let unyanked = self.check("unyank", |suite| {
+            let path = format!("/api/v1/crates/{name}/{version}/unyank");
+            assert::ok_true(&suite.client.put(&path)?)?;
+
+            let response = suite
+                .client
+                .get(&format!("/api/v1/crates/{name}/{version}"))?;
+            assert::bool_eq(&response.json, "/version/yanked", false)
+        });
+
+        if unyanked {
+            self.check_sparse_index("sparse index contains the unyank", &version, |record| {
+                match record.yanked {
+                    Some(true) => Err(anyhow!("version is still yanked")),
+                    _ => Ok(()),
+                }
+            });
+        } else {
+            self.skip("sparse index contains the unyank", "unyank failed");
+        }
+
+        Ok(self.report())
+    }
+
+    /// Returns the next patch version after the highest published version of
+    /// the test crate, or `0.1.0` if the crate does not exist yet.
+    fn next_version(&self) -> anyhow::Result<String> {
+        let name = self.crate_name;
+        let response = self.client.get(&format!("/api/v1/crates/{name}"))?;
+        if response.status == StatusCode::NOT_FOUND {
+            return Ok("0.1.0".to_string());
+        }
+
+        assert::status(&response, StatusCode::OK)?;
+        let max_version = assert::array(&response.json, "/versions")?
+            .iter()
+            .filter_map(|version| version["num"].as_str())
+            .filter_map(|num| semver::Version::parse(num).ok())
+            .max()
+            .ok_or_else(|| anyhow!("crate `{name}` has no valid versions"))?;
+
+        let version =
+            semver::Version::new(max_version.major, max_version.minor, max_version.patch + 1);
+
+        Ok(version.to_string())
+    }
+
+    /// Waits until the sparse index record of `version` satisfies `f`, since
+    /// the index is updated asynchronously by a background job.
+    fn check_sparse_index<F>(&mut self, name: &'static str, version: &str, f: F)
+    where
+        F: Fn(&crates_io_index::Crate) -> anyhow::Result<()>,
+    {
+        if !self.client.has_index_url() {
+            self.skip(name, "no sparse index URL configured");
+            return;
+        }
+
+        self.check(name, |suite| {
+            let deadline = Instant::now() + suite.index_timeout;
+            loop {
+                let result = suite
+                    .client
+                    .load_from_sparse_index(suite.crate_name)?
+                    .and_then(|records| records.into_iter().find(|r| r.vers == version))
+                    .ok_or_else(|| anyhow!("version {version} is missing in the sparse index"))
+                    .and_then(|record| f(&record));
+
+                match result {
+                    Ok(()) => return Ok(()),
+                    Err(error) if Instant::now() >= deadline => {
+                        return Err(error).context("Timed out waiting for the sparse index");
+                    }
+                    Err(_) => sleep(Duration::from_secs(2)),
+                }
+            }
+        });
+    }
+
+    fn skip_remaining(&mut self) {
+        const REMAINING: &[&str] = &[
+            "publish of an existing version is rejected",
+            "crate metadata",
+            "version metadata",
+            "crate file download",
+            "sparse index contains the new version",
+            "owners",
+            "yank",
+            "sparse index contains the yank",
+            "unyank",
+            "sparse index contains the unyank",
+        ];
+
+        for name in REMAINING {
+            self.skip(name, "publish failed");
+        }
+    }
+
+    /// Prints the outcome of all checks, and returns `true` if none of them
+    /// failed.
+    fn report(self) -> bool {
+        println!();
+        let mut failed = 0;
+        for (name, outcome) in &self.results {
+            match outcome {
+                Outcome::Passed => println!("PASS  {name}"),
+                Outcome::Skipped(reason) => println!("SKIP  {name} ({reason})"),
+                Outcome::Failed(error) => {
+                    failed += 1;
+                    println!("FAIL  {name}: {error:#}");
+                }
+            }
+        }
+
+        println!();
+        println!("{} checks, {failed} failed", self.results.len());
+
+        failed == 0
+    }
+}
+
+fn build_tarball(name: &str, version: &str) -> Vec<u8> {
+    let manifest = format!(
+        "[package]\nname = \"{name}\"\nversion = \"{version}\"\nedition = \"2021\"\n\
+         license = \"MIT\"\ndescription = \"crates.io conformance test crate\"\n"
+    );
+
+    TarballBuilder::new(name, version)
+        .add_raw_manifest(manifest.as_bytes())
+        .add_file(&format!("{name}-{version}/src/lib.rs"), b"")
+        .build()
+}
+
+/// Builds the body of a publish request, consisting of the length-prefixed
+/// JSON metadata followed by the length-prefixed crate file.
+fn publish_body(name: &str, version: &str, tarball: &[u8]) -> Vec<u8> {
+    let metadata = json!({
+        "name": name,
+        "vers": version,
+        "deps": [],
+        "features": {},
+        "description": "crates.io conformance test crate",
+        "homepage": null,
+        "documentation": null,
+        "readme": null,
+        "readme_file": null,
+        "keywords": [],
+        "categories": [],
+        "license": "MIT",
+        "license_file": null,
+        "repository": null,
+        "links": null,
+    })
+    .to_string();
+
+    let mut body = Vec::new();
+    body.extend((metadata.len() as u32).to_le_bytes());
+    body.extend(metadata.as_bytes());
+    body.extend((tarball.len() as u32).to_le_bytes());
+    body.extend(tarball);
+    body
+}
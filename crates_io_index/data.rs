@@ -58,6 +58,10 @@ pub struct Dependency {
     pub kind: Option<DependencyKind>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub package: Option<String>,
+    /// The index URL of the registry of the dependency, or `None` if the
+    /// dependency is hosted on the same registry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
 }
 
 impl PartialOrd for Dependency {
@@ -82,6 +86,7 @@ impl Ord for Dependency {
             self.default_features,
             &self.target,
             &self.package,
+            &self.registry,
             &self.features,
         )
             .cmp(&(
@@ -92,6 +97,7 @@ impl Ord for Dependency {
                 other.default_features,
                 &other.target,
                 &other.package,
+                &other.registry,
                 &other.features,
             ))
     }
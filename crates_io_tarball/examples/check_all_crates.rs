@@ -1,6 +1,6 @@
 use anyhow::anyhow;
 use clap::Parser;
-use crates_io_tarball::process_tarball;
+use crates_io_tarball::{process_tarball, process_tarball_manifest_only, TarballLimits};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use std::fs::File;
@@ -15,6 +15,11 @@ use walkdir::WalkDir;
 pub struct Options {
     /// Path to the folder to scan for crate files
     path: PathBuf,
+
+    /// Only read the manifests of the crate files, which is a lot faster for
+    /// large crate files
+    #[arg(long)]
+    manifest_only: bool,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -22,7 +27,7 @@ fn main() -> anyhow::Result<()> {
 
     let options = Options::parse();
 
-    let path = options.path;
+    let path = &options.path;
     if !path.is_dir() {
         return Err(anyhow!("`{}` not found or not a directory", path.display()));
     }
@@ -54,12 +59,12 @@ fn main() -> anyhow::Result<()> {
     paths
         .par_iter()
         .progress_with(pb.clone())
-        .for_each(|path| process_path(path, &pb));
+        .for_each(|path| process_path(path, options.manifest_only, &pb));
 
     Ok(())
 }
 
-fn process_path(path: &Path, pb: &ProgressBar) {
+fn process_path(path: &Path, manifest_only: bool, pb: &ProgressBar) {
     let file =
         File::open(path).map_err(|error| pb.suspend(|| warn!(%error, "Failed to read crate file")));
 
@@ -69,7 +74,12 @@ fn process_path(path: &Path, pb: &ProgressBar) {
     let pkg_name = path_no_ext.file_name().unwrap().to_string_lossy();
     pb.set_message(format!("{pkg_name}"));
 
-    let result = process_tarball(&pkg_name, &file, u64::MAX);
+    let limits = TarballLimits::default();
+    let result = if manifest_only {
+        process_tarball_manifest_only(&pkg_name, &file, &limits)
+    } else {
+        process_tarball(&pkg_name, &file, &limits)
+    };
     pb.suspend(|| match result {
         Ok(result) => debug!(%pkg_name, path = %path.display(), ?result),
         Err(error) => warn!(%pkg_name, path = %path.display(), %error, "Failed to process tarball"),
@@ -1,5 +1,5 @@
+use crate::Compression;
 use flate2::read::GzEncoder;
-use flate2::Compression;
 use std::io::Read;
 
 pub struct TarballBuilder {
@@ -28,24 +28,66 @@ impl TarballBuilder {
         self
     }
 
+    /// Adds a symbolic link at `path` that points to `target`.
+    pub fn add_symlink(self, path: &str, target: &str) -> Self {
+        self.add_link(tar::EntryType::symlink(), path, target)
+    }
+
+    /// Adds a hard link at `path` to the entry at `target`.
+    pub fn add_hardlink(self, path: &str, target: &str) -> Self {
+        self.add_link(tar::EntryType::hard_link(), path, target)
+    }
+
+    fn add_link(mut self, entry_type: tar::EntryType, path: &str, target: &str) -> Self {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(0);
+        header.set_link_name(target).unwrap();
+        self.inner.append_data(&mut header, path, &[][..]).unwrap();
+
+        self
+    }
+
+    /// Adds an entry with the given header as-is, followed by `content`.
+    ///
+    /// Unlike [`TarballBuilder::add_file`], the path, size and checksum of
+    /// the header are neither validated nor fixed up, which allows building
+    /// malformed archives, e.g. with a size that doesn't match the content.
+    pub fn add_entry_with_header(mut self, header: &tar::Header, content: &[u8]) -> Self {
+        self.inner.append(header, content).unwrap();
+        self
+    }
+
     pub fn build_unzipped(self) -> Vec<u8> {
         self.inner.into_inner().unwrap()
     }
 
     pub fn build(self) -> Vec<u8> {
-        self.build_with_compression(Compression::default())
+        self.build_gzip_with_level(flate2::Compression::default())
     }
 
     pub fn build_with_compression(self, compression: Compression) -> Vec<u8> {
+        match compression {
+            Compression::Gzip => self.build(),
+            Compression::Zstd => self.build_zstd(),
+        }
+    }
+
+    pub fn build_gzip_with_level(self, level: flate2::Compression) -> Vec<u8> {
         let tarball_bytes = self.build_unzipped();
 
         let mut gzip_bytes = vec![];
-        GzEncoder::new(tarball_bytes.as_slice(), compression)
+        GzEncoder::new(tarball_bytes.as_slice(), level)
             .read_to_end(&mut gzip_bytes)
             .unwrap();
 
         gzip_bytes
     }
+
+    pub fn build_zstd(self) -> Vec<u8> {
+        let tarball_bytes = self.build_unzipped();
+        zstd::encode_all(tarball_bytes.as_slice(), 0).unwrap()
+    }
 }
 
 impl AsMut<tar::Builder<Vec<u8>>> for TarballBuilder {
@@ -0,0 +1,154 @@
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::fmt;
+use std::io::{BufReader, Chain, Cursor, ErrorKind, Read};
+use std::str::FromStr;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression format of a crate file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression format from the magic bytes at the start of
+    /// the given data, or returns `None` if the format is unknown.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(&ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(format!("unknown compression format: {s}")),
+        }
+    }
+}
+
+type Peeked<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// Decompresses a crate file in any of the supported [`Compression`] formats.
+pub enum Decoder<R: Read> {
+    Gzip(GzDecoder<Peeked<R>>),
+    Zstd(zstd::Decoder<'static, BufReader<Peeked<R>>>),
+}
+
+impl<R: Read> Decoder<R> {
+    /// Returns a decoder for the compression format detected from the magic
+    /// bytes at the start of `reader`.
+    ///
+    /// Fails with [`ErrorKind::InvalidData`] if the format is unknown.
+    pub fn new(reader: R) -> std::io::Result<Self> {
+        match Self::detect(reader)? {
+            Some((_, decoder)) => Ok(decoder),
+            None => Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "unknown compression format",
+            )),
+        }
+    }
+
+    /// Reads the magic bytes from the start of `reader` and returns the
+    /// detected compression format, together with a decoder for it.
+    ///
+    /// Returns `Ok(None)` if the format is unknown.
+    pub(crate) fn detect(mut reader: R) -> std::io::Result<Option<(Compression, Self)>> {
+        let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+        (&mut reader)
+            .take(ZSTD_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+
+        let Some(compression) = Compression::detect(&magic) else {
+            return Ok(None);
+        };
+
+        // The magic bytes are part of the compressed stream, so they have to
+        // be passed on to the decoder too.
+        let reader = Cursor::new(magic).chain(reader);
+        let decoder = match compression {
+            Compression::Gzip => Self::Gzip(GzDecoder::new(reader)),
+            Compression::Zstd => Self::Zstd(zstd::Decoder::new(reader)?),
+        };
+
+        Ok(Some((compression, decoder)))
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Gzip(decoder) => decoder.read(buf),
+            Self::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, Decoder};
+    use crate::TarballBuilder;
+    use std::io::Read;
+
+    #[test]
+    fn detect() {
+        assert_some_eq!(Compression::detect(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+        assert_some_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Compression::Zstd
+        );
+        assert_none!(Compression::detect(b"foo-0.0.1/Cargo.toml"));
+        assert_none!(Compression::detect(&[0x1f]));
+        assert_none!(Compression::detect(&[]));
+    }
+
+    #[test]
+    fn from_str() {
+        assert_ok_eq!("gzip".parse::<Compression>(), Compression::Gzip);
+        assert_ok_eq!("zstd".parse::<Compression>(), Compression::Zstd);
+        assert_err!("xz".parse::<Compression>());
+    }
+
+    #[test]
+    fn decoder() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let builder = || TarballBuilder::new("foo", "0.0.1").add_file("foo-0.0.1/a", b"a");
+            let tarball = builder().build_with_compression(compression);
+
+            let mut decoded = Vec::new();
+            let mut decoder = Decoder::new(&*tarball).unwrap();
+            decoder.read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, builder().build_unzipped());
+        }
+
+        assert!(Decoder::new(&b"foo-0.0.1/Cargo.toml"[..]).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16_BE_BOM: &[u8] = &[0xfe, 0xff];
+const UTF16_LE_BOM: &[u8] = &[0xff, 0xfe];
+
+/// The reason why a text file of a crate file could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EncodingError {
+    #[error("the file starts with a {0} byte order mark")]
+    ByteOrderMark(&'static str),
+    #[error("invalid UTF-8 at byte offset {0}")]
+    InvalidUtf8(usize),
+}
+
+/// Checks that `bytes` are valid UTF-8 without a byte order mark.
+///
+/// Byte order marks are rejected even though they are valid UTF-8, because
+/// most tools treat them as part of the content, e.g. the Markdown renderer
+/// would not recognize a heading on the first line.
+pub fn check_utf8(bytes: &[u8]) -> Result<&str, EncodingError> {
+    if bytes.starts_with(UTF8_BOM) {
+        return Err(EncodingError::ByteOrderMark("UTF-8"));
+    } else if bytes.starts_with(UTF16_BE_BOM) {
+        return Err(EncodingError::ByteOrderMark("UTF-16BE"));
+    } else if bytes.starts_with(UTF16_LE_BOM) {
+        return Err(EncodingError::ByteOrderMark("UTF-16LE"));
+    }
+
+    std::str::from_utf8(bytes).map_err(|error| EncodingError::InvalidUtf8(error.valid_up_to()))
+}
+
+/// Decodes `bytes` as UTF-8, stripping a UTF-8 byte order mark and replacing
+/// invalid sequences with `U+FFFD REPLACEMENT CHARACTER`.
+///
+/// This is meant for files of crates that were published before the
+/// encoding was checked, which should still be rendered somehow.
+pub fn decode_utf8_lossy(bytes: &[u8]) -> Cow<'_, str> {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    String::from_utf8_lossy(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_utf8, decode_utf8_lossy, EncodingError};
+
+    #[test]
+    fn check() {
+        assert_ok_eq!(check_utf8(b"# foo"), "# foo");
+        assert_ok_eq!(check_utf8("# föö".as_bytes()), "# föö");
+        assert_ok_eq!(check_utf8(b""), "");
+        assert_err_eq!(check_utf8(b"# f\xf6\xf6"), EncodingError::InvalidUtf8(3));
+        assert_err_eq!(
+            check_utf8(b"\xef\xbb\xbf# foo"),
+            EncodingError::ByteOrderMark("UTF-8")
+        );
+        assert_err_eq!(
+            check_utf8(b"\xff\xfe#\x00"),
+            EncodingError::ByteOrderMark("UTF-16LE")
+        );
+    }
+
+    #[test]
+    fn decode_lossy() {
+        assert_eq!(decode_utf8_lossy(b"# foo"), "# foo");
+        assert_eq!(decode_utf8_lossy(b"\xef\xbb\xbf# foo"), "# foo");
+        assert_eq!(decode_utf8_lossy(b"# f\xf6o"), "# f\u{fffd}o");
+    }
+}
@@ -0,0 +1,187 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// The number of file hashes that are kept in a fingerprint.
+pub const FINGERPRINT_SIZE: usize = 64;
+
+/// Files that are generated or rewritten by `cargo package`, and thus differ
+/// between crates even if their source files are identical.
+const IGNORED_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.toml.orig",
+    "Cargo.lock",
+    ".cargo_vcs_info.json",
+];
+
+/// Prefixes of file names of license texts, which are shared by many
+/// unrelated crates.
+const IGNORED_FILE_PREFIXES: &[&str] = &["LICENSE", "LICENCE", "COPYING"];
+
+/// A compact fingerprint of the source files of a crate file.
+///
+/// The fingerprint consists of the smallest hashes of the normalized file
+/// contents (a "bottom-k sketch"), which allows estimating how similar the
+/// sets of files of two crate files are without storing a hash for every file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContentFingerprint {
+    /// The smallest hashes of the file contents, in ascending order.
+    pub hashes: Vec<i64>,
+    /// The number of distinct non-empty files that contributed to the
+    /// fingerprint.
+    pub file_count: usize,
+}
+
+impl ContentFingerprint {
+    /// Estimates the Jaccard similarity of the file sets of two fingerprints,
+    /// ranging from `0.0` for no common files to `1.0` for identical files.
+    pub fn similarity(&self, other: &[i64]) -> f64 {
+        let union = self
+            .hashes
+            .iter()
+            .chain(other)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .take(FINGERPRINT_SIZE)
+            .collect::<Vec<_>>();
+
+        if union.is_empty() {
+            return 0.0;
+        }
+
+        let common = union
+            .iter()
+            .filter(|&&hash| self.hashes.binary_search(hash).is_ok() && other.contains(hash))
+            .count();
+
+        common as f64 / union.len() as f64
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FingerprintBuilder {
+    hashes: BTreeSet<i64>,
+}
+
+impl FingerprintBuilder {
+    /// Adds a file of the package, with its `path` relative to the package
+    /// root.
+    pub(crate) fn add_file(&mut self, path: &Path, contents: &[u8]) {
+        if IGNORED_FILES.iter().any(|file| path == Path::new(file)) {
+            return;
+        }
+
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let file_name = file_name.to_ascii_uppercase();
+        if IGNORED_FILE_PREFIXES
+            .iter()
+            .any(|prefix| file_name.starts_with(prefix))
+        {
+            return;
+        }
+
+        let normalized = normalize(contents);
+        if normalized.is_empty() {
+            return;
+        }
+
+        let digest = Sha256::digest(&normalized);
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        self.hashes.insert(i64::from_be_bytes(bytes));
+    }
+
+    pub(crate) fn build(self) -> ContentFingerprint {
+        ContentFingerprint {
+            file_count: self.hashes.len(),
+            hashes: self.hashes.into_iter().take(FINGERPRINT_SIZE).collect(),
+        }
+    }
+}
+
+/// Normalizes text files, so that different line endings and whitespace at
+/// the end of lines don't affect the fingerprint. Binary files are used as is.
+fn normalize(contents: &[u8]) -> Vec<u8> {
+    let Ok(text) = std::str::from_utf8(contents) else {
+        return contents.to_vec();
+    };
+
+    let mut normalized = String::with_capacity(text.len());
+    for line in text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+    {
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+
+    normalized.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(files: &[(&str, &str)]) -> ContentFingerprint {
+        let mut builder = FingerprintBuilder::default();
+        for (path, contents) in files {
+            builder.add_file(Path::new(path), contents.as_bytes());
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn normalized_contents() {
+        let a = fingerprint(&[
+            ("Cargo.toml", "[package]\nname = \"foo\""),
+            ("src/lib.rs", "pub fn foo() {}\n\n"),
+            ("LICENSE-APACHE", "Apache License"),
+        ]);
+        let b = fingerprint(&[
+            ("Cargo.toml", "[package]\nname = \"bar\""),
+            ("src/main.rs", "pub fn foo() {}  \r\n"),
+            ("src/empty.rs", ""),
+            ("license.txt", "MIT License"),
+        ]);
+        assert_eq!(a, b);
+        assert_eq!(a.file_count, 1);
+    }
+
+    #[test]
+    fn similarity() {
+        let files = (0..10)
+            .map(|i| (format!("src/{i}.rs"), format!("fn f{i}() {{}}")))
+            .collect::<Vec<_>>();
+        let files = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+            .collect::<Vec<_>>();
+
+        let a = fingerprint(&files);
+        assert_eq!(a.similarity(&a.hashes), 1.0);
+
+        let b = fingerprint(&files[..9]);
+        assert_eq!(a.similarity(&b.hashes), 0.9);
+
+        let c = fingerprint(&[("src/lib.rs", "fn other() {}")]);
+        assert_eq!(a.similarity(&c.hashes), 0.0);
+        assert_eq!(ContentFingerprint::default().similarity(&[]), 0.0);
+    }
+
+    #[test]
+    fn bounded_size() {
+        let files = (0..100)
+            .map(|i| (format!("src/{i}.rs"), format!("fn f{i}() {{}}")))
+            .collect::<Vec<_>>();
+        let files = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+            .collect::<Vec<_>>();
+
+        let fingerprint = fingerprint(&files);
+        assert_eq!(fingerprint.hashes.len(), FINGERPRINT_SIZE);
+        assert_eq!(fingerprint.file_count, 100);
+        assert!(fingerprint.hashes.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,34 @@
+use sha2::{Digest, Sha256};
+use std::io::{self, Read};
+
+/// Computes the SHA256 checksum of all bytes that are read through it, so
+/// that the crate file doesn't have to be read twice.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Reads the rest of the inner reader, since decompressors don't have to
+    /// read up to the end of their input, and returns the checksum of all
+    /// bytes.
+    pub fn finalize(mut self) -> io::Result<[u8; 32]> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.hasher.finalize().into())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let num_bytes = self.inner.read(buf)?;
+        self.hasher.update(&buf[..num_bytes]);
+        Ok(num_bytes)
+    }
+}
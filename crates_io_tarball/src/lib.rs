@@ -4,24 +4,83 @@ extern crate claims;
 
 #[cfg(any(feature = "builder", test))]
 pub use crate::builder::TarballBuilder;
+pub use crate::compression::{Compression, Decoder};
+pub use crate::encoding::{check_utf8, decode_utf8_lossy, EncodingError};
+pub use crate::fingerprint::ContentFingerprint;
+use crate::fingerprint::FingerprintBuilder;
+use crate::hashing_reader::HashingReader;
+use crate::license::looks_like_license;
+pub use crate::license::{validate_license_expr, LicenseFile, LicenseInfo};
 use crate::limit_reader::LimitErrorReader;
-pub use crate::manifest::Manifest;
-pub use crate::vcs_info::CargoVcsInfo;
-use flate2::read::GzDecoder;
+pub use crate::lockfile::{LockedPackage, LockfileInfo};
+use crate::manifest::{inherited_package_field, normalize_path};
+pub use crate::manifest::{validate_manifest, Dependency, FeatureError, Manifest, ManifestLimits};
+use crate::package_root::equivalent_root;
+use crate::scanner::{scan_file, ScanFinding, ScanReport};
+pub use crate::summary::{summarize_tarball, SummaryEntry, TarballSummary};
+pub use crate::validation::{
+    DenyPaths, MaxFileSize, NoExecutables, TarballEntry, TarballValidator, ValidatorSet,
+};
+pub use crate::vcs_info::{CargoVcsInfo, GitVcsInfo};
+use std::collections::{HashMap, HashSet};
 use std::io::Read;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use tracing::instrument;
 
 #[cfg(any(feature = "builder", test))]
 mod builder;
+mod compression;
+mod encoding;
+mod fingerprint;
+mod hashing_reader;
+mod license;
 mod limit_reader;
+mod lockfile;
 mod manifest;
+mod package_root;
+pub mod scanner;
+mod summary;
+mod validation;
 mod vcs_info;
 
 #[derive(Debug)]
 pub struct TarballInfo {
     pub manifest: Option<Manifest>,
     pub vcs_info: Option<CargoVcsInfo>,
+    /// The contents of the `Cargo.lock` file, if the crate file includes one.
+    pub lockfile: Option<LockfileInfo>,
+    /// Why the `Cargo.lock` file could not be read, if the crate file
+    /// includes a malformed one. The lockfile is only used for warnings, so
+    /// this does not reject the crate file.
+    pub lockfile_error: Option<String>,
+    /// The contents of the readme file referenced by the manifest, or of
+    /// the first of cargo's default readme files if the manifest doesn't
+    /// specify one.
+    pub readme_contents: Option<String>,
+    /// The path of the readme file relative to the package root, if the
+    /// crate file includes one.
+    pub readme_path: Option<PathBuf>,
+    /// The `license` expression of the manifest and the license files of
+    /// the crate file.
+    pub license: LicenseInfo,
+    pub fingerprint: ContentFingerprint,
+    /// Files that look like compiled binaries or otherwise don't belong in
+    /// a source package.
+    pub scan: ScanReport,
+    /// The compression format of the crate file.
+    pub compression: Compression,
+    /// The SHA256 checksum of the crate file, as used in the index.
+    pub tarball_checksum: [u8; 32],
+    /// All regular files of the tarball, in the order of the archive.
+    pub files: Vec<TarballFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarballFile {
+    /// Path of the file relative to the package root, e.g. `src/lib.rs`.
+    pub path: PathBuf,
+    /// Decompressed size of the file in bytes.
+    pub size: u64,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -32,43 +91,221 @@ pub enum TarballError {
     InvalidPath(String),
     #[error("unexpected symlink or hard link found: {0}")]
     UnexpectedSymlink(String),
+    #[error("unsupported entry type found: {0}")]
+    UnsupportedEntryType(String),
+    #[error("uploaded tarball uses an unknown compression format")]
+    UnknownCompression,
+    #[error("uploaded tarball contains more than {0} entries")]
+    TooManyEntries(u64),
+    #[error("the file `{path}` is larger than the maximum file size of {max} bytes")]
+    FileTooLarge { path: String, max: u64 },
+    #[error("duplicate path found: {0}")]
+    DuplicatePath(String),
+    #[error("the file `{path}` has an invalid encoding: {error}")]
+    InvalidEncoding { path: String, error: EncodingError },
+    #[error("the file `{path}` was rejected: {message}")]
+    Rejected { path: String, message: String },
+    #[error("the `license` field `{expression}` is not a valid SPDX license expression: {reason}")]
+    InvalidLicense { expression: String, reason: String },
+    #[error("invalid feature in `Cargo.toml`: {0}")]
+    InvalidFeature(#[source] FeatureError),
+    #[error("the manifest declares {count} features, but at most {max} are allowed")]
+    TooManyFeatures { count: usize, max: usize },
+    #[error("the manifest declares {count} dependencies, but at most {max} are allowed")]
+    TooManyDependencies { count: usize, max: usize },
+    #[error(
+        "`{field}` uses workspace inheritance, which is not supported in crate files; \
+         publish with cargo 1.64 or newer, which replaces inherited values with the \
+         values of the workspace"
+    )]
+    InheritedManifestValue { field: String },
     #[error(transparent)]
     IO(#[from] std::io::Error),
 }
 
-#[instrument(skip_all, fields(%pkg_name))]
+/// The limits that are enforced on crate files by [`process_tarball`] and
+/// its variants.
+///
+/// The default doesn't limit anything, for crate files that were already
+/// accepted on publish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TarballLimits {
+    /// Maximum decompressed size of the whole crate file in bytes.
+    pub max_unpack: u64,
+    /// Maximum number of entries in the crate file.
+    pub max_entries: u64,
+    /// Maximum decompressed size of a single file in bytes.
+    pub max_file_size: u64,
+    /// Whether paths that only differ in case are rejected as duplicates,
+    /// since they can't be extracted on case-insensitive file systems.
+    pub case_insensitive_paths: bool,
+}
+
+impl Default for TarballLimits {
+    fn default() -> Self {
+        Self {
+            max_unpack: u64::MAX,
+            max_entries: u64::MAX,
+            max_file_size: u64::MAX,
+            case_insensitive_paths: false,
+        }
+    }
+}
+
 pub fn process_tarball<R: Read>(
     pkg_name: &str,
     tarball: R,
-    max_unpack: u64,
+    limits: &TarballLimits,
+) -> Result<TarballInfo, TarballError> {
+    process_tarball_with_validator(pkg_name, tarball, limits, &ValidatorSet::new())
+}
+
+/// Like [`process_tarball`], but additionally runs the `validator` on every
+/// regular file and directory of the tarball, rejecting the tarball if any
+/// entry fails it.
+#[instrument(skip_all, fields(%pkg_name))]
+pub fn process_tarball_with_validator<R: Read>(
+    pkg_name: &str,
+    tarball: R,
+    limits: &TarballLimits,
+    validator: &dyn TarballValidator,
 ) -> Result<TarballInfo, TarballError> {
-    // All our data is currently encoded with gzip
-    let decoder = GzDecoder::new(tarball);
+    read_tarball(pkg_name, tarball, limits, ReadMode::Full(validator))
+}
+
+/// A fast path of [`process_tarball`] for tools that only need the manifest
+/// and the vcs info of crate files that were already accepted, like bulk
+/// revalidations.
+///
+/// All entries are still checked based on their headers, e.g. for invalid
+/// paths, links and duplicates, but only the contents of `Cargo.toml` and
+/// `.cargo_vcs_info.json` are read. The readme, lockfile, license files,
+/// fingerprint and scan report of the returned [`TarballInfo`] are thus
+/// always empty.
+#[instrument(skip_all, fields(%pkg_name))]
+pub fn process_tarball_manifest_only<R: Read>(
+    pkg_name: &str,
+    tarball: R,
+    limits: &TarballLimits,
+) -> Result<TarballInfo, TarballError> {
+    read_tarball(pkg_name, tarball, limits, ReadMode::ManifestOnly)
+}
 
-    // Don't let gzip decompression go into the weeeds, apply a fixed cap after
+/// Like [`process_tarball`], but for crate files that were accepted by older
+/// versions of crates.io, which didn't check everything that is checked on
+/// publish today, like backfills of metadata for old versions.
+///
+/// Instead of rejecting the crate file, links, special files and duplicates
+/// are skipped, and a manifest, lockfile or readme that can't be read is
+/// treated as missing. The `license` expression is returned as is, without
+/// validating it. Entries outside of the package directory are still
+/// rejected, since crates.io always rejected them.
+#[instrument(skip_all, fields(%pkg_name))]
+pub fn process_tarball_lenient<R: Read>(
+    pkg_name: &str,
+    tarball: R,
+) -> Result<TarballInfo, TarballError> {
+    read_tarball(
+        pkg_name,
+        tarball,
+        &TarballLimits::default(),
+        ReadMode::Lenient,
+    )
+}
+
+/// Which entries of a crate file [`read_tarball`] reads the contents of.
+#[derive(Clone, Copy)]
+enum ReadMode<'a> {
+    /// Reads the contents of all regular files and runs the validator on
+    /// them.
+    Full(&'a dyn TarballValidator),
+    /// Reads only the contents of the manifest and the vcs info, all other
+    /// entries are skipped after their headers were checked.
+    ManifestOnly,
+    /// Reads the contents of all regular files without a validator, and
+    /// skips what the other modes reject, see [`process_tarball_lenient`].
+    Lenient,
+}
+
+fn read_tarball<R: Read>(
+    pkg_name: &str,
+    tarball: R,
+    limits: &TarballLimits,
+    mode: ReadMode<'_>,
+) -> Result<TarballInfo, TarballError> {
+    let TarballLimits {
+        max_unpack,
+        max_entries,
+        max_file_size,
+        case_insensitive_paths,
+    } = *limits;
+    let lenient = matches!(mode, ReadMode::Lenient);
+    let mut tarball = HashingReader::new(tarball);
+
+    // Crate files are usually compressed with gzip, but we also support zstd,
+    // so we look at the magic bytes to pick the right decoder.
+    let (compression, decoder) = Decoder::detect(&mut tarball)
+        .map_err(TarballError::Malformed)?
+        .ok_or(TarballError::UnknownCompression)?;
+
+    // Don't let decompression go into the weeeds, apply a fixed cap after
     // which point we say the decompressed source is "too large".
     let decoder = LimitErrorReader::new(decoder, max_unpack);
 
     // Use this I/O object now to take a peek inside
     let mut archive = tar::Archive::new(decoder);
 
-    let vcs_info_path = Path::new(&pkg_name).join(".cargo_vcs_info.json");
+    // The top-level directory of the first entry, which all other entries
+    // have to share.
+    let mut package_root: Option<PathBuf> = None;
+
     let mut vcs_info = None;
+    let mut manifest: Option<Manifest> = None;
+    let mut lockfile = None;
+    let mut lockfile_error = None;
+
+    let mut fingerprint = FingerprintBuilder::default();
+    let mut files = Vec::new();
+    let mut scan = ScanReport::default();
+    let mut seen_paths = HashSet::new();
+    // The readme file is only known once the manifest has been read, so we
+    // remember which files are not valid text until then.
+    let mut encoding_errors = HashMap::new();
+
+    // Cargo puts the manifest before the readme file, but for other tarballs
+    // we keep files that look like a readme until the manifest is known.
+    // The readme is picked from the candidates once all files were read,
+    // since the default readme files have to be tried in order.
+    let mut readme_candidates = HashMap::new();
+    // License files are collected in the same way, since the manifest may
+    // reference a license file with an arbitrary name.
+    let mut license_candidates = Vec::new();
 
-    let manifest_path = Path::new(&pkg_name).join("Cargo.toml");
-    let manifest_path_lower = Path::new(&pkg_name).join("cargo.toml");
-    let mut manifest = None;
+    for (index, entry) in archive.entries()?.enumerate() {
+        // The total size limit doesn't protect against a huge number of tiny
+        // entries, which would still take a long time to process.
+        if index as u64 >= max_entries {
+            return Err(TarballError::TooManyEntries(max_entries));
+        }
 
-    for entry in archive.entries()? {
         let mut entry = entry.map_err(TarballError::Malformed)?;
 
         // Verify that all entries actually start with `$name-$vers/`.
         // Historically Cargo didn't verify this on extraction so you could
         // upload a tarball that contains both `foo-0.1.0/` source code as well
         // as `bar-0.1.0/` source code, and this could overwrite other crates in
-        // the registry!
-        let entry_path = entry.path()?;
-        if !entry_path.starts_with(pkg_name) {
+        // the registry! The directory may differ in the spelling of the build
+        // metadata of the version, but all entries have to agree on it.
+        let entry_path = entry.path()?.into_owned();
+        let root = match &package_root {
+            Some(root) => root,
+            None => {
+                let root = equivalent_root(&entry_path, pkg_name)
+                    .ok_or_else(|| TarballError::InvalidPath(entry_path.display().to_string()))?;
+                &*package_root.insert(root)
+            }
+        };
+        if !entry_path.starts_with(root) {
             return Err(TarballError::InvalidPath(entry_path.display().to_string()));
         }
 
@@ -79,32 +316,286 @@ pub fn process_tarball<R: Read>(
         // generate a tarball with these file types so this should work for now.
         let entry_type = entry.header().entry_type();
         if entry_type.is_hard_link() || entry_type.is_symlink() {
+            if lenient {
+                continue;
+            }
             return Err(TarballError::UnexpectedSymlink(
                 entry_path.display().to_string(),
             ));
         }
 
-        if entry_path == vcs_info_path {
-            let mut contents = String::new();
-            entry.read_to_string(&mut contents)?;
-            vcs_info = CargoVcsInfo::from_contents(&contents).ok();
-        } else if entry_path == manifest_path || entry_path == manifest_path_lower {
+        // Only regular files and directories are accepted. Other entry types,
+        // like contiguous files, are unpacked as regular files by some
+        // extractors, so they must not bypass the checks of regular files.
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            if lenient {
+                continue;
+            }
+            return Err(TarballError::UnsupportedEntryType(
+                entry_path.display().to_string(),
+            ));
+        }
+
+        if entry.size() > max_file_size {
+            return Err(TarballError::FileTooLarge {
+                path: entry_path.display().to_string(),
+                max: max_file_size,
+            });
+        }
+
+        // Different extractors handle duplicate files differently, e.g. by
+        // keeping the first or the last one, so a tarball with duplicates
+        // could show different contents to different tools. With
+        // `case_insensitive_paths`, files that would overwrite each other
+        // on case-insensitive file systems are rejected too.
+        // The paths are compared in their normalized form, since e.g.
+        // `foo-0.0.1/./Cargo.toml` is extracted to the same file as
+        // `foo-0.0.1/Cargo.toml`.
+        let seen_path = entry_path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect::<PathBuf>();
+        let seen_path = seen_path.to_string_lossy();
+        let seen_path = if case_insensitive_paths {
+            seen_path.to_lowercase()
+        } else {
+            seen_path.into_owned()
+        };
+        if !seen_paths.insert(seen_path) {
+            if lenient {
+                continue;
+            }
+            return Err(TarballError::DuplicatePath(
+                entry_path.display().to_string(),
+            ));
+        }
+
+        let path = entry_path.strip_prefix(root).unwrap_or(&entry_path);
+        let path = path.to_path_buf();
+
+        let is_file = entry_type.is_file();
+        let is_vcs_info = is_file && path == Path::new(".cargo_vcs_info.json");
+        let is_manifest =
+            is_file && (path == Path::new("Cargo.toml") || path == Path::new("cargo.toml"));
+
+        // Everything above only needs the header of the entry. Skipping the
+        // contents saves copying, validating and hashing them, while the
+        // `tar` crate still decompresses them to get to the next header.
+        let validator = match mode {
+            ReadMode::Full(validator) => Some(validator),
+            ReadMode::ManifestOnly | ReadMode::Lenient => None,
+        };
+        let read_all = !matches!(mode, ReadMode::ManifestOnly);
+        let read_contents = is_file && (read_all || is_vcs_info || is_manifest);
+
+        let mut contents = Vec::new();
+        if read_contents {
+            entry
+                .read_to_end(&mut contents)
+                .map_err(TarballError::Malformed)?;
+        }
+
+        // Directories are validated too, with empty contents, so that
+        // validators checking paths see every entry.
+        if let Some(validator) = validator {
+            let validator_entry = TarballEntry {
+                path: &path,
+                size: contents.len() as u64,
+                contents: &contents,
+            };
+            validator
+                .validate(&validator_entry)
+                .map_err(|message| TarballError::Rejected {
+                    path: entry_path.display().to_string(),
+                    message,
+                })?;
+        }
+
+        if !is_file {
+            continue;
+        }
+
+        files.push(TarballFile {
+            path: path.clone(),
+            size: entry.size(),
+        });
+
+        if !read_contents {
+            continue;
+        }
+
+        if validator.is_some() {
+            if let Some(kind) = scan_file(&path, &contents) {
+                let path = path.clone();
+                scan.findings.push(ScanFinding { path, kind });
+            }
+        }
+
+        if is_vcs_info {
+            let contents = std::str::from_utf8(&contents).ok();
+            vcs_info = contents.and_then(|contents| CargoVcsInfo::from_contents(contents).ok());
+        } else if is_manifest {
             // Try to extract and read the Cargo.toml from the tarball, silently
-            // erroring if it cannot be read.
-            let mut contents = String::new();
-            entry.read_to_string(&mut contents)?;
-            manifest = toml::from_str(&contents).ok();
+            // erroring if it cannot be parsed.
+            let path = entry_path.display().to_string();
+            let contents = match check_utf8(&contents) {
+                Ok(contents) => contents,
+                Err(_) if lenient => continue,
+                Err(error) => return Err(TarballError::InvalidEncoding { path, error }),
+            };
+            if let Some(field) = inherited_package_field(contents).filter(|_| !lenient) {
+                return Err(TarballError::InheritedManifestValue { field });
+            }
+            manifest = toml::from_str(contents).ok();
+        } else {
+            fingerprint.add_file(&path, &contents);
+
+            // The lockfile is part of the fingerprint like any other file.
+            // Cargo ignores the lockfile of dependencies, so a malformed one
+            // is only reported back instead of rejecting the crate file.
+            if path == Path::new("Cargo.lock") {
+                let info = check_utf8(&contents)
+                    .map_err(|error| error.to_string())
+                    .and_then(|contents| {
+                        LockfileInfo::from_contents(contents).map_err(|error| error.to_string())
+                    });
+                match info {
+                    Ok(info) => lockfile = Some(info),
+                    Err(error) => lockfile_error = Some(error),
+                }
+            } else {
+                match check_utf8(&contents) {
+                    Ok(text) => {
+                        let license_file = manifest
+                            .as_ref()
+                            .and_then(|manifest| manifest.package.license_file.as_deref());
+                        if looks_like_license(&path) || license_file == Some(path.as_path()) {
+                            license_candidates.push(LicenseFile {
+                                path: path.clone(),
+                                contents: text.to_string(),
+                            });
+                        }
+
+                        let readme_path = normalize_path(&path).filter(|path| match &manifest {
+                            Some(manifest) => manifest.package.readme_paths().contains(path),
+                            None => looks_like_readme(path),
+                        });
+                        if let Some(readme_path) = readme_path {
+                            readme_candidates.insert(readme_path, text.to_string());
+                        }
+                    }
+                    Err(error) => {
+                        if let Some(path) = normalize_path(&path) {
+                            encoding_errors.insert(path, error);
+                        }
+                    }
+                }
+            }
         }
     }
 
-    Ok(TarballInfo { manifest, vcs_info })
+    let mut readme_contents = None;
+    let mut readme_path = None;
+    let readme_paths = manifest
+        .as_ref()
+        .map(|manifest| manifest.package.readme_paths())
+        .unwrap_or_default();
+    for path in readme_paths {
+        if let Some(error) = encoding_errors.remove(&path) {
+            if lenient {
+                break;
+            }
+            let root = package_root.as_deref().unwrap_or(Path::new(pkg_name));
+            let path = root.join(path).display().to_string();
+            return Err(TarballError::InvalidEncoding { path, error });
+        }
+
+        if let Some(contents) = readme_candidates.remove(&path) {
+            readme_contents = Some(contents);
+            readme_path = Some(path);
+            break;
+        }
+    }
+
+    let license = license_info(manifest.as_ref(), license_candidates, !lenient)?;
+
+    let tarball_checksum = tarball.finalize()?;
+
+    Ok(TarballInfo {
+        manifest,
+        vcs_info,
+        lockfile,
+        lockfile_error,
+        readme_contents,
+        readme_path,
+        license,
+        fingerprint: fingerprint.build(),
+        scan,
+        compression,
+        tarball_checksum,
+        files,
+    })
+}
+
+/// Validates the `license` field of the manifest, unless `validate` is false,
+/// and picks the file that the `license-file` field references from the
+/// collected license files.
+fn license_info(
+    manifest: Option<&Manifest>,
+    mut candidates: Vec<LicenseFile>,
+    validate: bool,
+) -> Result<LicenseInfo, TarballError> {
+    let Some(package) = manifest.map(|manifest| &manifest.package) else {
+        candidates.retain(|file| looks_like_license(&file.path));
+        return Ok(LicenseInfo {
+            files: candidates,
+            ..Default::default()
+        });
+    };
+
+    if let Some(expression) = package.license.as_ref().filter(|_| validate) {
+        validate_license_expr(expression).map_err(|reason| TarballError::InvalidLicense {
+            expression: expression.clone(),
+            reason,
+        })?;
+    }
+
+    let license_file = package.license_file.as_deref().and_then(|license_file| {
+        candidates
+            .iter()
+            .find(|file| file.path == license_file)
+            .cloned()
+    });
+
+    candidates.retain(|file| looks_like_license(&file.path));
+
+    Ok(LicenseInfo {
+        expression: package.license.clone(),
+        license_file,
+        files: candidates,
+    })
+}
+
+/// Returns whether the file name of `path` starts with `readme`, ignoring
+/// case, like `README.md` or `readme.txt`.
+fn looks_like_readme(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.get(..6))
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("readme"))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::process_tarball;
+    use super::{
+        process_tarball, process_tarball_lenient, process_tarball_manifest_only,
+        process_tarball_with_validator, Compression, DenyPaths, EncodingError, MaxFileSize,
+        NoExecutables, TarballError, TarballFile, TarballLimits, ValidatorSet,
+    };
+    use crate::scanner::{ScanFinding, ScanFindingKind};
     use crate::TarballBuilder;
     use cargo_toml::OptionalFile;
+    use sha2::{Digest, Sha256};
     use std::path::Path;
 
     #[test]
@@ -113,14 +604,563 @@ mod tests {
             .add_raw_manifest(b"")
             .build();
 
-        let limit = 512 * 1024 * 1024;
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
         assert_eq!(
-            process_tarball("foo-0.0.1", &*tarball, limit)
+            process_tarball("foo-0.0.1", &*tarball, &limits)
                 .unwrap()
                 .vcs_info,
             None
         );
-        assert_err!(process_tarball("bar-0.0.1", &*tarball, limit));
+        assert_err!(process_tarball("bar-0.0.1", &*tarball, &limits));
+    }
+
+    #[test]
+    fn process_tarball_test_compression() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .build();
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_eq!(tarball_info.compression, Compression::Gzip);
+        assert_eq!(tarball_info.tarball_checksum, *Sha256::digest(&tarball));
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .build_zstd();
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_eq!(tarball_info.compression, Compression::Zstd);
+        assert_eq!(tarball_info.tarball_checksum, *Sha256::digest(&tarball));
+        assert_eq!(tarball_info.files.len(), 1);
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .build_unzipped();
+        let error = assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_matches!(error, TarballError::UnknownCompression);
+
+        let error = assert_err!(process_tarball("foo-0.0.1", &[][..], &limits));
+        assert_matches!(error, TarballError::UnknownCompression);
+    }
+
+    #[test]
+    fn process_tarball_test_zstd_limit() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_file("foo-0.0.1/src/lib.rs", &[b' '; 10_000])
+            .build_zstd();
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits {
+                max_unpack: 1000,
+                ..Default::default()
+            }
+        ));
+        assert_matches!(error, TarballError::Malformed(_));
+    }
+
+    #[test]
+    fn process_tarball_test_equivalent_roots() {
+        for root in ["foo-0.0.1+abc", "foo-0.0.1"] {
+            let tarball = TarballBuilder::new("foo", "0.0.1+abc")
+                .add_file(&format!("{root}/Cargo.toml"), b"[package]")
+                .add_file(&format!("{root}/.cargo_vcs_info.json"), b"{}")
+                .add_file(&format!("{root}/src/lib.rs"), b"pub fn foo() {}")
+                .build();
+
+            let tarball_info = assert_ok!(process_tarball(
+                "foo-0.0.1+abc",
+                &*tarball,
+                &TarballLimits::default()
+            ));
+            assert_some!(tarball_info.manifest);
+            assert_some!(tarball_info.vcs_info);
+            let paths = tarball_info.files.iter().map(|file| file.path.as_path());
+            assert_eq!(
+                paths.collect::<Vec<_>>(),
+                vec![
+                    Path::new("Cargo.toml"),
+                    Path::new(".cargo_vcs_info.json"),
+                    Path::new("src/lib.rs")
+                ]
+            );
+        }
+
+        // All entries have to use the same directory
+        let tarball = TarballBuilder::new("foo", "0.0.1+abc")
+            .add_file("foo-0.0.1/Cargo.toml", b"[package]")
+            .add_file("foo-0.0.1+abc/Cargo.toml", b"[package]")
+            .build();
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1+abc",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_matches!(error, TarballError::InvalidPath(path) if path == "foo-0.0.1+abc/Cargo.toml");
+
+        // Versions without build metadata require the exact directory
+        for root in ["foo-0.0.1+abc", "foo-0.0.1+", "foo-0.0.1%2Babc", "foo-0.0"] {
+            let tarball = TarballBuilder::new("foo", "0.0.1")
+                .add_file(&format!("{root}/Cargo.toml"), b"[package]")
+                .build();
+
+            let error = assert_err!(process_tarball(
+                "foo-0.0.1",
+                &*tarball,
+                &TarballLimits::default()
+            ));
+            assert_matches!(error, TarballError::InvalidPath(path) if path == format!("{root}/Cargo.toml"));
+        }
+
+        // Versions with build metadata only accept the stripped directory
+        for root in ["foo-0.0.1%2Babc", "foo-0.0.1+ab", "foo-0.0.1+ABC"] {
+            let tarball = TarballBuilder::new("foo", "0.0.1+abc")
+                .add_file(&format!("{root}/Cargo.toml"), b"[package]")
+                .build();
+
+            let error = assert_err!(process_tarball(
+                "foo-0.0.1+abc",
+                &*tarball,
+                &TarballLimits::default()
+            ));
+            assert_matches!(error, TarballError::InvalidPath(path) if path == format!("{root}/Cargo.toml"));
+        }
+    }
+
+    #[test]
+    fn process_tarball_test_links() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_symlink("foo-0.0.1/README.md", "/etc/passwd")
+            .build();
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_matches!(error, TarballError::UnexpectedSymlink(path) if path == "foo-0.0.1/README.md");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_hardlink("foo-0.0.1/src/lib.rs", "foo-0.0.1/Cargo.toml")
+            .build_with_compression(Compression::Zstd);
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_matches!(error, TarballError::UnexpectedSymlink(path) if path == "foo-0.0.1/src/lib.rs");
+    }
+
+    #[test]
+    fn process_tarball_test_entry_types() {
+        // Contiguous files are unpacked as regular files by cargo
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Continuous);
+        assert_ok!(header.set_path("foo-0.0.1/bin/tool"));
+        header.set_size(4);
+        header.set_cksum();
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_entry_with_header(&header, b"\x7fELF")
+            .build();
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_matches!(error, TarballError::UnsupportedEntryType(path) if path == "foo-0.0.1/bin/tool");
+
+        // Directories are accepted, but run through the validators
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        assert_ok!(header.set_path("foo-0.0.1/vendor/"));
+        header.set_size(0);
+        header.set_cksum();
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_entry_with_header(&header, b"")
+            .build();
+
+        let info = assert_ok!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_eq!(info.files.len(), 1);
+
+        let validator = ValidatorSet::new().with(DenyPaths::new(["vendor"]));
+        let error = assert_err!(process_tarball_with_validator(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default(),
+            &validator
+        ));
+        assert_matches!(error, TarballError::Rejected { path, .. } if path == "foo-0.0.1/vendor/");
+    }
+
+    #[test]
+    fn process_tarball_test_malformed_header() {
+        // The checksum of the header is never set
+        let mut header = tar::Header::new_gnu();
+        assert_ok!(header.set_path("foo-0.0.1/src/lib.rs"));
+        header.set_size(3);
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_entry_with_header(&header, b"foo")
+            .build();
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_matches!(error, TarballError::Malformed(_));
+    }
+
+    #[test]
+    fn process_tarball_test_max_entries() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .add_file("foo-0.0.1/src/main.rs", b"fn main() {}")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        assert_ok!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits {
+                max_entries: 3,
+                ..limits
+            }
+        ));
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits {
+                max_entries: 2,
+                ..limits
+            }
+        ));
+        assert_matches!(error, TarballError::TooManyEntries(2));
+    }
+
+    #[test]
+    fn process_tarball_test_max_file_size() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        assert_ok!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits {
+                max_file_size: 15,
+                ..limits
+            }
+        ));
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits {
+                max_file_size: 14,
+                ..limits
+            }
+        ));
+        assert_matches!(
+            error,
+            TarballError::FileTooLarge { path, max: 14 } if path == "foo-0.0.1/src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn process_tarball_test_duplicate_paths() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nname = \"foo\"")
+            .add_raw_manifest(b"[package]\nname = \"bar\"")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let error = assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_matches!(error, TarballError::DuplicatePath(path) if path == "foo-0.0.1/Cargo.toml");
+    }
+
+    #[test]
+    fn process_tarball_test_duplicate_normalized_paths() {
+        // `tar::Header::set_path()` normalizes the path, so the name is
+        // written to the header directly
+        let mut header = tar::Header::new_gnu();
+        let name = b"foo-0.0.1/./Cargo.toml";
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name);
+        header.set_size(0);
+        header.set_cksum();
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nname = \"foo\"")
+            .add_entry_with_header(&header, b"")
+            .build();
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits::default()
+        ));
+        assert_matches!(error, TarballError::DuplicatePath(path) if path == "foo-0.0.1/./Cargo.toml");
+    }
+
+    #[test]
+    fn process_tarball_test_case_insensitive_duplicate_paths() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .add_file("foo-0.0.1/src/LIB.rs", b"pub fn bar() {}")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_eq!(tarball_info.files.len(), 3);
+
+        let error = assert_err!(process_tarball(
+            "foo-0.0.1",
+            &*tarball,
+            &TarballLimits {
+                case_insensitive_paths: true,
+                ..limits
+            }
+        ));
+        assert_matches!(error, TarballError::DuplicatePath(path) if path == "foo-0.0.1/src/LIB.rs");
+    }
+
+    #[test]
+    fn process_tarball_test_manifest_encoding() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nname = \"f\xf6o\"")
+            .build();
+        let error = assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_matches!(
+            error,
+            TarballError::InvalidEncoding { path, error: EncodingError::InvalidUtf8(19) }
+                if path == "foo-0.0.1/Cargo.toml"
+        );
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"\xef\xbb\xbf[package]")
+            .build();
+        let error = assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_matches!(
+            error,
+            TarballError::InvalidEncoding {
+                error: EncodingError::ByteOrderMark("UTF-8"),
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn process_tarball_test_readme_encoding() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+
+        // The readme is checked even if it comes before the manifest
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_file("foo-0.0.1/docs/README.md", b"# f\xf6o")
+            .add_raw_manifest(b"[package]\nreadme = \"docs/README.md\"")
+            .build();
+        let error = assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_matches!(
+            error,
+            TarballError::InvalidEncoding { path, error: EncodingError::InvalidUtf8(3) }
+                if path == "foo-0.0.1/docs/README.md"
+        );
+
+        // Other files don't have to be text
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nreadme = false")
+            .add_file("foo-0.0.1/README.md", b"# f\xf6o")
+            .add_file("foo-0.0.1/logo.png", b"\x89PNG\r\n\x1a\n\xff")
+            .build();
+        assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+    }
+
+    #[test]
+    fn process_tarball_test_readme_contents() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let readme_contents = |tarball: Vec<u8>| {
+            let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+            tarball_info.readme_contents
+        };
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/README.md", b"# foo")
+            .add_file("foo-0.0.1/docs/README.md", b"# docs")
+            .build();
+        assert_some_eq!(readme_contents(tarball), "# foo");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nreadme = \"docs/README.md\"")
+            .add_file("foo-0.0.1/README.md", b"# foo")
+            .add_file("foo-0.0.1/docs/README.md", b"# docs")
+            .build();
+        assert_some_eq!(readme_contents(tarball), "# docs");
+
+        // Readme files before the manifest are found too
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_file("foo-0.0.1/docs/README.md", b"# docs")
+            .add_raw_manifest(b"[package]\nreadme = \"docs/README.md\"")
+            .build();
+        assert_some_eq!(readme_contents(tarball), "# docs");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nreadme = false")
+            .add_file("foo-0.0.1/README.md", b"# foo")
+            .build();
+        assert_none!(readme_contents(tarball));
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .build();
+        assert_none!(readme_contents(tarball));
+
+        // The default readme files are tried in the same order as by cargo
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/README", b"# plain")
+            .add_file("foo-0.0.1/README.txt", b"# txt")
+            .build();
+        assert_some_eq!(readme_contents(tarball), "# txt");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_file("foo-0.0.1/README", b"# plain")
+            .add_file("foo-0.0.1/README.md", b"# foo")
+            .add_raw_manifest(b"[package]")
+            .build();
+        assert_some_eq!(readme_contents(tarball), "# foo");
+
+        // The readme path is normalized like the paths of the entries
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nreadme = \"./docs/README.md\"")
+            .add_file("foo-0.0.1/./docs/README.md", b"# docs")
+            .build();
+        assert_some_eq!(readme_contents(tarball), "# docs");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nreadme = \"../README.md\"")
+            .add_file("foo-0.0.1/README.md", b"# foo")
+            .build();
+        assert_none!(readme_contents(tarball));
+    }
+
+    #[test]
+    fn process_tarball_test_readme_path() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/README.txt", b"# txt")
+            .build();
+
+        let tarball_info = assert_ok!(process_tarball_lenient("foo-0.0.1", &*tarball));
+        assert_some_eq!(tarball_info.readme_path, Path::new("README.txt"));
+    }
+
+    #[test]
+    fn process_tarball_test_files() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_eq!(
+            tarball_info.files,
+            vec![
+                TarballFile {
+                    path: "Cargo.toml".into(),
+                    size: 9,
+                },
+                TarballFile {
+                    path: "src/lib.rs".into(),
+                    size: 15,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn process_tarball_test_scan() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .add_file("foo-0.0.1/bin/tool", b"\x7fELF\x02\x01\x01")
+            .add_file("foo-0.0.1/lib/libfoo.so.1", b"INPUT(libfoo.so)")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_eq!(
+            tarball_info.scan.findings,
+            vec![
+                ScanFinding {
+                    path: "bin/tool".into(),
+                    kind: ScanFindingKind::Binary {
+                        format: "ELF".into()
+                    },
+                },
+                ScanFinding {
+                    path: "lib/libfoo.so.1".into(),
+                    kind: ScanFindingKind::NativeLibrary {
+                        extension: "so".into()
+                    },
+                },
+            ]
+        );
     }
 
     #[test]
@@ -130,8 +1170,11 @@ mod tests {
             .add_file("foo-0.0.1/.cargo_vcs_info.json", br#"{"unknown": "field"}"#)
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let vcs_info = process_tarball("foo-0.0.1", &*tarball, limit)
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let vcs_info = process_tarball("foo-0.0.1", &*tarball, &limits)
             .unwrap()
             .vcs_info
             .unwrap();
@@ -148,14 +1191,261 @@ mod tests {
             )
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let vcs_info = process_tarball("foo-0.0.1", &*tarball, limit)
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let vcs_info = process_tarball("foo-0.0.1", &*tarball, &limits)
             .unwrap()
             .vcs_info
             .unwrap();
         assert_eq!(vcs_info.path_in_vcs, "path/in/vcs");
     }
 
+    #[test]
+    fn process_tarball_test_validator() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let validator = ValidatorSet::new()
+            .with(NoExecutables)
+            .with(MaxFileSize(100));
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .build();
+        assert_ok!(process_tarball_with_validator(
+            "foo-0.0.1",
+            &*tarball,
+            &limits,
+            &validator
+        ));
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/bin/tool", b"\x7fELF\x02\x01\x01")
+            .build();
+        let error = assert_err!(process_tarball_with_validator(
+            "foo-0.0.1",
+            &*tarball,
+            &limits,
+            &validator
+        ));
+        assert_eq!(
+            error.to_string(),
+            "the file `foo-0.0.1/bin/tool` was rejected: ELF binaries are not allowed"
+        );
+
+        // The manifest is validated like any other file
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(&[b' '; 200])
+            .build();
+        let error = assert_err!(process_tarball_with_validator(
+            "foo-0.0.1",
+            &*tarball,
+            &limits,
+            &validator
+        ));
+        assert_matches!(error, TarballError::Rejected { path, .. } if path == "foo-0.0.1/Cargo.toml");
+    }
+
+    #[test]
+    fn process_tarball_test_manifest_only() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nrepository = \"https://github.com/foo/foo\"\n")
+            .add_file(
+                "foo-0.0.1/.cargo_vcs_info.json",
+                br#"{"path_in_vcs": "foo"}"#,
+            )
+            .add_file("foo-0.0.1/README.md", b"# foo")
+            .add_file("foo-0.0.1/Cargo.lock", b"invalid")
+            .add_file("foo-0.0.1/bin/tool", b"\x7fELF\x02\x01\x01")
+            .build();
+
+        // Only the process of the full crate file looks at the other files
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_some!(tarball_info.readme_contents);
+        assert_some!(tarball_info.lockfile_error);
+        assert!(!tarball_info.scan.is_clean());
+
+        let tarball_info = assert_ok!(process_tarball_manifest_only(
+            "foo-0.0.1",
+            &*tarball,
+            &limits
+        ));
+        let manifest = assert_some!(tarball_info.manifest);
+        assert_some_eq!(manifest.package.repository, "https://github.com/foo/foo");
+        assert_eq!(assert_some!(tarball_info.vcs_info).path_in_vcs, "foo");
+        assert_none!(tarball_info.readme_contents);
+        assert_none!(tarball_info.lockfile);
+        assert_none!(tarball_info.lockfile_error);
+        assert!(tarball_info.scan.is_clean());
+        assert_eq!(tarball_info.files.len(), 5);
+        assert_eq!(tarball_info.tarball_checksum, *Sha256::digest(&tarball));
+
+        // The headers of all entries are still checked
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_symlink("foo-0.0.1/bar", "/etc/passwd")
+            .build();
+        let error = assert_err!(process_tarball_manifest_only(
+            "foo-0.0.1",
+            &*tarball,
+            &limits
+        ));
+        assert_matches!(error, TarballError::UnexpectedSymlink(_));
+    }
+
+    #[test]
+    fn process_tarball_test_lenient() {
+        // A crate file like the ones that older versions of crates.io accepted
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nlicense = \"MIT/Apache-2.0\"\nreadme = \"README.md\"\n")
+            .add_file("foo-0.0.1/README.md", b"\xff\xfe")
+            .add_file("foo-0.0.1/Cargo.lock", b"invalid")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn bar() {}")
+            .add_symlink("foo-0.0.1/bar", "src/lib.rs")
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+
+        let tarball_info = assert_ok!(process_tarball_lenient("foo-0.0.1", &*tarball));
+        assert_some!(tarball_info.manifest);
+        assert_some_eq!(tarball_info.license.expression, "MIT/Apache-2.0");
+        assert_none!(tarball_info.readme_contents);
+        assert_none!(tarball_info.lockfile);
+        let paths = tarball_info
+            .files
+            .iter()
+            .map(|file| file.path.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            ["Cargo.toml", "README.md", "Cargo.lock", "src/lib.rs"]
+        );
+        assert_eq!(tarball_info.tarball_checksum, *Sha256::digest(&tarball));
+
+        // The fingerprint includes the first of the duplicated files
+        let expected = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("foo-0.0.1/README.md", b"\xff\xfe")
+            .add_file("foo-0.0.1/Cargo.lock", b"invalid")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .build();
+        let expected = assert_ok!(process_tarball_lenient("foo-0.0.1", &*expected));
+        assert_eq!(tarball_info.fingerprint, expected.fingerprint);
+
+        // Entries outside of the package directory are still rejected
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]")
+            .add_file("bar-0.0.1/Cargo.toml", b"[package]")
+            .build();
+        let error = assert_err!(process_tarball_lenient("foo-0.0.1", &*tarball));
+        assert_matches!(error, TarballError::InvalidPath(_));
+    }
+
+    #[test]
+    fn process_tarball_test_lockfile() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"")
+            .build();
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_none!(tarball_info.lockfile);
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"")
+            .add_file(
+                "foo-0.0.1/Cargo.lock",
+                b"version = 3\n\n[[package]]\nname = \"bar\"\nversion = \"1.0.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+            )
+            .build();
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        let lockfile = assert_some!(tarball_info.lockfile);
+        assert_some_eq!(lockfile.version, 3);
+        assert_eq!(lockfile.crates_io_packages().count(), 1);
+        assert_eq!(tarball_info.files.len(), 2);
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"")
+            .add_file("foo-0.0.1/Cargo.lock", b"[[package]]\nname = \"bar\"\n")
+            .build();
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_none!(tarball_info.lockfile);
+        let error = assert_some!(tarball_info.lockfile_error);
+        assert!(error.contains("missing field `version`"), "{error}");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"")
+            .add_file("foo-0.0.1/Cargo.lock", b"\xff\xfe")
+            .build();
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_none!(tarball_info.lockfile);
+        assert_some!(tarball_info.lockfile_error);
+    }
+
+    #[test]
+    fn process_tarball_test_license() {
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let process = |tarball: Vec<u8>| process_tarball("foo-0.0.1", &*tarball, &limits);
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_file("foo-0.0.1/LICENSE-MIT", b"MIT License")
+            .add_raw_manifest(b"[package]\nlicense = \"MIT OR Apache-2.0\"")
+            .add_file("foo-0.0.1/LICENSE-APACHE", b"Apache License")
+            .add_file("foo-0.0.1/src/license.rs", b"pub fn foo() {}")
+            .build();
+        let license = assert_ok!(process(tarball)).license;
+        assert_some_eq!(license.expression, "MIT OR Apache-2.0");
+        assert_none!(license.license_file);
+        let paths = license
+            .files
+            .iter()
+            .map(|file| file.path.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["LICENSE-MIT", "LICENSE-APACHE"]);
+        assert_eq!(license.files[0].contents, "MIT License");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nlicense-file = \"docs/terms.txt\"")
+            .add_file("foo-0.0.1/docs/terms.txt", b"All rights reserved")
+            .build();
+        let license = assert_ok!(process(tarball)).license;
+        assert_none!(license.expression);
+        let license_file = assert_some!(license.license_file);
+        assert_eq!(license_file.path, Path::new("docs/terms.txt"));
+        assert_eq!(license_file.contents, "All rights reserved");
+        assert!(license.files.is_empty());
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nlicense = \"MIT OR Apache-2.0 OR Foo\"")
+            .build();
+        let error = assert_err!(process(tarball));
+        assert_eq!(
+            error.to_string(),
+            "the `license` field `MIT OR Apache-2.0 OR Foo` is not a valid SPDX license expression: unknown term `Foo`"
+        );
+    }
+
     #[test]
     fn process_tarball_test_manifest() {
         let tarball = TarballBuilder::new("foo", "0.0.1")
@@ -169,8 +1459,11 @@ repository = "https://github.com/foo/bar"
             )
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, limit));
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
         let manifest = assert_some!(tarball_info.manifest);
         assert_some_eq!(manifest.package.readme.as_path(), Path::new("README.md"));
         assert_some_eq!(manifest.package.repository, "https://github.com/foo/bar");
@@ -188,12 +1481,43 @@ repository = "https://github.com/foo/bar"
             )
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, limit));
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
         let manifest = assert_some!(tarball_info.manifest);
         assert_some_eq!(manifest.package.rust_version, "1.23");
     }
 
+    #[test]
+    fn process_tarball_test_manifest_with_inherited_rust_version() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(
+                br#"
+                [package]
+                rust-version = { workspace = true }
+                "#,
+            )
+            .build();
+
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let error = assert_err!(process_tarball("foo-0.0.1", &*tarball, &limits));
+        assert_matches!(
+            &error,
+            TarballError::InheritedManifestValue { field } if field == "package.rust-version"
+        );
+        assert_eq!(
+            error.to_string(),
+            "`package.rust-version` uses workspace inheritance, which is not supported in \
+             crate files; publish with cargo 1.64 or newer, which replaces inherited values \
+             with the values of the workspace"
+        );
+    }
+
     #[test]
     fn process_tarball_test_manifest_with_default_readme() {
         let tarball = TarballBuilder::new("foo", "0.0.1")
@@ -204,8 +1528,11 @@ repository = "https://github.com/foo/bar"
             )
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, limit));
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
         let manifest = assert_some!(tarball_info.manifest);
         assert_matches!(manifest.package.readme, OptionalFile::Flag(true));
     }
@@ -221,8 +1548,11 @@ repository = "https://github.com/foo/bar"
             )
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, limit));
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
         let manifest = assert_some!(tarball_info.manifest);
         assert_matches!(manifest.package.readme, OptionalFile::Flag(false));
     }
@@ -239,8 +1569,11 @@ repository = "https://github.com/foo/bar"
             )
             .build();
 
-        let limit = 512 * 1024 * 1024;
-        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, limit));
+        let limits = TarballLimits {
+            max_unpack: 512 * 1024 * 1024,
+            ..Default::default()
+        };
+        let tarball_info = assert_ok!(process_tarball("foo-0.0.1", &*tarball, &limits));
         let manifest = assert_some!(tarball_info.manifest);
         assert_some_eq!(manifest.package.repository, "https://github.com/foo/bar");
     }
@@ -0,0 +1,121 @@
+use spdx::error::Reason;
+use std::path::{Path, PathBuf};
+
+/// The same rules that crates.io has always used for the `license` field,
+/// which are a bit more lenient than the SPDX specification.
+const PARSE_MODE: spdx::ParseMode = spdx::ParseMode {
+    allow_lower_case_operators: false,
+    allow_slash_as_or_operator: true,
+    allow_imprecise_license_names: false,
+    allow_postfix_plus_on_gpl: true,
+};
+
+/// The licensing information of a crate file, as declared in the manifest
+/// and as found in the files of the package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LicenseInfo {
+    /// The `license` field of the manifest, which is a valid SPDX expression.
+    pub expression: Option<String>,
+    /// The file referenced by the `license-file` field of the manifest, if
+    /// the crate file includes it.
+    pub license_file: Option<LicenseFile>,
+    /// All files that look like a license or copyright file, like
+    /// `LICENSE-MIT`, `COPYRIGHT` or `licenses/license.txt`, in the order of
+    /// the archive.
+    pub files: Vec<LicenseFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LicenseFile {
+    /// Path of the file relative to the package root.
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Checks that `expression` is a valid SPDX license expression.
+///
+/// On failure, a description of the problem is returned that includes the
+/// offending part of the expression, e.g. ``unknown term `MIT2` ``.
+pub fn validate_license_expr(expression: &str) -> Result<(), String> {
+    let Err(error) = spdx::Expression::parse_mode(expression, PARSE_MODE) else {
+        return Ok(());
+    };
+
+    let reason = &error.reason;
+    match error.original.get(error.span.clone()).map(str::trim) {
+        Some("") | None => Err(reason.to_string()),
+        Some(term) if matches!(reason, Reason::Unexpected(_)) => {
+            Err(format!("{reason}, found `{term}`"))
+        }
+        Some(term) => Err(format!("{reason} `{term}`")),
+    }
+}
+
+/// Returns whether `path` looks like a license or copyright file: either its
+/// name starts with `LICENSE`, `LICENCE` or `COPYRIGHT`, or it is named like
+/// that in any case with an optional `.md` or `.txt` extension, so that e.g.
+/// `src/license.rs` doesn't count.
+pub(crate) fn looks_like_license(path: &Path) -> bool {
+    const NAMES: [&str; 3] = ["LICENSE", "LICENCE", "COPYRIGHT"];
+
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+
+    if NAMES.iter().any(|prefix| name.starts_with(prefix)) {
+        return true;
+    }
+
+    let stem = match name.rsplit_once('.') {
+        Some((stem, "md" | "txt")) => stem,
+        Some(_) => return false,
+        None => name,
+    };
+    NAMES
+        .iter()
+        .any(|license| stem.eq_ignore_ascii_case(license))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{looks_like_license, validate_license_expr};
+    use std::path::Path;
+
+    #[test]
+    fn license_expressions() {
+        assert_ok!(validate_license_expr("MIT"));
+        assert_ok!(validate_license_expr("MIT OR Apache-2.0"));
+        assert_ok!(validate_license_expr("MIT/Apache-2.0"));
+        assert_ok!(validate_license_expr("Apache-2.0 WITH LLVM-exception"));
+        assert_ok!(validate_license_expr("GPL-3.0+"));
+
+        assert_err_eq!(validate_license_expr("MIT2"), "unknown term `MIT2`");
+        assert_err_eq!(
+            validate_license_expr("MIT OR Apache-2.0 WITH MIT"),
+            "expected a `<exception>` here, found `MIT`"
+        );
+        assert_err_eq!(
+            validate_license_expr("mit or apache-2.0"),
+            "unknown term `mit`"
+        );
+        assert_err!(validate_license_expr("(MIT OR Apache-2.0"));
+        assert_err!(validate_license_expr(""));
+    }
+
+    #[test]
+    fn license_file_names() {
+        assert!(looks_like_license(Path::new("LICENSE")));
+        assert!(looks_like_license(Path::new("LICENSE-MIT")));
+        assert!(looks_like_license(Path::new("LICENSE-APACHE-2.0")));
+        assert!(looks_like_license(Path::new("licence.txt")));
+        assert!(looks_like_license(Path::new("License.md")));
+        assert!(looks_like_license(Path::new("licenses/LICENSE-APACHE")));
+        assert!(looks_like_license(Path::new("COPYRIGHT")));
+        assert!(looks_like_license(Path::new("copyright.txt")));
+        assert!(!looks_like_license(Path::new("COPYING")));
+        assert!(!looks_like_license(Path::new("LICENSE/README.md")));
+        assert!(!looks_like_license(Path::new("licenses.txt")));
+        assert!(!looks_like_license(Path::new("src/license.rs")));
+        assert!(!looks_like_license(Path::new("src/copyright.rs")));
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Deserialize;
+
+/// The `source` values of packages that were resolved from the crates.io
+/// index, via the git protocol or the sparse protocol.
+const CRATES_IO_SOURCES: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+/// Represents relevant contents of a `Cargo.lock` file included in a crate
+/// file.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+pub struct LockfileInfo {
+    /// Format version of the lockfile, which is missing in the oldest format.
+    pub version: Option<u32>,
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: semver::Version,
+    /// Where the package was resolved from, or `None` for path dependencies
+    /// and the package itself.
+    pub source: Option<String>,
+}
+
+impl LockfileInfo {
+    pub fn from_contents(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Returns the locked packages that were resolved from crates.io.
+    pub fn crates_io_packages(&self) -> impl Iterator<Item = &LockedPackage> {
+        self.packages
+            .iter()
+            .filter(|package| package.is_from_crates_io())
+    }
+}
+
+impl LockedPackage {
+    pub fn is_from_crates_io(&self) -> bool {
+        self.source
+            .as_deref()
+            .is_some_and(|source| CRATES_IO_SOURCES.contains(&source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LockfileInfo;
+
+    #[test]
+    fn from_contents() {
+        let lockfile = assert_ok!(LockfileInfo::from_contents(
+            r#"
+version = 3
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = ["bar", "baz"]
+
+[[package]]
+name = "bar"
+version = "1.2.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "0000000000000000000000000000000000000000000000000000000000000000"
+
+[[package]]
+name = "baz"
+version = "2.0.0-beta.1"
+source = "git+https://github.com/foo/baz#0123456789abcdef"
+"#
+        ));
+
+        assert_some_eq!(lockfile.version, 3);
+        assert_eq!(lockfile.packages.len(), 3);
+        assert_eq!(lockfile.packages[1].name, "bar");
+        assert_eq!(lockfile.packages[1].version.to_string(), "1.2.3");
+
+        let names = lockfile
+            .crates_io_packages()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["bar"]);
+    }
+
+    #[test]
+    fn from_contents_v1() {
+        let lockfile = assert_ok!(LockfileInfo::from_contents(
+            r#"
+[[package]]
+name = "bar"
+version = "1.2.3"
+source = "sparse+https://index.crates.io/"
+
+[metadata]
+"checksum bar 1.2.3 (sparse+https://index.crates.io/)" = "0000"
+"#
+        ));
+
+        assert_none!(lockfile.version);
+        assert_eq!(lockfile.crates_io_packages().count(), 1);
+    }
+
+    #[test]
+    fn from_contents_empty() {
+        let lockfile = assert_ok!(LockfileInfo::from_contents(""));
+        assert!(lockfile.packages.is_empty());
+    }
+
+    #[test]
+    fn from_contents_invalid() {
+        assert_err!(LockfileInfo::from_contents("[[package]]\nname = \"bar\""));
+        assert_err!(LockfileInfo::from_contents(
+            "[[package]]\nname = \"bar\"\nversion = \"one\""
+        ));
+        assert_err!(LockfileInfo::from_contents("[[package"));
+    }
+}
@@ -1,22 +1,365 @@
+use crate::TarballError;
 use cargo_toml::OptionalFile;
 use derive_deref::Deref;
 use serde::{de, Deserialize, Deserializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct Manifest {
     #[serde(alias = "project")]
     pub package: Package,
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, alias = "build_dependencies")]
+    pub build_dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, alias = "dev_dependencies")]
+    pub dev_dependencies: BTreeMap<String, Dependency>,
+    /// Platform-specific dependencies, keyed by the `cfg()` expression or
+    /// target triple.
+    #[serde(default)]
+    pub target: BTreeMap<String, Target>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Target {
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, alias = "build_dependencies")]
+    pub build_dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, alias = "dev_dependencies")]
+    pub dev_dependencies: BTreeMap<String, Dependency>,
+}
+
+/// A dependency of the manifest.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    /// A dependency with only a version requirement, like `foo = "1.0"`.
+    Simple(String),
+    Detailed {
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+impl Dependency {
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Self::Detailed { optional: true })
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Package {
+    pub license: Option<String>,
+    pub license_file: Option<PathBuf>,
     #[serde(default)]
     pub readme: OptionalFile,
     pub repository: Option<String>,
     pub rust_version: Option<RustVersion>,
 }
 
+/// The files that cargo uses as the readme, in this order, if the manifest
+/// doesn't specify one.
+const DEFAULT_READMES: [&str; 3] = ["README.md", "README.txt", "README"];
+
+impl Package {
+    /// Returns the possible paths of the readme file relative to the package
+    /// root, in the order in which they are tried.
+    ///
+    /// The list is empty if the readme has been disabled with
+    /// `readme = false`, or if the `readme` field points outside of the
+    /// package root.
+    pub fn readme_paths(&self) -> Vec<PathBuf> {
+        if !self.readme.is_some() {
+            return Vec::new();
+        }
+
+        match self.readme.as_path() {
+            Some(path) => normalize_path(path).into_iter().collect(),
+            None => DEFAULT_READMES.into_iter().map(PathBuf::from).collect(),
+        }
+    }
+}
+
+/// Normalizes a path relative to the package root like the paths of the
+/// tarball entries, by removing `.` components.
+///
+/// Returns `None` for paths that are absolute or contain `..` components,
+/// since they can't refer to a file of the tarball.
+pub(crate) fn normalize_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+impl Manifest {
+    /// Returns all dependencies that features may refer to, i.e. regular and
+    /// build dependencies including platform-specific ones.
+    pub fn dependencies(&self) -> impl Iterator<Item = (&str, &Dependency)> {
+        let targets = self.target.values();
+        let tables = [&self.dependencies, &self.build_dependencies]
+            .into_iter()
+            .chain(targets.flat_map(|t| [&t.dependencies, &t.build_dependencies]));
+
+        tables.flatten().map(|(name, dep)| (name.as_str(), dep))
+    }
+
+    /// Returns the number of dependency declarations, including
+    /// dev-dependencies, which all end up in the index entry of the version.
+    pub fn dependency_count(&self) -> usize {
+        let dev_dependencies = self.dev_dependencies.len()
+            + self
+                .target
+                .values()
+                .map(|target| target.dev_dependencies.len())
+                .sum::<usize>();
+
+        self.dependencies().count() + dev_dependencies
+    }
+}
+
+/// Upper limits for the size of a manifest, which protect the index from
+/// versions with huge entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestLimits {
+    /// Maximum number of entries in the `[features]` table.
+    pub max_features: usize,
+    /// Maximum number of dependency declarations, see
+    /// [`Manifest::dependency_count`].
+    pub max_dependencies: usize,
+}
+
+impl Default for ManifestLimits {
+    fn default() -> Self {
+        Self {
+            max_features: 300,
+            max_dependencies: 500,
+        }
+    }
+}
+
+/// Returns the first field of the `[package]` table whose value is inherited
+/// from the workspace with `{ workspace = true }`, e.g.
+/// `package.rust-version`.
+///
+/// `cargo package` replaces inherited values with the ones of the workspace,
+/// so they only show up in crate files that were built by other tools, and
+/// cargo can't resolve them when the crate is used as a dependency.
+pub(crate) fn inherited_package_field(contents: &str) -> Option<String> {
+    let manifest: toml::Table = toml::from_str(contents).ok()?;
+    let (key, package) = ["package", "project"]
+        .into_iter()
+        .find_map(|key| Some((key, manifest.get(key)?.as_table()?)))?;
+
+    package
+        .iter()
+        .find(|(_, value)| value.get("workspace").and_then(toml::Value::as_bool) == Some(true))
+        .map(|(field, _)| format!("{key}.{field}"))
+}
+
+/// A reason why the `[features]` table of a manifest is invalid, mostly
+/// mirroring the checks that cargo performs when the crate is used.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FeatureError {
+    #[error("feature `{feature}` includes `{value}`, which is neither a feature nor a dependency")]
+    UnknownFeature { feature: String, value: String },
+    #[error("feature `{feature}` includes `{value}`, but `{dependency}` is not a dependency")]
+    UnknownDependency {
+        feature: String,
+        value: String,
+        dependency: String,
+    },
+    #[error(
+        "feature `{feature}` includes `{value}`, but `{dependency}` is not an optional dependency"
+    )]
+    NotOptional {
+        feature: String,
+        value: String,
+        dependency: String,
+    },
+    #[error(
+        "feature `{feature}` includes `{value}`, but `{value}` is an optional dependency \
+         without an implicit feature, use `dep:{value}` to enable it"
+    )]
+    MissingDepPrefix { feature: String, value: String },
+    #[error("feature `{}` depends on itself via `{}`", .0[0], .0.join("` -> `"))]
+    Cycle(Vec<String>),
+}
+
+/// Checks that the manifest stays within the given limits, that all values
+/// of the `[features]` table refer to existing features or dependencies, and
+/// that features don't depend on each other in a cycle.
+pub fn validate_manifest(manifest: &Manifest, limits: &ManifestLimits) -> Result<(), TarballError> {
+    let count = manifest.features.len();
+    if count > limits.max_features {
+        let max = limits.max_features;
+        return Err(TarballError::TooManyFeatures { count, max });
+    }
+
+    let count = manifest.dependency_count();
+    if count > limits.max_dependencies {
+        let max = limits.max_dependencies;
+        return Err(TarballError::TooManyDependencies { count, max });
+    }
+
+    check_features(manifest).map_err(TarballError::InvalidFeature)
+}
+
+fn check_features(manifest: &Manifest) -> Result<(), FeatureError> {
+    let features = &manifest.features;
+
+    // A dependency is optional if any of its declarations is optional, e.g.
+    // if it is only optional on some platforms.
+    let mut dependencies = HashMap::new();
+    for (name, dependency) in manifest.dependencies() {
+        *dependencies.entry(name).or_default() |= dependency.is_optional();
+    }
+
+    // Optional dependencies only get an implicit feature of the same name if
+    // they are never referenced with the `dep:` syntax.
+    let explicit_dependencies = features
+        .values()
+        .flatten()
+        .filter_map(|value| value.strip_prefix("dep:"))
+        .collect::<HashSet<_>>();
+
+    for (feature, values) in features {
+        for value in values {
+            let dependency_error = |dependency: &str, exists: bool| {
+                let (feature, value) = (feature.clone(), value.clone());
+                let dependency = dependency.to_string();
+                if exists {
+                    FeatureError::NotOptional {
+                        feature,
+                        value,
+                        dependency,
+                    }
+                } else {
+                    FeatureError::UnknownDependency {
+                        feature,
+                        value,
+                        dependency,
+                    }
+                }
+            };
+
+            if let Some(dependency) = value.strip_prefix("dep:") {
+                match dependencies.get(dependency) {
+                    Some(true) => {}
+                    Some(false) => return Err(dependency_error(dependency, true)),
+                    None => return Err(dependency_error(dependency, false)),
+                }
+            } else if let Some((dependency, _)) = value.split_once('/') {
+                // `foo?/bar` only enables `bar` if `foo` is enabled otherwise,
+                // which only makes sense for optional dependencies.
+                let (dependency, weak) = match dependency.strip_suffix('?') {
+                    Some(dependency) => (dependency, true),
+                    None => (dependency, false),
+                };
+                match dependencies.get(dependency) {
+                    None => return Err(dependency_error(dependency, false)),
+                    Some(false) if weak => return Err(dependency_error(dependency, true)),
+                    Some(_) => {}
+                }
+            } else if !features.contains_key(value) {
+                match dependencies.get(value.as_str()) {
+                    Some(true) if !explicit_dependencies.contains(value.as_str()) => {}
+                    Some(true) => {
+                        return Err(FeatureError::MissingDepPrefix {
+                            feature: feature.clone(),
+                            value: value.clone(),
+                        })
+                    }
+                    Some(false) => return Err(dependency_error(value, true)),
+                    None => {
+                        return Err(FeatureError::UnknownFeature {
+                            feature: feature.clone(),
+                            value: value.clone(),
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    match find_cycle(features) {
+        Some(cycle) => Err(FeatureError::Cycle(cycle)),
+        None => Ok(()),
+    }
+}
+
+/// Returns the features of a cycle in the feature graph, starting and ending
+/// with the same feature, or `None` if the graph is acyclic.
+///
+/// The graph is traversed iteratively, so that long chains of features can't
+/// overflow the stack.
+fn find_cycle(features: &BTreeMap<String, Vec<String>>) -> Option<Vec<String>> {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum State {
+        Visiting,
+        Done,
+    }
+
+    let mut states = HashMap::new();
+    for start in features.keys() {
+        if states.contains_key(start.as_str()) {
+            continue;
+        }
+
+        // The path from `start` to the current feature, together with the
+        // index of the next value of each feature that is followed.
+        let mut stack = vec![(start.as_str(), 0)];
+        states.insert(start.as_str(), State::Visiting);
+
+        while let Some(top) = stack.last_mut() {
+            let (feature, index) = *top;
+            top.1 += 1;
+
+            let Some(value) = features[feature].get(index) else {
+                states.insert(feature, State::Done);
+                stack.pop();
+                continue;
+            };
+
+            let Some((next, _)) = features.get_key_value(value) else {
+                continue;
+            };
+
+            match states.get(next.as_str()) {
+                Some(State::Visiting) => {
+                    let position = stack.iter().position(|(f, _)| f == next)?;
+                    let mut cycle = stack[position..]
+                        .iter()
+                        .map(|(f, _)| f.to_string())
+                        .collect::<Vec<_>>();
+                    cycle.push(next.clone());
+                    return Some(cycle);
+                }
+                Some(State::Done) => {}
+                None => {
+                    states.insert(next.as_str(), State::Visiting);
+                    stack.push((next.as_str(), 0));
+                }
+            }
+        }
+    }
+
+    None
+}
+
 #[derive(Debug, Deref)]
 pub struct RustVersion(String);
 
@@ -40,3 +383,239 @@ impl<'de> Deserialize<'de> for RustVersion {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_features, inherited_package_field, normalize_path, validate_manifest, FeatureError,
+        Manifest, ManifestLimits,
+    };
+    use crate::TarballError;
+    use std::path::{Path, PathBuf};
+
+    fn check(manifest: &str) -> Result<(), FeatureError> {
+        let manifest: Manifest = toml::from_str(manifest).unwrap();
+        check_features(&manifest)
+    }
+
+    #[test]
+    fn valid_features() {
+        assert_ok!(check("[package]"));
+        assert_ok!(check(
+            r#"
+[package]
+
+[features]
+default = ["std", "serde"]
+std = ["log/std", "serde_json?/std"]
+json = ["dep:serde_json", "serde"]
+
+[dependencies]
+log = "0.4"
+serde = { version = "1", optional = true }
+serde_json = { version = "1", optional = true }
+"#
+        ));
+        assert_ok!(check(
+            r#"
+[package]
+
+[features]
+unix = ["libc", "cc"]
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2", optional = true }
+
+[build-dependencies]
+cc = { version = "1", optional = true }
+"#
+        ));
+    }
+
+    #[test]
+    fn dangling_references() {
+        assert_err_eq!(
+            check("[package]\n[features]\ndefault = [\"std\"]"),
+            FeatureError::UnknownFeature {
+                feature: "default".into(),
+                value: "std".into(),
+            }
+        );
+        assert_err_eq!(
+            check("[package]\n[features]\nstd = [\"log/std\"]"),
+            FeatureError::UnknownDependency {
+                feature: "std".into(),
+                value: "log/std".into(),
+                dependency: "log".into(),
+            }
+        );
+        assert_err_eq!(
+            check("[package]\n[features]\njson = [\"dep:serde\"]\n[dependencies]\nserde = \"1\""),
+            FeatureError::NotOptional {
+                feature: "json".into(),
+                value: "dep:serde".into(),
+                dependency: "serde".into(),
+            }
+        );
+        assert_err!(check(
+            "[package]\n[features]\njson = [\"serde?/std\"]\n[dependencies]\nserde = \"1\""
+        ));
+        assert_err!(check(
+            "[package]\n[features]\njson = [\"serde\"]\n[dev-dependencies]\nserde = \"1\""
+        ));
+
+        let error = assert_err!(check(
+            r#"
+[package]
+
+[features]
+json = ["dep:serde"]
+yaml = ["serde"]
+
+[dependencies]
+serde = { version = "1", optional = true }
+"#
+        ));
+        assert_eq!(
+            error.to_string(),
+            "feature `yaml` includes `serde`, but `serde` is an optional dependency without an implicit feature, use `dep:serde` to enable it"
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let error = assert_err!(check(
+            "[package]\n[features]\na = [\"b\"]\nb = [\"c\"]\nc = [\"a\"]\nd = [\"a\"]"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "feature `a` depends on itself via `a` -> `b` -> `c` -> `a`"
+        );
+
+        assert_err_eq!(
+            check("[package]\n[features]\ndefault = [\"default\"]"),
+            FeatureError::Cycle(vec!["default".into(), "default".into()])
+        );
+
+        assert_ok!(check(
+            "[package]\n[features]\na = [\"b\", \"c\"]\nb = [\"c\"]\nc = []"
+        ));
+    }
+
+    #[test]
+    fn limits() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+[package]
+
+[features]
+default = ["std"]
+std = []
+
+[dependencies]
+log = "0.4"
+
+[dev-dependencies]
+env_logger = "0.10"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[target.'cfg(unix)'.dev-dependencies]
+nix = "0.26"
+"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.dependency_count(), 4);
+
+        let limits = ManifestLimits {
+            max_features: 2,
+            max_dependencies: 4,
+        };
+        assert_ok!(validate_manifest(&manifest, &limits));
+
+        let limits = ManifestLimits {
+            max_features: 1,
+            max_dependencies: 4,
+        };
+        let error = assert_err!(validate_manifest(&manifest, &limits));
+        assert!(matches!(
+            error,
+            TarballError::TooManyFeatures { count: 2, max: 1 }
+        ));
+
+        let limits = ManifestLimits {
+            max_features: 2,
+            max_dependencies: 3,
+        };
+        let error = assert_err!(validate_manifest(&manifest, &limits));
+        assert_eq!(
+            error.to_string(),
+            "the manifest declares 4 dependencies, but at most 3 are allowed"
+        );
+    }
+
+    #[test]
+    fn inherited_package_fields() {
+        assert_none!(inherited_package_field(
+            "[package]\nrust-version = \"1.64\""
+        ));
+        assert_none!(inherited_package_field(
+            "[package]\nrust-version = { workspace = false }"
+        ));
+        assert_none!(inherited_package_field(
+            "[package]\n[dependencies]\nlog = { workspace = true }"
+        ));
+        assert_none!(inherited_package_field("not toml"));
+
+        assert_some_eq!(
+            inherited_package_field("[package]\nrust-version = { workspace = true }"),
+            "package.rust-version"
+        );
+        assert_some_eq!(
+            inherited_package_field("[package]\nrust-version.workspace = true"),
+            "package.rust-version"
+        );
+        assert_some_eq!(
+            inherited_package_field("[project]\nlicense = { workspace = true }"),
+            "project.license"
+        );
+    }
+
+    #[test]
+    fn readme_paths() {
+        let readme_paths = |manifest: &str| {
+            let manifest: Manifest = toml::from_str(manifest).unwrap();
+            manifest.package.readme_paths()
+        };
+
+        assert_eq!(
+            readme_paths("[package]"),
+            vec![
+                PathBuf::from("README.md"),
+                PathBuf::from("README.txt"),
+                PathBuf::from("README")
+            ]
+        );
+        assert_eq!(
+            readme_paths("[package]\nreadme = \"./docs/README.md\""),
+            vec![PathBuf::from("docs/README.md")]
+        );
+        assert!(readme_paths("[package]\nreadme = false").is_empty());
+        assert!(readme_paths("[package]\nreadme = \"../README.md\"").is_empty());
+        assert!(readme_paths("[package]\nreadme = \"/etc/passwd\"").is_empty());
+    }
+
+    #[test]
+    fn normalized_paths() {
+        let normalize = |path: &str| normalize_path(Path::new(path));
+
+        assert_eq!(normalize("README.md"), Some(PathBuf::from("README.md")));
+        assert_eq!(
+            normalize("./docs/./README.md"),
+            Some(PathBuf::from("docs/README.md"))
+        );
+        assert_eq!(normalize("."), None);
+        assert_eq!(normalize("docs/../README.md"), None);
+    }
+}
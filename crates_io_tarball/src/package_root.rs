@@ -0,0 +1,137 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Returns the top-level directory of `path`, if it is equivalent to the
+/// canonical package directory `pkg_name` (`$name-$vers`).
+///
+/// Apart from the canonical directory, the build metadata of versions that
+/// have some may be stripped (`foo-1.0.0` for `foo-1.0.0+abc`), since older
+/// versions of cargo packaged crates like that. Everything else has to match
+/// exactly, since cargo refuses to extract crate files with other package
+/// directories.
+pub(crate) fn equivalent_root(path: &Path, pkg_name: &str) -> Option<PathBuf> {
+    let Some(Component::Normal(root)) = path.components().next() else {
+        return None;
+    };
+
+    let root = root.to_str()?;
+    is_equivalent_root(root, pkg_name).then(|| PathBuf::from(root))
+}
+
+fn is_equivalent_root(root: &str, pkg_name: &str) -> bool {
+    if root == pkg_name {
+        return true;
+    }
+
+    // Neither crate names nor pre-release identifiers can contain a `+`, so
+    // the first one always starts the build metadata.
+    pkg_name
+        .split_once('+')
+        .is_some_and(|(base, _build)| root == base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{equivalent_root, is_equivalent_root};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn canonical_root() {
+        assert!(is_equivalent_root("foo-1.0.0", "foo-1.0.0"));
+        assert!(is_equivalent_root("foo-1.0.0-beta.1", "foo-1.0.0-beta.1"));
+        assert!(is_equivalent_root("foo-1.0.0+abc", "foo-1.0.0+abc"));
+        assert!(is_equivalent_root(
+            "foo-1.0.0-beta.1+abc.2",
+            "foo-1.0.0-beta.1+abc.2"
+        ));
+    }
+
+    #[test]
+    fn stripped_build_metadata() {
+        assert!(is_equivalent_root("foo-1.0.0", "foo-1.0.0+abc"));
+        assert!(is_equivalent_root(
+            "foo-1.0.0-beta.1",
+            "foo-1.0.0-beta.1+abc"
+        ));
+
+        // Only the build metadata may be stripped
+        assert!(!is_equivalent_root("foo-1.0.0", "foo-1.0.0-beta.1"));
+        assert!(!is_equivalent_root("foo-1.0.0", "foo-1.0.0-beta.1+abc"));
+        assert!(!is_equivalent_root("foo-1.0", "foo-1.0.0+abc"));
+
+        // ... and only entirely, not partially
+        assert!(!is_equivalent_root("foo-1.0.0+ab", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0+abc", "foo-1.0.0+abc.2"));
+        assert!(!is_equivalent_root("foo-1.0.0+", "foo-1.0.0+abc"));
+    }
+
+    #[test]
+    fn encoded_build_metadata() {
+        // cargo doesn't decode the package directory
+        assert!(!is_equivalent_root("foo-1.0.0%2Babc", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0%2babc", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root(
+            "foo-1.0.0-beta.1%2Babc.2",
+            "foo-1.0.0-beta.1+abc.2"
+        ));
+
+        // Neither are other encodings and separators equivalent
+        assert!(!is_equivalent_root("foo-1.0.0%2B", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0%252Babc", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0%20abc", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0-abc", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0_abc", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0abc", "foo-1.0.0+abc"));
+
+        // The build metadata itself is not case-folded
+        assert!(!is_equivalent_root("foo-1.0.0+ABC", "foo-1.0.0+abc"));
+    }
+
+    #[test]
+    fn versions_without_build_metadata() {
+        assert!(!is_equivalent_root("foo-1.0.0+abc", "foo-1.0.0"));
+        assert!(!is_equivalent_root("foo-1.0.0+", "foo-1.0.0"));
+        assert!(!is_equivalent_root("foo-1.0.0%2Babc", "foo-1.0.0"));
+        assert!(!is_equivalent_root("foo-1.0.0-beta.1", "foo-1.0.0"));
+        assert!(!is_equivalent_root("foo-1.0", "foo-1.0.0"));
+        assert!(!is_equivalent_root(
+            "foo-1.0.0-beta.1",
+            "foo-1.0.0-beta.1.2"
+        ));
+        assert!(!is_equivalent_root("foo-1.0.0-BETA.1", "foo-1.0.0-beta.1"));
+    }
+
+    #[test]
+    fn different_names() {
+        assert!(!is_equivalent_root("Foo-1.0.0", "foo-1.0.0"));
+        assert!(!is_equivalent_root("Foo-1.0.0", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("bar-1.0.0", "foo-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo_bar-1.0.0", "foo-bar-1.0.0+abc"));
+        assert!(!is_equivalent_root("foo-1.0.0", "foo-bar-1.0.0+abc"));
+        assert!(!is_equivalent_root("", "foo-1.0.0+abc"));
+    }
+
+    #[test]
+    fn roots_of_paths() {
+        let root = |path: &str, pkg_name| equivalent_root(Path::new(path), pkg_name);
+
+        assert_eq!(
+            root("foo-1.0.0/Cargo.toml", "foo-1.0.0"),
+            Some(PathBuf::from("foo-1.0.0"))
+        );
+        assert_eq!(
+            root("foo-1.0.0+abc/src/lib.rs", "foo-1.0.0+abc"),
+            Some(PathBuf::from("foo-1.0.0+abc"))
+        );
+        assert_eq!(
+            root("foo-1.0.0", "foo-1.0.0+abc"),
+            Some(PathBuf::from("foo-1.0.0"))
+        );
+
+        assert_eq!(root("bar-1.0.0/Cargo.toml", "foo-1.0.0"), None);
+        assert_eq!(root("foo-1.0.0%2Babc/Cargo.toml", "foo-1.0.0+abc"), None);
+        assert_eq!(root("/foo-1.0.0/Cargo.toml", "foo-1.0.0"), None);
+        assert_eq!(root("./foo-1.0.0/Cargo.toml", "foo-1.0.0"), None);
+        assert_eq!(root("../foo-1.0.0/Cargo.toml", "foo-1.0.0"), None);
+        assert_eq!(root("", "foo-1.0.0"), None);
+    }
+}
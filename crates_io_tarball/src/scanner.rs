@@ -0,0 +1,305 @@
+//! Inspects the files of a crate file for content that doesn't belong in a
+//! source package, like compiled binaries and native libraries.
+//!
+//! Unlike a [`TarballValidator`](crate::TarballValidator), the scanner never
+//! rejects a crate file. Its findings are collected in a [`ScanReport`] that
+//! is stored for the crates.io team to review.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes of compiled binaries that have no business being in a crate
+/// file, since crates are built from source.
+///
+/// PE binaries are recognized separately, since their `MZ` magic bytes are
+/// too short to be reliable on their own.
+const BINARY_MAGIC: [(&[u8], &str); 7] = [
+    (b"\x7fELF", "ELF"),
+    (b"\xfe\xed\xfa\xce", "Mach-O"),
+    (b"\xfe\xed\xfa\xcf", "Mach-O"),
+    (b"\xce\xfa\xed\xfe", "Mach-O"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O"),
+    (b"\xca\xfe\xba\xbe", "Mach-O universal"),
+    (b"!<arch>\n", "static library"),
+];
+
+/// File extensions of native libraries, which are reported even if their
+/// contents don't look like a binary, e.g. because they are linker scripts.
+const NATIVE_LIBRARY_EXTENSIONS: &[&str] = &["so", "dll", "dylib", "a", "lib"];
+
+/// File extensions of formats that are compressed by design and thus
+/// always have a high entropy.
+const COMPRESSED_MEDIA_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "ico", "woff", "woff2", "ttf", "otf",
+];
+
+/// Files smaller than this are not checked for a high entropy, since the
+/// estimate is unreliable for small samples.
+const MIN_ENTROPY_SIZE: usize = 4 * 1024;
+
+/// The entropy in bits per byte above which a file is reported. Source code
+/// and other text rarely exceeds 5, while compressed and encrypted data gets
+/// close to the maximum of 8.
+const HIGH_ENTROPY_THRESHOLD: f64 = 7.5;
+
+/// The files of a crate file that look suspicious, in the order of the
+/// archive.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub findings: Vec<ScanFinding>,
+}
+
+impl ScanReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanFinding {
+    /// Path of the file relative to the package root, e.g. `lib/libfoo.so`.
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub kind: ScanFindingKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScanFindingKind {
+    /// The file starts with the magic bytes of a compiled binary, e.g. `ELF`.
+    Binary { format: String },
+    /// The file is named like a native library, e.g. `libfoo.so.1`.
+    NativeLibrary { extension: String },
+    /// The file consists of seemingly random bytes, which may be compressed
+    /// or encrypted data. The entropy is given in bits per byte.
+    HighEntropy { entropy: f64 },
+}
+
+impl ScanFindingKind {
+    /// A short description of the finding, e.g. for log messages.
+    pub fn description(&self) -> String {
+        match self {
+            Self::Binary { format } => format!("{format} binary"),
+            Self::NativeLibrary { extension } => format!("native library (`.{extension}`)"),
+            Self::HighEntropy { entropy } => format!("high entropy ({entropy:.2} bits per byte)"),
+        }
+    }
+}
+
+/// Returns the binary format of `contents` based on its magic bytes, e.g.
+/// `ELF` or `PE`.
+pub(crate) fn binary_format(contents: &[u8]) -> Option<&'static str> {
+    if is_pe(contents) {
+        return Some("PE");
+    }
+
+    BINARY_MAGIC
+        .iter()
+        .find(|(magic, _)| contents.starts_with(magic))
+        .map(|(_, format)| *format)
+}
+
+/// Returns whether `contents` is a PE binary, i.e. starts with the `MZ`
+/// magic bytes of the DOS header and has a valid offset to the `PE` header
+/// at `0x3c`. Text files that happen to start with `MZ` lack the latter.
+fn is_pe(contents: &[u8]) -> bool {
+    if !contents.starts_with(b"MZ") {
+        return false;
+    }
+
+    let Some(offset) = contents.get(0x3c..0x40) else {
+        return false;
+    };
+    let offset = u32::from_le_bytes(offset.try_into().unwrap()) as usize;
+
+    offset
+        .checked_add(4)
+        .and_then(|end| contents.get(offset..end))
+        == Some(b"PE\0\0")
+}
+
+/// Scans a single file, returning the most significant finding if there is
+/// any: a binary is only reported as such, even if it is also named like a
+/// native library and has a high entropy.
+pub(crate) fn scan_file(path: &Path, contents: &[u8]) -> Option<ScanFindingKind> {
+    if let Some(format) = binary_format(contents) {
+        let format = format.to_string();
+        return Some(ScanFindingKind::Binary { format });
+    }
+
+    if let Some(extension) = native_library_extension(path) {
+        let extension = extension.to_string();
+        return Some(ScanFindingKind::NativeLibrary { extension });
+    }
+
+    if contents.len() >= MIN_ENTROPY_SIZE && !is_compressed_media(path) {
+        let entropy = shannon_entropy(contents);
+        if entropy >= HIGH_ENTROPY_THRESHOLD {
+            return Some(ScanFindingKind::HighEntropy { entropy });
+        }
+    }
+
+    None
+}
+
+/// Returns the native library extension of the file name of `path`, also
+/// recognizing versioned shared libraries like `libfoo.so.1.2`.
+fn native_library_extension(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+
+    let mut parts = name.split('.').skip(1).collect::<Vec<_>>();
+    while parts.len() > 1 && parts.last()?.bytes().all(|c| c.is_ascii_digit()) {
+        parts.pop();
+    }
+
+    let extension = parts.last()?;
+    NATIVE_LIBRARY_EXTENSIONS
+        .iter()
+        .find(|known| extension.eq_ignore_ascii_case(known))
+        .copied()
+}
+
+fn is_compressed_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            COMPRESSED_MEDIA_EXTENSIONS
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Calculates the Shannon entropy of `contents` in bits per byte.
+fn shannon_entropy(contents: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in contents {
+        counts[byte as usize] += 1;
+    }
+
+    let len = contents.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Bytes that are spread evenly over all values, like compressed data.
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// A minimal PE binary with the `PE` header right after the DOS header.
+    pub(crate) fn pe_binary() -> Vec<u8> {
+        let mut contents = b"MZ".to_vec();
+        contents.resize(0x3c, 0);
+        contents.extend(0x40u32.to_le_bytes());
+        contents.extend(b"PE\0\0");
+        contents
+    }
+
+    fn scan(path: &str, contents: &[u8]) -> Option<ScanFindingKind> {
+        scan_file(Path::new(path), contents)
+    }
+
+    #[test]
+    fn binaries() {
+        assert_eq!(binary_format(b"\x7fELF\x02\x01\x01"), Some("ELF"));
+        assert_eq!(binary_format(&pe_binary()), Some("PE"));
+        assert_eq!(binary_format(b"MZ\x90\x00"), None);
+        assert_eq!(binary_format(b"MZ is the code of an airport\n"), None);
+        assert_eq!(binary_format(b"\xcf\xfa\xed\xfe"), Some("Mach-O"));
+        assert_eq!(binary_format(b"!<arch>\nfoo.o"), Some("static library"));
+        assert_eq!(binary_format(b"fn main() {}"), None);
+        assert_eq!(binary_format(b""), None);
+
+        assert_eq!(
+            scan("lib/libfoo.so", b"\x7fELF\x02\x01\x01"),
+            Some(ScanFindingKind::Binary {
+                format: "ELF".into()
+            })
+        );
+    }
+
+    #[test]
+    fn native_libraries() {
+        assert_eq!(native_library_extension(Path::new("libfoo.so")), Some("so"));
+        assert_eq!(
+            native_library_extension(Path::new("lib/libfoo.so.1.2")),
+            Some("so")
+        );
+        assert_eq!(native_library_extension(Path::new("foo.DLL")), Some("dll"));
+        assert_eq!(native_library_extension(Path::new("libfoo.a")), Some("a"));
+        assert_eq!(
+            native_library_extension(Path::new("foo.dylib")),
+            Some("dylib")
+        );
+        assert_eq!(native_library_extension(Path::new("src/lib.rs")), None);
+        assert_eq!(native_library_extension(Path::new("so")), None);
+        assert_eq!(native_library_extension(Path::new("foo.1")), None);
+
+        assert_eq!(
+            scan("libfoo.so", b"INPUT(libfoo.so.1)"),
+            Some(ScanFindingKind::NativeLibrary {
+                extension: "so".into()
+            })
+        );
+    }
+
+    #[test]
+    fn entropy() {
+        assert_eq!(shannon_entropy(b""), 0.0);
+        assert_eq!(shannon_entropy(b"aaaa"), 0.0);
+        assert_eq!(shannon_entropy(b"abab"), 1.0);
+
+        let random = random_bytes(MIN_ENTROPY_SIZE);
+        assert!(shannon_entropy(&random) > HIGH_ENTROPY_THRESHOLD);
+        assert!(matches!(
+            scan("data.bin", &random),
+            Some(ScanFindingKind::HighEntropy { .. })
+        ));
+
+        // Small files and images are not reported
+        assert_eq!(scan("data.bin", &random[..MIN_ENTROPY_SIZE - 1]), None);
+        assert_eq!(scan("logo.png", &random), None);
+
+        let source = "pub fn foo() -> u32 { 42 }\n".repeat(200);
+        assert_eq!(scan("src/lib.rs", source.as_bytes()), None);
+    }
+
+    #[test]
+    fn serialization() {
+        let report = ScanReport {
+            findings: vec![ScanFinding {
+                path: "bin/tool".into(),
+                kind: ScanFindingKind::Binary {
+                    format: "ELF".into(),
+                },
+            }],
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "findings": [{ "path": "bin/tool", "kind": "binary", "format": "ELF" }]
+            })
+        );
+        assert_eq!(serde_json::from_value::<ScanReport>(json).unwrap(), report);
+    }
+}
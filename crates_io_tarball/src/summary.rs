@@ -0,0 +1,158 @@
+use crate::compression::Decoder;
+use crate::limit_reader::LimitErrorReader;
+use crate::{decode_utf8_lossy, Compression};
+use serde::Serialize;
+use std::io::Read;
+
+/// The number of entries that are listed in a [`TarballSummary`].
+const MAX_SUMMARY_ENTRIES: usize = 100;
+
+/// The number of bytes of the manifest that are kept in a [`TarballSummary`].
+const MAX_MANIFEST_SNIPPET: usize = 8 * 1024;
+
+/// A best-effort overview of the contents of a crate file, which is meant to
+/// help debugging crate files that were rejected by `process_tarball`.
+///
+/// Unlike `process_tarball`, this never fails: reading stops at the first
+/// problem, which is recorded in `error`, and everything read until then is
+/// kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TarballSummary {
+    pub compression: Option<Compression>,
+    /// The number of entries that could be read, including the ones that
+    /// are not listed in `entries`.
+    pub entry_count: u64,
+    /// The first entries of the archive, in the order of the archive.
+    pub entries: Vec<SummaryEntry>,
+    /// The beginning of the first `Cargo.toml` file of the package root,
+    /// decoded lossily if it is not valid UTF-8.
+    pub manifest: Option<String>,
+    /// The problem that stopped reading the archive, if any.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SummaryEntry {
+    /// Path of the entry as stored in the archive, e.g. `foo-1.0.0/src/lib.rs`.
+    pub path: String,
+    /// `file`, `directory`, `symlink`, `hard_link` or `other`.
+    pub kind: &'static str,
+    pub size: u64,
+}
+
+/// Summarizes the entries of a crate file, decompressing at most
+/// `max_unpack` bytes.
+pub fn summarize_tarball(tarball: &[u8], max_unpack: u64) -> TarballSummary {
+    let mut summary = TarballSummary::default();
+    if let Err(error) = read_summary(tarball, max_unpack, &mut summary) {
+        summary.error = Some(error);
+    }
+    summary
+}
+
+fn read_summary(
+    tarball: &[u8],
+    max_unpack: u64,
+    summary: &mut TarballSummary,
+) -> Result<(), String> {
+    let (compression, decoder) = Decoder::detect(tarball)
+        .map_err(|error| error.to_string())?
+        .ok_or("unknown compression format")?;
+    summary.compression = Some(compression);
+
+    let decoder = LimitErrorReader::new(decoder, max_unpack);
+    let mut archive = tar::Archive::new(decoder);
+
+    for entry in archive.entries().map_err(|error| error.to_string())? {
+        let mut entry = entry.map_err(|error| error.to_string())?;
+        summary.entry_count += 1;
+
+        let path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let entry_type = entry.header().entry_type();
+        let kind = match entry_type {
+            _ if entry_type.is_file() => "file",
+            _ if entry_type.is_dir() => "directory",
+            _ if entry_type.is_symlink() => "symlink",
+            _ if entry_type.is_hard_link() => "hard_link",
+            _ => "other",
+        };
+
+        let is_manifest = path
+            .split_once('/')
+            .is_some_and(|(_, path)| path == "Cargo.toml");
+        if summary.manifest.is_none() && entry_type.is_file() && is_manifest {
+            let mut contents = Vec::new();
+            (&mut entry)
+                .take(MAX_MANIFEST_SNIPPET as u64)
+                .read_to_end(&mut contents)
+                .map_err(|error| error.to_string())?;
+            summary.manifest = Some(decode_utf8_lossy(&contents).into_owned());
+        }
+
+        if summary.entries.len() < MAX_SUMMARY_ENTRIES {
+            let size = entry.size();
+            summary.entries.push(SummaryEntry { path, kind, size });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TarballBuilder;
+
+    #[test]
+    fn summary() {
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\nname = \"foo\"\n")
+            .add_file("foo-0.0.1/src/lib.rs", b"pub fn foo() {}")
+            .add_symlink("foo-0.0.1/bar", "/etc/passwd")
+            .add_file("foo-0.0.1/examples/Cargo.toml", b"[package]")
+            .build();
+
+        let summary = summarize_tarball(&tarball, u64::MAX);
+        assert_eq!(summary.compression, Some(Compression::Gzip));
+        assert_eq!(summary.entry_count, 4);
+        assert_eq!(
+            summary.manifest.as_deref(),
+            Some("[package]\nname = \"foo\"\n")
+        );
+        assert_none!(summary.error);
+
+        let entry = |path: &str, kind, size| SummaryEntry {
+            path: path.into(),
+            kind,
+            size,
+        };
+        assert_eq!(
+            summary.entries,
+            vec![
+                entry("foo-0.0.1/Cargo.toml", "file", 23),
+                entry("foo-0.0.1/src/lib.rs", "file", 15),
+                entry("foo-0.0.1/bar", "symlink", 0),
+                entry("foo-0.0.1/examples/Cargo.toml", "file", 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_of_broken_tarballs() {
+        let summary = summarize_tarball(b"foo", u64::MAX);
+        assert_none!(summary.compression);
+        assert_some_eq!(summary.error, "unknown compression format");
+
+        let tarball = TarballBuilder::new("foo", "0.0.1")
+            .add_file("foo-0.0.1/src/lib.rs", &[b' '; 2048])
+            .add_file("foo-0.0.1/src/main.rs", &[b' '; 2048])
+            .build();
+
+        // Entries that were read before the limit was exceeded are kept
+        let summary = summarize_tarball(&tarball, 2 * 1024);
+        assert_eq!(summary.compression, Some(Compression::Gzip));
+        assert_eq!(summary.entry_count, 1);
+        assert_eq!(summary.entries[0].path, "foo-0.0.1/src/lib.rs");
+        assert_some!(summary.error);
+    }
+}
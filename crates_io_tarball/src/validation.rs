@@ -0,0 +1,242 @@
+use crate::scanner::binary_format;
+use std::path::Path;
+
+/// A regular file or a directory of a crate file, as passed to a
+/// [`TarballValidator`].
+#[derive(Debug, Clone, Copy)]
+pub struct TarballEntry<'a> {
+    /// Path of the entry relative to the package root, e.g. `src/lib.rs`.
+    pub path: &'a Path,
+    /// Decompressed size of the file in bytes, or zero for directories.
+    pub size: u64,
+    /// The contents of the file, which are empty for directories.
+    pub contents: &'a [u8],
+}
+
+/// A check that every regular file and directory of a crate file has to
+/// pass, in addition to the checks that `process_tarball` always performs.
+///
+/// The returned error message is reported to the user together with the
+/// path of the rejected file.
+pub trait TarballValidator: Send + Sync {
+    fn validate(&self, entry: &TarballEntry<'_>) -> Result<(), String>;
+}
+
+/// A list of validators that are run one after another, stopping at the
+/// first rejection. An empty set accepts all files.
+#[derive(Default)]
+pub struct ValidatorSet {
+    validators: Vec<Box<dyn TarballValidator>>,
+}
+
+impl ValidatorSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, validator: impl TarballValidator + 'static) -> Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty()
+    }
+}
+
+impl TarballValidator for ValidatorSet {
+    fn validate(&self, entry: &TarballEntry<'_>) -> Result<(), String> {
+        self.validators
+            .iter()
+            .try_for_each(|validator| validator.validate(entry))
+    }
+}
+
+/// Rejects compiled binaries like executables and libraries, which are
+/// recognized by their magic bytes. The file mode is not checked, since
+/// scripts are often executable too.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoExecutables;
+
+impl TarballValidator for NoExecutables {
+    fn validate(&self, entry: &TarballEntry<'_>) -> Result<(), String> {
+        match binary_format(entry.contents) {
+            Some(format) => Err(format!("{format} binaries are not allowed")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Rejects files that are larger than the given number of bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxFileSize(pub u64);
+
+impl TarballValidator for MaxFileSize {
+    fn validate(&self, entry: &TarballEntry<'_>) -> Result<(), String> {
+        if entry.size > self.0 {
+            return Err(format!(
+                "the file is larger than the maximum file size of {} bytes",
+                self.0
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Rejects files whose path relative to the package root matches any of the
+/// given glob patterns.
+///
+/// `*` matches any characters except `/`, `**` matches any characters
+/// including `/`, and `?` matches a single character except `/`. Patterns
+/// without a `/` are matched against the file name only, like in
+/// `.gitignore` files, e.g. `*.exe` matches `bin/tool.exe`.
+#[derive(Debug, Clone)]
+pub struct DenyPaths {
+    patterns: Vec<String>,
+}
+
+impl DenyPaths {
+    pub fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let patterns = patterns.into_iter().map(Into::into).collect();
+        Self { patterns }
+    }
+}
+
+impl TarballValidator for DenyPaths {
+    fn validate(&self, entry: &TarballEntry<'_>) -> Result<(), String> {
+        let path = entry.path.to_string_lossy().replace('\\', "/");
+        let file_name = path.rsplit('/').next().unwrap_or(&path);
+
+        let pattern = self.patterns.iter().find(|pattern| {
+            let subject = if pattern.contains('/') {
+                &path
+            } else {
+                file_name
+            };
+            glob_matches(pattern.as_bytes(), subject.as_bytes())
+        });
+
+        match pattern {
+            Some(pattern) => Err(format!("the path matches the denied pattern `{pattern}`")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn glob_matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        // `**/` only matches whole directories, so `**/foo` doesn't match `barfoo`
+        [b'*', b'*', b'/', rest @ ..] => (0..=path.len())
+            .filter(|&i| i == 0 || path[i - 1] == b'/')
+            .any(|i| glob_matches(rest, &path[i..])),
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| glob_matches(rest, &path[i..])),
+        [b'*', rest @ ..] => {
+            let segment = path.iter().position(|&c| c == b'/').unwrap_or(path.len());
+            (0..=segment).any(|i| glob_matches(rest, &path[i..]))
+        }
+        [b'?', rest @ ..] => match path {
+            [c, path @ ..] if *c != b'/' => glob_matches(rest, path),
+            _ => false,
+        },
+        [p, rest @ ..] => match path {
+            [c, path @ ..] if c == p => glob_matches(rest, path),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::tests::pe_binary;
+
+    fn entry<'a>(path: &'a str, contents: &'a [u8]) -> TarballEntry<'a> {
+        TarballEntry {
+            path: Path::new(path),
+            size: contents.len() as u64,
+            contents,
+        }
+    }
+
+    #[test]
+    fn no_executables() {
+        assert_ok!(NoExecutables.validate(&entry("src/lib.rs", b"pub fn foo() {}")));
+        assert_ok!(NoExecutables.validate(&entry("empty", b"")));
+        assert_ok!(NoExecutables.validate(&entry("run.sh", b"#!/bin/sh\n")));
+        assert_err_eq!(
+            NoExecutables.validate(&entry("bin/tool", b"\x7fELF\x02\x01\x01")),
+            "ELF binaries are not allowed"
+        );
+        assert_err_eq!(
+            NoExecutables.validate(&entry("tool.exe", &pe_binary())),
+            "PE binaries are not allowed"
+        );
+        assert_ok!(NoExecutables.validate(&entry("notes.txt", b"MZ is a text file")));
+        assert_err!(NoExecutables.validate(&entry("libfoo.a", b"!<arch>\nfoo.o")));
+    }
+
+    #[test]
+    fn max_file_size() {
+        assert_ok!(MaxFileSize(3).validate(&entry("foo", b"foo")));
+        assert_err_eq!(
+            MaxFileSize(2).validate(&entry("foo", b"foo")),
+            "the file is larger than the maximum file size of 2 bytes"
+        );
+    }
+
+    #[test]
+    fn deny_paths() {
+        let validator = DenyPaths::new(["*.exe", "vendor/**", "tests/fixtures/*.bin"]);
+        assert_ok!(validator.validate(&entry("src/lib.rs", b"")));
+        assert_ok!(validator.validate(&entry("src/vendor.rs", b"")));
+        assert_ok!(validator.validate(&entry("tests/fixtures/data/foo.bin", b"")));
+        assert_err_eq!(
+            validator.validate(&entry("bin/tool.exe", b"")),
+            "the path matches the denied pattern `*.exe`"
+        );
+        assert_err!(validator.validate(&entry("vendor/foo/src/lib.rs", b"")));
+        assert_err!(validator.validate(&entry("tests/fixtures/foo.bin", b"")));
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches(b"foo", b"foo"));
+        assert!(!glob_matches(b"foo", b"foobar"));
+        assert!(glob_matches(b"foo*", b"foobar"));
+        assert!(!glob_matches(b"foo*", b"foo/bar"));
+        assert!(glob_matches(b"f?o", b"fao"));
+        assert!(!glob_matches(b"f?o", b"f/o"));
+        assert!(glob_matches(b"**/*.rs", b"src/lib.rs"));
+        assert!(glob_matches(b"**/*.rs", b"lib.rs"));
+        assert!(!glob_matches(b"**/foo", b"barfoo"));
+        assert!(glob_matches(b"**/foo", b"bar/foo"));
+        assert!(glob_matches(b"src/**", b"src/a/b/c"));
+    }
+
+    #[test]
+    fn validator_set() {
+        let validators = ValidatorSet::new();
+        assert!(validators.is_empty());
+        assert_ok!(validators.validate(&entry("tool.exe", b"\x7fELF")));
+
+        let validators = ValidatorSet::new()
+            .with(DenyPaths::new(["*.exe"]))
+            .with(NoExecutables);
+        assert!(!validators.is_empty());
+        assert_ok!(validators.validate(&entry("src/lib.rs", b"")));
+        assert_err_eq!(
+            validators.validate(&entry("tool.exe", b"\x7fELF")),
+            "the path matches the denied pattern `*.exe`"
+        );
+        assert_err_eq!(
+            validators.validate(&entry("tool", b"\x7fELF")),
+            "ELF binaries are not allowed"
+        );
+    }
+}
@@ -1,23 +1,67 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 
 /// Represents relevant contents of .cargo_vcs_info.json file when uploaded from cargo
 /// or downloaded from crates.io
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
 pub struct CargoVcsInfo {
+    /// The git commit the package was built from, if cargo found a git
+    /// repository. Malformed values are treated as missing, so that they
+    /// don't hide the other fields.
+    #[serde(default, deserialize_with = "lenient_git_info")]
+    pub git: Option<GitVcsInfo>,
     /// Path to the package within repo (empty string if root). / not \
     #[serde(default)]
     pub path_in_vcs: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+pub struct GitVcsInfo {
+    /// The hex encoded hash of the `HEAD` commit, either a SHA-1 or, for
+    /// repositories using the newer object format, a SHA-256 hash.
+    pub sha1: String,
+    /// Whether the working tree had uncommitted changes when the package was
+    /// built, which means that the commit doesn't match the package contents.
+    /// Cargo omits the field for clean working trees.
+    #[serde(default)]
+    pub dirty: bool,
+}
+
 impl CargoVcsInfo {
     pub fn from_contents(contents: &str) -> serde_json::Result<Self> {
         serde_json::from_str(contents)
     }
+
+    /// The hash of the commit the package was built from.
+    pub fn commit_sha(&self) -> Option<&str> {
+        self.git.as_ref().map(|git| git.sha1.as_str())
+    }
+
+    /// Whether the package was built from a git working tree with
+    /// uncommitted changes.
+    pub fn is_dirty(&self) -> bool {
+        self.git.as_ref().is_some_and(|git| git.dirty)
+    }
+}
+
+fn lenient_git_info<'de, D>(deserializer: D) -> Result<Option<GitVcsInfo>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let git = serde_json::from_value::<GitVcsInfo>(value).ok();
+    Ok(git.filter(|git| is_commit_sha(&git.sha1)))
+}
+
+/// Returns whether `sha` is a full SHA-1 or SHA-256 hash in lowercase hex.
+fn is_commit_sha(sha: &str) -> bool {
+    matches!(sha.len(), 40 | 64) && sha.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CargoVcsInfo;
+    use super::{CargoVcsInfo, GitVcsInfo};
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
 
     #[test]
     fn test_cargo_vcs_info() {
@@ -25,20 +69,64 @@ mod tests {
         assert_eq!(
             CargoVcsInfo::from_contents("{}").unwrap(),
             CargoVcsInfo {
+                git: None,
                 path_in_vcs: "".into()
             }
         );
         assert_eq!(
             CargoVcsInfo::from_contents(r#"{"path_in_vcs": "hi"}"#).unwrap(),
             CargoVcsInfo {
+                git: None,
                 path_in_vcs: "hi".into()
             }
         );
         assert_eq!(
             CargoVcsInfo::from_contents(r#"{"path_in_vcs": "hi", "future": "field"}"#).unwrap(),
             CargoVcsInfo {
+                git: None,
                 path_in_vcs: "hi".into()
             }
         );
     }
+
+    #[test]
+    fn test_cargo_vcs_info_git() {
+        let contents = format!(r#"{{"git": {{"sha1": "{SHA}"}}, "path_in_vcs": "foo"}}"#);
+        let vcs_info = CargoVcsInfo::from_contents(&contents).unwrap();
+        assert_eq!(
+            vcs_info,
+            CargoVcsInfo {
+                git: Some(GitVcsInfo {
+                    sha1: SHA.into(),
+                    dirty: false,
+                }),
+                path_in_vcs: "foo".into()
+            }
+        );
+        assert_eq!(vcs_info.commit_sha(), Some(SHA));
+        assert!(!vcs_info.is_dirty());
+
+        let contents = format!(r#"{{"git": {{"sha1": "{SHA}", "dirty": true}}}}"#);
+        let vcs_info = CargoVcsInfo::from_contents(&contents).unwrap();
+        assert_eq!(vcs_info.commit_sha(), Some(SHA));
+        assert!(vcs_info.is_dirty());
+
+        let sha256 = SHA.repeat(2);
+        let contents = format!(r#"{{"git": {{"sha1": "{}"}}}}"#, &sha256[..64]);
+        let vcs_info = CargoVcsInfo::from_contents(&contents).unwrap();
+        assert_eq!(vcs_info.commit_sha(), Some(&sha256[..64]));
+
+        // Malformed git information doesn't affect the other fields
+        for git in [
+            r#""main""#,
+            r#"{"sha1": "main"}"#,
+            r#"{"dirty": true}"#,
+            "null",
+        ] {
+            let contents = format!(r#"{{"git": {git}, "path_in_vcs": "foo"}}"#);
+            let vcs_info = CargoVcsInfo::from_contents(&contents).unwrap();
+            assert_eq!(vcs_info.git, None);
+            assert_eq!(vcs_info.path_in_vcs, "foo");
+        }
+    }
 }
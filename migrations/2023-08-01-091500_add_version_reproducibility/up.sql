@@ -0,0 +1,10 @@
+CREATE TABLE version_reproducibility (
+  version_id INTEGER PRIMARY KEY NOT NULL REFERENCES versions ON DELETE CASCADE,
+  reproducible BOOLEAN NOT NULL,
+  git_tag VARCHAR NOT NULL,
+  checked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+COMMENT ON TABLE version_reproducibility IS 'Results of comparing the files of a published crate file with its source repository';
+COMMENT ON COLUMN version_reproducibility.reproducible IS 'TRUE if all files of the crate file match the files in the repository';
+COMMENT ON COLUMN version_reproducibility.git_tag IS 'The git tag that the crate file was compared against';
@@ -0,0 +1,11 @@
+ALTER TABLE crate_owner_invitations ADD COLUMN expiry_notified_at TIMESTAMP;
+
+COMMENT ON COLUMN crate_owner_invitations.expiry_notified_at IS 'Point in time at which the invited user and the inviter were notified that the invitation expired';
+
+-- Invitations that expired before the notifications were introduced are
+-- marked as notified, so that the first run of the job doesn't send emails
+-- about invitations that expired a long time ago. Invitations expire after
+-- 30 days, see `DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS`.
+UPDATE crate_owner_invitations
+SET expiry_notified_at = CURRENT_TIMESTAMP
+WHERE created_at <= CURRENT_TIMESTAMP - INTERVAL '30 days';
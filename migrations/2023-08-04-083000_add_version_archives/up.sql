@@ -0,0 +1,11 @@
+CREATE TABLE version_archives (
+  version_id INTEGER PRIMARY KEY NOT NULL REFERENCES versions ON DELETE CASCADE,
+  archived_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  restore_requested_at TIMESTAMP,
+  original_deleted_at TIMESTAMP
+);
+
+COMMENT ON TABLE version_archives IS 'Versions whose crate files were moved to the archive storage tier';
+COMMENT ON COLUMN version_archives.archived_at IS 'Point in time at which the crate file was copied to the archive storage tier';
+COMMENT ON COLUMN version_archives.restore_requested_at IS 'Point in time at which the crate file was requested to be restored, or NULL if no restore was requested yet';
+COMMENT ON COLUMN version_archives.original_deleted_at IS 'Point in time at which the crate file was deleted from its regular location, or NULL if it was only copied to the archive storage tier so far';
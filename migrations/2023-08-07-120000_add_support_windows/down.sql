@@ -0,0 +1 @@
+DROP TABLE support_windows;
@@ -0,0 +1,13 @@
+CREATE TABLE support_windows (
+  crate_id INTEGER NOT NULL REFERENCES crates ON DELETE CASCADE,
+  major_version VARCHAR NOT NULL,
+  status INTEGER NOT NULL,
+  end_of_life_date DATE,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (crate_id, major_version)
+);
+
+COMMENT ON TABLE support_windows IS 'Support status of the semver-compatible release lines of a crate, as declared by its owners';
+COMMENT ON COLUMN support_windows.major_version IS 'The semver-compatible release line, e.g. `1` for `1.x.y` or `0.4` for `0.4.x`';
+COMMENT ON COLUMN support_windows.status IS '0 = supported, 1 = security fixes only, 2 = end of life';
+COMMENT ON COLUMN support_windows.end_of_life_date IS 'Date at which the release line stops or stopped receiving any fixes';
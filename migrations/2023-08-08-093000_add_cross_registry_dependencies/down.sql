@@ -0,0 +1 @@
+DROP TABLE cross_registry_dependencies;
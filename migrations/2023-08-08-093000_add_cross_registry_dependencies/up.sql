@@ -0,0 +1,19 @@
+CREATE TABLE cross_registry_dependencies (
+  id SERIAL PRIMARY KEY,
+  version_id INTEGER NOT NULL REFERENCES versions ON DELETE CASCADE,
+  registry VARCHAR NOT NULL,
+  name VARCHAR NOT NULL,
+  req VARCHAR NOT NULL,
+  optional BOOLEAN NOT NULL,
+  default_features BOOLEAN NOT NULL,
+  features TEXT[] NOT NULL,
+  target VARCHAR,
+  kind INTEGER NOT NULL DEFAULT 0,
+  explicit_name VARCHAR
+);
+
+CREATE INDEX cross_registry_dependencies_version_id ON cross_registry_dependencies (version_id);
+
+COMMENT ON TABLE cross_registry_dependencies IS 'Dependencies of versions on crates that are hosted on other registries';
+COMMENT ON COLUMN cross_registry_dependencies.registry IS 'The index URL of the registry that hosts the dependency';
+COMMENT ON COLUMN cross_registry_dependencies.name IS 'The name of the crate on the other registry';
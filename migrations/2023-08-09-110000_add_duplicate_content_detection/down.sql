@@ -0,0 +1,2 @@
+DROP TABLE moderation_queue;
+DROP TABLE version_fingerprints;
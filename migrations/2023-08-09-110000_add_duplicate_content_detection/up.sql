@@ -0,0 +1,29 @@
+CREATE TABLE version_fingerprints (
+  version_id INTEGER PRIMARY KEY NOT NULL REFERENCES versions ON DELETE CASCADE,
+  crate_id INTEGER NOT NULL REFERENCES crates ON DELETE CASCADE,
+  hashes BIGINT[] NOT NULL
+);
+
+CREATE INDEX version_fingerprints_hashes ON version_fingerprints USING GIN (hashes);
+
+COMMENT ON TABLE version_fingerprints IS 'Fingerprints of the source files of published crate files, used to detect crates with duplicate content';
+COMMENT ON COLUMN version_fingerprints.crate_id IS 'The crate of the version, to exclude other versions of the same crate when looking for duplicates';
+COMMENT ON COLUMN version_fingerprints.hashes IS 'The smallest hashes of the normalized file contents, in ascending order';
+
+CREATE TABLE moderation_queue (
+  id SERIAL PRIMARY KEY,
+  crate_id INTEGER NOT NULL REFERENCES crates ON DELETE CASCADE,
+  version_id INTEGER REFERENCES versions ON DELETE CASCADE,
+  reason VARCHAR NOT NULL,
+  details JSONB NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  resolved_at TIMESTAMP
+);
+
+CREATE INDEX moderation_queue_unresolved ON moderation_queue (created_at) WHERE resolved_at IS NULL;
+
+COMMENT ON TABLE moderation_queue IS 'Crates that were flagged automatically and need to be reviewed by the crates.io team';
+COMMENT ON COLUMN moderation_queue.version_id IS 'The version that caused the crate to be flagged, if any';
+COMMENT ON COLUMN moderation_queue.reason IS 'Machine readable reason for the flag, e.g. `duplicate_content`';
+COMMENT ON COLUMN moderation_queue.details IS 'Reason specific details, e.g. the crate with similar content';
+COMMENT ON COLUMN moderation_queue.resolved_at IS 'Point in time at which the flag was reviewed, or NULL if it still needs to be reviewed';
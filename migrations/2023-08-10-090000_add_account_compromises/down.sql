@@ -0,0 +1,3 @@
+DROP TABLE account_compromises;
+
+ALTER TABLE users DROP COLUMN sessions_revoked_at;
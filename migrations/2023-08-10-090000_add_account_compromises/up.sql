@@ -0,0 +1,22 @@
+ALTER TABLE users ADD COLUMN sessions_revoked_at TIMESTAMP;
+
+COMMENT ON COLUMN users.sessions_revoked_at IS 'Browser sessions of the user that were started before this point in time are no longer valid';
+
+CREATE TABLE account_compromises (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+  admin_id INTEGER NOT NULL REFERENCES users,
+  reason VARCHAR NOT NULL,
+  revoked_tokens INTEGER NOT NULL,
+  flagged_versions INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  resolved_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX account_compromises_unresolved ON account_compromises (user_id) WHERE resolved_at IS NULL;
+
+COMMENT ON TABLE account_compromises IS 'Accounts that were put into compromise mode by the crates.io team. New publishes from these accounts are held until the compromise is resolved.';
+COMMENT ON COLUMN account_compromises.admin_id IS 'The crates.io team member that put the account into compromise mode';
+COMMENT ON COLUMN account_compromises.revoked_tokens IS 'Number of API tokens that were revoked';
+COMMENT ON COLUMN account_compromises.flagged_versions IS 'Number of recently published versions that were added to the moderation queue';
+COMMENT ON COLUMN account_compromises.resolved_at IS 'Point in time at which the compromise was resolved, or NULL if new publishes are still held';
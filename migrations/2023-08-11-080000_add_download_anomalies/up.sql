@@ -0,0 +1,18 @@
+CREATE TABLE download_anomalies (
+  crate_id INTEGER NOT NULL REFERENCES crates ON DELETE CASCADE,
+  date DATE NOT NULL,
+  downloads BIGINT NOT NULL,
+  baseline_mean DOUBLE PRECISION NOT NULL,
+  baseline_stddev DOUBLE PRECISION NOT NULL,
+  excluded_downloads BIGINT NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (crate_id, date)
+);
+
+CREATE INDEX download_anomalies_date ON download_anomalies (date);
+
+COMMENT ON TABLE download_anomalies IS 'Days on which a crate was downloaded unusually often compared to its baseline, e.g. because of artificial inflation';
+COMMENT ON COLUMN download_anomalies.downloads IS 'Number of downloads of all versions of the crate on that day';
+COMMENT ON COLUMN download_anomalies.baseline_mean IS 'Average number of daily downloads of the crate in the days before';
+COMMENT ON COLUMN download_anomalies.baseline_stddev IS 'Standard deviation of the daily downloads of the crate in the days before';
+COMMENT ON COLUMN download_anomalies.excluded_downloads IS 'Number of downloads above the baseline that were subtracted from the public download counts of the crate';
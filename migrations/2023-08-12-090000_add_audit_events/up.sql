@@ -0,0 +1,21 @@
+CREATE TABLE audit_events (
+  id BIGSERIAL PRIMARY KEY,
+  kind VARCHAR NOT NULL,
+  user_id INTEGER REFERENCES users ON DELETE SET NULL,
+  api_token_id INTEGER REFERENCES api_tokens ON DELETE SET NULL,
+  ip_address VARCHAR,
+  details JSONB NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  exported_at TIMESTAMP
+);
+
+CREATE INDEX audit_events_not_exported ON audit_events (id) WHERE exported_at IS NULL;
+CREATE INDEX audit_events_created_at ON audit_events (created_at);
+
+COMMENT ON TABLE audit_events IS 'Security relevant events like logins, token anomalies and admin actions, which are shipped to an external audit sink';
+COMMENT ON COLUMN audit_events.kind IS 'Kind of the event, e.g. `login` or `admin.delete_crate`';
+COMMENT ON COLUMN audit_events.user_id IS 'User that caused the event, if any';
+COMMENT ON COLUMN audit_events.api_token_id IS 'API token that was used, if any';
+COMMENT ON COLUMN audit_events.ip_address IS 'IP address of the client, as reported by the `X-Real-Ip` header';
+COMMENT ON COLUMN audit_events.details IS 'Additional event specific information';
+COMMENT ON COLUMN audit_events.exported_at IS 'Time at which the event was shipped to the audit sink, or NULL if it has not been shipped yet';
@@ -0,0 +1,5 @@
+DROP TABLE category_redirects;
+
+ALTER TABLE categories DROP COLUMN deprecated_at;
+
+ALTER TABLE users DROP COLUMN is_admin;
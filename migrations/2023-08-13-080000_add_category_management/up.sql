@@ -0,0 +1,19 @@
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+COMMENT ON COLUMN users.is_admin IS 'Whether the user is a member of the crates.io team and may use the admin endpoints of the API';
+
+ALTER TABLE categories ADD COLUMN deprecated_at TIMESTAMP;
+
+COMMENT ON COLUMN categories.deprecated_at IS 'Time at which the category was deprecated, or NULL. Deprecated categories can no longer be assigned to crates.';
+
+CREATE TABLE category_redirects (
+  old_slug VARCHAR PRIMARY KEY,
+  category_id INTEGER NOT NULL REFERENCES categories ON DELETE CASCADE,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX category_redirects_category_id ON category_redirects (category_id);
+
+COMMENT ON TABLE category_redirects IS 'Slugs of renamed or merged categories, and the categories that they now point to';
+COMMENT ON COLUMN category_redirects.old_slug IS 'Previous slug of the category';
+COMMENT ON COLUMN category_redirects.category_id IS 'Category that the old slug redirects to';
@@ -0,0 +1,3 @@
+DROP TABLE digest_preferences;
+DROP TABLE category_subscriptions;
+DROP TABLE keyword_subscriptions;
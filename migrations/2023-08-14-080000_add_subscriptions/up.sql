@@ -0,0 +1,29 @@
+CREATE TABLE keyword_subscriptions (
+  user_id INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+  keyword_id INTEGER NOT NULL REFERENCES keywords ON DELETE CASCADE,
+  PRIMARY KEY (user_id, keyword_id)
+);
+
+CREATE INDEX keyword_subscriptions_keyword_id ON keyword_subscriptions (keyword_id);
+
+COMMENT ON TABLE keyword_subscriptions IS 'Keywords that users want to be notified about when crates with them are published';
+
+CREATE TABLE category_subscriptions (
+  user_id INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+  category_id INTEGER NOT NULL REFERENCES categories ON DELETE CASCADE,
+  PRIMARY KEY (user_id, category_id)
+);
+
+CREATE INDEX category_subscriptions_category_id ON category_subscriptions (category_id);
+
+COMMENT ON TABLE category_subscriptions IS 'Categories that users want to be notified about when crates in them, or in their subcategories, are published';
+
+CREATE TABLE digest_preferences (
+  user_id INTEGER PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+  frequency INTEGER NOT NULL,
+  last_sent_at TIMESTAMP
+);
+
+COMMENT ON TABLE digest_preferences IS 'How often users receive the email digest of their keyword and category subscriptions. Users without a row receive a weekly digest.';
+COMMENT ON COLUMN digest_preferences.frequency IS '0 = never, 1 = daily, 2 = weekly';
+COMMENT ON COLUMN digest_preferences.last_sent_at IS 'Time up to which publishes were included in the last digest';
@@ -0,0 +1,3 @@
+ALTER TABLE follows
+  DROP COLUMN mode,
+  DROP COLUMN channel;
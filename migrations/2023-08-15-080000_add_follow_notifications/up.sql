@@ -0,0 +1,6 @@
+ALTER TABLE follows
+  ADD COLUMN mode INTEGER NOT NULL DEFAULT 0,
+  ADD COLUMN channel INTEGER NOT NULL DEFAULT 0;
+
+COMMENT ON COLUMN follows.mode IS '0 = new releases only, 1 = all activity, including yanks and ownership changes';
+COMMENT ON COLUMN follows.channel IS '0 = dashboard feed only, 1 = also included in the email digest';
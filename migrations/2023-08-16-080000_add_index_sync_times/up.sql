@@ -0,0 +1,9 @@
+CREATE TABLE index_sync_times (
+  crate_id INTEGER PRIMARY KEY REFERENCES crates ON DELETE CASCADE,
+  git_synced_at TIMESTAMP,
+  sparse_synced_at TIMESTAMP
+);
+
+COMMENT ON TABLE index_sync_times IS 'When the index files of crates were last synced to the git and sparse indexes';
+COMMENT ON COLUMN index_sync_times.git_synced_at IS 'Time at which the index data was read for the last successful push to the git index';
+COMMENT ON COLUMN index_sync_times.sparse_synced_at IS 'Time at which the index data was read for the last successful upload to the sparse index, including the CDN invalidation';
@@ -0,0 +1,13 @@
+CREATE TABLE repository_verifications (
+  user_id INTEGER PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+  repository VARCHAR NOT NULL,
+  challenge VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT now(),
+  verified_at TIMESTAMP
+);
+
+COMMENT ON TABLE repository_verifications IS 'Repositories that users have proven control of, which exempts them from the stricter rate limits for newcomers';
+COMMENT ON COLUMN repository_verifications.repository IS 'GitHub repository in the `owner/name` form';
+COMMENT ON COLUMN repository_verifications.challenge IS 'Random value that has to be pushed to the repository, either as the content of the `.crates-io-verification` file or as the `crates-io-verification-<challenge>` tag';
+COMMENT ON COLUMN repository_verifications.created_at IS 'Time at which the challenge was issued';
+COMMENT ON COLUMN repository_verifications.verified_at IS 'Time at which the challenge was found in the repository, or NULL if it has not been verified yet';
@@ -0,0 +1,12 @@
+CREATE TABLE features_usage (
+  crate_id INTEGER NOT NULL REFERENCES crates ON DELETE CASCADE,
+  feature VARCHAR NOT NULL,
+  date DATE NOT NULL,
+  dependents INTEGER NOT NULL,
+  PRIMARY KEY (crate_id, feature, date)
+);
+
+COMMENT ON TABLE features_usage IS 'Daily snapshots of how many dependent crates request each feature of a crate';
+COMMENT ON COLUMN features_usage.feature IS 'Name of the feature, or `default` for dependents that use the default features';
+COMMENT ON COLUMN features_usage.date IS 'Date of the snapshot';
+COMMENT ON COLUMN features_usage.dependents IS 'Number of crates whose newest non-yanked version requests the feature in a normal or build dependency on the crate';
@@ -0,0 +1,16 @@
+CREATE TABLE security_yanks (
+  id SERIAL PRIMARY KEY,
+  version_id INTEGER NOT NULL UNIQUE REFERENCES versions ON DELETE CASCADE,
+  advisory_url VARCHAR NOT NULL,
+  published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  modified_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  withdrawn_at TIMESTAMP
+);
+
+CREATE INDEX security_yanks_modified_at ON security_yanks (modified_at);
+
+COMMENT ON TABLE security_yanks IS 'Versions that were yanked for security reasons, which are exported as OSV records';
+COMMENT ON COLUMN security_yanks.advisory_url IS 'Link to the security advisory that was given when the version was yanked';
+COMMENT ON COLUMN security_yanks.published_at IS 'Time at which the version was first yanked for security reasons';
+COMMENT ON COLUMN security_yanks.modified_at IS 'Time at which the record was last changed';
+COMMENT ON COLUMN security_yanks.withdrawn_at IS 'Time at which the version was unyanked, if it is no longer considered affected';
@@ -0,0 +1,17 @@
+CREATE TABLE download_redirect_samples (
+  id BIGSERIAL PRIMARY KEY,
+  crate_name VARCHAR NOT NULL,
+  version VARCHAR NOT NULL,
+  user_agent_class VARCHAR NOT NULL,
+  cdn VARCHAR NOT NULL,
+  sample_rate DOUBLE PRECISION NOT NULL,
+  sampled_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX download_redirect_samples_sampled_at ON download_redirect_samples (sampled_at);
+
+COMMENT ON TABLE download_redirect_samples IS 'Random sample of the download redirects, which is pruned after 30 days';
+COMMENT ON COLUMN download_redirect_samples.crate_name IS 'Crate name as requested by the client, which might not be canonical';
+COMMENT ON COLUMN download_redirect_samples.user_agent_class IS 'Coarse class of the `User-Agent` header, e.g. `cargo/1.72` or `browser`';
+COMMENT ON COLUMN download_redirect_samples.cdn IS 'Host that the client was redirected to, or `archive` if the file was served from the archive storage';
+COMMENT ON COLUMN download_redirect_samples.sample_rate IS 'Fraction of the download redirects that were sampled at the time, to extrapolate totals';
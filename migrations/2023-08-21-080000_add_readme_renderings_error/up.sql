@@ -0,0 +1,3 @@
+ALTER TABLE readme_renderings ADD COLUMN error VARCHAR;
+
+COMMENT ON COLUMN readme_renderings.error IS 'Reason why the README could not be rendered, in which case a placeholder was uploaded instead';
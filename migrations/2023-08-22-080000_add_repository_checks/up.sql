@@ -0,0 +1,15 @@
+CREATE TABLE repository_checks (
+  crate_id INTEGER PRIMARY KEY REFERENCES crates ON DELETE CASCADE,
+  repository VARCHAR NOT NULL,
+  status VARCHAR NOT NULL,
+  redirected_to VARCHAR,
+  checked_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX repository_checks_checked_at ON repository_checks (checked_at);
+
+COMMENT ON TABLE repository_checks IS 'Results of the periodic checks whether the repositories that crates link to still exist';
+COMMENT ON COLUMN repository_checks.repository IS 'The repository URL of the crate at the time of the check';
+COMMENT ON COLUMN repository_checks.status IS 'Either `available`, `archived` or `not_found`';
+COMMENT ON COLUMN repository_checks.redirected_to IS 'URL that the repository redirects to if it was renamed or transferred, or NULL otherwise';
+COMMENT ON COLUMN repository_checks.checked_at IS 'Time of the latest check';
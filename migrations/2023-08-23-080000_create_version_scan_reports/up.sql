@@ -0,0 +1,11 @@
+CREATE TABLE version_scan_reports (
+  version_id INTEGER PRIMARY KEY NOT NULL REFERENCES versions ON DELETE CASCADE,
+  findings JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX version_scan_reports_created_at ON version_scan_reports (created_at);
+
+COMMENT ON TABLE version_scan_reports IS 'Files of published crate files that look like compiled binaries or otherwise do not belong in a source package, see `crates_io_tarball::scanner`';
+COMMENT ON COLUMN version_scan_reports.findings IS 'The suspicious files, as a JSON array of objects with a `path` and a `kind` field';
+COMMENT ON COLUMN version_scan_reports.created_at IS 'Point in time at which the crate file was scanned';
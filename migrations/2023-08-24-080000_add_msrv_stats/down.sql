@@ -0,0 +1 @@
+DROP TABLE msrv_stats;
@@ -0,0 +1,13 @@
+CREATE TABLE msrv_stats (
+  date DATE NOT NULL,
+  rust_version VARCHAR NOT NULL,
+  version_count INTEGER NOT NULL,
+  crate_count INTEGER NOT NULL,
+  PRIMARY KEY (date, rust_version)
+);
+
+COMMENT ON TABLE msrv_stats IS 'Daily snapshots of the `rust-version` values that recently published versions declare';
+COMMENT ON COLUMN msrv_stats.date IS 'Date of the snapshot';
+COMMENT ON COLUMN msrv_stats.rust_version IS 'The declared `rust-version`, truncated to the major and minor version, or an empty string for versions that do not declare one';
+COMMENT ON COLUMN msrv_stats.version_count IS 'Number of non-yanked versions published in the period before the snapshot that declare the `rust-version`';
+COMMENT ON COLUMN msrv_stats.crate_count IS 'Number of distinct crates of these versions';
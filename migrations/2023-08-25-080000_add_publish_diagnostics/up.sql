@@ -0,0 +1,19 @@
+CREATE TABLE publish_diagnostics (
+  token VARCHAR PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+  crate_name VARCHAR NOT NULL,
+  version VARCHAR NOT NULL,
+  error VARCHAR NOT NULL,
+  context JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX publish_diagnostics_expires_at ON publish_diagnostics (expires_at);
+
+COMMENT ON TABLE publish_diagnostics IS 'Context of crate files that were rejected on publish, kept for a short time to debug support requests';
+COMMENT ON COLUMN publish_diagnostics.token IS 'Random token that is included in the publish error, with which the crates.io team can look up the context';
+COMMENT ON COLUMN publish_diagnostics.user_id IS 'The user that tried to publish the crate file';
+COMMENT ON COLUMN publish_diagnostics.error IS 'The error message that was returned to the user';
+COMMENT ON COLUMN publish_diagnostics.context IS 'Summary of the rejected crate file, like its manifest and the first entries of the archive';
+COMMENT ON COLUMN publish_diagnostics.expires_at IS 'Point in time after which the context is deleted';
@@ -0,0 +1,3 @@
+ALTER TABLE versions ADD COLUMN dirty_worktree BOOLEAN NOT NULL DEFAULT FALSE;
+
+COMMENT ON COLUMN versions.dirty_worktree IS 'Whether the `.cargo_vcs_info.json` file of the crate file reported uncommitted changes in the git working tree, in which case the recorded commit does not match the published source';
@@ -0,0 +1,3 @@
+ALTER TABLE versions
+    DROP COLUMN commit_sha,
+    DROP COLUMN source_url;
@@ -0,0 +1,6 @@
+ALTER TABLE versions
+    ADD COLUMN commit_sha VARCHAR,
+    ADD COLUMN source_url VARCHAR;
+
+COMMENT ON COLUMN versions.commit_sha IS 'The hash of the git commit that the crate file was packaged from, according to its `.cargo_vcs_info.json` file';
+COMMENT ON COLUMN versions.source_url IS 'Link to the source code of the version at `commit_sha` in the repository of the crate at the time of publishing, if the repository is hosted on a supported site';
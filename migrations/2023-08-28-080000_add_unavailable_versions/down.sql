@@ -0,0 +1 @@
+DROP TABLE unavailable_versions;
@@ -0,0 +1,9 @@
+CREATE TABLE unavailable_versions (
+  version_id INTEGER PRIMARY KEY NOT NULL REFERENCES versions ON DELETE CASCADE,
+  reason VARCHAR NOT NULL,
+  marked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+COMMENT ON TABLE unavailable_versions IS 'Versions whose crate files are missing or corrupted in the storage and could not be repaired';
+COMMENT ON COLUMN unavailable_versions.reason IS 'Why the crate file is unavailable, e.g. `missing` or `empty`';
+COMMENT ON COLUMN unavailable_versions.marked_at IS 'Point in time at which the version was marked as unavailable';
@@ -0,0 +1,14 @@
+DROP TRIGGER trigger_versions_update_crate_activity ON versions;
+DROP FUNCTION update_crate_activity();
+
+DROP TRIGGER trigger_crates_set_updated_at ON crates;
+CREATE TRIGGER trigger_crates_set_updated_at
+    BEFORE UPDATE
+    ON crates
+    FOR EACH ROW
+EXECUTE PROCEDURE set_updated_at_ignore_downloads();
+DROP FUNCTION set_crates_updated_at();
+
+ALTER TABLE crates
+    DROP COLUMN first_version_published_at,
+    DROP COLUMN last_activity_at;
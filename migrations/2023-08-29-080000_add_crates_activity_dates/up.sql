@@ -0,0 +1,74 @@
+ALTER TABLE crates
+    ADD COLUMN first_version_published_at TIMESTAMP,
+    ADD COLUMN last_activity_at TIMESTAMP;
+
+COMMENT ON COLUMN crates.first_version_published_at IS 'Point in time at which the oldest existing version of the crate was published, maintained by the `trigger_versions_update_crate_activity` trigger';
+COMMENT ON COLUMN crates.last_activity_at IS 'Point in time at which a version of the crate was last published, yanked or unyanked, maintained by the `trigger_versions_update_crate_activity` trigger';
+
+-- The activity dates are maintained by a trigger, like the download counts,
+-- so changing them doesn't count as an update of the crate.
+CREATE FUNCTION set_crates_updated_at() RETURNS trigger AS $$
+BEGIN
+    OLD.downloads := NEW.downloads;
+    OLD.first_version_published_at := NEW.first_version_published_at;
+    OLD.last_activity_at := NEW.last_activity_at;
+    IF (
+        NEW IS DISTINCT FROM OLD AND
+        NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at
+    ) THEN
+        NEW.updated_at = CURRENT_TIMESTAMP;
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER trigger_crates_set_updated_at ON crates;
+CREATE TRIGGER trigger_crates_set_updated_at
+    BEFORE UPDATE
+    ON crates
+    FOR EACH ROW
+EXECUTE PROCEDURE set_crates_updated_at();
+
+CREATE FUNCTION update_crate_activity() RETURNS trigger AS $$
+BEGIN
+    IF (TG_OP = 'INSERT') THEN
+        UPDATE crates
+        SET first_version_published_at = LEAST(first_version_published_at, NEW.created_at),
+            last_activity_at = GREATEST(last_activity_at, NEW.created_at)
+        WHERE id = NEW.crate_id;
+    ELSIF (TG_OP = 'UPDATE') THEN
+        IF (NEW.yanked IS DISTINCT FROM OLD.yanked) THEN
+            UPDATE crates
+            SET last_activity_at = GREATEST(last_activity_at, NEW.updated_at)
+            WHERE id = NEW.crate_id;
+        END IF;
+    ELSE
+        UPDATE crates
+        SET first_version_published_at = (
+            SELECT MIN(created_at) FROM versions WHERE crate_id = OLD.crate_id
+        )
+        WHERE id = OLD.crate_id;
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_versions_update_crate_activity
+    AFTER INSERT OR DELETE OR UPDATE OF yanked
+    ON versions
+    FOR EACH ROW
+EXECUTE PROCEDURE update_crate_activity();
+
+-- The activity dates of the existing crates are not updated here, since that
+-- would lock all rows of `crates` until the deploy is finished. They are
+-- backfilled in batches after the deployment instead. Versions that are
+-- published in the meantime make the trigger set the dates of their crate
+-- from the new version alone, so the filter compares with the actual oldest
+-- version, instead of only looking for missing dates:
+--
+--   crates-admin enqueue-job batched_backfill --table crates \
+--     --set "first_version_published_at = (SELECT MIN(created_at) FROM versions WHERE versions.crate_id = crates.id), last_activity_at = GREATEST(last_activity_at, (SELECT MAX(GREATEST(created_at, updated_at)) FROM versions WHERE versions.crate_id = crates.id))" \
+--     --where "first_version_published_at IS DISTINCT FROM (SELECT MIN(created_at) FROM versions WHERE versions.crate_id = crates.id)"
+--
+-- The indexes for the `new` and `recent-updates` sort orders of the crate
+-- list are built concurrently in the next migrations.
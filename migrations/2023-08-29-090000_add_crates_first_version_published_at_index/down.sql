@@ -0,0 +1 @@
+DROP INDEX CONCURRENTLY crates_first_version_published_at;
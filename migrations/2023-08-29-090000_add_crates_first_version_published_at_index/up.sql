@@ -0,0 +1,3 @@
+-- For the `new` sort order of the crate list. Built concurrently, so that
+-- publishes are not blocked while the index is built.
+CREATE INDEX CONCURRENTLY crates_first_version_published_at ON crates (first_version_published_at DESC NULLS LAST);
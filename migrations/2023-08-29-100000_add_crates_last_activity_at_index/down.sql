@@ -0,0 +1 @@
+DROP INDEX CONCURRENTLY crates_last_activity_at;
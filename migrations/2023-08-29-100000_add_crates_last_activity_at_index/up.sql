@@ -0,0 +1,3 @@
+-- For the `recent-updates` sort order of the crate list. Built concurrently,
+-- so that publishes are not blocked while the index is built.
+CREATE INDEX CONCURRENTLY crates_last_activity_at ON crates (last_activity_at DESC NULLS LAST);
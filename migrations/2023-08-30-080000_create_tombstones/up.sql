@@ -0,0 +1,13 @@
+CREATE TABLE tombstones (
+  id SERIAL PRIMARY KEY,
+  crate_name VARCHAR NOT NULL,
+  version VARCHAR,
+  deleted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX tombstones_canon_crate_name ON tombstones (canon_crate_name(crate_name));
+
+COMMENT ON TABLE tombstones IS 'Deleted crates and versions, whose names can only be published again after a cooldown';
+COMMENT ON COLUMN tombstones.crate_name IS 'Name of the deleted crate, or of the crate of the deleted version';
+COMMENT ON COLUMN tombstones.version IS 'Number of the deleted version, or NULL if the whole crate was deleted';
+COMMENT ON COLUMN tombstones.deleted_at IS 'Point in time at which the crate or version was deleted';
@@ -0,0 +1,3 @@
+DROP TABLE failed_background_jobs;
+
+ALTER TABLE background_jobs DROP COLUMN queue;
@@ -0,0 +1,23 @@
+ALTER TABLE background_jobs ADD COLUMN queue VARCHAR NOT NULL DEFAULT 'default';
+
+COMMENT ON COLUMN background_jobs.queue IS 'Name of the queue of the job. Each queue is processed by its own runner, so that slow jobs don''t hold up the jobs of other queues.';
+
+UPDATE background_jobs SET queue = 'readmes' WHERE job_type = 'render_and_upload_readme';
+
+CREATE TABLE failed_background_jobs (
+  id BIGINT PRIMARY KEY,
+  job_type TEXT NOT NULL,
+  data JSONB NOT NULL,
+  queue VARCHAR NOT NULL,
+  retries INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  failed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  error TEXT NOT NULL
+);
+
+COMMENT ON TABLE failed_background_jobs IS 'Background jobs of queues with a retry limit that failed too often, and are not retried anymore';
+COMMENT ON COLUMN failed_background_jobs.id IS 'ID of the job in the `background_jobs` table';
+COMMENT ON COLUMN failed_background_jobs.retries IS 'Number of failed attempts to run the job';
+COMMENT ON COLUMN failed_background_jobs.created_at IS 'Point in time at which the job was enqueued';
+COMMENT ON COLUMN failed_background_jobs.failed_at IS 'Point in time at which the job failed for the last time';
+COMMENT ON COLUMN failed_background_jobs.error IS 'Error of the last failed attempt to run the job';
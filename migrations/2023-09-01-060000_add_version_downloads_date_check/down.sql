@@ -0,0 +1 @@
+ALTER TABLE version_downloads DROP CONSTRAINT IF EXISTS version_downloads_date_check;
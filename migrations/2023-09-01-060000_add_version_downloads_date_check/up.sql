@@ -0,0 +1,19 @@
+SET lock_timeout = '5s';
+
+-- Attaching `version_downloads` as a partition in the next migrations scans
+-- the whole table to check its bounds, while holding a lock that blocks the
+-- download counting. A validated constraint that implies the bounds skips
+-- that scan. The constraint is only added here, without checking the
+-- existing rows, and validated in a separate migration, so that the brief
+-- exclusive lock taken here isn't held during the scan.
+--
+-- Downloads of the next month are rejected until the table is partitioned,
+-- which happens right after this in the same deployment.
+DO $$
+BEGIN
+  EXECUTE format(
+    'ALTER TABLE version_downloads ADD CONSTRAINT version_downloads_date_check CHECK (date < %L) NOT VALID',
+    date_trunc('month', CURRENT_DATE + interval '1 month')::date
+  );
+END
+$$;
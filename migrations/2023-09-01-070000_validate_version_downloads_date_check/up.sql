@@ -0,0 +1,4 @@
+-- Validating the constraint runs in its own transaction and only takes a
+-- `SHARE UPDATE EXCLUSIVE` lock, so downloads can still be counted during the
+-- full table scan.
+ALTER TABLE version_downloads VALIDATE CONSTRAINT version_downloads_date_check;
@@ -0,0 +1,12 @@
+ALTER TABLE version_downloads DETACH PARTITION version_downloads_legacy;
+
+INSERT INTO version_downloads_legacy (version_id, downloads, counted, date, processed)
+SELECT version_id, downloads, counted, date, processed FROM version_downloads;
+
+DROP TABLE version_downloads;
+
+ALTER TABLE version_downloads_legacy RENAME TO version_downloads;
+ALTER TABLE version_downloads RENAME CONSTRAINT version_downloads_legacy_pkey TO version_downloads_pkey;
+ALTER TABLE version_downloads RENAME CONSTRAINT fk_version_downloads_legacy_version_id TO fk_version_downloads_version_id;
+ALTER INDEX index_version_downloads_legacy_date RENAME TO index_version_downloads_date;
+ALTER INDEX index_version_downloads_legacy_not_processed RENAME TO index_version_downloads_not_processed;
@@ -0,0 +1,61 @@
+SET lock_timeout = '5s';
+
+-- The existing table becomes the first partition of the new partitioned
+-- table, covering all dates up to the start of the next month. The monthly
+-- partitions after that are created ahead of time by the
+-- `manage_downloads_partitions` background job.
+ALTER TABLE version_downloads RENAME TO version_downloads_legacy;
+ALTER TABLE version_downloads_legacy RENAME CONSTRAINT version_downloads_pkey TO version_downloads_legacy_pkey;
+ALTER TABLE version_downloads_legacy RENAME CONSTRAINT fk_version_downloads_version_id TO fk_version_downloads_legacy_version_id;
+ALTER INDEX index_version_downloads_date RENAME TO index_version_downloads_legacy_date;
+ALTER INDEX index_version_downloads_not_processed RENAME TO index_version_downloads_legacy_not_processed;
+
+CREATE TABLE version_downloads (
+  version_id INTEGER NOT NULL,
+  downloads INTEGER NOT NULL DEFAULT 1,
+  counted INTEGER NOT NULL DEFAULT 0,
+  date DATE NOT NULL DEFAULT CURRENT_DATE,
+  processed BOOLEAN NOT NULL DEFAULT 'f',
+  CONSTRAINT version_downloads_pkey PRIMARY KEY (version_id, date),
+  CONSTRAINT fk_version_downloads_version_id FOREIGN KEY (version_id) REFERENCES versions (id) ON DELETE CASCADE
+) PARTITION BY RANGE (date);
+
+-- The indexes match the ones of the legacy table, so that they are attached
+-- instead of being built again.
+CREATE INDEX index_version_downloads_date ON version_downloads USING brin (date) WITH (pages_per_range = 1);
+CREATE INDEX index_version_downloads_not_processed ON version_downloads (processed) WHERE NOT processed;
+
+DO $$
+DECLARE
+  next_month DATE := date_trunc('month', CURRENT_DATE + interval '1 month');
+  month DATE;
+BEGIN
+  -- The validated `version_downloads_date_check` constraint from the previous
+  -- migrations implies the partition bounds, so attaching the partition
+  -- doesn't scan the table.
+  EXECUTE format(
+    'ALTER TABLE version_downloads ATTACH PARTITION version_downloads_legacy FOR VALUES FROM (MINVALUE) TO (%L)',
+    next_month
+  );
+
+  -- The partition bounds enforce the same condition from now on.
+  ALTER TABLE version_downloads_legacy DROP CONSTRAINT version_downloads_date_check;
+
+  FOR i IN 0..2 LOOP
+    month := next_month + make_interval(months => i);
+    EXECUTE format(
+      'CREATE TABLE %I PARTITION OF version_downloads FOR VALUES FROM (%L) TO (%L)',
+      'version_downloads_' || to_char(month, 'YYYY_MM'),
+      month,
+      month + interval '1 month'
+    );
+  END LOOP;
+END
+$$;
+
+-- Downloads for dates without a monthly partition end up here, so that they
+-- are still counted if the `manage_downloads_partitions` job stops running.
+-- The job moves them to the monthly partition when it creates it.
+CREATE TABLE version_downloads_default PARTITION OF version_downloads DEFAULT;
+
+COMMENT ON TABLE version_downloads IS 'Number of downloads per version and day, partitioned by month. Old partitions are archived to the storage and detached by the `manage_downloads_partitions` background job.';
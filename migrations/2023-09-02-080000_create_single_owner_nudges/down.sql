@@ -0,0 +1 @@
+DROP TABLE single_owner_nudges;
@@ -0,0 +1,14 @@
+CREATE TABLE single_owner_nudges (
+  crate_id INTEGER PRIMARY KEY REFERENCES crates (id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  flagged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  emailed_at TIMESTAMP
+);
+
+CREATE INDEX single_owner_nudges_user_id ON single_owner_nudges (user_id);
+
+COMMENT ON TABLE single_owner_nudges IS 'Popular crates with a single owner, whose owner is asked to add another owner';
+COMMENT ON COLUMN single_owner_nudges.crate_id IS 'The crate that only has a single owner';
+COMMENT ON COLUMN single_owner_nudges.user_id IS 'The only owner of the crate';
+COMMENT ON COLUMN single_owner_nudges.flagged_at IS 'Point in time at which the crate was first found to only have a single owner';
+COMMENT ON COLUMN single_owner_nudges.emailed_at IS 'Point in time at which the owner was last reminded by email, or NULL if no email was sent yet';
@@ -0,0 +1,9 @@
+CREATE TABLE version_publish_warnings (
+  version_id INTEGER PRIMARY KEY NOT NULL REFERENCES versions ON DELETE CASCADE,
+  warnings JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+COMMENT ON TABLE version_publish_warnings IS 'Warnings that were returned to the publisher of a version, for versions that were published with warnings';
+COMMENT ON COLUMN version_publish_warnings.warnings IS 'The warnings, as a JSON array of objects with a `kind` and a `message` field';
+COMMENT ON COLUMN version_publish_warnings.created_at IS 'Point in time at which the version was published';
@@ -0,0 +1 @@
+DROP TABLE archived_version_downloads;
@@ -0,0 +1,9 @@
+CREATE TABLE archived_version_downloads (
+  version_id INTEGER NOT NULL PRIMARY KEY REFERENCES versions (id) ON DELETE CASCADE,
+  downloads BIGINT NOT NULL DEFAULT 0,
+  counted BIGINT NOT NULL DEFAULT 0
+);
+
+COMMENT ON TABLE archived_version_downloads IS 'Sums of the daily downloads of versions in the `version_downloads` partitions that were archived and dropped, so that the download counts can still be recomputed';
+COMMENT ON COLUMN archived_version_downloads.downloads IS 'Sum of the `downloads` column of the dropped rows';
+COMMENT ON COLUMN archived_version_downloads.counted IS 'Sum of the `counted` column of the dropped rows';
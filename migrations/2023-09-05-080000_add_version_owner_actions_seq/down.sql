@@ -0,0 +1,4 @@
+DROP TRIGGER trigger_assign_version_owner_actions_seq ON version_owner_actions;
+DROP FUNCTION assign_version_owner_actions_seq();
+ALTER TABLE version_owner_actions DROP COLUMN seq;
+DROP SEQUENCE version_owner_actions_seq_seq;
@@ -0,0 +1,46 @@
+-- The `id` of a row is assigned on insert, but transactions commit in a
+-- different order, so a reader could see a change with a higher `id` before
+-- one with a lower `id` becomes visible. The change feed pages on `seq`
+-- instead, which is assigned right before the commit, while holding a lock
+-- until the commit is done, so that it increases in commit order.
+CREATE SEQUENCE version_owner_actions_seq_seq AS BIGINT;
+
+ALTER TABLE version_owner_actions ADD COLUMN seq BIGINT;
+
+COMMENT ON COLUMN version_owner_actions.seq IS 'Increasing in the order in which the rows were committed, used for paging through the change feed';
+
+-- The commit order of the existing rows is not known anymore, so their `id`
+-- is the best approximation. New rows get a higher `seq` than any existing
+-- `id`. The existing rows are not updated here, since that would rewrite and
+-- lock the whole table. They are backfilled in batches after the deployment
+-- instead, and are missing from the change feed until then:
+--
+--   crates-admin enqueue-job batched_backfill --table version_owner_actions --set "seq = id" --where "seq IS NULL"
+--
+-- The unique index on `seq` is built concurrently in the next migration.
+SELECT setval('version_owner_actions_seq_seq', COALESCE((SELECT MAX(id) FROM version_owner_actions), 0) + 1, false);
+
+CREATE FUNCTION assign_version_owner_actions_seq() RETURNS TRIGGER AS $$
+BEGIN
+    -- The lock is held until the transaction is committed, so that
+    -- transactions that assign a sequence number later also commit later.
+    --
+    -- This serializes the commits of all transactions that record an action,
+    -- i.e. publishes, yanks and unyanks. Since the trigger is deferred, the
+    -- lock is only taken at commit time, after all other work of the
+    -- transaction is done, so it is held just for the commit itself. A lock
+    -- per crate would not be enough, since the sequence is shared by all
+    -- crates.
+    PERFORM pg_advisory_xact_lock(hashtext('version_owner_actions_seq'));
+    UPDATE version_owner_actions
+        SET seq = nextval('version_owner_actions_seq_seq')
+        WHERE id = NEW.id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER trigger_assign_version_owner_actions_seq
+    AFTER INSERT ON version_owner_actions
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+EXECUTE PROCEDURE assign_version_owner_actions_seq();
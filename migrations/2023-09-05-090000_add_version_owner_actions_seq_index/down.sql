@@ -0,0 +1 @@
+DROP INDEX CONCURRENTLY version_owner_actions_seq;
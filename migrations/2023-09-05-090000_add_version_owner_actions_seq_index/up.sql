@@ -0,0 +1,4 @@
+-- Built concurrently, so that publishes and yanks are not blocked while the
+-- index is built. Rows that are not backfilled yet have no `seq`, which
+-- doesn't violate the uniqueness.
+CREATE UNIQUE INDEX CONCURRENTLY version_owner_actions_seq ON version_owner_actions (seq);
@@ -0,0 +1,3 @@
+ALTER TABLE dependencies DROP COLUMN requirement_id;
+
+DROP TABLE dependency_requirements;
@@ -0,0 +1,35 @@
+CREATE TABLE dependency_requirements (
+    id SERIAL PRIMARY KEY,
+    req VARCHAR NOT NULL UNIQUE
+);
+
+COMMENT ON TABLE dependency_requirements IS 'Version requirement strings of dependencies, which are stored once and referenced by the `dependencies` table, since most crates use the same few requirements';
+
+SET lock_timeout = '5s';
+
+ALTER TABLE dependencies ADD COLUMN requirement_id INTEGER;
+
+COMMENT ON COLUMN dependencies.requirement_id IS 'The interned `req` of the dependency. NULL for dependencies that were inserted before the requirements were interned, until they are backfilled';
+
+-- The foreign key is only checked for new rows here, and validated in a
+-- separate migration, so that the lock of the `ALTER TABLE` isn't held
+-- during the full table scan.
+ALTER TABLE dependencies
+    ADD CONSTRAINT fk_dependencies_requirement_id FOREIGN KEY (requirement_id)
+    REFERENCES dependency_requirements (id) NOT VALID;
+
+-- The requirements of the existing dependencies are interned after the
+-- deployment, first by inserting the distinct requirement strings, which
+-- only reads `dependencies`, and then by backfilling the references in
+-- batches:
+--
+--   INSERT INTO dependency_requirements (req)
+--   SELECT DISTINCT req FROM dependencies
+--   ON CONFLICT (req) DO NOTHING;
+--
+--   crates-admin enqueue-job batched_backfill --table dependencies \
+--     --set "requirement_id = (SELECT id FROM dependency_requirements WHERE dependency_requirements.req = dependencies.req)" \
+--     --where "requirement_id IS NULL"
+--
+-- The `req` column is still written and read until the backfill is done, and
+-- dropped in a follow-up once all readers join the requirements instead.
@@ -0,0 +1,4 @@
+-- Validating the constraint runs in its own transaction and only takes a
+-- `SHARE UPDATE EXCLUSIVE` lock, so versions can still be published during
+-- the full table scan.
+ALTER TABLE dependencies VALIDATE CONSTRAINT fk_dependencies_requirement_id;
@@ -0,0 +1 @@
+DROP TABLE versions_without_readme;
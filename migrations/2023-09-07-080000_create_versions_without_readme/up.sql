@@ -0,0 +1,7 @@
+CREATE TABLE versions_without_readme (
+  version_id INTEGER PRIMARY KEY REFERENCES versions (id) ON DELETE CASCADE,
+  checked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+COMMENT ON TABLE versions_without_readme IS 'Versions whose crate file was found to contain no README that could be rendered, so that the `repair_readmes` job does not download it again';
+COMMENT ON COLUMN versions_without_readme.checked_at IS 'Point in time at which the crate file was checked';
@@ -0,0 +1,216 @@
+//! Respond to compromised user accounts.
+//!
+//! Putting an account into compromise mode revokes all of its API tokens and
+//! browser sessions, holds new publishes from the account, adds its recently
+//! published versions to the moderation queue and notifies the user. All of
+//! this happens within a single database transaction. Until the compromise is
+//! resolved, the account can't log in, create API tokens or add owners.
+
+use crate::background_jobs::Job;
+use crate::models::{
+    AccountCompromise, NewAccountCompromise, NewAuditEvent, NewModerationFlag, User,
+};
+use crate::schema::{api_tokens, users, versions};
+use crate::{admin::dialoguer, db};
+use anyhow::{anyhow, Context};
+use chrono::{Duration, Utc};
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "account-compromise",
+    about = "Put user accounts into compromise mode, or resolve a compromise"
+)]
+pub enum Command {
+    /// Revoke all API tokens and sessions of the account, hold new publishes
+    /// from it, flag its recently published versions for review and notify
+    /// the user.
+    Start {
+        /// GitHub login of the compromised account
+        user: String,
+        /// GitHub login of the admin that the compromise is recorded for
+        #[arg(long)]
+        admin: String,
+        /// Reason for the compromise mode, as recorded in the audit trail
+        #[arg(long)]
+        reason: String,
+        /// Number of days to look back for versions published by the account
+        #[arg(long, default_value_t = 30)]
+        lookback_days: i64,
+        /// Don't ask for confirmation: yes, we are sure. Best for scripting.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Allow the account to publish again.
+    Resolve {
+        /// GitHub login of the account
+        user: String,
+        /// GitHub login of the admin that the resolution is recorded for
+        #[arg(long)]
+        admin: String,
+    },
+}
+
+pub fn run(command: Command) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    match command {
+        Command::Start {
+            user,
+            admin,
+            reason,
+            lookback_days,
+            yes,
+        } => {
+            let user = find_user_by_login(&user, conn)?;
+            let admin = find_user_by_login(&admin, conn)?;
+
+            let prompt = format!(
+                "Are you sure you want to put the account of {} ({}) into compromise mode?",
+                user.gh_login, user.id
+            );
+            if !yes && !dialoguer::confirm(&prompt) {
+                return Ok(());
+            }
+
+            let compromise = conn.transaction(|conn| {
+                start_compromise_mode(&user, &admin, &reason, lookback_days, conn)
+            })?;
+
+            println!(
+                "Revoked {} API tokens and flagged {} versions for review",
+                compromise.revoked_tokens, compromise.flagged_versions
+            );
+        }
+        Command::Resolve { user, admin } => {
+            let user = find_user_by_login(&user, conn)?;
+            let admin = find_user_by_login(&admin, conn)?;
+            resolve_compromise(&user, &admin, conn)?;
+
+            println!("Account of {} can publish again", user.gh_login);
+        }
+    }
+
+    Ok(())
+}
+
+fn find_user_by_login(login: &str, conn: &mut PgConnection) -> anyhow::Result<User> {
+    users::table
+        .filter(users::gh_login.eq(login))
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("user `{login}` not found"))
+}
+
+/// Puts the account of `user` into compromise mode.
+///
+/// This should be called within a transaction, so that either all or none of
+/// the steps are applied.
+pub fn start_compromise_mode(
+    user: &User,
+    admin: &User,
+    reason: &str,
+    lookback_days: i64,
+    conn: &mut PgConnection,
+) -> anyhow::Result<AccountCompromise> {
+    if AccountCompromise::is_active(user.id, conn)? {
+        return Err(anyhow!(
+            "account `{}` is already compromised",
+            user.gh_login
+        ));
+    }
+
+    let revoked_tokens = diesel::update(api_tokens::table)
+        .filter(api_tokens::user_id.eq(user.id))
+        .filter(api_tokens::revoked.eq(false))
+        .set(api_tokens::revoked.eq(true))
+        .execute(conn)?;
+
+    diesel::update(user)
+        .set(users::sessions_revoked_at.eq(now.nullable()))
+        .execute(conn)?;
+
+    let cutoff = (Utc::now() - Duration::days(lookback_days)).naive_utc();
+    let recent_versions: Vec<(i32, i32)> = versions::table
+        .filter(versions::published_by.eq(user.id))
+        .filter(versions::created_at.gt(cutoff))
+        .select((versions::id, versions::crate_id))
+        .load(conn)?;
+
+    for (version_id, crate_id) in &recent_versions {
+        NewModerationFlag {
+            crate_id: *crate_id,
+            version_id: Some(*version_id),
+            reason: "account_compromise",
+            details: json!({ "user": user.gh_login, "reason": reason }),
+        }
+        .insert(conn)?;
+    }
+
+    let compromise = NewAccountCompromise {
+        user_id: user.id,
+        admin_id: admin.id,
+        reason,
+        revoked_tokens: revoked_tokens as i32,
+        flagged_versions: recent_versions.len() as i32,
+    }
+    .insert(conn)?;
+
+    NewAuditEvent {
+        user_id: Some(admin.id),
+        details: json!({
+            "user": user.gh_login,
+            "reason": reason,
+            "revoked_tokens": compromise.revoked_tokens,
+            "flagged_versions": compromise.flagged_versions,
+        }),
+        ..NewAuditEvent::new("admin.account_compromise.start")
+    }
+    .insert(conn)?;
+
+    Job::notify_account_compromise(compromise.id).enqueue(conn)?;
+
+    info!(
+        user.id = user.id,
+        user.login = %user.gh_login,
+        admin.login = %admin.gh_login,
+        %reason,
+        revoked_tokens,
+        flagged_versions = recent_versions.len(),
+        "Put account into compromise mode"
+    );
+
+    Ok(compromise)
+}
+
+/// Resolves the active compromise of the account of `user`, which allows it
+/// to log in and publish again.
+///
+/// The revoked API tokens and sessions are not restored.
+pub fn resolve_compromise(
+    user: &User,
+    admin: &User,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    let compromise = AccountCompromise::active(user.id, conn)?
+        .ok_or_else(|| anyhow!("account `{}` is not compromised", user.gh_login))?;
+
+    compromise.resolve(conn)?;
+
+    NewAuditEvent {
+        user_id: Some(admin.id),
+        details: json!({ "user": user.gh_login }),
+        ..NewAuditEvent::new("admin.account_compromise.resolve")
+    }
+    .insert(conn)?;
+
+    info!(
+        user.id = user.id,
+        user.login = %user.gh_login,
+        admin.login = %admin.gh_login,
+        "Resolved account compromise"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,311 @@
+//! Find versions whose crate files are missing or empty in the storage, and
+//! optionally repair them.
+//!
+//! Missing crate files can be re-uploaded from a local mirror, either a cargo
+//! local registry (e.g. created by `export-bundle`) or a copy of the crate
+//! file storage. Mirrored files are only used if their checksum matches the
+//! one that was recorded when the version was published. Versions that can't
+//! be repaired can be marked as unavailable, so that their downloads fail
+//! with an explanation instead of a 404 response of the CDN.
+//!
+//! Archived crate files are not audited, since they are not served via the
+//! CDN.
+
+use crate::background_jobs::Job;
+use crate::models::{NewAuditEvent, UnavailableVersion};
+use crate::schema::{crates, unavailable_versions, version_archives, versions};
+use crate::storage::{ReplicatedFile, Storage};
+use crate::{admin::dialoguer, db};
+use anyhow::{anyhow, Context};
+use diesel::prelude::*;
+use hyper::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "audit-crate-files",
+    about = "Check that the crate files of all versions exist in the storage, and \
+        optionally repair them."
+)]
+pub struct Opts {
+    /// Only audit the versions of the crate with this name
+    #[arg(long = "crate")]
+    crate_name: Option<String>,
+
+    /// Path of the JSON report to write
+    #[arg(long)]
+    report: PathBuf,
+
+    /// Folder to re-upload missing or empty crate files from, containing
+    /// either `{name}-{version}.crate` or `crates/{name}/{name}-{version}.crate`
+    /// files
+    #[arg(long)]
+    mirror: Option<PathBuf>,
+
+    /// Mark versions as unavailable whose crate files could not be repaired
+    #[arg(long)]
+    mark_unavailable: bool,
+
+    /// Don't ask for confirmation: yes, we are sure. Best for scripting.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Debug, Queryable)]
+struct Candidate {
+    version_id: i32,
+    crate_name: String,
+    num: String,
+    checksum: String,
+    unavailable: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    checked: usize,
+    problems: Vec<Problem>,
+}
+
+#[derive(Debug, Serialize)]
+struct Problem {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    version: String,
+    /// `missing`, `empty` or `error`
+    problem: &'static str,
+    /// `reuploaded`, `marked_unavailable` or `none`
+    action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    version_id: i32,
+    #[serde(skip)]
+    checksum: String,
+    #[serde(skip)]
+    unavailable: bool,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let storage = Storage::from_environment();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let candidates = find_candidates(opts.crate_name.as_deref(), conn)?;
+    info!("Auditing the crate files of {} versions", candidates.len());
+
+    let mut report = Report {
+        checked: candidates.len(),
+        problems: Vec::new(),
+    };
+
+    for candidate in candidates {
+        let name = &candidate.crate_name;
+        let version = &candidate.num;
+        let (problem, error) = match rt.block_on(storage.crate_file_size(name, version)) {
+            Ok(Some(size)) if size > 0 => continue,
+            Ok(Some(_)) => ("empty", None),
+            Ok(None) => ("missing", None),
+            Err(error) => ("error", Some(error.to_string())),
+        };
+
+        warn!(%name, %version, problem, "Broken crate file");
+        report.problems.push(Problem {
+            crate_name: candidate.crate_name,
+            version: candidate.num,
+            problem,
+            action: "none",
+            error,
+            version_id: candidate.version_id,
+            checksum: candidate.checksum,
+            unavailable: candidate.unavailable,
+        });
+    }
+
+    println!(
+        "Found {} broken crate files in {} versions",
+        report.problems.len(),
+        report.checked
+    );
+
+    // Files that could not be checked may well exist, so they are neither
+    // overwritten nor marked as unavailable.
+    let repairable = report
+        .problems
+        .iter()
+        .filter(|problem| problem.problem != "error")
+        .count();
+
+    let repair = opts.mirror.is_some() || opts.mark_unavailable;
+    if repair && repairable > 0 {
+        let prompt = format!("Do you want to repair {repairable} crate files?");
+        if opts.yes || dialoguer::confirm(&prompt) {
+            let problems = report.problems.iter_mut();
+            for problem in problems.filter(|problem| problem.problem != "error") {
+                repair_crate_file(problem, &opts, &storage, &rt, conn)?;
+            }
+        }
+    }
+
+    let file = File::create(&opts.report)
+        .with_context(|| format!("Failed to create `{}`", opts.report.display()))?;
+    serde_json::to_writer_pretty(file, &report)?;
+    println!("Wrote the report to {}", opts.report.display());
+
+    Ok(())
+}
+
+fn find_candidates(
+    crate_name: Option<&str>,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<Candidate>> {
+    let mut query = versions::table
+        .inner_join(crates::table)
+        .left_join(version_archives::table)
+        .left_join(unavailable_versions::table)
+        .filter(version_archives::version_id.nullable().is_null())
+        .select((
+            versions::id,
+            crates::name,
+            versions::num,
+            versions::checksum,
+            unavailable_versions::version_id.nullable().is_not_null(),
+        ))
+        .order((crates::name, versions::id))
+        .into_boxed();
+
+    if let Some(crate_name) = crate_name {
+        query = query.filter(crates::name.eq(crate_name));
+    }
+
+    query.load(conn)
+}
+
+fn repair_crate_file(
+    problem: &mut Problem,
+    opts: &Opts,
+    storage: &Storage,
+    rt: &tokio::runtime::Runtime,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    let name = &problem.crate_name;
+    let version = &problem.version;
+
+    if let Some(mirror) = &opts.mirror {
+        match read_from_mirror(mirror, name, version, &problem.checksum) {
+            Ok(Some(bytes)) => {
+                info!(%name, %version, "Re-uploading crate file from the mirror");
+                rt.block_on(storage.upload_crate_file(name, version, bytes))?;
+                let file = ReplicatedFile::crate_file(name, version);
+                Job::enqueue_replication(storage, file, conn)?;
+                if problem.unavailable {
+                    UnavailableVersion::remove(problem.version_id, conn)?;
+                }
+
+                NewAuditEvent {
+                    details: json!({ "crate": name, "version": version }),
+                    ..NewAuditEvent::new("admin.reupload_crate_file")
+                }
+                .record(conn);
+
+                problem.action = "reuploaded";
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(error) => {
+                warn!(%name, %version, "{error:#}");
+                problem.error = Some(format!("{error:#}"));
+            }
+        }
+    }
+
+    if opts.mark_unavailable {
+        info!(%name, %version, "Marking version as unavailable");
+        UnavailableVersion::mark(problem.version_id, problem.problem, conn)?;
+
+        NewAuditEvent {
+            details: json!({ "crate": name, "version": version, "reason": problem.problem }),
+            ..NewAuditEvent::new("admin.mark_version_unavailable")
+        }
+        .record(conn);
+
+        problem.action = "marked_unavailable";
+    }
+
+    Ok(())
+}
+
+/// Reads the crate file of a version from the mirror, or returns `None` if
+/// the mirror doesn't contain it.
+fn read_from_mirror(
+    mirror: &Path,
+    name: &str,
+    version: &str,
+    checksum: &str,
+) -> anyhow::Result<Option<Bytes>> {
+    let file_name = format!("{name}-{version}.crate");
+    let paths = [
+        mirror.join(&file_name),
+        mirror.join("crates").join(name).join(&file_name),
+    ];
+
+    let Some(path) = paths.iter().find(|path| path.is_file()) else {
+        return Ok(None);
+    };
+
+    let bytes = fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    let actual = hex::encode(Sha256::digest(&bytes));
+    if actual != checksum {
+        return Err(anyhow!(
+            "checksum mismatch of `{}`: expected {checksum}, found {actual}",
+            path.display()
+        ));
+    }
+
+    Ok(Some(bytes.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_from_mirror;
+    use sha2::{Digest, Sha256};
+    use std::fs;
+
+    #[test]
+    fn mirror_layouts() {
+        let mirror = tempfile::tempdir().unwrap();
+        let mirror = mirror.path();
+        let checksum = hex::encode(Sha256::digest(b"foo"));
+
+        assert!(read_from_mirror(mirror, "foo", "1.0.0", &checksum)
+            .unwrap()
+            .is_none());
+
+        // cargo local registry
+        fs::write(mirror.join("foo-1.0.0.crate"), b"foo").unwrap();
+        let bytes = read_from_mirror(mirror, "foo", "1.0.0", &checksum).unwrap();
+        assert_eq!(bytes.unwrap().as_ref(), b"foo");
+
+        // copy of the crate file storage
+        fs::create_dir_all(mirror.join("crates/bar")).unwrap();
+        fs::write(mirror.join("crates/bar/bar-1.0.0.crate"), b"foo").unwrap();
+        let bytes = read_from_mirror(mirror, "bar", "1.0.0", &checksum).unwrap();
+        assert_eq!(bytes.unwrap().as_ref(), b"foo");
+    }
+
+    #[test]
+    fn mirror_checksum_mismatch() {
+        let mirror = tempfile::tempdir().unwrap();
+        let mirror = mirror.path();
+        fs::write(mirror.join("foo-1.0.0.crate"), b"bar").unwrap();
+
+        let checksum = hex::encode(Sha256::digest(b"foo"));
+        let error = read_from_mirror(mirror, "foo", "1.0.0", &checksum).unwrap_err();
+        assert!(error.to_string().starts_with("checksum mismatch"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Fill in the tarball metadata of versions that were published before it was
+//! recorded on publish.
+//!
+//! The crate files of these versions are downloaded from the storage and
+//! processed leniently, since older versions of crates.io accepted crate files
+//! that would be rejected on publish today. Only missing values are written, so values that
+//! were recorded on publish are never overwritten. Crate files whose checksum
+//! doesn't match the one in the database are skipped.
+
+use crate::db;
+use crate::models::VersionFingerprint;
+use crate::schema::{crates, version_fingerprints, versions};
+use crate::storage::Storage;
+use anyhow::{anyhow, Context};
+use crates_io_tarball::{process_tarball_lenient, TarballInfo};
+use diesel::prelude::*;
+use std::ops::Deref;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "backfill-tarball-info",
+    about = "Download the crate files of versions with missing tarball metadata, \
+        and fill in the metadata from their contents.",
+    after_help = "Warning: this can take a lot of time."
+)]
+pub struct Opts {
+    /// Only backfill the versions of the crate with this name
+    #[arg(long = "crate")]
+    crate_name: Option<String>,
+
+    /// Only backfill versions with this ID or a higher one, e.g. to resume an
+    /// interrupted run
+    #[arg(long)]
+    since_version_id: Option<i32>,
+
+    /// How many versions should be queried and processed at a time
+    #[arg(long, default_value = "100")]
+    page_size: i64,
+
+    /// How many crate files should be downloaded per second at most
+    #[arg(long, default_value = "10")]
+    max_per_second: u32,
+
+    /// Only print the changes, without writing them to the database
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Queryable)]
+struct Candidate {
+    id: i32,
+    crate_id: i32,
+    crate_name: String,
+    num: String,
+    checksum: String,
+    crate_size: Option<i32>,
+    rust_version: Option<String>,
+    commit_sha: Option<String>,
+    has_fingerprint: bool,
+}
+
+/// The columns of a version that are filled in, where `None` means that the
+/// column is left unchanged.
+#[derive(Debug, Default, PartialEq, Eq, AsChangeset)]
+#[diesel(table_name = versions)]
+struct Changes {
+    crate_size: Option<i32>,
+    rust_version: Option<String>,
+    commit_sha: Option<String>,
+    dirty_worktree: Option<bool>,
+}
+
+impl Changes {
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let storage = Storage::from_environment();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let period = Duration::from_secs(1) / opts.max_per_second.max(1);
+    let mut interval = rt.block_on(async { tokio::time::interval(period) });
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut last_version_id = None;
+    let mut updated = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+
+    loop {
+        let candidates = find_candidates(&opts, last_version_id, conn)?;
+        let Some(last_id) = candidates.last().map(|candidate| candidate.id) else {
+            break;
+        };
+        last_version_id = Some(last_id);
+
+        for candidate in candidates {
+            let name = &candidate.crate_name;
+            let version = &candidate.num;
+
+            rt.block_on(interval.tick());
+            let (size, tarball_info) = match rt.block_on(read_tarball(&storage, name, version)) {
+                Ok(result) => result,
+                Err(error) => {
+                    warn!(%name, %version, "Failed to process crate file: {error:#}");
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            let checksum = hex::encode(tarball_info.tarball_checksum);
+            if checksum != candidate.checksum {
+                warn!(%name, %version, %checksum, "Checksum mismatch, skipping version");
+                skipped += 1;
+                continue;
+            }
+
+            let changes = changes(&candidate, size, &tarball_info)?;
+
+            if opts.dry_run {
+                info!(%name, %version, ?changes, "Would backfill tarball metadata");
+                updated += 1;
+                continue;
+            }
+
+            conn.transaction(|conn| {
+                if !changes.is_empty() {
+                    diesel::update(versions::table.find(candidate.id))
+                        .set(&changes)
+                        .execute(conn)?;
+                }
+
+                if !candidate.has_fingerprint {
+                    let hashes = &tarball_info.fingerprint.hashes;
+                    VersionFingerprint::record(candidate.id, candidate.crate_id, hashes, conn)?;
+                }
+
+                Ok::<_, diesel::result::Error>(())
+            })?;
+
+            info!(%name, %version, "Backfilled tarball metadata");
+            updated += 1;
+        }
+
+        println!(
+            "Processed versions up to ID {last_id}: {updated} updated, {skipped} skipped, {failed} failed"
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the next page of versions after `last_version_id` that lack a
+/// fingerprint or a crate size, ordered by ID.
+fn find_candidates(
+    opts: &Opts,
+    last_version_id: Option<i32>,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<Candidate>> {
+    let mut query = versions::table
+        .inner_join(crates::table)
+        .left_join(version_fingerprints::table)
+        .filter(
+            version_fingerprints::version_id
+                .is_null()
+                .or(versions::crate_size.is_null()),
+        )
+        .select((
+            versions::id,
+            versions::crate_id,
+            crates::name,
+            versions::num,
+            versions::checksum,
+            versions::crate_size,
+            versions::rust_version,
+            versions::commit_sha,
+            version_fingerprints::version_id.nullable().is_not_null(),
+        ))
+        .order(versions::id)
+        .limit(opts.page_size)
+        .into_boxed();
+
+    if let Some(since_version_id) = opts.since_version_id {
+        query = query.filter(versions::id.ge(since_version_id));
+    }
+
+    if let Some(last_version_id) = last_version_id {
+        query = query.filter(versions::id.gt(last_version_id));
+    }
+
+    if let Some(crate_name) = &opts.crate_name {
+        query = query.filter(crates::name.eq(crate_name));
+    }
+
+    query.load(conn)
+}
+
+/// Downloads a crate file from the storage and processes it. Returns the size
+/// of the crate file along with the tarball metadata.
+async fn read_tarball(
+    storage: &Storage,
+    name: &str,
+    version: &str,
+) -> anyhow::Result<(usize, TarballInfo)> {
+    let bytes = storage
+        .read_crate_file(name, version)
+        .await
+        .context("Failed to fetch crate file")?;
+
+    // The crate file was accepted on publish already, so neither the limits
+    // nor the checks of the current configuration apply.
+    let pkg_name = format!("{name}-{version}");
+    let size = bytes.len();
+    let tarball_info = tokio::task::spawn_blocking(move || {
+        process_tarball_lenient(&pkg_name, &*bytes).map_err(|error| anyhow!("{error}"))
+    })
+    .await??;
+
+    Ok((size, tarball_info))
+}
+
+fn changes(
+    candidate: &Candidate,
+    size: usize,
+    tarball_info: &TarballInfo,
+) -> anyhow::Result<Changes> {
+    let mut changes = Changes::default();
+
+    if candidate.crate_size.is_none() {
+        changes.crate_size = Some(size.try_into().context("Crate file is too large")?);
+    }
+
+    if candidate.rust_version.is_none() {
+        changes.rust_version = tarball_info
+            .manifest
+            .as_ref()
+            .and_then(|manifest| manifest.package.rust_version.as_ref())
+            .map(|rv| rv.deref().to_string());
+    }
+
+    if candidate.commit_sha.is_none() {
+        let vcs_info = tarball_info.vcs_info.as_ref();
+        changes.commit_sha = vcs_info
+            .and_then(|info| info.commit_sha())
+            .map(String::from);
+        if changes.commit_sha.is_some() {
+            changes.dirty_worktree = vcs_info.map(|info| info.is_dirty());
+        }
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crates_io_tarball::TarballBuilder;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn legacy_crate_files_are_backfilled() {
+        // Older versions of crates.io accepted `/` in license expressions and
+        // invalid lockfiles, which are rejected on publish today.
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_raw_manifest(
+                b"[package]\nname = \"foo\"\nversion = \"0.1.0\"\n\
+                  license = \"MIT/Apache-2.0\"\nrust-version = \"1.56\"\n",
+            )
+            .add_file(
+                "foo-0.1.0/.cargo_vcs_info.json",
+                format!(r#"{{"git": {{"sha1": "{SHA}"}}}}"#).as_bytes(),
+            )
+            .add_file("foo-0.1.0/Cargo.lock", b"invalid")
+            .add_file("foo-0.1.0/src/lib.rs", b"pub fn foo() {}")
+            .build();
+
+        let tarball_info = assert_ok!(process_tarball_lenient("foo-0.1.0", &*tarball));
+        assert!(!tarball_info.fingerprint.hashes.is_empty());
+
+        let candidate = Candidate {
+            id: 1,
+            crate_id: 1,
+            crate_name: "foo".into(),
+            num: "0.1.0".into(),
+            checksum: hex::encode(tarball_info.tarball_checksum),
+            crate_size: None,
+            rust_version: None,
+            commit_sha: None,
+            has_fingerprint: false,
+        };
+
+        let changes = assert_ok!(changes(&candidate, tarball.len(), &tarball_info));
+        assert_eq!(
+            changes,
+            Changes {
+                crate_size: Some(tarball.len() as i32),
+                rust_version: Some("1.56".into()),
+                commit_sha: Some(SHA.into()),
+                dirty_worktree: Some(false),
+            }
+        );
+    }
+}
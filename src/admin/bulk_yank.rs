@@ -0,0 +1,215 @@
+use crate::background_jobs::Job;
+use crate::models::{insert_version_owner_action, NewAuditEvent, VersionAction};
+use crate::schema::{crates, users, versions};
+use crate::sql::canon_crate_name;
+use crate::{admin::dialoguer, db};
+use anyhow::{anyhow, Context};
+use diesel::prelude::*;
+use semver::VersionReq;
+use std::collections::BTreeSet;
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "bulk-yank",
+    about = "Yank all versions matching the given filters from the database and index.",
+    after_help = "At least one of the filters is required. All given filters have to \
+        match for a version to be yanked."
+)]
+#[command(group(
+    clap::ArgGroup::new("filters")
+        .required(true)
+        .multiple(true)
+        .args(["crate_pattern", "versions", "published_by"])
+))]
+pub struct Opts {
+    /// Only yank versions of crates with a matching name. `*` matches any
+    /// number of characters and `?` matches a single character. Like crate
+    /// names themselves, the pattern is case-insensitive and does not
+    /// distinguish between `-` and `_`.
+    #[arg(long, value_name = "PATTERN")]
+    crate_pattern: Option<String>,
+
+    /// Only yank versions that match this version requirement, e.g. `>=1.2.0, <1.3.0`.
+    #[arg(long, value_name = "REQ")]
+    versions: Option<VersionReq>,
+
+    /// Only yank versions that were published by this GitHub login.
+    #[arg(long, value_name = "LOGIN")]
+    published_by: Option<String>,
+
+    /// GitHub login of the admin account that the yanks are recorded for in
+    /// the audit log of the versions.
+    #[arg(long, value_name = "LOGIN")]
+    admin: String,
+
+    /// Don't ask for confirmation: yes, we are sure. Best for scripting.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Debug, Queryable)]
+struct Candidate {
+    version_id: i32,
+    crate_name: String,
+    num: String,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let admin_id = find_user_id(&opts.admin, conn)?;
+
+    let candidates = find_candidates(&opts, conn)?;
+    if candidates.is_empty() {
+        println!("No unyanked versions match the given filters");
+        return Ok(());
+    }
+
+    println!("Yanking the following versions:");
+    println!();
+    for candidate in &candidates {
+        println!(" - {}@{}", candidate.crate_name, candidate.num);
+    }
+    println!();
+
+    let prompt = format!(
+        "Are you sure you want to yank these {} versions?",
+        candidates.len()
+    );
+    if !opts.yes && !dialoguer::confirm(&prompt) {
+        return Ok(());
+    }
+
+    let num_yanked = conn.transaction(|conn| {
+        let mut num_yanked = 0;
+        for candidate in &candidates {
+            let updated = diesel::update(versions::table.find(candidate.version_id))
+                .filter(versions::yanked.eq(false))
+                .set(versions::yanked.eq(true))
+                .execute(conn)?;
+
+            // Skip versions that were yanked by their owners in the meantime
+            if updated > 0 {
+                num_yanked += 1;
+                insert_version_owner_action(
+                    conn,
+                    candidate.version_id,
+                    admin_id,
+                    None,
+                    VersionAction::Yank,
+                )?;
+            }
+        }
+
+        // The index files contain all versions of a crate, so they only need
+        // to be synced once per crate.
+        let crate_names = candidates
+            .iter()
+            .map(|candidate| candidate.crate_name.as_str())
+            .collect::<BTreeSet<_>>();
+
+        for crate_name in crate_names {
+            info!(%crate_name, "Enqueuing index sync jobs");
+            Job::enqueue_sync_to_index(crate_name, conn)?;
+        }
+
+        let versions = candidates
+            .iter()
+            .map(|candidate| format!("{}@{}", candidate.crate_name, candidate.num))
+            .collect::<Vec<_>>();
+
+        NewAuditEvent {
+            user_id: Some(admin_id),
+            details: json!({ "versions": versions }),
+            ..NewAuditEvent::new("admin.bulk_yank")
+        }
+        .insert(conn)?;
+
+        Ok::<_, anyhow::Error>(num_yanked)
+    })?;
+
+    println!("Yanked {num_yanked} versions");
+
+    Ok(())
+}
+
+fn find_user_id(gh_login: &str, conn: &mut PgConnection) -> anyhow::Result<i32> {
+    users::table
+        .filter(users::gh_login.eq(gh_login))
+        .select(users::id)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("User `{gh_login}` not found"))
+}
+
+fn find_candidates(opts: &Opts, conn: &mut PgConnection) -> anyhow::Result<Vec<Candidate>> {
+    let mut query = versions::table
+        .inner_join(crates::table)
+        .filter(versions::yanked.eq(false))
+        .select((versions::id, crates::name, versions::num))
+        .order((crates::name, versions::id))
+        .into_boxed();
+
+    if let Some(pattern) = &opts.crate_pattern {
+        query = query.filter(canon_crate_name(crates::name).like(glob_to_like_pattern(pattern)));
+    }
+
+    if let Some(gh_login) = &opts.published_by {
+        let user_id = find_user_id(gh_login, conn)?;
+        query = query.filter(versions::published_by.eq(user_id));
+    }
+
+    let candidates: Vec<Candidate> = query.load(conn)?;
+
+    let Some(version_req) = &opts.versions else {
+        return Ok(candidates);
+    };
+
+    Ok(candidates
+        .into_iter()
+        .filter(|candidate| {
+            semver::Version::parse(&candidate.num)
+                .map(|version| version_req.matches(&version))
+                .unwrap_or(false)
+        })
+        .collect())
+}
+
+/// Converts a glob pattern into a pattern for the SQL `LIKE` operator, that
+/// matches the `canon_crate_name()` of the crate names.
+///
+/// The pattern is canonicalized in here rather than with `canon_crate_name()`
+/// in SQL, because the `_` that it produces would be a wildcard for `LIKE`.
+fn glob_to_like_pattern(glob: &str) -> String {
+    let mut pattern = String::with_capacity(glob.len());
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push('%'),
+            '?' => pattern.push('_'),
+            '-' | '_' => pattern.push_str("\\_"),
+            '%' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c => pattern.extend(c.to_lowercase()),
+        }
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_to_like_pattern;
+
+    #[test]
+    fn test_glob_to_like_pattern() {
+        assert_eq!(glob_to_like_pattern("foo"), "foo");
+        assert_eq!(glob_to_like_pattern("Foo"), "foo");
+        assert_eq!(glob_to_like_pattern("foo*"), "foo%");
+        assert_eq!(glob_to_like_pattern("*-sys"), "%\\_sys");
+        assert_eq!(glob_to_like_pattern("foo?"), "foo_");
+        assert_eq!(glob_to_like_pattern("foo-*"), "foo\\_%");
+        assert_eq!(glob_to_like_pattern("foo_bar*"), "foo\\_bar%");
+        assert_eq!(glob_to_like_pattern("100%"), "100\\%");
+    }
+}
@@ -0,0 +1,812 @@
+//! Check pending migrations for patterns that are unsafe to run against a
+//! busy production database.
+//!
+//! The checks are based on the size of the tables in the target database, so
+//! that migrations of small tables are not held to the same standard as
+//! migrations of large tables like `versions` or `version_downloads`. The
+//! command is meant to run before a deploy, against the database that the
+//! migrations are about to be applied to.
+//!
+//! See the "Database" section of `docs/BACKEND.md` for the safe alternatives
+//! to the patterns that are reported here.
+
+use crate::db;
+use anyhow::{anyhow, Context};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use diesel_migrations::MigrationHarness;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "check-migrations",
+    about = "Check pending database migrations for unsafe patterns."
+)]
+pub struct Opts {
+    /// Directory that contains the migrations
+    #[arg(long, default_value = "migrations")]
+    path: PathBuf,
+
+    /// Tables with at least this many rows are considered large
+    #[arg(long, default_value_t = 100_000)]
+    large_table_rows: i64,
+
+    /// Also check the migrations that were already applied
+    #[arg(long)]
+    all: bool,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let applied = conn
+        .applied_migrations()
+        .map_err(|error| anyhow!("Failed to load the applied migrations: {error}"))?
+        .into_iter()
+        .map(|version| version.to_string())
+        .collect::<HashSet<_>>();
+
+    let tables = TableSizes::load(conn, opts.large_table_rows)?;
+
+    let mut migrations = fs::read_dir(&opts.path)
+        .with_context(|| format!("Failed to read {}", opts.path.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join("up.sql").exists())
+        .collect::<Vec<_>>();
+    migrations.sort();
+
+    let mut checked = 0;
+    let mut errors = 0;
+    for path in migrations {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !opts.all && applied.contains(&migration_version(&name)) {
+            continue;
+        }
+
+        let sql = fs::read_to_string(path.join("up.sql"))?;
+        let in_transaction = runs_in_transaction(&path)?;
+
+        checked += 1;
+        for finding in analyze(&sql, in_transaction, &tables) {
+            if finding.severity == Severity::Error {
+                errors += 1;
+            }
+            println!("{name}: {finding}");
+        }
+    }
+
+    println!("Checked {checked} migrations");
+
+    if errors > 0 {
+        return Err(anyhow!(
+            "Found {errors} unsafe patterns in pending migrations"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the version that diesel records for the migration directory, e.g.
+/// `20230817080000` for `2023-08-17-080000_add_foo`.
+fn migration_version(name: &str) -> String {
+    let version = name.split('_').next().unwrap_or(name);
+    version.replace('-', "")
+}
+
+/// Returns whether diesel runs the migration inside of a transaction, which
+/// is the default unless the `metadata.toml` file of the migration disables
+/// it.
+fn runs_in_transaction(path: &Path) -> anyhow::Result<bool> {
+    #[derive(Deserialize)]
+    struct Metadata {
+        #[serde(default = "default_run_in_transaction")]
+        run_in_transaction: bool,
+    }
+
+    fn default_run_in_transaction() -> bool {
+        true
+    }
+
+    let path = path.join("metadata.toml");
+    if !path.exists() {
+        return Ok(true);
+    }
+
+    let metadata = fs::read_to_string(&path)?;
+    let metadata: Metadata =
+        toml::from_str(&metadata).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    Ok(metadata.run_in_transaction)
+}
+
+/// Tables that are large in production. They are considered large regardless
+/// of the estimate, so that the checks also apply to a fresh database, like
+/// the one that the tests run against.
+const LARGE_TABLES: &[&str] = &[
+    "api_tokens",
+    "crate_owners",
+    "crates",
+    "crates_categories",
+    "crates_keywords",
+    "dependencies",
+    "emails",
+    "follows",
+    "readme_renderings",
+    "users",
+    "version_downloads",
+    "version_owner_actions",
+    "versions",
+];
+
+/// The estimated number of rows of the tables in the database.
+#[derive(Debug, Default)]
+pub struct TableSizes {
+    rows: HashMap<String, i64>,
+    large_table_rows: i64,
+}
+
+impl TableSizes {
+    fn load(conn: &mut PgConnection, large_table_rows: i64) -> anyhow::Result<Self> {
+        #[derive(QueryableByName)]
+        struct Table {
+            #[diesel(sql_type = Text)]
+            name: String,
+            #[diesel(sql_type = BigInt)]
+            rows: i64,
+        }
+
+        // `reltuples` is an estimate that is updated by `VACUUM` and
+        // `ANALYZE`, which is good enough to tell small and large tables
+        // apart without scanning them.
+        let tables: Vec<Table> = diesel::sql_query(
+            "SELECT c.relname::TEXT AS name, GREATEST(c.reltuples, 0)::BIGINT AS rows \
+            FROM pg_class c \
+            JOIN pg_namespace n ON n.oid = c.relnamespace \
+            WHERE n.nspname = 'public' AND c.relkind IN ('r', 'p')",
+        )
+        .load(conn)
+        .context("Failed to load the table sizes")?;
+
+        let rows = tables
+            .into_iter()
+            .map(|table| (table.name, table.rows))
+            .collect();
+
+        Ok(Self {
+            rows,
+            large_table_rows,
+        })
+    }
+
+    fn is_large(&self, table: &str) -> bool {
+        LARGE_TABLES.contains(&table) || self.rows(table) >= self.large_table_rows
+    }
+
+    fn is_empty(&self, table: &str) -> bool {
+        !LARGE_TABLES.contains(&table) && self.rows(table) == 0
+    }
+
+    fn rows(&self, table: &str) -> i64 {
+        self.rows.get(table).copied().unwrap_or_default()
+    }
+}
+
+/// The tables of the database, as seen by a statement of a migration that
+/// created or renamed some of them in its earlier statements.
+struct MigrationTables<'a> {
+    tables: &'a TableSizes,
+    /// Tables that were created or renamed by the migration, mapped to the
+    /// table of the database whose rows they contain, if any.
+    changed: HashMap<String, Option<String>>,
+}
+
+impl<'a> MigrationTables<'a> {
+    fn new(tables: &'a TableSizes) -> Self {
+        let changed = HashMap::new();
+        Self { tables, changed }
+    }
+
+    fn resolve<'b>(&'b self, table: &'b str) -> Option<&'b str> {
+        let table = table_name(table);
+        match self.changed.get(table) {
+            Some(original) => original.as_deref(),
+            None => Some(table),
+        }
+    }
+
+    fn is_large(&self, table: &str) -> bool {
+        let table = self.resolve(table);
+        table.map_or(false, |table| self.tables.is_large(table))
+    }
+
+    fn is_empty(&self, table: &str) -> bool {
+        let table = self.resolve(table);
+        table.map_or(true, |table| self.tables.is_empty(table))
+    }
+
+    fn create(&mut self, table: &str) {
+        self.changed.insert(table_name(table).to_string(), None);
+    }
+
+    fn rename(&mut self, from: &str, to: &str) {
+        let original = self.resolve(from).map(String::from);
+        self.create(from);
+        self.changed.insert(table_name(to).to_string(), original);
+    }
+}
+
+fn table_name(table: &str) -> &str {
+    let table = table.trim_matches('"');
+    table.strip_prefix("public.").unwrap_or(table)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(message: impl Into<String>) -> Self {
+        let severity = Severity::Error;
+        let message = message.into();
+        Self { severity, message }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        let severity = Severity::Warning;
+        let message = message.into();
+        Self { severity, message }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// Functions that make a column default volatile, which forces Postgres to
+/// rewrite the whole table when the column is added.
+const VOLATILE_DEFAULTS: &[&str] = &[
+    " random (",
+    " clock_timestamp (",
+    " gen_random_uuid (",
+    " uuid_generate_v4 (",
+];
+
+/// Checks the statements of a migration for patterns that hold long locks on
+/// large tables, or that fail on tables with existing rows.
+pub fn analyze(sql: &str, in_transaction: bool, tables: &TableSizes) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut lock_timeout = false;
+    let mut not_valid = false;
+    let mut tables = MigrationTables::new(tables);
+
+    let statements = statements(sql);
+    if !in_transaction
+        && statements.len() > 1
+        && statements.iter().any(|s| s.contains(" concurrently "))
+    {
+        findings.push(Finding::error(
+            "diesel sends the whole migration as a single query, which Postgres runs in \
+            a transaction if it contains more than one statement; put the statement \
+            that runs `CONCURRENTLY` into a migration of its own",
+        ));
+    }
+
+    for statement in statements {
+        let tokens = statement.split_whitespace().collect::<Vec<_>>();
+        let has = |phrase: &str| statement.contains(phrase);
+
+        match tokens.as_slice() {
+            ["set", "lock_timeout", ..] | ["set", "local", "lock_timeout", ..] => {
+                lock_timeout = true;
+            }
+            ["create", "table", rest @ ..] => {
+                let table = rest
+                    .iter()
+                    .find(|token| !matches!(**token, "if" | "not" | "exists"));
+                if let Some(table) = table {
+                    tables.create(table);
+                }
+            }
+            ["create", "index", ..] | ["create", "unique", "index", ..] => {
+                let Some(table) = token_after(&tokens, "on") else {
+                    continue;
+                };
+
+                let concurrently = tokens.contains(&"concurrently");
+                if concurrently && in_transaction {
+                    findings.push(Finding::error(format!(
+                        "`CREATE INDEX CONCURRENTLY` on `{table}` can't run inside a \
+                        transaction; add a `metadata.toml` file with \
+                        `run_in_transaction = false` to the migration"
+                    )));
+                } else if !concurrently && tables.is_large(table) {
+                    findings.push(Finding::error(format!(
+                        "`CREATE INDEX` on the large table `{table}` blocks writes until the \
+                        index is built; use `CREATE INDEX CONCURRENTLY` instead"
+                    )));
+                }
+            }
+            ["alter", "table", rest @ ..] => {
+                let Some(table) = rest
+                    .iter()
+                    .find(|token| !matches!(**token, "if" | "exists" | "only"))
+                else {
+                    continue;
+                };
+
+                if let Some(position) = tokens.windows(2).position(|w| w == ["rename", "to"]) {
+                    if let Some(new_name) = tokens.get(position + 2) {
+                        tables.rename(table, new_name);
+                    }
+                }
+
+                if has(" not valid") {
+                    not_valid = true;
+                }
+
+                if has(" validate constraint ") && not_valid {
+                    findings.push(Finding::error(format!(
+                        "validating a constraint of `{table}` in the migration that adds it \
+                        holds the lock of the `ALTER TABLE` during the full table scan; \
+                        validate it in a separate migration"
+                    )));
+                }
+
+                let adds_constraint = has(" foreign key ") || has(" references ") || has(" check ");
+                if adds_constraint && !has(" not valid") && !tables.is_empty(table) {
+                    findings.push(Finding::error(format!(
+                        "adding a constraint to `{table}` validates all existing rows while \
+                        holding a lock; add it with `NOT VALID` and run `VALIDATE CONSTRAINT` \
+                        in a separate migration"
+                    )));
+                }
+
+                if has(" add column ")
+                    && has(" not null")
+                    && !has(" default ")
+                    && !tables.is_empty(table)
+                {
+                    findings.push(Finding::error(format!(
+                        "adding a `NOT NULL` column without a default fails for the existing \
+                        rows of `{table}`; add a default, or add the column as nullable and \
+                        backfill it"
+                    )));
+                }
+
+                let volatile_default = VOLATILE_DEFAULTS.iter().any(|f| has(f));
+                if has(" add column ") && volatile_default && tables.is_large(table) {
+                    findings.push(Finding::error(format!(
+                        "adding a column with a volatile default rewrites the large table \
+                        `{table}`; add the column without a default and backfill it"
+                    )));
+                }
+
+                if has(" type ") && has(" alter column ") && tables.is_large(table) {
+                    findings.push(Finding::error(format!(
+                        "changing the type of a column rewrites the large table `{table}` \
+                        while holding an exclusive lock; add a new column and backfill it \
+                        instead"
+                    )));
+                }
+
+                if has(" set not null") && tables.is_large(table) {
+                    findings.push(Finding::warning(format!(
+                        "`SET NOT NULL` scans the large table `{table}` while holding an \
+                        exclusive lock; validate a `CHECK (column IS NOT NULL) NOT VALID` \
+                        constraint first"
+                    )));
+                }
+
+                if tables.is_large(table) && !lock_timeout {
+                    findings.push(Finding::warning(format!(
+                        "`ALTER TABLE` on the large table `{table}` should be preceded by \
+                        `SET lock_timeout`, so that it fails instead of blocking all other \
+                        queries while it waits for its lock"
+                    )));
+                }
+            }
+            ["update", table, ..] | ["delete", "from", table, ..] if tables.is_large(table) => {
+                findings.push(Finding::error(format!(
+                    "backfilling the large table `{table}` in a migration holds row locks \
+                    until the deploy finishes; use the `batched_backfill` background job \
+                    instead"
+                )));
+            }
+            ["lock", ..] => {
+                findings.push(Finding::warning(
+                    "explicit `LOCK TABLE` statements block other queries until the \
+                    migration finishes",
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    findings
+}
+
+/// Returns the statements that the migration runs, including the statements
+/// in the body of `DO` blocks and the dynamic SQL that they `EXECUTE`.
+fn statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    for statement in split_statements(sql) {
+        let tokens = statement.split_whitespace().collect::<Vec<_>>();
+        let tokens = strip_control_flow(&tokens);
+        match tokens {
+            [] => {}
+            ["execute", ..] => {
+                if let Some(sql) = string_literal(&statement) {
+                    statements.extend(split_statements(&sql));
+                }
+            }
+            tokens => statements.push(format!(" {} ", tokens.join(" "))),
+        }
+    }
+    statements
+}
+
+/// Removes the PL/pgSQL control flow from the start of a statement in a `DO`
+/// block, e.g. `BEGIN` or `IF ... THEN`, leaving the SQL statement that it
+/// runs.
+fn strip_control_flow<'a, 'b>(mut tokens: &'b [&'a str]) -> &'b [&'a str] {
+    loop {
+        tokens = match tokens {
+            ["begin" | "declare" | "else" | "loop", rest @ ..] => rest,
+            ["if" | "elsif" | "for" | "foreach" | "while", rest @ ..] => {
+                match rest.iter().position(|t| matches!(*t, "then" | "loop")) {
+                    Some(position) => &rest[position + 1..],
+                    None => return &[],
+                }
+            }
+            tokens => return tokens,
+        };
+    }
+}
+
+/// Returns the content of the first string literal in the statement.
+fn string_literal(statement: &str) -> Option<String> {
+    let (_, rest) = statement.split_once('\'')?;
+    let mut content = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            if chars.peek() != Some(&'\'') {
+                return Some(content);
+            }
+            chars.next();
+        }
+        content.push(c);
+    }
+    None
+}
+
+fn token_after<'a>(tokens: &[&'a str], keyword: &str) -> Option<&'a str> {
+    let position = tokens.iter().position(|token| *token == keyword)?;
+    tokens[position + 1..]
+        .iter()
+        .find(|token| **token != "only")
+        .copied()
+}
+
+/// Splits the SQL into statements, with comments removed, whitespace
+/// collapsed, parentheses and commas separated by spaces, and everything
+/// outside of string literals converted to lowercase.
+fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut in_do_block = false;
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                current.push(' ');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                current.push(' ');
+            }
+            '\'' => {
+                current.push(c);
+                for c in chars.by_ref() {
+                    current.push(c);
+                    if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'$') && in_do_block => {
+                chars.next();
+                in_do_block = false;
+                current.clear();
+            }
+            // The statements in the body of a `DO` block are run by the
+            // migration, so they are split like the statements outside of it.
+            '$' if chars.peek() == Some(&'$') && current.trim() == "do" => {
+                chars.next();
+                in_do_block = true;
+                current.clear();
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                chars.next();
+                current.push_str("$$");
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    current.push(c);
+                    if previous == '$' && c == '$' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            ';' => statements.push(std::mem::take(&mut current)),
+            '(' | ')' | ',' => {
+                current.push(' ');
+                current.push(c);
+                current.push(' ');
+            }
+            c => current.extend(c.to_lowercase()),
+        }
+    }
+    statements.push(current);
+
+    statements
+        .into_iter()
+        .map(|statement| {
+            let statement = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+            // Pad the statement, so that phrases can be matched with
+            // surrounding spaces at the start and end of the statement too.
+            format!(" {statement} ")
+        })
+        .filter(|statement| !statement.trim().is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The older migrations were written before these checks existed.
+    const CHECKED_SINCE: &str = "2023-08-01";
+
+    fn tables() -> TableSizes {
+        let rows = [("versions", 1_000_000), ("teams", 100), ("empty", 0)];
+        let rows = rows
+            .into_iter()
+            .map(|(name, rows)| (name.to_string(), rows))
+            .collect();
+
+        TableSizes {
+            rows,
+            large_table_rows: 100_000,
+        }
+    }
+
+    fn errors(sql: &str) -> Vec<String> {
+        analyze(sql, true, &tables())
+            .into_iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    #[test]
+    fn migration_version() {
+        assert_eq!(
+            super::migration_version("2023-08-17-080000_add_foo"),
+            "20230817080000"
+        );
+        assert_eq!(
+            super::migration_version("20170305095748_create_foo"),
+            "20170305095748"
+        );
+    }
+
+    #[test]
+    fn split_statements() {
+        let sql = "-- A comment; with a semicolon\n\
+            CREATE INDEX foo ON Versions (crate_id);\n\
+            /* another; comment */ COMMENT ON TABLE foo IS 'Text; with a semicolon';";
+
+        assert_eq!(
+            super::split_statements(sql),
+            [
+                " create index foo on versions ( crate_id ) ",
+                " comment on table foo is 'Text; with a semicolon' ",
+            ]
+        );
+    }
+
+    #[test]
+    fn create_index() {
+        assert_eq!(errors("CREATE INDEX foo ON teams (login);").len(), 0);
+        assert_eq!(errors("CREATE INDEX foo ON versions (num);").len(), 1);
+        assert_eq!(
+            errors("CREATE UNIQUE INDEX foo ON versions (num);").len(),
+            1
+        );
+
+        let sql = "CREATE INDEX CONCURRENTLY foo ON versions (num);";
+        assert_eq!(errors(sql).len(), 1);
+        assert_eq!(analyze(sql, false, &tables()), []);
+    }
+
+    #[test]
+    fn add_constraint() {
+        let sql =
+            "ALTER TABLE versions ADD CONSTRAINT fk FOREIGN KEY (crate_id) REFERENCES crates;";
+        assert_eq!(errors(sql).len(), 1);
+
+        let sql = "ALTER TABLE versions ADD CONSTRAINT fk FOREIGN KEY (crate_id) REFERENCES crates NOT VALID;";
+        assert_eq!(errors(sql).len(), 0);
+
+        let sql = "ALTER TABLE empty ADD CONSTRAINT c CHECK (id > 0);";
+        assert_eq!(errors(sql).len(), 0);
+    }
+
+    #[test]
+    fn add_column() {
+        assert_eq!(
+            errors("ALTER TABLE teams ADD COLUMN foo INT NOT NULL;").len(),
+            1
+        );
+        assert_eq!(
+            errors("ALTER TABLE empty ADD COLUMN foo INT NOT NULL;").len(),
+            0
+        );
+
+        let sql = "ALTER TABLE versions ADD COLUMN foo INT NOT NULL DEFAULT 0;";
+        assert_eq!(errors(sql).len(), 0);
+
+        let sql = "ALTER TABLE versions ADD COLUMN foo UUID NOT NULL DEFAULT gen_random_uuid();";
+        assert_eq!(errors(sql).len(), 1);
+    }
+
+    #[test]
+    fn alter_column() {
+        let sql = "ALTER TABLE versions ALTER COLUMN num TYPE TEXT;";
+        assert_eq!(errors(sql).len(), 1);
+
+        let sql = "SET lock_timeout = '1s'; ALTER TABLE versions ALTER COLUMN num SET NOT NULL;";
+        assert_eq!(
+            analyze(sql, true, &tables()),
+            [Finding::warning(
+                "`SET NOT NULL` scans the large table `versions` while holding an \
+                exclusive lock; validate a `CHECK (column IS NOT NULL) NOT VALID` \
+                constraint first"
+            )]
+        );
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let sql = "ALTER TABLE versions DROP COLUMN foo;";
+        let findings = analyze(sql, true, &tables());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+
+        let sql = "SET lock_timeout = '1s'; ALTER TABLE versions DROP COLUMN foo;";
+        assert_eq!(analyze(sql, true, &tables()), []);
+    }
+
+    #[test]
+    fn backfills() {
+        assert_eq!(errors("UPDATE versions SET foo = 1;").len(), 1);
+        assert_eq!(errors("DELETE FROM versions WHERE foo = 1;").len(), 1);
+        assert_eq!(errors("UPDATE teams SET foo = 1;").len(), 0);
+    }
+
+    #[test]
+    fn do_blocks() {
+        let sql = "DO $$\n\
+            BEGIN\n\
+              -- Only if it's missing\n\
+              IF NOT EXISTS (SELECT 1 FROM versions WHERE foo) THEN\n\
+                UPDATE versions SET foo = 1;\n\
+              END IF;\n\
+            END\n\
+            $$;";
+        assert_eq!(errors(sql).len(), 1);
+
+        let sql = "DO $$ BEGIN EXECUTE 'CREATE INDEX foo ON versions (num) WHERE num <> ''1.0.0'''; END $$;";
+        assert_eq!(errors(sql).len(), 1);
+
+        let sql = "CREATE FUNCTION foo() RETURNS trigger AS $$ BEGIN UPDATE versions SET foo = 1; END $$ LANGUAGE plpgsql;";
+        assert_eq!(errors(sql).len(), 0);
+    }
+
+    #[test]
+    fn validate_constraint() {
+        let sql = "ALTER TABLE versions VALIDATE CONSTRAINT c;";
+        assert_eq!(errors(sql).len(), 0);
+
+        let sql = "ALTER TABLE versions ADD CONSTRAINT c CHECK (id > 0) NOT VALID;\n\
+            ALTER TABLE versions VALIDATE CONSTRAINT c;";
+        assert_eq!(errors(sql).len(), 1);
+    }
+
+    #[test]
+    fn concurrently_with_other_statements() {
+        let sql = "SET lock_timeout = '1s'; CREATE INDEX CONCURRENTLY foo ON versions (num);";
+        assert_eq!(analyze(sql, false, &tables()).len(), 1);
+
+        let sql = "-- A comment\nDROP INDEX CONCURRENTLY foo;";
+        assert_eq!(analyze(sql, false, &tables()), []);
+    }
+
+    #[test]
+    fn created_and_renamed_tables() {
+        let sql = "ALTER TABLE versions RENAME TO versions_legacy;\n\
+            CREATE TABLE versions (id INTEGER NOT NULL);\n\
+            CREATE INDEX foo ON versions (id);\n\
+            CREATE INDEX bar ON versions_legacy (id);";
+        let errors = errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("`versions_legacy`"));
+    }
+
+    /// Runs the checks over the migrations of the repository, with only the
+    /// `LARGE_TABLES` considered large.
+    #[test]
+    fn migrations() {
+        let tables = TableSizes {
+            rows: HashMap::new(),
+            large_table_rows: 100_000,
+        };
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let mut migrations = fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.join("up.sql").exists())
+            .collect::<Vec<_>>();
+        migrations.sort();
+
+        let mut errors = Vec::new();
+        for path in migrations {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            if name.as_str() < CHECKED_SINCE {
+                continue;
+            }
+
+            let sql = fs::read_to_string(path.join("up.sql")).unwrap();
+            let in_transaction = runs_in_transaction(&path).unwrap();
+            for finding in analyze(&sql, in_transaction, &tables) {
+                if finding.severity == Severity::Error {
+                    errors.push(format!("{name}: {finding}"));
+                }
+            }
+        }
+
+        assert_eq!(errors, Vec::<String>::new());
+    }
+}
@@ -1,9 +1,16 @@
 use crate::background_jobs::Job;
-use crate::storage::Storage;
-use crate::{admin::dialoguer, db, schema::crates};
+use crate::models::{NewAuditEvent, Tombstone};
+use crate::storage::{CrateObjectKind, ReplicatedFile, Storage};
+use crate::worker::fastly::Fastly;
+use crate::{
+    admin::dialoguer,
+    db,
+    schema::{crates, versions},
+};
 use anyhow::Context;
 use diesel::prelude::*;
-use std::collections::HashMap;
+use reqwest::blocking::Client;
+use std::collections::{BTreeSet, HashMap};
 
 #[derive(clap::Parser, Debug)]
 #[command(
@@ -16,6 +23,10 @@ pub struct Opts {
     #[arg(value_name = "NAME", required = true)]
     crate_names: Vec<String>,
 
+    /// Only print the crates and files that would be deleted
+    #[arg(long)]
+    dry_run: bool,
+
     /// Don't ask for confirmation: yes, we are sure. Best for scripting.
     #[arg(short, long)]
     yes: bool,
@@ -40,41 +51,118 @@ pub fn run(opts: Opts) {
 
     let existing_crates: HashMap<String, i32> = existing_crates.into_iter().collect();
 
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")
+        .unwrap();
+
+    // The versions are looked up in the storage as well, so that the files of
+    // versions that were already deleted from the database are purged from
+    // the CDN caches too.
+    let mut crate_versions = HashMap::new();
+    let mut crate_objects = HashMap::new();
+    for name in &crate_names {
+        let mut version_nums = BTreeSet::new();
+        if let Some(id) = existing_crates.get(name) {
+            let nums: Vec<String> = versions::table
+                .filter(versions::crate_id.eq(id))
+                .select(versions::num)
+                .load(conn)
+                .context("Failed to look up versions from the database")
+                .unwrap();
+            version_nums.extend(nums);
+        }
+
+        let objects = match rt.block_on(store.list_all_for_crate(name)) {
+            Ok(objects) => objects,
+            Err(error) => {
+                warn!(%name, ?error, "Failed to list files of crate");
+                Vec::new()
+            }
+        };
+        version_nums.extend(objects.iter().filter_map(|object| match &object.kind {
+            CrateObjectKind::Version(version) => Some(version.clone()),
+            _ => None,
+        }));
+
+        crate_versions.insert(name, version_nums);
+        crate_objects.insert(name, objects);
+    }
+
     println!("Deleting the following crates:");
     println!();
     for name in &crate_names {
+        let num_versions = crate_versions[name].len();
         match existing_crates.get(name) {
-            Some(id) => println!(" - {name} (id={id})"),
-            None => println!(" - {name} (⚠️ crate not found)"),
+            Some(id) => println!(" - {name} (id={id}, {num_versions} versions)"),
+            None => println!(" - {name} (⚠️ crate not found, {num_versions} versions in storage)"),
         }
     }
     println!();
 
+    if opts.dry_run {
+        // Blobs of deduplicated crate files are only deleted if no other
+        // crate references them, which is only known during the deletion.
+        println!("The following files would be deleted:");
+        println!();
+        for name in &crate_names {
+            for object in &crate_objects[name] {
+                println!(" - {}", object.path);
+            }
+        }
+        println!();
+        println!("Dry run, no crates have been deleted");
+        return;
+    }
+
     if !opts.yes && !dialoguer::confirm("Do you want to permanently delete these crates?") {
         return;
     }
 
-    let rt = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .context("Failed to initialize tokio runtime")
-        .unwrap();
+    let fastly = Fastly::from_environment();
+    let client = Client::new();
 
     for name in &crate_names {
         if let Some(id) = existing_crates.get(name) {
             info!(%name, "Deleting crate from the database");
-            if let Err(error) = diesel::delete(crates::table.find(id)).execute(conn) {
+            let result = conn.transaction(|conn| {
+                diesel::delete(crates::table.find(id)).execute(conn)?;
+                Tombstone::record(name, None, conn)?;
+
+                NewAuditEvent {
+                    details: json!({
+                        "crate": name,
+                        "crate_id": id,
+                        "versions": crate_versions[name],
+                    }),
+                    ..NewAuditEvent::new("admin.delete_crate")
+                }
+                .insert(conn)?;
+
+                info!(%name, "Enqueuing index sync jobs");
+                Job::enqueue_sync_to_index(name, conn)?;
+
+                Ok::<_, anyhow::Error>(())
+            });
+
+            // The files are kept if the crate still exists, since they are
+            // served for its versions.
+            if let Err(error) = result {
                 warn!(%name, %id, ?error, "Failed to delete crate from the database");
+                continue;
             }
         } else {
             info!(%name, "Skipping missing crate");
-        };
 
-        info!(%name, "Enqueuing index sync jobs");
-        if let Err(error) = Job::enqueue_sync_to_index(name, conn) {
-            warn!(%name, ?error, "Failed to enqueue index sync jobs");
-        }
+            info!(%name, "Enqueuing index sync jobs");
+            if let Err(error) = Job::enqueue_sync_to_index(name, conn) {
+                warn!(%name, ?error, "Failed to enqueue index sync jobs");
+            }
+        };
 
+        // This includes the manifests of deduplicated crate files, and the
+        // blobs that are not referenced by other crates anymore.
         info!(%name, "Deleting crate files from S3");
         if let Err(error) = rt.block_on(store.delete_all_crate_files(name)) {
             warn!(%name, ?error, "Failed to delete crate files from S3");
@@ -84,5 +172,32 @@ pub fn run(opts: Opts) {
         if let Err(error) = rt.block_on(store.delete_all_readmes(name)) {
             warn!(%name, ?error, "Failed to delete readme files from S3");
         }
+
+        info!(%name, "Deleting license texts from S3");
+        if let Err(error) = rt.block_on(store.delete_all_license_texts(name)) {
+            warn!(%name, ?error, "Failed to delete license texts from S3");
+        }
+
+        info!(%name, "Enqueuing replication jobs");
+        for version in &crate_versions[name] {
+            let crate_file = ReplicatedFile::crate_file(name, version);
+            let readme = ReplicatedFile::readme(name, version);
+            for file in [crate_file, readme] {
+                if let Err(error) = Job::enqueue_replication(&store, file, conn) {
+                    warn!(%name, %version, ?error, "Failed to enqueue replication job");
+                }
+            }
+        }
+
+        if let Some(fastly) = &fastly {
+            info!(%name, "Invalidating files on Fastly");
+            for version in &crate_versions[name] {
+                for path in store.cdn_paths(name, version) {
+                    if let Err(error) = fastly.invalidate(&client, &path) {
+                        warn!(%name, %path, ?error, "Failed to invalidate file on Fastly");
+                    }
+                }
+            }
+        }
     }
 }
@@ -1,6 +1,7 @@
 use crate::background_jobs::Job;
+use crate::models::{NewAuditEvent, Tombstone};
 use crate::schema::crates;
-use crate::storage::Storage;
+use crate::storage::{ReplicatedFile, Storage};
 use crate::{admin::dialoguer, db, schema::versions};
 use anyhow::Context;
 use diesel::prelude::*;
@@ -57,22 +58,37 @@ pub fn run(opts: Opts) {
             .filter(versions::crate_id.eq(crate_id))
             .filter(versions::num.eq_any(&opts.versions)),
     )
-    .execute(conn);
+    .returning(versions::num)
+    .get_results::<String>(conn);
 
     match result {
-        Ok(num_deleted) if num_deleted == opts.versions.len() => {}
-        Ok(num_deleted) => {
-            warn!(
-                %crate_name,
-                "Deleted only {num_deleted} of {num_expected} versions from the database",
-                num_expected = opts.versions.len()
-            );
+        Ok(deleted) => {
+            if deleted.len() != opts.versions.len() {
+                warn!(
+                    %crate_name,
+                    "Deleted only {num_deleted} of {num_expected} versions from the database",
+                    num_deleted = deleted.len(),
+                    num_expected = opts.versions.len()
+                );
+            }
+
+            for version in &deleted {
+                if let Err(error) = Tombstone::record(crate_name, Some(version), conn) {
+                    warn!(%crate_name, %version, ?error, "Failed to record tombstone of version");
+                }
+            }
         }
         Err(error) => {
             warn!(%crate_name, ?error, "Failed to delete versions from the database")
         }
     }
 
+    NewAuditEvent {
+        details: json!({ "crate": crate_name, "versions": opts.versions }),
+        ..NewAuditEvent::new("admin.delete_version")
+    }
+    .record(conn);
+
     info!(%crate_name, "Enqueuing index sync jobs");
     if let Err(error) = Job::enqueue_sync_to_index(crate_name, conn) {
         warn!(%crate_name, ?error, "Failed to enqueue index sync jobs");
@@ -98,5 +114,18 @@ pub fn run(opts: Opts) {
             }
             Ok(_) => {}
         }
+
+        debug!(%crate_name, %version, "Deleting license texts from S3");
+        if let Err(error) = rt.block_on(store.delete_license_texts(crate_name, version)) {
+            warn!(%crate_name, %version, ?error, "Failed to delete license texts from S3");
+        }
+
+        let crate_file = ReplicatedFile::crate_file(crate_name, version);
+        let readme = ReplicatedFile::readme(crate_name, version);
+        for file in [crate_file, readme] {
+            if let Err(error) = Job::enqueue_replication(&store, file, conn) {
+                warn!(%crate_name, %version, ?error, "Failed to enqueue replication job");
+            }
+        }
     }
 }
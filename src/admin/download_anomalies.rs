@@ -0,0 +1,45 @@
+use crate::db;
+use crate::models::DownloadAnomaly;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "download-anomalies",
+    about = "List crates that were downloaded unusually often."
+)]
+pub struct Opts {
+    /// Number of days to look back for download anomalies
+    #[arg(long, default_value_t = 7)]
+    days: i64,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let since = Utc::now().date_naive() - Duration::days(opts.days);
+    let anomalies = DownloadAnomaly::since(since, conn)?;
+
+    if anomalies.is_empty() {
+        println!("No download anomalies since {since}");
+        return Ok(());
+    }
+
+    println!(
+        "{:<10}  {:<40}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "date", "crate", "downloads", "baseline", "stddev", "excluded"
+    );
+    for (anomaly, crate_name) in anomalies {
+        println!(
+            "{:<10}  {:<40}  {:>12}  {:>12.1}  {:>12.1}  {:>12}",
+            anomaly.date,
+            crate_name,
+            anomaly.downloads,
+            anomaly.baseline_mean,
+            anomaly.baseline_stddev,
+            anomaly.excluded_downloads,
+        );
+    }
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use crate::background_jobs::Job;
+use crate::config::DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS;
 use crate::db;
 use crate::schema::background_jobs::dsl::*;
+use crate::schema::{crates, versions};
 use anyhow::Result;
 use diesel::prelude::*;
 use secrecy::{ExposeSecret, SecretString};
@@ -12,7 +14,87 @@ use secrecy::{ExposeSecret, SecretString};
     rename_all = "snake_case"
 )]
 pub enum Command {
+    /// Record how many dependent crates request each feature of a crate, and
+    /// prune old snapshots
+    AggregateFeatureUsage {
+        /// Number of days after which snapshots are pruned
+        #[arg(long, default_value_t = 365)]
+        retention_days: i32,
+    },
+    /// Record which `rust-version` recently published versions declare
+    AggregateMsrvStats {
+        /// Number of days to look back for published versions
+        #[arg(long, default_value_t = 90)]
+        days: i32,
+    },
+    /// Move crate files of dead crates to the archive storage tier
+    ArchiveVersions {
+        /// Minimum number of days without any downloads
+        #[arg(long, default_value_t = 730)]
+        idle_days: i32,
+        /// Maximum number of versions to archive
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+    },
+    /// Update the rows of a large table in small batches, e.g. to backfill a
+    /// new column outside of a migration
+    BatchedBackfill {
+        /// Name of the table
+        #[arg(long)]
+        table: String,
+        /// Assignments of the `UPDATE` statement, e.g. `foo = bar`
+        #[arg(long)]
+        set: String,
+        /// Condition that matches the rows that still need to be updated,
+        /// e.g. `foo IS NULL`
+        #[arg(long = "where")]
+        filter: String,
+        /// Number of rows that are updated per batch
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+        /// Number of batches before the backfill continues in a new job
+        #[arg(long, default_value_t = 100)]
+        max_batches: i64,
+    },
+    /// Check whether the repositories that crates link to were archived,
+    /// deleted or moved
+    CheckRepositories {
+        /// Minimum number of days between two checks of the same repository
+        #[arg(long, default_value_t = 30)]
+        recheck_days: i32,
+        /// Maximum number of repositories to check, which should stay below
+        /// the rate limit of the GitHub API
+        #[arg(long, default_value_t = 50)]
+        batch_size: i64,
+    },
+    /// Render the readmes of recently published versions whose rendered
+    /// readme is missing
+    RepairReadmes {
+        /// Number of days to look back for published versions
+        #[arg(long, default_value_t = 7)]
+        lookback_days: i32,
+        /// Maximum number of renders to enqueue
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+    },
     UpdateDownloads,
+    /// Record crates that were downloaded unusually often yesterday
+    DetectDownloadAnomalies {
+        /// Number of days before yesterday that the downloads are compared with
+        #[arg(long, default_value_t = 28)]
+        baseline_days: i32,
+        /// Minimum number of downloads for a crate to be considered
+        #[arg(long, default_value_t = 10_000)]
+        min_downloads: i64,
+        /// Number of standard deviations above the baseline from which on
+        /// downloads are considered anomalous
+        #[arg(long, default_value_t = 6.0)]
+        threshold: f64,
+        /// Subtract the downloads above the baseline from the public download
+        /// counts of the crate
+        #[arg(long)]
+        exclude: bool,
+    },
     DumpDb {
         #[arg(env = "READ_ONLY_REPLICA_URL")]
         database_url: SecretString,
@@ -20,11 +102,98 @@ pub enum Command {
         target_name: String,
     },
     DailyDbMaintenance,
+    ExpireOwnershipInvitations {
+        #[arg(long, default_value_t = DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS)]
+        expiration_days: u64,
+    },
+    /// Delete the files of expired publish dry runs
+    ExpirePublishDryRuns,
+    /// Ship recorded audit events to the configured audit sink, and prune
+    /// old events
+    ExportAuditEvents {
+        /// Number of events that are sent to the sink at once
+        #[arg(long, default_value_t = 500)]
+        batch_size: i64,
+        /// Maximum number of batches to send
+        #[arg(long, default_value_t = 20)]
+        max_batches: i64,
+        /// Number of days after which events are pruned
+        #[arg(long, default_value_t = 90)]
+        retention_days: i32,
+    },
+    /// Create the upcoming monthly partitions of the `version_downloads`
+    /// table, and archive and detach the partitions of old months
+    ManageDownloadsPartitions {
+        /// Number of months after the current one to create partitions for
+        #[arg(long, default_value_t = 3)]
+        months_ahead: u32,
+        /// Number of months before the current one whose partitions are kept
+        #[arg(long, default_value_t = 6)]
+        retention_months: u32,
+        /// Only report the partitions that would be created or archived
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Flag popular crates that are owned by a single user, and ask their
+    /// owners to add another owner
+    NudgeSingleOwnerCrates {
+        /// Minimum number of downloads of a crate to be flagged
+        #[arg(long, default_value_t = 1_000_000)]
+        min_downloads: i32,
+        /// Minimum number of days before the owner is reminded again by email
+        #[arg(long, default_value_t = 180)]
+        reminder_days: i32,
+        /// Send reminder emails in addition to flagging the crates on the
+        /// dashboard of their owners
+        #[arg(long = "email")]
+        send_emails: bool,
+    },
+    /// Send the email digests of keyword and category subscriptions that are
+    /// due
+    SendSubscriptionDigests {
+        /// Maximum number of releases that are listed in a digest
+        #[arg(long, default_value_t = 50)]
+        max_entries: i64,
+    },
     SquashIndex,
+    /// Delete files in the storage that don't belong to any crate or version
+    /// in the database, like the readmes of deleted crates
+    SweepOrphanedFiles {
+        /// Only sweep the files of this crate
+        #[arg(long)]
+        crate_name: Option<String>,
+        /// Minimum number of hours since a file was last modified
+        #[arg(long, default_value_t = 6)]
+        min_age_hours: i64,
+        /// Only report orphaned files instead of deleting them
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
     NormalizeIndex {
         #[arg(long = "dry-run")]
         dry_run: bool,
     },
+    /// Compare the SHA256 checksums of all stored crate files with the
+    /// checksums recorded in the database, and mark the versions of
+    /// mismatching crate files as unavailable
+    VerifyChecksums {
+        /// Only verify the crate files of this crate
+        #[arg(long)]
+        crate_name: Option<String>,
+        /// Only verify the crate files of versions with a higher ID, to
+        /// resume an earlier verification
+        #[arg(long, default_value_t = 0)]
+        after_version_id: i32,
+        /// Number of crate files to verify per job
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+    },
+    VerifyReproducibility {
+        /// Name of the crate
+        name: String,
+        /// Version number to verify
+        version: String,
+    },
 }
 
 pub fn run(command: Command) -> Result<()> {
@@ -32,6 +201,32 @@ pub fn run(command: Command) -> Result<()> {
     println!("Enqueueing background job: {command:?}");
 
     match command {
+        Command::AggregateFeatureUsage { retention_days } => {
+            Ok(Job::aggregate_feature_usage(retention_days).enqueue(conn)?)
+        }
+        Command::AggregateMsrvStats { days } => Ok(Job::aggregate_msrv_stats(days).enqueue(conn)?),
+        Command::ArchiveVersions {
+            idle_days,
+            batch_size,
+        } => Ok(Job::archive_versions(idle_days, batch_size).enqueue(conn)?),
+        Command::BatchedBackfill {
+            table,
+            set,
+            filter,
+            batch_size,
+            max_batches,
+        } => {
+            let job = Job::batched_backfill(table, set, filter, batch_size, max_batches);
+            Ok(job.enqueue(conn)?)
+        }
+        Command::CheckRepositories {
+            recheck_days,
+            batch_size,
+        } => Ok(Job::check_repositories(recheck_days, batch_size).enqueue(conn)?),
+        Command::RepairReadmes {
+            lookback_days,
+            batch_size,
+        } => Ok(Job::repair_readmes(lookback_days, batch_size).enqueue(conn)?),
         Command::UpdateDownloads => {
             let count: i64 = background_jobs
                 .filter(job_type.eq("update_downloads"))
@@ -46,12 +241,79 @@ pub fn run(command: Command) -> Result<()> {
                 Ok(Job::update_downloads().enqueue(conn)?)
             }
         }
+        Command::DetectDownloadAnomalies {
+            baseline_days,
+            min_downloads,
+            threshold,
+            exclude,
+        } => {
+            let job =
+                Job::detect_download_anomalies(baseline_days, min_downloads, threshold, exclude);
+            Ok(job.enqueue(conn)?)
+        }
         Command::DumpDb {
             database_url,
             target_name,
         } => Ok(Job::dump_db(database_url.expose_secret().to_string(), target_name).enqueue(conn)?),
         Command::DailyDbMaintenance => Ok(Job::daily_db_maintenance().enqueue(conn)?),
+        Command::ExpireOwnershipInvitations { expiration_days } => {
+            Ok(Job::expire_ownership_invitations(expiration_days).enqueue(conn)?)
+        }
+        Command::ExpirePublishDryRuns => Ok(Job::expire_publish_dry_runs().enqueue(conn)?),
+        Command::ExportAuditEvents {
+            batch_size,
+            max_batches,
+            retention_days,
+        } => {
+            let job = Job::export_audit_events(batch_size, max_batches, retention_days);
+            Ok(job.enqueue(conn)?)
+        }
+        Command::ManageDownloadsPartitions {
+            months_ahead,
+            retention_months,
+            dry_run,
+        } => {
+            let job = Job::manage_downloads_partitions(months_ahead, retention_months, dry_run);
+            Ok(job.enqueue(conn)?)
+        }
+        Command::NudgeSingleOwnerCrates {
+            min_downloads,
+            reminder_days,
+            send_emails,
+        } => {
+            let job = Job::nudge_single_owner_crates(min_downloads, reminder_days, send_emails);
+            Ok(job.enqueue(conn)?)
+        }
+        Command::SendSubscriptionDigests { max_entries } => {
+            Ok(Job::send_subscription_digests(max_entries).enqueue(conn)?)
+        }
         Command::SquashIndex => Ok(Job::squash_index().enqueue(conn)?),
+        Command::SweepOrphanedFiles {
+            crate_name,
+            min_age_hours,
+            dry_run,
+        } => {
+            let job = Job::sweep_orphaned_files(crate_name, min_age_hours, dry_run);
+            Ok(job.enqueue(conn)?)
+        }
         Command::NormalizeIndex { dry_run } => Ok(Job::normalize_index(dry_run).enqueue(conn)?),
+        Command::VerifyChecksums {
+            crate_name,
+            after_version_id,
+            batch_size,
+        } => {
+            let job = Job::verify_checksums(crate_name, after_version_id, batch_size);
+            Ok(job.enqueue(conn)?)
+        }
+        Command::VerifyReproducibility { name, version } => {
+            let version_id: i32 = versions::table
+                .inner_join(crates::table)
+                .filter(crates::name.eq(&name))
+                .filter(versions::num.eq(&version))
+                .select(versions::id)
+                .first(conn)?;
+
+            Ok(Job::verify_reproducibility(version_id).enqueue(conn)?)
+        }
     }
 }
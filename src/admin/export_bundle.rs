@@ -0,0 +1,251 @@
+//! Export a self-contained registry bundle for offline use.
+//!
+//! The bundle contains the index files and crate files of the selected
+//! crates, and of all versions of their transitive dependencies that match
+//! the version requirements. Dev-dependencies are not followed, since they
+//! are not needed to build a crate as a dependency.
+//!
+//! The bundle uses the layout of a cargo local registry, so it can be used
+//! with source replacement:
+//!
+//! ```toml
+//! [source.crates-io]
+//! replace-with = "offline"
+//!
+//! [source.offline]
+//! local-registry = "/path/to/bundle"
+//! ```
+//!
+//! If a `--base-url` is given, the `index` folder also gets a `config.json`
+//! file, so that the bundle can be served as a sparse registry from that URL.
+
+use crate::models::Crate;
+use crate::schema::crates;
+use crate::storage::Storage;
+use crate::{admin::dialoguer, db};
+use anyhow::{anyhow, bail, Context};
+use crates_io_index::{DependencyKind, Repository};
+use diesel::prelude::*;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use semver::VersionReq;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "export-bundle",
+    about = "Export the index and crate files of a set of crates and their dependencies \
+        for offline use."
+)]
+pub struct Opts {
+    /// Names of the crates to export, or `all` to export all crates
+    #[arg(
+        long = "crates",
+        value_name = "NAME",
+        value_delimiter = ',',
+        required = true
+    )]
+    crate_names: Vec<String>,
+
+    /// Folder to write the bundle to, which must not exist yet
+    #[arg(long)]
+    output: PathBuf,
+
+    /// URL that the bundle will be served from, which is used for the `dl`
+    /// URL in the `config.json` file of the index
+    #[arg(long)]
+    base_url: Option<String>,
+
+    /// Pack the bundle into a `.tar.gz` file next to the output folder, and
+    /// remove the folder afterwards
+    #[arg(long)]
+    tarball: bool,
+
+    /// Don't ask for confirmation: yes, we are sure. Best for scripting.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    if opts.output.exists() {
+        bail!("output folder `{}` already exists", opts.output.display());
+    }
+
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let roots = if opts.crate_names.iter().any(|name| name == "all") {
+        crates::table.select(crates::name).load(conn)?
+    } else {
+        opts.crate_names
+    };
+
+    let mut index = IndexCache::default();
+    let selection = select_versions(&roots, &mut index, conn)?;
+
+    let num_versions = selection.values().map(BTreeSet::len).sum::<usize>();
+    println!(
+        "Exporting {num_versions} versions of {} crates to {}",
+        selection.len(),
+        opts.output.display()
+    );
+
+    if !opts.yes && !dialoguer::confirm("Do you want to continue?") {
+        return Ok(());
+    }
+
+    let storage = Storage::from_environment();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let index_dir = opts.output.join("index");
+    fs::create_dir_all(&index_dir)?;
+
+    for (name, versions) in &selection {
+        let records = index
+            .remove(name)
+            .into_iter()
+            .filter(|record| versions.contains(&record.vers))
+            .collect::<Vec<_>>();
+
+        let path = index_dir.join(Repository::relative_index_file(name));
+        fs::create_dir_all(path.parent().unwrap())?;
+        let file = File::create(&path)?;
+        crates_io_index::write_crates(&records, file)
+            .with_context(|| format!("Failed to write index file of `{name}`"))?;
+
+        for record in &records {
+            let bytes = rt
+                .block_on(storage.read_crate_file(name, &record.vers))
+                .with_context(|| format!("Failed to download `{name}@{}`", record.vers))?;
+
+            let cksum = hex::encode(Sha256::digest(&bytes));
+            if cksum != record.cksum {
+                bail!("checksum mismatch of `{name}@{}`", record.vers);
+            }
+
+            let path = opts.output.join(format!("{name}-{}.crate", record.vers));
+            fs::write(path, bytes)?;
+        }
+
+        println!("Exported {} versions of `{name}`", records.len());
+    }
+
+    if let Some(base_url) = &opts.base_url {
+        let base_url = base_url.trim_end_matches('/');
+        let config = json!({
+            "dl": format!("{base_url}/{{crate}}-{{version}}.crate"),
+            "api": null,
+        });
+        fs::write(index_dir.join("config.json"), config.to_string())?;
+    }
+
+    if opts.tarball {
+        let path = create_tarball(&opts.output)?;
+        fs::remove_dir_all(&opts.output)?;
+        println!("Wrote {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// The index records of the crates that were looked at so far.
+#[derive(Default)]
+struct IndexCache(BTreeMap<String, Vec<crates_io_index::Crate>>);
+
+impl IndexCache {
+    fn get(
+        &mut self,
+        name: &str,
+        conn: &mut PgConnection,
+    ) -> anyhow::Result<&[crates_io_index::Crate]> {
+        if !self.0.contains_key(name) {
+            let krate: Crate = Crate::by_exact_name(name)
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| anyhow!("crate `{name}` not found"))?;
+
+            self.0.insert(name.to_string(), krate.index_metadata(conn)?);
+        }
+
+        Ok(&self.0[name])
+    }
+
+    fn remove(&mut self, name: &str) -> Vec<crates_io_index::Crate> {
+        self.0.remove(name).unwrap_or_default()
+    }
+}
+
+/// Selects all versions of the `roots` crates, and all versions of their
+/// transitive dependencies that match the version requirements.
+fn select_versions(
+    roots: &[String],
+    index: &mut IndexCache,
+    conn: &mut PgConnection,
+) -> anyhow::Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut queue = VecDeque::new();
+    for name in roots {
+        for record in index.get(name, conn)? {
+            queue.push_back((name.clone(), record.vers.clone()));
+        }
+    }
+
+    let mut selection: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    while let Some((name, vers)) = queue.pop_front() {
+        if !selection
+            .entry(name.clone())
+            .or_default()
+            .insert(vers.clone())
+        {
+            continue;
+        }
+
+        let dependencies = index
+            .get(&name, conn)?
+            .iter()
+            .find(|record| record.vers == vers)
+            .into_iter()
+            .flat_map(|record| &record.deps)
+            .filter(|dep| dep.registry.is_none() && dep.kind != Some(DependencyKind::Dev))
+            .map(|dep| {
+                let name = dep.package.as_ref().unwrap_or(&dep.name);
+                (name.clone(), dep.req.clone())
+            })
+            .collect::<Vec<_>>();
+
+        for (dep_name, req) in dependencies {
+            let req = VersionReq::parse(&req)
+                .with_context(|| format!("Invalid requirement `{req}` in `{name}@{vers}`"))?;
+
+            for record in index.get(&dep_name, conn)? {
+                let matches = semver::Version::parse(&record.vers)
+                    .map_or(false, |version| req.matches(&version));
+
+                if matches {
+                    queue.push_back((dep_name.clone(), record.vers.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(selection)
+}
+
+/// Packs the bundle folder into a `.tar.gz` file next to it.
+fn create_tarball(dir: &Path) -> anyhow::Result<PathBuf> {
+    let path = dir.with_extension("tar.gz");
+    let file = File::create(&path)?;
+
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let name = dir
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid output folder `{}`", dir.display()))?;
+    archive.append_dir_all(name, dir)?;
+    archive.into_inner()?.finish()?;
+
+    Ok(path)
+}
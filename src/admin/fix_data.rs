@@ -0,0 +1,448 @@
+//! Targeted data fixes for common support tasks.
+//!
+//! All commands run within a single database transaction and record an audit
+//! event for every change they make. With `--dry-run` the transaction is
+//! rolled back at the end, so the changes can be reviewed without applying
+//! them.
+
+use crate::background_jobs::Job;
+use crate::db;
+use crate::models::{Crate, NewAuditEvent, OwnerKind, User, Version};
+use crate::schema::{
+    account_compromises, api_tokens, audit_events, category_subscriptions, crate_owner_invitations,
+    crate_owners, digest_preferences, emails, follows, keyword_subscriptions, publish_diagnostics,
+    publish_limit_buckets, publish_rate_overrides, repository_verifications, single_owner_nudges,
+    users, version_owner_actions, versions,
+};
+use anyhow::{anyhow, Context};
+use diesel::dsl::exists;
+use diesel::prelude::*;
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "fix-data",
+    about = "Apply targeted data fixes for common support tasks"
+)]
+pub enum Command {
+    /// Transfer the ownership of a single crate from one user to another.
+    TransferCrateOwner {
+        /// Name of the crate
+        crate_name: String,
+        /// GitHub login of the current owner
+        from_user: String,
+        /// GitHub login of the new owner
+        to_user: String,
+        /// Roll back all changes instead of committing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Set the yanked state of a version and sync it to the index.
+    FixVersionYankState {
+        /// Name of the crate
+        crate_name: String,
+        /// Version number that should be fixed
+        version: String,
+        /// The yanked state that the version should have
+        #[arg(long, action = clap::ArgAction::Set)]
+        yanked: bool,
+        /// Roll back all changes instead of committing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move all data of a duplicate user account to another account of the
+    /// same GitHub user and delete the duplicate.
+    MergeDuplicateUsers {
+        /// ID of the duplicate user that will be deleted
+        from_user_id: i32,
+        /// ID of the user that will be kept
+        into_user_id: i32,
+        /// Roll back all changes instead of committing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Enqueue index sync jobs for the given crates.
+    RequeueIndexSync {
+        /// Names of the crates
+        #[arg(required = true)]
+        crate_names: Vec<String>,
+        /// Roll back all changes instead of committing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl Command {
+    fn dry_run(&self) -> bool {
+        match self {
+            Command::TransferCrateOwner { dry_run, .. }
+            | Command::FixVersionYankState { dry_run, .. }
+            | Command::MergeDuplicateUsers { dry_run, .. }
+            | Command::RequeueIndexSync { dry_run, .. } => *dry_run,
+        }
+    }
+}
+
+pub fn run(command: Command) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection()?;
+
+    let dry_run = command.dry_run();
+    if dry_run {
+        // The outer transaction is never committed, so all changes made in
+        // the nested transaction below are rolled back in the end.
+        conn.begin_test_transaction()?;
+    }
+
+    conn.transaction(|conn| match command {
+        Command::TransferCrateOwner {
+            crate_name,
+            from_user,
+            to_user,
+            ..
+        } => transfer_crate_owner(&crate_name, &from_user, &to_user, conn),
+        Command::FixVersionYankState {
+            crate_name,
+            version,
+            yanked,
+            ..
+        } => fix_version_yank_state(&crate_name, &version, yanked, conn),
+        Command::MergeDuplicateUsers {
+            from_user_id,
+            into_user_id,
+            ..
+        } => merge_duplicate_users(from_user_id, into_user_id, conn),
+        Command::RequeueIndexSync { crate_names, .. } => requeue_index_sync(&crate_names, conn),
+    })?;
+
+    if dry_run {
+        println!("Dry run, all changes have been rolled back");
+    }
+
+    Ok(())
+}
+
+fn find_user_by_login(login: &str, conn: &mut PgConnection) -> anyhow::Result<User> {
+    users::table
+        .filter(users::gh_login.eq(login))
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("user `{login}` not found"))
+}
+
+fn find_crate(name: &str, conn: &mut PgConnection) -> anyhow::Result<Crate> {
+    Crate::by_name(name)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("crate `{name}` not found"))
+}
+
+fn transfer_crate_owner(
+    crate_name: &str,
+    from_user: &str,
+    to_user: &str,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    let krate = find_crate(crate_name, conn)?;
+    let from = find_user_by_login(from_user, conn)?;
+    let to = find_user_by_login(to_user, conn)?;
+
+    let from_ownership = crate_owners::table
+        .filter(crate_owners::crate_id.eq(krate.id))
+        .filter(crate_owners::owner_id.eq(from.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::deleted.eq(false));
+
+    let removed = diesel::update(from_ownership)
+        .set(crate_owners::deleted.eq(true))
+        .execute(conn)?;
+    if removed == 0 {
+        return Err(anyhow!("`{from_user}` is not an owner of `{crate_name}`"));
+    }
+
+    diesel::insert_into(crate_owners::table)
+        .values((
+            crate_owners::crate_id.eq(krate.id),
+            crate_owners::owner_id.eq(to.id),
+            crate_owners::created_by.eq(from.id),
+            crate_owners::owner_kind.eq(OwnerKind::User as i32),
+        ))
+        .on_conflict(crate_owners::table.primary_key())
+        .do_update()
+        .set(crate_owners::deleted.eq(false))
+        .execute(conn)?;
+
+    NewAuditEvent {
+        details: json!({
+            "crate": krate.name,
+            "from": from.gh_login,
+            "to": to.gh_login,
+        }),
+        ..NewAuditEvent::new("admin.transfer_crate_owner")
+    }
+    .insert(conn)?;
+
+    info!(
+        krate.name = %krate.name,
+        from.id = from.id,
+        from.login = %from.gh_login,
+        to.id = to.id,
+        to.login = %to.gh_login,
+        "Transferred crate ownership"
+    );
+
+    Ok(())
+}
+
+fn fix_version_yank_state(
+    crate_name: &str,
+    version: &str,
+    yanked: bool,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    let krate = find_crate(crate_name, conn)?;
+    let v: Version = Version::belonging_to(&krate)
+        .filter(versions::num.eq(version))
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("crate `{crate_name}` does not have a version `{version}`"))?;
+
+    if v.yanked != yanked {
+        diesel::update(&v)
+            .set(versions::yanked.eq(yanked))
+            .execute(conn)?;
+    }
+
+    // The index is synced even if the database state was already correct,
+    // since the index might be out of sync with the database.
+    Job::enqueue_sync_to_index(&krate.name, conn)?;
+
+    NewAuditEvent {
+        details: json!({
+            "crate": krate.name,
+            "version": v.num,
+            "previously_yanked": v.yanked,
+            "yanked": yanked,
+        }),
+        ..NewAuditEvent::new("admin.fix_version_yank_state")
+    }
+    .insert(conn)?;
+
+    info!(
+        krate.name = %krate.name,
+        version.num = %v.num,
+        version.id = v.id,
+        previously_yanked = v.yanked,
+        yanked,
+        "Fixed version yank state"
+    );
+
+    Ok(())
+}
+
+fn merge_duplicate_users(
+    from_user_id: i32,
+    into_user_id: i32,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    if from_user_id == into_user_id {
+        return Err(anyhow!("cannot merge a user into itself"));
+    }
+
+    let from = User::find(conn, from_user_id).context("duplicate user not found")?;
+    let into = User::find(conn, into_user_id).context("target user not found")?;
+
+    if from.gh_id != into.gh_id {
+        return Err(anyhow!(
+            "users have different GitHub IDs ({} and {}), they are not duplicates",
+            from.gh_id,
+            into.gh_id
+        ));
+    }
+
+    // Crate ownerships that the target user already has are dropped, all
+    // others are moved over.
+    let existing_crates = crate_owners::table
+        .select(crate_owners::crate_id)
+        .filter(crate_owners::owner_id.eq(into.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32));
+    diesel::delete(crate_owners::table)
+        .filter(crate_owners::owner_id.eq(from.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::crate_id.eq_any(existing_crates))
+        .execute(conn)?;
+    diesel::update(crate_owners::table)
+        .filter(crate_owners::owner_id.eq(from.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .set(crate_owners::owner_id.eq(into.id))
+        .execute(conn)?;
+    diesel::update(crate_owners::table)
+        .filter(crate_owners::created_by.eq(from.id))
+        .set(crate_owners::created_by.eq(into.id))
+        .execute(conn)?;
+
+    let existing_invitations = crate_owner_invitations::table
+        .select(crate_owner_invitations::crate_id)
+        .filter(crate_owner_invitations::invited_user_id.eq(into.id));
+    diesel::delete(crate_owner_invitations::table)
+        .filter(crate_owner_invitations::invited_user_id.eq(from.id))
+        .filter(crate_owner_invitations::crate_id.eq_any(existing_invitations))
+        .execute(conn)?;
+    diesel::update(crate_owner_invitations::table)
+        .filter(crate_owner_invitations::invited_user_id.eq(from.id))
+        .set(crate_owner_invitations::invited_user_id.eq(into.id))
+        .execute(conn)?;
+    diesel::update(crate_owner_invitations::table)
+        .filter(crate_owner_invitations::invited_by_user_id.eq(from.id))
+        .set(crate_owner_invitations::invited_by_user_id.eq(into.id))
+        .execute(conn)?;
+
+    let existing_follows = follows::table
+        .select(follows::crate_id)
+        .filter(follows::user_id.eq(into.id));
+    diesel::delete(follows::table)
+        .filter(follows::user_id.eq(from.id))
+        .filter(follows::crate_id.eq_any(existing_follows))
+        .execute(conn)?;
+    diesel::update(follows::table)
+        .filter(follows::user_id.eq(from.id))
+        .set(follows::user_id.eq(into.id))
+        .execute(conn)?;
+
+    diesel::update(api_tokens::table)
+        .filter(api_tokens::user_id.eq(from.id))
+        .set(api_tokens::user_id.eq(into.id))
+        .execute(conn)?;
+    diesel::update(versions::table)
+        .filter(versions::published_by.eq(from.id))
+        .set(versions::published_by.eq(into.id))
+        .execute(conn)?;
+    diesel::update(version_owner_actions::table)
+        .filter(version_owner_actions::user_id.eq(from.id))
+        .set(version_owner_actions::user_id.eq(into.id))
+        .execute(conn)?;
+    diesel::update(publish_diagnostics::table)
+        .filter(publish_diagnostics::user_id.eq(from.id))
+        .set(publish_diagnostics::user_id.eq(into.id))
+        .execute(conn)?;
+    diesel::update(single_owner_nudges::table)
+        .filter(single_owner_nudges::user_id.eq(from.id))
+        .set(single_owner_nudges::user_id.eq(into.id))
+        .execute(conn)?;
+    // Audit events would otherwise lose their user when the duplicate is
+    // deleted.
+    diesel::update(audit_events::table)
+        .filter(audit_events::user_id.eq(from.id))
+        .set(audit_events::user_id.eq(into.id))
+        .execute(conn)?;
+
+    let existing_keywords = keyword_subscriptions::table
+        .select(keyword_subscriptions::keyword_id)
+        .filter(keyword_subscriptions::user_id.eq(into.id));
+    diesel::delete(keyword_subscriptions::table)
+        .filter(keyword_subscriptions::user_id.eq(from.id))
+        .filter(keyword_subscriptions::keyword_id.eq_any(existing_keywords))
+        .execute(conn)?;
+    diesel::update(keyword_subscriptions::table)
+        .filter(keyword_subscriptions::user_id.eq(from.id))
+        .set(keyword_subscriptions::user_id.eq(into.id))
+        .execute(conn)?;
+
+    let existing_categories = category_subscriptions::table
+        .select(category_subscriptions::category_id)
+        .filter(category_subscriptions::user_id.eq(into.id));
+    diesel::delete(category_subscriptions::table)
+        .filter(category_subscriptions::user_id.eq(from.id))
+        .filter(category_subscriptions::category_id.eq_any(existing_categories))
+        .execute(conn)?;
+    diesel::update(category_subscriptions::table)
+        .filter(category_subscriptions::user_id.eq(from.id))
+        .set(category_subscriptions::user_id.eq(into.id))
+        .execute(conn)?;
+
+    // Compromises are moved over, so that the merged account stays on hold.
+    // Only one of them can be unresolved, so the one of the target user wins.
+    let into_unresolved = account_compromises::table
+        .filter(account_compromises::user_id.eq(into.id))
+        .filter(account_compromises::resolved_at.is_null());
+    diesel::delete(account_compromises::table)
+        .filter(account_compromises::user_id.eq(from.id))
+        .filter(account_compromises::resolved_at.is_null())
+        .filter(exists(into_unresolved))
+        .execute(conn)?;
+    diesel::update(account_compromises::table)
+        .filter(account_compromises::user_id.eq(from.id))
+        .set(account_compromises::user_id.eq(into.id))
+        .execute(conn)?;
+    diesel::update(account_compromises::table)
+        .filter(account_compromises::admin_id.eq(from.id))
+        .set(account_compromises::admin_id.eq(into.id))
+        .execute(conn)?;
+
+    // Settings of the target user take precedence, the ones of the duplicate
+    // are only moved over if the target user has none.
+    let into_digest_preferences = digest_preferences::table.find(into.id);
+    diesel::delete(digest_preferences::table.find(from.id))
+        .filter(exists(into_digest_preferences))
+        .execute(conn)?;
+    diesel::update(digest_preferences::table.find(from.id))
+        .set(digest_preferences::user_id.eq(into.id))
+        .execute(conn)?;
+
+    let into_repository_verification = repository_verifications::table.find(into.id);
+    diesel::delete(repository_verifications::table.find(from.id))
+        .filter(exists(into_repository_verification))
+        .execute(conn)?;
+    diesel::update(repository_verifications::table.find(from.id))
+        .set(repository_verifications::user_id.eq(into.id))
+        .execute(conn)?;
+
+    // Data that only makes sense for a single account is not merged.
+    diesel::delete(emails::table.filter(emails::user_id.eq(from.id))).execute(conn)?;
+    diesel::delete(publish_limit_buckets::table.filter(publish_limit_buckets::user_id.eq(from.id)))
+        .execute(conn)?;
+    diesel::delete(
+        publish_rate_overrides::table.filter(publish_rate_overrides::user_id.eq(from.id)),
+    )
+    .execute(conn)?;
+
+    // This fails if any data is still referencing the duplicate user, which
+    // rolls back the whole merge.
+    diesel::delete(users::table.find(from.id)).execute(conn)?;
+
+    NewAuditEvent {
+        details: json!({
+            "from": { "id": from.id, "login": from.gh_login },
+            "into": { "id": into.id, "login": into.gh_login },
+            "gh_id": into.gh_id,
+        }),
+        ..NewAuditEvent::new("admin.merge_duplicate_users")
+    }
+    .insert(conn)?;
+
+    info!(
+        from.id = from.id,
+        from.login = %from.gh_login,
+        into.id = into.id,
+        into.login = %into.gh_login,
+        gh_id = into.gh_id,
+        "Merged duplicate users"
+    );
+
+    Ok(())
+}
+
+fn requeue_index_sync(crate_names: &[String], conn: &mut PgConnection) -> anyhow::Result<()> {
+    for name in crate_names {
+        let krate = find_crate(name, conn)?;
+        Job::enqueue_sync_to_index(&krate.name, conn)?;
+
+        info!(krate.name = %krate.name, "Requeued index sync");
+    }
+
+    NewAuditEvent {
+        details: json!({ "crates": crate_names }),
+        ..NewAuditEvent::new("admin.requeue_index_sync")
+    }
+    .insert(conn)?;
+
+    Ok(())
+}
@@ -0,0 +1,892 @@
+//! Import crates from another registry.
+//!
+//! The versions of a crate are read from the sparse index of the source
+//! registry, and their crate files are downloaded through the `dl` URL of
+//! the index configuration. The crate metadata is read from the normalized
+//! `Cargo.toml` file inside of the crate files, so that only the sparse
+//! index and the downloads are required.
+//!
+//! If the source registry has a web API, it is used to list all crates and
+//! to load their owners. Owners are matched to accounts on this registry by
+//! the ID of their GitHub account, which is looked up by their login, since
+//! logins can change hands after a GitHub account is renamed. Owners that
+//! don't have an account yet get a stub account for their GitHub ID, which
+//! they take over once they log in. Owners without a GitHub account get a
+//! stub account with their login, which is not connected to a GitHub
+//! account. Without a web API, the `--default-owner` is used instead.
+//!
+//! Crates are imported in the order of the index files, and crates with
+//! dependencies on crates that were not imported yet are retried until no
+//! more progress is made.
+
+use crate::background_jobs::{Job, PRIORITY_RENDER_README};
+use crate::github::{GitHubClient, RealGitHubClient};
+use crate::models::{
+    Category, CrateOwner, Keyword, NewCrate, NewCrossRegistryDependency, NewDependency, NewUser,
+    NewVersion, OwnerKind, User,
+};
+use crate::schema::{crate_owners, crates, users, versions};
+use crate::sql::lower;
+use crate::storage::{ReplicatedFile, Storage};
+use crate::util::errors::{AppResult, NotFound};
+use crate::util::source_links::VersionSource;
+use crate::util::url_normalization::normalize_url;
+use crate::{admin::dialoguer, db};
+use anyhow::{anyhow, bail, Context};
+use crates_io_index::Repository;
+use crates_io_tarball::{process_tarball, Decoder, TarballLimits};
+use diesel::prelude::*;
+use oauth2::AccessToken;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::AUTHORIZATION;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+use tokio::runtime::Runtime;
+
+const USER_AGENT: &str = "crates-admin";
+
+/// Maximum size of a crate file when decompressed.
+const MAX_UNPACK_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Maximum number of entries in a crate file.
+const MAX_UNPACK_ENTRIES: u64 = 100_000;
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "import-registry",
+    about = "Import crates from another registry with a sparse index into the database and storage."
+)]
+pub struct Opts {
+    /// URL of the sparse index of the source registry
+    #[arg(long)]
+    index_url: String,
+
+    /// URL of the web API of the source registry, which is used to list all
+    /// crates and to load their owners. Defaults to the `api` URL of the
+    /// index configuration.
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// Token that is sent in the `Authorization` header to the source
+    /// registry
+    #[arg(long, env = "IMPORT_REGISTRY_TOKEN", hide_env_values = true)]
+    token: Option<SecretString>,
+
+    /// Token that is used to look up the GitHub accounts of the crate owners
+    #[arg(long, env = "GITHUB_TOKEN", hide_env_values = true)]
+    github_token: SecretString,
+
+    /// GitHub login of the user that owns and publishes the imported crates
+    /// if their owners can't be loaded from the source registry
+    #[arg(long)]
+    default_owner: String,
+
+    /// Names of the crates to import. All crates of the source registry are
+    /// imported if this is empty, which requires a web API.
+    #[arg(value_name = "NAME")]
+    crate_names: Vec<String>,
+
+    /// Don't ask for confirmation: yes, we are sure. Best for scripting.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let default_owner = users::table
+        .filter(lower(users::gh_login).eq(opts.default_owner.to_lowercase()))
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("user `{}` not found", opts.default_owner))?;
+
+    let source = SourceRegistry::new(&opts.index_url, opts.api_url, opts.token)?;
+
+    let crate_names = if opts.crate_names.is_empty() {
+        source.crate_names()?
+    } else {
+        opts.crate_names
+    };
+
+    let prompt = format!(
+        "Are you sure you want to import {} crates from {}?",
+        crate_names.len(),
+        source.index_url
+    );
+    if !opts.yes && !dialoguer::confirm(&prompt) {
+        return Ok(());
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let github = RealGitHubClient::new(Some(source.client.clone()));
+    let github_token = AccessToken::new(opts.github_token.expose_secret().clone());
+
+    let importer = Importer {
+        source,
+        github,
+        github_token,
+        github_ids: RefCell::new(HashMap::new()),
+        storage: Storage::from_environment(),
+        rt,
+        default_owner,
+        stub_users: RefCell::new(HashMap::new()),
+    };
+
+    let mut pending = crate_names;
+    let mut blocked = BTreeMap::new();
+    let mut failed = Vec::new();
+    let mut total_versions = 0;
+
+    // Crates with dependencies on crates that were not imported yet are
+    // retried, until a pass over them doesn't import any more versions.
+    loop {
+        let mut deferred = Vec::new();
+        let mut progress = false;
+
+        for name in pending {
+            match importer.import_crate(&name, conn) {
+                Ok(import) => {
+                    total_versions += import.versions;
+                    progress |= import.versions > 0;
+
+                    if import.missing_dependencies.is_empty() {
+                        println!("Imported {} versions of `{name}`", import.versions);
+                        blocked.remove(&name);
+                    } else {
+                        blocked.insert(name.clone(), import.missing_dependencies);
+                        deferred.push(name);
+                    }
+                }
+                Err(error) => {
+                    println!("Failed to import `{name}`: {error:#}");
+                    blocked.remove(&name);
+                    failed.push(name);
+                }
+            }
+        }
+
+        if deferred.is_empty() || !progress {
+            break;
+        }
+
+        pending = deferred;
+    }
+
+    println!();
+    println!("Imported {total_versions} versions");
+
+    for (name, missing) in &blocked {
+        let missing = missing.iter().cloned().collect::<Vec<_>>().join(", ");
+        println!("Could not import all versions of `{name}`, missing dependencies: {missing}");
+    }
+
+    if !failed.is_empty() || !blocked.is_empty() {
+        bail!(
+            "{} crates failed to import and {} crates have missing dependencies",
+            failed.len(),
+            blocked.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// The result of importing the versions of a single crate.
+struct CrateImport {
+    versions: usize,
+    /// Dependencies of the next version that don't exist on this registry
+    /// yet. The remaining versions are not imported if this is not empty.
+    missing_dependencies: BTreeSet<String>,
+}
+
+struct Importer {
+    source: SourceRegistry,
+    github: RealGitHubClient,
+    github_token: AccessToken,
+    /// GitHub IDs of the owners that were looked up during this import, by
+    /// their login.
+    github_ids: RefCell<HashMap<String, Option<i32>>>,
+    storage: Storage,
+    rt: Runtime,
+    default_owner: User,
+    /// Stub users of owners without a GitHub account that were created
+    /// during this import, by their login.
+    stub_users: RefCell<HashMap<String, User>>,
+}
+
+impl Importer {
+    fn import_crate(&self, name: &str, conn: &mut PgConnection) -> anyhow::Result<CrateImport> {
+        let records = self.source.index_records(name)?;
+
+        let existing: HashSet<String> = versions::table
+            .inner_join(crates::table)
+            .filter(crates::name.eq(name))
+            .select(versions::num)
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut import = CrateImport {
+            versions: 0,
+            missing_dependencies: BTreeSet::new(),
+        };
+
+        let records = records
+            .into_iter()
+            .filter(|record| !existing.contains(&record.vers))
+            .collect::<Vec<_>>();
+        if records.is_empty() {
+            return Ok(import);
+        }
+
+        let owners = self.owners(name, conn)?;
+
+        for record in records {
+            import.missing_dependencies = missing_dependencies(&record, conn)?;
+            if !import.missing_dependencies.is_empty() {
+                break;
+            }
+
+            self.import_version(&record, &owners, conn)
+                .with_context(|| format!("Failed to import version {}", record.vers))?;
+
+            import.versions += 1;
+        }
+
+        Ok(import)
+    }
+
+    /// Returns the users that own the crate on the source registry, the
+    /// first of which is used as the publisher of the imported versions.
+    fn owners(&self, name: &str, conn: &mut PgConnection) -> anyhow::Result<Vec<User>> {
+        let mut owners = Vec::new();
+        for owner in self.source.owners(name)? {
+            if owner.kind.as_deref() == Some("team") {
+                warn!(krate = %name, team = %owner.login, "Skipping team owner");
+                continue;
+            }
+
+            owners.push(self.find_or_create_user(&owner, conn)?);
+        }
+
+        if owners.is_empty() {
+            owners.push(self.default_owner.clone());
+        }
+
+        Ok(owners)
+    }
+
+    fn find_or_create_user(
+        &self,
+        owner: &SourceOwner,
+        conn: &mut PgConnection,
+    ) -> anyhow::Result<User> {
+        let gh_id = self.github_user_id(&owner.login)?;
+        if gh_id.is_some() {
+            return Ok(find_or_create_user(owner, gh_id, conn)?);
+        }
+
+        if let Some(user) = self.stub_users.borrow().get(&owner.login) {
+            return Ok(user.clone());
+        }
+
+        let user = find_or_create_user(owner, None, conn)?;
+        self.stub_users
+            .borrow_mut()
+            .insert(owner.login.clone(), user.clone());
+        Ok(user)
+    }
+
+    fn github_user_id(&self, login: &str) -> anyhow::Result<Option<i32>> {
+        if let Some(gh_id) = self.github_ids.borrow().get(login) {
+            return Ok(*gh_id);
+        }
+
+        let gh_id = github_user_id(&self.github, login, &self.github_token)
+            .map_err(|error| anyhow!("Failed to look up GitHub user `{login}`: {error}"))?;
+        self.github_ids
+            .borrow_mut()
+            .insert(login.to_string(), gh_id);
+        Ok(gh_id)
+    }
+
+    fn import_version(
+        &self,
+        record: &crates_io_index::Crate,
+        owners: &[User],
+        conn: &mut PgConnection,
+    ) -> anyhow::Result<()> {
+        let name = &record.name;
+        let vers = semver::Version::parse(&record.vers)?;
+
+        let bytes = self.source.download(record)?;
+        let cksum = hex::encode(Sha256::digest(&bytes));
+        if cksum != record.cksum {
+            bail!(
+                "checksum mismatch: expected {}, found {cksum}",
+                record.cksum
+            );
+        }
+
+        let pkg_name = format!("{name}-{vers}");
+        let limits = TarballLimits {
+            max_unpack: MAX_UNPACK_SIZE,
+            max_entries: MAX_UNPACK_ENTRIES,
+            max_file_size: MAX_UNPACK_SIZE,
+            case_insensitive_paths: false,
+        };
+        let tarball_info = process_tarball(&pkg_name, &*bytes, &limits)?;
+        let dirty_worktree = tarball_info
+            .vcs_info
+            .as_ref()
+            .is_some_and(|info| info.is_dirty());
+        let vcs_info = tarball_info.vcs_info;
+
+        let manifest = read_file(&bytes, &Path::new(&pkg_name).join("Cargo.toml"))?
+            .ok_or_else(|| anyhow!("crate file does not contain a `Cargo.toml` file"))?;
+        let package = toml::from_str::<ImportManifest>(&manifest)
+            .context("Failed to parse `Cargo.toml` file")?
+            .package;
+
+        let repository = package.repository.as_deref().map(normalize_url);
+        let homepage = package.homepage.as_deref().map(normalize_url);
+        let documentation = package.documentation.as_deref().map(normalize_url);
+        let source = VersionSource::new(repository.as_deref(), vcs_info.as_ref());
+        let pkg_path_in_vcs = vcs_info.map(|info| info.path_in_vcs);
+
+        let readme_file = package.readme_file();
+        let readme = match &readme_file {
+            Some(path) => read_file(&bytes, &Path::new(&pkg_name).join(path))?,
+            None => None,
+        };
+
+        let publisher = &owners[0];
+        let email = publisher.verified_email(conn)?.unwrap_or_default();
+
+        let mut features = record.features.clone();
+        features.extend(record.features2.clone().unwrap_or_default());
+
+        conn.transaction(|conn| {
+            let krate = NewCrate {
+                name,
+                description: package.description.as_deref(),
+                homepage: homepage.as_deref(),
+                documentation: documentation.as_deref(),
+                readme: readme.as_deref(),
+                repository: repository.as_deref(),
+                max_upload_size: None,
+            }
+            .create_or_update(conn, publisher.id, None)
+            .map_err(|error| anyhow!("{error}"))?;
+
+            for owner in &owners[1..] {
+                diesel::insert_into(crate_owners::table)
+                    .values(&CrateOwner {
+                        crate_id: krate.id,
+                        owner_id: owner.id,
+                        created_by: publisher.id,
+                        owner_kind: OwnerKind::User as i32,
+                        email_notifications: true,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+
+            let version = NewVersion::new(
+                krate.id,
+                &vers,
+                &features,
+                package.license.clone(),
+                package.license_file.as_deref(),
+                bytes.len() as i32,
+                publisher.id,
+                cksum,
+                record.links.clone(),
+                record.rust_version.clone(),
+                dirty_worktree,
+                source,
+            )
+            .and_then(|version| version.save(conn, &email))
+            .map_err(|error| anyhow!("{error}"))?;
+
+            if record.yanked == Some(true) {
+                diesel::update(&version)
+                    .set(versions::yanked.eq(true))
+                    .execute(conn)?;
+            }
+
+            insert_dependencies(&record.deps, version.id, conn)?;
+
+            let keywords = package
+                .keywords
+                .iter()
+                .map(String::as_str)
+                .filter(|keyword| Keyword::valid_name(keyword))
+                .collect::<Vec<_>>();
+            Keyword::update_crate(conn, &krate, &keywords)?;
+
+            let categories = package.categories.iter().map(String::as_str);
+            Category::update_crate(conn, &krate, &categories.collect::<Vec<_>>())?;
+
+            if let (Some(readme), Some(readme_file)) = (readme, readme_file) {
+                Job::render_and_upload_readme(
+                    version.id,
+                    readme,
+                    readme_file,
+                    repository.clone(),
+                    pkg_path_in_vcs,
+                )
+                .enqueue_with_priority(conn, PRIORITY_RENDER_README)?;
+            }
+
+            self.rt
+                .block_on(
+                    self.storage
+                        .upload_crate_file(name, &record.vers, bytes.into()),
+                )
+                .context("Failed to upload crate file")?;
+
+            let file = ReplicatedFile::crate_file(name, &record.vers);
+            Job::enqueue_replication(&self.storage, file, conn)?;
+            Job::enqueue_sync_to_index(name, conn)?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Returns the names of the dependencies of `record` on the same registry
+/// that don't exist on this registry yet.
+fn missing_dependencies(
+    record: &crates_io_index::Crate,
+    conn: &mut PgConnection,
+) -> QueryResult<BTreeSet<String>> {
+    let names = record
+        .deps
+        .iter()
+        .filter(|dep| dep.registry.is_none())
+        .map(|dep| dep.package.as_deref().unwrap_or(&dep.name))
+        .filter(|name| *name != record.name)
+        .collect::<Vec<_>>();
+
+    let existing: HashSet<String> = crates::table
+        .filter(crates::name.eq_any(&names))
+        .select(crates::name)
+        .load::<String>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(names
+        .into_iter()
+        .filter(|name| !existing.contains(*name))
+        .map(String::from)
+        .collect())
+}
+
+fn insert_dependencies(
+    deps: &[crates_io_index::Dependency],
+    version_id: i32,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    // The index uses the name from the `Cargo.toml` file of the dependent
+    // crate, and the name of the crate in `package` if it was renamed.
+    fn crate_name(dep: &crates_io_index::Dependency) -> &str {
+        dep.package.as_deref().unwrap_or(&dep.name)
+    }
+
+    fn explicit_name(dep: &crates_io_index::Dependency) -> Option<&str> {
+        dep.package.as_ref().map(|_| dep.name.as_str())
+    }
+
+    let (cross_registry_deps, deps): (Vec<_>, Vec<_>) =
+        deps.iter().partition(|dep| dep.registry.is_some());
+
+    let names = deps.iter().map(|dep| crate_name(dep)).collect::<Vec<_>>();
+    let crate_ids: HashMap<String, i32> = crates::table
+        .filter(crates::name.eq_any(&names))
+        .select((crates::name, crates::id))
+        .load::<(String, i32)>(conn)?
+        .into_iter()
+        .collect();
+
+    let new_dependencies = deps
+        .iter()
+        .zip(&names)
+        .map(|(dep, name)| {
+            let crate_id = *crate_ids
+                .get(*name)
+                .ok_or_else(|| anyhow!("no known crate named `{name}`"))?;
+
+            Ok(NewDependency {
+                version_id,
+                crate_id,
+                req: &dep.req,
+                optional: dep.optional,
+                default_features: dep.default_features,
+                features: dep.features.iter().map(String::as_str).collect(),
+                target: dep.target.as_deref(),
+                kind: dep.kind.map(Into::into),
+                explicit_name: explicit_name(dep),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let new_cross_registry_dependencies = cross_registry_deps
+        .iter()
+        .map(|dep| NewCrossRegistryDependency {
+            version_id,
+            registry: dep.registry.as_deref().unwrap_or_default(),
+            name: crate_name(dep),
+            req: &dep.req,
+            optional: dep.optional,
+            default_features: dep.default_features,
+            features: dep.features.iter().map(String::as_str).collect(),
+            target: dep.target.as_deref(),
+            kind: dep.kind.map(Into::into),
+            explicit_name: explicit_name(dep),
+        })
+        .collect::<Vec<_>>();
+
+    NewDependency::insert_all(&new_dependencies, conn)?;
+    NewCrossRegistryDependency::insert_all(&new_cross_registry_dependencies, conn)?;
+
+    Ok(())
+}
+
+/// Returns the user with the GitHub ID of a crate owner, or creates a stub
+/// user for the owner if there is none.
+///
+/// Users are never matched by their login, because a login can belong to a
+/// different GitHub account after a rename. Owners without a GitHub ID
+/// always get a new stub user, which can't be matched to a GitHub account.
+pub fn find_or_create_user(
+    owner: &SourceOwner,
+    gh_id: Option<i32>,
+    conn: &mut PgConnection,
+) -> QueryResult<User> {
+    if let Some(gh_id) = gh_id {
+        let user = users::table
+            .filter(users::gh_id.eq(gh_id))
+            .first(conn)
+            .optional()?;
+
+        if let Some(user) = user {
+            return Ok(user);
+        }
+    }
+
+    info!(login = %owner.login, ?gh_id, "Creating stub user");
+
+    diesel::insert_into(users::table)
+        .values(&NewUser {
+            gh_id: gh_id.unwrap_or(-1),
+            gh_login: &owner.login,
+            name: owner.name.as_deref(),
+            gh_avatar: owner.avatar.as_deref(),
+            gh_access_token: "".into(),
+        })
+        .get_result(conn)
+}
+
+/// Returns the ID of the GitHub account with the given login, or `None` if
+/// there is no such account.
+pub fn github_user_id(
+    github: &dyn GitHubClient,
+    login: &str,
+    auth: &AccessToken,
+) -> AppResult<Option<i32>> {
+    match github.user_by_login(login, auth) {
+        Ok(user) => Ok(Some(user.id)),
+        Err(error) if error.is::<NotFound>() => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Returns the content of the file at `path` in a crate file, if it exists.
+fn read_file(tarball: &[u8], path: &Path) -> anyhow::Result<Option<String>> {
+    let decoder = Decoder::new(tarball)?.take(MAX_UNPACK_SIZE);
+    let mut archive = tar::Archive::new(decoder);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == path {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            return Ok(Some(content));
+        }
+    }
+
+    Ok(None)
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportManifest {
+    package: ImportPackage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ImportPackage {
+    description: Option<String>,
+    homepage: Option<String>,
+    documentation: Option<String>,
+    repository: Option<String>,
+    license: Option<String>,
+    license_file: Option<String>,
+    readme: Option<toml::Value>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    categories: Vec<String>,
+}
+
+impl ImportPackage {
+    /// Returns the path of the README file, which defaults to `README.md`
+    /// like in `cargo`.
+    fn readme_file(&self) -> Option<String> {
+        match &self.readme {
+            Some(toml::Value::String(path)) => Some(path.clone()),
+            Some(toml::Value::Boolean(false)) => None,
+            _ => Some("README.md".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexConfig {
+    dl: String,
+    api: Option<String>,
+}
+
+/// An owner of a crate on the source registry.
+#[derive(Debug, Deserialize)]
+pub struct SourceOwner {
+    pub login: String,
+    pub kind: Option<String>,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// A client for the sparse index, downloads and web API of the source
+/// registry.
+struct SourceRegistry {
+    client: Client,
+    index_url: String,
+    api_url: Option<String>,
+    dl: String,
+    token: Option<SecretString>,
+}
+
+impl SourceRegistry {
+    fn new(
+        index_url: &str,
+        api_url: Option<String>,
+        token: Option<SecretString>,
+    ) -> anyhow::Result<Self> {
+        let index_url = index_url.trim_start_matches("sparse+");
+        let index_url = index_url.trim_end_matches('/').to_string();
+
+        let mut registry = Self {
+            client: Client::builder().user_agent(USER_AGENT).build()?,
+            index_url,
+            api_url,
+            dl: String::new(),
+            token,
+        };
+
+        let url = format!("{}/config.json", registry.index_url);
+        let config: IndexConfig = registry
+            .get(&url)
+            .send()?
+            .error_for_status()?
+            .json()
+            .context("Failed to load the index configuration")?;
+
+        registry.dl = config.dl;
+        if registry.api_url.is_none() {
+            registry.api_url = config.api;
+        }
+        if let Some(api_url) = &mut registry.api_url {
+            *api_url = api_url.trim_end_matches('/').to_string();
+        }
+
+        Ok(registry)
+    }
+
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+        match &self.token {
+            Some(token) => request.header(AUTHORIZATION, token.expose_secret()),
+            None => request,
+        }
+    }
+
+    fn index_records(&self, name: &str) -> anyhow::Result<Vec<crates_io_index::Crate>> {
+        let path = Repository::relative_index_file_for_url(name);
+        let url = format!("{}/{path}", self.index_url);
+
+        let text = self.get(&url).send()?.error_for_status()?.text()?;
+        text.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).map_err(Into::into))
+            .collect::<anyhow::Result<_>>()
+            .with_context(|| format!("Failed to parse index file of `{name}`"))
+    }
+
+    fn download(&self, record: &crates_io_index::Crate) -> anyhow::Result<Vec<u8>> {
+        let url = download_url(&self.dl, &record.name, &record.vers, &record.cksum);
+        let bytes = self.get(&url).send()?.error_for_status()?.bytes()?;
+        Ok(bytes.to_vec())
+    }
+
+    fn api_url(&self) -> anyhow::Result<&str> {
+        self.api_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("the source registry does not have a web API"))
+    }
+
+    /// Returns the names of all crates of the source registry.
+    fn crate_names(&self) -> anyhow::Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Response {
+            crates: Vec<CrateName>,
+        }
+
+        #[derive(Deserialize)]
+        struct CrateName {
+            name: String,
+        }
+
+        let api_url = self.api_url()?;
+
+        let mut names = Vec::new();
+        for page in 1.. {
+            let url = format!("{api_url}/api/v1/crates?per_page=100&page={page}");
+            let response: Response = self.get(&url).send()?.error_for_status()?.json()?;
+            if response.crates.is_empty() {
+                break;
+            }
+
+            names.extend(response.crates.into_iter().map(|krate| krate.name));
+        }
+
+        Ok(names)
+    }
+
+    /// Returns the owners of a crate on the source registry, or an empty
+    /// list if the source registry does not have a web API.
+    fn owners(&self, name: &str) -> anyhow::Result<Vec<SourceOwner>> {
+        #[derive(Deserialize)]
+        struct Response {
+            users: Vec<SourceOwner>,
+        }
+
+        let Some(api_url) = &self.api_url else {
+            return Ok(Vec::new());
+        };
+
+        let url = format!("{api_url}/api/v1/crates/{name}/owners");
+        let response = self.get(&url).send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+
+        let response: Response = response.error_for_status()?.json()?;
+        Ok(response.users)
+    }
+}
+
+/// Returns the download URL of a crate file, based on the `dl` field of the
+/// index configuration.
+///
+/// See <https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration>
+fn download_url(dl: &str, name: &str, version: &str, cksum: &str) -> String {
+    const MARKERS: &[&str] = &[
+        "{crate}",
+        "{version}",
+        "{prefix}",
+        "{lowerprefix}",
+        "{sha256-checksum}",
+    ];
+
+    if !MARKERS.iter().any(|marker| dl.contains(marker)) {
+        return format!("{}/{name}/{version}/download", dl.trim_end_matches('/'));
+    }
+
+    let chars = name.chars().collect::<Vec<_>>();
+    let prefix = match chars.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", chars[0]),
+        _ => format!(
+            "{}/{}",
+            chars[..2].iter().collect::<String>(),
+            chars[2..4].iter().collect::<String>()
+        ),
+    };
+
+    dl.replace("{crate}", name)
+        .replace("{version}", version)
+        .replace("{prefix}", &prefix)
+        .replace("{lowerprefix}", &prefix.to_lowercase())
+        .replace("{sha256-checksum}", cksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_url() {
+        let dl = "https://example.com/api/v1/crates";
+        assert_eq!(
+            download_url(dl, "foo", "1.0.0", "abc"),
+            "https://example.com/api/v1/crates/foo/1.0.0/download"
+        );
+
+        let dl = "https://example.com/{prefix}/{crate}/{crate}-{version}.crate";
+        assert_eq!(
+            download_url(dl, "Serde", "1.0.0", "abc"),
+            "https://example.com/Se/rd/Serde/Serde-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(dl, "a", "1.0.0", "abc"),
+            "https://example.com/1/a/a-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(dl, "foo", "1.0.0", "abc"),
+            "https://example.com/3/f/foo/foo-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(dl, "ä", "1.0.0", "abc"),
+            "https://example.com/1/ä/ä-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(dl, "ab", "1.0.0", "abc"),
+            "https://example.com/2/ab/ab-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(dl, "äöü", "1.0.0", "abc"),
+            "https://example.com/3/ä/äöü/äöü-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(dl, "äöüß", "1.0.0", "abc"),
+            "https://example.com/äö/üß/äöüß/äöüß-1.0.0.crate"
+        );
+
+        let dl = "https://example.com/{lowerprefix}/{sha256-checksum}";
+        assert_eq!(
+            download_url(dl, "Serde", "1.0.0", "abc"),
+            "https://example.com/se/rd/abc"
+        );
+    }
+}
@@ -45,7 +45,7 @@ pub fn run(_opts: Opts) -> Result<(), Error> {
         .run_pending_migrations(MIGRATIONS)
         .expect("failed to run migrations");
 
-    info!("Synchronizing crate categories");
+    info!("Seeding crate categories");
     crate::boot::categories::sync_with_connection(CATEGORIES_TOML, conn).unwrap();
 
     Ok(())
@@ -1,12 +1,26 @@
+pub mod account_compromise;
+pub mod audit_crate_files;
+pub mod backfill_tarball_info;
+pub mod bulk_yank;
+pub mod check_migrations;
 pub mod delete_crate;
 pub mod delete_version;
 pub mod dialoguer;
+pub mod download_anomalies;
 pub mod enqueue_job;
+pub mod export_bundle;
+pub mod fix_data;
 pub mod git_import;
+pub mod import_registry;
 pub mod migrate;
 pub mod on_call;
 pub mod populate;
+pub mod reconcile_downloads;
 pub mod render_readmes;
+pub mod scan_reports;
+#[cfg(feature = "seed")]
+pub mod seed;
+pub mod smoke_test;
 pub mod test_pagerduty;
 pub mod transfer_crates;
 pub mod upload_index;
@@ -0,0 +1,224 @@
+//! Recompute the download counts of versions and crates from the daily
+//! `version_downloads` rows, and repair counts that drifted.
+//!
+//! The `update_downloads` background job adds the downloads of each day to
+//! the totals incrementally, so totals that were changed by hand or by an
+//! incident stay off forever. The expected totals only include downloads
+//! that the job has counted already, including the sums of the daily rows of
+//! archived partitions. Downloads of deleted versions are not part of the
+//! daily rows anymore, so repairing a crate with deleted versions lowers its
+//! total accordingly.
+
+use crate::db;
+use crate::schema::{crates, metadata, versions};
+use anyhow::Context;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer, Text};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "reconcile-downloads",
+    about = "Recompute the download counts of versions and crates from the daily \
+        download counts, and repair the ones that drifted.",
+    after_help = "Warning: this can take a lot of time."
+)]
+pub struct Opts {
+    /// Only reconcile the downloads of the crate with this name
+    #[arg(long = "crate")]
+    crate_name: Option<String>,
+
+    /// How many crates should be reconciled per transaction
+    #[arg(long, default_value = "1000")]
+    batch_size: i64,
+
+    /// Only print the drifted counts, without repairing them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, QueryableByName)]
+struct VersionDrift {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Text)]
+    crate_name: String,
+    #[diesel(sql_type = Text)]
+    num: String,
+    #[diesel(sql_type = Integer)]
+    downloads: i32,
+    #[diesel(sql_type = Integer)]
+    expected: i32,
+}
+
+#[derive(Debug, QueryableByName)]
+struct CrateDrift {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Integer)]
+    downloads: i32,
+    #[diesel(sql_type = Integer)]
+    expected: i32,
+}
+
+/// Locks the versions of the batch, so that the `update_downloads` job can't
+/// count new downloads while they are reconciled. The job updates versions
+/// before crates too, so this can't deadlock.
+const LOCK_VERSIONS: &str =
+    "SELECT id FROM versions WHERE crate_id = ANY($1) ORDER BY id FOR UPDATE";
+
+const VERSION_DRIFT: &str = "\
+    SELECT id, crate_name, num, downloads, expected::int4 AS expected FROM ( \
+        SELECT versions.id, crates.name AS crate_name, versions.num, versions.downloads, \
+            COALESCE(( \
+                SELECT SUM(version_downloads.counted) FROM version_downloads \
+                WHERE version_downloads.version_id = versions.id \
+            ), 0) + COALESCE(( \
+                SELECT archived_version_downloads.counted FROM archived_version_downloads \
+                WHERE archived_version_downloads.version_id = versions.id \
+            ), 0) AS expected \
+        FROM versions \
+        INNER JOIN crates ON crates.id = versions.crate_id \
+        WHERE versions.crate_id = ANY($1) \
+    ) AS totals \
+    WHERE downloads <> expected \
+    ORDER BY id";
+
+const CRATE_DRIFT: &str = "\
+    SELECT id, name, downloads, expected::int4 AS expected FROM ( \
+        SELECT crates.id, crates.name, crates.downloads, \
+            COALESCE(( \
+                SELECT SUM(version_downloads.counted) FROM versions \
+                INNER JOIN version_downloads ON version_downloads.version_id = versions.id \
+                WHERE versions.crate_id = crates.id \
+            ), 0) + COALESCE(( \
+                SELECT SUM(archived_version_downloads.counted) FROM versions \
+                INNER JOIN archived_version_downloads \
+                    ON archived_version_downloads.version_id = versions.id \
+                WHERE versions.crate_id = crates.id \
+            ), 0) AS expected \
+        FROM crates \
+        WHERE crates.id = ANY($1) \
+    ) AS totals \
+    WHERE downloads <> expected \
+    ORDER BY id";
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let mut last_crate_id = None;
+    let mut checked = 0;
+    let mut drifted_versions = 0;
+    let mut drifted_crates = 0;
+    let mut total_delta = 0;
+
+    loop {
+        let crate_ids = next_batch(&opts, last_crate_id, conn)?;
+        let Some(last_id) = crate_ids.last().copied() else {
+            break;
+        };
+        last_crate_id = Some(last_id);
+
+        let (version_drifts, crate_drifts) =
+            conn.transaction(|conn| reconcile_batch(&crate_ids, opts.dry_run, conn))?;
+
+        checked += crate_ids.len();
+        drifted_versions += version_drifts.len();
+        drifted_crates += crate_drifts.len();
+
+        for drift in &version_drifts {
+            println!(
+                "{}-{}: {} downloads, expected {}",
+                drift.crate_name, drift.num, drift.downloads, drift.expected
+            );
+        }
+        for drift in &crate_drifts {
+            println!(
+                "{}: {} downloads, expected {}",
+                drift.name, drift.downloads, drift.expected
+            );
+            total_delta += i64::from(drift.expected) - i64::from(drift.downloads);
+        }
+
+        println!(
+            "Checked {checked} crates: {drifted_versions} versions and {drifted_crates} crates drifted"
+        );
+    }
+
+    if opts.dry_run {
+        println!("Dry run, the total downloads would change by {total_delta}");
+    } else {
+        println!("Repaired downloads, the total downloads changed by {total_delta}");
+    }
+
+    Ok(())
+}
+
+fn next_batch(
+    opts: &Opts,
+    last_crate_id: Option<i32>,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<i32>> {
+    let mut query = crates::table
+        .select(crates::id)
+        .order(crates::id)
+        .limit(opts.batch_size)
+        .into_boxed();
+
+    if let Some(last_crate_id) = last_crate_id {
+        query = query.filter(crates::id.gt(last_crate_id));
+    }
+
+    if let Some(crate_name) = &opts.crate_name {
+        query = query.filter(crates::name.eq(crate_name));
+    }
+
+    query.load(conn)
+}
+
+/// Finds the drifted versions and crates of a batch of crates, and repairs
+/// them unless `dry_run` is set.
+fn reconcile_batch(
+    crate_ids: &[i32],
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> QueryResult<(Vec<VersionDrift>, Vec<CrateDrift>)> {
+    diesel::sql_query(LOCK_VERSIONS)
+        .bind::<Array<Integer>, _>(crate_ids)
+        .execute(conn)?;
+
+    let version_drifts: Vec<VersionDrift> = diesel::sql_query(VERSION_DRIFT)
+        .bind::<Array<Integer>, _>(crate_ids)
+        .load(conn)?;
+
+    let crate_drifts: Vec<CrateDrift> = diesel::sql_query(CRATE_DRIFT)
+        .bind::<Array<Integer>, _>(crate_ids)
+        .load(conn)?;
+
+    if dry_run {
+        return Ok((version_drifts, crate_drifts));
+    }
+
+    for drift in &version_drifts {
+        diesel::update(versions::table.find(drift.id))
+            .set(versions::downloads.eq(drift.expected))
+            .execute(conn)?;
+    }
+
+    let mut delta = 0;
+    for drift in &crate_drifts {
+        diesel::update(crates::table.find(drift.id))
+            .set(crates::downloads.eq(drift.expected))
+            .execute(conn)?;
+        delta += i64::from(drift.expected) - i64::from(drift.downloads);
+    }
+
+    if delta != 0 {
+        diesel::update(metadata::table)
+            .set(metadata::total_downloads.eq(metadata::total_downloads + delta))
+            .execute(conn)?;
+    }
+
+    Ok((version_drifts, crate_drifts))
+}
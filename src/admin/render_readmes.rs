@@ -4,19 +4,21 @@ use crate::{
     schema::{crates, readme_renderings, versions},
 };
 use anyhow::{anyhow, Context};
-use std::{io::Read, path::Path, sync::Arc, thread};
-
-use crate::storage::Storage;
+use futures_util::{stream, StreamExt};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::pin::pin;
+
+use crate::background_jobs::Job;
+use crate::storage::{ReplicatedFile, Storage};
+use crate::worker::{render_readme, RenderLimits, RenderedReadme};
 use chrono::{TimeZone, Utc};
-use crates_io_markdown::text_to_html;
-use crates_io_tarball::Manifest;
+use crates_io_tarball::{check_utf8, decode_utf8_lossy, Decoder, Manifest};
 use diesel::prelude::*;
-use flate2::read::GzDecoder;
-use reqwest::{blocking::Client, header};
 use tar::{self, Archive};
 
-const USER_AGENT: &str = "crates-admin";
-
 #[derive(clap::Parser, Debug)]
 #[command(
     name = "render-readmes",
@@ -26,9 +28,14 @@ const USER_AGENT: &str = "crates-admin";
 )]
 pub struct Opts {
     /// How many versions should be queried and processed at a time.
-    #[arg(long, default_value = "25")]
+    #[arg(long, default_value = "100")]
     page_size: usize,
 
+    /// How many readmes should be downloaded, rendered and uploaded
+    /// concurrently.
+    #[arg(long, default_value = "10")]
+    concurrency: usize,
+
     /// Only rerender readmes that are older than this date.
     #[arg(long)]
     older_than: Option<String>,
@@ -36,10 +43,91 @@ pub struct Opts {
     /// Only rerender readmes for the specified crate.
     #[arg(long = "crate")]
     crate_name: Option<String>,
+
+    /// Render readmes that are not valid UTF-8 anyway, replacing invalid
+    /// sequences with `U+FFFD`. Crates published before the encoding was
+    /// checked on publish can contain such readmes.
+    #[arg(long)]
+    lossy_utf8: bool,
+
+    /// File that the ID of the last processed version is stored in after
+    /// every page. If the file exists, only versions with a higher ID are
+    /// rendered, so that an interrupted run can be resumed.
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+
+    /// File that the failed versions are appended to, as one JSON object per
+    /// line.
+    #[arg(long)]
+    failure_report: Option<PathBuf>,
+
+    /// Only rerender the versions listed in this failure report of a
+    /// previous run, ignoring `--older-than` and `--crate`.
+    #[arg(long, value_name = "REPORT", conflicts_with_all = ["older_than", "crate_name"])]
+    retry_failed: Option<PathBuf>,
+}
+
+/// Progress of a run, as stored in the `--state-file`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct State {
+    last_version_id: i32,
+}
+
+impl State {
+    fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(contents) => {
+                let state = serde_json::from_slice(&contents)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                Ok(Some(state))
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Writes the state to a temporary file first, so that the state file is
+    /// never left half-written if the process is killed.
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(self)?)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// A line of the `--failure-report`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Failure {
+    version_id: i32,
+    #[serde(rename = "crate")]
+    krate: String,
+    version: String,
+    error: String,
+}
+
+/// Returns the IDs of the versions in a failure report, in ascending order.
+fn read_failure_report(reader: impl BufRead) -> anyhow::Result<Vec<i32>> {
+    let mut version_ids = BTreeSet::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let failure: Failure = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid failure report entry on line {}", index + 1))?;
+        version_ids.insert(failure.version_id);
+    }
+
+    Ok(version_ids.into_iter().collect())
 }
 
 pub fn run(opts: Opts) -> anyhow::Result<()> {
-    let storage = Arc::new(Storage::from_environment());
+    let storage = Storage::from_environment();
+    let storage = &storage;
     let conn = &mut db::oneoff_connection().unwrap();
 
     let start_time = Utc::now();
@@ -53,39 +141,68 @@ pub fn run(opts: Opts) -> anyhow::Result<()> {
     let older_than = older_than.naive_utc();
 
     println!("Start time:                   {start_time}");
-    println!("Rendering readmes older than: {older_than}");
-
-    let mut query = versions::table
-        .inner_join(crates::table)
-        .left_outer_join(readme_renderings::table)
-        .filter(
-            readme_renderings::rendered_at
-                .lt(older_than)
-                .or(readme_renderings::version_id.is_null()),
-        )
-        .select(versions::id)
-        .into_boxed();
 
-    if let Some(crate_name) = opts.crate_name {
-        println!("Rendering readmes for {crate_name}");
-        query = query.filter(crates::name.eq(crate_name));
+    let mut version_ids: Vec<i32> = if let Some(path) = &opts.retry_failed {
+        println!("Rendering failed readmes of:  {}", path.display());
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        read_failure_report(BufReader::new(file))?
+    } else {
+        println!("Rendering readmes older than: {older_than}");
+
+        let mut query = versions::table
+            .inner_join(crates::table)
+            .left_outer_join(readme_renderings::table)
+            .filter(
+                readme_renderings::rendered_at
+                    .lt(older_than)
+                    .or(readme_renderings::version_id.is_null()),
+            )
+            .select(versions::id)
+            .order(versions::id)
+            .into_boxed();
+
+        if let Some(crate_name) = opts.crate_name {
+            println!("Rendering readmes for {crate_name}");
+            query = query.filter(crates::name.eq(crate_name));
+        }
+
+        query.load(conn).expect("error loading version ids")
+    };
+
+    if let Some(path) = &opts.state_file {
+        if let Some(state) = State::load(path)? {
+            println!("Resuming after version id:    {}", state.last_version_id);
+            version_ids.retain(|id| *id > state.last_version_id);
+        }
     }
 
-    let version_ids: Vec<i32> = query.load(conn).expect("error loading version ids");
+    let mut failure_report = match &opts.failure_report {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            Some(BufWriter::new(file))
+        }
+        None => None,
+    };
 
     let total_versions = version_ids.len();
     println!("Rendering {total_versions} versions");
 
     let page_size = opts.page_size;
+    let total_pages = (total_versions + page_size - 1) / page_size;
 
-    let total_pages = total_versions / page_size;
-    let total_pages = if total_versions % page_size == 0 {
-        total_pages
-    } else {
-        total_pages + 1
-    };
+    let concurrency = opts.concurrency.max(1);
+    let lossy_utf8 = opts.lossy_utf8;
+    let limits = RenderLimits::from_environment();
 
-    let client = Client::new();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
 
     for (page_num, version_ids_chunk) in version_ids.chunks(page_size).enumerate() {
         println!(
@@ -94,122 +211,222 @@ pub fn run(opts: Opts) -> anyhow::Result<()> {
             total_pages
         );
 
-        let versions: Vec<(Version, String)> = versions::table
+        let versions: Vec<(i32, String, String)> = versions::table
             .inner_join(crates::table)
             .filter(versions::id.eq_any(version_ids_chunk))
-            .select((versions::all_columns, crates::name))
+            .select((versions::id, versions::num, crates::name))
             .load(conn)
             .expect("error loading versions");
 
-        let mut tasks = Vec::with_capacity(page_size);
-        for (version, krate_name) in versions {
-            Version::record_readme_rendering(version.id, conn)
+        for (version_id, _, _) in &versions {
+            Version::record_readme_rendering(*version_id, None, conn)
                 .context("Couldn't record rendering time")?;
+        }
 
-            let client = client.clone();
-            let storage = storage.clone();
-            let handle = thread::spawn::<_, anyhow::Result<()>>(move || {
-                println!("[{}-{}] Rendering README...", krate_name, version.num);
-                let readme = get_readme(&storage, &client, &version, &krate_name)?;
-                if !readme.is_empty() {
-                    let rt = tokio::runtime::Builder::new_current_thread()
-                        .enable_all()
-                        .build()
-                        .context("Failed to initialize tokio runtime")
-                        .unwrap();
-
-                    rt.block_on(storage.upload_readme(&krate_name, &version.num, readme.into()))
-                        .context("Failed to upload rendered README file to S3")?;
+        let results = stream::iter(versions)
+            .map(|(version_id, num, krate_name)| async move {
+                println!("[{krate_name}-{num}] Rendering README...");
+                let result =
+                    render_and_upload(storage, &krate_name, &num, lossy_utf8, limits).await;
+                (version_id, krate_name, num, result)
+            })
+            .buffer_unordered(concurrency);
+
+        rt.block_on(async {
+            let mut results = pin!(results);
+            while let Some((version_id, krate, version, result)) = results.next().await {
+                match result {
+                    Err(err) => {
+                        println!("Rendering failed: {err:?}");
+                        if let Some(report) = &mut failure_report {
+                            let failure = Failure {
+                                version_id,
+                                krate,
+                                version,
+                                error: format!("{err:#}"),
+                            };
+                            serde_json::to_writer(&mut *report, &failure)?;
+                            writeln!(report)?;
+                        }
+                    }
+                    Ok(error) => {
+                        if let Some(error) = error {
+                            println!("Uploaded placeholder README: {error}");
+                            Version::record_readme_rendering(version_id, Some(&error), conn)
+                                .context("Couldn't record rendering error")?;
+                        }
+
+                        let file = ReplicatedFile::readme(&krate, &version);
+                        Job::enqueue_replication(storage, file, conn)?;
+                    }
                 }
+            }
 
-                Ok(())
-            });
-            tasks.push(handle);
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        // The state is only saved after the failures of the page were
+        // reported, so that resuming never skips an unreported failure.
+        if let Some(report) = &mut failure_report {
+            report.flush().context("Failed to write failure report")?;
         }
-        for handle in tasks {
-            match handle.join() {
-                Err(err) => println!("Thread panicked: {err:?}"),
-                Ok(Err(err)) => println!("Thread failed: {err:?}"),
-                _ => {}
-            }
+        if let (Some(path), Some(last_version_id)) = (&opts.state_file, version_ids_chunk.last()) {
+            let state = State {
+                last_version_id: *last_version_id,
+            };
+            state.save(path)?;
         }
     }
 
     Ok(())
 }
 
-/// Renders the readme of an uploaded crate version.
-fn get_readme(
+/// Renders the readme of an uploaded crate version, and uploads it to the
+/// storage. Returns the rendering error, if a placeholder was uploaded
+/// instead.
+async fn render_and_upload(
     storage: &Storage,
-    client: &Client,
-    version: &Version,
     krate_name: &str,
-) -> anyhow::Result<String> {
-    let pkg_name = format!("{}-{}", krate_name, version.num);
-
-    let location = storage.crate_location(krate_name, &version.num.to_string());
-
-    let mut extra_headers = header::HeaderMap::new();
-    extra_headers.insert(
-        header::USER_AGENT,
-        header::HeaderValue::from_static(USER_AGENT),
-    );
-    let request = client.get(location).headers(extra_headers);
-    let response = request.send().context("Failed to fetch crate")?;
-
-    if !response.status().is_success() {
-        return Err(anyhow!(
-            "Failed to get a 200 response: {}",
-            response.text().unwrap()
-        ));
+    version: &str,
+    lossy_utf8: bool,
+    limits: RenderLimits,
+) -> anyhow::Result<Option<String>> {
+    let readme = get_readme(storage, krate_name, version, lossy_utf8, limits).await?;
+    if !readme.html.is_empty() {
+        let bytes = readme.html.into();
+        storage
+            .upload_readme(krate_name, version, bytes)
+            .await
+            .context("Failed to upload rendered README file to S3")?;
     }
 
-    let reader = GzDecoder::new(response);
-    let archive = Archive::new(reader);
-    render_pkg_readme(archive, &pkg_name)
+    Ok(readme.error)
 }
 
-fn render_pkg_readme<R: Read>(mut archive: Archive<R>, pkg_name: &str) -> anyhow::Result<String> {
+/// Renders the readme of an uploaded crate version.
+async fn get_readme(
+    storage: &Storage,
+    krate_name: &str,
+    version: &str,
+    lossy_utf8: bool,
+    limits: RenderLimits,
+) -> anyhow::Result<RenderedReadme> {
+    let pkg_name = format!("{krate_name}-{version}");
+
+    let bytes = storage
+        .read_crate_file(krate_name, version)
+        .await
+        .context("Failed to fetch crate")?;
+
+    // Rendering is CPU bound, so it must not block the downloads and uploads
+    // of the other readmes.
+    tokio::task::spawn_blocking(move || {
+        let reader = Decoder::new(&*bytes).context("Failed to decompress crate")?;
+        let archive = Archive::new(reader);
+        render_pkg_readme(archive, &pkg_name, lossy_utf8, limits)
+    })
+    .await?
+}
+
+fn render_pkg_readme<R: Read>(
+    mut archive: Archive<R>,
+    pkg_name: &str,
+    lossy_utf8: bool,
+    limits: RenderLimits,
+) -> anyhow::Result<RenderedReadme> {
     let mut entries = archive.entries().context("Invalid tar archive entries")?;
 
     let manifest: Manifest = {
         let path = format!("{pkg_name}/Cargo.toml");
         let contents = find_file_by_path(&mut entries, Path::new(&path))
             .context("Failed to read Cargo.toml file")?;
+        let contents = check_utf8(&contents).context("Failed to decode Cargo.toml file")?;
 
-        toml::from_str(&contents).context("Failed to parse manifest file")?
+        toml::from_str(contents).context("Failed to parse manifest file")?
     };
 
     let rendered = {
-        let readme = manifest.package.readme;
-        if !readme.is_some() {
-            return Ok("".to_string());
+        let readme_paths = manifest.package.readme_paths();
+        if readme_paths.is_empty() {
+            return Ok(RenderedReadme {
+                html: String::new(),
+                error: None,
+            });
         }
 
-        let readme_path = readme.as_path().unwrap_or_else(|| Path::new("README.md"));
-        let path = Path::new(pkg_name).join(readme_path);
-        let contents = find_file_by_path(&mut entries, Path::new(&path))
-            .with_context(|| format!("Failed to read {} file", readme_path.display()))?;
+        let (readme_path, contents) = find_readme(&mut entries, pkg_name, &readme_paths)
+            .context("Failed to read README file")?;
+        let contents = if lossy_utf8 {
+            decode_utf8_lossy(&contents)
+        } else {
+            check_utf8(&contents)
+                .with_context(|| format!("Failed to decode {} file", readme_path.display()))?
+                .into()
+        };
 
         // pkg_path_in_vcs Unsupported from admin::render_readmes. See #4095
         // Would need access to cargo_vcs_info
         let pkg_path_in_vcs = None;
 
-        text_to_html(
+        render_readme(
             &contents,
-            &readme_path,
+            &readme_path.to_string_lossy(),
             manifest.package.repository.as_deref(),
             pkg_path_in_vcs,
-        )
+            limits,
+        )?
     };
     Ok(rendered)
 }
 
+/// Searches the readme file in a Tar archive, trying the possible paths of
+/// the readme in order.
+///
+/// The paths of the entries are normalized like by `process_tarball`, so
+/// that e.g. `foo-0.0.1/./README.md` is found as `README.md`.
+fn find_readme<R: Read>(
+    entries: &mut tar::Entries<'_, R>,
+    pkg_name: &str,
+    readme_paths: &[PathBuf],
+) -> anyhow::Result<(PathBuf, Vec<u8>)> {
+    let mut found = HashMap::new();
+    for mut entry in entries.filter_map(|entry| entry.ok()) {
+        let Ok(path) = entry.path() else {
+            continue;
+        };
+        let Ok(path) = path.strip_prefix(pkg_name) else {
+            continue;
+        };
+        let path = path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect::<PathBuf>();
+
+        if readme_paths.contains(&path) {
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .context("Failed to read file contents")?;
+            found.insert(path, contents);
+        }
+    }
+
+    readme_paths
+        .iter()
+        .find_map(|path| found.remove_entry(path))
+        .ok_or_else(|| {
+            anyhow!(
+                "Failed to find tarball entry: {}",
+                readme_paths[0].display()
+            )
+        })
+}
+
 /// Search an entry by its path in a Tar archive.
 fn find_file_by_path<R: Read>(
     entries: &mut tar::Entries<'_, R>,
     path: &Path,
-) -> anyhow::Result<String> {
+) -> anyhow::Result<Vec<u8>> {
     let mut file = entries
         .filter_map(|entry| entry.ok())
         .find(|file| match file.path() {
@@ -218,8 +435,8 @@ fn find_file_by_path<R: Read>(
         })
         .ok_or_else(|| anyhow!("Failed to find tarball entry: {}", path.display()))?;
 
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
         .context("Failed to read file contents")?;
 
     Ok(contents)
@@ -229,7 +446,15 @@ fn find_file_by_path<R: Read>(
 pub mod tests {
     use crates_io_tarball::TarballBuilder;
 
-    use super::render_pkg_readme;
+    use super::{read_failure_report, render_pkg_readme, State};
+    use crate::worker::RenderLimits;
+    use std::time::Duration;
+
+    const LIMITS: RenderLimits = RenderLimits {
+        timeout: Duration::from_secs(60),
+        max_size: 1024 * 1024,
+        max_threads: 8,
+    };
 
     #[test]
     fn test_render_pkg_readme() {
@@ -243,9 +468,32 @@ readme = "README.md"
             .add_file("foo-0.0.1/README.md", b"readme")
             .build_unzipped();
 
-        let result =
-            render_pkg_readme(tar::Archive::new(&*serialized_archive), "foo-0.0.1").unwrap();
-        assert!(result.contains("readme"))
+        let result = render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            false,
+            LIMITS,
+        )
+        .unwrap();
+        assert!(result.html.contains("readme"))
+    }
+
+    #[test]
+    fn test_render_pkg_default_readme() {
+        let serialized_archive = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(b"[package]\n")
+            .add_file("foo-0.0.1/README", b"plain")
+            .add_file("foo-0.0.1/README.txt", b"text")
+            .build_unzipped();
+
+        let result = render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            false,
+            LIMITS,
+        )
+        .unwrap();
+        assert!(result.html.contains("text"))
     }
 
     #[test]
@@ -260,7 +508,9 @@ readme = "README.md"
 
         assert_err!(render_pkg_readme(
             tar::Archive::new(&*serialized_archive),
-            "foo-0.0.1"
+            "foo-0.0.1",
+            false,
+            LIMITS
         ));
     }
 
@@ -275,9 +525,14 @@ readme = "README.md"
             .add_file("foo-0.0.1/README.md", b"readme")
             .build_unzipped();
 
-        let result =
-            render_pkg_readme(tar::Archive::new(&*serialized_archive), "foo-0.0.1").unwrap();
-        assert!(result.contains("readme"))
+        let result = render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            false,
+            LIMITS,
+        )
+        .unwrap();
+        assert!(result.html.contains("readme"))
     }
 
     #[test]
@@ -293,9 +548,16 @@ repository = "https://github.com/foo/foo"
             .add_file("foo-0.0.1/README.md", b"readme [link](./Other.md)")
             .build_unzipped();
 
-        let result =
-            render_pkg_readme(tar::Archive::new(&*serialized_archive), "foo-0.0.1").unwrap();
-        assert!(result.contains("\"https://github.com/foo/foo/blob/HEAD/./Other.md\""))
+        let result = render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            false,
+            LIMITS,
+        )
+        .unwrap();
+        assert!(result
+            .html
+            .contains("\"https://github.com/foo/foo/blob/HEAD/./Other.md\""))
     }
 
     #[test]
@@ -314,9 +576,76 @@ repository = "https://github.com/foo/foo"
             )
             .build_unzipped();
 
-        let result =
-            render_pkg_readme(tar::Archive::new(&*serialized_archive), "foo-0.0.1").unwrap();
-        assert!(result.contains("docs/readme"));
-        assert!(result.contains("\"https://github.com/foo/foo/blob/HEAD/docs/./Other.md\""))
+        let result = render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            false,
+            LIMITS,
+        )
+        .unwrap();
+        assert!(result.html.contains("docs/readme"));
+        assert!(result
+            .html
+            .contains("\"https://github.com/foo/foo/blob/HEAD/docs/./Other.md\""))
+    }
+
+    #[test]
+    fn test_render_pkg_readme_invalid_utf8() {
+        let serialized_archive = TarballBuilder::new("foo", "0.0.1")
+            .add_raw_manifest(
+                br#"
+[package]
+readme = "README.md"
+"#,
+            )
+            .add_file("foo-0.0.1/README.md", b"\xef\xbb\xbf# f\xf6o")
+            .build_unzipped();
+
+        let error = assert_err!(render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            false,
+            LIMITS
+        ));
+        assert_eq!(
+            format!("{error:#}"),
+            "Failed to decode README.md file: the file starts with a UTF-8 byte order mark"
+        );
+
+        let result = render_pkg_readme(
+            tar::Archive::new(&*serialized_archive),
+            "foo-0.0.1",
+            true,
+            LIMITS,
+        )
+        .unwrap();
+        assert!(result.html.contains("f\u{fffd}o</h1>"));
+    }
+
+    #[test]
+    fn state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        assert_eq!(State::load(&path).unwrap(), None);
+
+        let state = State {
+            last_version_id: 42,
+        };
+        state.save(&path).unwrap();
+        assert_eq!(State::load(&path).unwrap(), Some(state));
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn failure_report() {
+        let report = br#"{"version_id":3,"crate":"foo","version":"1.0.0","error":"boom"}
+{"version_id":1,"crate":"bar","version":"0.1.0","error":"boom"}
+
+{"version_id":3,"crate":"foo","version":"1.0.0","error":"boom again"}
+"#;
+        assert_eq!(read_failure_report(&report[..]).unwrap(), vec![1, 3]);
+
+        let error = assert_err!(read_failure_report(&b"{}"[..]));
+        assert_eq!(error.to_string(), "Invalid failure report entry on line 1");
     }
 }
@@ -0,0 +1,47 @@
+use crate::db;
+use crate::models::VersionScanReport;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "scan-reports",
+    about = "List published crate files that contain binaries or other suspicious files."
+)]
+pub struct Opts {
+    /// Number of days to look back for scan reports
+    #[arg(long, default_value_t = 7)]
+    days: i64,
+
+    /// Only list the reports of the crate with this name
+    #[arg(long = "crate")]
+    crate_name: Option<String>,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let since = Utc::now().naive_utc() - Duration::days(opts.days);
+    let reports = VersionScanReport::since(since, opts.crate_name.as_deref(), conn)?;
+
+    if reports.is_empty() {
+        println!("No scan reports since {}", since.date());
+        return Ok(());
+    }
+
+    for (report, crate_name, version) in reports {
+        println!("{crate_name}@{version} (scanned at {})", report.created_at);
+
+        let Some(scan) = report.report() else {
+            println!("  unreadable findings: {}", report.findings);
+            continue;
+        };
+
+        for finding in scan.findings {
+            let path = finding.path.display();
+            println!("  {path}: {}", finding.kind.description());
+        }
+    }
+
+    Ok(())
+}
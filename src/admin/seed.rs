@@ -0,0 +1,693 @@
+//! Generate a realistic dataset for local development.
+//!
+//! The generated crates go through the same models as published crates, and
+//! their crate files and rendered READMEs are uploaded to the configured
+//! storage, so that search, pagination and the download statistics can be
+//! exercised without a production database dump.
+
+use crate::background_jobs::Job;
+use crate::email::Emails;
+use crate::models::{
+    Category, Crate, CrateOwner, DependencyKind, Keyword, NewCrate, NewTeam, NewUser, NewVersion,
+    OwnerKind, Team, User, Version,
+};
+use crate::schema::{
+    categories, crate_owners, crates, dependencies, emails, metadata, version_downloads, versions,
+};
+use crate::storage::{ReplicatedFile, Storage};
+use crate::util::source_links::VersionSource;
+use crate::{admin::dialoguer, config, db, Env};
+use anyhow::{anyhow, bail, Context};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use crates_io_markdown::text_to_html;
+use crates_io_tarball::TarballBuilder;
+use diesel::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use tokio::runtime::Runtime;
+use url::Url;
+
+const ADJECTIVES: &[&str] = &[
+    "async", "atomic", "blazing", "bright", "compact", "cozy", "crisp", "eager", "fast", "fuzzy",
+    "gentle", "happy", "lazy", "lean", "lucky", "nimble", "quick", "quiet", "rapid", "rusty",
+    "shiny", "simple", "sleek", "smart", "snappy", "solid", "spicy", "swift", "tiny", "witty",
+];
+
+const NOUNS: &[&str] = &[
+    "arena",
+    "buffer",
+    "cache",
+    "channel",
+    "codec",
+    "config",
+    "crab",
+    "cursor",
+    "ferris",
+    "graph",
+    "hasher",
+    "heap",
+    "index",
+    "lexer",
+    "logger",
+    "matrix",
+    "parser",
+    "pool",
+    "queue",
+    "router",
+    "scanner",
+    "server",
+    "signal",
+    "socket",
+    "stream",
+    "table",
+    "timer",
+    "tokenizer",
+    "tree",
+    "vector",
+];
+
+const SUFFIXES: &[&str] = &["core", "derive", "macros", "rs", "sys", "utils"];
+
+const KEYWORDS: &[&str] = &[
+    "async",
+    "cli",
+    "data-structures",
+    "embedded",
+    "encoding",
+    "ffi",
+    "http",
+    "logging",
+    "macros",
+    "network",
+    "no-std",
+    "parser",
+    "performance",
+    "serialization",
+    "testing",
+    "web",
+];
+
+const LICENSES: &[&str] = &[
+    "MIT OR Apache-2.0",
+    "MIT OR Apache-2.0",
+    "MIT",
+    "Apache-2.0",
+    "BSD-3-Clause",
+    "MPL-2.0",
+];
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "seed",
+    about = "Seed the database and storage with generated users, teams, crates, versions and \
+        download statistics for local development.",
+    after_help = "This should never be run against a production database."
+)]
+pub struct Opts {
+    /// Number of crates to generate
+    #[arg(long, default_value_t = 100)]
+    crates: usize,
+
+    /// Maximum number of versions per crate
+    #[arg(long, default_value_t = 10)]
+    max_versions: usize,
+
+    /// Number of users to generate, which are used as crate owners
+    #[arg(long, default_value_t = 20)]
+    users: usize,
+
+    /// Number of GitHub teams to generate, which are used as crate owners
+    #[arg(long, default_value_t = 3)]
+    teams: usize,
+
+    /// Number of days of publishing and download history to generate
+    #[arg(long, default_value_t = 90)]
+    days: i64,
+
+    /// Seed of the random number generator, to generate the same dataset
+    /// again
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Don't ask for confirmation: yes, we are sure. Best for scripting.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+/// Refuses to seed a database unless this is a development environment or
+/// the database is running on the local machine.
+fn ensure_development_database() -> anyhow::Result<()> {
+    if config::Base::from_environment().env == Env::Development {
+        return Ok(());
+    }
+
+    let database_url = dotenvy::var("DATABASE_URL").context("DATABASE_URL is not set")?;
+    let database_url = Url::parse(&database_url).context("Invalid DATABASE_URL")?;
+    let is_local = matches!(
+        database_url.host_str(),
+        Some("localhost" | "127.0.0.1" | "[::1]")
+    );
+    if !is_local {
+        bail!("Refusing to seed a database that is not on localhost outside of development");
+    }
+
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    if opts.users == 0 || opts.max_versions == 0 || opts.days < 1 {
+        return Err(anyhow!(
+            "--users, --max-versions and --days must be at least 1"
+        ));
+    }
+
+    ensure_development_database()?;
+
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+
+    let prompt = format!(
+        "Are you sure you want to add {} generated crates to the database and storage?",
+        opts.crates
+    );
+    if !opts.yes && !dialoguer::confirm(&prompt) {
+        return Ok(());
+    }
+
+    let rng = match opts.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let categories = categories::table
+        .select(categories::slug)
+        .load(conn)
+        .context("Failed to load categories")?;
+
+    let mut seeder = Seeder {
+        opts: &opts,
+        rng,
+        storage: Storage::from_environment(),
+        rt,
+        users: vec![],
+        teams: vec![],
+        categories,
+        crates: vec![],
+    };
+
+    seeder.users = conn.transaction(|conn| seeder.seed_users(conn))?;
+    println!("Created {} users", seeder.users.len());
+
+    seeder.teams = conn.transaction(|conn| seeder.seed_teams(conn))?;
+    println!("Created {} teams", seeder.teams.len());
+
+    for name in seeder.crate_names() {
+        let exists: bool = diesel::select(diesel::dsl::exists(
+            crates::table.filter(crates::name.eq(&name)),
+        ))
+        .get_result(conn)?;
+        if exists {
+            println!("Skipping `{name}`, which already exists");
+            continue;
+        }
+
+        let krate = conn
+            .transaction(|conn| seeder.seed_crate(&name, conn))
+            .with_context(|| format!("Failed to seed crate `{name}`"))?;
+
+        println!("Created `{}` with {} versions", name, krate.versions);
+        seeder.crates.push(krate);
+    }
+
+    sql_function!(fn refresh_recent_crate_downloads());
+    diesel::select(refresh_recent_crate_downloads()).execute(conn)?;
+
+    println!();
+    println!(
+        "Seeded {} crates. The index is updated by the background worker.",
+        seeder.crates.len()
+    );
+
+    Ok(())
+}
+
+/// A crate that was created by the seeder, which later crates can depend on.
+struct SeededCrate {
+    id: i32,
+    name: String,
+    latest: semver::Version,
+    versions: usize,
+}
+
+struct Seeder<'a> {
+    opts: &'a Opts,
+    rng: StdRng,
+    storage: Storage,
+    rt: Runtime,
+    users: Vec<User>,
+    teams: Vec<Team>,
+    categories: Vec<String>,
+    crates: Vec<SeededCrate>,
+}
+
+impl Seeder<'_> {
+    fn seed_users(&mut self, conn: &mut PgConnection) -> anyhow::Result<Vec<User>> {
+        // Generated users don't have real email addresses, so the confirmation
+        // emails are never sent.
+        let mailer = Emails::new_in_memory();
+
+        let logins = self.unique_names(self.opts.users, two_words);
+
+        let mut users = Vec::with_capacity(logins.len());
+        for login in logins {
+            let gh_id = self.rng.gen_range(100_000_000..i32::MAX);
+            let email = format!("{login}@example.com");
+
+            let user = NewUser::new(gh_id, &login, Some(login.as_str()), None, "")
+                .create_or_update(Some(email.as_str()), &mailer, conn)?;
+
+            diesel::update(emails::table.filter(emails::user_id.eq(user.id)))
+                .set(emails::verified.eq(true))
+                .execute(conn)?;
+
+            users.push(user);
+        }
+
+        Ok(users)
+    }
+
+    fn seed_teams(&mut self, conn: &mut PgConnection) -> anyhow::Result<Vec<Team>> {
+        let orgs = self.unique_names(self.opts.teams, two_words);
+
+        let mut teams = Vec::with_capacity(orgs.len());
+        for org in orgs {
+            let login = format!("github:{org}:maintainers");
+            let org_id = self.rng.gen_range(100_000_000..i32::MAX);
+            let github_id = self.rng.gen_range(100_000_000..i32::MAX);
+            let name = Some("maintainers".to_string());
+
+            let team =
+                NewTeam::new(&login, org_id, github_id, name, None).create_or_update(conn)?;
+            teams.push(team);
+        }
+
+        Ok(teams)
+    }
+
+    fn crate_names(&mut self) -> Vec<String> {
+        self.unique_names(self.opts.crates, |rng| {
+            let noun = NOUNS.choose(rng).unwrap();
+            match rng.gen_range(0..3) {
+                0 => format!("{}-{noun}", ADJECTIVES.choose(rng).unwrap()),
+                1 => format!("{noun}-{}", SUFFIXES.choose(rng).unwrap()),
+                _ => format!(
+                    "{}-{noun}-{}",
+                    ADJECTIVES.choose(rng).unwrap(),
+                    SUFFIXES.choose(rng).unwrap()
+                ),
+            }
+        })
+    }
+
+    /// Generates `count` distinct names, falling back to numbered names once
+    /// the generator keeps returning names that were already used.
+    fn unique_names<F>(&mut self, count: usize, mut generate: F) -> Vec<String>
+    where
+        F: FnMut(&mut StdRng) -> String,
+    {
+        let mut names = HashSet::with_capacity(count);
+        let mut result = Vec::with_capacity(count);
+        while result.len() < count {
+            let mut name = generate(&mut self.rng);
+            if names.contains(&name) {
+                name = format!("{name}-{}", result.len());
+            }
+            if names.insert(name.clone()) {
+                result.push(name);
+            }
+        }
+
+        result
+    }
+
+    fn seed_crate(&mut self, name: &str, conn: &mut PgConnection) -> anyhow::Result<SeededCrate> {
+        let owner = self.users.choose(&mut self.rng).unwrap().clone();
+
+        let noun = name
+            .split('-')
+            .find(|word| NOUNS.contains(word))
+            .unwrap_or(name);
+        let description = format!(
+            "A {} {noun} for Rust",
+            ADJECTIVES.choose(&mut self.rng).unwrap()
+        );
+        let repository = format!("https://github.com/{}/{name}", owner.gh_login);
+        let documentation = format!("https://docs.rs/{name}");
+        let readme = readme(name, &description);
+
+        let krate = NewCrate {
+            name,
+            description: Some(&description),
+            documentation: Some(&documentation),
+            repository: Some(&repository),
+            readme: Some(&readme),
+            ..NewCrate::default()
+        }
+        .create_or_update(conn, owner.id, None)
+        .map_err(|error| anyhow!("{error}"))?;
+
+        self.add_owners(&krate, &owner, conn)?;
+
+        let keywords = pick(&mut self.rng, KEYWORDS, 3);
+        Keyword::update_crate(conn, &krate, &keywords)?;
+
+        let categories = pick(&mut self.rng, &self.categories, 2);
+        let categories = categories.iter().map(String::as_str).collect::<Vec<_>>();
+        Category::update_crate(conn, &krate, &categories)?;
+
+        let history = self.version_history();
+        let license = *LICENSES.choose(&mut self.rng).unwrap();
+
+        let mut published = Vec::with_capacity(history.len());
+        for (num, created_at) in &history {
+            let deps = self.pick_dependencies();
+
+            let manifest = manifest(
+                name,
+                num,
+                &description,
+                license,
+                &repository,
+                &keywords,
+                &categories,
+                &deps,
+            );
+            let tarball = TarballBuilder::new(name, &num.to_string())
+                .add_raw_manifest(manifest.as_bytes())
+                .add_file(&format!("{name}-{num}/README.md"), readme.as_bytes())
+                .add_file(
+                    &format!("{name}-{num}/src/lib.rs"),
+                    format!("//! {description}\n").as_bytes(),
+                )
+                .build();
+
+            let version = insert_version(&krate, num, license, &owner, &tarball, &deps, conn)?;
+
+            diesel::update(&version)
+                .set((
+                    versions::created_at.eq(*created_at),
+                    versions::updated_at.eq(*created_at),
+                ))
+                .execute(conn)?;
+
+            let rendered = text_to_html(&readme, "README.md", Some(repository.as_str()), None);
+            Version::record_readme_rendering(version.id, None, conn)?;
+
+            let num = num.to_string();
+            self.rt
+                .block_on(self.storage.upload_crate_file(name, &num, tarball.into()))
+                .context("Failed to upload crate file")?;
+            self.rt
+                .block_on(self.storage.upload_readme(name, &num, rendered.into()))
+                .context("Failed to upload rendered README file")?;
+
+            let file = ReplicatedFile::crate_file(name, &num);
+            Job::enqueue_replication(&self.storage, file, conn)?;
+            let file = ReplicatedFile::readme(name, &num);
+            Job::enqueue_replication(&self.storage, file, conn)?;
+
+            published.push((version.id, created_at.date()));
+        }
+
+        self.seed_downloads(krate.id, &published, conn)?;
+
+        // Set the timestamps last, so that they are not overwritten by the
+        // updates above.
+        let (_, first_published) = history.first().unwrap();
+        let (latest, last_published) = history.last().unwrap();
+        diesel::update(&krate)
+            .set((
+                crates::created_at.eq(*first_published),
+                crates::updated_at.eq(*last_published),
+            ))
+            .execute(conn)?;
+
+        Job::enqueue_sync_to_index(name, conn)?;
+
+        Ok(SeededCrate {
+            id: krate.id,
+            name: name.to_string(),
+            latest: latest.clone(),
+            versions: history.len(),
+        })
+    }
+
+    /// Adds another user and a team as owners to some of the crates.
+    fn add_owners(
+        &mut self,
+        krate: &Crate,
+        owner: &User,
+        conn: &mut PgConnection,
+    ) -> QueryResult<()> {
+        let mut owners = vec![];
+
+        if self.rng.gen_bool(0.3) {
+            let user = self.users.choose(&mut self.rng).unwrap();
+            if user.id != owner.id {
+                owners.push((user.id, OwnerKind::User));
+            }
+        }
+
+        if self.rng.gen_bool(0.2) {
+            if let Some(team) = self.teams.choose(&mut self.rng) {
+                owners.push((team.id, OwnerKind::Team));
+            }
+        }
+
+        for (owner_id, kind) in owners {
+            diesel::insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: krate.id,
+                    owner_id,
+                    created_by: owner.id,
+                    owner_kind: kind as i32,
+                    email_notifications: true,
+                })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns an increasing list of version numbers, together with their
+    /// publish times within the configured number of days.
+    fn version_history(&mut self) -> Vec<(semver::Version, NaiveDateTime)> {
+        let count = self.rng.gen_range(1..=self.opts.max_versions);
+        let now = Utc::now().naive_utc();
+        let window = self.opts.days * 24 * 60 * 60;
+
+        let mut offsets = (0..count)
+            .map(|_| self.rng.gen_range(0..window))
+            .collect::<Vec<_>>();
+        offsets.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut num = semver::Version::new(0, 1, 0);
+        let mut history = Vec::with_capacity(count);
+        for offset in offsets {
+            history.push((num.clone(), now - Duration::seconds(offset)));
+
+            num = match self.rng.gen_range(0..20) {
+                0 => semver::Version::new(num.major + 1, 0, 0),
+                1..=5 => semver::Version::new(num.major, num.minor + 1, 0),
+                _ => semver::Version::new(num.major, num.minor, num.patch + 1),
+            };
+        }
+
+        history
+    }
+
+    /// Picks up to three previously seeded crates to depend on, as
+    /// `(crate_id, name, req)`.
+    fn pick_dependencies(&mut self) -> Vec<(i32, String, String)> {
+        let count = self.rng.gen_range(0..=3).min(self.crates.len());
+        self.crates
+            .choose_multiple(&mut self.rng, count)
+            .map(|krate| {
+                let req = format!("^{}.{}", krate.latest.major, krate.latest.minor);
+                (krate.id, krate.name.clone(), req)
+            })
+            .collect()
+    }
+
+    /// Generates daily download counts for the versions of a crate, where
+    /// the newest version gets most of the downloads of a day.
+    fn seed_downloads(
+        &mut self,
+        crate_id: i32,
+        published: &[(i32, NaiveDate)],
+        conn: &mut PgConnection,
+    ) -> QueryResult<()> {
+        let today = Utc::now().date_naive();
+        let popularity = 10f64.powf(self.rng.gen_range(0.0..4.0));
+
+        let mut totals = vec![0; published.len()];
+        let mut rows = vec![];
+        for date in published[0].1.iter_days().take_while(|date| *date <= today) {
+            let count = published.iter().filter(|(_, d)| *d <= date).count();
+            let daily = popularity * self.rng.gen_range(0.5..1.5);
+
+            for (i, (version_id, _)) in published[..count].iter().enumerate() {
+                let share = match (count, i + 1 == count) {
+                    (1, _) => 1.0,
+                    (_, true) => 0.7,
+                    _ => 0.3 / (count - 1) as f64,
+                };
+
+                let downloads = (daily * share).round() as i32;
+                if downloads > 0 {
+                    totals[i] += downloads;
+                    rows.push((
+                        version_downloads::version_id.eq(*version_id),
+                        version_downloads::downloads.eq(downloads),
+                        version_downloads::counted.eq(downloads),
+                        version_downloads::date.eq(date),
+                        version_downloads::processed.eq(date < today),
+                    ));
+                }
+            }
+        }
+
+        for chunk in rows.chunks(10_000) {
+            diesel::insert_into(version_downloads::table)
+                .values(chunk)
+                .execute(conn)?;
+        }
+
+        for ((version_id, _), total) in published.iter().zip(&totals) {
+            diesel::update(versions::table.find(*version_id))
+                .set(versions::downloads.eq(*total))
+                .execute(conn)?;
+        }
+
+        let total = totals.iter().sum::<i32>();
+        diesel::update(crates::table.find(crate_id))
+            .set(crates::downloads.eq(total))
+            .execute(conn)?;
+
+        diesel::update(metadata::table)
+            .set(metadata::total_downloads.eq(metadata::total_downloads + i64::from(total)))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_version(
+    krate: &Crate,
+    num: &semver::Version,
+    license: &str,
+    owner: &User,
+    tarball: &[u8],
+    deps: &[(i32, String, String)],
+    conn: &mut PgConnection,
+) -> anyhow::Result<Version> {
+    let features = BTreeMap::from([
+        ("default".to_string(), vec!["std".to_string()]),
+        ("std".to_string(), vec![]),
+    ]);
+    let checksum = hex::encode(Sha256::digest(tarball));
+    let email = format!("{}@example.com", owner.gh_login);
+
+    let version = NewVersion::new(
+        krate.id,
+        num,
+        &features,
+        Some(license.to_string()),
+        None,
+        tarball.len() as i32,
+        owner.id,
+        checksum,
+        None,
+        None,
+        false,
+        VersionSource::default(),
+    )
+    .and_then(|version| version.save(conn, &email))
+    .map_err(|error| anyhow!("{error}"))?;
+
+    let deps = deps
+        .iter()
+        .map(|(crate_id, _, req)| {
+            (
+                dependencies::version_id.eq(version.id),
+                dependencies::crate_id.eq(*crate_id),
+                dependencies::req.eq(req.as_str()),
+                dependencies::optional.eq(false),
+                dependencies::default_features.eq(true),
+                dependencies::features.eq(Vec::<String>::new()),
+                dependencies::kind.eq(DependencyKind::Normal as i32),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    diesel::insert_into(dependencies::table)
+        .values(&deps)
+        .execute(conn)?;
+
+    Ok(version)
+}
+
+fn two_words(rng: &mut StdRng) -> String {
+    let adjective = ADJECTIVES.choose(rng).unwrap();
+    let noun = NOUNS.choose(rng).unwrap();
+    format!("{adjective}-{noun}")
+}
+
+fn pick<T: Clone>(rng: &mut StdRng, values: &[T], max: usize) -> Vec<T> {
+    let count = rng.gen_range(0..=max).min(values.len());
+    values.choose_multiple(rng, count).cloned().collect()
+}
+
+fn readme(name: &str, description: &str) -> String {
+    format!(
+        "# {name}\n\n{description}.\n\n## Usage\n\n```toml\n[dependencies]\n{name} = \"*\"\n```\n\n\
+        ## License\n\nThis crate was generated for local development of crates.io.\n"
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn manifest(
+    name: &str,
+    num: &semver::Version,
+    description: &str,
+    license: &str,
+    repository: &str,
+    keywords: &[&str],
+    categories: &[&str],
+    deps: &[(i32, String, String)],
+) -> String {
+    let mut manifest = format!(
+        "[package]\nname = \"{name}\"\nversion = \"{num}\"\nedition = \"2021\"\n\
+        description = \"{description}\"\nlicense = \"{license}\"\nrepository = \"{repository}\"\n\
+        readme = \"README.md\"\nkeywords = {keywords:?}\ncategories = {categories:?}\n\n\
+        [features]\ndefault = [\"std\"]\nstd = []\n"
+    );
+
+    if !deps.is_empty() {
+        manifest.push_str("\n[dependencies]\n");
+        for (_, name, req) in deps {
+            manifest.push_str(&format!("{name} = \"{req}\"\n"));
+        }
+    }
+
+    manifest
+}
@@ -0,0 +1,306 @@
+//! Run a minimal end-to-end scenario against a deployed instance.
+//!
+//! The smoke test only uses endpoints that don't require authentication, so
+//! it can be run right after a deploy without any credentials. Each check is
+//! printed with its result, and the command fails if any of the checks
+//! failed, so that it can be used as a post-deploy verification step.
+
+use anyhow::{anyhow, bail, ensure, Context};
+use crates_io_index::Repository;
+use reqwest::blocking::{Client, Response};
+use reqwest::redirect::Policy;
+use reqwest::{header, StatusCode};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+const USER_AGENT: &str = "crates-admin smoke-test";
+
+const DEPENDS_ON_FAILED_CHECK: &str = "depends on a failed check";
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "smoke-test",
+    about = "Run a minimal end-to-end scenario against a deployed instance."
+)]
+pub struct Opts {
+    /// Base URL of the deployed instance, e.g. `https://staging.crates.io`
+    #[arg(long)]
+    base_url: String,
+
+    /// URL of the sparse index of the deployed instance. The sparse index is
+    /// not checked if this is not set.
+    #[arg(long)]
+    index_url: Option<String>,
+
+    /// Name of the crate that is used for the checks. Defaults to the most
+    /// recently updated crate.
+    #[arg(long = "crate")]
+    crate_name: Option<String>,
+
+    /// Commit SHA that is expected to be deployed
+    #[arg(long)]
+    expect_sha: Option<String>,
+
+    /// Timeout of each request in seconds
+    #[arg(long, default_value = "30")]
+    timeout: u64,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let client = Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(opts.timeout))
+        .redirect(Policy::none())
+        .build()?;
+
+    let base_url = opts.base_url.trim_end_matches('/');
+    let mut test = SmokeTest {
+        client,
+        base_url,
+        passed: 0,
+        failed: 0,
+        skipped: 0,
+    };
+
+    test.check("site metadata", |test| {
+        test.site_metadata(opts.expect_sha.as_deref())
+    });
+
+    let newest_crate = test.check("summary", SmokeTest::summary);
+    let crate_name = opts.crate_name.or(newest_crate);
+
+    let version = match &crate_name {
+        Some(crate_name) => {
+            test.check("search", |test| test.search(crate_name));
+            test.check("crate metadata", |test| test.crate_metadata(crate_name))
+        }
+        None => {
+            test.skip("search", DEPENDS_ON_FAILED_CHECK);
+            test.skip("crate metadata", DEPENDS_ON_FAILED_CHECK);
+            None
+        }
+    };
+
+    match (&crate_name, &version) {
+        (Some(crate_name), Some(version)) => {
+            test.check("download redirect", |test| {
+                test.download(crate_name, version)
+            });
+        }
+        _ => test.skip("download redirect", DEPENDS_ON_FAILED_CHECK),
+    }
+
+    match (&opts.index_url, &crate_name, &version) {
+        (Some(index_url), Some(crate_name), Some(version)) => {
+            let index_url = index_url.trim_end_matches('/');
+            test.check("sparse index", |test| {
+                test.sparse_index(index_url, crate_name, version)
+            });
+        }
+        (None, _, _) => test.skip("sparse index", "no `--index-url` given"),
+        _ => test.skip("sparse index", DEPENDS_ON_FAILED_CHECK),
+    }
+
+    test.check(
+        "authentication required",
+        SmokeTest::authentication_required,
+    );
+
+    println!();
+    println!(
+        "{} passed, {} failed, {} skipped",
+        test.passed, test.failed, test.skipped
+    );
+
+    if test.failed > 0 {
+        bail!("smoke test failed");
+    }
+
+    Ok(())
+}
+
+struct SmokeTest<'a> {
+    client: Client,
+    base_url: &'a str,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+impl SmokeTest<'_> {
+    /// Runs a single check and prints its result. The check returns a value
+    /// that is used by later checks, and a short description of what was
+    /// seen.
+    fn check<T>(
+        &mut self,
+        name: &str,
+        check: impl FnOnce(&Self) -> anyhow::Result<(T, String)>,
+    ) -> Option<T> {
+        let start = Instant::now();
+        let result = check(self);
+        let elapsed = start.elapsed().as_millis();
+
+        match result {
+            Ok((value, detail)) => {
+                self.passed += 1;
+                println!("PASS  {name} ({elapsed}ms): {detail}");
+                Some(value)
+            }
+            Err(error) => {
+                self.failed += 1;
+                println!("FAIL  {name} ({elapsed}ms): {error:#}");
+                None
+            }
+        }
+    }
+
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.skipped += 1;
+        println!("SKIP  {name}: {reason}");
+    }
+
+    fn get(&self, url: &str) -> anyhow::Result<Response> {
+        self.client
+            .get(url)
+            .send()
+            .with_context(|| format!("failed to request {url}"))
+    }
+
+    fn get_json(&self, path: &str) -> anyhow::Result<Value> {
+        let url = format!("{}{path}", self.base_url);
+        let response = self.get(&url)?;
+
+        let status = response.status();
+        ensure!(status == StatusCode::OK, "{url} returned {status}");
+
+        response
+            .json()
+            .with_context(|| format!("{url} did not return JSON"))
+    }
+
+    fn site_metadata(&self, expect_sha: Option<&str>) -> anyhow::Result<((), String)> {
+        let metadata = self.get_json("/api/v1/site_metadata")?;
+
+        let deployed_sha = metadata["deployed_sha"].as_str().unwrap_or("unknown");
+        if let Some(expect_sha) = expect_sha {
+            ensure!(
+                deployed_sha == expect_sha,
+                "expected {expect_sha} to be deployed, found {deployed_sha}"
+            );
+        }
+
+        if metadata["read_only"].as_bool() == Some(true) {
+            bail!("{deployed_sha} is deployed, but the instance is in read-only mode");
+        }
+
+        Ok(((), format!("{deployed_sha} is deployed")))
+    }
+
+    fn summary(&self) -> anyhow::Result<(String, String)> {
+        let summary = self.get_json("/api/v1/summary")?;
+
+        let crate_name = summary["just_updated"][0]["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no recently updated crates"))?
+            .to_string();
+
+        let detail = format!("{crate_name} was updated most recently");
+        Ok((crate_name, detail))
+    }
+
+    fn crate_metadata(&self, crate_name: &str) -> anyhow::Result<(String, String)> {
+        let krate = self.get_json(&format!("/api/v1/crates/{crate_name}"))?;
+
+        let version = krate["crate"]["newest_version"]
+            .as_str()
+            .ok_or_else(|| anyhow!("{crate_name} has no versions"))?
+            .to_string();
+
+        let detail = format!("{crate_name} v{version}");
+        Ok((version, detail))
+    }
+
+    fn search(&self, crate_name: &str) -> anyhow::Result<((), String)> {
+        let results = self.get_json(&format!("/api/v1/crates?q={crate_name}"))?;
+
+        let found = results["crates"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|krate| krate["name"].as_str() == Some(crate_name));
+        ensure!(found, "searching for {crate_name} did not find it");
+
+        let total = results["meta"]["total"].as_i64().unwrap_or_default();
+        Ok(((), format!("{total} results")))
+    }
+
+    fn download(&self, crate_name: &str, version: &str) -> anyhow::Result<((), String)> {
+        let url = format!(
+            "{}/api/v1/crates/{crate_name}/{version}/download",
+            self.base_url
+        );
+        let response = self.get(&url)?;
+
+        let status = response.status();
+        ensure!(status.is_redirection(), "{url} returned {status}");
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or_else(|| anyhow!("{url} did not return a location"))?;
+
+        let response = self
+            .client
+            .head(location)
+            .send()
+            .with_context(|| format!("failed to request {location}"))?;
+
+        let status = response.status();
+        ensure!(status == StatusCode::OK, "{location} returned {status}");
+
+        Ok(((), format!("redirected to {location}")))
+    }
+
+    fn sparse_index(
+        &self,
+        index_url: &str,
+        crate_name: &str,
+        version: &str,
+    ) -> anyhow::Result<((), String)> {
+        let url = format!("{index_url}/config.json");
+        let response = self.get(&url)?;
+
+        let status = response.status();
+        ensure!(status == StatusCode::OK, "{url} returned {status}");
+
+        let path = Repository::relative_index_file_for_url(crate_name);
+        let url = format!("{index_url}/{path}");
+        let response = self.get(&url)?;
+
+        let status = response.status();
+        ensure!(status == StatusCode::OK, "{url} returned {status}");
+
+        let index_file = response.text()?;
+        let found = index_file
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .any(|line| line["vers"].as_str() == Some(version));
+        ensure!(found, "{url} does not contain v{version}");
+
+        Ok(((), format!("{url} contains v{version}")))
+    }
+
+    fn authentication_required(&self) -> anyhow::Result<((), String)> {
+        let url = format!("{}/api/v1/me", self.base_url);
+        let response = self.get(&url)?;
+
+        let status = response.status();
+        ensure!(
+            status == StatusCode::FORBIDDEN,
+            "{url} returned {status} without authentication"
+        );
+
+        Ok(((), format!("{url} returned {status}")))
+    }
+}
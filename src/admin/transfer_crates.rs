@@ -1,7 +1,7 @@
 use crate::{
     admin::dialoguer,
     db,
-    models::{Crate, OwnerKind, User},
+    models::{Crate, NewAuditEvent, OwnerKind, User},
     schema::{crate_owners, crates, users},
 };
 use std::process::exit;
@@ -66,7 +66,7 @@ fn transfer(opts: Opts, conn: &mut PgConnection) {
         .load(conn)
         .unwrap();
 
-    for krate in crates {
+    for krate in &crates {
         let owners = krate.owners(conn).unwrap();
         if owners.len() != 1 {
             println!("warning: not exactly one owner for {}", krate.name);
@@ -78,6 +78,17 @@ fn transfer(opts: Opts, conn: &mut PgConnection) {
         .execute(conn)
         .unwrap();
 
+    NewAuditEvent {
+        details: json!({
+            "from": from.gh_login,
+            "to": to.gh_login,
+            "crates": crates.iter().map(|krate| &krate.name).collect::<Vec<_>>(),
+        }),
+        ..NewAuditEvent::new("admin.transfer_crates")
+    }
+    .insert(conn)
+    .unwrap();
+
     get_confirm("commit?");
 }
 
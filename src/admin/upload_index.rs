@@ -1,8 +1,12 @@
 use crate::admin::dialoguer;
-use crate::storage::Storage;
+use crate::background_jobs::Job;
+use crate::db;
+use crate::storage::{ReplicatedFile, Storage};
 use anyhow::Context;
 use crates_io_index::{Repository, RepositoryConfig};
-use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::pin::pin;
 
 #[derive(clap::Parser, Debug)]
 #[command(
@@ -35,19 +39,57 @@ pub fn run(opts: Opts) -> anyhow::Result<()> {
         .context("Failed to initialize tokio runtime")
         .unwrap();
 
+    // The replicas are updated by background jobs, so a database connection
+    // is only needed if there are any.
+    let mut conn = if storage.has_replicas() {
+        Some(db::oneoff_connection().context("Failed to connect to the database")?)
+    } else {
+        None
+    };
+
     let pb = ProgressBar::new(files.len() as u64);
     pb.set_style(ProgressStyle::with_template("{bar:60} ({pos}/{len}, ETA {eta})").unwrap());
 
-    for file in files.iter().progress_with(pb.clone()) {
+    let mut unreadable = 0;
+    let contents = files.iter().filter_map(|file| {
         let crate_name = file.file_name().unwrap().to_str().unwrap();
         let path = repo.index_file(crate_name);
-        if !path.exists() {
-            pb.suspend(|| println!("skipping file `{crate_name}`"));
-            continue;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Some((crate_name.to_string(), Some(contents))),
+            Err(error) => {
+                if path.exists() {
+                    pb.suspend(|| println!("failed to read file `{crate_name}`: {error}"));
+                    unreadable += 1;
+                } else {
+                    pb.suspend(|| println!("skipping file `{crate_name}`"));
+                }
+                pb.inc(1);
+                None
+            }
+        }
+    });
+
+    let mut failed = 0;
+    rt.block_on(async {
+        let mut results = pin!(storage.sync_index_batch(contents));
+        while let Some((crate_name, result)) = results.next().await {
+            if let Err(error) = result {
+                pb.suspend(|| println!("failed to upload `{crate_name}`: {error}"));
+                failed += 1;
+            } else if let Some(conn) = &mut conn {
+                let file = ReplicatedFile::index_file(&crate_name);
+                if let Err(error) = Job::enqueue_replication(&storage, file, conn) {
+                    pb.suspend(|| println!("failed to replicate `{crate_name}`: {error}"));
+                    failed += 1;
+                }
+            }
+            pb.inc(1);
         }
+    });
+    pb.finish();
 
-        let contents = std::fs::read_to_string(&path)?;
-        rt.block_on(storage.sync_index(crate_name, Some(contents)))?;
+    if unreadable + failed > 0 {
+        anyhow::bail!("failed to read {unreadable} and to upload {failed} files");
     }
 
     println!(
@@ -1,69 +1,97 @@
 use crate::{
     admin::dialoguer,
     db,
-    models::{Crate, Version},
+    models::{Crate, NewAuditEvent, SecurityYank, Version},
     schema::versions,
 };
 
 use crate::background_jobs::Job;
+use anyhow::{anyhow, Context};
 use diesel::prelude::*;
 
 #[derive(clap::Parser, Debug)]
 #[command(
     name = "yank-version",
-    about = "Yank a crate from the database and index."
+    about = "Yank a crate from the database and index, or unyank it with `--undo`."
 )]
 pub struct Opts {
     /// Name of the crate
     crate_name: String,
-    /// Version number that should be deleted
+    /// Version number that should be yanked
     version: String,
+    /// Why the version is yanked or unyanked, as recorded in the audit log
+    #[arg(long)]
+    reason: String,
+    /// Unyank the version instead of yanking it
+    #[arg(long)]
+    undo: bool,
     /// Don't ask for confirmation: yes, we are sure. Best for scripting.
     #[arg(short, long)]
     yes: bool,
 }
 
-pub fn run(opts: Opts) {
-    let mut conn = db::oneoff_connection().unwrap();
-    conn.transaction::<_, diesel::result::Error, _>(|conn| {
-        yank(opts, conn);
-        Ok(())
-    })
-    .unwrap()
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = &mut db::oneoff_connection().context("Failed to connect to the database")?;
+    conn.transaction(|conn| yank(opts, conn))
 }
 
-fn yank(opts: Opts, conn: &mut PgConnection) {
+fn yank(opts: Opts, conn: &mut PgConnection) -> anyhow::Result<()> {
     let Opts {
         crate_name,
         version,
+        reason,
+        undo,
         yes,
     } = opts;
-    let krate: Crate = Crate::by_name(&crate_name).first(conn).unwrap();
+    let yanked = !undo;
+    let action = if yanked { "yank" } else { "unyank" };
+
+    let krate: Crate = Crate::by_name(&crate_name)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("crate `{crate_name}` not found"))?;
     let v: Version = Version::belonging_to(&krate)
         .filter(versions::num.eq(&version))
         .first(conn)
-        .unwrap();
+        .optional()?
+        .ok_or_else(|| anyhow!("version `{version}` of crate `{crate_name}` not found"))?;
 
-    if v.yanked {
-        println!("Version {version} of crate {crate_name} is already yanked");
-        return;
+    if v.yanked == yanked {
+        println!("Version {version} of crate {crate_name} is already {action}ed");
+        return Ok(());
     }
 
     if !yes {
         let prompt = format!(
-            "Are you sure you want to yank {crate_name}#{version} ({})?",
+            "Are you sure you want to {action} {crate_name}#{version} ({})?",
             v.id
         );
         if !dialoguer::confirm(&prompt) {
-            return;
+            return Ok(());
         }
     }
 
-    println!("yanking version {} ({})", v.num, v.id);
+    println!("{action}ing version {} ({})", v.num, v.id);
     diesel::update(&v)
-        .set(versions::yanked.eq(true))
-        .execute(conn)
-        .unwrap();
+        .set(versions::yanked.eq(yanked))
+        .execute(conn)?;
+
+    // Like on the API, unyanking withdraws the security advisory of the yank.
+    if !yanked {
+        SecurityYank::withdraw(v.id, conn)?;
+    }
+
+    NewAuditEvent {
+        details: json!({ "crate": krate.name, "version": v.num, "reason": reason }),
+        ..NewAuditEvent::new(if yanked {
+            "admin.yank_version"
+        } else {
+            "admin.unyank_version"
+        })
+    }
+    .insert(conn)?;
+
+    Job::enqueue_sync_to_index(&krate.name, conn)?;
 
-    Job::enqueue_sync_to_index(&krate.name, conn).unwrap();
+    Ok(())
 }
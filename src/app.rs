@@ -6,6 +6,7 @@ use std::ops::Deref;
 use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
+use crate::download_samples::DownloadSampler;
 use crate::downloads_counter::DownloadsCounter;
 use crate::email::Emails;
 use crate::github::{GitHubClient, RealGitHubClient};
@@ -42,11 +43,21 @@ pub struct App {
     /// `version_id` is only cached under the canonical spelling of the crate name.
     pub(crate) version_id_cacher: Cache<(String, String), i32>,
 
+    /// Cache the total downloads of a crate, as requested by its name
+    ///
+    /// This is used by the total downloads endpoint, which is polled by badge services. The
+    /// totals only change when the `update_downloads` job runs, so caching them for a few
+    /// minutes is fine.
+    pub(crate) crate_downloads_cacher: Cache<String, i32>,
+
     /// Count downloads and periodically persist them in the database
     pub downloads_counter: DownloadsCounter,
 
+    /// Sample download redirects and periodically persist them in the database
+    pub download_sampler: DownloadSampler,
+
     /// Backend used to send emails
-    pub emails: Emails,
+    pub emails: Arc<Emails>,
 
     pub storage: Arc<Storage>,
 
@@ -159,6 +170,10 @@ impl App {
             .time_to_live(config.version_id_cache_ttl)
             .build();
 
+        let crate_downloads_cacher = CacheBuilder::new(config.crate_downloads_cache_size)
+            .time_to_live(config.crate_downloads_cache_ttl)
+            .build();
+
         let fastboot_client = match config.use_fastboot.as_deref() {
             Some("staging-experimental") => Some(reqwest::Client::new()),
             _ => None,
@@ -170,8 +185,10 @@ impl App {
             github,
             github_oauth,
             version_id_cacher,
+            crate_downloads_cacher,
             downloads_counter: DownloadsCounter::new(),
-            emails: Emails::from_environment(&config),
+            download_sampler: DownloadSampler::new(config.download_sample_rate),
+            emails: Arc::new(Emails::from_environment(&config)),
             storage: Arc::new(Storage::from_config(&config.storage)),
             service_metrics: ServiceMetrics::new().expect("could not initialize service metrics"),
             instance_metrics,
@@ -1,13 +1,14 @@
 use crate::controllers;
-use crate::controllers::util::RequestPartsExt;
+use crate::controllers::util::{audit_event, RequestPartsExt};
 use crate::middleware::log_request::RequestLogExt;
 use crate::middleware::session::RequestSession;
 use crate::models::token::{CrateScope, EndpointScope};
-use crate::models::{ApiToken, User};
+use crate::models::{ApiToken, NewAuditEvent, User};
 use crate::util::errors::{
-    account_locked, forbidden, internal, AppError, AppResult, InsecurelyGeneratedTokenRevoked,
+    account_locked, forbidden, internal, AppError, AppResult, BoxedAppError,
+    InsecurelyGeneratedTokenRevoked,
 };
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::PgConnection;
 use http::header;
 
@@ -16,6 +17,8 @@ pub struct AuthCheck {
     allow_token: bool,
     endpoint_scope: Option<EndpointScope>,
     crate_name: Option<String>,
+    any_crate: bool,
+    require_admin: bool,
 }
 
 impl AuthCheck {
@@ -27,6 +30,8 @@ impl AuthCheck {
             allow_token: true,
             endpoint_scope: None,
             crate_name: None,
+            any_crate: false,
+            require_admin: false,
         }
     }
 
@@ -36,6 +41,8 @@ impl AuthCheck {
             allow_token: false,
             endpoint_scope: None,
             crate_name: None,
+            any_crate: false,
+            require_admin: false,
         }
     }
 
@@ -44,6 +51,8 @@ impl AuthCheck {
             allow_token: self.allow_token,
             endpoint_scope: Some(endpoint_scope),
             crate_name: self.crate_name.clone(),
+            any_crate: self.any_crate,
+            require_admin: self.require_admin,
         }
     }
 
@@ -52,6 +61,31 @@ impl AuthCheck {
             allow_token: self.allow_token,
             endpoint_scope: self.endpoint_scope,
             crate_name: Some(crate_name.to_string()),
+            any_crate: self.any_crate,
+            require_admin: self.require_admin,
+        }
+    }
+
+    /// Allows tokens with any crate scopes, for endpoints that deal with
+    /// multiple crates and apply the crate scopes themselves.
+    pub fn for_any_crate(&self) -> Self {
+        Self {
+            allow_token: self.allow_token,
+            endpoint_scope: self.endpoint_scope,
+            crate_name: self.crate_name.clone(),
+            any_crate: true,
+            require_admin: self.require_admin,
+        }
+    }
+
+    /// Only allows users that are members of the crates.io team.
+    pub fn require_admin(&self) -> Self {
+        Self {
+            allow_token: self.allow_token,
+            endpoint_scope: self.endpoint_scope,
+            crate_name: self.crate_name.clone(),
+            any_crate: self.any_crate,
+            require_admin: true,
         }
     }
 
@@ -67,20 +101,24 @@ impl AuthCheck {
             if !self.allow_token {
                 let error_message =
                     "API Token authentication was explicitly disallowed for this API";
-                return Err(internal(error_message).chain(forbidden()));
+                return Err(reject_token(request, conn, token, error_message));
             }
 
             if !self.endpoint_scope_matches(token.endpoint_scopes.as_ref()) {
                 let error_message = "Endpoint scope mismatch";
-                return Err(internal(error_message).chain(forbidden()));
+                return Err(reject_token(request, conn, token, error_message));
             }
 
             if !self.crate_scope_matches(token.crate_scopes.as_ref()) {
                 let error_message = "Crate scope mismatch";
-                return Err(internal(error_message).chain(forbidden()));
+                return Err(reject_token(request, conn, token, error_message));
             }
         }
 
+        if self.require_admin && !auth.user().is_admin {
+            return Err(forbidden());
+        }
+
         Ok(auth)
     }
 
@@ -105,6 +143,9 @@ impl AuthCheck {
             // The token does not have any crate scopes.
             (Some(token_scopes), _) if token_scopes.is_empty() => true,
 
+            // The token has crate scopes, and the endpoint applies them itself.
+            (Some(_), None) if self.any_crate => true,
+
             // The token has crate scopes, but the endpoint does not deal with crates.
             (Some(_), None) => false,
 
@@ -157,6 +198,25 @@ impl Authentication {
     }
 }
 
+/// Records the rejected use of an API token as an audit event, and returns
+/// the corresponding error.
+fn reject_token<T: RequestPartsExt>(
+    req: &T,
+    conn: &mut PgConnection,
+    token: &ApiToken,
+    error_message: &'static str,
+) -> BoxedAppError {
+    NewAuditEvent {
+        user_id: Some(token.user_id),
+        api_token_id: Some(token.id),
+        details: json!({ "reason": error_message, "path": req.uri().path() }),
+        ..audit_event(req, "token.rejected")
+    }
+    .record(conn);
+
+    internal(error_message).chain(forbidden())
+}
+
 #[instrument(skip_all)]
 fn authenticate_via_cookie<T: RequestPartsExt>(
     req: &T,
@@ -175,6 +235,15 @@ fn authenticate_via_cookie<T: RequestPartsExt>(
         .map_err(|err| err.chain(internal("user_id from cookie not found in database")))?;
 
     ensure_not_locked(&user)?;
+    ensure_session_not_revoked(req, &user).map_err(|error| {
+        NewAuditEvent {
+            user_id: Some(user.id),
+            ..audit_event(req, "session.revoked")
+        }
+        .record(conn);
+
+        error
+    })?;
 
     req.request_log().add("uid", id);
 
@@ -249,6 +318,27 @@ fn ensure_not_locked(user: &User) -> AppResult<()> {
     Ok(())
 }
 
+/// Sessions that were started before the sessions of the user were revoked
+/// (e.g. because the account was compromised) are rejected. Sessions without
+/// a start time predate the tracking of it, and are rejected too.
+fn ensure_session_not_revoked<T: RequestPartsExt>(req: &T, user: &User) -> AppResult<()> {
+    let Some(revoked_at) = user.sessions_revoked_at else {
+        return Ok(());
+    };
+
+    let started_at = req
+        .session()
+        .get("session_started_at")
+        .and_then(|s| s.parse::<i64>().ok())
+        .and_then(NaiveDateTime::from_timestamp_millis);
+
+    if started_at.map_or(true, |started_at| started_at < revoked_at) {
+        return Err(internal("session was revoked").chain(forbidden()));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,15 +4,18 @@ use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::sql_types::{Int2, Jsonb, Text};
 use paste::paste;
 use reqwest::blocking::Client;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::panic::AssertUnwindSafe;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
 use crate::db::ConnectionPool;
-use crate::storage::Storage;
+use crate::email::Emails;
+use crate::storage::{ReplicatedFile, Storage};
 use crate::swirl::errors::EnqueueError;
 use crate::swirl::PerformError;
 use crate::worker;
+use crate::worker::audit_export::AuditSink;
 use crate::worker::cloudfront::CloudFront;
 use crate::worker::fastly::Fastly;
 use crates_io_index::Repository;
@@ -21,6 +24,11 @@ pub const PRIORITY_DEFAULT: i16 = 0;
 pub const PRIORITY_RENDER_README: i16 = 50;
 pub const PRIORITY_SYNC_TO_INDEX: i16 = 100;
 
+pub const QUEUE_DEFAULT: &str = "default";
+/// Readmes are rendered by a separate runner, so that large or slow readmes
+/// don't delay index updates and other jobs.
+pub const QUEUE_READMES: &str = "readmes";
+
 macro_rules! jobs {
     {
         $vis:vis enum $name:ident {
@@ -77,14 +85,33 @@ macro_rules! job_variant_from_value {
 
 jobs! {
     pub enum Job {
+        AggregateFeatureUsage(AggregateFeatureUsageJob),
+        AggregateMsrvStats(AggregateMsrvStatsJob),
+        ArchiveVersions(ArchiveVersionsJob),
+        BatchedBackfill(BatchedBackfillJob),
+        CheckRepositories(CheckRepositoriesJob),
         DailyDbMaintenance,
+        DetectDownloadAnomalies(DetectDownloadAnomaliesJob),
         DumpDb(DumpDbJob),
+        ExpireOwnershipInvitations(ExpireOwnershipInvitationsJob),
+        ExpirePublishDryRuns,
+        ExportAuditEvents(ExportAuditEventsJob),
+        ManageDownloadsPartitions(ManageDownloadsPartitionsJob),
         NormalizeIndex(NormalizeIndexJob),
+        NotifyAccountCompromise(NotifyAccountCompromiseJob),
+        NudgeSingleOwnerCrates(NudgeSingleOwnerCratesJob),
         RenderAndUploadReadme(RenderAndUploadReadmeJob),
+        RepairReadmes(RepairReadmesJob),
+        ReplicateFile(ReplicatedFile),
+        RestoreCrateFile(RestoreCrateFileJob),
+        SendSubscriptionDigests(SendSubscriptionDigestsJob),
         SquashIndex,
+        SweepOrphanedFiles(SweepOrphanedFilesJob),
         SyncToGitIndex(SyncToIndexJob),
         SyncToSparseIndex(SyncToIndexJob),
         UpdateDownloads,
+        VerifyChecksums(VerifyChecksumsJob),
+        VerifyReproducibility(VerifyReproducibilityJob),
     }
 }
 
@@ -166,10 +193,94 @@ impl Job {
         Ok(())
     }
 
+    /// Enqueue a job that mirrors a file of the primary storage to the
+    /// replica buckets, unless no replicas are configured.
+    ///
+    /// This has to be called after the file was written or deleted, within
+    /// the transaction of the related database changes, if there is one.
+    pub fn enqueue_replication(
+        storage: &Storage,
+        file: ReplicatedFile,
+        conn: &mut PgConnection,
+    ) -> Result<(), EnqueueError> {
+        if !storage.has_replicas() {
+            return Ok(());
+        }
+
+        Self::ReplicateFile(file).enqueue(conn)
+    }
+
+    /// Returns the ids of the versions whose README render job is still in
+    /// the queue, including the ones that are currently being rendered.
+    pub fn pending_readme_renders(conn: &mut PgConnection) -> QueryResult<HashSet<i32>> {
+        use crate::schema::background_jobs::dsl::*;
+        use diesel::dsl::sql;
+        use diesel::sql_types::Integer;
+
+        let version_ids = background_jobs
+            .filter(job_type.eq("render_and_upload_readme"))
+            .select(sql::<Integer>("(data->>'version_id')::int"))
+            .load::<i32>(conn)?;
+
+        Ok(version_ids.into_iter().collect())
+    }
+
+    pub fn aggregate_feature_usage(retention_days: i32) -> Self {
+        Self::AggregateFeatureUsage(AggregateFeatureUsageJob { retention_days })
+    }
+
+    pub fn aggregate_msrv_stats(days: i32) -> Self {
+        Self::AggregateMsrvStats(AggregateMsrvStatsJob { days })
+    }
+
+    pub fn archive_versions(idle_days: i32, batch_size: i64) -> Self {
+        Self::ArchiveVersions(ArchiveVersionsJob {
+            idle_days,
+            batch_size,
+        })
+    }
+
+    pub fn batched_backfill(
+        table: String,
+        set: String,
+        filter: String,
+        batch_size: i64,
+        max_batches: i64,
+    ) -> Self {
+        Self::BatchedBackfill(BatchedBackfillJob {
+            table,
+            set,
+            filter,
+            batch_size,
+            max_batches,
+        })
+    }
+
+    pub fn check_repositories(recheck_days: i32, batch_size: i64) -> Self {
+        Self::CheckRepositories(CheckRepositoriesJob {
+            recheck_days,
+            batch_size,
+        })
+    }
+
     pub fn daily_db_maintenance() -> Self {
         Self::DailyDbMaintenance
     }
 
+    pub fn detect_download_anomalies(
+        baseline_days: i32,
+        min_downloads: i64,
+        threshold: f64,
+        exclude: bool,
+    ) -> Self {
+        Self::DetectDownloadAnomalies(DetectDownloadAnomaliesJob {
+            baseline_days,
+            min_downloads,
+            threshold,
+            exclude,
+        })
+    }
+
     pub fn dump_db(database_url: String, target_name: String) -> Self {
         Self::DumpDb(DumpDbJob {
             database_url,
@@ -177,10 +288,54 @@ impl Job {
         })
     }
 
+    pub fn expire_ownership_invitations(expiration_days: u64) -> Self {
+        Self::ExpireOwnershipInvitations(ExpireOwnershipInvitationsJob { expiration_days })
+    }
+
+    pub fn expire_publish_dry_runs() -> Self {
+        Self::ExpirePublishDryRuns
+    }
+
+    pub fn export_audit_events(batch_size: i64, max_batches: i64, retention_days: i32) -> Self {
+        Self::ExportAuditEvents(ExportAuditEventsJob {
+            batch_size,
+            max_batches,
+            retention_days,
+        })
+    }
+
+    pub fn manage_downloads_partitions(
+        months_ahead: u32,
+        retention_months: u32,
+        dry_run: bool,
+    ) -> Self {
+        Self::ManageDownloadsPartitions(ManageDownloadsPartitionsJob {
+            months_ahead,
+            retention_months,
+            dry_run,
+        })
+    }
+
     pub fn normalize_index(dry_run: bool) -> Self {
         Self::NormalizeIndex(NormalizeIndexJob { dry_run })
     }
 
+    pub fn notify_account_compromise(compromise_id: i32) -> Self {
+        Self::NotifyAccountCompromise(NotifyAccountCompromiseJob { compromise_id })
+    }
+
+    pub fn nudge_single_owner_crates(
+        min_downloads: i32,
+        reminder_days: i32,
+        send_emails: bool,
+    ) -> Self {
+        Self::NudgeSingleOwnerCrates(NudgeSingleOwnerCratesJob {
+            min_downloads,
+            reminder_days,
+            send_emails,
+        })
+    }
+
     pub fn render_and_upload_readme(
         version_id: i32,
         text: String,
@@ -197,10 +352,30 @@ impl Job {
         })
     }
 
+    pub fn restore_crate_file(version_id: i32) -> Self {
+        Self::RestoreCrateFile(RestoreCrateFileJob { version_id })
+    }
+
+    pub fn send_subscription_digests(max_entries: i64) -> Self {
+        Self::SendSubscriptionDigests(SendSubscriptionDigestsJob { max_entries })
+    }
+
     pub fn squash_index() -> Self {
         Self::SquashIndex
     }
 
+    pub fn sweep_orphaned_files(
+        crate_name: Option<String>,
+        min_age_hours: i64,
+        dry_run: bool,
+    ) -> Self {
+        Self::SweepOrphanedFiles(SweepOrphanedFilesJob {
+            crate_name,
+            min_age_hours,
+            dry_run,
+        })
+    }
+
     pub fn sync_to_git_index<T: ToString>(krate: T) -> Self {
         Self::SyncToGitIndex(SyncToIndexJob {
             krate: krate.to_string(),
@@ -217,6 +392,37 @@ impl Job {
         Self::UpdateDownloads
     }
 
+    pub fn repair_readmes(lookback_days: i32, batch_size: i64) -> Self {
+        Self::RepairReadmes(RepairReadmesJob {
+            lookback_days,
+            batch_size,
+        })
+    }
+
+    pub fn verify_checksums(
+        crate_name: Option<String>,
+        after_version_id: i32,
+        batch_size: i64,
+    ) -> Self {
+        Self::VerifyChecksums(VerifyChecksumsJob {
+            crate_name,
+            after_version_id,
+            batch_size,
+        })
+    }
+
+    pub fn verify_reproducibility(version_id: i32) -> Self {
+        Self::VerifyReproducibility(VerifyReproducibilityJob { version_id })
+    }
+
+    /// Returns the name of the queue that the job is processed in.
+    pub fn queue(&self) -> &'static str {
+        match self {
+            Job::RenderAndUploadReadme(_) => QUEUE_READMES,
+            _ => QUEUE_DEFAULT,
+        }
+    }
+
     pub fn enqueue(&self, conn: &mut PgConnection) -> Result<(), EnqueueError> {
         self.enqueue_with_priority(conn, PRIORITY_DEFAULT)
     }
@@ -235,6 +441,7 @@ impl Job {
                 job_type.eq(self.as_type_str()),
                 data.eq(job_data),
                 priority.eq(job_priority),
+                queue.eq(self.queue()),
             ))
             .execute(conn)?;
         Ok(())
@@ -250,12 +457,60 @@ impl Job {
             .as_ref()
             .expect("Application should configure a background runner environment");
         match self {
+            Job::AggregateFeatureUsage(args) => {
+                worker::perform_aggregate_feature_usage(conn, args.retention_days)
+            }
+            Job::AggregateMsrvStats(args) => worker::perform_aggregate_msrv_stats(conn, args.days),
+            Job::ArchiveVersions(args) => {
+                worker::perform_archive_versions(conn, env, args.idle_days, args.batch_size)
+            }
+            Job::BatchedBackfill(args) => {
+                worker::perform_batched_backfill(&mut *fresh_connection(pool)?, &args)
+            }
+            Job::CheckRepositories(args) => {
+                worker::perform_check_repositories(conn, env, args.recheck_days, args.batch_size)
+            }
             Job::DailyDbMaintenance => {
                 worker::perform_daily_db_maintenance(&mut *fresh_connection(pool)?)
             }
+            Job::DetectDownloadAnomalies(args) => worker::perform_detect_download_anomalies(
+                conn,
+                args.baseline_days,
+                args.min_downloads,
+                args.threshold,
+                args.exclude,
+            ),
             Job::DumpDb(args) => worker::perform_dump_db(env, args.database_url, args.target_name),
+            Job::ExpireOwnershipInvitations(args) => {
+                worker::perform_expire_ownership_invitations(conn, env, args.expiration_days)
+            }
+            Job::ExpirePublishDryRuns => worker::perform_expire_publish_dry_runs(env),
+            Job::ExportAuditEvents(args) => worker::perform_export_audit_events(
+                conn,
+                env,
+                args.batch_size,
+                args.max_batches,
+                args.retention_days,
+            ),
+            Job::ManageDownloadsPartitions(args) => worker::perform_manage_downloads_partitions(
+                conn,
+                env,
+                args.months_ahead,
+                args.retention_months,
+                args.dry_run,
+            ),
             Job::SquashIndex => worker::perform_index_squash(env),
             Job::NormalizeIndex(args) => worker::perform_normalize_index(env, args),
+            Job::NotifyAccountCompromise(args) => {
+                worker::perform_notify_account_compromise(conn, env, args.compromise_id)
+            }
+            Job::NudgeSingleOwnerCrates(args) => worker::perform_nudge_single_owner_crates(
+                conn,
+                env,
+                args.min_downloads,
+                args.reminder_days,
+                args.send_emails,
+            ),
             Job::RenderAndUploadReadme(args) => worker::perform_render_and_upload_readme(
                 conn,
                 env,
@@ -265,9 +520,30 @@ impl Job {
                 args.base_url.as_deref(),
                 args.pkg_path_in_vcs.as_deref(),
             ),
+            Job::RepairReadmes(args) => {
+                worker::perform_repair_readmes(conn, env, args.lookback_days, args.batch_size)
+            }
+            Job::ReplicateFile(file) => worker::perform_replicate_file(env, &file),
+            Job::RestoreCrateFile(args) => {
+                worker::perform_restore_crate_file(conn, env, args.version_id)
+            }
+            Job::SendSubscriptionDigests(args) => {
+                worker::perform_send_subscription_digests(conn, env, args.max_entries)
+            }
+            Job::SweepOrphanedFiles(args) => worker::perform_sweep_orphaned_files(
+                conn,
+                env,
+                args.crate_name.as_deref(),
+                args.min_age_hours,
+                args.dry_run,
+            ),
             Job::SyncToGitIndex(args) => worker::sync_to_git_index(env, conn, &args.krate),
             Job::SyncToSparseIndex(args) => worker::sync_to_sparse_index(env, conn, &args.krate),
             Job::UpdateDownloads => worker::perform_update_downloads(&mut *fresh_connection(pool)?),
+            Job::VerifyChecksums(args) => worker::perform_verify_checksums(conn, env, args),
+            Job::VerifyReproducibility(args) => {
+                worker::perform_verify_reproducibility(conn, env, args.version_id)
+            }
         }
     }
 }
@@ -287,12 +563,80 @@ fn fresh_connection(
     Ok(pool.get()?)
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct AggregateFeatureUsageJob {
+    /// Number of days after which snapshots are pruned
+    pub(super) retention_days: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AggregateMsrvStatsJob {
+    /// Number of days to look back for published versions
+    pub(super) days: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchiveVersionsJob {
+    pub(super) idle_days: i32,
+    pub(super) batch_size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchedBackfillJob {
+    /// Name of the table that is backfilled
+    pub(super) table: String,
+    /// The `SET` clause of the `UPDATE` statement, e.g. `foo = bar`
+    pub(super) set: String,
+    /// The `WHERE` clause that matches the rows that still need to be
+    /// backfilled, e.g. `foo IS NULL`
+    pub(super) filter: String,
+    pub(super) batch_size: i64,
+    /// Maximum number of batches before the backfill continues in a new job
+    pub(super) max_batches: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CheckRepositoriesJob {
+    /// Minimum number of days between two checks of the same repository
+    pub(super) recheck_days: i32,
+    pub(super) batch_size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DetectDownloadAnomaliesJob {
+    pub(super) baseline_days: i32,
+    pub(super) min_downloads: i64,
+    pub(super) threshold: f64,
+    pub(super) exclude: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DumpDbJob {
     pub(super) database_url: String,
     pub(super) target_name: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct ExpireOwnershipInvitationsJob {
+    pub(super) expiration_days: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportAuditEventsJob {
+    pub(super) batch_size: i64,
+    pub(super) max_batches: i64,
+    pub(super) retention_days: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ManageDownloadsPartitionsJob {
+    /// Number of months after the current one to create partitions for
+    pub(super) months_ahead: u32,
+    /// Number of months before the current one whose partitions are kept
+    pub(super) retention_months: u32,
+    pub(super) dry_run: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct AddCrateJob {
     pub(super) krate: crates_io_index::Crate,
@@ -319,6 +663,20 @@ pub struct NormalizeIndexJob {
     pub dry_run: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct NotifyAccountCompromiseJob {
+    pub(super) compromise_id: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NudgeSingleOwnerCratesJob {
+    /// Minimum number of downloads of the crates whose owners are nudged
+    pub(super) min_downloads: i32,
+    /// Minimum number of days between two emails about the same crate
+    pub(super) reminder_days: i32,
+    pub(super) send_emails: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RenderAndUploadReadmeJob {
     pub(super) version_id: i32,
@@ -328,12 +686,56 @@ pub struct RenderAndUploadReadmeJob {
     pub(super) pkg_path_in_vcs: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RepairReadmesJob {
+    pub(super) lookback_days: i32,
+    pub(super) batch_size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RestoreCrateFileJob {
+    pub(super) version_id: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SendSubscriptionDigestsJob {
+    pub(super) max_entries: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SweepOrphanedFilesJob {
+    /// Only sweep the files of this crate, instead of all crates that have
+    /// files in the storage
+    pub(super) crate_name: Option<String>,
+    /// Files that were modified more recently are kept, since they may belong
+    /// to a publish whose transaction is not committed yet
+    pub(super) min_age_hours: i64,
+    /// Only report orphaned files instead of deleting them
+    pub(super) dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyChecksumsJob {
+    /// Only verify the crate files of this crate, instead of all of them
+    pub(super) crate_name: Option<String>,
+    /// The ID of the last version that was verified by the previous batch
+    pub(super) after_version_id: i32,
+    pub(super) batch_size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyReproducibilityJob {
+    pub(super) version_id: i32,
+}
+
 pub struct Environment {
     index: Arc<Mutex<Repository>>,
     http_client: AssertUnwindSafe<Client>,
     cloudfront: Option<CloudFront>,
     fastly: Option<Fastly>,
     pub storage: AssertUnwindSafe<Arc<Storage>>,
+    emails: Arc<Emails>,
+    audit_sink: Option<AuditSink>,
 }
 
 impl Environment {
@@ -343,6 +745,8 @@ impl Environment {
         cloudfront: Option<CloudFront>,
         fastly: Option<Fastly>,
         storage: Arc<Storage>,
+        emails: Arc<Emails>,
+        audit_sink: Option<AuditSink>,
     ) -> Self {
         Self::new_shared(
             Arc::new(Mutex::new(index)),
@@ -350,6 +754,8 @@ impl Environment {
             cloudfront,
             fastly,
             storage,
+            emails,
+            audit_sink,
         )
     }
 
@@ -359,6 +765,8 @@ impl Environment {
         cloudfront: Option<CloudFront>,
         fastly: Option<Fastly>,
         storage: Arc<Storage>,
+        emails: Arc<Emails>,
+        audit_sink: Option<AuditSink>,
     ) -> Self {
         Self {
             index,
@@ -366,6 +774,8 @@ impl Environment {
             cloudfront,
             fastly,
             storage: AssertUnwindSafe(storage),
+            emails,
+            audit_sink,
         }
     }
 
@@ -388,4 +798,12 @@ impl Environment {
     pub(crate) fn fastly(&self) -> Option<&Fastly> {
         self.fastly.as_ref()
     }
+
+    pub(crate) fn emails(&self) -> &Emails {
+        &self.emails
+    }
+
+    pub(crate) fn audit_sink(&self) -> Option<&AuditSink> {
+        self.audit_sink.as_ref()
+    }
 }
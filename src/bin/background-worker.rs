@@ -1,12 +1,16 @@
 //! Runs enqueued background jobs
 //!
 //! This binary will loop until interrupted. It will run all jobs in the
-//! background queue, sleeping for 1 second whenever the queue is empty. If we
+//! background queues, sleeping for 1 second whenever a queue is empty. If we
 //! are unable to spawn workers to run jobs (either because we couldn't connect
 //! to the DB, an error occurred while loading, or we just never heard back from
 //! the worker thread), we will rebuild the runner and try again up to 5 times.
 //! After the 5th occurrence, we will panic.
 //!
+//! Readmes are rendered by a separate runner, which gives up on a job after
+//! `README_RENDER_MAX_RETRIES` failed attempts and moves it to the
+//! `failed_background_jobs` table.
+//!
 //! Usage:
 //!      cargo run --bin background-worker
 
@@ -17,13 +21,14 @@ extern crate tracing;
 
 use crates_io::config;
 use crates_io::storage::Storage;
+use crates_io::worker::audit_export::AuditSink;
 use crates_io::worker::cloudfront::CloudFront;
-use crates_io::{background_jobs::*, db, ssh};
+use crates_io::{background_jobs::*, db, env_optional, ssh, Emails};
 use crates_io_index::{Repository, RepositoryConfig};
 use reqwest::blocking::Client;
 use secrecy::ExposeSecret;
 use std::sync::{Arc, Mutex};
-use std::thread::sleep;
+use std::thread::{self, sleep};
 use std::time::{Duration, Instant};
 
 use crates_io::swirl;
@@ -75,23 +80,63 @@ fn main() {
 
     let cloudfront = CloudFront::from_environment();
     let fastly = Fastly::from_environment();
+    let audit_sink = AuditSink::from_environment();
     let storage = Arc::new(Storage::from_config(&config.storage));
+    let emails = Arc::new(Emails::from_environment(&config));
 
     let client = Client::builder()
         .timeout(Duration::from_secs(45))
         .build()
         .expect("Couldn't build client");
 
-    let environment = Environment::new_shared(repository, client, cloudfront, fastly, storage);
+    let environment = Environment::new_shared(
+        repository, client, cloudfront, fastly, storage, emails, audit_sink,
+    );
 
     let environment = Arc::new(Some(environment));
 
-    let build_runner =
-        || swirl::Runner::production_runner(environment.clone(), db_url.clone(), job_start_timeout);
+    let readme_max_retries = env_optional("README_RENDER_MAX_RETRIES").unwrap_or(5);
+
+    let readme_runner = {
+        let environment = environment.clone();
+        let db_url = db_url.clone();
+        move || {
+            swirl::Runner::production_runner(environment.clone(), db_url.clone(), job_start_timeout)
+                .with_queue(QUEUE_READMES, Some(readme_max_retries))
+        }
+    };
+
+    let default_runner = move || {
+        swirl::Runner::production_runner(environment.clone(), db_url.clone(), job_start_timeout)
+            .with_queue(QUEUE_DEFAULT, None)
+    };
+
+    let threads = vec![
+        thread::spawn(move || run_jobs(QUEUE_READMES, readme_runner)),
+        thread::spawn(move || run_jobs(QUEUE_DEFAULT, default_runner)),
+    ];
+
+    // The runners only return by panicking. If one of them does, the whole
+    // process has to go down so that it gets restarted, instead of silently
+    // continuing with only one of the queues being worked on.
+    loop {
+        if let Some(thread) = threads.iter().position(|thread| thread.is_finished()) {
+            let result = threads.into_iter().nth(thread).unwrap().join();
+            if let Err(panic) = result {
+                std::panic::resume_unwind(panic);
+            }
+            panic!("A job runner exited unexpectedly. Restarting the process");
+        }
+        sleep(Duration::from_secs(1));
+    }
+}
 
+/// Runs the jobs of a queue until interrupted, rebuilding the runner after
+/// errors.
+fn run_jobs(queue: &str, build_runner: impl Fn() -> swirl::Runner) {
     let mut runner = build_runner();
 
-    info!("Runner booted, running jobs");
+    info!(queue, "Runner booted, running jobs");
 
     let mut failure_count = 0;
 
@@ -99,7 +144,7 @@ fn main() {
         if let Err(e) = runner.run_all_pending_jobs() {
             failure_count += 1;
             if failure_count < 5 {
-                warn!(?failure_count, err = ?e, "Error running jobs -- retrying");
+                warn!(queue, ?failure_count, err = ?e, "Error running jobs -- retrying");
                 runner = build_runner();
             } else {
                 panic!("Failed to begin running jobs 5 times. Restarting the process");
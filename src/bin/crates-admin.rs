@@ -4,17 +4,34 @@
 extern crate tracing;
 
 use crates_io::admin::{
-    delete_crate, delete_version, enqueue_job, git_import, migrate, populate, render_readmes,
-    test_pagerduty, transfer_crates, upload_index, verify_token, yank_version,
+    account_compromise, audit_crate_files, backfill_tarball_info, bulk_yank, check_migrations,
+    delete_crate, delete_version, download_anomalies, enqueue_job, export_bundle, fix_data,
+    git_import, import_registry, migrate, populate, reconcile_downloads, render_readmes,
+    scan_reports, smoke_test, test_pagerduty, transfer_crates, upload_index, verify_token,
+    yank_version,
 };
 
+#[cfg(feature = "seed")]
+use crates_io::admin::seed;
+
 #[derive(clap::Parser, Debug)]
 #[command(name = "crates-admin")]
 enum Command {
+    AuditCrateFiles(audit_crate_files::Opts),
+    BackfillTarballInfo(backfill_tarball_info::Opts),
+    BulkYank(bulk_yank::Opts),
+    CheckMigrations(check_migrations::Opts),
     DeleteCrate(delete_crate::Opts),
     DeleteVersion(delete_version::Opts),
+    DownloadAnomalies(download_anomalies::Opts),
+    ExportBundle(export_bundle::Opts),
     Populate(populate::Opts),
+    ReconcileDownloads(reconcile_downloads::Opts),
     RenderReadmes(render_readmes::Opts),
+    ScanReports(scan_reports::Opts),
+    #[cfg(feature = "seed")]
+    Seed(seed::Opts),
+    SmokeTest(smoke_test::Opts),
     TestPagerduty(test_pagerduty::Opts),
     TransferCrates(transfer_crates::Opts),
     VerifyToken(verify_token::Opts),
@@ -22,8 +39,13 @@ enum Command {
     UploadIndex(upload_index::Opts),
     YankVersion(yank_version::Opts),
     GitImport(git_import::Opts),
+    ImportRegistry(import_registry::Opts),
+    #[clap(subcommand)]
+    AccountCompromise(account_compromise::Command),
     #[clap(subcommand)]
     EnqueueJob(enqueue_job::Command),
+    #[clap(subcommand)]
+    FixData(fix_data::Command),
 }
 
 fn main() -> anyhow::Result<()> {
@@ -39,18 +61,32 @@ fn main() -> anyhow::Result<()> {
     span.record("command", tracing::field::debug(&command));
 
     match command {
+        Command::AuditCrateFiles(opts) => audit_crate_files::run(opts)?,
+        Command::BackfillTarballInfo(opts) => backfill_tarball_info::run(opts)?,
+        Command::BulkYank(opts) => bulk_yank::run(opts)?,
+        Command::CheckMigrations(opts) => check_migrations::run(opts)?,
         Command::DeleteCrate(opts) => delete_crate::run(opts),
         Command::DeleteVersion(opts) => delete_version::run(opts),
+        Command::DownloadAnomalies(opts) => download_anomalies::run(opts)?,
+        Command::ExportBundle(opts) => export_bundle::run(opts)?,
         Command::Populate(opts) => populate::run(opts),
+        Command::ReconcileDownloads(opts) => reconcile_downloads::run(opts)?,
         Command::RenderReadmes(opts) => render_readmes::run(opts)?,
+        Command::ScanReports(opts) => scan_reports::run(opts)?,
+        #[cfg(feature = "seed")]
+        Command::Seed(opts) => seed::run(opts)?,
+        Command::SmokeTest(opts) => smoke_test::run(opts)?,
         Command::TestPagerduty(opts) => test_pagerduty::run(opts)?,
         Command::TransferCrates(opts) => transfer_crates::run(opts),
         Command::VerifyToken(opts) => verify_token::run(opts).unwrap(),
         Command::Migrate(opts) => migrate::run(opts)?,
         Command::UploadIndex(opts) => upload_index::run(opts)?,
-        Command::YankVersion(opts) => yank_version::run(opts),
+        Command::YankVersion(opts) => yank_version::run(opts)?,
         Command::GitImport(opts) => git_import::run(opts)?,
+        Command::ImportRegistry(opts) => import_registry::run(opts)?,
+        Command::AccountCompromise(command) => account_compromise::run(command)?,
         Command::EnqueueJob(command) => enqueue_job::run(command)?,
+        Command::FixData(command) => fix_data::run(command)?,
     }
 
     Ok(())
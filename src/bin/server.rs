@@ -13,6 +13,7 @@ use prometheus::Encoder;
 use reqwest::blocking::Client;
 use std::io::{self, Write};
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::signal::unix::{signal, SignalKind};
 use tower::Layer;
 
@@ -33,6 +34,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Start the background thread periodically persisting download counts to the database.
     downloads_counter_thread(app.clone());
 
+    // Start the background thread periodically persisting sampled download redirects.
+    download_samples_thread(app.clone());
+
+    // Start the background thread periodically checkpointing the publish rate limit buckets.
+    publish_rate_limit_checkpoint_thread(app.clone());
+
     // Start the background thread periodically logging instance metrics.
     log_instance_metrics_thread(app.clone());
 
@@ -53,6 +60,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let make_service = axum_router.into_make_service_with_connect_info::<SocketAddr>();
 
+    let restart_requested = Arc::new(AtomicBool::new(false));
+
     let (addr, server) = rt.block_on(async {
         let socket_addr = (app.config.ip, app.config.port).into();
         let server = hyper::Server::bind(&socket_addr).serve(make_service);
@@ -63,11 +72,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         let mut sig_int = signal(SignalKind::interrupt())?;
         let mut sig_term = signal(SignalKind::terminate())?;
+        let watchdog = tokio::spawn(crates_io::watchdog::run(app.clone()));
+        let restart_requested = restart_requested.clone();
         let server = server.with_graceful_shutdown(async move {
-            // Wait for either signal
+            // Wait for either signal, or for the watchdog to request a restart
             tokio::select! {
                 _ = sig_int.recv().fuse() => {},
                 _ = sig_term.recv().fuse() => {},
+                Ok(()) = watchdog.fuse() => restart_requested.store(true, Ordering::SeqCst),
             };
 
             info!("Starting graceful shutdown");
@@ -107,8 +119,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(stats) => stats.log(),
         Err(err) => error!(?err, "downloads_counter error"),
     }
+    if let Err(err) = app.download_sampler.persist(&app) {
+        error!(?err, "download_sampler error");
+    }
+    if let Err(err) = checkpoint_publish_rate_limits(&app) {
+        error!(?err, "publish rate limit checkpoint error");
+    }
 
     info!("Server has gracefully shutdown!");
+
+    // Exit with an error, so that the process manager restarts the server
+    if restart_requested.load(Ordering::SeqCst) {
+        return Err("the resource watchdog requested a restart".into());
+    }
+
     Ok(())
 }
 
@@ -127,6 +151,42 @@ fn downloads_counter_thread(app: Arc<App>) {
     });
 }
 
+fn download_samples_thread(app: Arc<App>) {
+    // Sampling is disabled, so there is nothing to persist
+    if app.config.download_sample_rate <= 0.0 {
+        return;
+    }
+
+    let interval = Duration::from_millis(app.config.downloads_persist_interval_ms as u64);
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+
+        match app.download_sampler.persist(&app) {
+            Ok(count) => debug!(count, "Persisted download redirect samples"),
+            Err(err) => error!(?err, "download_sampler error"),
+        }
+    });
+}
+
+fn publish_rate_limit_checkpoint_thread(app: Arc<App>) {
+    let interval = app.config.rate_limiter.checkpoint_interval;
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+
+        match checkpoint_publish_rate_limits(&app) {
+            Ok(count) => debug!(count, "Checkpointed publish rate limit buckets"),
+            Err(err) => error!(?err, "publish rate limit checkpoint error"),
+        }
+    });
+}
+
+fn checkpoint_publish_rate_limits(app: &App) -> AppResult<usize> {
+    let conn = &mut app.primary_database.get()?;
+    Ok(app.config.rate_limiter.checkpoint(conn)?)
+}
+
 fn log_instance_metrics_thread(app: Arc<App>) {
     // Only run the thread if the configuration is provided
     let interval = if let Some(secs) = app.config.instance_metrics_log_every_seconds {
@@ -1,5 +1,10 @@
-// Sync available crate categories from `src/categories.toml`.
-// Runs when the server is started.
+// Seed the crate categories from `src/categories.toml`.
+// Runs when the database is migrated.
+//
+// Categories are managed through the admin endpoints of the API. The TOML file
+// only provides the initial set of categories, so existing categories are not
+// updated or removed, and slugs of renamed or merged categories are not added
+// again.
 
 use anyhow::{Context, Result};
 use diesel::prelude::*;
@@ -77,38 +82,34 @@ fn categories_from_toml(
 
 pub fn sync_with_connection(toml_str: &str, conn: &mut PgConnection) -> Result<()> {
     use crate::schema::categories::dsl::*;
-    use diesel::pg::upsert::excluded;
+    use crate::schema::category_redirects;
 
     let toml: toml::value::Table =
         toml::from_str(toml_str).context("Could not parse categories toml")?;
 
+    let redirected_slugs: Vec<String> = category_redirects::table
+        .select(category_redirects::old_slug)
+        .load(conn)?;
+
     let to_insert = categories_from_toml(&toml, None)
         .expect("Could not convert categories from TOML")
         .into_iter()
-        .map(|c| {
+        .map(|c| (c.slug.to_lowercase(), c))
+        .filter(|(new_slug, _)| !redirected_slugs.contains(new_slug))
+        .map(|(new_slug, c)| {
             (
-                slug.eq(c.slug.to_lowercase()),
+                slug.eq(new_slug),
                 category.eq(c.name),
                 description.eq(c.description),
             )
         })
         .collect::<Vec<_>>();
 
-    conn.transaction(|conn| {
-        let slugs: Vec<String> = diesel::insert_into(categories)
-            .values(&to_insert)
-            .on_conflict(slug)
-            .do_update()
-            .set((
-                category.eq(excluded(category)),
-                description.eq(excluded(description)),
-            ))
-            .returning(slug)
-            .get_results(conn)?;
-
-        diesel::delete(categories)
-            .filter(slug.ne_all(slugs))
-            .execute(conn)?;
-        Ok(())
-    })
+    diesel::insert_into(categories)
+        .values(&to_insert)
+        .on_conflict(slug)
+        .do_nothing()
+        .execute(conn)?;
+
+    Ok(())
 }
@@ -1,12 +1,22 @@
 mod balance_capacity;
 mod base;
+mod cross_registry;
 mod database_pools;
+mod duplicate_content;
+mod ip_anonymization;
 mod sentry;
 mod server;
+mod version_policy;
+mod watchdog;
 
 pub use self::balance_capacity::BalanceCapacityConfig;
 pub use self::base::Base;
+pub use self::cross_registry::CrossRegistryConfig;
 pub use self::database_pools::{DatabasePools, DbPoolConfig};
+pub use self::duplicate_content::DuplicateContentConfig;
+pub use self::ip_anonymization::IpAnonymization;
 pub use self::sentry::SentryConfig;
-pub(crate) use self::server::domain_name;
 pub use self::server::Server;
+pub(crate) use self::server::{domain_name, DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS};
+pub use self::version_policy::VersionPolicyConfig;
+pub use self::watchdog::WatchdogConfig;
@@ -0,0 +1,66 @@
+use crate::env_optional;
+
+/// How dependencies on crates of other registries are handled on publish.
+///
+/// Crates with such dependencies can only be installed by users that have
+/// configured access to the other registry, so by default they are rejected.
+#[derive(Debug, Default)]
+pub struct CrossRegistryConfig {
+    /// Index URLs of the registries that dependencies may point at without
+    /// any warning.
+    pub allowed_registries: Vec<String>,
+    /// Whether dependencies on any other registry are accepted with a
+    /// warning instead of being rejected.
+    pub warn_on_unknown: bool,
+}
+
+impl CrossRegistryConfig {
+    pub fn from_environment() -> Self {
+        let allowed_registries = match env_optional::<String>("ALLOWED_DEPENDENCY_REGISTRIES") {
+            None => vec![],
+            Some(s) if s.is_empty() => vec![],
+            Some(s) => s.split(',').map(normalize_registry_url).collect(),
+        };
+
+        let warn_on_unknown = match env_optional::<String>("UNKNOWN_DEPENDENCY_REGISTRIES") {
+            None => false,
+            Some(s) if s == "deny" => false,
+            Some(s) if s == "warn" => true,
+            Some(s) => panic!("UNKNOWN_DEPENDENCY_REGISTRIES must be `deny` or `warn`, got `{s}`"),
+        };
+
+        Self {
+            allowed_registries,
+            warn_on_unknown,
+        }
+    }
+
+    pub fn is_allowed(&self, registry: &str) -> bool {
+        let registry = normalize_registry_url(registry);
+        self.allowed_registries.contains(&registry)
+    }
+}
+
+/// Index URLs are compared without trailing slashes, since cargo does not
+/// normalize them before sending them to the registry.
+fn normalize_registry_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_registries() {
+        let config = CrossRegistryConfig {
+            allowed_registries: vec!["https://example.com/index".into()],
+            warn_on_unknown: false,
+        };
+
+        assert!(config.is_allowed("https://example.com/index"));
+        assert!(config.is_allowed("https://example.com/index/"));
+        assert!(!config.is_allowed("https://example.com"));
+        assert!(!config.is_allowed("sparse+https://example.com/index"));
+    }
+}
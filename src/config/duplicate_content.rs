@@ -0,0 +1,41 @@
+use crate::env_optional;
+
+/// Thresholds for flagging new crates whose source files are near-identical
+/// to the files of an existing crate.
+///
+/// Such crates are often name squats, spam, or forks that are easily confused
+/// with the original crate, so they are added to the moderation queue.
+#[derive(Debug)]
+pub struct DuplicateContentConfig {
+    /// Estimated share of identical files, from `0.0` to `1.0`, at which a
+    /// new crate is flagged. Values above `1.0` disable the detection.
+    pub similarity_threshold: f64,
+    /// Crates with fewer distinct source files are never flagged, since
+    /// small crates are often identical by coincidence.
+    pub min_files: usize,
+}
+
+impl Default for DuplicateContentConfig {
+    fn default() -> Self {
+        Self {
+            similarity_threshold: 0.9,
+            min_files: 5,
+        }
+    }
+}
+
+impl DuplicateContentConfig {
+    pub fn from_environment() -> Self {
+        let default = Self::default();
+
+        Self {
+            similarity_threshold: env_optional("DUPLICATE_CONTENT_SIMILARITY_THRESHOLD")
+                .unwrap_or(default.similarity_threshold),
+            min_files: env_optional("DUPLICATE_CONTENT_MIN_FILES").unwrap_or(default.min_files),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.similarity_threshold <= 1.0
+    }
+}
@@ -0,0 +1,110 @@
+use crate::env;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// How client IP addresses are anonymized before they reach the request
+/// logs, error reports and database tables like `audit_events`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum IpAnonymization {
+    /// IP addresses are kept as they are.
+    #[default]
+    Disabled,
+    /// IPv4 addresses are truncated to their `/24` network and IPv6
+    /// addresses to their `/48` network.
+    Truncate,
+    /// IP addresses are replaced by a salted hash, so that requests from the
+    /// same client can still be correlated.
+    Hash { salt: String },
+}
+
+impl IpAnonymization {
+    pub fn from_environment() -> Self {
+        match dotenvy::var("IP_ANONYMIZATION").as_deref() {
+            Err(_) | Ok("") | Ok("none") => Self::Disabled,
+            Ok("truncate") => Self::Truncate,
+            Ok("hash") => Self::Hash {
+                salt: env("IP_ANONYMIZATION_SALT"),
+            },
+            Ok(value) => panic!(
+                "IP_ANONYMIZATION must be one of `none`, `truncate` or `hash`, got `{value}`"
+            ),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::Disabled
+    }
+
+    /// Returns the anonymized form of the given IP address, or `None` if
+    /// anonymization is enabled and the value is not a valid IP address.
+    pub fn anonymize(&self, ip: &str) -> Option<String> {
+        match self {
+            Self::Disabled => Some(ip.to_string()),
+            Self::Truncate => ip.parse().ok().map(truncate).map(|ip| ip.to_string()),
+            Self::Hash { salt } => {
+                let ip: IpAddr = ip.parse().ok()?;
+
+                let mut hasher = Sha256::new();
+                hasher.update(salt.as_bytes());
+                hasher.update(ip.to_string().as_bytes());
+
+                // 64 bits are plenty to tell clients apart, and keep the log
+                // lines short.
+                Some(hex::encode(&hasher.finalize()[..8]))
+            }
+        }
+    }
+}
+
+fn truncate(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            Ipv4Addr::new(a, b, c, 0).into()
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, ..] = ip.segments();
+            Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0).into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_keeps_the_ip() {
+        let anonymization = IpAnonymization::Disabled;
+        assert_some_eq!(anonymization.anonymize("192.0.2.42"), "192.0.2.42");
+        assert_some_eq!(anonymization.anonymize("not an ip"), "not an ip");
+    }
+
+    #[test]
+    fn truncate_keeps_the_network() {
+        let anonymization = IpAnonymization::Truncate;
+        assert_some_eq!(anonymization.anonymize("192.0.2.42"), "192.0.2.0");
+        assert_some_eq!(
+            anonymization.anonymize("2001:db8:85a3:8d3:1319:8a2e:370:7348"),
+            "2001:db8:85a3::"
+        );
+        assert_none!(anonymization.anonymize("not an ip"));
+    }
+
+    #[test]
+    fn hash_is_stable_and_salted() {
+        let salt = "salt".to_string();
+        let anonymization = IpAnonymization::Hash { salt };
+
+        let hash = anonymization.anonymize("192.0.2.42").unwrap();
+        assert_eq!(hash.len(), 16);
+        assert_some_eq!(anonymization.anonymize("192.0.2.42"), hash.as_str());
+        assert_ne!(anonymization.anonymize("192.0.2.43").unwrap(), hash);
+
+        let salt = "pepper".to_string();
+        let other_salt = IpAnonymization::Hash { salt };
+        assert_ne!(other_salt.anonymize("192.0.2.42").unwrap(), hash);
+
+        assert_none!(anonymization.anonymize("not an ip"));
+    }
+}
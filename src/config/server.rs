@@ -8,7 +8,14 @@ use crate::{env, env_optional, Env};
 use super::base::Base;
 use super::database_pools::DatabasePools;
 use crate::config::balance_capacity::BalanceCapacityConfig;
+use crate::config::cross_registry::CrossRegistryConfig;
+use crate::config::duplicate_content::DuplicateContentConfig;
+use crate::config::ip_anonymization::IpAnonymization;
+use crate::config::version_policy::VersionPolicyConfig;
+use crate::config::watchdog::WatchdogConfig;
+use crate::policy::PublishPolicy;
 use crate::storage::StorageConfig;
+use crates_io_tarball::{Compression, ManifestLimits};
 use http::HeaderValue;
 use std::collections::HashSet;
 use std::net::IpAddr;
@@ -16,6 +23,9 @@ use std::time::Duration;
 
 const DEFAULT_VERSION_ID_CACHE_SIZE: u64 = 10_000;
 const DEFAULT_VERSION_ID_CACHE_TTL: u64 = 5 * 60; // 5 minutes
+const DEFAULT_CRATE_DOWNLOADS_CACHE_SIZE: u64 = 10_000;
+const DEFAULT_CRATE_DOWNLOADS_CACHE_TTL: u64 = 5 * 60; // 5 minutes
+pub(crate) const DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS: u64 = 30;
 
 pub struct Server {
     pub base: Base,
@@ -30,6 +40,30 @@ pub struct Server {
     pub gh_client_secret: ClientSecret,
     pub max_upload_size: u64,
     pub max_unpack_size: u64,
+    /// Maximum number of entries in a published tarball.
+    pub max_unpack_entries: u64,
+    /// Maximum decompressed size of a single file in a published tarball.
+    pub max_unpack_file_size: u64,
+    /// Whether paths in a published tarball that only differ in case are
+    /// rejected as duplicates.
+    pub case_insensitive_tarball_paths: bool,
+    /// Whether compiled binaries in a published tarball are rejected.
+    pub deny_tarball_executables: bool,
+    /// Glob patterns of paths that may not appear in a published tarball.
+    pub denied_tarball_paths: Vec<String>,
+    /// Decompressed size from which files in a published tarball are
+    /// reported as a warning in the publish response.
+    pub large_file_warning_size: u64,
+    /// Maximum number of features and dependencies of a published manifest.
+    pub manifest_limits: ManifestLimits,
+    /// Compression formats that are accepted for published crate files.
+    pub allowed_compressions: Vec<Compression>,
+    /// How long the context of crate files that were rejected on publish is
+    /// kept for support requests, or `None` to not keep it at all.
+    pub publish_diagnostics_retention: Option<Duration>,
+    /// How long the name of a deleted crate, or the number of a deleted
+    /// version, can't be published again. A zero duration disables the check.
+    pub deleted_name_cooldown: Duration,
     pub rate_limiter: RateLimiter,
     pub new_version_rate_limit: Option<u32>,
     pub blocked_traffic: Vec<(String, Vec<String>)>,
@@ -40,6 +74,9 @@ pub struct Server {
     pub domain_name: String,
     pub allowed_origins: AllowedOrigins,
     pub downloads_persist_interval_ms: usize,
+    /// Fraction of the download redirects that are recorded in the
+    /// `download_redirect_samples` table, between 0 and 1.
+    pub download_sample_rate: f64,
     pub ownership_invitations_expiration_days: u64,
     pub metrics_authorization_token: Option<String>,
     pub use_test_database_pool: bool,
@@ -48,8 +85,19 @@ pub struct Server {
     pub blocked_routes: HashSet<String>,
     pub version_id_cache_size: u64,
     pub version_id_cache_ttl: Duration,
+    /// Number of crates whose total downloads are cached by the
+    /// `/crates/:crate_id/downloads/total` endpoint.
+    pub crate_downloads_cache_size: u64,
+    pub crate_downloads_cache_ttl: Duration,
     pub cdn_user_agent: String,
     pub balance_capacity: BalanceCapacityConfig,
+    pub cross_registry: CrossRegistryConfig,
+    pub duplicate_content: DuplicateContentConfig,
+    pub version_policy: VersionPolicyConfig,
+    pub publish_policy: PublishPolicy,
+    pub request_budget: Option<Duration>,
+    pub ip_anonymization: IpAnonymization,
+    pub watchdog: WatchdogConfig,
 
     /// Should the server serve the frontend assets in the `dist` directory?
     pub serve_dist: bool,
@@ -94,6 +142,59 @@ impl Default for Server {
     ///   endpoint even with a healthy database pool.
     /// - `BLOCKED_ROUTES`: A comma separated list of HTTP route patterns that are manually blocked
     ///   by an operator (e.g. `/crates/:crate_id/:version/download`).
+    /// - `ALLOWED_DEPENDENCY_REGISTRIES`: A comma separated list of index URLs of other registries
+    ///   that dependencies of published crates may point at.
+    /// - `UNKNOWN_DEPENDENCY_REGISTRIES`: `deny` (default) to reject dependencies on any other
+    ///   registry on publish, or `warn` to accept them with a warning.
+    /// - `DUPLICATE_CONTENT_SIMILARITY_THRESHOLD`: Share of identical source files (from 0.0 to
+    ///   1.0) at which new crates are added to the moderation queue as duplicates of an existing
+    ///   crate. Defaults to 0.9, values above 1.0 disable the detection.
+    /// - `DUPLICATE_CONTENT_MIN_FILES`: Minimum number of distinct source files of crates that
+    ///   are checked for duplicate content. Defaults to 5.
+    /// - `DISABLE_LOWER_VERSION_WARNINGS`: If set, publishing a version that is lower than an
+    ///   existing version of the same release series is not reported as a warning.
+    /// - `VERSION_JUMP_MAX_MAJOR_INCREASE`: Largest increase of the major version (or of the minor
+    ///   version of `0.x` crates) that is not reported as an unusual jump. Defaults to 3. Unusual
+    ///   jumps are reported as a warning and added to the moderation queue.
+    /// - `PUBLISH_POLICY_FILE`: Path of a policy file with additional rules that published crates
+    ///   have to comply with. See the `policy` module for the format of the file.
+    /// - `WEB_REQUEST_BUDGET_MS`: Time budget of a request in milliseconds. The database
+    ///   statement timeouts and storage operations of a request are limited to the time left in
+    ///   its budget. If not set, requests have no deadline.
+    /// - `IP_ANONYMIZATION`: `none` (default), `truncate` or `hash`. Controls how the client IP
+    ///   from the `X-Real-Ip` header is anonymized before it reaches the logs or the database.
+    ///   `hash` requires `IP_ANONYMIZATION_SALT` to be set to a secret value.
+    /// - `WATCHDOG_INTERVAL_SECONDS`: How often the resource watchdog checks the server process.
+    ///   Defaults to 10.
+    /// - `WATCHDOG_MAX_MEMORY_GROWTH_MB`, `WATCHDOG_MAX_BLOCKING_DELAY_MS` and
+    ///   `WATCHDOG_MAX_EVENT_LOOP_STALL_MS`: Thresholds of the resource watchdog. Thresholds that
+    ///   are not set are not checked.
+    /// - `WATCHDOG_RESTART_AFTER`: Number of consecutive watchdog checks exceeding a threshold
+    ///   after which the server shuts down gracefully to be restarted. If not set, exceeded
+    ///   thresholds are only logged.
+    /// - `MAX_UNPACK_ENTRIES`: Maximum number of entries in a published tarball. Defaults to
+    ///   100,000.
+    /// - `MAX_UNPACK_FILE_SIZE`: Maximum decompressed size in bytes of a single file in a published
+    ///   tarball. Defaults to 128 MiB.
+    /// - `CASE_INSENSITIVE_TARBALL_PATHS`: If set, files in published tarballs whose paths only
+    ///   differ in case are rejected as duplicates, like files with identical paths always are.
+    /// - `DENY_TARBALL_EXECUTABLES`: If set, published tarballs that contain compiled binaries,
+    ///   like ELF or PE executables and static libraries, are rejected.
+    /// - `DENIED_TARBALL_PATHS`: A comma separated list of glob patterns, e.g. `*.exe,vendor/**`.
+    ///   Published tarballs that contain files matching any of the patterns are rejected.
+    /// - `LARGE_FILE_WARNING_SIZE`: Decompressed size in bytes from which files in published
+    ///   crates are reported as a warning in the publish response. Defaults to 5 MiB.
+    /// - `MAX_MANIFEST_FEATURES`: Maximum number of features of a published crate. Defaults to
+    ///   300.
+    /// - `MAX_MANIFEST_DEPENDENCIES`: Maximum number of dependency declarations of a published
+    ///   crate, including dev-dependencies. Defaults to 500.
+    /// - `ALLOWED_TARBALL_COMPRESSIONS`: Comma-separated list of compression formats (`gzip`,
+    ///   `zstd`) that are accepted for published crate files. Defaults to `gzip`.
+    /// - `PUBLISH_DIAGNOSTICS_RETENTION_HOURS`: If set, the context of crate files that are rejected
+    ///   on publish, like their manifest and list of files, is kept for this many hours. The
+    ///   publish error then contains a token with which admins can look up the context.
+    /// - `DOWNLOAD_SAMPLE_RATE`: Fraction of the download redirects (between 0 and 1) that are
+    ///   recorded for analytics. Defaults to 0, which disables sampling.
     ///
     /// # Panics
     ///
@@ -136,6 +237,15 @@ impl Default for Server {
             Some(s) => s.split(',').map(String::from).collect(),
         };
 
+        let allowed_compressions = match env_optional::<String>("ALLOWED_TARBALL_COMPRESSIONS") {
+            None => vec![Compression::Gzip],
+            Some(s) => s
+                .split(',')
+                .map(|s| s.trim().parse())
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|err| panic!("ALLOWED_TARBALL_COMPRESSIONS: {err}")),
+        };
+
         let max_blocking_threads = dotenvy::var("SERVER_THREADS")
             .map(|s| s.parse().expect("SERVER_THREADS was not a valid number"))
             .ok();
@@ -153,6 +263,29 @@ impl Default for Server {
             gh_client_secret: ClientSecret::new(env("GH_CLIENT_SECRET")),
             max_upload_size: 10 * 1024 * 1024, // 10 MB default file upload size limit
             max_unpack_size: 512 * 1024 * 1024, // 512 MB max when decompressed
+            max_unpack_entries: env_optional("MAX_UNPACK_ENTRIES").unwrap_or(100_000),
+            max_unpack_file_size: env_optional("MAX_UNPACK_FILE_SIZE").unwrap_or(128 * 1024 * 1024),
+            case_insensitive_tarball_paths: dotenvy::var("CASE_INSENSITIVE_TARBALL_PATHS").is_ok(),
+            deny_tarball_executables: dotenvy::var("DENY_TARBALL_EXECUTABLES").is_ok(),
+            denied_tarball_paths: match env_optional::<String>("DENIED_TARBALL_PATHS") {
+                None => vec![],
+                Some(s) if s.is_empty() => vec![],
+                Some(s) => s
+                    .split(',')
+                    .map(|pattern| pattern.trim().to_string())
+                    .collect(),
+            },
+            large_file_warning_size: env_optional("LARGE_FILE_WARNING_SIZE")
+                .unwrap_or(5 * 1024 * 1024),
+            manifest_limits: manifest_limits(),
+            allowed_compressions,
+            publish_diagnostics_retention: env_optional::<u64>(
+                "PUBLISH_DIAGNOSTICS_RETENTION_HOURS",
+            )
+            .map(|hours| Duration::from_secs(hours * 60 * 60)),
+            deleted_name_cooldown: Duration::from_secs(
+                env_optional::<u64>("DELETED_NAME_COOLDOWN_DAYS").unwrap_or(30) * 24 * 60 * 60,
+            ),
             rate_limiter: Default::default(),
             new_version_rate_limit: env_optional("MAX_NEW_VERSIONS_DAILY"),
             blocked_traffic: blocked_traffic(),
@@ -169,7 +302,10 @@ impl Default for Server {
                         .expect("invalid DOWNLOADS_PERSIST_INTERVAL_MS")
                 })
                 .unwrap_or(60_000), // 1 minute
-            ownership_invitations_expiration_days: 30,
+            download_sample_rate: env_optional::<f64>("DOWNLOAD_SAMPLE_RATE")
+                .unwrap_or(0.0)
+                .clamp(0.0, 1.0),
+            ownership_invitations_expiration_days: DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS,
             metrics_authorization_token: dotenvy::var("METRICS_AUTHORIZATION_TOKEN").ok(),
             use_test_database_pool: false,
             instance_metrics_log_every_seconds: env_optional("INSTANCE_METRICS_LOG_EVERY_SECONDS"),
@@ -182,9 +318,22 @@ impl Default for Server {
             version_id_cache_ttl: Duration::from_secs(
                 env_optional("VERSION_ID_CACHE_TTL").unwrap_or(DEFAULT_VERSION_ID_CACHE_TTL),
             ),
+            crate_downloads_cache_size: env_optional("CRATE_DOWNLOADS_CACHE_SIZE")
+                .unwrap_or(DEFAULT_CRATE_DOWNLOADS_CACHE_SIZE),
+            crate_downloads_cache_ttl: Duration::from_secs(
+                env_optional("CRATE_DOWNLOADS_CACHE_TTL")
+                    .unwrap_or(DEFAULT_CRATE_DOWNLOADS_CACHE_TTL),
+            ),
             cdn_user_agent: dotenvy::var("WEB_CDN_USER_AGENT")
                 .unwrap_or_else(|_| "Amazon CloudFront".into()),
             balance_capacity: BalanceCapacityConfig::from_environment(),
+            cross_registry: CrossRegistryConfig::from_environment(),
+            duplicate_content: DuplicateContentConfig::from_environment(),
+            version_policy: VersionPolicyConfig::from_environment(),
+            publish_policy: PublishPolicy::from_environment(),
+            request_budget: env_optional("WEB_REQUEST_BUDGET_MS").map(Duration::from_millis),
+            ip_anonymization: IpAnonymization::from_environment(),
+            watchdog: WatchdogConfig::from_environment(),
             serve_dist: true,
             serve_html: true,
             use_fastboot: dotenvy::var("USE_FASTBOOT").ok(),
@@ -228,6 +377,15 @@ fn parse_cidr_block(block: &str) -> anyhow::Result<IpNetwork> {
     Ok(cidr)
 }
 
+fn manifest_limits() -> ManifestLimits {
+    let defaults = ManifestLimits::default();
+    ManifestLimits {
+        max_features: env_optional("MAX_MANIFEST_FEATURES").unwrap_or(defaults.max_features),
+        max_dependencies: env_optional("MAX_MANIFEST_DEPENDENCIES")
+            .unwrap_or(defaults.max_dependencies),
+    }
+}
+
 fn blocked_traffic() -> Vec<(String, Vec<String>)> {
     let pattern_list = dotenvy::var("BLOCKED_TRAFFIC").unwrap_or_default();
     parse_traffic_patterns(&pattern_list)
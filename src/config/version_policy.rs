@@ -0,0 +1,40 @@
+use crate::env_optional;
+
+/// Thresholds for warning about the version numbers of new versions that are
+/// probably a mistake.
+///
+/// Typos in version numbers can't be undone, since versions can only be
+/// yanked, and a huge jump of the major version can also be a sign of a
+/// compromised account publishing a malicious version that takes precedence
+/// over all previous ones.
+#[derive(Debug)]
+pub struct VersionPolicyConfig {
+    /// Whether a version that is lower than an existing version of the same
+    /// release series (e.g. `1.2.0` after `1.3.0`) is reported.
+    pub warn_lower_versions: bool,
+    /// Largest increase of the major version, or of the minor version if
+    /// both versions are `0.x`, compared to the highest existing version that
+    /// is not reported as an unusual jump.
+    pub max_major_increase: u64,
+}
+
+impl Default for VersionPolicyConfig {
+    fn default() -> Self {
+        Self {
+            warn_lower_versions: true,
+            max_major_increase: 3,
+        }
+    }
+}
+
+impl VersionPolicyConfig {
+    pub fn from_environment() -> Self {
+        let default = Self::default();
+
+        Self {
+            warn_lower_versions: dotenvy::var("DISABLE_LOWER_VERSION_WARNINGS").is_err(),
+            max_major_increase: env_optional("VERSION_JUMP_MAX_MAJOR_INCREASE")
+                .unwrap_or(default.max_major_increase),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::env_optional;
+use std::time::Duration;
+
+/// Thresholds of the resource watchdog of the server process. See the
+/// `watchdog` module for how they are checked.
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    /// How often the resources of the process are checked.
+    pub interval: Duration,
+    /// Maximum growth of the resident memory since the server was started.
+    pub max_memory_growth: Option<u64>,
+    /// Maximum time it may take until a task on the blocking thread pool
+    /// starts running.
+    pub max_blocking_delay: Option<Duration>,
+    /// Maximum time the async event loop may be stalled.
+    pub max_event_loop_stall: Option<Duration>,
+    /// Number of consecutive checks exceeding a threshold after which the
+    /// server shuts down gracefully, so that it is restarted by the process
+    /// manager. If not set, exceeded thresholds are only logged.
+    pub restart_after: Option<u32>,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            max_memory_growth: None,
+            max_blocking_delay: None,
+            max_event_loop_stall: None,
+            restart_after: None,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn from_environment() -> Self {
+        let default = Self::default();
+
+        Self {
+            interval: env_optional("WATCHDOG_INTERVAL_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.interval),
+            max_memory_growth: env_optional::<u64>("WATCHDOG_MAX_MEMORY_GROWTH_MB")
+                .map(|megabytes| megabytes * 1024 * 1024),
+            max_blocking_delay: env_optional("WATCHDOG_MAX_BLOCKING_DELAY_MS")
+                .map(Duration::from_millis),
+            max_event_loop_stall: env_optional("WATCHDOG_MAX_EVENT_LOOP_STALL_MS")
+                .map(Duration::from_millis),
+            restart_after: env_optional("WATCHDOG_RESTART_AFTER"),
+        }
+    }
+}
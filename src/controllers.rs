@@ -9,7 +9,7 @@ mod frontend_prelude {
 }
 
 mod prelude {
-    pub use super::helpers::ok_true;
+    pub use super::helpers::{ok_true, with_transaction};
     pub use axum::extract::Path;
     pub use axum::response::{IntoResponse, Response};
     pub use axum::Json;
@@ -72,7 +72,11 @@ pub mod github;
 pub mod keyword;
 pub mod krate;
 pub mod metrics;
+pub mod osv;
 pub mod site_metadata;
+pub mod stats;
+pub mod status;
+pub mod subscription;
 pub mod team;
 pub mod token;
 pub mod user;
@@ -1,8 +1,11 @@
+use super::frontend_prelude::*;
 use super::helpers::pagination::*;
-use super::prelude::*;
 
-use crate::models::Category;
+use crate::auth::AuthCheck;
+use crate::controllers::util::audit_event;
+use crate::models::{Category, NewAuditEvent, NewCategory};
 use crate::schema::categories;
+use crate::util::errors::not_found;
 use crate::views::{EncodableCategory, EncodableCategoryWithSubcategories};
 
 /// Handles the `GET /categories` route.
@@ -35,10 +38,22 @@ pub async fn index(app: AppState, req: Parts) -> AppResult<Json<Value>> {
 }
 
 /// Handles the `GET /categories/:category_id` route.
-pub async fn show(state: AppState, Path(slug): Path<String>) -> AppResult<Json<Value>> {
+///
+/// Slugs of renamed or merged categories redirect to the category that they
+/// now point to.
+pub async fn show(state: AppState, Path(slug): Path<String>) -> AppResult<Response> {
     conduit_compat(move || {
         let conn = &mut *state.db_read()?;
-        let cat: Category = Category::by_slug(&slug).first(conn)?;
+        let Some(cat) = Category::by_slug(&slug)
+            .first::<Category>(conn)
+            .optional()?
+        else {
+            return match Category::by_old_slug(&slug, conn)? {
+                Some(cat) => Ok(redirect(format!("/api/v1/categories/{}", cat.slug))),
+                None => Err(not_found()),
+            };
+        };
+
         let subcats = cat
             .subcategories(conn)?
             .into_iter()
@@ -62,7 +77,7 @@ pub async fn show(state: AppState, Path(slug): Path<String>) -> AppResult<Json<V
             parent_categories: parents,
         };
 
-        Ok(Json(json!({ "category": cat_with_subcats })))
+        Ok(Json(json!({ "category": cat_with_subcats })).into_response())
     })
     .await
 }
@@ -87,3 +102,281 @@ pub async fn slugs(state: AppState) -> AppResult<Json<Value>> {
     })
     .await
 }
+
+#[derive(Deserialize)]
+struct NewCategoryRequest {
+    category: NewCategoryParams,
+}
+
+#[derive(Deserialize)]
+struct NewCategoryParams {
+    /// Full slug of the category, e.g. `parsing::json`.
+    slug: String,
+    /// Name of the category, without the names of its parent categories.
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// Handles the `POST /categories` route.
+pub async fn create(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let new: NewCategoryRequest = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+        let new = new.category;
+
+        validate_slug(&new.slug)?;
+        validate_name(&new.name)?;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let category = with_transaction(conn, |conn| {
+            ensure_slug_is_available(&new.slug, conn)?;
+            let name = full_name(&new.slug, &new.name, conn)?;
+
+            let category = NewCategory {
+                category: &name,
+                slug: &new.slug,
+                description: &new.description,
+            }
+            .insert(conn)?;
+
+            NewAuditEvent {
+                user_id: Some(auth.user_id()),
+                details: json!({ "slug": category.slug }),
+                ..audit_event(&req, "admin.category.create")
+            }
+            .insert(conn)?;
+
+            Ok(category)
+        })?;
+
+        let category = EncodableCategory::from(category);
+        Ok(Json(json!({ "category": category })))
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct UpdateCategoryRequest {
+    category: CategoryUpdate,
+}
+
+/// The fields of a category that can be changed. Fields that are missing from
+/// the request are left untouched.
+#[derive(Deserialize)]
+struct CategoryUpdate {
+    /// New full slug of the category. Subcategories are moved along with the
+    /// category, and the previous slugs redirect to the new ones.
+    slug: Option<String>,
+    /// New name of the category, without the names of its parent categories.
+    name: Option<String>,
+    description: Option<String>,
+    deprecated: Option<bool>,
+}
+
+/// Handles the `PATCH /categories/:category_id` route.
+pub async fn update(
+    app: AppState,
+    Path(slug): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let update: UpdateCategoryRequest = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+        let update = update.category;
+
+        if let Some(new_slug) = &update.slug {
+            validate_slug(new_slug)?;
+        }
+        if let Some(name) = &update.name {
+            validate_name(name)?;
+        }
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let category = with_transaction(conn, |conn| {
+            let mut category: Category = Category::by_slug(&slug).first(conn)?;
+            let old_slug = category.slug.clone();
+
+            if update.slug.is_some() || update.name.is_some() {
+                let new_slug = update.slug.clone().unwrap_or_else(|| category.slug.clone());
+                let name = match &update.name {
+                    Some(name) => name.clone(),
+                    None => category
+                        .category
+                        .rsplit("::")
+                        .next()
+                        .unwrap_or_default()
+                        .into(),
+                };
+
+                if new_slug != category.slug {
+                    if new_slug.starts_with(&format!("{}::", category.slug)) {
+                        return Err(bad_request(
+                            "a category can't be moved into one of its subcategories",
+                        ));
+                    }
+
+                    for subcategory in category.with_subcategories(conn)? {
+                        let suffix = &subcategory.slug[category.slug.len()..];
+                        ensure_slug_is_available(&format!("{new_slug}{suffix}"), conn)?;
+                    }
+                }
+
+                let name = full_name(&new_slug, &name, conn)?;
+                category = category.rename(&new_slug, &name, conn)?;
+            }
+
+            if let Some(description) = &update.description {
+                category = diesel::update(&category)
+                    .set(categories::description.eq(description))
+                    .get_result(conn)?;
+            }
+
+            if let Some(deprecated) = update.deprecated {
+                category = category.set_deprecated(deprecated, conn)?;
+            }
+
+            NewAuditEvent {
+                user_id: Some(auth.user_id()),
+                details: json!({
+                    "slug": old_slug,
+                    "new_slug": category.slug,
+                    "deprecated": category.deprecated_at.is_some(),
+                }),
+                ..audit_event(&req, "admin.category.update")
+            }
+            .insert(conn)?;
+
+            Ok(category)
+        })?;
+
+        let category = EncodableCategory::from(category);
+        Ok(Json(json!({ "category": category })))
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct MergeCategoryRequest {
+    /// Slug of the category that the crates are moved to.
+    into: String,
+}
+
+/// Handles the `PUT /categories/:category_id/merge` route.
+///
+/// Moves all crates of the category into another category, deletes the
+/// category and keeps its slug as a redirect to the other category.
+pub async fn merge(
+    app: AppState,
+    Path(slug): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let merge: MergeCategoryRequest = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie()
+            .require_admin()
+            .check(&req, conn)?;
+
+        with_transaction(conn, |conn| {
+            let source: Category = Category::by_slug(&slug).first(conn)?;
+            let target: Category = Category::by_slug(&merge.into)
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    bad_request(&format_args!("category `{}` does not exist", merge.into))
+                })?;
+
+            if source.id == target.id {
+                return Err(bad_request("a category can't be merged into itself"));
+            }
+            if target.deprecated_at.is_some() {
+                return Err(bad_request("a category can't be merged into a deprecated category"));
+            }
+            if source.with_subcategories(conn)?.len() > 1 {
+                return Err(bad_request(
+                    "a category with subcategories can't be merged, move or merge its subcategories first",
+                ));
+            }
+
+            source.merge_into(&target, conn)?;
+
+            NewAuditEvent {
+                user_id: Some(auth.user_id()),
+                details: json!({ "slug": source.slug, "into": target.slug }),
+                ..audit_event(&req, "admin.category.merge")
+            }
+            .insert(conn)?;
+
+            Ok(())
+        })?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Checks that each segment of the slug consists of lowercase ASCII letters,
+/// digits and dashes. Other characters are not supported by the `path` column
+/// of the `categories` table.
+fn validate_slug(slug: &str) -> AppResult<()> {
+    let is_valid = slug.split("::").all(|segment| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    });
+
+    if !is_valid {
+        return Err(bad_request(&format_args!(
+            "invalid category slug `{slug}`, only lowercase letters, digits and dashes are allowed"
+        )));
+    }
+
+    Ok(())
+}
+
+fn validate_name(name: &str) -> AppResult<()> {
+    if name.trim().is_empty() || name.contains("::") {
+        return Err(bad_request(&format_args!("invalid category name `{name}`")));
+    }
+
+    Ok(())
+}
+
+fn ensure_slug_is_available(slug: &str, conn: &mut PgConnection) -> AppResult<()> {
+    let exists: bool =
+        diesel::select(diesel::dsl::exists(Category::by_slug(slug))).get_result(conn)?;
+    if exists {
+        return Err(bad_request(&format_args!(
+            "category `{slug}` already exists"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the full name of the category with the given slug, which includes
+/// the names of its parent categories.
+fn full_name(slug: &str, name: &str, conn: &mut PgConnection) -> AppResult<String> {
+    let Some((parent_slug, _)) = slug.rsplit_once("::") else {
+        return Ok(name.to_string());
+    };
+
+    let parent: Category = Category::by_slug(parent_slug)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| {
+            bad_request(&format_args!(
+                "parent category `{parent_slug}` does not exist"
+            ))
+        })?;
+
+    Ok(format!("{}::{name}", parent.category))
+}
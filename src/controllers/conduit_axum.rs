@@ -1,17 +1,19 @@
+use crate::util::deadline::Deadline;
 use crate::util::errors::AppResult;
 use sentry::Hub;
 use std::convert::identity;
 use tokio::task::JoinHandle;
 
 /// Just like [tokio::task::spawn_blocking], but automatically runs the passed
-/// in function in the context of the current Sentry hub.
+/// in function in the context of the current Sentry hub and request deadline.
 fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
     R: Send + 'static,
 {
     let hub = Hub::current();
-    tokio::task::spawn_blocking(move || Hub::run(hub, f))
+    let deadline = Deadline::current();
+    tokio::task::spawn_blocking(move || Hub::run(hub, || Deadline::enter(deadline, f)))
 }
 
 /// This runs the passed-in function in a synchronous [spawn_blocking] context
@@ -3,8 +3,10 @@ use axum::response::IntoResponse;
 use axum::Json;
 
 pub(crate) mod pagination;
+mod transaction;
 
 pub(crate) use self::pagination::Paginate;
+pub(crate) use self::transaction::with_transaction;
 
 pub fn ok_true() -> AppResult<Response> {
     let json = json!({ "ok": true });
@@ -1,6 +1,7 @@
 use crate::config::Server;
 use crate::controllers::prelude::*;
 use crate::controllers::util::RequestPartsExt;
+use crate::middleware::anonymize_ip::real_ip;
 use crate::middleware::log_request::RequestLogExt;
 use crate::models::helpers::with_count::*;
 use crate::util::errors::{bad_request, AppResult};
@@ -11,7 +12,6 @@ use diesel::pg::Pg;
 use diesel::query_builder::*;
 use diesel::query_dsl::LoadQuery;
 use diesel::sql_types::BigInt;
-use http::HeaderMap;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
@@ -102,7 +102,7 @@ impl PaginationOptionsBuilder {
                 if self.limit_page_numbers {
                     let config = &req.app().config;
                     if numeric_page > config.max_allowed_page_offset
-                        && is_useragent_or_ip_blocked(config, req.headers())
+                        && is_useragent_or_ip_blocked(config, req)
                     {
                         req.request_log().add("cause", "large page offset");
                         return Err(bad_request("requested page offset is too large"));
@@ -258,9 +258,9 @@ impl RawSeekPayload {
 ///
 /// A request can be blocked if either the User Agent is on the User Agent block list or if the client
 /// IP is on the CIDR block list.
-fn is_useragent_or_ip_blocked(config: &Server, headers: &HeaderMap) -> bool {
-    let user_agent = headers.get_str_or_default(header::USER_AGENT);
-    let client_ip = headers.get_str_or_default("x-real-ip");
+fn is_useragent_or_ip_blocked<T: RequestPartsExt>(config: &Server, req: &T) -> bool {
+    let user_agent = req.headers().get_str_or_default(header::USER_AGENT);
+    let client_ip = real_ip(req).unwrap_or_default();
 
     // check if user agent is blocked
     if config
@@ -0,0 +1,144 @@
+use crate::util::errors::{AppResult, TransactionConflict};
+use diesel::prelude::*;
+use rand::Rng;
+use std::time::Duration;
+
+/// The number of times a transaction is attempted before a conflict with
+/// concurrent transactions is returned to the client.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// The delay before the first retry. It is doubled for every further retry
+/// and a random jitter of up to the same amount is added, so that the
+/// conflicting transactions don't collide again.
+const BASE_DELAY: Duration = Duration::from_millis(20);
+
+/// Runs `f` within a database transaction.
+///
+/// If the transaction fails because of a serialization failure, a deadlock or
+/// a unique violation caused by a concurrent transaction, it is rolled back
+/// and retried a bounded number of times. `f` can therefore be called more
+/// than once and must not have side effects outside of the database that
+/// can't be repeated.
+pub fn with_transaction<T, F>(conn: &mut PgConnection, mut f: F) -> AppResult<T>
+where
+    F: FnMut(&mut PgConnection) -> AppResult<T>,
+{
+    retry_on_conflict(|| conn.transaction(&mut f))
+}
+
+fn retry_on_conflict<T>(mut f: impl FnMut() -> AppResult<T>) -> AppResult<T> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(error) if error.is::<TransactionConflict>() && attempt < MAX_ATTEMPTS => {
+                let delay = BASE_DELAY * 2u32.pow(attempt - 1);
+                let delay = delay + rand::thread_rng().gen_range(Duration::ZERO..=delay);
+                warn!(%error, attempt, ?delay, "Retrying conflicting transaction");
+
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::errors::{bad_request, BoxedAppError};
+    use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind, Error as DieselError};
+
+    fn conflict() -> BoxedAppError {
+        DieselError::DatabaseError(
+            DatabaseErrorKind::SerializationFailure,
+            Box::new("could not serialize access".to_string()),
+        )
+        .into()
+    }
+
+    struct UniqueViolation(&'static str);
+
+    impl DatabaseErrorInformation for UniqueViolation {
+        fn message(&self) -> &str {
+            "duplicate key value violates unique constraint"
+        }
+        fn details(&self) -> Option<&str> {
+            None
+        }
+        fn hint(&self) -> Option<&str> {
+            None
+        }
+        fn table_name(&self) -> Option<&str> {
+            None
+        }
+        fn column_name(&self) -> Option<&str> {
+            None
+        }
+        fn constraint_name(&self) -> Option<&str> {
+            Some(self.0)
+        }
+        fn statement_position(&self) -> Option<i32> {
+            None
+        }
+    }
+
+    fn unique_violation(constraint: &'static str) -> BoxedAppError {
+        let info = Box::new(UniqueViolation(constraint));
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info).into()
+    }
+
+    #[test]
+    fn conflicting_unique_violations() {
+        assert!(unique_violation("crates_keywords_pkey").is::<TransactionConflict>());
+        assert!(unique_violation("crates_categories_pkey").is::<TransactionConflict>());
+        assert!(!unique_violation("emails_user_id_key").is::<TransactionConflict>());
+    }
+
+    #[test]
+    fn deadlocks() {
+        let deadlock = |message: &str| -> BoxedAppError {
+            let info = Box::new(message.to_string());
+            DieselError::DatabaseError(DatabaseErrorKind::Unknown, info).into()
+        };
+
+        assert!(deadlock("deadlock detected").is::<TransactionConflict>());
+        assert!(!deadlock("division by zero").is::<TransactionConflict>());
+    }
+
+    #[test]
+    fn retries_conflicts() {
+        let mut calls = 0;
+        let result = retry_on_conflict(|| {
+            calls += 1;
+            if calls < MAX_ATTEMPTS {
+                Err(conflict())
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let mut calls = 0;
+        let result = retry_on_conflict(|| {
+            calls += 1;
+            Err::<(), _>(conflict())
+        });
+        assert!(result.unwrap_err().is::<TransactionConflict>());
+        assert_eq!(calls, MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let mut calls = 0;
+        let result = retry_on_conflict(|| {
+            calls += 1;
+            Err::<(), _>(bad_request("invalid"))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -1,6 +1,11 @@
+pub mod badge;
 pub mod downloads;
+pub mod feature_usage;
 pub mod follow;
 pub mod metadata;
 pub mod owners;
 pub mod publish;
+pub mod publish_diagnostics;
 pub mod search;
+pub mod support_windows;
+pub mod update;
@@ -0,0 +1,211 @@
+//! Endpoint for the badges of a crate
+//!
+//! The badges use the JSON format of shields.io endpoint badges
+//! (<https://shields.io/badges/endpoint-badge>). All badges of a crate are
+//! built from a single query of its versions, so that badge services don't
+//! need to poll the full crate endpoint, and the responses can be cached for
+//! a long time.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::models::Crate;
+use crate::schema::{crates, versions};
+use crate::util::errors::not_found;
+use axum::extract::Query;
+
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+#[derive(Deserialize)]
+pub struct BadgeParams {
+    #[serde(rename = "type", default)]
+    kind: BadgeKind,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum BadgeKind {
+    #[default]
+    Version,
+    Downloads,
+    Msrv,
+    License,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct Badge {
+    schema_version: u8,
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
+
+#[derive(Queryable, Debug)]
+struct BadgeVersion {
+    downloads: i32,
+    num: String,
+    yanked: bool,
+    rust_version: Option<String>,
+    license: Option<String>,
+}
+
+/// Handles the `GET /crates/:crate_id/badge.json` route.
+///
+/// The `type` query parameter selects the badge: `version` (the default),
+/// `downloads`, `msrv` or `license`. The MSRV and license are the ones of
+/// the version that the `version` badge shows.
+pub async fn badge(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    Query(params): Query<BadgeParams>,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *state.db_read()?;
+        let versions: Vec<BadgeVersion> = versions::table
+            .inner_join(crates::table)
+            .filter(Crate::with_name(&crate_name))
+            .select((
+                crates::downloads,
+                versions::num,
+                versions::yanked,
+                versions::rust_version,
+                versions::license,
+            ))
+            .load(conn)?;
+
+        let version = badge_version(&versions).ok_or_else(not_found)?;
+        let badge = build_badge(params.kind, version);
+
+        let headers = [(header::CACHE_CONTROL, CACHE_CONTROL)];
+        Ok((headers, Json(badge)).into_response())
+    })
+    .await
+}
+
+/// Picks the version that the badges describe: the highest stable version
+/// that is not yanked, falling back to pre-releases and then to yanked
+/// versions.
+fn badge_version(versions: &[BadgeVersion]) -> Option<&BadgeVersion> {
+    let parsed = versions
+        .iter()
+        .filter_map(|version| Some((semver::Version::parse(&version.num).ok()?, version)))
+        .collect::<Vec<_>>();
+
+    let highest = |filter: &dyn Fn(&semver::Version, &BadgeVersion) -> bool| {
+        parsed
+            .iter()
+            .filter(|(semver, version)| filter(semver, version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, version)| *version)
+    };
+
+    highest(&|semver, version| semver.pre.is_empty() && !version.yanked)
+        .or_else(|| highest(&|_, version| !version.yanked))
+        .or_else(|| highest(&|_, _| true))
+}
+
+fn build_badge(kind: BadgeKind, version: &BadgeVersion) -> Badge {
+    let (label, message, color) = match kind {
+        BadgeKind::Version => {
+            let prerelease =
+                semver::Version::parse(&version.num).map_or(false, |semver| !semver.pre.is_empty());
+            let color = if version.yanked {
+                "red"
+            } else if prerelease {
+                "orange"
+            } else {
+                "blue"
+            };
+            ("crates.io", format!("v{}", version.num), color)
+        }
+        BadgeKind::Downloads => {
+            let message = format_count(version.downloads.into());
+            ("downloads", message, "brightgreen")
+        }
+        BadgeKind::Msrv => match &version.rust_version {
+            Some(rust_version) => ("rust-version", rust_version.clone(), "blue"),
+            None => ("rust-version", "unknown".into(), "lightgrey"),
+        },
+        BadgeKind::License => match &version.license {
+            Some(license) => ("license", license.clone(), "blue"),
+            None => ("license", "unknown".into(), "lightgrey"),
+        },
+    };
+
+    Badge {
+        schema_version: 1,
+        label,
+        message,
+        color,
+    }
+}
+
+/// Formats a count like `1.2k` or `34M`, with one decimal place (rounded
+/// down) below ten of a unit.
+fn format_count(count: i64) -> String {
+    const UNITS: [(i64, &str); 3] = [(1_000_000_000, "B"), (1_000_000, "M"), (1_000, "k")];
+
+    for (size, unit) in UNITS {
+        if count >= size {
+            let tenths = count / (size / 10);
+            return match (tenths / 10, tenths % 10) {
+                (whole, fraction) if whole < 10 && fraction > 0 => {
+                    format!("{whole}.{fraction}{unit}")
+                }
+                _ => format!("{}{unit}", count / size),
+            };
+        }
+    }
+
+    count.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(num: &str, yanked: bool) -> BadgeVersion {
+        BadgeVersion {
+            downloads: 0,
+            num: num.into(),
+            yanked,
+            rust_version: None,
+            license: None,
+        }
+    }
+
+    #[test]
+    fn badge_versions() {
+        let num = |versions: &[BadgeVersion]| badge_version(versions).map(|v| v.num.clone());
+
+        let versions = [
+            version("1.0.0", false),
+            version("1.1.0", false),
+            version("2.0.0-beta.1", false),
+            version("1.2.0", true),
+            version("not-semver", false),
+        ];
+        assert_eq!(num(&versions).unwrap(), "1.1.0");
+
+        let versions = [version("1.0.0", true), version("2.0.0-beta.1", false)];
+        assert_eq!(num(&versions).unwrap(), "2.0.0-beta.1");
+
+        let versions = [version("1.0.0", true), version("1.1.0", true)];
+        assert_eq!(num(&versions).unwrap(), "1.1.0");
+
+        assert_eq!(num(&[]), None);
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_000), "1k");
+        assert_eq!(format_count(1_250), "1.2k");
+        assert_eq!(format_count(1_999), "1.9k");
+        assert_eq!(format_count(12_345), "12k");
+        assert_eq!(format_count(999_999), "999k");
+        assert_eq!(format_count(3_400_000), "3.4M");
+        assert_eq!(format_count(5_600_000_000), "5.6B");
+    }
+}
@@ -4,13 +4,19 @@
 //! download counts are located in `version::downloads`.
 
 use std::cmp;
+use std::collections::BTreeMap;
 
 use crate::controllers::frontend_prelude::*;
 
 use crate::models::{Crate, CrateVersions, Version, VersionDownload};
-use crate::schema::version_downloads;
+use crate::schema::{crates, version_downloads, versions};
 use crate::sql::to_char;
 use crate::views::EncodableVersionDownload;
+use axum::extract::Query;
+use chrono::{Duration, Utc};
+
+const DEFAULT_LINE_DAYS: i64 = 90;
+const MAX_LINE_DAYS: i64 = 365;
 
 /// Handles the `GET /crates/:crate_id/downloads` route.
 pub async fn downloads(state: AppState, Path(crate_name): Path<String>) -> AppResult<Json<Value>> {
@@ -60,3 +66,206 @@ pub async fn downloads(state: AppState, Path(crate_name): Path<String>) -> AppRe
     })
     .await
 }
+
+/// Handles the `GET /crates/:crate_id/downloads/total` route.
+///
+/// Badge services only need this one number, so it is read from the counter
+/// of the `crates` table, which the `update_downloads` job maintains, instead
+/// of the downloads tables. Since the counter only changes when that job
+/// runs, the totals are cached by each instance and by the CDN.
+pub async fn total_downloads(
+    state: AppState,
+    Path(crate_name): Path<String>,
+) -> AppResult<Response> {
+    let cached = state.crate_downloads_cacher.get(&crate_name);
+    let downloads = match cached {
+        Some(downloads) => downloads,
+        None => {
+            let app = state.clone();
+            let name = crate_name.clone();
+            let downloads = conduit_compat(move || {
+                let conn = &mut *app.db_read()?;
+                let downloads = crates::table
+                    .filter(Crate::with_name(&name))
+                    .select(crates::downloads)
+                    .first::<i32>(conn)?;
+                Ok(downloads)
+            })
+            .await?;
+
+            state
+                .crate_downloads_cacher
+                .insert(crate_name, downloads)
+                .await;
+
+            downloads
+        }
+    };
+
+    let max_age = state.config.crate_downloads_cache_ttl.as_secs();
+    let cache_control = format!("public, max-age={max_age}");
+    let headers = [(header::CACHE_CONTROL, cache_control)];
+    Ok((headers, Json(json!({ "downloads": downloads }))).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct DownloadLinesParams {
+    days: Option<i64>,
+}
+
+/// The downloads of all versions of a release line, see [`release_line`].
+#[derive(Serialize, Debug, PartialEq)]
+struct DownloadLine {
+    line: String,
+    downloads: i64,
+    yanked_downloads: i64,
+    /// The share of all downloads of the crate, from `0.0` to `1.0`.
+    share: f64,
+}
+
+/// Handles the `GET /crates/:crate_id/download_lines` route.
+///
+/// Reports how the recent downloads of a crate are spread over its release
+/// lines, and how many of them went to yanked versions, so that maintainers
+/// can judge whether an old line is still in use.
+pub async fn download_lines(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    Query(params): Query<DownloadLinesParams>,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        use diesel::dsl::sum;
+
+        let conn = &mut *state.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+        let days = params
+            .days
+            .unwrap_or(DEFAULT_LINE_DAYS)
+            .clamp(1, MAX_LINE_DAYS);
+        let since = Utc::now().date_naive() - Duration::days(days);
+
+        let version_downloads: Vec<(String, bool, Option<i64>)> = versions::table
+            .inner_join(version_downloads::table)
+            .filter(versions::crate_id.eq(krate.id))
+            .filter(version_downloads::date.gt(since))
+            .group_by((versions::id, versions::num, versions::yanked))
+            .select((
+                versions::num,
+                versions::yanked,
+                sum(version_downloads::downloads),
+            ))
+            .load(conn)?;
+
+        let version_downloads = version_downloads
+            .into_iter()
+            .map(|(num, yanked, downloads)| (num, yanked, downloads.unwrap_or_default()));
+        let lines = download_lines_from(version_downloads);
+
+        let total_downloads = lines.iter().map(|line| line.downloads).sum::<i64>();
+        let yanked_downloads = lines.iter().map(|line| line.yanked_downloads).sum::<i64>();
+
+        Ok(Json(json!({
+            "lines": lines,
+            "meta": {
+                "days": days,
+                "total_downloads": total_downloads,
+                "yanked_downloads": yanked_downloads,
+            },
+        })))
+    })
+    .await
+}
+
+/// Returns the release line of a version: the major and minor version, or
+/// for `0.0.x` versions, which are incompatible with each other, the whole
+/// version number without pre-release and build metadata.
+///
+/// The returned key sorts the lines in the same order as their versions.
+fn release_line(version: &semver::Version) -> ((u64, u64, u64), String) {
+    match (version.major, version.minor, version.patch) {
+        (0, 0, patch) => ((0, 0, patch), format!("0.0.{patch}")),
+        (major, minor, _) => ((major, minor, 0), format!("{major}.{minor}")),
+    }
+}
+
+/// Sums up the downloads of `(version, yanked, downloads)` tuples by release
+/// line, newest line first. Versions that aren't valid semver are ignored.
+fn download_lines_from(
+    version_downloads: impl IntoIterator<Item = (String, bool, i64)>,
+) -> Vec<DownloadLine> {
+    let mut lines: BTreeMap<(u64, u64, u64), DownloadLine> = BTreeMap::new();
+    for (num, yanked, downloads) in version_downloads {
+        let Ok(version) = semver::Version::parse(&num) else {
+            continue;
+        };
+
+        let (key, line) = release_line(&version);
+        let entry = lines.entry(key).or_insert_with(|| DownloadLine {
+            line,
+            downloads: 0,
+            yanked_downloads: 0,
+            share: 0.0,
+        });
+        entry.downloads += downloads;
+        if yanked {
+            entry.yanked_downloads += downloads;
+        }
+    }
+
+    let total = lines.values().map(|line| line.downloads).sum::<i64>();
+    let mut lines = lines.into_values().rev().collect::<Vec<_>>();
+    if total > 0 {
+        for line in &mut lines {
+            line.share = line.downloads as f64 / total as f64;
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_lines() {
+        let line = |num: &str| release_line(&semver::Version::parse(num).unwrap()).1;
+        assert_eq!(line("1.2.3"), "1.2");
+        assert_eq!(line("1.2.0-beta.1"), "1.2");
+        assert_eq!(line("0.3.1"), "0.3");
+        assert_eq!(line("0.0.4"), "0.0.4");
+        assert_eq!(line("0.0.4+build"), "0.0.4");
+    }
+
+    #[test]
+    fn lines_from_downloads() {
+        let downloads = [
+            ("1.0.0", false, 10),
+            ("1.0.1", true, 20),
+            ("0.9.0", false, 50),
+            ("1.10.0", false, 20),
+            ("not-semver", false, 100),
+        ];
+        let downloads = downloads
+            .into_iter()
+            .map(|(num, yanked, downloads)| (num.to_string(), yanked, downloads));
+
+        let line = |line: &str, downloads, yanked_downloads, share| DownloadLine {
+            line: line.into(),
+            downloads,
+            yanked_downloads,
+            share,
+        };
+        assert_eq!(
+            download_lines_from(downloads),
+            vec![
+                line("1.10", 20, 0, 0.2),
+                line("1.0", 30, 20, 0.3),
+                line("0.9", 50, 0, 0.5),
+            ]
+        );
+
+        assert_eq!(download_lines_from([]), vec![]);
+    }
+}
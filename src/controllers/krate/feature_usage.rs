@@ -0,0 +1,55 @@
+//! Endpoint for exposing how many dependents request the features of a crate
+//!
+//! The numbers are snapshots that are recorded daily by the
+//! `aggregate_feature_usage` background job, so maintainers can see how many
+//! users would be affected if e.g. a default feature was removed.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::models::{Crate, FeatureUsage};
+use axum::extract::Query;
+use chrono::{Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+
+const DEFAULT_DAYS: i64 = 90;
+const MAX_DAYS: i64 = 365;
+
+#[derive(Deserialize)]
+pub struct FeatureUsageParams {
+    days: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct FeatureUsageSnapshot {
+    date: NaiveDate,
+    dependents: i32,
+}
+
+/// Handles the `GET /crates/:crate_id/feature_usage` route.
+pub async fn feature_usage(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    Query(params): Query<FeatureUsageParams>,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *state.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+        let days = params.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS);
+        let since = Utc::now().date_naive() - Duration::days(days);
+
+        let mut features: BTreeMap<String, Vec<FeatureUsageSnapshot>> = BTreeMap::new();
+        for usage in FeatureUsage::for_crate(&krate, since, conn)? {
+            features
+                .entry(usage.feature)
+                .or_default()
+                .push(FeatureUsageSnapshot {
+                    date: usage.date,
+                    dependents: usage.dependents,
+                });
+        }
+
+        Ok(Json(json!({ "features": features })))
+    })
+    .await
+}
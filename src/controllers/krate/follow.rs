@@ -4,28 +4,77 @@ use crate::auth::AuthCheck;
 use diesel::associations::Identifiable;
 
 use crate::controllers::frontend_prelude::*;
-use crate::models::{Crate, Follow};
+use crate::models::{Crate, Follow, FollowChannel, FollowMode};
 use crate::schema::*;
 
 fn follow_target(crate_name: &str, conn: &mut PgConnection, user_id: i32) -> AppResult<Follow> {
     let crate_id = Crate::by_name(crate_name).select(crates::id).first(conn)?;
-    Ok(Follow { user_id, crate_id })
+    Ok(Follow::new(user_id, crate_id))
+}
+
+/// The notification settings of a followed crate.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+struct FollowSettings {
+    #[serde(default = "default_mode")]
+    mode: FollowMode,
+    #[serde(default = "default_channel")]
+    channel: FollowChannel,
+}
+
+fn default_mode() -> FollowMode {
+    FollowMode::Releases
+}
+
+fn default_channel() -> FollowChannel {
+    FollowChannel::Feed
+}
+
+impl From<&Follow> for FollowSettings {
+    fn from(follow: &Follow) -> Self {
+        Self {
+            mode: follow.mode,
+            channel: follow.channel,
+        }
+    }
 }
 
 /// Handles the `PUT /crates/:crate_id/follow` route.
+///
+/// The request body may contain the notification settings of the follow, e.g.
+/// `{"mode": "all_activity", "channel": "email"}`. Following a crate again
+/// without a body keeps the current settings.
 pub async fn follow(
     app: AppState,
     Path(crate_name): Path<String>,
-    req: Parts,
+    req: BytesRequest,
 ) -> AppResult<Response> {
     conduit_compat(move || {
+        let settings = if req.body().is_empty() {
+            None
+        } else {
+            let settings: FollowSettings = serde_json::from_slice(req.body())
+                .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+            Some(settings)
+        };
+
         let conn = &mut *app.db_write()?;
         let user_id = AuthCheck::default().check(&req, conn)?.user_id();
         let follow = follow_target(&crate_name, conn, user_id)?;
-        diesel::insert_into(follows::table)
-            .values(&follow)
-            .on_conflict_do_nothing()
-            .execute(conn)?;
+
+        match settings {
+            Some(settings) => Follow {
+                mode: settings.mode,
+                channel: settings.channel,
+                ..follow
+            }
+            .save(conn)?,
+            None => {
+                diesel::insert_into(follows::table)
+                    .values(&follow)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+        }
 
         ok_true()
     })
@@ -56,15 +105,98 @@ pub async fn following(
     req: Parts,
 ) -> AppResult<Json<Value>> {
     conduit_compat(move || {
-        use diesel::dsl::exists;
-
         let conn = &mut *app.db_read_prefer_primary()?;
         let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
         let follow = follow_target(&crate_name, conn, user_id)?;
-        let following =
-            diesel::select(exists(follows::table.find(follow.id()))).get_result::<bool>(conn)?;
+        let follow: Option<Follow> = follows::table.find(follow.id()).first(conn).optional()?;
+
+        let settings = follow.as_ref().map(FollowSettings::from);
+
+        Ok(Json(json!({
+            "following": follow.is_some(),
+            "settings": settings,
+        })))
+    })
+    .await
+}
+
+/// Handles the `GET /me/follows` route.
+pub async fn list(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+
+        let follows: Vec<(Follow, String)> = follows::table
+            .inner_join(crates::table)
+            .filter(follows::user_id.eq(user_id))
+            .select((follows::all_columns, crates::name))
+            .order(crates::name)
+            .load(conn)?;
+
+        let follows = follows
+            .iter()
+            .map(|(follow, name)| {
+                json!({
+                    "crate": name,
+                    "mode": follow.mode,
+                    "channel": follow.channel,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "follows": follows })))
+    })
+    .await
+}
+
+/// Handles the `PUT /me/follows` route.
+///
+/// Follows the crates listed in `follow` with the given notification
+/// settings, and unfollows the crates listed in `unfollow`. All changes are
+/// applied in a single transaction, so that an unknown crate name does not
+/// leave the list of followed crates half updated.
+pub async fn bulk_update(app: AppState, req: BytesRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct FollowRequest {
+            #[serde(rename = "crate")]
+            crate_name: String,
+            #[serde(flatten)]
+            settings: FollowSettings,
+        }
+
+        #[derive(Deserialize)]
+        struct BulkUpdate {
+            #[serde(default)]
+            follow: Vec<FollowRequest>,
+            #[serde(default)]
+            unfollow: Vec<String>,
+        }
+
+        let update: BulkUpdate = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+
+        let conn = &mut *app.db_write()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+
+        with_transaction(conn, |conn| {
+            for request in &update.follow {
+                let follow = follow_target(&request.crate_name, conn, user_id)?;
+                Follow {
+                    mode: request.settings.mode,
+                    channel: request.settings.channel,
+                    ..follow
+                }
+                .save(conn)?;
+            }
+
+            for crate_name in &update.unfollow {
+                let follow = follow_target(crate_name, conn, user_id)?;
+                diesel::delete(&follow).execute(conn)?;
+            }
 
-        Ok(Json(json!({ "following": following })))
+            ok_true()
+        })
     })
     .await
 }
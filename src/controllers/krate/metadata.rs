@@ -5,19 +5,25 @@
 //! `Cargo.toml` file.
 
 use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use crate::controllers::frontend_prelude::*;
 use crate::controllers::helpers::pagination::PaginationOptions;
 
+use crate::models::support_window::major_version;
 use crate::models::{
     Category, Crate, CrateCategory, CrateKeyword, CrateVersions, Keyword, RecentCrateDownloads,
-    TopVersions, User, Version, VersionOwnerAction,
+    RepositoryCheck, SupportStatus, SupportWindow, TopVersions, User, Version, VersionOwnerAction,
 };
 use crate::schema::*;
+use crate::storage::ContentEncoding;
 use crate::views::{
-    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
+    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword,
+    EncodableSupportWarning, EncodableVersion,
 };
+use chrono::{NaiveDate, Utc};
+use http::HeaderValue;
 
 /// Handles the `GET /summary` route.
 pub async fn summary(state: AppState) -> AppResult<Json<Value>> {
@@ -209,7 +215,9 @@ pub async fn show(app: AppState, Path(name): Path<String>, req: Parts) -> AppRes
             None
         };
 
-        let encodable_crate = EncodableCrate::from(
+        let repository_check = RepositoryCheck::for_crate(&krate, conn)?;
+
+        let mut encodable_crate = EncodableCrate::from(
             krate.clone(),
             top_versions.as_ref(),
             ids,
@@ -219,6 +227,7 @@ pub async fn show(app: AppState, Path(name): Path<String>, req: Parts) -> AppRes
             false,
             recent_downloads,
         );
+        encodable_crate.repository_status = repository_check.map(Into::into);
         let encodable_versions = versions_publishers_and_audit_actions.map(|vpa| {
             vpa.into_iter()
                 .map(|(v, pb, aas)| EncodableVersion::from(v, &krate.name, pb, aas))
@@ -307,12 +316,47 @@ impl FromStr for ShowIncludeMode {
 }
 
 /// Handles the `GET /crates/:crate_id/:version/readme` route.
+///
+/// If compressed variants of the readmes are stored, the location of the
+/// variant that matches the `Accept-Encoding` header of the request is
+/// returned instead of the uncompressed readme.
 pub async fn readme(
     app: AppState,
     Path((crate_name, version)): Path<(String, String)>,
     req: Parts,
 ) -> Response {
-    let redirect_url = app.storage.readme_location(&crate_name, &version);
+    let redirect_url = ContentEncoding::negotiate(&req.headers)
+        .and_then(|encoding| {
+            app.storage
+                .compressed_readme_location(&crate_name, &version, encoding)
+        })
+        .unwrap_or_else(|| app.storage.readme_location(&crate_name, &version));
+
+    let mut response = if req.wants_json() {
+        Json(json!({ "url": redirect_url })).into_response()
+    } else {
+        redirect(redirect_url)
+    };
+
+    if app.storage.has_compressed_variants() {
+        let vary = HeaderValue::from_static("Accept-Encoding");
+        response.headers_mut().insert(header::VARY, vary);
+    }
+
+    response
+}
+
+/// Handles the `GET /crates/:crate_id/:version/license-text` route.
+///
+/// Redirects to a JSON document with the `license` expression and the
+/// license and copyright files of the version. Versions without any license
+/// files don't have such a document.
+pub async fn license_text(
+    app: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+    req: Parts,
+) -> Response {
+    let redirect_url = app.storage.license_texts_location(&crate_name, &version);
     if req.wants_json() {
         Json(json!({ "url": redirect_url })).into_response()
     } else {
@@ -368,6 +412,8 @@ pub async fn reverse_dependencies(
             .map(|dep| EncodableDependency::from_reverse_dep(dep, &krate.name))
             .collect();
 
+        let support_warnings = support_warnings(&krate, &rev_deps, conn)?;
+
         let version_ids: Vec<i32> = rev_deps.iter().map(|dep| dep.version_id).collect();
 
         let versions_and_publishers: Vec<(Version, String, Option<User>)> = versions::table
@@ -396,8 +442,84 @@ pub async fn reverse_dependencies(
         Ok(Json(json!({
             "dependencies": rev_deps,
             "versions": versions,
+            "support_warnings": support_warnings,
             "meta": { "total": total },
         })))
     })
     .await
 }
+
+/// Returns warnings for all reverse dependencies whose version requirement
+/// resolves to a release line of the crate that only receives security fixes
+/// or reached its end of life.
+fn support_warnings(
+    krate: &Crate,
+    rev_deps: &[EncodableDependency],
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<EncodableSupportWarning>> {
+    let windows: HashMap<_, _> = SupportWindow::for_crate(krate, conn)?
+        .into_iter()
+        .filter(|window| window.status != SupportStatus::Supported)
+        .map(|window| (window.major_version.clone(), window))
+        .collect();
+
+    if windows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let versions = versions::table
+        .filter(versions::crate_id.eq(krate.id))
+        .filter(versions::yanked.eq(false))
+        .select(versions::num)
+        .load::<String>(conn)?
+        .into_iter()
+        .filter_map(|num| semver::Version::parse(&num).ok())
+        .collect::<Vec<_>>();
+
+    let today = Utc::now().date_naive();
+
+    let warnings = rev_deps
+        .iter()
+        .filter_map(|dep| {
+            let req = semver::VersionReq::parse(&dep.req).ok()?;
+            let version = versions.iter().filter(|v| req.matches(v)).max()?;
+            let window = windows.get(&major_version(version))?;
+
+            Some(EncodableSupportWarning {
+                dependency_id: dep.id,
+                major_version: window.major_version.clone(),
+                status: window.status.as_str().to_string(),
+                end_of_life_date: window.end_of_life_date,
+                message: support_warning_message(&krate.name, window, today),
+            })
+        })
+        .collect();
+
+    Ok(warnings)
+}
+
+fn support_warning_message(crate_name: &str, window: &SupportWindow, today: NaiveDate) -> String {
+    let release_line = format!(
+        "the `{}` release line of `{crate_name}`",
+        window.major_version
+    );
+
+    match (window.status, window.end_of_life_date) {
+        (SupportStatus::EndOfLife, Some(date)) if date > today => {
+            format!("{release_line} reaches its end of life on {date}")
+        }
+        (SupportStatus::EndOfLife, Some(date)) => {
+            format!("{release_line} reached its end of life on {date}")
+        }
+        (SupportStatus::SecurityFixesOnly, Some(date)) if date > today => {
+            format!("{release_line} only receives security fixes until {date}")
+        }
+        (SupportStatus::SecurityFixesOnly, Some(date)) => {
+            format!("{release_line} stopped receiving security fixes on {date}")
+        }
+        (SupportStatus::SecurityFixesOnly, None) => {
+            format!("{release_line} only receives security fixes")
+        }
+        (_, _) => format!("{release_line} reached its end of life"),
+    }
+}
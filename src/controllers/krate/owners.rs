@@ -3,7 +3,9 @@
 use crate::auth::AuthCheck;
 use crate::controllers::prelude::*;
 use crate::models::token::EndpointScope;
-use crate::models::{Crate, Owner, Rights, Team, User};
+use crate::models::{
+    AccountCompromise, Crate, CrateOwnerInvitation, Owner, OwnerInviteEmail, Rights, Team, User,
+};
 use crate::views::EncodableOwner;
 use axum::body::Bytes;
 use http::Request;
@@ -72,6 +74,67 @@ pub async fn remove_owners(
     conduit_compat(move || modify_owners(&app, &crate_name, &req, false)).await
 }
 
+/// Handles the `PATCH /crates/:crate_id/owners` route.
+pub async fn batch_modify_owners(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || batch_modify(&app, &crate_name, &req)).await
+}
+
+/// Handles the `PUT /crates/:crate_id/owner_invitations/:login/resend` route.
+///
+/// Renews an expired ownership invitation and sends the invitation email
+/// to the invited user again.
+pub async fn resend_invitation(
+    app: AppState,
+    Path((crate_name, login)): Path<(String, String)>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::default()
+            .with_endpoint_scope(EndpointScope::ChangeOwners)
+            .for_crate(&crate_name)
+            .check(&req, conn)?;
+
+        let user = auth.user();
+
+        let (invitee, krate, token) = conn.transaction(|conn| {
+            let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+            let owners = krate.owners(conn)?;
+
+            ensure_owner_rights(&app, user, &owners)?;
+
+            let Owner::User(invitee) = Owner::find_or_create_by_login(&app, conn, user, &login)?
+            else {
+                return Err(cargo_err("only invitations of users can be re-sent"));
+            };
+
+            let invitation = CrateOwnerInvitation::find_by_id(invitee.id, krate.id, conn)?;
+            let token = invitation.renew(user.id, conn, &app.config)?;
+
+            Ok((invitee, krate, token))
+        })?;
+
+        if let Ok(Some(email)) = invitee.verified_email(conn) {
+            // Swallow any error, the renewed invitation is visible to the
+            // user on https://crates.io/me/pending-invites/ either way.
+            let _ = app
+                .emails
+                .send_owner_invite(&email, &user.gh_login, &krate.name, &token);
+        }
+
+        let msg = format!(
+            "user {} has been invited again to be an owner of crate {}",
+            invitee.gh_login, krate.name
+        );
+        Ok(Json(json!({ "ok": true, "msg": msg })))
+    })
+    .await
+}
+
 /// Parse the JSON request body of requests to modify the owners of a crate.
 ///
 /// The format is:
@@ -109,50 +172,196 @@ fn modify_owners(
         .check(req, conn)?;
 
     let user = auth.user();
+    if add {
+        AccountCompromise::ensure_not_active(user.id, conn)?;
+    }
 
-    conn.transaction(|conn| {
+    let mut invite_emails = Vec::new();
+    let comma_sep_msg = conn.transaction::<_, BoxedAppError, _>(|conn| {
         let krate: Crate = Crate::by_name(crate_name).first(conn)?;
         let owners = krate.owners(conn)?;
 
-        match user.rights(app, &owners)? {
-            Rights::Full => {}
-            // Yes!
-            Rights::Publish => {
-                return Err(cargo_err(
-                    "team members don't have permission to modify owners",
-                ));
-            }
-            Rights::None => {
-                return Err(cargo_err("only owners have permission to modify owners"));
-            }
-        }
+        ensure_owner_rights(app, user, &owners)?;
 
-        let comma_sep_msg = if add {
+        if add {
             let mut msgs = Vec::with_capacity(logins.len());
             for login in &logins {
-                let login_test =
-                    |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
-                if owners.iter().any(login_test) {
-                    return Err(cargo_err(&format_args!("`{login}` is already an owner")));
-                }
-                let msg = krate.owner_add(app, conn, user, login)?;
+                let (msg, invite_email) = add_owner(app, conn, &krate, &owners, user, login)?;
                 msgs.push(msg);
+                invite_emails.extend(invite_email);
             }
-            msgs.join(",")
+            Ok(msgs.join(","))
         } else {
             for login in &logins {
                 krate.owner_remove(app, conn, user, login)?;
             }
-            if User::owning(&krate, conn)?.is_empty() {
-                return Err(cargo_err(
-                    "cannot remove all individual owners of a crate. \
-                     Team member don't have permission to modify owners, so \
-                     at least one individual owner is required.",
-                ));
+            ensure_individual_owner_left(&krate, conn)?;
+            Ok("owners successfully removed".to_owned())
+        }
+    })?;
+
+    for invite_email in invite_emails {
+        invite_email.send(&app.emails);
+    }
+
+    Ok(Json(json!({ "ok": true, "msg": comma_sep_msg })))
+}
+
+/// Parse the JSON request body of requests to modify the owners of a crate
+/// in a single batch.
+///
+/// The format is:
+///
+/// ```json
+/// {"add": ["username", "github:org:team", ...], "remove": ["username", ...]}
+/// ```
+fn parse_batch_owners_request(req: &Request<Bytes>) -> AppResult<(Vec<String>, Vec<String>)> {
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    }
+    let request: Request =
+        serde_json::from_slice(req.body()).map_err(|_| cargo_err("invalid json request"))?;
+    if request.add.is_empty() && request.remove.is_empty() {
+        return Err(cargo_err("invalid json request"));
+    }
+    Ok((request.add, request.remove))
+}
+
+/// The outcome of adding or removing a single owner as part of a batch request.
+#[derive(Serialize)]
+struct BatchOwnerResult {
+    owner: String,
+    action: &'static str,
+    ok: bool,
+    msg: String,
+}
+
+/// Adds and removes multiple owners of a crate within a single transaction.
+///
+/// Every entry is processed, even if a previous one failed, so that the
+/// response can report the outcome of each entry. If any of them failed, none
+/// of the changes are applied.
+fn batch_modify(app: &AppState, crate_name: &str, req: &Request<Bytes>) -> AppResult<Json<Value>> {
+    let (to_add, to_remove) = parse_batch_owners_request(req)?;
+
+    let conn = &mut *app.db_write()?;
+    let auth = AuthCheck::default()
+        .with_endpoint_scope(EndpointScope::ChangeOwners)
+        .for_crate(crate_name)
+        .check(req, conn)?;
+
+    let user = auth.user();
+    if !to_add.is_empty() {
+        AccountCompromise::ensure_not_active(user.id, conn)?;
+    }
+
+    let mut results = Vec::with_capacity(to_add.len() + to_remove.len());
+    let mut invite_emails = Vec::new();
+    let mut failed = false;
+
+    let result = conn.transaction(|conn| {
+        let krate: Crate = Crate::by_name(crate_name).first(conn)?;
+        let owners = krate.owners(conn)?;
+
+        ensure_owner_rights(app, user, &owners)?;
+
+        for login in &to_add {
+            // Each entry uses a savepoint, so that a failing entry does not
+            // abort the whole transaction for the remaining entries.
+            let result =
+                conn.transaction(|conn| add_owner(app, conn, &krate, &owners, user, login));
+
+            failed |= result.is_err();
+            results.push(BatchOwnerResult {
+                owner: login.clone(),
+                action: "add",
+                ok: result.is_ok(),
+                msg: match result {
+                    Ok((msg, invite_email)) => {
+                        invite_emails.extend(invite_email);
+                        msg
+                    }
+                    Err(error) => error.to_string(),
+                },
+            });
+        }
+
+        for login in &to_remove {
+            let result = conn.transaction(|conn| krate.owner_remove(app, conn, user, login));
+
+            failed |= result.is_err();
+            results.push(BatchOwnerResult {
+                owner: login.clone(),
+                action: "remove",
+                ok: result.is_ok(),
+                msg: match result {
+                    Ok(()) => format!(
+                        "`{login}` has been removed as an owner of crate {}",
+                        krate.name
+                    ),
+                    Err(error) => error.to_string(),
+                },
+            });
+        }
+
+        if failed {
+            return Err(cargo_err(
+                "failed to modify owners, no changes were applied",
+            ));
+        }
+
+        ensure_individual_owner_left(&krate, conn)
+    });
+
+    match result {
+        Ok(()) => {
+            for invite_email in invite_emails {
+                invite_email.send(&app.emails);
             }
-            "owners successfully removed".to_owned()
-        };
+            Ok(Json(json!({ "ok": true, "results": results })))
+        }
+        Err(_) if failed => Ok(Json(json!({ "ok": false, "results": results }))),
+        Err(error) => Err(error),
+    }
+}
 
-        Ok(Json(json!({ "ok": true, "msg": comma_sep_msg })))
-    })
+fn ensure_owner_rights(app: &AppState, user: &User, owners: &[Owner]) -> AppResult<()> {
+    match user.rights(app, owners)? {
+        Rights::Full => Ok(()),
+        // Yes!
+        Rights::Publish => Err(cargo_err(
+            "team members don't have permission to modify owners",
+        )),
+        Rights::None => Err(cargo_err("only owners have permission to modify owners")),
+    }
+}
+
+fn add_owner(
+    app: &AppState,
+    conn: &mut PgConnection,
+    krate: &Crate,
+    owners: &[Owner],
+    user: &User,
+    login: &str,
+) -> AppResult<(String, Option<OwnerInviteEmail>)> {
+    let login_test = |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
+    if owners.iter().any(login_test) {
+        return Err(cargo_err(&format_args!("`{login}` is already an owner")));
+    }
+    krate.owner_add(app, conn, user, login)
+}
+
+fn ensure_individual_owner_left(krate: &Crate, conn: &mut PgConnection) -> AppResult<()> {
+    if User::owning(krate, conn)?.is_empty() {
+        return Err(cargo_err(
+            "cannot remove all individual owners of a crate. \
+             Team member don't have permission to modify owners, so \
+             at least one individual owner is required.",
+        ));
+    }
+    Ok(())
 }
@@ -3,28 +3,55 @@
 use crate::auth::AuthCheck;
 use crate::background_jobs::{Job, PRIORITY_RENDER_README};
 use axum::body::Bytes;
-use crates_io_tarball::{process_tarball, TarballError};
+use crates_io_tarball::{
+    process_tarball_with_validator, summarize_tarball, validate_manifest, Compression,
+    ContentFingerprint, DenyPaths, LockfileInfo, ManifestLimits, NoExecutables, TarballError,
+    TarballLimits, ValidatorSet,
+};
 use hex::ToHex;
 use hyper::body::Buf;
-use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 use tokio::runtime::Handle;
 
 use crate::controllers::cargo_prelude::*;
 use crate::controllers::util::RequestPartsExt;
 use crate::models::{
-    insert_version_owner_action, Category, Crate, Keyword, NewCrate, NewVersion, Rights,
-    VersionAction,
+    insert_version_owner_action, AccountCompromise, Category, Crate, DependencyKind, Keyword,
+    NewCrate, NewCrossRegistryDependency, NewDependency, NewModerationFlag, NewPublishDiagnostics,
+    NewVersion, Rights, Tombstone, Version, VersionAction, VersionFingerprint,
+    VersionPublishWarnings, VersionScanReport,
 };
 
+use crate::config::{CrossRegistryConfig, DuplicateContentConfig, Server, VersionPolicyConfig};
 use crate::middleware::log_request::RequestLogExt;
 use crate::models::token::EndpointScope;
 use crate::schema::*;
-use crate::util::errors::{cargo_err, internal, AppResult};
+use crate::sql::{canon_crate_name, hashtext, pg_try_advisory_xact_lock};
+use crate::storage::{ReplicatedFile, StorageUnavailable, DRY_RUN_EXPIRATION_MINUTES};
+use crate::util::errors::{cargo_err, internal, service_unavailable, AppResult, PublishInProgress};
+use crate::util::source_links::VersionSource;
+use crate::util::token::generate_secure_alphanumeric_string;
+use crate::util::url_normalization::normalize_url;
 use crate::util::Maximums;
 use crate::views::{
-    EncodableCrate, EncodableCrateDependency, EncodableCrateUpload, GoodCrate, PublishWarnings,
+    is_blocked_documentation_url, EncodableCrate, EncodableCrateDependency, EncodableCrateUpload,
+    EncodableLicenseTexts, EncodablePublishDryRun, GoodCrate, PublishWarningKind, PublishWarnings,
 };
+use crate::worker::{render_readme, RenderLimits, RenderedReadme};
+use crate::App;
+
+/// The maximum number of fingerprints of other crates that the fingerprint
+/// of a new crate is compared with.
+const MAX_DUPLICATE_CANDIDATES: i64 = 500;
+
+/// The first key of the advisory locks that are taken while publishing a
+/// crate, which keeps them apart from other advisory locks.
+const PUBLISH_LOCK_NAMESPACE: i32 = 1;
+
+/// The length of the tokens that refer to the diagnostics of rejected crate
+/// files.
+const DIAGNOSTICS_TOKEN_LENGTH: usize = 32;
 
 pub const MISSING_RIGHTS_ERROR_MESSAGE: &str =
     "this crate exists but you don't seem to be an owner. \
@@ -39,16 +66,35 @@ pub const MISSING_RIGHTS_ERROR_MESSAGE: &str =
 /// Currently blocks the HTTP thread, perhaps some function calls can spawn new
 /// threads and return completion or error through other methods  a `cargo publish
 /// --status` command, via crates.io's front end, or email.
+///
+/// If the `dry_run=true` query parameter is set, the upload is processed as
+/// usual, but the database transaction is rolled back at the end. The crate
+/// file and the rendered README are uploaded to a shadow namespace instead of
+/// their regular location, where they are deleted by the
+/// `expire_publish_dry_runs` background job once they expired. The response
+/// then additionally contains the index entry that would have been written
+/// and the locations of the uploaded files, which allows publishing tools to
+/// run end-to-end tests against the real service.
 pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCrate>> {
     let (req, bytes) = req.0.into_parts();
-    let (json_bytes, tarball_bytes) = split_body(bytes, &req)?;
 
-    let new_crate: EncodableCrateUpload = serde_json::from_slice(&json_bytes)
-        .map_err(|e| cargo_err(&format_args!("invalid upload request: {e}")))?;
+    let dry_run = req.query().get("dry_run").map_or(false, |v| v == "true");
+
+    let (mut new_crate, tarball_bytes) = stage(&app, "parse_body", || {
+        let (json_bytes, tarball_bytes) = split_body(bytes, &req)?;
+
+        let new_crate: EncodableCrateUpload = serde_json::from_slice(&json_bytes)
+            .map_err(|e| cargo_err(&format_args!("invalid upload request: {e}")))?;
+
+        Ok::<_, BoxedAppError>((new_crate, tarball_bytes))
+    })?;
 
     let request_log = req.request_log();
     request_log.add("crate_name", new_crate.name.to_string());
     request_log.add("crate_version", new_crate.vers.to_string());
+    if dry_run {
+        request_log.add("dry_run", true);
+    }
 
     // Make sure required fields are provided
     fn empty(s: Option<&String>) -> bool {
@@ -69,6 +115,9 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
         return Err(cargo_err(&message));
     }
 
+    canonicalize_requirements(&mut new_crate.deps)?;
+    check_metadata_limits(&new_crate, &app.config.manifest_limits)?;
+
     conduit_compat(move || {
         let conn = &mut *app.primary_database.get()?;
 
@@ -101,14 +150,71 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             ))
         })?;
 
+        if AccountCompromise::is_active(user.id, conn)? {
+            return Err(cargo_err(
+                "Publishing from this account is on hold because it may have been compromised. \
+                 Please contact help@crates.io for further information.",
+            ));
+        }
+
+        let violations = app.config.publish_policy.evaluate(&new_crate, conn)?;
+        if !violations.is_empty() {
+            let violations = violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(cargo_err(&format_args!(
+                "the crate does not comply with the publish policy of this registry: {violations}"
+            )));
+        }
+
+        // Dry runs process everything within the transaction below, but then
+        // abort it and return the response that was stashed here instead.
+        let mut dry_run_response = None;
+
+        // The context of a rejected crate file is only inserted once the
+        // transaction has been rolled back, so it is stashed here too.
+        let mut diagnostics = None;
+
         // Create a transaction on the database, if there are no errors,
         // commit the transactions to record a new or updated crate.
-        conn.transaction(|conn| {
+        let result = conn.transaction(|conn| {
+            // Concurrent publishes of the same crate could enqueue their index
+            // updates in a different order than their versions are inserted,
+            // so the slower one is rejected instead of waiting for the lock.
+            if !try_lock_crate_for_publish(&new_crate.name, conn)? {
+                return Err(Box::new(PublishInProgress {
+                    crate_name: new_crate.name.to_string(),
+                }) as BoxedAppError);
+            }
+
             let _ = &new_crate;
             let name = new_crate.name;
             let vers = &*new_crate.vers;
             let links = new_crate.links;
-            let repo = new_crate.repository;
+            let repo = new_crate.repository.as_deref().map(normalize_url);
+            let homepage = new_crate.homepage.as_deref().map(normalize_url);
+            let documentation = new_crate.documentation.as_deref().map(normalize_url);
+            let documentation_warning = documentation
+                .as_deref()
+                .filter(|url| is_blocked_documentation_url(url))
+                .map(|url| {
+                    format!(
+                        "the documentation URL `{url}` points to a host that is known not to \
+                         host documentation, so it will not be displayed"
+                    )
+                });
+            let manifest_warnings = new_crate
+                .badges
+                .keys()
+                .map(|badge| {
+                    format!(
+                        "the `{badge}` badge of the `[badges]` manifest section was ignored, \
+                         because badges are no longer displayed on crates.io"
+                    )
+                })
+                .collect::<Vec<_>>();
             let features = new_crate
                 .features
                 .into_iter()
@@ -119,6 +225,8 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
                 .iter()
                 .map(|s| s.as_str())
                 .collect::<Vec<_>>();
+            let keyword_warnings = duplicate_keyword_warnings(&keywords);
+            let graph_warnings = dependency_graph_warnings(&name, &new_crate.deps);
             let categories = new_crate
                 .categories
                 .iter()
@@ -129,13 +237,15 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             let persist = NewCrate {
                 name: &name,
                 description: new_crate.description.as_deref(),
-                homepage: new_crate.homepage.as_deref(),
-                documentation: new_crate.documentation.as_deref(),
+                homepage: homepage.as_deref(),
+                documentation: documentation.as_deref(),
                 readme: new_crate.readme.as_deref(),
                 repository: repo.as_deref(),
                 max_upload_size: None,
             };
 
+            check_deletion_cooldown(&name, vers, app.config.deleted_name_cooldown, conn)?;
+
             let license_file = new_crate.license_file.as_deref();
             let krate = persist.create_or_update(conn, user.id, Some(&app.config.rate_limiter))?;
 
@@ -166,6 +276,7 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
                 krate.max_upload_size,
                 app.config.max_upload_size,
                 app.config.max_unpack_size,
+                app.config.max_unpack_file_size,
             );
 
             if content_length > maximums.max_upload_size {
@@ -179,101 +290,666 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             let license = new_crate.license.clone();
 
             // Read tarball from request
-            let hex_cksum: String = Sha256::digest(&tarball_bytes).encode_hex();
+            let (hex_cksum, tarball_info) = stage(&app, "validate_tarball", || {
+                // Check the compression format before decompressing anything, so
+                // that we don't spend time on formats that are not accepted.
+                if let Some(compression) = Compression::detect(&tarball_bytes) {
+                    if !app.config.allowed_compressions.contains(&compression) {
+                        return Err(cargo_err(&format_args!(
+                            "{compression} compressed crate files are not accepted by this registry"
+                        )));
+                    }
+                }
+
+                let mut reject = |error: TarballError| {
+                    let Some(retention) = app.config.publish_diagnostics_retention else {
+                        return tarball_to_app_error(error);
+                    };
+
+                    let (error, new_diagnostics) = rejection_diagnostics(
+                        error,
+                        retention,
+                        user.id,
+                        &krate.name,
+                        vers,
+                        &tarball_bytes,
+                        maximums.max_unpack_size,
+                    );
+                    diagnostics = new_diagnostics;
+                    error
+                };
+
+                let pkg_name = format!("{}-{}", krate.name, vers);
+                let limits = TarballLimits {
+                    max_unpack: maximums.max_unpack_size,
+                    max_entries: app.config.max_unpack_entries,
+                    max_file_size: maximums.max_unpack_file_size,
+                    case_insensitive_paths: app.config.case_insensitive_tarball_paths,
+                };
+                let tarball_info = process_tarball_with_validator(
+                    &pkg_name,
+                    &*tarball_bytes,
+                    &limits,
+                    &tarball_validator(&app.config),
+                )
+                .map_err(&mut reject)?;
+
+                if let Some(manifest) = &tarball_info.manifest {
+                    validate_manifest(manifest, &app.config.manifest_limits)
+                        .map_err(&mut reject)?;
+                }
+
+                let hex_cksum: String = tarball_info.tarball_checksum.encode_hex();
+                Ok::<_, BoxedAppError>((hex_cksum, tarball_info))
+            })?;
+
+            let file_warnings = tarball_info
+                .files
+                .iter()
+                .filter(|file| file.size >= app.config.large_file_warning_size)
+                .map(|file| {
+                    format!(
+                        "the file `{}` is {} bytes large, consider excluding it from the \
+                         package if it is not needed to build the crate",
+                        file.path.display(),
+                        file.size
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let lockfile_warnings = match &tarball_info.lockfile {
+                Some(lockfile) => stage(&app, "check_lockfile", || {
+                    yanked_lockfile_warnings(conn, lockfile)
+                })?,
+                None => Vec::new(),
+            };
+
+            let invalid_lockfile_warning = tarball_info.lockfile_error.as_ref().map(|error| {
+                format!(
+                    "the `Cargo.lock` file could not be parsed, so its pinned dependencies \
+                     were not checked: {error}"
+                )
+            });
+
+            let dirty_worktree_warning = tarball_info
+                .vcs_info
+                .as_ref()
+                .filter(|vcs_info| vcs_info.is_dirty())
+                .map(|vcs_info| {
+                    let commit = vcs_info.commit_sha().unwrap_or_default();
+                    format!(
+                        "the crate was packaged from a git working tree with uncommitted \
+                         changes, so its contents may differ from commit `{commit}`; \
+                         consider publishing from a clean checkout"
+                    )
+                });
+            let dirty_worktree = dirty_worktree_warning.is_some();
 
-            let pkg_name = format!("{}-{}", krate.name, vers);
-            let tarball_info =
-                process_tarball(&pkg_name, &*tarball_bytes, maximums.max_unpack_size)
-                    .map_err(tarball_to_app_error)?;
+            let source = VersionSource::new(repo.as_deref(), tarball_info.vcs_info.as_ref());
 
             let rust_version = tarball_info
                 .manifest
                 .and_then(|m| m.package.rust_version)
                 .map(|rv| rv.deref().to_string());
 
-            // Persist the new version of this crate
-            let version = NewVersion::new(
-                krate.id,
-                vers,
-                &features,
-                license,
-                license_file,
-                // Downcast is okay because the file length must be less than the max upload size
-                // to get here, and max upload sizes are way less than i32 max
-                content_length as i32,
-                user.id,
-                hex_cksum,
-                links,
-                rust_version,
-            )?
-            .save(conn, &verified_email_address)?;
-
-            insert_version_owner_action(
-                conn,
-                version.id,
-                user.id,
-                api_token_id,
-                VersionAction::Publish,
-            )?;
-
-            // Link this new version to all dependencies
-            add_dependencies(conn, &new_crate.deps, version.id)?;
-
-            // Update all keywords for this crate
-            Keyword::update_crate(conn, &krate, &keywords)?;
-
-            // Update all categories for this crate, collecting any invalid categories
-            // in order to be able to warn about them
-            let ignored_invalid_categories = Category::update_crate(conn, &krate, &categories)?;
-
-            let top_versions = krate.top_versions(conn)?;
+            let version_warnings = stage(&app, "check_version_number", || {
+                let existing_versions = unyanked_versions(krate.id, conn)?;
+                let config = &app.config.version_policy;
+                Ok::<_, BoxedAppError>(version_number_warnings(config, &existing_versions, vers))
+            })?;
 
-            let pkg_path_in_vcs = tarball_info.vcs_info.map(|info| info.path_in_vcs);
+            let (version, ignored_invalid_categories, dependency_warnings, top_versions) =
+                stage(&app, "insert_version", || {
+                    // Persist the new version of this crate
+                    let version = NewVersion::new(
+                        krate.id,
+                        vers,
+                        &features,
+                        license,
+                        license_file,
+                        // Downcast is okay because the file length must be less than the max
+                        // upload size to get here, and max upload sizes are way less than i32 max
+                        content_length as i32,
+                        user.id,
+                        hex_cksum,
+                        links,
+                        rust_version,
+                        dirty_worktree,
+                        source,
+                    )?
+                    .save(conn, &verified_email_address)?;
+
+                    insert_version_owner_action(
+                        conn,
+                        version.id,
+                        user.id,
+                        api_token_id,
+                        VersionAction::Publish,
+                    )?;
+
+                    let fingerprint = &tarball_info.fingerprint;
+                    VersionFingerprint::record(version.id, krate.id, &fingerprint.hashes, conn)?;
+                    let config = &app.config.duplicate_content;
+                    flag_duplicate_content(conn, config, &krate, version.id, fingerprint)?;
+                    flag_version_jump(conn, &krate, &version, &version_warnings)?;
 
-            if let Some(readme) = new_crate.readme {
-                if !readme.is_empty() {
-                    Job::render_and_upload_readme(
+                    // Suspicious files don't block the publish, but are kept
+                    // for moderators to review via `crates-admin scan-reports`
+                    if !tarball_info.scan.is_clean() {
+                        VersionScanReport::record(version.id, &tarball_info.scan, conn)?;
+                    }
+
+                    // Link this new version to all dependencies
+                    let dependency_warnings = add_dependencies(
+                        conn,
+                        &new_crate.deps,
                         version.id,
-                        readme,
-                        new_crate
-                            .readme_file
-                            .unwrap_or_else(|| String::from("README.md")),
-                        repo,
+                        &app.config.cross_registry,
+                    )?;
+
+                    // Update all keywords for this crate
+                    Keyword::update_crate(conn, &krate, &keywords)?;
+
+                    // Update all categories for this crate, collecting any invalid categories
+                    // in order to be able to warn about them
+                    let ignored_invalid_categories =
+                        Category::update_crate(conn, &krate, &categories)?;
+
+                    let top_versions = krate.top_versions(conn)?;
+
+                    Ok::<_, BoxedAppError>((
+                        version,
+                        ignored_invalid_categories,
+                        dependency_warnings,
+                        top_versions,
+                    ))
+                })?;
+
+            let pkg_path_in_vcs = tarball_info.vcs_info.map(|info| info.path_in_vcs);
+
+            // The readme is rendered from the file in the crate file, which is
+            // what was actually published. The readme of the publish metadata
+            // is only a fallback if the crate file doesn't include it.
+            let readme = tarball_info
+                .readme_contents
+                .or(new_crate.readme)
+                .filter(|readme| !readme.is_empty());
+            let readme_path = new_crate
+                .readme_file
+                .unwrap_or_else(|| String::from("README.md"));
+
+            // Dry runs render the README once the transaction was rolled
+            // back, since the job would be rolled back with it.
+            let mut dry_run_readme = None;
+            if let Some(readme) = readme {
+                if dry_run {
+                    dry_run_readme = Some(DryRunReadme {
+                        text: readme,
+                        path: readme_path,
+                        base_url: repo,
                         pkg_path_in_vcs,
-                    )
-                    .enqueue_with_priority(conn, PRIORITY_RENDER_README)?;
+                    });
+                } else {
+                    // The README is rendered by a background job, which is
+                    // instrumented separately.
+                    stage(&app, "enqueue_readme", || {
+                        Job::render_and_upload_readme(
+                            version.id,
+                            readme,
+                            readme_path,
+                            repo,
+                            pkg_path_in_vcs,
+                        )
+                        .enqueue_with_priority(conn, PRIORITY_RENDER_README)
+                    })?;
+                }
+            }
+
+            // Upload crate tarball. Dry runs upload it to their shadow
+            // namespace once the transaction was rolled back instead.
+            let mut dry_run_crate_file = None;
+            if dry_run {
+                dry_run_crate_file = Some(tarball_bytes);
+            } else {
+                stage(&app, "upload_crate", || {
+                    Handle::current()
+                        .block_on(app.storage.upload_crate_file(
+                            &krate.name,
+                            &vers.to_string(),
+                            tarball_bytes,
+                        ))
+                        .map_err(|e| {
+                            if StorageUnavailable::is(&e) {
+                                service_unavailable(
+                                    "The crate storage is temporarily unavailable, please try again later",
+                                )
+                            } else {
+                                internal(format!("failed to upload crate: {e}"))
+                            }
+                        })
+                })?;
+
+                let file = ReplicatedFile::crate_file(&krate.name, &vers.to_string());
+                Job::enqueue_replication(&app.storage, file, conn)?;
+            }
+
+            // The license texts are only a copy of files of the crate file, so
+            // failing to upload them doesn't fail the publish.
+            let license_texts = EncodableLicenseTexts::from(tarball_info.license);
+            if !dry_run && !license_texts.files.is_empty() {
+                let result = stage(&app, "upload_license_texts", || {
+                    let bytes = serde_json::to_vec(&license_texts)?;
+                    let future = app.storage.upload_license_texts(
+                        &krate.name,
+                        &vers.to_string(),
+                        bytes.into(),
+                    );
+                    Ok::<_, anyhow::Error>(Handle::current().block_on(future)?)
+                });
+                if let Err(error) = result {
+                    warn!(%error, "Failed to upload license texts");
                 }
             }
 
-            // Upload crate tarball
-            Handle::current()
-                .block_on(app.storage.upload_crate_file(
-                    &krate.name,
-                    &vers.to_string(),
-                    tarball_bytes,
-                ))
-                .map_err(|e| internal(format!("failed to upload crate: {e}")))?;
-
-            Job::enqueue_sync_to_index(&krate.name, conn)?;
-
-            // The `other` field on `PublishWarnings` was introduced to handle a temporary warning
-            // that is no longer needed. As such, crates.io currently does not return any `other`
-            // warnings at this time, but if we need to, the field is available.
-            let warnings = PublishWarnings {
-                invalid_categories: ignored_invalid_categories,
-                invalid_badges: vec![],
-                other: vec![],
+            stage(&app, "enqueue_index_sync", || {
+                Job::enqueue_sync_to_index(&krate.name, conn)
+            })?;
+
+            let mut warnings = PublishWarnings::default();
+            for category in ignored_invalid_categories {
+                warnings.add_invalid_category(category);
+            }
+            for message in dependency_warnings {
+                warnings.add(PublishWarningKind::UnknownRegistry, message);
+            }
+            for message in keyword_warnings {
+                warnings.add(PublishWarningKind::DuplicateKeyword, message);
+            }
+            for warning in &graph_warnings {
+                warnings.add(warning.kind(), warning.message(&krate.name));
+            }
+            for message in manifest_warnings {
+                warnings.add(PublishWarningKind::DeprecatedField, message);
+            }
+            for message in file_warnings {
+                warnings.add(PublishWarningKind::LargeFile, message);
+            }
+            for message in lockfile_warnings {
+                warnings.add(PublishWarningKind::YankedDependency, message);
+            }
+            if let Some(message) = invalid_lockfile_warning {
+                warnings.add(PublishWarningKind::InvalidLockfile, message);
+            }
+            for warning in &version_warnings {
+                warnings.add(warning.kind(), warning.message(vers));
+            }
+            if let Some(message) = documentation_warning {
+                warnings.add(PublishWarningKind::BlockedDocumentationUrl, message);
+            }
+            if let Some(message) = dirty_worktree_warning {
+                warnings.add(PublishWarningKind::DirtyWorktree, message);
+            }
+
+            // The warnings are kept with the version, so that they can be
+            // looked up after the publish response is gone.
+            if !warnings.details.is_empty() {
+                VersionPublishWarnings::record(version.id, json!(warnings.details), conn)?;
+            }
+
+            let index_entry = if dry_run {
+                let index_entry = krate
+                    .index_metadata(conn)?
+                    .into_iter()
+                    .find(|entry| entry.vers == vers.to_string());
+
+                Some(index_entry.ok_or_else(|| internal("index entry not found"))?)
+            } else {
+                None
             };
 
-            Ok(Json(GoodCrate {
+            let crate_name = krate.name.clone();
+            let good_crate = GoodCrate {
                 krate: EncodableCrate::from_minimal(krate, Some(&top_versions), None, false, None),
                 warnings,
-            }))
-        })
+                index_entry,
+                dry_run: None,
+            };
+
+            if let Some(crate_file) = dry_run_crate_file {
+                let files = DryRunFiles {
+                    crate_name,
+                    version: vers.to_string(),
+                    crate_file,
+                    readme: dry_run_readme,
+                };
+                dry_run_response = Some((good_crate, files));
+                return Err(cargo_err("dry run, rolling back the transaction"));
+            }
+
+            Ok(Json(good_crate))
+        });
+
+        if let (Err(_), Some(diagnostics)) = (&result, diagnostics) {
+            if let Err(error) = diagnostics.insert(conn) {
+                warn!(%error, "Failed to store publish diagnostics");
+            }
+        }
+
+        match dry_run_response {
+            Some((mut good_crate, files)) => {
+                good_crate.dry_run = Some(upload_dry_run(&app, files)?);
+                Ok(Json(good_crate))
+            }
+            None => result,
+        }
     })
     .await
 }
 
+/// The files of a publish dry run, which are uploaded to the shadow namespace
+/// of the dry run once its transaction was rolled back.
+struct DryRunFiles {
+    crate_name: String,
+    version: String,
+    crate_file: Bytes,
+    readme: Option<DryRunReadme>,
+}
+
+/// The README of a publish dry run, with the arguments that the render job
+/// would have been enqueued with.
+struct DryRunReadme {
+    text: String,
+    path: String,
+    base_url: Option<String>,
+    pkg_path_in_vcs: Option<String>,
+}
+
+/// Renders the README of a publish dry run and uploads it with the crate file
+/// below a new random prefix, where they expire after
+/// [`DRY_RUN_EXPIRATION_MINUTES`].
+fn upload_dry_run(app: &App, files: DryRunFiles) -> AppResult<EncodablePublishDryRun> {
+    let DryRunFiles {
+        crate_name,
+        version,
+        crate_file,
+        readme,
+    } = files;
+
+    let readme = readme
+        .map(|readme| {
+            stage(app, "render_readme", || {
+                render_readme(
+                    &readme.text,
+                    &readme.path,
+                    readme.base_url.as_deref(),
+                    readme.pkg_path_in_vcs.as_deref(),
+                    RenderLimits::from_environment(),
+                )
+            })
+        })
+        .transpose()
+        .map_err(|_| service_unavailable("The README renderer is busy, please try again later"))?;
+
+    let id = generate_secure_alphanumeric_string(32);
+    let expires_at =
+        chrono::Utc::now().naive_utc() + chrono::Duration::minutes(DRY_RUN_EXPIRATION_MINUTES);
+
+    let has_readme = readme.is_some();
+    let (html, readme_error) = match readme {
+        Some(RenderedReadme { html, error }) => (Some(Bytes::from(html)), error),
+        None => (None, None),
+    };
+
+    stage(app, "upload_dry_run", || {
+        let future = app
+            .storage
+            .upload_dry_run(&id, &crate_name, &version, crate_file, html);
+        Handle::current()
+            .block_on(future)
+            .map_err(|e| internal(format!("failed to upload dry run: {e}")))
+    })?;
+
+    Ok(EncodablePublishDryRun {
+        crate_file: app
+            .storage
+            .dry_run_crate_location(&id, &crate_name, &version),
+        readme: has_readme.then(|| {
+            app.storage
+                .dry_run_readme_location(&id, &crate_name, &version)
+        }),
+        readme_error,
+        expires_at,
+    })
+}
+
+/// Runs `f` within a tracing span for the given stage of the publish endpoint
+/// and records its duration in the `publish_stage_duration` metric, so that
+/// slow publishes can be attributed to a specific stage.
+fn stage<T>(app: &App, stage: &'static str, f: impl FnOnce() -> T) -> T {
+    let _span = info_span!("publish.stage", stage).entered();
+
+    app.instance_metrics
+        .publish_stage_duration
+        .with_label_values(&[stage])
+        .observe_closure_duration(f)
+}
+
+/// Takes the publish lock of a crate until the end of the current transaction.
+///
+/// Returns `false` if another publish of the same crate holds the lock. Names
+/// are canonicalized, so that e.g. `foo-bar` and `foo_bar` share a lock.
+fn try_lock_crate_for_publish(name: &str, conn: &mut PgConnection) -> QueryResult<bool> {
+    let key = hashtext(canon_crate_name(name));
+    diesel::select(pg_try_advisory_xact_lock(PUBLISH_LOCK_NAMESPACE, key)).get_result(conn)
+}
+
+/// Returns the validator for the files of published tarballs, according to
+/// the `deny_tarball_executables` and `denied_tarball_paths` settings.
+fn tarball_validator(config: &Server) -> ValidatorSet {
+    let mut validator = ValidatorSet::new();
+    if config.deny_tarball_executables {
+        validator = validator.with(NoExecutables);
+    }
+    if !config.denied_tarball_paths.is_empty() {
+        validator = validator.with(DenyPaths::new(&config.denied_tarball_paths));
+    }
+    validator
+}
+
+/// Returns a warning for each package in the `Cargo.lock` file of the crate
+/// that is pinned to a version that has been yanked from crates.io.
+fn yanked_lockfile_warnings(
+    conn: &mut PgConnection,
+    lockfile: &LockfileInfo,
+) -> QueryResult<Vec<String>> {
+    let packages = lockfile.crates_io_packages().collect::<Vec<_>>();
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let names = packages
+        .iter()
+        .map(|package| package.name.as_str())
+        .collect::<Vec<_>>();
+
+    let yanked = versions::table
+        .inner_join(crates::table)
+        .filter(crates::name.eq_any(names))
+        .filter(versions::yanked.eq(true))
+        .select((crates::name, versions::num))
+        .load::<(String, String)>(conn)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    let warnings = packages
+        .into_iter()
+        .filter(|package| yanked.contains(&(package.name.clone(), package.version.to_string())))
+        .map(|package| {
+            format!(
+                "the `Cargo.lock` file pins `{name}` to the yanked version {version}, \
+                 consider updating it with `cargo update -p {name}`",
+                name = package.name,
+                version = package.version,
+            )
+        })
+        .collect();
+
+    Ok(warnings)
+}
+
+/// Returns a warning for each keyword that only differs in case from an
+/// earlier keyword, since keywords are stored in lowercase.
+fn duplicate_keyword_warnings(keywords: &[&str]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    keywords
+        .iter()
+        .filter(|keyword| !seen.insert(keyword.to_lowercase()))
+        .map(|keyword| format!("the keyword `{keyword}` is a duplicate and was ignored"))
+        .collect()
+}
+
+/// A problem with the declared dependencies that commonly breaks tools which
+/// resolve the dependency graph of the crate.
+#[derive(Debug, PartialEq, Eq)]
+enum DependencyGraphWarning {
+    /// The same crate is declared in two places with version requirements
+    /// that no version matches together, e.g. `^1` and `^2`, so that two
+    /// versions of it end up in the build.
+    Conflicting {
+        name: String,
+        first: (String, String),
+        second: (String, String),
+    },
+    /// The crate declares a dev-dependency on itself.
+    SelfDevDependency { req: String },
+}
+
+impl DependencyGraphWarning {
+    fn kind(&self) -> PublishWarningKind {
+        match self {
+            Self::Conflicting { .. } => PublishWarningKind::ConflictingDependency,
+            Self::SelfDevDependency { .. } => PublishWarningKind::SelfDevDependency,
+        }
+    }
+
+    fn message(&self, crate_name: &str) -> String {
+        match self {
+            Self::Conflicting {
+                name,
+                first: (first_req, first_section),
+                second: (second_req, second_section),
+            } => format!(
+                "the dependency `{name}` is declared with incompatible version requirements, \
+                 `{first_req}` in `{first_section}` and `{second_req}` in `{second_section}`, \
+                 so two different versions of it end up in the dependency graph"
+            ),
+            Self::SelfDevDependency { req } => format!(
+                "the crate has a dev-dependency on itself (`{crate_name} = \"{req}\"`), which \
+                 creates a cycle in the dependency graph that tools resolving dev-dependencies \
+                 may fail on"
+            ),
+        }
+    }
+}
+
+/// Checks the declared dependencies for dev-dependencies on the crate itself,
+/// and for crates that are declared more than once with incompatible version
+/// requirements, e.g. as a regular and as a dev-dependency.
+fn dependency_graph_warnings(
+    crate_name: &str,
+    deps: &[EncodableCrateDependency],
+) -> Vec<DependencyGraphWarning> {
+    let section = |dep: &EncodableCrateDependency| {
+        let table = match dep.kind.unwrap_or(DependencyKind::Normal) {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Build => "build-dependencies",
+            DependencyKind::Dev => "dev-dependencies",
+        };
+        match &dep.target {
+            Some(target) => format!("target.'{target}'.{table}"),
+            None => table.to_string(),
+        }
+    };
+
+    let mut warnings = deps
+        .iter()
+        .filter(|dep| dep.kind == Some(DependencyKind::Dev) && dep.registry.is_none())
+        .filter(|dep| dep.name == *crate_name)
+        .map(|dep| DependencyGraphWarning::SelfDevDependency {
+            req: dep.version_req.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    for (index, first) in deps.iter().enumerate() {
+        for second in &deps[index + 1..] {
+            if first.name != second.name || first.registry != second.registry {
+                continue;
+            }
+
+            // Renamed dependencies are the way to depend on several versions
+            // of a crate on purpose, and dependencies for different targets
+            // are never part of the same build.
+            let renamed =
+                first.explicit_name_in_toml.is_some() || second.explicit_name_in_toml.is_some();
+            let other_target =
+                first.target.is_some() && second.target.is_some() && first.target != second.target;
+            if renamed || other_target {
+                continue;
+            }
+
+            let (Ok(first_req), Ok(second_req)) = (
+                semver::VersionReq::parse(&first.version_req),
+                semver::VersionReq::parse(&second.version_req),
+            ) else {
+                continue;
+            };
+
+            if !requirements_overlap(&first_req, &second_req) {
+                warnings.push(DependencyGraphWarning::Conflicting {
+                    name: first.name.to_string(),
+                    first: (first.version_req.to_string(), section(first)),
+                    second: (second.version_req.to_string(), section(second)),
+                });
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Returns whether any version matches both requirements.
+///
+/// If there is such a version, the lowest one is the lower bound of one of
+/// the comparators, or `0.0.0` if there are none, so only those are checked.
+fn requirements_overlap(a: &semver::VersionReq, b: &semver::VersionReq) -> bool {
+    use semver::{BuildMetadata, Op, Version};
+
+    let lower_bounds = a.comparators.iter().chain(&b.comparators).filter_map(|c| {
+        let (minor, patch) = (c.minor.unwrap_or(0), c.patch.unwrap_or(0));
+        let (major, minor, patch) = match c.op {
+            Op::Less | Op::LessEq => return None,
+            Op::Greater => match (c.minor, c.patch) {
+                (Some(_), Some(_)) => (c.major, minor, patch + 1),
+                (Some(_), None) => (c.major, minor + 1, 0),
+                (None, _) => (c.major + 1, 0, 0),
+            },
+            _ => (c.major, minor, patch),
+        };
+
+        Some(Version {
+            major,
+            minor,
+            patch,
+            pre: c.pre.clone(),
+            build: BuildMetadata::EMPTY,
+        })
+    });
+
+    std::iter::once(Version::new(0, 0, 0))
+        .chain(lower_bounds)
+        .any(|version| a.matches(&version) && b.matches(&version))
+}
+
 /// Counts the number of versions for `krate_id` that were published within
 /// the last 24 hours.
 fn count_versions_published_today(krate_id: i32, conn: &mut PgConnection) -> QueryResult<i64> {
@@ -287,6 +963,49 @@ fn count_versions_published_today(krate_id: i32, conn: &mut PgConnection) -> Que
         .get_result(conn)
 }
 
+/// Rejects the publish if the crate or the version was deleted within the
+/// `cooldown`, so that freed names can't be taken over right away.
+fn check_deletion_cooldown(
+    name: &str,
+    vers: &str,
+    cooldown: std::time::Duration,
+    conn: &mut PgConnection,
+) -> AppResult<()> {
+    if cooldown.is_zero() {
+        return Ok(());
+    }
+
+    let cooldown =
+        chrono::Duration::from_std(cooldown).map_err(|_| internal("invalid cooldown"))?;
+    let since = chrono::Utc::now().naive_utc() - cooldown;
+
+    let crate_exists = diesel::select(diesel::dsl::exists(
+        crates::table.filter(Crate::with_name(name)),
+    ))
+    .get_result(conn)?;
+
+    let Some(tombstone) = Tombstone::find_blocking(name, vers, crate_exists, since, conn)? else {
+        return Ok(());
+    };
+
+    let deleted_at = tombstone.deleted_at.format("%Y-%m-%d %H:%M UTC");
+    let available_at = (tombstone.deleted_at + cooldown).format("%Y-%m-%d %H:%M UTC");
+    let message = match tombstone.version {
+        Some(version) => format!(
+            "version `{version}` of crate `{}` was deleted at {deleted_at}, and can only be \
+             published again after {available_at}",
+            tombstone.crate_name
+        ),
+        None => format!(
+            "the crate `{}` was deleted at {deleted_at}, and its name can only be used again \
+             after {available_at}",
+            tombstone.crate_name
+        ),
+    };
+
+    Err(cargo_err(&message))
+}
+
 #[instrument(skip_all)]
 fn split_body<R: RequestPartsExt>(mut bytes: Bytes, req: &R) -> AppResult<(Bytes, Bytes)> {
     // The format of the req.body() of a publish request is as follows:
@@ -338,59 +1057,381 @@ pub fn missing_metadata_error_message(missing: &[&str]) -> String {
     )
 }
 
+/// Stores the dependencies of a new version.
+///
+/// Dependencies on crates of other registries are rejected, unless the
+/// registry is allowed or the `cross_registry` config only asks for a warning
+/// about unknown registries. The returned list contains these warnings.
 #[instrument(skip_all)]
 pub fn add_dependencies(
     conn: &mut PgConnection,
     deps: &[EncodableCrateDependency],
     target_version_id: i32,
-) -> AppResult<()> {
-    use self::dependencies::dsl::*;
-    use diesel::insert_into;
+    cross_registry: &CrossRegistryConfig,
+) -> AppResult<Vec<String>> {
+    let (cross_registry_deps, deps): (Vec<_>, Vec<_>) = deps
+        .iter()
+        .partition(|dep| dep.registry.as_deref().map_or(false, |r| !r.is_empty()));
+
+    let mut warnings = Vec::new();
+    let mut new_cross_registry_dependencies = Vec::new();
+    for dep in cross_registry_deps {
+        let registry = dep.registry.as_deref().unwrap_or_default();
+        check_wildcard_requirement(dep)?;
+
+        if !cross_registry.is_allowed(registry) {
+            if !cross_registry.warn_on_unknown {
+                return Err(cargo_err(&format_args!("Dependency `{}` is hosted on another registry. Cross-registry dependencies are not permitted on crates.io.", &*dep.name)));
+            }
+
+            warnings.push(format!(
+                "dependency `{}` is hosted on the unknown registry `{registry}`, \
+                so this crate can only be installed by users with access to that registry",
+                &*dep.name
+            ));
+        }
+
+        new_cross_registry_dependencies.push(NewCrossRegistryDependency {
+            version_id: target_version_id,
+            registry,
+            name: &dep.name,
+            req: &dep.version_req.0,
+            optional: dep.optional,
+            default_features: dep.default_features,
+            features: dep.features.iter().map(|f| f.as_str()).collect(),
+            target: dep.target.as_deref(),
+            kind: dep.kind,
+            explicit_name: dep.explicit_name_in_toml.as_deref().map(|n| n.as_str()),
+        });
+    }
+
+    // Resolve all dependency crates with a single query instead of one query
+    // per dependency. Match only identical names to ensure the index always
+    // references the original crate name.
+    let names = deps.iter().map(|dep| dep.name.as_str()).collect::<Vec<_>>();
+    let crate_ids: HashMap<String, i32> = crates::table
+        .filter(crates::name.eq_any(&names))
+        .select((crates::name, crates::id))
+        .load::<(String, i32)>(conn)?
+        .into_iter()
+        .collect();
 
     let new_dependencies = deps
         .iter()
         .map(|dep| {
-            if let Some(registry) = &dep.registry {
-                if !registry.is_empty() {
-                    return Err(cargo_err(&format_args!("Dependency `{}` is hosted on another registry. Cross-registry dependencies are not permitted on crates.io.", &*dep.name)));
-                }
-            }
+            let crate_id = *crate_ids
+                .get(dep.name.as_str())
+                .ok_or_else(|| cargo_err(&format_args!("no known crate named `{}`", &*dep.name)))?;
 
-            // Match only identical names to ensure the index always references the original crate name
-            let krate:Crate = Crate::by_exact_name(&dep.name)
-                .first(conn)
-                .map_err(|_| cargo_err(&format_args!("no known crate named `{}`", &*dep.name)))?;
-
-            if let Ok(version_req) = semver::VersionReq::parse(&dep.version_req.0) {
-                 if version_req == semver::VersionReq::STAR {
-                    return Err(cargo_err(&format_args!("wildcard (`*`) dependency constraints are not allowed \
-                        on crates.io. Crate with this problem: `{}` See https://doc.rust-lang.org/cargo/faq.html#can-\
-                        libraries-use--as-a-version-for-their-dependencies for more \
-                        information", &*dep.name)));
-                }
-            }
+            check_wildcard_requirement(dep)?;
 
-            Ok((
-                version_id.eq(target_version_id),
-                crate_id.eq(krate.id),
-                req.eq(dep.version_req.to_string()),
-                dep.kind.map(|k| kind.eq(k as i32)),
-                optional.eq(dep.optional),
-                default_features.eq(dep.default_features),
-                features.eq(&dep.features),
-                target.eq(dep.target.as_deref()),
-                explicit_name.eq(dep.explicit_name_in_toml.as_deref())
-            ))
+            Ok(NewDependency {
+                version_id: target_version_id,
+                crate_id,
+                req: &dep.version_req.0,
+                optional: dep.optional,
+                default_features: dep.default_features,
+                features: dep.features.iter().map(|f| f.as_str()).collect(),
+                target: dep.target.as_deref(),
+                kind: dep.kind,
+                explicit_name: dep.explicit_name_in_toml.as_deref().map(|n| n.as_str()),
+            })
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    insert_into(dependencies)
-        .values(&new_dependencies)
-        .execute(conn)?;
+    NewDependency::insert_all(&new_dependencies, conn)?;
+    NewCrossRegistryDependency::insert_all(&new_cross_registry_dependencies, conn)?;
+
+    Ok(warnings)
+}
+
+/// Adds a new crate to the moderation queue if its source files are
+/// near-identical to the files of an existing crate under a different name.
+///
+/// The publisher is not notified, the crate is reviewed by the crates.io team.
+fn flag_duplicate_content(
+    conn: &mut PgConnection,
+    config: &DuplicateContentConfig,
+    krate: &Crate,
+    version_id: i32,
+    fingerprint: &ContentFingerprint,
+) -> QueryResult<()> {
+    if !config.is_enabled() || fingerprint.file_count < config.min_files {
+        return Ok(());
+    }
+
+    // Only the first version of a crate is compared with other crates, since
+    // later versions are expected to resemble the earlier ones anyway.
+    let num_versions: i64 = versions::table
+        .filter(versions::crate_id.eq(krate.id))
+        .count()
+        .get_result(conn)?;
+
+    if num_versions > 1 {
+        return Ok(());
+    }
+
+    let candidates = VersionFingerprint::overlapping(
+        krate.id,
+        &fingerprint.hashes,
+        MAX_DUPLICATE_CANDIDATES,
+        conn,
+    )?;
+
+    let most_similar = candidates
+        .iter()
+        .map(|(candidate, name)| (fingerprint.similarity(&candidate.hashes), name))
+        .max_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    let Some((similarity, similar_crate)) = most_similar else {
+        return Ok(());
+    };
+
+    if similarity < config.similarity_threshold {
+        return Ok(());
+    }
+
+    info!(
+        krate.name = %krate.name,
+        %similar_crate,
+        similarity,
+        "Adding crate with duplicate content to the moderation queue"
+    );
+
+    NewModerationFlag {
+        crate_id: krate.id,
+        version_id: Some(version_id),
+        reason: "duplicate_content",
+        details: json!({ "similar_crate": similar_crate, "similarity": similarity }),
+    }
+    .insert(conn)?;
 
     Ok(())
 }
 
+/// A version number that is probably a mistake, see [`VersionPolicyConfig`].
+#[derive(Debug, PartialEq, Eq)]
+enum VersionNumberWarning {
+    /// The version is lower than this existing version of the same release
+    /// series.
+    Lower(semver::Version),
+    /// The version is an unusually large jump from this highest existing
+    /// version.
+    Jump(semver::Version),
+}
+
+impl VersionNumberWarning {
+    fn kind(&self) -> PublishWarningKind {
+        match self {
+            Self::Lower(_) => PublishWarningKind::LowerVersion,
+            Self::Jump(_) => PublishWarningKind::VersionJump,
+        }
+    }
+
+    fn message(&self, version: &semver::Version) -> String {
+        match self {
+            Self::Lower(existing) => format!(
+                "the version `{version}` is lower than the already published version \
+                 `{existing}`, make sure that the version number is not a typo"
+            ),
+            Self::Jump(highest) => format!(
+                "the version `{version}` is an unusually large jump from the highest published \
+                 version `{highest}`, make sure that the version number is not a typo"
+            ),
+        }
+    }
+}
+
+fn unyanked_versions(krate_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<semver::Version>> {
+    let nums: Vec<String> = versions::table
+        .filter(versions::crate_id.eq(krate_id))
+        .filter(versions::yanked.eq(false))
+        .select(versions::num)
+        .load(conn)?;
+
+    Ok(nums
+        .iter()
+        .filter_map(|num| semver::Version::parse(num).ok())
+        .collect())
+}
+
+/// Compares the number of a new version with the existing versions of the
+/// crate and returns warnings for numbers that look like a mistake.
+fn version_number_warnings(
+    config: &VersionPolicyConfig,
+    existing: &[semver::Version],
+    version: &semver::Version,
+) -> Vec<VersionNumberWarning> {
+    let mut warnings = Vec::new();
+
+    // Releases of older series, like backports of fixes, are common, so
+    // only versions that are lower within the same series are reported.
+    if config.warn_lower_versions {
+        let same_series = |other: &semver::Version| {
+            other.major == version.major && (version.major != 0 || other.minor == version.minor)
+        };
+        let higher = existing
+            .iter()
+            .filter(|other| same_series(other) && *other > version)
+            .max();
+        if let Some(higher) = higher {
+            warnings.push(VersionNumberWarning::Lower(higher.clone()));
+        }
+    }
+
+    if let Some(highest) = existing.iter().max() {
+        let increase = if highest.major == 0 && version.major == 0 {
+            version.minor.saturating_sub(highest.minor)
+        } else {
+            version.major.saturating_sub(highest.major)
+        };
+        if increase > config.max_major_increase {
+            warnings.push(VersionNumberWarning::Jump(highest.clone()));
+        }
+    }
+
+    warnings
+}
+
+/// Adds the new version to the moderation queue if its number is an unusual
+/// jump, which may be a compromised account publishing a version that takes
+/// precedence over all existing versions.
+fn flag_version_jump(
+    conn: &mut PgConnection,
+    krate: &Crate,
+    version: &Version,
+    warnings: &[VersionNumberWarning],
+) -> QueryResult<()> {
+    for warning in warnings {
+        let VersionNumberWarning::Jump(highest) = warning else {
+            continue;
+        };
+
+        info!(
+            krate.name = %krate.name,
+            version = %version.num,
+            %highest,
+            "Adding version with an unusual version number jump to the moderation queue"
+        );
+
+        NewModerationFlag {
+            crate_id: krate.id,
+            version_id: Some(version.id),
+            reason: "version_jump",
+            details: json!({ "highest_version": highest.to_string() }),
+        }
+        .insert(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Applies the manifest limits to the metadata of the publish request, which
+/// is what ends up in the database and the index, since it doesn't have to
+/// match the manifest in the crate file.
+fn check_metadata_limits(
+    new_crate: &EncodableCrateUpload,
+    limits: &ManifestLimits,
+) -> AppResult<()> {
+    let count = new_crate.features.len();
+    if count > limits.max_features {
+        let max = limits.max_features;
+        return Err(tarball_to_app_error(TarballError::TooManyFeatures {
+            count,
+            max,
+        }));
+    }
+
+    let count = new_crate.deps.len();
+    if count > limits.max_dependencies {
+        let max = limits.max_dependencies;
+        return Err(tarball_to_app_error(TarballError::TooManyDependencies {
+            count,
+            max,
+        }));
+    }
+
+    Ok(())
+}
+
+/// Replaces the version requirements of the dependencies with their canonical
+/// format, e.g. `^1.2.3` for `1.2.3`, so that the database and the index only
+/// contain requirements that all resolvers read the same way.
+fn canonicalize_requirements(deps: &mut [EncodableCrateDependency]) -> AppResult<()> {
+    for dep in deps {
+        let req = semver::VersionReq::parse(&dep.version_req).map_err(|error| {
+            cargo_err(&format_args!(
+                "invalid version requirement `{}` for dependency `{}`: {error}",
+                &*dep.version_req, &*dep.name
+            ))
+        })?;
+
+        dep.version_req.0 = req.to_string();
+    }
+
+    Ok(())
+}
+
+fn check_wildcard_requirement(dep: &EncodableCrateDependency) -> AppResult<()> {
+    if let Ok(version_req) = semver::VersionReq::parse(&dep.version_req.0) {
+        if version_req == semver::VersionReq::STAR {
+            return Err(cargo_err(&format_args!("wildcard (`*`) dependency constraints are not allowed \
+                on crates.io. Crate with this problem: `{}` See https://doc.rust-lang.org/cargo/faq.html#can-\
+                libraries-use--as-a-version-for-their-dependencies for more \
+                information", &*dep.name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts the rejection of a crate file into an error that refers to the
+/// returned diagnostics, which summarize the crate file for support requests.
+///
+/// I/O errors are not caused by the crate file, so they are returned as
+/// usual and without diagnostics.
+fn rejection_diagnostics(
+    error: TarballError,
+    retention: std::time::Duration,
+    user_id: i32,
+    crate_name: &str,
+    version: &str,
+    tarball: &[u8],
+    max_unpack: u64,
+) -> (BoxedAppError, Option<NewPublishDiagnostics>) {
+    if matches!(error, TarballError::IO(_)) {
+        return (tarball_to_app_error(error), None);
+    }
+
+    let token = generate_secure_alphanumeric_string(DIAGNOSTICS_TOKEN_LENGTH);
+    let message = error.to_string();
+    let hours = retention.as_secs() / (60 * 60);
+    let app_error = cargo_err(&format_args!(
+        "{message}\n\nThe details of this error are kept for {hours} hours under the \
+         reference `{token}`. Please include the reference when contacting \
+         help@crates.io about this error."
+    ));
+
+    let context = json!({
+        "details": format!("{error:?}"),
+        "tarball_size": tarball.len(),
+        "tarball": summarize_tarball(tarball, max_unpack),
+    });
+
+    let retention = chrono::Duration::from_std(retention).unwrap_or(chrono::Duration::zero());
+    let diagnostics = NewPublishDiagnostics {
+        token,
+        user_id,
+        crate_name: crate_name.to_string(),
+        version: version.to_string(),
+        error: message,
+        context,
+        expires_at: chrono::Utc::now().naive_utc() + retention,
+    };
+
+    (app_error, Some(diagnostics))
+}
+
 fn tarball_to_app_error(error: TarballError) -> BoxedAppError {
     match error {
         TarballError::Malformed(err) => err.chain(cargo_err(
@@ -400,13 +1441,52 @@ fn tarball_to_app_error(error: TarballError) -> BoxedAppError {
         TarballError::UnexpectedSymlink(path) => {
             cargo_err(&format!("unexpected symlink or hard link found: {path}"))
         }
+        TarballError::UnsupportedEntryType(path) => {
+            cargo_err(&format!("unsupported entry type found: {path}"))
+        }
+        TarballError::UnknownCompression => {
+            cargo_err("uploaded tarball uses an unknown compression format")
+        }
+        TarballError::TooManyEntries(max) => cargo_err(&format_args!(
+            "uploaded tarball contains more than {max} entries"
+        )),
+        TarballError::FileTooLarge { path, max } => cargo_err(&format_args!(
+            "the file `{path}` is larger than the maximum file size of {max} bytes"
+        )),
+        TarballError::DuplicatePath(path) => {
+            cargo_err(&format_args!("duplicate path found: {path}"))
+        }
+        TarballError::InvalidEncoding { path, error } => cargo_err(&format_args!(
+            "the file `{path}` has an invalid encoding: {error}"
+        )),
+        TarballError::Rejected { path, message } => {
+            cargo_err(&format_args!("the file `{path}` was rejected: {message}"))
+        }
+        TarballError::InvalidLicense { expression, reason } => cargo_err(&format_args!(
+            "the `license` field `{expression}` is not a valid SPDX license expression: {reason}"
+        )),
+        TarballError::InvalidFeature(error) => {
+            cargo_err(&format_args!("invalid feature in `Cargo.toml`: {error}"))
+        }
+        TarballError::TooManyFeatures { count, max } => cargo_err(&format_args!(
+            "the manifest declares {count} features, but at most {max} are allowed"
+        )),
+        TarballError::TooManyDependencies { count, max } => cargo_err(&format_args!(
+            "the manifest declares {count} dependencies, but at most {max} are allowed"
+        )),
+        error @ TarballError::InheritedManifestValue { .. } => cargo_err(&error),
         TarballError::IO(err) => err.into(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::missing_metadata_error_message;
+    use super::{
+        dependency_graph_warnings, missing_metadata_error_message, requirements_overlap,
+        version_number_warnings, DependencyGraphWarning, VersionNumberWarning,
+    };
+    use crate::config::VersionPolicyConfig;
+    use crate::views::EncodableCrateDependency;
 
     #[test]
     fn missing_metadata_error_message_test() {
@@ -414,4 +1494,145 @@ mod tests {
         assert_eq!(missing_metadata_error_message(&["a", "b"]), "missing or empty metadata fields: a, b. Please see https://doc.rust-lang.org/cargo/reference/manifest.html for how to upload metadata");
         assert_eq!(missing_metadata_error_message(&["a", "b", "c"]), "missing or empty metadata fields: a, b, c. Please see https://doc.rust-lang.org/cargo/reference/manifest.html for how to upload metadata");
     }
+
+    #[test]
+    fn version_number_warnings_test() {
+        fn warnings(existing: &[&str], version: &str) -> Vec<VersionNumberWarning> {
+            let config = VersionPolicyConfig::default();
+            let existing = existing
+                .iter()
+                .map(|num| semver::Version::parse(num).unwrap())
+                .collect::<Vec<_>>();
+            let version = semver::Version::parse(version).unwrap();
+            version_number_warnings(&config, &existing, &version)
+        }
+
+        let v = |num| semver::Version::parse(num).unwrap();
+
+        assert_eq!(warnings(&[], "9.0.0"), vec![]);
+        assert_eq!(warnings(&["0.3.0", "0.3.1"], "0.3.2"), vec![]);
+        assert_eq!(warnings(&["0.3.1"], "1.0.0"), vec![]);
+        assert_eq!(warnings(&["1.2.0", "2.0.0"], "1.2.1"), vec![]);
+        assert_eq!(warnings(&["0.2.0", "0.3.1"], "0.2.1"), vec![]);
+        assert_eq!(warnings(&["1.0.0"], "4.0.0"), vec![]);
+
+        assert_eq!(
+            warnings(&["1.2.0", "1.3.0"], "1.2.1"),
+            vec![VersionNumberWarning::Lower(v("1.3.0"))]
+        );
+        assert_eq!(
+            warnings(&["0.3.1"], "0.3.0"),
+            vec![VersionNumberWarning::Lower(v("0.3.1"))]
+        );
+        assert_eq!(
+            warnings(&["0.3.1"], "9.0.0"),
+            vec![VersionNumberWarning::Jump(v("0.3.1"))]
+        );
+        assert_eq!(
+            warnings(&["0.3.1"], "0.9.0"),
+            vec![VersionNumberWarning::Jump(v("0.3.1"))]
+        );
+        assert_eq!(
+            warnings(&["1.0.0"], "5.0.0"),
+            vec![VersionNumberWarning::Jump(v("1.0.0"))]
+        );
+    }
+
+    #[test]
+    fn requirements_overlap_test() {
+        fn overlap(a: &str, b: &str) -> bool {
+            let a = semver::VersionReq::parse(a).unwrap();
+            let b = semver::VersionReq::parse(b).unwrap();
+            requirements_overlap(&a, &b)
+        }
+
+        assert!(overlap("^1", "^1.5"));
+        assert!(overlap("=1.2.3", "^1"));
+        assert!(overlap("~1.2", "^1.2.5"));
+        assert!(overlap(">1.2.3", "=1.2.4"));
+        assert!(overlap("<1", "^0.5"));
+        assert!(overlap("*", "^3"));
+        assert!(!overlap("^1", "^2"));
+        assert!(!overlap("0.3", "0.4"));
+        assert!(!overlap(">=1.2, <1.5", "^1.6"));
+        assert!(!overlap(">1.2", "~1.2"));
+        assert!(!overlap("<=1.0.0", ">1"));
+    }
+
+    #[test]
+    fn dependency_graph_warnings_test() {
+        fn dep(name: &str, req: &str, kind: &str, target: Option<&str>) -> serde_json::Value {
+            json!({
+                "optional": false,
+                "default_features": true,
+                "name": name,
+                "features": [],
+                "version_req": req,
+                "target": target,
+                "kind": kind,
+                "explicit_name_in_toml": null,
+                "registry": null,
+            })
+        }
+
+        fn warnings(deps: Vec<serde_json::Value>) -> Vec<DependencyGraphWarning> {
+            let deps: Vec<EncodableCrateDependency> =
+                serde_json::from_value(serde_json::Value::Array(deps)).unwrap();
+            dependency_graph_warnings("foo", &deps)
+        }
+
+        let section = |req: &str, section: &str| (req.to_string(), section.to_string());
+
+        assert_eq!(
+            warnings(vec![
+                dep("bar", "^1", "normal", None),
+                dep("bar", "^1.2", "dev", None)
+            ]),
+            vec![]
+        );
+        assert_eq!(
+            warnings(vec![
+                dep("bar", "^1", "normal", None),
+                dep("bar", "^2", "dev", None)
+            ]),
+            vec![DependencyGraphWarning::Conflicting {
+                name: "bar".to_string(),
+                first: section("^1", "dependencies"),
+                second: section("^2", "dev-dependencies"),
+            }]
+        );
+        assert_eq!(
+            warnings(vec![
+                dep("bar", "^0.3", "build", None),
+                dep("bar", "^0.4", "normal", Some("cfg(unix)")),
+            ]),
+            vec![DependencyGraphWarning::Conflicting {
+                name: "bar".to_string(),
+                first: section("^0.3", "build-dependencies"),
+                second: section("^0.4", "target.'cfg(unix)'.dependencies"),
+            }]
+        );
+        assert_eq!(
+            warnings(vec![
+                dep("bar", "^1", "normal", Some("cfg(unix)")),
+                dep("bar", "^2", "normal", Some("cfg(windows)")),
+            ]),
+            vec![]
+        );
+
+        let mut renamed = dep("bar", "^2", "normal", None);
+        renamed["explicit_name_in_toml"] = json!("bar2");
+        assert_eq!(
+            warnings(vec![dep("bar", "^1", "normal", None), renamed]),
+            vec![]
+        );
+
+        assert_eq!(
+            warnings(vec![dep("foo", "^1", "dev", None)]),
+            vec![DependencyGraphWarning::SelfDevDependency {
+                req: "^1".to_string()
+            }]
+        );
+        assert_eq!(warnings(vec![dep("foo", "^1", "build", None)]), vec![]);
+    }
 }
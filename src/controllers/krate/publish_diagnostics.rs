@@ -0,0 +1,23 @@
+//! Admin functionality for looking up why a crate file was rejected.
+//!
+//! When a crate file fails validation on publish, the error message includes a
+//! reference to the stored diagnostics, which users can pass on when asking
+//! for help.
+
+use crate::auth::AuthCheck;
+use crate::controllers::frontend_prelude::*;
+use crate::models::PublishDiagnostics;
+use crate::util::errors::not_found;
+
+/// Handles the `GET /publish_diagnostics/:token` route.
+pub async fn show(app: AppState, Path(token): Path<String>, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let (diagnostics, user) = PublishDiagnostics::find(&token, conn)?.ok_or_else(not_found)?;
+
+        Ok(Json(json!({ "diagnostics": diagnostics, "user": user })))
+    })
+    .await
+}
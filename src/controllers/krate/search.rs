@@ -234,16 +234,23 @@ pub async fn search(app: AppState, req: Parts) -> AppResult<Json<Value>> {
             supports_seek = false;
 
             query = query.order(recent_crate_downloads::downloads.desc().nulls_last())
-        } else if sort == Some("recent-updates") {
+        } else if sort == Some("recent-updates") || sort == Some("recently-updated") {
             // Custom sorting is not supported yet with seek.
             supports_seek = false;
 
-            query = query.order(crates::updated_at.desc());
+            // Crates without versions have no activity dates
+            query = query.order((
+                crates::last_activity_at.desc().nulls_last(),
+                crates::updated_at.desc(),
+            ));
         } else if sort == Some("new") {
             // Custom sorting is not supported yet with seek.
             supports_seek = false;
 
-            query = query.order(crates::created_at.desc());
+            query = query.order((
+                crates::first_version_published_at.desc().nulls_last(),
+                crates::created_at.desc(),
+            ));
         } else {
             query = query.then_order_by(crates::name.asc())
         }
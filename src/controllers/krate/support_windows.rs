@@ -0,0 +1,133 @@
+//! Endpoints for declaring the support windows of the release lines of a crate
+//!
+//! Owners can declare for each semver-compatible release line (e.g. `1` or
+//! `0.4`) whether it is still supported, only receives security fixes, or has
+//! reached its end of life.
+
+use crate::auth::AuthCheck;
+use crate::controllers::frontend_prelude::*;
+use crate::models::support_window::parse_major_version;
+use crate::models::token::EndpointScope;
+use crate::models::{Crate, Rights, SupportStatus, SupportWindow, User};
+use crate::util::errors::forbidden;
+use crate::views::EncodableSupportWindow;
+use chrono::NaiveDate;
+
+#[derive(Deserialize)]
+struct SupportWindowRequest {
+    support_window: NewSupportWindow,
+}
+
+#[derive(Deserialize)]
+struct NewSupportWindow {
+    status: String,
+    end_of_life_date: Option<NaiveDate>,
+}
+
+/// Handles the `GET /crates/:crate_id/support_windows` route.
+pub async fn list(app: AppState, Path(crate_name): Path<String>) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+        let support_windows = SupportWindow::for_crate(&krate, conn)?
+            .into_iter()
+            .map(EncodableSupportWindow::from)
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "support_windows": support_windows })))
+    })
+    .await
+}
+
+/// Handles the `PUT /crates/:crate_id/support_windows/:major_version` route.
+pub async fn update(
+    app: AppState,
+    Path((crate_name, major_version)): Path<(String, String)>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let major_version = parse_major_version(&major_version)
+            .ok_or_else(|| bad_request(&format_args!("invalid major version: {major_version}")))?;
+
+        let request: SupportWindowRequest = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+        let request = request.support_window;
+
+        let status = SupportStatus::from_name(&request.status)
+            .ok_or_else(|| bad_request(&format_args!("invalid status: {}", request.status)))?;
+
+        if status == SupportStatus::EndOfLife && request.end_of_life_date.is_none() {
+            return Err(bad_request(
+                "an end_of_life_date is required for release lines that reached their end of life",
+            ));
+        }
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::default()
+            .with_endpoint_scope(EndpointScope::PublishUpdate)
+            .for_crate(&crate_name)
+            .check(&req, conn)?;
+
+        with_transaction(conn, |conn| {
+            let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+            ensure_publish_rights(&app, &krate, auth.user(), conn)?;
+
+            let support_window = SupportWindow::upsert(
+                krate.id,
+                &major_version,
+                status,
+                request.end_of_life_date,
+                conn,
+            )?;
+
+            let support_window = EncodableSupportWindow::from(support_window);
+            Ok(Json(json!({ "support_window": support_window })))
+        })
+    })
+    .await
+}
+
+/// Handles the `DELETE /crates/:crate_id/support_windows/:major_version` route.
+pub async fn delete(
+    app: AppState,
+    Path((crate_name, major_version)): Path<(String, String)>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let major_version = parse_major_version(&major_version)
+            .ok_or_else(|| bad_request(&format_args!("invalid major version: {major_version}")))?;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::default()
+            .with_endpoint_scope(EndpointScope::PublishUpdate)
+            .for_crate(&crate_name)
+            .check(&req, conn)?;
+
+        with_transaction(conn, |conn| {
+            let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+            ensure_publish_rights(&app, &krate, auth.user(), conn)?;
+
+            SupportWindow::delete(krate.id, &major_version, conn)?;
+
+            ok_true()
+        })
+    })
+    .await
+}
+
+/// Everyone who is allowed to publish new versions of the crate is also
+/// allowed to declare the support windows of its release lines.
+fn ensure_publish_rights(
+    app: &AppState,
+    krate: &Crate,
+    user: &User,
+    conn: &mut PgConnection,
+) -> AppResult<()> {
+    let owners = krate.owners(conn)?;
+    if user.rights(app, &owners)? < Rights::Publish {
+        return Err(forbidden());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! Endpoint for correcting the metadata of an already published crate
+//!
+//! Keywords, categories and links are stored per crate and are usually
+//! replaced on every publish. This endpoint allows owners to fix them without
+//! having to publish a new version.
+
+use crate::auth::AuthCheck;
+use crate::controllers::frontend_prelude::*;
+use crate::models::krate::validate_url;
+use crate::models::token::EndpointScope;
+use crate::models::{Category, Crate, CrateCategory, CrateKeyword, Keyword, Rights};
+use crate::schema::{categories, crates, keywords};
+use crate::util::errors::forbidden;
+use crate::util::url_normalization::normalize_url;
+use crate::views::krate_publish::{EncodableCategoryList, EncodableKeywordList};
+
+#[derive(Deserialize)]
+struct UpdateRequest {
+    #[serde(rename = "crate")]
+    krate: CrateUpdate,
+}
+
+/// The metadata fields that can be changed. Fields that are missing from the
+/// request are left untouched.
+#[derive(Deserialize)]
+struct CrateUpdate {
+    keywords: Option<EncodableKeywordList>,
+    categories: Option<EncodableCategoryList>,
+    homepage: Option<String>,
+    documentation: Option<String>,
+    repository: Option<String>,
+}
+
+/// Handles the `PATCH /crates/:crate_id` route.
+pub async fn update(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let update: UpdateRequest = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+        let update = update.krate;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::default()
+            .with_endpoint_scope(EndpointScope::PublishUpdate)
+            .for_crate(&crate_name)
+            .check(&req, conn)?;
+
+        let user = auth.user();
+
+        with_transaction(conn, |conn| {
+            let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+            // Everyone who is allowed to publish new versions of the crate
+            // is also allowed to change the metadata that a publish would set.
+            let owners = krate.owners(conn)?;
+            if user.rights(&app, &owners)? < Rights::Publish {
+                return Err(forbidden());
+            }
+
+            let old_keywords = crate_keywords(&krate, conn)?;
+            let old_categories = crate_categories(&krate, conn)?;
+
+            if let Some(keywords) = &update.keywords {
+                let keywords = keywords.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+                Keyword::update_crate(conn, &krate, &keywords)?;
+            }
+
+            if let Some(categories) = &update.categories {
+                let categories = categories.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+                let invalid_categories = Category::update_crate(conn, &krate, &categories)?;
+                if !invalid_categories.is_empty() {
+                    return Err(bad_request(&format_args!(
+                        "unknown categories: {}",
+                        invalid_categories.join(", ")
+                    )));
+                }
+            }
+
+            let homepage = update.homepage.as_deref().map(normalize_url);
+            let documentation = update.documentation.as_deref().map(normalize_url);
+            let repository = update.repository.as_deref().map(normalize_url);
+            validate_url(homepage.as_deref(), "homepage")?;
+            validate_url(documentation.as_deref(), "documentation")?;
+            validate_url(repository.as_deref(), "repository")?;
+
+            // Diesel refuses to run an `UPDATE` without any changes.
+            let updated = if homepage.is_none() && documentation.is_none() && repository.is_none() {
+                krate.clone()
+            } else {
+                diesel::update(&krate)
+                    .set((
+                        homepage.map(|url| crates::homepage.eq(url)),
+                        documentation.map(|url| crates::documentation.eq(url)),
+                        repository.map(|url| crates::repository.eq(url)),
+                    ))
+                    .returning(Crate::as_returning())
+                    .get_result(conn)?
+            };
+
+            let new_keywords = crate_keywords(&krate, conn)?;
+            let new_categories = crate_categories(&krate, conn)?;
+
+            info!(
+                krate.name = %krate.name,
+                user.id = user.id,
+                user.login = %user.gh_login,
+                api_token_id = ?auth.api_token_id(),
+                ?old_keywords,
+                ?new_keywords,
+                ?old_categories,
+                ?new_categories,
+                old_homepage = ?krate.homepage,
+                new_homepage = ?updated.homepage,
+                old_documentation = ?krate.documentation,
+                new_documentation = ?updated.documentation,
+                old_repository = ?krate.repository,
+                new_repository = ?updated.repository,
+                "Crate metadata updated"
+            );
+
+            Ok(Json(json!({
+                "crate": {
+                    "keywords": new_keywords,
+                    "categories": new_categories,
+                    "homepage": updated.homepage,
+                    "documentation": updated.documentation,
+                    "repository": updated.repository,
+                }
+            })))
+        })
+    })
+    .await
+}
+
+fn crate_keywords(krate: &Crate, conn: &mut PgConnection) -> QueryResult<Vec<String>> {
+    CrateKeyword::belonging_to(krate)
+        .inner_join(keywords::table)
+        .select(keywords::keyword)
+        .order(keywords::keyword)
+        .load(conn)
+}
+
+fn crate_categories(krate: &Crate, conn: &mut PgConnection) -> QueryResult<Vec<String>> {
+    CrateCategory::belonging_to(krate)
+        .inner_join(categories::table)
+        .select(categories::slug)
+        .order(categories::slug)
+        .load(conn)
+}
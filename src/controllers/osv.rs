@@ -0,0 +1,112 @@
+//! Export of security yanks in the OSV format
+//!
+//! Versions that were yanked with the `security` reason are published as
+//! [OSV](https://ossf.github.io/osv-schema/) records, so that vulnerability
+//! scanners can ingest them. The bulk feed is ordered by the time of the last
+//! modification and paginated with `seek` keys on `(modified_at, id)`.
+//! Scanners can keep the `next_page` link of the last page and poll it later
+//! to only receive records that changed in the meantime.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::controllers::helpers::pagination::{encode_seek, Page, PaginationOptions};
+use crate::models::SecurityYank;
+use crate::schema::{crates, security_yanks, versions};
+use crate::util::errors::not_found;
+use crate::views::OsvRecord;
+use chrono::{DateTime, NaiveDateTime};
+use indexmap::IndexMap;
+
+/// Handles the `GET /osv` route.
+///
+/// Lists the OSV records, ordered by the time of their last modification.
+/// Unlike other paginated lists, the `next_page` link is also returned for
+/// the last page, so that it can be used to poll for future changes. It is
+/// only omitted if there are no records after the requested position.
+pub async fn index(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let pagination = PaginationOptions::builder()
+            .enable_pages(false)
+            .enable_seek(true)
+            .gather(&req)?;
+
+        let since = req
+            .query()
+            .get("since")
+            .map(|time| DateTime::parse_from_rfc3339(time).map(|time| time.naive_utc()))
+            .transpose()
+            .map_err(|_| {
+                bad_request("invalid `since` parameter, expected an RFC 3339 timestamp")
+            })?;
+
+        let conn = &mut *app.db_read()?;
+
+        let mut query = security_yanks::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .select((security_yanks::all_columns, crates::name, versions::num))
+            .order((security_yanks::modified_at, security_yanks::id))
+            .limit(pagination.per_page)
+            .into_boxed();
+
+        if let Some(since) = since {
+            query = query.filter(security_yanks::modified_at.gt(since));
+        }
+
+        match pagination.page {
+            Page::Unspecified => {}
+            Page::Seek(s) => {
+                // Records can share the same `modified_at`, so the ID is used
+                // as a tiebreaker to not skip any of them at page boundaries.
+                let (modified_at, id): (NaiveDateTime, i32) = s.decode()?;
+                query = query.filter(
+                    security_yanks::modified_at
+                        .gt(modified_at)
+                        .or(security_yanks::modified_at
+                            .eq(modified_at)
+                            .and(security_yanks::id.gt(id))),
+                );
+            }
+            Page::Numeric(_) => unreachable!("page-based pagination is disabled"),
+        }
+
+        let records = query.load::<(SecurityYank, String, String)>(conn)?;
+
+        let next_page = match records.last() {
+            Some((last, _, _)) => {
+                let mut params = IndexMap::new();
+                params.insert("seek".into(), encode_seek((last.modified_at, last.id))?);
+                Some(req.query_with_params(params))
+            }
+            None => None,
+        };
+
+        let vulns = records
+            .into_iter()
+            .map(|(yank, krate, num)| OsvRecord::from(yank, krate, num))
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({
+            "vulns": vulns,
+            "meta": { "next_page": next_page },
+        })))
+    })
+    .await
+}
+
+/// Handles the `GET /osv/:id` route.
+pub async fn show(app: AppState, Path(osv_id): Path<String>) -> AppResult<Json<OsvRecord>> {
+    conduit_compat(move || {
+        let id = SecurityYank::parse_osv_id(&osv_id).ok_or_else(not_found)?;
+
+        let conn = &mut *app.db_read()?;
+
+        let (yank, krate, num) = security_yanks::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .filter(security_yanks::id.eq(id))
+            .select((security_yanks::all_columns, crates::name, versions::num))
+            .first::<(SecurityYank, String, String)>(conn)?;
+
+        Ok(Json(OsvRecord::from(yank, krate, num)))
+    })
+    .await
+}
@@ -0,0 +1,124 @@
+//! Endpoints for registry-wide statistics
+//!
+//! The numbers are snapshots that are recorded daily by background jobs, so
+//! these endpoints never have to scan the whole registry.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::models::MsrvStat;
+use std::cmp::Reverse;
+
+#[derive(Serialize, Debug, PartialEq)]
+struct MsrvShare {
+    rust_version: String,
+    versions: i32,
+    crates: i32,
+    /// The share of all versions in the snapshot, from `0.0` to `1.0`.
+    share: f64,
+}
+
+/// Handles the `GET /stats/msrv` route.
+///
+/// Returns how many recently published versions declare each `rust-version`,
+/// newest Rust version first, based on the most recent snapshot of the
+/// `aggregate_msrv_stats` background job.
+pub async fn msrv(state: AppState) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *state.db_read()?;
+        let stats = MsrvStat::latest(conn)?;
+
+        let date = stats.first().map(|stat| stat.date);
+        let total_versions = stats.iter().map(|stat| stat.version_count).sum::<i32>();
+        let (undeclared, declared): (Vec<_>, Vec<_>) = stats
+            .into_iter()
+            .partition(|stat| stat.rust_version.is_empty());
+
+        let without_rust_version = undeclared
+            .iter()
+            .map(|stat| stat.version_count)
+            .sum::<i32>();
+
+        Ok(Json(json!({
+            "date": date,
+            "rust_versions": msrv_shares(declared, total_versions),
+            "meta": {
+                "total_versions": total_versions,
+                "versions_without_rust_version": without_rust_version,
+            },
+        })))
+    })
+    .await
+}
+
+/// Converts the declared `rust-version` values of a snapshot into their share
+/// of `total_versions`, newest Rust version first. Values that aren't version
+/// numbers come last.
+fn msrv_shares(stats: Vec<MsrvStat>, total_versions: i32) -> Vec<MsrvShare> {
+    let mut shares = stats
+        .into_iter()
+        .map(|stat| MsrvShare {
+            share: match total_versions {
+                0 => 0.0,
+                total => stat.version_count as f64 / total as f64,
+            },
+            rust_version: stat.rust_version,
+            versions: stat.version_count,
+            crates: stat.crate_count,
+        })
+        .collect::<Vec<_>>();
+
+    shares.sort_by_cached_key(|share| {
+        let parts = share
+            .rust_version
+            .split('.')
+            .map(str::parse::<u64>)
+            .collect::<Result<Vec<_>, _>>();
+        (Reverse(parts.ok()), share.rust_version.clone())
+    });
+
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn shares() {
+        let date = NaiveDate::from_ymd_opt(2023, 8, 24).unwrap();
+        let stat = |rust_version: &str, version_count| MsrvStat {
+            date,
+            rust_version: rust_version.into(),
+            version_count,
+            crate_count: 1,
+        };
+        let share = |rust_version: &str, versions, share| MsrvShare {
+            rust_version: rust_version.into(),
+            versions,
+            crates: 1,
+            share,
+        };
+
+        let stats = vec![
+            stat("1.9", 10),
+            stat("foo", 10),
+            stat("1.60", 20),
+            stat("1.10", 10),
+        ];
+        assert_eq!(
+            msrv_shares(stats, 100),
+            vec![
+                share("1.60", 20, 0.2),
+                share("1.10", 10, 0.1),
+                share("1.9", 10, 0.1),
+                share("foo", 10, 0.1),
+            ]
+        );
+
+        assert_eq!(
+            msrv_shares(vec![stat("1.60", 0)], 0),
+            vec![share("1.60", 0, 0.0)]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Endpoints that report on the health of the registry, so that users can
+//! diagnose issues like "why can't cargo see my new release" themselves.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::models::{
+    newest_synced_version, newest_version, Crate, IndexKind, IndexSyncTime, VisibleVersion,
+};
+use crate::schema::{background_jobs, versions};
+use crate::util::rfc3339;
+use chrono::NaiveDateTime;
+
+#[derive(Serialize)]
+struct EncodableVisibleVersion {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    num: String,
+    #[serde(with = "rfc3339")]
+    published_at: NaiveDateTime,
+}
+
+impl From<VisibleVersion> for EncodableVisibleVersion {
+    fn from(version: VisibleVersion) -> Self {
+        Self {
+            crate_name: version.crate_name,
+            num: version.num,
+            published_at: version.published_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct IndexStatus {
+    newest_version: Option<EncodableVisibleVersion>,
+    /// How long the newest version in the index was published before the
+    /// newest version in the database.
+    lag_seconds: Option<i64>,
+    pending_syncs: i64,
+}
+
+#[derive(Serialize)]
+struct CrateIndexStatus {
+    #[serde(with = "rfc3339::option")]
+    synced_at: Option<NaiveDateTime>,
+    /// Whether the last sync included the newest version of the crate.
+    up_to_date: bool,
+    sync_pending: bool,
+}
+
+fn pending_syncs(
+    kind: IndexKind,
+    crate_name: Option<&str>,
+    conn: &mut PgConnection,
+) -> QueryResult<i64> {
+    let mut query = background_jobs::table
+        .filter(background_jobs::job_type.eq(kind.job_type()))
+        .into_boxed();
+
+    if let Some(crate_name) = crate_name {
+        query = query.filter(background_jobs::data.eq(json!({ "krate": crate_name })));
+    }
+
+    query.count().get_result(conn)
+}
+
+fn index_status(
+    kind: IndexKind,
+    newest: Option<&VisibleVersion>,
+    conn: &mut PgConnection,
+) -> QueryResult<IndexStatus> {
+    let newest_synced = newest_synced_version(kind, conn)?;
+    let lag_seconds = newest
+        .zip(newest_synced.as_ref())
+        .map(|(newest, synced)| (newest.published_at - synced.published_at).num_seconds());
+
+    Ok(IndexStatus {
+        newest_version: newest_synced.map(Into::into),
+        lag_seconds,
+        pending_syncs: pending_syncs(kind, None, conn)?,
+    })
+}
+
+fn crate_index_status(
+    kind: IndexKind,
+    krate: &Crate,
+    newest: Option<NaiveDateTime>,
+    sync_time: Option<&IndexSyncTime>,
+    conn: &mut PgConnection,
+) -> QueryResult<CrateIndexStatus> {
+    let synced_at = sync_time.and_then(|sync_time| match kind {
+        IndexKind::Git => sync_time.git_synced_at,
+        IndexKind::Sparse => sync_time.sparse_synced_at,
+    });
+
+    let up_to_date = match (newest, synced_at) {
+        (Some(newest), Some(synced_at)) => synced_at >= newest,
+        _ => false,
+    };
+
+    Ok(CrateIndexStatus {
+        synced_at,
+        up_to_date,
+        sync_pending: pending_syncs(kind, Some(&krate.name), conn)? > 0,
+    })
+}
+
+/// Handles the `GET /status/propagation` route.
+///
+/// Reports the newest version in the database, and the newest version that
+/// was synced to the git and sparse indexes. With the `crate` query parameter
+/// the sync status of a single crate is reported as well.
+pub async fn propagation(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let crate_name = req.query().get("crate").cloned();
+
+        let conn = &mut *app.db_read_prefer_primary()?;
+
+        let newest = newest_version(conn)?;
+        let git_index = index_status(IndexKind::Git, newest.as_ref(), conn)?;
+        let sparse_index = index_status(IndexKind::Sparse, newest.as_ref(), conn)?;
+
+        let krate = match crate_name {
+            Some(crate_name) => {
+                let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+                let newest: Option<(String, NaiveDateTime)> = versions::table
+                    .filter(versions::crate_id.eq(krate.id))
+                    .select((versions::num, versions::created_at))
+                    .order(versions::created_at.desc())
+                    .first(conn)
+                    .optional()?;
+
+                let published_at = newest.as_ref().map(|(_, published_at)| *published_at);
+                let sync_time = IndexSyncTime::for_crate(krate.id, conn)?;
+                let sync_time = sync_time.as_ref();
+
+                let git_index =
+                    crate_index_status(IndexKind::Git, &krate, published_at, sync_time, conn)?;
+                let sparse_index =
+                    crate_index_status(IndexKind::Sparse, &krate, published_at, sync_time, conn)?;
+
+                Some(json!({
+                    "name": krate.name,
+                    "newest_version": newest.map(|(num, _)| num),
+                    "git_index": git_index,
+                    "sparse_index": sparse_index,
+                }))
+            }
+            None => None,
+        };
+
+        Ok(Json(json!({
+            "database": newest.map(EncodableVisibleVersion::from),
+            "git_index": git_index,
+            "sparse_index": sparse_index,
+            "crate": krate,
+        })))
+    })
+    .await
+}
@@ -0,0 +1,236 @@
+//! Endpoints for managing a per user list of subscribed keywords and categories
+
+use crate::auth::AuthCheck;
+
+use crate::controllers::frontend_prelude::*;
+use crate::controllers::helpers::pagination::{Paginated, PaginationOptions};
+use crate::controllers::helpers::*;
+use crate::models::{
+    subscribed_category_ids, subscribed_crate_ids, Category, CategorySubscription, DigestFrequency,
+    DigestPreference, Keyword, KeywordSubscription, User, Version, VersionOwnerAction,
+};
+use crate::schema::*;
+use crate::util::rfc3339;
+use crate::views::EncodableVersion;
+use chrono::NaiveDateTime;
+
+fn keyword_subscription(
+    keyword: &str,
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> AppResult<KeywordSubscription> {
+    let keyword_id = Keyword::find_by_keyword(conn, keyword)?.id;
+    Ok(KeywordSubscription {
+        user_id,
+        keyword_id,
+    })
+}
+
+fn category_subscription(
+    slug: &str,
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> AppResult<CategorySubscription> {
+    let category_id = Category::by_slug(slug).select(categories::id).first(conn)?;
+    Ok(CategorySubscription {
+        user_id,
+        category_id,
+    })
+}
+
+/// Handles the `PUT /keywords/:keyword_id/subscribe` route.
+pub async fn subscribe_keyword(
+    app: AppState,
+    Path(keyword): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+        let subscription = keyword_subscription(&keyword, conn, user_id)?;
+        diesel::insert_into(keyword_subscriptions::table)
+            .values(&subscription)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `DELETE /keywords/:keyword_id/subscribe` route.
+pub async fn unsubscribe_keyword(
+    app: AppState,
+    Path(keyword): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+        let subscription = keyword_subscription(&keyword, conn, user_id)?;
+        diesel::delete(&subscription).execute(conn)?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `PUT /categories/:category_id/subscribe` route.
+pub async fn subscribe_category(
+    app: AppState,
+    Path(slug): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+        let subscription = category_subscription(&slug, conn, user_id)?;
+        diesel::insert_into(category_subscriptions::table)
+            .values(&subscription)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `DELETE /categories/:category_id/subscribe` route.
+pub async fn unsubscribe_category(
+    app: AppState,
+    Path(slug): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+        let subscription = category_subscription(&slug, conn, user_id)?;
+        diesel::delete(&subscription).execute(conn)?;
+
+        ok_true()
+    })
+    .await
+}
+
+#[derive(Serialize)]
+struct EncodableDigestPreference {
+    frequency: DigestFrequency,
+    #[serde(with = "rfc3339::option")]
+    last_sent_at: Option<NaiveDateTime>,
+}
+
+/// Handles the `GET /me/subscriptions` route.
+pub async fn list(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+
+        let keywords: Vec<String> = keyword_subscriptions::table
+            .inner_join(keywords::table)
+            .filter(keyword_subscriptions::user_id.eq(user_id))
+            .select(keywords::keyword)
+            .order(keywords::keyword)
+            .load(conn)?;
+
+        let categories: Vec<String> = category_subscriptions::table
+            .inner_join(categories::table)
+            .filter(category_subscriptions::user_id.eq(user_id))
+            .select(categories::slug)
+            .order(categories::slug)
+            .load(conn)?;
+
+        let preference = DigestPreference::for_user(user_id, conn)?;
+        let digest = EncodableDigestPreference {
+            frequency: preference.frequency,
+            last_sent_at: preference.last_sent_at,
+        };
+
+        Ok(Json(json!({
+            "keywords": keywords,
+            "categories": categories,
+            "digest": digest,
+        })))
+    })
+    .await
+}
+
+/// Handles the `PUT /me/subscriptions` route.
+pub async fn update(app: AppState, req: BytesRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct DigestUpdate {
+            frequency: DigestFrequency,
+        }
+
+        #[derive(Deserialize)]
+        struct SubscriptionsUpdate {
+            digest: DigestUpdate,
+        }
+
+        let update: SubscriptionsUpdate = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+
+        let conn = &mut *app.db_write()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+
+        let preference = DigestPreference::for_user(user_id, conn)?;
+        DigestPreference {
+            frequency: update.digest.frequency,
+            ..preference
+        }
+        .save(conn)?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `GET /me/subscriptions/updates` route.
+///
+/// Lists the versions of the crates that match the keyword and category
+/// subscriptions of the user, newest first.
+pub async fn updates(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut app.db_read_prefer_primary()?;
+        let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+
+        let category_ids = subscribed_category_ids(user_id, conn)?;
+        let (by_keyword, by_category) = subscribed_crate_ids(user_id, category_ids);
+
+        let query = versions::table
+            .inner_join(crates::table)
+            .left_outer_join(users::table)
+            .filter(
+                crates::id
+                    .eq_any(by_keyword)
+                    .or(crates::id.eq_any(by_category)),
+            )
+            .order(versions::created_at.desc())
+            .select((
+                versions::all_columns,
+                crates::name,
+                users::all_columns.nullable(),
+            ))
+            .pages_pagination(PaginationOptions::builder().gather(&req)?);
+        let data: Paginated<(Version, String, Option<User>)> = query.load(conn)?;
+        let more = data.next_page_params().is_some();
+        let versions = data.iter().map(|(v, _, _)| v).cloned().collect::<Vec<_>>();
+        let data = data
+            .into_iter()
+            .zip(VersionOwnerAction::for_versions(conn, &versions)?)
+            .map(|((v, cn, pb), voas)| (v, cn, pb, voas));
+
+        let versions = data
+            .into_iter()
+            .map(|(version, crate_name, published_by, actions)| {
+                EncodableVersion::from(version, &crate_name, published_by, actions)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({
+            "versions": versions,
+            "meta": { "more": more },
+        })))
+    })
+    .await
+}
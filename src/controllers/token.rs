@@ -1,6 +1,7 @@
 use super::frontend_prelude::*;
 
-use crate::models::ApiToken;
+use crate::controllers::util::audit_event;
+use crate::models::{AccountCompromise, ApiToken, NewAuditEvent};
 use crate::schema::api_tokens;
 use crate::util::rfc3339;
 use crate::views::EncodableApiTokenWithToken;
@@ -101,6 +102,7 @@ pub async fn new(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
         }
 
         let user = auth.user();
+        AccountCompromise::ensure_not_active(user.id, conn)?;
 
         let max_token_per_user = 500;
         let count: i64 = ApiToken::belonging_to(user).count().get_result(conn)?;
@@ -142,6 +144,21 @@ pub async fn new(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
             endpoint_scopes,
             new.api_token.expired_at,
         )?;
+
+        let token = &api_token.model;
+        NewAuditEvent {
+            user_id: Some(user.id),
+            api_token_id: Some(token.id),
+            details: json!({
+                "name": token.name,
+                "crate_scopes": token.crate_scopes,
+                "endpoint_scopes": token.endpoint_scopes,
+                "expired_at": token.expired_at,
+            }),
+            ..audit_event(&req, "token.create")
+        }
+        .record(conn);
+
         let api_token = EncodableApiTokenWithToken::from(api_token);
 
         Ok(Json(json!({ "api_token": api_token })))
@@ -155,10 +172,19 @@ pub async fn revoke(app: AppState, Path(id): Path<i32>, req: Parts) -> AppResult
         let conn = &mut *app.db_write()?;
         let auth = AuthCheck::default().check(&req, conn)?;
         let user = auth.user();
-        diesel::update(ApiToken::belonging_to(user).find(id))
+        let revoked = diesel::update(ApiToken::belonging_to(user).find(id))
             .set(api_tokens::revoked.eq(true))
             .execute(conn)?;
 
+        if revoked > 0 {
+            NewAuditEvent {
+                user_id: Some(user.id),
+                api_token_id: Some(id),
+                ..audit_event(&req, "token.revoke")
+            }
+            .record(conn);
+        }
+
         Ok(Json(json!({})))
     })
     .await
@@ -177,6 +203,13 @@ pub async fn revoke_current(app: AppState, req: Parts) -> AppResult<Response> {
             .set(api_tokens::revoked.eq(true))
             .execute(conn)?;
 
+        NewAuditEvent {
+            user_id: Some(auth.user_id()),
+            api_token_id: Some(api_token_id),
+            ..audit_event(&req, "token.revoke")
+        }
+        .record(conn);
+
         Ok(StatusCode::NO_CONTENT.into_response())
     })
     .await
@@ -1,3 +1,4 @@
 pub mod me;
 pub mod other;
+pub mod repository_verification;
 pub mod session;
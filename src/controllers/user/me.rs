@@ -6,11 +6,14 @@ use crate::controllers::frontend_prelude::*;
 use crate::controllers::helpers::*;
 
 use crate::controllers::helpers::pagination::{Paginated, PaginationOptions};
+use crate::models::token::EndpointScope;
 use crate::models::{
     CrateOwner, Email, Follow, NewEmail, OwnerKind, User, Version, VersionOwnerAction,
 };
-use crate::schema::{crate_owners, crates, emails, follows, users, versions};
-use crate::views::{EncodableMe, EncodablePrivateUser, EncodableVersion, OwnedCrate};
+use crate::schema::{crate_owners, crates, emails, follows, single_owner_nudges, users, versions};
+use crate::views::{
+    EncodableMe, EncodablePrivateUser, EncodablePublishableCrate, EncodableVersion, OwnedCrate,
+};
 
 /// Handles the `GET /me` route.
 pub async fn me(app: AppState, req: Parts) -> AppResult<Json<EncodableMe>> {
@@ -32,16 +35,25 @@ pub async fn me(app: AppState, req: Parts) -> AppResult<Json<EncodableMe>> {
 
         let owned_crates = CrateOwner::by_owner_kind(OwnerKind::User)
             .inner_join(crates::table)
+            .left_join(single_owner_nudges::table.on(single_owner_nudges::crate_id.eq(crates::id)))
             .filter(crate_owners::owner_id.eq(user_id))
-            .select((crates::id, crates::name, crate_owners::email_notifications))
+            .select((
+                crates::id,
+                crates::name,
+                crate_owners::email_notifications,
+                single_owner_nudges::crate_id.nullable().is_not_null(),
+            ))
             .order(crates::name.asc())
             .load(conn)?
             .into_iter()
-            .map(|(id, name, email_notifications)| OwnedCrate {
-                id,
-                name,
-                email_notifications,
-            })
+            .map(
+                |(id, name, email_notifications, needs_backup_owner)| OwnedCrate {
+                    id,
+                    name,
+                    email_notifications,
+                    needs_backup_owner,
+                },
+            )
             .collect();
 
         let verified = verified.unwrap_or(false);
@@ -54,6 +66,33 @@ pub async fn me(app: AppState, req: Parts) -> AppResult<Json<EncodableMe>> {
     .await
 }
 
+/// Handles the `GET /me/publishable-crates` route.
+///
+/// Lists all existing crates that new versions can be published of with the
+/// current credentials, so that publishing tools can check their targets
+/// upfront. The crate scopes of API tokens are applied to the list.
+pub async fn publishable_crates(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let auth = AuthCheck::default()
+            .with_endpoint_scope(EndpointScope::PublishUpdate)
+            .for_any_crate()
+            .check(&req, conn)?;
+
+        let token = auth.api_token();
+        let crates = auth
+            .user()
+            .publishable_crates(&app, conn)?
+            .into_iter()
+            .filter(|krate| token.map_or(true, |token| token.allows_crate(&krate.name)))
+            .map(EncodablePublishableCrate::from)
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "crates": crates })))
+    })
+    .await
+}
+
 /// Handles the `GET /me/updates` route.
 pub async fn updates(app: AppState, req: Parts) -> AppResult<Json<Value>> {
     conduit_compat(move || {
@@ -138,32 +177,30 @@ pub async fn update_user(
             return Err(bad_request("empty email rejected"));
         }
 
-        conn.transaction::<_, BoxedAppError, _>(|conn| {
+        let token: String = with_transaction(conn, |conn| {
             let new_email = NewEmail {
                 user_id: user.id,
                 email: user_email,
             };
 
-            let token: String = insert_into(emails::table)
+            insert_into(emails::table)
                 .values(&new_email)
                 .on_conflict(user_id)
                 .do_update()
                 .set(&new_email)
                 .returning(emails::token)
                 .get_result(conn)
-                .map_err(|_| server_error("Error in creating token"))?;
-
-            // This swallows any errors that occur while attempting to send the email. Some users have
-            // an invalid email set in their GitHub profile, and we should let them sign in even though
-            // we're trying to silently use their invalid address during signup and can't send them an
-            // email. They'll then have to provide a valid email address.
-            let _ = state
-                .emails
-                .send_user_confirm(user_email, &user.gh_login, &token);
-
-            Ok(())
+                .map_err(|_| server_error("Error in creating token"))
         })?;
 
+        // This swallows any errors that occur while attempting to send the email. Some users have
+        // an invalid email set in their GitHub profile, and we should let them sign in even though
+        // we're trying to silently use their invalid address during signup and can't send them an
+        // email. They'll then have to provide a valid email address.
+        let _ = state
+            .emails
+            .send_user_confirm(user_email, &user.gh_login, &token);
+
         ok_true()
     })
     .await
@@ -0,0 +1,122 @@
+//! Endpoints for verifying control of a GitHub repository
+//!
+//! Users that have not published any crates yet can prove that they control
+//! a repository by pushing a challenge to it, either as the content of a
+//! `.crates-io-verification` file in the default branch or as a
+//! `crates-io-verification-<challenge>` tag. A verified repository exempts
+//! the user from the stricter rate limits for newcomers.
+
+use crate::app::App;
+use crate::auth::AuthCheck;
+use crate::controllers::frontend_prelude::*;
+use crate::models::repository_verification::{parse_github_repository, CHALLENGE_FILE};
+use crate::models::{RepositoryVerification, User};
+use crate::util::errors::{not_found, NotFound};
+use crate::views::EncodableRepositoryVerification;
+use oauth2::AccessToken;
+
+#[derive(Deserialize)]
+struct StartVerificationRequest {
+    repository: String,
+}
+
+/// Handles the `GET /me/repository_verification` route.
+pub async fn show(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+
+        let verification = RepositoryVerification::find(auth.user_id(), conn)?
+            .map(EncodableRepositoryVerification::from);
+
+        Ok(Json(json!({ "repository_verification": verification })))
+    })
+    .await
+}
+
+/// Handles the `PUT /me/repository_verification` route.
+///
+/// Issues a new challenge for the given repository, replacing any previous
+/// verification of the user.
+pub async fn start(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let request: StartVerificationRequest = serde_json::from_slice(req.body())
+            .map_err(|error| bad_request(&format_args!("invalid json request: {error}")))?;
+
+        let repository = parse_github_repository(&request.repository).ok_or_else(|| {
+            bad_request(&format_args!(
+                "invalid repository: {}, only GitHub repositories like \
+                 `https://github.com/owner/name` are supported",
+                request.repository
+            ))
+        })?;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+
+        let verification = RepositoryVerification::start(auth.user_id(), &repository, conn)?;
+        let verification = EncodableRepositoryVerification::from(verification);
+
+        Ok(Json(json!({ "repository_verification": verification })))
+    })
+    .await
+}
+
+/// Handles the `PUT /me/repository_verification/verify` route.
+///
+/// Checks whether the challenge was pushed to the repository, and marks the
+/// repository as verified if it was.
+pub async fn verify(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+        let user = auth.user();
+
+        let mut verification =
+            RepositoryVerification::find(user.id, conn)?.ok_or_else(not_found)?;
+
+        if verification.verified_at.is_none() {
+            if !challenge_was_pushed(&app, &verification, user)? {
+                return Err(bad_request(&format_args!(
+                    "the challenge was not found in {}: add a `{CHALLENGE_FILE}` file \
+                     containing `{}` to the default branch, or push the `{}` tag",
+                    verification.repository,
+                    verification.challenge,
+                    verification.challenge_tag(),
+                )));
+            }
+
+            verification = verification.mark_verified(conn)?;
+        }
+
+        let verification = EncodableRepositoryVerification::from(verification);
+        Ok(Json(json!({ "repository_verification": verification })))
+    })
+    .await
+}
+
+fn challenge_was_pushed(
+    app: &App,
+    verification: &RepositoryVerification,
+    user: &User,
+) -> AppResult<bool> {
+    let (owner, name) = verification.owner_and_name();
+    let token = AccessToken::new(user.gh_access_token.clone());
+
+    match app
+        .github
+        .repository_file(owner, name, CHALLENGE_FILE, &token)
+    {
+        Ok(content) if content.trim() == verification.challenge => return Ok(true),
+        Ok(_) => {}
+        Err(e) if e.is::<NotFound>() => {}
+        Err(e) => return Err(e),
+    }
+
+    let tag = verification.challenge_tag();
+    match app.github.repository_tag(owner, name, &tag, &token) {
+        Ok(_) => Ok(true),
+        Err(e) if e.is::<NotFound>() => Ok(false),
+        Err(e) => Err(e),
+    }
+}
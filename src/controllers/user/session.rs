@@ -3,13 +3,15 @@ use crate::controllers::frontend_prelude::*;
 use oauth2::reqwest::http_client;
 use oauth2::{AuthorizationCode, Scope, TokenResponse};
 
+use crate::controllers::util::audit_event;
 use crate::email::Emails;
 use crate::github::GithubUser;
 use crate::middleware::session::SessionExtension;
-use crate::models::{NewUser, User};
+use crate::models::{AccountCompromise, NewAuditEvent, NewUser, User};
 use crate::schema::users;
 use crate::util::errors::ReadOnlyMode;
 use crate::views::EncodableMe;
+use chrono::Utc;
 
 /// Handles the `GET /api/private/session/begin` route.
 ///
@@ -101,11 +103,20 @@ pub async fn authorize(
 
         // Fetch the user info from GitHub using the access token we just got and create a user record
         let ghuser = app.github.current_user(token)?;
-        let user =
-            save_user_to_database(&ghuser, token.secret(), &app.emails, &mut *app.db_write()?)?;
+        let conn = &mut *app.db_write()?;
+        let user = save_user_to_database(&ghuser, token.secret(), &app.emails, conn)?;
+        AccountCompromise::ensure_not_active(user.id, conn)?;
+
+        NewAuditEvent {
+            user_id: Some(user.id),
+            ..audit_event(&req, "login")
+        }
+        .record(conn);
 
         // Log in by setting a cookie and the middleware authentication
         session.insert("user_id".to_string(), user.id.to_string());
+        let started_at = Utc::now().timestamp_millis();
+        session.insert("session_started_at".to_string(), started_at.to_string());
 
         Ok(req)
     })
@@ -147,6 +158,7 @@ fn save_user_to_database(
 /// Handles the `DELETE /api/private/session` route.
 pub async fn logout(session: SessionExtension) -> Json<bool> {
     session.remove("user_id");
+    session.remove("session_started_at");
     Json(true)
 }
 
@@ -1,4 +1,5 @@
 use super::prelude::*;
+use crate::models::NewAuditEvent;
 use crate::util::errors::{forbidden, internal, AppError, AppResult};
 use http::request::Parts;
 use http::{Extensions, HeaderMap, HeaderValue, Method, Request, Uri, Version};
@@ -25,6 +26,21 @@ pub fn verify_origin<T: RequestPartsExt>(req: &T) -> AppResult<()> {
     Ok(())
 }
 
+/// Creates an audit event of the given kind, with the IP address of the client
+/// that sent the request. The IP address has already been anonymized by the
+/// `anonymize_ip` middleware if `IP_ANONYMIZATION` is enabled.
+pub fn audit_event<'a, T: RequestPartsExt>(req: &'a T, kind: &'a str) -> NewAuditEvent<'a> {
+    let ip_address = req
+        .headers()
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok());
+
+    NewAuditEvent {
+        ip_address,
+        ..NewAuditEvent::new(kind)
+    }
+}
+
 pub trait RequestPartsExt {
     fn method(&self) -> &Method;
     fn uri(&self) -> &Uri;
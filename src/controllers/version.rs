@@ -1,6 +1,8 @@
+pub mod changes;
 pub mod deprecated;
 pub mod downloads;
 pub mod metadata;
+pub mod readme;
 pub mod yank;
 
 use super::prelude::*;
@@ -0,0 +1,110 @@
+//! Feed of changes to published versions
+//!
+//! Mirrors that cache crate files can poll these endpoints to learn about new
+//! versions and changes of the yanked state without scanning the index. Every
+//! change has an increasing sequence number, which is passed as the `since`
+//! parameter of the next request.
+//!
+//! The sequence numbers are assigned when the changes are committed, so a
+//! change that becomes visible later never has a lower sequence number than
+//! the changes that a client has already seen.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::models::VersionAction;
+use crate::schema::{crates, version_owner_actions, versions};
+use crate::views::EncodableVersionChange;
+use chrono::{DateTime, NaiveDateTime};
+
+const DEFAULT_PER_PAGE: i64 = 100;
+const MAX_PER_PAGE: i64 = 1000;
+
+/// Handles the `GET /changes` route.
+///
+/// Lists publishes, yanks and unyanks of versions.
+pub async fn index(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || list_changes(&app, &req, true)).await
+}
+
+/// Handles the `GET /changes/yanks` route.
+///
+/// Lists only yanks and unyanks of versions.
+pub async fn yanks(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || list_changes(&app, &req, false)).await
+}
+
+fn list_changes(app: &AppState, req: &Parts, include_publishes: bool) -> AppResult<Json<Value>> {
+    let params = req.query();
+
+    let since = match params.get("since") {
+        Some(since) => since
+            .parse::<i64>()
+            .map_err(|_| bad_request("invalid `since` parameter, expected a sequence number"))?,
+        None => 0,
+    };
+
+    let since_time = params
+        .get("since_time")
+        .map(|time| DateTime::parse_from_rfc3339(time).map(|time| time.naive_utc()))
+        .transpose()
+        .map_err(|_| {
+            bad_request("invalid `since_time` parameter, expected an RFC 3339 timestamp")
+        })?;
+
+    let per_page = match params.get("per_page") {
+        Some(per_page) => per_page
+            .parse::<i64>()
+            .ok()
+            .filter(|per_page| (1..=MAX_PER_PAGE).contains(per_page))
+            .ok_or_else(|| {
+                bad_request(&format_args!(
+                    "invalid `per_page` parameter, expected a number between 1 and {MAX_PER_PAGE}"
+                ))
+            })?,
+        None => DEFAULT_PER_PAGE,
+    };
+
+    let conn = &mut *app.db_read()?;
+
+    let mut query = version_owner_actions::table
+        .inner_join(versions::table.inner_join(crates::table))
+        .filter(version_owner_actions::seq.gt(since))
+        .select((
+            version_owner_actions::seq.assume_not_null(),
+            crates::name,
+            versions::num,
+            version_owner_actions::action,
+            version_owner_actions::time,
+        ))
+        .order(version_owner_actions::seq)
+        .limit(per_page)
+        .into_boxed();
+
+    if !include_publishes {
+        query = query.filter(version_owner_actions::action.ne(VersionAction::Publish));
+    }
+
+    if let Some(since_time) = since_time {
+        query = query.filter(version_owner_actions::time.gt(since_time));
+    }
+
+    let changes = query
+        .load::<(i64, String, String, VersionAction, NaiveDateTime)>(conn)?
+        .into_iter()
+        .map(|(seq, krate, num, action, time)| EncodableVersionChange {
+            seq,
+            krate,
+            num,
+            action: action.into(),
+            yanked: action == VersionAction::Yank,
+            time,
+        })
+        .collect::<Vec<_>>();
+
+    let next_since = changes.last().map_or(since, |change| change.seq);
+
+    Ok(Json(json!({
+        "changes": changes,
+        "meta": { "next_since": next_since },
+    })))
+}
@@ -3,14 +3,23 @@
 //! Crate level functionality is located in `krate::downloads`.
 
 use super::version_and_crate;
+use crate::background_jobs::Job;
 use crate::controllers::prelude::*;
 use crate::db::PoolError;
 use crate::middleware::log_request::RequestLogExt;
-use crate::models::{Crate, VersionDownload};
+use crate::models::{Crate, VersionArchive, VersionDownload};
 use crate::schema::*;
+use crate::storage::Storage;
+use crate::util::errors::{
+    bad_request, internal, not_found, service_unavailable, CrateFileUnavailable,
+};
 use crate::views::EncodableVersionDownload;
 use chrono::{Duration, NaiveDate, Utc};
 
+/// How long the signed URLs of crate files are valid, if the storage requires
+/// them. Cargo follows the redirect immediately, so this can be short.
+const SIGNED_URL_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
 /// Handles the `GET /crates/:crate_id/:version/download` route.
 /// This returns a URL to the location where the crate is stored.
 pub async fn download(
@@ -19,20 +28,28 @@ pub async fn download(
     req: Parts,
 ) -> AppResult<Response> {
     let wants_json = req.wants_json();
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
 
     let cache_key = (crate_name.to_string(), version.to_string());
 
     let cache_result =
         info_span!("cache.read", ?cache_key).in_scope(|| app.version_id_cacher.get(&cache_key));
 
-    let (crate_name, version) = if let Some(version_id) = cache_result {
+    let (crate_name, version, archived) = if let Some(version_id) = cache_result {
         app.instance_metrics.version_id_cache_hits.inc();
 
         // The increment does not happen instantly, but it's deferred to be executed in a batch
         // along with other downloads. See crate::downloads_counter for the implementation.
         app.downloads_counter.increment(version_id);
 
-        (crate_name, version)
+        // Only versions that were not archived are cached. The `archive_versions`
+        // job keeps the crate file at its regular location for longer than
+        // the cache TTL after archiving it, so the redirect still works.
+        (crate_name, version, Some(false))
     } else {
         app.instance_metrics.version_id_cache_misses.inc();
 
@@ -57,7 +74,7 @@ pub async fn download(
 
                 // Returns the crate name as stored in the database, or an error if we could
                 // not load the version ID from the database.
-                let (version_id, canonical_crate_name) = app
+                let (version_id, canonical_crate_name, archived, unavailable) = app
                     .instance_metrics
                     .downloads_select_query_execution_time
                     .observe_closure_duration(|| {
@@ -65,25 +82,57 @@ pub async fn download(
                             || {
                                 versions
                                     .inner_join(crates::table)
-                                    .select((id, crates::name))
+                                    .left_join(version_archives::table)
+                                    .left_join(unavailable_versions::table)
+                                    .select((
+                                        id,
+                                        crates::name,
+                                        version_archives::version_id.nullable().is_not_null(),
+                                        unavailable_versions::version_id.nullable().is_not_null(),
+                                    ))
                                     .filter(Crate::with_name(&crate_name))
                                     .filter(num.eq(&version))
-                                    .first::<(i32, String)>(&mut *conn)
+                                    .first::<(i32, String, bool, bool)>(&mut *conn)
                             },
                         )
                     })?;
 
+                if unavailable {
+                    // Without the crate file the redirect would only lead
+                    // to a confusing 404 response of the CDN.
+                    return Err(Box::new(CrateFileUnavailable {
+                        crate_name: canonical_crate_name,
+                        version,
+                    }));
+                }
+
                 // The increment does not happen instantly, but it's deferred to be executed in a batch
                 // along with other downloads. See crate::downloads_counter for the implementation.
                 app.downloads_counter.increment(version_id);
 
-                if canonical_crate_name != crate_name {
+                if archived {
+                    // The crate file is restored in the background, so that
+                    // subsequent downloads can be served via the CDN again.
+                    // The version_id is not cached, so that these downloads
+                    // notice when the restore has finished.
+                    let conn = &mut *app.db_write()?;
+                    with_transaction(conn, |conn| {
+                        if VersionArchive::request_restore(version_id, conn)? {
+                            Job::restore_crate_file(version_id).enqueue(conn)?;
+                        }
+                        Ok(())
+                    })?;
+
+                    req.request_log().add("archived", "true");
+
+                    Ok((canonical_crate_name, version, Some(true)))
+                } else if canonical_crate_name != crate_name {
                     app.instance_metrics
                         .downloads_non_canonical_crate_name_total
                         .inc();
                     req.request_log().add("bot", "dl");
 
-                    Ok((canonical_crate_name, version))
+                    Ok((canonical_crate_name, version, Some(false)))
                 } else {
                     // The version_id is only cached if the provided crate name was canonical.
                     // Non-canonical requests fallback to the "slow" path with a DB query, but
@@ -94,7 +143,7 @@ pub async fn download(
                             .insert(cache_key, version_id)
                     });
 
-                    Ok((crate_name, version))
+                    Ok((crate_name, version, Some(false)))
                 }
             } else {
                 // The download endpoint is the most critical route in the whole crates.io application,
@@ -119,13 +168,69 @@ pub async fn download(
 
                 req.request_log().add("unconditional_redirect", "true");
 
-                Ok((crate_name, version))
+                Ok((crate_name, version, None))
             }
         })
         .await?
     };
 
-    let redirect_url = app.storage.crate_location(&crate_name, &version);
+    // Without the database it's unknown whether the crate file was archived,
+    // so the storage is checked instead, which costs an additional request
+    // per download while the database is unavailable. JSON clients get the
+    // regular URL as before, instead of an error about a restore that can't
+    // be requested without the database.
+    let archived = match archived {
+        Some(archived) => archived,
+        None if wants_json => false,
+        None => matches!(
+            app.storage.crate_file_size(&crate_name, &version).await,
+            Ok(None)
+        ),
+    };
+
+    if archived {
+        if wants_json {
+            return Err(service_unavailable(
+                "This version is currently being restored from the archive, please try again later",
+            ));
+        }
+
+        // Crate files in the archive storage tier are not available via the
+        // CDN, so they are served directly until the restore has finished.
+        // If the file is not found it was restored in the meantime.
+        match app
+            .storage
+            .download_archived_crate_file(&crate_name, &version)
+            .await
+        {
+            Ok(bytes) => {
+                app.download_sampler.sample(
+                    &crate_name,
+                    &version,
+                    user_agent.as_deref(),
+                    "archive",
+                );
+
+                let headers = [(header::CONTENT_TYPE, "application/gzip")];
+                return Ok((headers, bytes).into_response());
+            }
+            Err(object_store::Error::NotFound { .. }) => {}
+            Err(error) => {
+                return Err(internal(format!(
+                    "failed to download archived crate file: {error}"
+                )))
+            }
+        }
+    }
+
+    let redirect_url = crate_url(&app.storage, &crate_name, &version)?;
+    app.download_sampler.sample(
+        &crate_name,
+        &version,
+        user_agent.as_deref(),
+        redirect_host(&redirect_url),
+    );
+
     if wants_json {
         Ok(Json(json!({ "url": redirect_url })).into_response())
     } else {
@@ -133,6 +238,91 @@ pub async fn download(
     }
 }
 
+/// Handles the `GET /crates/:crate_id/download?req=...` route.
+///
+/// This resolves the highest non-yanked version of the crate that matches the
+/// `req` semver requirement and returns a URL to the location where that
+/// version of the crate is stored.
+pub async fn download_matching(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let version_req = req
+            .query()
+            .get("req")
+            .cloned()
+            .ok_or_else(|| bad_request("missing `req` query parameter"))?;
+        let version_req = semver::VersionReq::parse(&version_req)
+            .map_err(|_| bad_request(&format_args!("invalid semver requirement: {version_req}")))?;
+
+        let conn = &mut *app.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+        let candidates: Vec<(i32, String)> = versions::table
+            .filter(versions::crate_id.eq(krate.id))
+            .filter(versions::yanked.eq(false))
+            .select((versions::id, versions::num))
+            .load(conn)?;
+
+        let (version_id, version, _) = candidates
+            .into_iter()
+            .filter_map(|(id, num)| {
+                let semver = semver::Version::parse(&num).ok()?;
+                Some((id, num, semver))
+            })
+            .filter(|(_, _, semver)| version_req.matches(semver))
+            .max_by(|(_, _, a), (_, _, b)| a.cmp(b))
+            .ok_or_else(not_found)?;
+
+        // The increment does not happen instantly, but it's deferred to be executed in a batch
+        // along with other downloads. See crate::downloads_counter for the implementation.
+        app.downloads_counter.increment(version_id);
+
+        let redirect_url = crate_url(&app.storage, &krate.name, &version)?;
+
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok());
+        app.download_sampler.sample(
+            &krate.name,
+            &version,
+            user_agent,
+            redirect_host(&redirect_url),
+        );
+
+        if req.wants_json() {
+            Ok(Json(json!({ "url": redirect_url })).into_response())
+        } else {
+            Ok(redirect(redirect_url))
+        }
+    })
+    .await
+}
+
+/// Returns the URL that a download is redirected to, which is signed if the
+/// storage requires it.
+fn crate_url(storage: &Storage, crate_name: &str, version: &str) -> AppResult<String> {
+    if !storage.requires_signed_urls() {
+        return Ok(storage.crate_location(crate_name, version));
+    }
+
+    storage
+        .presigned_crate_url(crate_name, version, SIGNED_URL_TTL)
+        .map_err(|error| internal(format!("failed to sign crate file URL: {error}")))
+}
+
+/// Returns the host that a download is redirected to, or `local` if the crate
+/// files are served by the application itself.
+fn redirect_host(redirect_url: &str) -> &str {
+    redirect_url
+        .strip_prefix("https://")
+        .and_then(|url| url.split('/').next())
+        .unwrap_or("local")
+}
+
 /// Handles the `GET /crates/:crate_id/:version/downloads` route.
 pub async fn downloads(
     app: AppState,
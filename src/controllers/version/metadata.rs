@@ -6,8 +6,10 @@
 
 use crate::controllers::frontend_prelude::*;
 
-use crate::models::VersionOwnerAction;
-use crate::views::{EncodableDependency, EncodableVersion};
+use crate::models::{VersionArchive, VersionOwnerAction, VersionReproducibility};
+use crate::views::{
+    EncodableDependency, EncodableVersion, EncodableVersionArchive, EncodableVersionReproducibility,
+};
 
 use super::version_and_crate;
 
@@ -74,3 +76,51 @@ pub async fn show(
     })
     .await
 }
+
+/// Handles the `GET /crates/:crate_id/:version/reproducibility` route.
+///
+/// Returns the result of the latest check whether the crate file of this
+/// version matches the files of its source repository, or `null` if the
+/// version has not been checked yet.
+pub async fn reproducibility(
+    state: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        if semver::Version::parse(&version).is_err() {
+            return Err(cargo_err(&format_args!("invalid semver: {version}")));
+        }
+
+        let conn = &mut state.db_read()?;
+        let (version, _) = version_and_crate(conn, &crate_name, &version)?;
+        let reproducibility = VersionReproducibility::for_version(&version, conn)?
+            .map(EncodableVersionReproducibility::from);
+
+        Ok(Json(json!({ "reproducibility": reproducibility })))
+    })
+    .await
+}
+
+/// Handles the `GET /crates/:crate_id/:version/archive` route.
+///
+/// Returns whether the crate file of this version was moved to the archive
+/// storage tier and whether it is currently being restored, or `null` if the
+/// crate file is available via the CDN.
+pub async fn archive(
+    state: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        if semver::Version::parse(&version).is_err() {
+            return Err(cargo_err(&format_args!("invalid semver: {version}")));
+        }
+
+        let conn = &mut state.db_read()?;
+        let (version, _) = version_and_crate(conn, &crate_name, &version)?;
+        let archive =
+            VersionArchive::for_version(&version, conn)?.map(EncodableVersionArchive::from);
+
+        Ok(Json(json!({ "archive": archive })))
+    })
+    .await
+}
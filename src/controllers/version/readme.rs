@@ -0,0 +1,96 @@
+//! Admin functionality for rolling back rendered readmes.
+//!
+//! Re-rendering a readme keeps the previously rendered HTML around, so that
+//! readmes broken by a bad renderer deploy can be restored one version at a
+//! time instead of re-rendering the whole corpus.
+
+use super::version_and_crate;
+use crate::auth::AuthCheck;
+use crate::background_jobs::Job;
+use crate::controllers::prelude::*;
+use crate::controllers::util::audit_event;
+use crate::models::NewAuditEvent;
+use crate::storage::ReplicatedFile;
+use crate::util::errors::{internal, not_found};
+
+/// Handles the `GET /crates/:crate_id/:version/readme/previous` route.
+///
+/// Returns the readme HTML that was replaced by the latest rendering.
+pub async fn previous(
+    app: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+    req: Parts,
+) -> AppResult<Response> {
+    let (crate_name, version) = {
+        let app = app.clone();
+        conduit_compat(move || {
+            let conn = &mut *app.db_read_prefer_primary()?;
+            AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+            let (version, krate) = version_and_crate(conn, &crate_name, &version)?;
+            Ok((krate.name, version.num))
+        })
+        .await?
+    };
+
+    match app
+        .storage
+        .download_previous_readme(&crate_name, &version)
+        .await
+    {
+        Ok(bytes) => Ok(([(header::CONTENT_TYPE, "text/html")], bytes).into_response()),
+        Err(object_store::Error::NotFound { .. }) => Err(not_found()),
+        Err(error) => Err(internal(format!(
+            "failed to download previous readme: {error}"
+        ))),
+    }
+}
+
+/// Handles the `PUT /crates/:crate_id/:version/readme/restore` route.
+///
+/// Replaces the readme with the previously rendered one. Since the replaced
+/// readme becomes the previous one, restoring again undoes the restore.
+pub async fn restore(
+    app: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+    req: Parts,
+) -> AppResult<Response> {
+    let (req, user_id, crate_name, version) = {
+        let app = app.clone();
+        conduit_compat(move || {
+            let conn = &mut *app.db_write()?;
+            let auth = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+            let (version, krate) = version_and_crate(conn, &crate_name, &version)?;
+            Ok((req, auth.user_id(), krate.name, version.num))
+        })
+        .await?
+    };
+
+    match app
+        .storage
+        .restore_previous_readme(&crate_name, &version)
+        .await
+    {
+        Ok(()) => {}
+        Err(object_store::Error::NotFound { .. }) => return Err(not_found()),
+        Err(error) => return Err(internal(format!("failed to restore readme: {error}"))),
+    }
+
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+
+        let file = ReplicatedFile::readme(&crate_name, &version);
+        Job::enqueue_replication(&app.storage, file, conn)?;
+
+        NewAuditEvent {
+            user_id: Some(user_id),
+            details: json!({ "crate": crate_name, "version": version }),
+            ..audit_event(&req, "admin.readme.restore")
+        }
+        .insert(conn)?;
+
+        ok_true()
+    })
+    .await
+}
@@ -6,15 +6,19 @@ use crate::background_jobs::Job;
 use super::version_and_crate;
 use crate::controllers::cargo_prelude::*;
 use crate::models::token::EndpointScope;
-use crate::models::Rights;
 use crate::models::{insert_version_owner_action, VersionAction};
+use crate::models::{Rights, SecurityYank};
 use crate::schema::versions;
+use url::Url;
 
 /// Handles the `DELETE /crates/:crate_id/:version/yank` route.
 /// This does not delete a crate version, it makes the crate
 /// version accessible only to crates that already have a
 /// `Cargo.lock` containing this version.
 ///
+/// Versions that are yanked with `?reason=security&advisory=<url>` are
+/// exported as OSV records by the `/osv` endpoints.
+///
 /// Notes:
 /// Crate deletion is not implemented to avoid breaking builds,
 /// and the goal of yanking a crate is to prevent crates
@@ -51,6 +55,12 @@ fn modify_yank(
         return Err(cargo_err(&format_args!("invalid semver: {version}")));
     }
 
+    let advisory_url = if yanked {
+        security_advisory(req)?
+    } else {
+        None
+    };
+
     let conn = &mut *state.db_write()?;
 
     let auth = AuthCheck::default()
@@ -67,6 +77,11 @@ fn modify_yank(
         return Err(cargo_err("must already be an owner to yank or unyank"));
     }
 
+    // Owners can attach an advisory to versions that were yanked before too.
+    if let Some(advisory_url) = &advisory_url {
+        SecurityYank::record(version.id, advisory_url, conn)?;
+    }
+
     if version.yanked == yanked {
         // The crate is already in the state requested, nothing to do
         return ok_true();
@@ -79,6 +94,7 @@ fn modify_yank(
     let action = if yanked {
         VersionAction::Yank
     } else {
+        SecurityYank::withdraw(version.id, conn)?;
         VersionAction::Unyank
     };
 
@@ -88,3 +104,31 @@ fn modify_yank(
 
     ok_true()
 }
+
+/// Returns the advisory link if the version is yanked for security reasons.
+fn security_advisory(req: &Parts) -> AppResult<Option<String>> {
+    let params = req.query();
+    let advisory = params.get("advisory");
+
+    match params.get("reason").map(String::as_str) {
+        None if advisory.is_some() => Err(cargo_err(
+            "the `advisory` parameter can only be used with `reason=security`",
+        )),
+        None => Ok(None),
+        Some("security") => {
+            let advisory = advisory.ok_or_else(|| {
+                cargo_err("yanking for security reasons requires an `advisory` link")
+            })?;
+
+            match Url::parse(advisory) {
+                Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {
+                    Ok(Some(advisory.clone()))
+                }
+                _ => Err(cargo_err(&format_args!(
+                    "invalid `advisory` link, expected an http(s) URL: {advisory}"
+                ))),
+            }
+        }
+        Some(reason) => Err(cargo_err(&format_args!("unknown yank reason: {reason}"))),
+    }
+}
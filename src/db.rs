@@ -11,6 +11,7 @@ use thiserror::Error;
 use url::Url;
 
 use crate::config;
+use crate::util::deadline::Deadline;
 
 pub type ConnectionPool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
@@ -19,6 +20,7 @@ pub enum DieselPool {
     Pool {
         pool: ConnectionPool,
         time_to_obtain_connection_metric: Histogram,
+        statement_timeout: Duration,
     },
     BackgroundJobPool {
         pool: ConnectionPool,
@@ -49,6 +51,7 @@ impl DieselPool {
         let pool = DieselPool::Pool {
             pool: r2d2_config.build_unchecked(manager),
             time_to_obtain_connection_metric,
+            statement_timeout: config.statement_timeout,
         };
         match pool.wait_until_healthy(Duration::from_secs(5)) {
             Ok(()) => {}
@@ -84,15 +87,30 @@ impl DieselPool {
             DieselPool::Pool {
                 pool,
                 time_to_obtain_connection_metric,
-            } => time_to_obtain_connection_metric.observe_closure_duration(|| {
-                if let Some(conn) = pool.try_get() {
-                    Ok(DieselPooledConn::Pool(conn))
-                } else if !self.is_healthy() {
-                    Err(PoolError::UnhealthyPool)
-                } else {
-                    Ok(DieselPooledConn::Pool(pool.get()?))
-                }
-            }),
+                statement_timeout,
+            } => {
+                let deadline = Deadline::current();
+                let mut conn = time_to_obtain_connection_metric.observe_closure_duration(|| {
+                    if let Some(conn) = pool.try_get() {
+                        Ok(conn)
+                    } else if !self.is_healthy() {
+                        Err(PoolError::UnhealthyPool)
+                    } else if let Some(deadline) = deadline {
+                        let remaining = deadline.remaining().ok_or(PoolError::DeadlineExceeded)?;
+                        let timeout = remaining.min(pool.connection_timeout());
+                        pool.get_timeout(timeout)
+                            .map_err(|error| match deadline.remaining() {
+                                Some(_) => PoolError::R2D2(error),
+                                None => PoolError::DeadlineExceeded,
+                            })
+                    } else {
+                        Ok(pool.get()?)
+                    }
+                })?;
+
+                apply_deadline(&mut conn, deadline, *statement_timeout)?;
+                Ok(DieselPooledConn::Pool(conn))
+            }
             DieselPool::BackgroundJobPool { pool } => Ok(DieselPooledConn::Pool(pool.get()?)),
             DieselPool::Test(conn) => Ok(DieselPooledConn::Test(conn.try_lock().unwrap())),
         }
@@ -226,10 +244,59 @@ impl CustomizeConnection<PgConnection, r2d2::Error> for ConnectionConfig {
     }
 }
 
+/// The `statement_timeout` that is currently set on a pooled connection, if
+/// it differs from the default of the pool.
+struct StatementTimeout(Duration);
+
+/// Lowers the `statement_timeout` of the connection to the time that is left
+/// until the deadline of the current request, or restores the default of the
+/// pool if a previous request has lowered it.
+fn apply_deadline(
+    conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    deadline: Option<Deadline>,
+    default: Duration,
+) -> Result<(), PoolError> {
+    let timeout = match deadline {
+        Some(deadline) => {
+            let remaining = deadline.remaining().ok_or(PoolError::DeadlineExceeded)?;
+            remaining.min(default)
+        }
+        None => default,
+    };
+
+    // The timeout is set in whole milliseconds, and `0` would disable it, so
+    // the remaining time is rounded up to at least one millisecond.
+    let millis = (timeout.as_nanos() + 999_999) / 1_000_000;
+    let timeout = Duration::from_millis(millis.max(1) as u64);
+
+    let extensions = r2d2::PooledConnection::extensions(conn);
+    let current = extensions
+        .get::<StatementTimeout>()
+        .map_or(default, |current| current.0);
+
+    if timeout != current {
+        diesel::sql_query(format!("SET statement_timeout = {}", timeout.as_millis()))
+            .execute(&mut **conn)?;
+
+        let extensions = r2d2::PooledConnection::extensions_mut(conn);
+        if timeout == default {
+            extensions.remove::<StatementTimeout>();
+        } else {
+            extensions.insert(StatementTimeout(timeout));
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 pub enum PoolError {
     #[error(transparent)]
     R2D2(#[from] r2d2::PoolError),
     #[error("unhealthy database pool")]
     UnhealthyPool,
+    #[error("request deadline exceeded")]
+    DeadlineExceeded,
+    #[error(transparent)]
+    Query(#[from] diesel::result::Error),
 }
@@ -0,0 +1,159 @@
+use crate::schema::download_redirect_samples;
+use crate::App;
+use anyhow::Error;
+use diesel::prelude::*;
+use std::sync::Mutex;
+
+/// Maximum number of samples that are kept in memory until they are persisted.
+/// Samples are dropped if the database can't keep up, since losing some of
+/// them only makes the analytics slightly less accurate.
+const MAX_PENDING_SAMPLES: usize = 10_000;
+
+/// Records a random sample of the download redirects in the
+/// `download_redirect_samples` table, which answers questions like "which
+/// cargo versions still download yanked versions" without having to process
+/// the CDN logs.
+///
+/// Like the `DownloadsCounter`, the samples are collected in memory and
+/// periodically written to the database in a single batch.
+#[derive(Debug)]
+pub struct DownloadSampler {
+    /// Fraction of the download redirects that are recorded, between 0 and 1.
+    rate: f64,
+    pending: Mutex<Vec<DownloadSample>>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = download_redirect_samples, check_for_backend(diesel::pg::Pg))]
+struct DownloadSample {
+    crate_name: String,
+    version: String,
+    user_agent_class: String,
+    cdn: String,
+    sample_rate: f64,
+}
+
+impl DownloadSampler {
+    pub(crate) fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Randomly decides whether the download redirect is sampled, according
+    /// to the configured rate.
+    ///
+    /// `cdn` is the host that the client is redirected to, or `archive` if
+    /// the crate file was served directly from the archive storage.
+    pub(crate) fn sample(
+        &self,
+        crate_name: &str,
+        version: &str,
+        user_agent: Option<&str>,
+        cdn: &str,
+    ) {
+        if self.rate <= 0.0 || rand::random::<f64>() >= self.rate {
+            return;
+        }
+
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        if pending.len() >= MAX_PENDING_SAMPLES {
+            return;
+        }
+
+        pending.push(DownloadSample {
+            crate_name: crate_name.to_string(),
+            version: version.to_string(),
+            user_agent_class: user_agent_class(user_agent),
+            cdn: cdn.to_string(),
+            sample_rate: self.rate,
+        });
+    }
+
+    /// Writes all pending samples to the database, returning how many were
+    /// written.
+    pub fn persist(&self, app: &App) -> Result<usize, Error> {
+        let samples = {
+            let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+            std::mem::take(&mut *pending)
+        };
+
+        if samples.is_empty() {
+            return Ok(0);
+        }
+
+        let conn = &mut app.primary_database.get()?;
+        let inserted = diesel::insert_into(download_redirect_samples::table)
+            .values(&samples)
+            .execute(conn)?;
+
+        Ok(inserted)
+    }
+}
+
+/// Reduces a `User-Agent` header to a small number of classes, so that the
+/// samples don't contain identifying details of the clients.
+///
+/// Cargo is classified by its minor version, e.g. `cargo/1.72`.
+fn user_agent_class(user_agent: Option<&str>) -> String {
+    let Some(user_agent) = user_agent.filter(|ua| !ua.is_empty()) else {
+        return "none".to_string();
+    };
+
+    if let Some(version) = user_agent.strip_prefix("cargo/") {
+        let version = version.split_whitespace().next().unwrap_or_default();
+        let mut parts = version.split('.');
+        return match (parts.next(), parts.next()) {
+            (Some(major), Some(minor))
+                if major.chars().all(|c| c.is_ascii_digit())
+                    && minor.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                format!("cargo/{major}.{minor}")
+            }
+            _ => "cargo".to_string(),
+        };
+    }
+
+    let class = if user_agent.starts_with("Mozilla/") {
+        "browser"
+    } else if user_agent.starts_with("curl/") {
+        "curl"
+    } else if user_agent.starts_with("Wget/") {
+        "wget"
+    } else {
+        "other"
+    };
+
+    class.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_agent_classes() {
+        let class = |ua| user_agent_class(Some(ua));
+        assert_eq!(class("cargo/1.72.0 (103a7ff2e 2023-08-15)"), "cargo/1.72");
+        assert_eq!(class("cargo/1.74.0-nightly"), "cargo/1.74");
+        assert_eq!(class("cargo/unknown"), "cargo");
+        assert_eq!(class("Mozilla/5.0 (X11; Linux x86_64)"), "browser");
+        assert_eq!(class("curl/8.1.2"), "curl");
+        assert_eq!(class("Wget/1.21.4"), "wget");
+        assert_eq!(class("python-requests/2.31.0"), "other");
+        assert_eq!(user_agent_class(Some("")), "none");
+        assert_eq!(user_agent_class(None), "none");
+    }
+
+    #[test]
+    fn disabled_sampler_records_nothing() {
+        let sampler = DownloadSampler::new(0.0);
+        sampler.sample("foo", "1.0.0", None, "static.crates.io");
+        assert!(sampler.pending.lock().unwrap().is_empty());
+
+        let sampler = DownloadSampler::new(1.0);
+        sampler.sample("foo", "1.0.0", Some("cargo/1.72.0"), "static.crates.io");
+        assert_eq!(sampler.pending.lock().unwrap().len(), 1);
+    }
+}
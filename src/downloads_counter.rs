@@ -246,6 +246,7 @@ mod tests {
     use crate::email::Emails;
     use crate::models::{Crate, NewCrate, NewUser, NewVersion, User};
     use crate::test_util::pg_connection;
+    use crate::util::source_links::VersionSource;
     use diesel::PgConnection;
     use semver::Version;
     use std::collections::BTreeMap;
@@ -461,6 +462,8 @@ mod tests {
                 "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
                 None,
                 None,
+                false,
+                VersionSource::default(),
             )
             .expect("failed to create version")
             .save(conn, "ghost@example.com")
@@ -4,6 +4,7 @@ use std::sync::Mutex;
 use crate::util::errors::{server_error, AppResult};
 
 use crate::config;
+use crate::models::{DigestEntry, FollowActivity, FollowEvent, VersionAction};
 use crate::Env;
 use lettre::message::header::ContentType;
 use lettre::transport::file::FileTransport;
@@ -91,6 +92,74 @@ or go to https://{domain}/me/pending-invites to manage all of your crate ownersh
         self.send(email, subject, &body)
     }
 
+    /// Attempts to notify an invited user that their ownership invitation expired.
+    pub fn send_owner_invite_expired(
+        &self,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+    ) -> AppResult<()> {
+        let subject = "Crate ownership invitation expired";
+        let body = format!(
+            "The invitation from {user_name} to become an owner of the crate {crate_name} has expired.\n
+If you still want to become an owner, please ask {user_name} or another owner of the crate
+to send you a new invitation."
+        );
+
+        self.send(email, subject, &body)
+    }
+
+    /// Attempts to notify the inviting user that an ownership invitation expired
+    /// without being accepted.
+    pub fn send_owner_invite_expired_to_inviter(
+        &self,
+        email: &str,
+        invitee_name: &str,
+        crate_name: &str,
+    ) -> AppResult<()> {
+        let subject = "Crate ownership invitation expired";
+        let body = format!(
+            "Your invitation for {invitee_name} to become an owner of the crate {crate_name} has expired
+without being accepted.\n
+Visit https://{domain}/crates/{crate_name}/settings to send the invitation again.",
+            domain = crate::config::domain_name()
+        );
+
+        self.send(email, subject, &body)
+    }
+
+    /// Attempts to ask the only owner of popular crates to add another owner.
+    pub fn send_single_owner_nudge(
+        &self,
+        email: &str,
+        user_name: &str,
+        crate_names: &[String],
+    ) -> AppResult<()> {
+        let domain = crate::config::domain_name();
+        let subject = "Please consider adding another owner to your crates";
+
+        let mut body = format!(
+            "Hello {user_name}!
+
+The following crates are downloaded by many users, but you are their only owner:
+"
+        );
+        for crate_name in crate_names {
+            body.push_str(&format!(
+                "\n- {crate_name}\n  https://{domain}/crates/{crate_name}/settings\n"
+            ));
+        }
+        body.push_str(
+            "
+If you lose access to your account, nobody would be able to publish fixes for
+these crates anymore. Please consider inviting another person or a team that
+you trust as an owner in the settings of the crates.
+",
+        );
+
+        self.send(email, subject, &body)
+    }
+
     /// Attempts to send an API token exposure notification email
     pub fn send_token_exposed_notification(
         &self,
@@ -118,6 +187,105 @@ Source type: {source}\n",
         self.send(email, subject, &body)
     }
 
+    /// Attempts to send a notification that the account was put into
+    /// compromise mode by the crates.io team.
+    pub fn send_account_compromise_notification(
+        &self,
+        email: &str,
+        user_name: &str,
+        revoked_tokens: i32,
+        flagged_versions: i32,
+    ) -> AppResult<()> {
+        let subject = "Your crates.io account may have been compromised";
+        let body = format!(
+            "Hello {user_name}!
+
+The crates.io team has reason to believe that your crates.io account may have
+been compromised. As a precaution, we have taken the following steps:
+
+- {revoked_tokens} API tokens of your account have been revoked.
+- You have been logged out of all browser sessions.
+- New versions published from your account are held until the compromise is
+  resolved.
+- {flagged_versions} recently published versions will be reviewed by the crates.io team.
+
+Please review the security of your GitHub account and the recent changes to
+your crates at https://{domain}, and contact help@crates.io to be able to
+publish from your account again.",
+            domain = crate::config::domain_name()
+        );
+
+        self.send(email, subject, &body)
+    }
+
+    /// Attempts to send a digest of the releases that match the keyword and
+    /// category subscriptions of the user, and of the activity of the crates
+    /// that the user follows with the email channel.
+    pub fn send_subscription_digest(
+        &self,
+        email: &str,
+        user_name: &str,
+        entries: &[DigestEntry],
+        activity: &[FollowActivity],
+        truncated: bool,
+    ) -> AppResult<()> {
+        let domain = crate::config::domain_name();
+        let subject = "Your crates.io digest";
+
+        let mut body = format!("Hello {user_name}!\n");
+        if !entries.is_empty() {
+            body.push_str(
+                "
+The following crates were published with the keywords and in the categories
+that you are subscribed to:
+",
+            );
+        }
+        for entry in entries {
+            let new_crate = if entry.new_crate { " (new crate)" } else { "" };
+            body.push_str(&format!(
+                "\n- {} {}{new_crate}\n  https://{domain}/crates/{}/{}\n",
+                entry.crate_name, entry.version, entry.crate_name, entry.version
+            ));
+            if let Some(description) = &entry.description {
+                body.push_str(&format!("  {}\n", description.trim()));
+            }
+        }
+        if !activity.is_empty() {
+            body.push_str("\nThe following happened to the crates that you follow:\n");
+        }
+        for activity in activity {
+            let name = &activity.crate_name;
+            body.push_str(&match &activity.event {
+                FollowEvent::Version { num, action } => {
+                    let action = match action {
+                        VersionAction::Publish => "published",
+                        VersionAction::Yank => "yanked",
+                        VersionAction::Unyank => "unyanked",
+                    };
+                    format!(
+                        "\n- {name} {num} was {action}\n  https://{domain}/crates/{name}/{num}\n"
+                    )
+                }
+                FollowEvent::OwnerAdded { login } => {
+                    format!("\n- {login} was added as an owner of {name}\n")
+                }
+                FollowEvent::OwnerRemoved { login } => {
+                    format!("\n- {login} was removed as an owner of {name}\n")
+                }
+            });
+        }
+        if truncated {
+            body.push_str("\nThere were too many updates to list all of them in this digest.\n");
+        }
+        body.push_str(&format!(
+            "\nYou can change how often you receive this digest, or unsubscribe from it, \
+            in your account settings at https://{domain}/me.\n"
+        ));
+
+        self.send(email, subject, &body)
+    }
+
     /// This is supposed to be used only during tests, to retrieve the messages stored in the
     /// "memory" backend. It's not cfg'd away because our integration tests need to access this.
     pub fn mails_in_memory(&self) -> Option<Vec<StoredEmail>> {
@@ -1,5 +1,6 @@
 //! This module implements functionality for interacting with GitHub.
 
+use base64::{engine::general_purpose, Engine};
 use oauth2::AccessToken;
 use reqwest::{self, header};
 
@@ -13,6 +14,7 @@ use reqwest::blocking::Client;
 
 pub trait GitHubClient: Send + Sync {
     fn current_user(&self, auth: &AccessToken) -> AppResult<GithubUser>;
+    fn user_by_login(&self, login: &str, auth: &AccessToken) -> AppResult<GithubUser>;
     fn org_by_name(&self, org_name: &str, auth: &AccessToken) -> AppResult<GitHubOrganization>;
     fn team_by_name(
         &self,
@@ -34,6 +36,21 @@ pub trait GitHubClient: Send + Sync {
         auth: &AccessToken,
     ) -> AppResult<GitHubOrgMembership>;
     fn public_keys(&self, username: &str, password: &str) -> AppResult<Vec<GitHubPublicKey>>;
+    /// Returns the contents of a file in the default branch of a repository.
+    fn repository_file(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        auth: &AccessToken,
+    ) -> AppResult<String>;
+    fn repository_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        auth: &AccessToken,
+    ) -> AppResult<GitHubRef>;
 }
 
 #[derive(Debug)]
@@ -103,6 +120,11 @@ impl GitHubClient for RealGitHubClient {
         self.request("/user", auth)
     }
 
+    fn user_by_login(&self, login: &str, auth: &AccessToken) -> AppResult<GithubUser> {
+        let url = format!("/users/{login}");
+        self.request(&url, auth)
+    }
+
     fn org_by_name(&self, org_name: &str, auth: &AccessToken) -> AppResult<GitHubOrganization> {
         let url = format!("/orgs/{org_name}");
         self.request(&url, auth)
@@ -149,6 +171,42 @@ impl GitHubClient for RealGitHubClient {
             Err(e) => Err(e),
         }
     }
+
+    fn repository_file(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        auth: &AccessToken,
+    ) -> AppResult<String> {
+        let url = format!("/repos/{owner}/{repo}/contents/{path}");
+        let file: GitHubFileContents = self.request(&url, auth)?;
+        if file.encoding != "base64" {
+            return Err(internal(format!(
+                "unexpected encoding of github file contents: {}",
+                file.encoding
+            )));
+        }
+
+        // GitHub wraps the base64 encoded contents into lines
+        let content: String = file.content.split_whitespace().collect();
+        let content = general_purpose::STANDARD
+            .decode(content)
+            .map_err(|e| internal(format!("invalid base64 from github: {e}")))?;
+
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    }
+
+    fn repository_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        auth: &AccessToken,
+    ) -> AppResult<GitHubRef> {
+        let url = format!("/repos/{owner}/{repo}/git/ref/tags/{tag}");
+        self.request(&url, auth)
+    }
 }
 
 fn handle_error_response(error: &reqwest::Error) -> BoxedAppError {
@@ -201,6 +259,18 @@ pub struct GitHubOrgMembership {
     pub role: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct GitHubFileContents {
+    content: String,
+    encoding: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitHubRef {
+    #[serde(rename = "ref")]
+    pub name: String,
+}
+
 pub fn team_url(login: &str) -> String {
     let mut login_pieces = login.split(':');
     login_pieces.next();
@@ -40,12 +40,14 @@ pub mod background_jobs;
 pub mod boot;
 pub mod config;
 pub mod db;
+mod download_samples;
 mod downloads_counter;
 pub mod email;
 pub mod github;
 pub mod headers;
 pub mod metrics;
 pub mod middleware;
+pub mod policy;
 mod rate_limiter;
 pub mod schema;
 pub mod sql;
@@ -53,6 +55,7 @@ pub mod ssh;
 pub mod swirl;
 mod test_util;
 pub mod util;
+pub mod watchdog;
 pub mod worker;
 
 pub mod auth;
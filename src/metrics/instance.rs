@@ -53,10 +53,22 @@ metrics! {
         /// Number of download requests that are not counted yet.
         downloads_not_counted_total: IntGauge,
 
+        /// Duration of the individual stages of the publish endpoint
+        pub publish_stage_duration: HistogramVec["stage"],
+
         /// Number of version ID cache hits on the download endpoint.
         pub version_id_cache_hits: IntCounter,
         /// Number of version ID cache misses on the download endpoint.
         pub version_id_cache_misses: IntCounter,
+
+        /// Resident memory of the process in bytes
+        pub process_memory_rss_bytes: IntGauge,
+        /// How long it takes until a task on the blocking thread pool starts running
+        pub watchdog_blocking_delay: Histogram,
+        /// How much later than scheduled the watchdog is woken up by the event loop
+        pub watchdog_event_loop_stall: Histogram,
+        /// Number of watchdog checks that exceeded their threshold
+        pub watchdog_thresholds_exceeded_total: IntCounterVec["check"],
     }
 
     // All instance metrics will be prefixed with this namespace.
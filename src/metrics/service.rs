@@ -11,8 +11,10 @@
 //! instance-level metric, and you should add it to `src/metrics/instance.rs`.
 
 use crate::metrics::macros::metrics;
+use crate::models::DownloadsPartition;
 use crate::schema::{background_jobs, crates, versions};
 use crate::util::errors::AppResult;
+use chrono::Utc;
 use diesel::{dsl::count_star, prelude::*, PgConnection};
 use prometheus::{proto::MetricFamily, IntGauge, IntGaugeVec};
 
@@ -24,6 +26,10 @@ metrics! {
         versions_total: IntGauge,
         /// Number of queued up background jobs
         background_jobs: IntGaugeVec["priority", "job"],
+        /// Number of partitions of the `version_downloads` table
+        version_downloads_partitions: IntGauge,
+        /// Number of days until the last partition of the `version_downloads` table ends
+        version_downloads_partition_days_left: IntGauge,
     }
 
     // All service metrics will be prefixed with this namespace.
@@ -52,6 +58,15 @@ impl ServiceMetrics {
                 .set(count);
         }
 
+        let partitions = DownloadsPartition::all(conn)?;
+        self.version_downloads_partitions
+            .set(partitions.len() as i64);
+        if let Some(last) = partitions.last() {
+            let today = Utc::now().date_naive();
+            self.version_downloads_partition_days_left
+                .set((last.to - today).num_days());
+        }
+
         Ok(self.registry.gather())
     }
 }
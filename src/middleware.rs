@@ -1,3 +1,4 @@
+pub mod anonymize_ip;
 pub mod app;
 mod balance_capacity;
 mod block_traffic;
@@ -6,6 +7,7 @@ mod ember_html;
 mod head;
 pub mod log_request;
 pub mod normalize_path;
+mod request_deadline;
 mod require_user_agent;
 pub mod session;
 mod static_or_continue;
@@ -38,10 +40,17 @@ pub fn apply_axum_middleware(state: AppState, router: Router) -> Router {
     }
 
     let middleware = tower::ServiceBuilder::new()
+        .layer(conditional_layer(
+            config.ip_anonymization.is_enabled(),
+            || from_fn_with_state(state.clone(), anonymize_ip::anonymize_ip),
+        ))
         .layer(sentry_tower::NewSentryLayer::<Request>::new_from_top())
         .layer(sentry_tower::SentryHttpLayer::with_transaction())
         .layer(from_fn(log_request::log_requests))
         .layer(CatchPanicLayer::new())
+        .layer(conditional_layer(config.request_budget.is_some(), || {
+            from_fn_with_state(state.clone(), request_deadline::attach_deadline)
+        }))
         .layer(from_fn_with_state(
             state.clone(),
             update_metrics::update_metrics,
@@ -66,7 +75,7 @@ pub fn apply_axum_middleware(state: AppState, router: Router) -> Router {
         ))
         .layer(from_fn(head::support_head_requests))
         .layer(conditional_layer(env == Env::Development, || {
-            from_fn(static_or_continue::serve_local_uploads)
+            from_fn_with_state(state.clone(), static_or_continue::serve_local_uploads)
         }))
         .layer(conditional_layer(config.serve_dist, || {
             from_fn(static_or_continue::serve_dist)
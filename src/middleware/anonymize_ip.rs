@@ -0,0 +1,50 @@
+//! Anonymize the client IP in the `X-Real-Ip` header according to the
+//! `IP_ANONYMIZATION` setting.
+//!
+//! This middleware runs before all other middleware, so the request logs,
+//! Sentry reports and handlers (e.g. `audit_event()`) only ever see the
+//! anonymized IP. The raw IP is kept in the [`RealIp`] request extension,
+//! which must only be used for IP blocklists and never logged or persisted.
+
+use crate::app::AppState;
+use crate::controllers::util::RequestPartsExt;
+use crate::headers::XRealIp;
+use axum::headers::Header;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{HeaderValue, Request};
+
+#[derive(Clone, Debug)]
+pub struct RealIp(String);
+
+impl RealIp {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+pub async fn anonymize_ip<B>(state: AppState, mut req: Request<B>, next: Next<B>) -> Response {
+    let header = req.headers_mut().remove(XRealIp::name());
+    if let Some(raw_ip) = header.as_ref().and_then(|value| value.to_str().ok()) {
+        let anonymized = state.config.ip_anonymization.anonymize(raw_ip);
+        if let Some(value) = anonymized.and_then(|ip| HeaderValue::try_from(ip).ok()) {
+            req.headers_mut().insert(XRealIp::name(), value);
+        }
+
+        req.extensions_mut().insert(RealIp(raw_ip.to_string()));
+    }
+
+    next.run(req).await
+}
+
+/// Returns the raw IP of the client, which must only be used to check it
+/// against IP blocklists.
+pub fn real_ip<T: RequestPartsExt>(req: &T) -> Option<&str> {
+    match req.extensions().get::<RealIp>() {
+        Some(real_ip) => Some(real_ip.as_str()),
+        None => req
+            .headers()
+            .get(XRealIp::name())
+            .and_then(|value| value.to_str().ok()),
+    }
+}
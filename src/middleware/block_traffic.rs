@@ -9,9 +9,12 @@
 //! examples). Values of the headers must match exactly.
 
 use crate::app::AppState;
+use crate::headers::XRealIp;
+use crate::middleware::anonymize_ip::RealIp;
 use crate::middleware::log_request::RequestLogExt;
 use crate::util::errors::RouteBlocked;
 use axum::extract::MatchedPath;
+use axum::headers::Header;
 use axum::middleware::Next;
 use axum::response::IntoResponse;
 use http::StatusCode;
@@ -25,11 +28,21 @@ pub async fn block_traffic<B>(
     let blocked_traffic = &state.config.blocked_traffic;
 
     for (header_name, blocked_values) in blocked_traffic {
-        let has_blocked_value = req
-            .headers()
-            .get_all(header_name)
-            .iter()
-            .any(|value| blocked_values.iter().any(|v| v == value));
+        // The `X-Real-Ip` header might have been anonymized already, so the
+        // raw IP is used instead.
+        let real_ip = req
+            .extensions()
+            .get::<RealIp>()
+            .filter(|_| header_name.eq_ignore_ascii_case(XRealIp::name().as_str()));
+
+        let has_blocked_value = match real_ip {
+            Some(real_ip) => blocked_values.iter().any(|v| v == real_ip.as_str()),
+            None => req
+                .headers()
+                .get_all(header_name)
+                .iter()
+                .any(|value| blocked_values.iter().any(|v| v == value)),
+        };
         if has_blocked_value {
             let cause = format!("blocked due to contents of header {header_name}");
             req.request_log().add("cause", cause);
@@ -0,0 +1,16 @@
+//! Attach a deadline to each request, which is derived from the configured
+//! `WEB_REQUEST_BUDGET_MS`. See the `util::deadline` module for how the
+//! deadline is used.
+
+use crate::app::AppState;
+use crate::util::deadline::Deadline;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::Request;
+
+pub async fn attach_deadline<B>(state: AppState, req: Request<B>, next: Next<B>) -> Response {
+    match state.config.request_budget {
+        Some(budget) => Deadline::after(budget).scope(next.run(req)).await,
+        None => next.run(req).await,
+    }
+}
@@ -1,29 +1,48 @@
 //! This module implements middleware to serve static files from the
 //! specified directory.
 
+use crate::app::AppState;
 use axum::middleware::Next;
-use axum::response::Response;
+use axum::response::{IntoResponse, Response};
 use http::{Method, Request, StatusCode};
-use std::path::Path;
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
 
-pub async fn serve_local_uploads<B>(request: Request<B>, next: Next<B>) -> Response {
-    serve("local_uploads", request, next).await
+/// Serves the files of the local file system storage. The compressed
+/// variants of readmes and index files are served to clients that accept
+/// their encoding, like a CDN would do in production.
+///
+/// If the storage requires signed URLs, crate files are only served with a
+/// valid signature, like a private S3 bucket would do.
+pub async fn serve_local_uploads<B>(
+    state: AppState,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let uri = request.uri();
+    if !state.storage.verify_local_request(uri.path(), uri.query()) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let serve_dir = ServeDir::new("local_uploads")
+        .precompressed_br()
+        .precompressed_gzip();
+
+    serve(serve_dir, request, next).await
 }
 
 pub async fn serve_dist<B>(request: Request<B>, next: Next<B>) -> Response {
-    serve("dist", request, next).await
+    serve(ServeDir::new("dist"), request, next).await
 }
 
-async fn serve<P: AsRef<Path>, B>(path: P, request: Request<B>, next: Next<B>) -> Response {
+async fn serve<B>(serve_dir: ServeDir, request: Request<B>, next: Next<B>) -> Response {
     if request.method() == Method::GET || request.method() == Method::HEAD {
         let mut static_req = Request::new(());
         *static_req.method_mut() = request.method().clone();
         *static_req.uri_mut() = request.uri().clone();
         *static_req.headers_mut() = request.headers().clone();
 
-        if let Ok(response) = ServeDir::new(path).oneshot(static_req).await {
+        if let Ok(response) = serve_dir.oneshot(static_req).await {
             if response.status() != StatusCode::NOT_FOUND {
                 return response.map(axum::body::boxed);
             }
@@ -1,33 +1,85 @@
+pub use self::account_compromise::{AccountCompromise, NewAccountCompromise};
 pub use self::action::{insert_version_owner_action, VersionAction, VersionOwnerAction};
+pub use self::archive::{ArchiveCandidate, VersionArchive};
+pub use self::audit_event::{AuditEvent, NewAuditEvent};
 pub use self::category::{Category, CrateCategory, NewCategory};
 pub use self::crate_owner_invitation::{CrateOwnerInvitation, NewCrateOwnerInvitationOutcome};
-pub use self::dependency::{Dependency, DependencyKind, ReverseDependency};
+pub use self::dependency::{
+    CrossRegistryDependency, Dependency, DependencyKind, NewCrossRegistryDependency, NewDependency,
+    ReverseDependency,
+};
 pub use self::download::VersionDownload;
+pub use self::download_anomaly::{DownloadAnomaly, DownloadBaseline};
+pub use self::downloads_partition::DownloadsPartition;
 pub use self::email::{Email, NewEmail};
-pub use self::follow::Follow;
+pub use self::feature_usage::FeatureUsage;
+pub use self::fingerprint::VersionFingerprint;
+pub use self::follow::{
+    follow_activity, Follow, FollowActivity, FollowChannel, FollowEvent, FollowMode,
+};
+pub use self::index_sync::{
+    newest_synced_version, newest_version, IndexKind, IndexSyncTime, VisibleVersion,
+};
 pub use self::keyword::{CrateKeyword, Keyword};
-pub use self::krate::{Crate, CrateVersions, NewCrate, RecentCrateDownloads};
+pub use self::krate::{Crate, CrateVersions, NewCrate, OwnerInviteEmail, RecentCrateDownloads};
+pub use self::moderation::{ModerationFlag, NewModerationFlag};
+pub use self::msrv_stats::MsrvStat;
 pub use self::owner::{CrateOwner, Owner, OwnerKind};
+pub use self::publish_diagnostics::{NewPublishDiagnostics, PublishDiagnostics};
+pub use self::publish_warnings::VersionPublishWarnings;
+pub use self::repository_check::{RepositoryCheck, RepositoryCheckCandidate, RepositoryStatus};
+pub use self::repository_verification::RepositoryVerification;
+pub use self::reproducibility::VersionReproducibility;
 pub use self::rights::Rights;
+pub use self::scan_report::VersionScanReport;
+pub use self::security_yank::SecurityYank;
+pub use self::subscription::{
+    digest_entries, subscribed_category_ids, subscribed_crate_ids, CategorySubscription,
+    DigestEntry, DigestFrequency, DigestPreference, KeywordSubscription,
+};
+pub use self::support_window::{SupportStatus, SupportWindow};
 pub use self::team::{NewTeam, Team};
 pub use self::token::{ApiToken, CreatedApiToken};
-pub use self::user::{NewUser, User};
+pub use self::tombstone::Tombstone;
+pub use self::unavailable::UnavailableVersion;
+pub use self::user::{NewUser, PublishableCrate, User};
 pub use self::version::{NewVersion, TopVersions, Version};
 
 pub mod helpers;
 
+mod account_compromise;
 mod action;
+mod archive;
+mod audit_event;
 pub mod category;
 mod crate_owner_invitation;
 pub mod dependency;
 mod download;
+mod download_anomaly;
+pub mod downloads_partition;
 mod email;
+mod feature_usage;
+mod fingerprint;
 mod follow;
+mod index_sync;
 mod keyword;
 pub mod krate;
+mod moderation;
+mod msrv_stats;
 mod owner;
+mod publish_diagnostics;
+mod publish_warnings;
+mod repository_check;
+pub mod repository_verification;
+mod reproducibility;
 mod rights;
+mod scan_report;
+mod security_yank;
+mod subscription;
+pub mod support_window;
 mod team;
 pub mod token;
+mod tombstone;
+mod unavailable;
 pub mod user;
 mod version;
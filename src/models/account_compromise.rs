@@ -0,0 +1,79 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::account_compromises;
+use crate::util::errors::{account_compromised, AppResult};
+
+/// An account that was put into compromise mode by the crates.io team.
+///
+/// New publishes from the account are held until the compromise is resolved,
+/// and the account can't log in, create API tokens or add crate owners.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(table_name = account_compromises, belongs_to(User))]
+pub struct AccountCompromise {
+    pub id: i32,
+    pub user_id: i32,
+    pub admin_id: i32,
+    pub reason: String,
+    pub revoked_tokens: i32,
+    pub flagged_versions: i32,
+    pub created_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+impl AccountCompromise {
+    /// Returns the unresolved compromise of the user, if any.
+    pub fn active(user_id: i32, conn: &mut PgConnection) -> QueryResult<Option<Self>> {
+        account_compromises::table
+            .filter(account_compromises::user_id.eq(user_id))
+            .filter(account_compromises::resolved_at.is_null())
+            .first(conn)
+            .optional()
+    }
+
+    pub fn is_active(user_id: i32, conn: &mut PgConnection) -> QueryResult<bool> {
+        let query = account_compromises::table
+            .filter(account_compromises::user_id.eq(user_id))
+            .filter(account_compromises::resolved_at.is_null());
+
+        diesel::select(diesel::dsl::exists(query)).get_result(conn)
+    }
+
+    /// Returns an error if the account is in compromise mode, so that an
+    /// attacker can't regain access to it or hand its crates to another
+    /// account before the compromise is resolved.
+    pub fn ensure_not_active(user_id: i32, conn: &mut PgConnection) -> AppResult<()> {
+        if Self::is_active(user_id, conn)? {
+            return Err(account_compromised());
+        }
+
+        Ok(())
+    }
+
+    /// Marks the compromise as resolved, which allows the account to publish
+    /// again.
+    pub fn resolve(&self, conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::update(self)
+            .set(account_compromises::resolved_at.eq(diesel::dsl::now.nullable()))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = account_compromises, check_for_backend(diesel::pg::Pg))]
+pub struct NewAccountCompromise<'a> {
+    pub user_id: i32,
+    pub admin_id: i32,
+    pub reason: &'a str,
+    pub revoked_tokens: i32,
+    pub flagged_versions: i32,
+}
+
+impl NewAccountCompromise<'_> {
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<AccountCompromise> {
+        diesel::insert_into(account_compromises::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
@@ -45,6 +45,8 @@ pub struct VersionOwnerAction {
     pub api_token_id: Option<i32>,
     pub action: VersionAction,
     pub time: NaiveDateTime,
+    /// Assigned when the action is committed, see the `seq` column.
+    pub seq: Option<i64>,
 }
 
 impl VersionOwnerAction {
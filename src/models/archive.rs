@@ -0,0 +1,153 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Text};
+
+use crate::models::Version;
+use crate::schema::version_archives;
+
+/// A version whose crate file was moved to the archive storage tier.
+///
+/// Crate files in the archive tier are not available via the CDN. They are
+/// moved back to the regular storage location once they are requested again.
+///
+/// The crate file is copied to the archive tier first, and only deleted from
+/// its regular location some time after the version was recorded as
+/// archived, see `worker::archive`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = version_archives,
+    primary_key(version_id),
+    belongs_to(Version),
+)]
+pub struct VersionArchive {
+    pub version_id: i32,
+    pub archived_at: NaiveDateTime,
+    pub restore_requested_at: Option<NaiveDateTime>,
+    pub original_deleted_at: Option<NaiveDateTime>,
+}
+
+/// A version that is eligible to be moved to the archive storage tier.
+#[derive(Debug, QueryableByName)]
+pub struct ArchiveCandidate {
+    #[diesel(sql_type = Integer)]
+    pub version_id: i32,
+    #[diesel(sql_type = Text)]
+    pub crate_name: String,
+    #[diesel(sql_type = Text)]
+    pub num: String,
+}
+
+impl VersionArchive {
+    /// Returns the archive state of a version, or `None` if the crate file of
+    /// the version is in the regular storage tier.
+    pub fn for_version(version: &Version, conn: &mut PgConnection) -> QueryResult<Option<Self>> {
+        Self::belonging_to(version).first(conn).optional()
+    }
+
+    /// Returns up to `limit` versions of crates whose versions are all
+    /// yanked and that have not been downloaded for at least `idle_days` days.
+    pub fn candidates(
+        idle_days: i32,
+        limit: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<ArchiveCandidate>> {
+        diesel::sql_query(include_str!("version_archive_candidates.sql"))
+            .bind::<Integer, _>(idle_days)
+            .bind::<BigInt, _>(limit)
+            .load(conn)
+    }
+
+    /// Records that the crate file of a version was copied to the archive
+    /// storage tier.
+    pub fn record(version_id_: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        use crate::schema::version_archives::dsl::*;
+
+        diesel::insert_into(version_archives)
+            .values(version_id.eq(version_id_))
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+
+    /// Returns up to `limit` archived versions whose crate files are still
+    /// stored at their regular location, although they were archived more
+    /// than `delay_minutes` minutes ago and no restore was requested since.
+    pub fn pending_original_deletions(
+        delay_minutes: i32,
+        limit: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<(i32, String, String)>> {
+        use crate::schema::{crates, versions};
+        use diesel::dsl::{now, IntervalDsl};
+
+        version_archives::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .filter(version_archives::original_deleted_at.is_null())
+            .filter(version_archives::restore_requested_at.is_null())
+            .filter(version_archives::archived_at.lt(now - delay_minutes.minutes()))
+            .select((version_archives::version_id, crates::name, versions::num))
+            .order(version_archives::version_id)
+            .limit(limit)
+            .load(conn)
+    }
+
+    /// Locks the archive state of a version until the end of the current
+    /// transaction, so that no restore can be requested while the crate file
+    /// is deleted from its regular location.
+    ///
+    /// Returns `false` if a restore was requested in the meantime, or if the
+    /// crate file was deleted already.
+    pub fn lock_for_original_deletion(
+        version_id_: i32,
+        conn: &mut PgConnection,
+    ) -> QueryResult<bool> {
+        use crate::schema::version_archives::dsl::*;
+
+        let locked = version_archives
+            .find(version_id_)
+            .filter(original_deleted_at.is_null())
+            .filter(restore_requested_at.is_null())
+            .select(version_id)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()?;
+
+        Ok(locked.is_some())
+    }
+
+    /// Records that the crate file of an archived version was deleted from
+    /// its regular location.
+    pub fn record_original_deletion(
+        version_id_: i32,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::version_archives::dsl::*;
+        use diesel::dsl::now;
+
+        diesel::update(version_archives.find(version_id_))
+            .set(original_deleted_at.eq(now.nullable()))
+            .execute(conn)
+    }
+
+    /// Marks the crate file of a version to be restored from the archive
+    /// storage tier.
+    ///
+    /// Returns `false` if a restore was already requested before, or if the
+    /// version is not archived.
+    pub fn request_restore(version_id_: i32, conn: &mut PgConnection) -> QueryResult<bool> {
+        use crate::schema::version_archives::dsl::*;
+        use diesel::dsl::now;
+
+        let updated = diesel::update(version_archives.find(version_id_))
+            .filter(restore_requested_at.is_null())
+            .set(restore_requested_at.eq(now.nullable()))
+            .execute(conn)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Records that the crate file of a version was moved back to the
+    /// regular storage tier.
+    pub fn remove(version_id_: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(version_archives::table.find(version_id_)).execute(conn)
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::schema::{audit_events, users};
+
+/// A security relevant event, like a login, an anomalous use of an API token
+/// or an action of the crates.io team.
+///
+/// Events are shipped to an external audit sink by the `export_audit_events`
+/// background job.
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = audit_events)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub kind: String,
+    pub user_id: Option<i32>,
+    pub api_token_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub details: Value,
+    pub created_at: NaiveDateTime,
+    pub exported_at: Option<NaiveDateTime>,
+}
+
+impl AuditEvent {
+    /// Returns the oldest events that have not been shipped to the audit sink
+    /// yet, together with the GitHub login of their user.
+    pub fn unexported(
+        limit: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<(Self, Option<String>)>> {
+        audit_events::table
+            .left_join(users::table)
+            .filter(audit_events::exported_at.is_null())
+            .select((audit_events::all_columns, users::gh_login.nullable()))
+            .order(audit_events::id)
+            .limit(limit)
+            .load(conn)
+    }
+
+    pub fn mark_exported(ids: &[i64], conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::update(audit_events::table.filter(audit_events::id.eq_any(ids)))
+            .set(audit_events::exported_at.eq(diesel::dsl::now.nullable()))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = audit_events, check_for_backend(diesel::pg::Pg))]
+pub struct NewAuditEvent<'a> {
+    pub kind: &'a str,
+    pub user_id: Option<i32>,
+    pub api_token_id: Option<i32>,
+    pub ip_address: Option<&'a str>,
+    pub details: Value,
+}
+
+impl<'a> NewAuditEvent<'a> {
+    pub fn new(kind: &'a str) -> Self {
+        Self {
+            kind,
+            user_id: None,
+            api_token_id: None,
+            ip_address: None,
+            details: Value::Object(Default::default()),
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<()> {
+        diesel::insert_into(audit_events::table)
+            .values(self)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Inserts the event without failing the surrounding operation.
+    ///
+    /// The event is inserted within a savepoint, so that a failure (e.g. on
+    /// a read-only replica connection) does not abort an outer transaction.
+    /// Errors are logged instead of returned.
+    pub fn record(&self, conn: &mut PgConnection) {
+        if let Err(error) = conn.transaction(|conn| self.insert(conn)) {
+            warn!(kind = %self.kind, %error, "Failed to record audit event");
+        }
+    }
+}
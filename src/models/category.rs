@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{self, *};
 
 use crate::models::Crate;
@@ -13,6 +13,7 @@ pub struct Category {
     pub description: String,
     pub crates_cnt: i32,
     pub created_at: NaiveDateTime,
+    pub deprecated_at: Option<NaiveDateTime>,
 }
 
 type WithSlug<'a> = diesel::dsl::Eq<categories::slug, crate::sql::lower::HelperType<&'a str>>;
@@ -40,25 +41,59 @@ impl Category {
         categories::table.filter(Self::with_slug(slug))
     }
 
+    /// Returns the category that a slug of a renamed or merged category now
+    /// points to.
+    pub fn by_old_slug(slug: &str, conn: &mut PgConnection) -> QueryResult<Option<Category>> {
+        category_redirects::table
+            .inner_join(categories::table)
+            .filter(category_redirects::old_slug.eq(slug.to_lowercase()))
+            .select(categories::all_columns)
+            .first(conn)
+            .optional()
+    }
+
+    /// Replaces the categories of the crate with the given categories.
+    ///
+    /// Slugs of renamed or merged categories are resolved to the category
+    /// they now point to. Slugs that don't exist and slugs of deprecated
+    /// categories are skipped, and returned to the caller.
     pub fn update_crate(
         conn: &mut PgConnection,
         krate: &Crate,
         slugs: &[&str],
     ) -> QueryResult<Vec<String>> {
         conn.transaction(|conn| {
-            let categories: Vec<Category> = categories::table
+            let mut categories: Vec<(String, Category)> = categories::table
                 .filter(categories::slug.eq_any(slugs))
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.slug.clone(), c))
+                .collect();
+
+            let redirected: Vec<(String, Category)> = category_redirects::table
+                .inner_join(categories::table)
+                .filter(category_redirects::old_slug.eq_any(slugs))
+                .select((category_redirects::old_slug, categories::all_columns))
                 .load(conn)?;
+
+            categories.extend(redirected);
+            categories.retain(|(_, c)| c.deprecated_at.is_none());
+
             let invalid_categories = slugs
                 .iter()
                 .cloned()
-                .filter(|s| !categories.iter().any(|c| c.slug == *s))
+                .filter(|s| !categories.iter().any(|(slug, _)| slug == s))
                 .map(ToString::to_string)
                 .collect();
-            let crate_categories = categories
-                .iter()
-                .map(|c| CrateCategory {
-                    category_id: c.id,
+
+            let mut category_ids = categories.iter().map(|(_, c)| c.id).collect::<Vec<_>>();
+            category_ids.sort_unstable();
+            category_ids.dedup();
+
+            let crate_categories = category_ids
+                .into_iter()
+                .map(|category_id| CrateCategory {
+                    category_id,
                     crate_id: krate.id,
                 })
                 .collect::<Vec<_>>();
@@ -122,8 +157,8 @@ impl Category {
     }
 }
 
-/// Struct for inserting categories; only used in tests. Actual categories are inserted
-/// in src/boot/categories.rs.
+/// Struct for inserting categories. The initial set of categories is inserted by
+/// src/boot/categories.rs, later ones through the admin endpoints of the API.
 #[derive(Insertable, AsChangeset, Default, Debug)]
 #[diesel(table_name = categories, check_for_backend(diesel::pg::Pg))]
 pub struct NewCategory<'a> {
@@ -144,6 +179,142 @@ impl<'a> NewCategory<'a> {
             .set(self)
             .get_result(conn)
     }
+
+    /// Inserts a new category into the database.
+    ///
+    /// If the slug was previously used by a renamed or merged category, the
+    /// redirect of that slug is removed.
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<Category> {
+        conn.transaction(|conn| {
+            remove_redirect(self.slug, conn)?;
+            insert_into(categories::table).values(self).get_result(conn)
+        })
+    }
+}
+
+impl Category {
+    /// Returns this category and all of its subcategories, ordered by slug.
+    pub fn with_subcategories(&self, conn: &mut PgConnection) -> QueryResult<Vec<Category>> {
+        categories::table
+            .filter(
+                categories::slug
+                    .eq(&self.slug)
+                    .or(categories::slug.like(format!("{}::%", self.slug))),
+            )
+            .order(categories::slug)
+            .load(conn)
+    }
+
+    /// Changes the slug and the full name of this category, and moves its
+    /// subcategories along with it. The previous slugs are kept as redirects.
+    ///
+    /// The caller is responsible for checking that the new slugs are not in use
+    /// and that the new parent category, if any, exists.
+    pub fn rename(&self, slug: &str, name: &str, conn: &mut PgConnection) -> QueryResult<Category> {
+        conn.transaction(|conn| {
+            for category in self.with_subcategories(conn)? {
+                let slug_suffix = category.slug.strip_prefix(&self.slug).unwrap_or_default();
+                let name_suffix = category
+                    .category
+                    .strip_prefix(&self.category)
+                    .unwrap_or_default();
+
+                let new_slug = format!("{slug}{slug_suffix}");
+                let new_name = format!("{name}{name_suffix}");
+
+                if new_slug != category.slug {
+                    remove_redirect(&new_slug, conn)?;
+                    add_redirect(&category.slug, category.id, conn)?;
+                }
+
+                update(&category)
+                    .set((
+                        categories::slug.eq(new_slug),
+                        categories::category.eq(new_name),
+                    ))
+                    .execute(conn)?;
+            }
+
+            categories::table.find(self.id).first(conn)
+        })
+    }
+
+    /// Marks the category as deprecated, or removes the mark again.
+    ///
+    /// Crates keep their deprecated categories, but new versions can no longer
+    /// add them.
+    pub fn set_deprecated(
+        &self,
+        deprecated: bool,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Category> {
+        let deprecated_at = if deprecated {
+            Some(self.deprecated_at.unwrap_or_else(|| Utc::now().naive_utc()))
+        } else {
+            None
+        };
+
+        update(self)
+            .set(categories::deprecated_at.eq(deprecated_at))
+            .get_result(conn)
+    }
+
+    /// Moves the crates of this category into the `target` category and
+    /// deletes this category. The slug of this category, and all slugs that
+    /// redirected to it, redirect to the `target` category afterwards.
+    ///
+    /// The caller is responsible for checking that this category has no
+    /// subcategories.
+    pub fn merge_into(&self, target: &Category, conn: &mut PgConnection) -> QueryResult<()> {
+        conn.transaction(|conn| {
+            let crate_categories = CrateCategory::belonging_to(self)
+                .select(crates_categories::crate_id)
+                .load::<i32>(conn)?
+                .into_iter()
+                .map(|crate_id| CrateCategory {
+                    crate_id,
+                    category_id: target.id,
+                })
+                .collect::<Vec<_>>();
+
+            insert_into(crates_categories::table)
+                .values(&crate_categories)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            delete(CrateCategory::belonging_to(self)).execute(conn)?;
+
+            update(category_redirects::table)
+                .filter(category_redirects::category_id.eq(self.id))
+                .set(category_redirects::category_id.eq(target.id))
+                .execute(conn)?;
+
+            add_redirect(&self.slug, target.id, conn)?;
+
+            delete(self).execute(conn)?;
+
+            Ok(())
+        })
+    }
+}
+
+fn add_redirect(old_slug: &str, category_id: i32, conn: &mut PgConnection) -> QueryResult<()> {
+    insert_into(category_redirects::table)
+        .values((
+            category_redirects::old_slug.eq(old_slug),
+            category_redirects::category_id.eq(category_id),
+        ))
+        .on_conflict(category_redirects::old_slug)
+        .do_update()
+        .set(category_redirects::category_id.eq(category_id))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn remove_redirect(old_slug: &str, conn: &mut PgConnection) -> QueryResult<()> {
+    delete(category_redirects::table.find(old_slug)).execute(conn)?;
+    Ok(())
 }
 
 #[cfg(test)]
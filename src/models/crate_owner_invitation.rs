@@ -1,10 +1,12 @@
 use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::now;
 use diesel::prelude::*;
 
 use crate::config;
 use crate::models::{CrateOwner, OwnerKind};
 use crate::schema::{crate_owner_invitations, crate_owners, crates};
-use crate::util::errors::{AppResult, OwnershipInvitationExpired};
+use crate::sql::random_string;
+use crate::util::errors::{bad_request, AppResult, OwnershipInvitationExpired};
 
 #[derive(Debug)]
 pub enum NewCrateOwnerInvitationOutcome {
@@ -22,6 +24,7 @@ pub struct CrateOwnerInvitation {
     pub created_at: NaiveDateTime,
     pub token: String,
     pub token_created_at: Option<NaiveDateTime>,
+    pub expiry_notified_at: Option<NaiveDateTime>,
 }
 
 impl CrateOwnerInvitation {
@@ -122,6 +125,34 @@ impl CrateOwnerInvitation {
         })
     }
 
+    /// Renews an expired invitation on behalf of `invited_by_user_id`.
+    ///
+    /// The invitation gets a new token and its expiration period starts over.
+    /// Returns the new plaintext token.
+    pub fn renew(
+        self,
+        invited_by_user_id: i32,
+        conn: &mut PgConnection,
+        config: &config::Server,
+    ) -> AppResult<String> {
+        if !self.is_expired(config) {
+            return Err(bad_request("the invitation has not expired yet"));
+        }
+
+        let token = diesel::update(&self)
+            .set((
+                crate_owner_invitations::invited_by_user_id.eq(invited_by_user_id),
+                crate_owner_invitations::created_at.eq(now),
+                crate_owner_invitations::token.eq(random_string(26)),
+                crate_owner_invitations::token_generated_at.eq(now.nullable()),
+                crate_owner_invitations::expiry_notified_at.eq(None::<NaiveDateTime>),
+            ))
+            .returning(crate_owner_invitations::token)
+            .get_result(conn)?;
+
+        Ok(token)
+    }
+
     pub fn decline(self, conn: &mut PgConnection) -> AppResult<()> {
         // The check to prevent declining expired invitations is *explicitly* missing. We do not
         // care if an expired invitation is declined, as that just removes the invitation from the
@@ -1,4 +1,6 @@
+use diesel::prelude::*;
 use diesel::sql_types::{Integer, Text};
+use std::collections::{BTreeSet, HashMap};
 
 use crate::models::{Crate, Version};
 use crate::schema::*;
@@ -23,6 +25,134 @@ pub struct Dependency {
     pub target: Option<String>,
     pub kind: DependencyKind,
     pub explicit_name: Option<String>,
+    pub requirement_id: Option<i32>,
+}
+
+/// A dependency of a newly published version.
+///
+/// Dependencies of a version are only ever inserted all at once, so there is
+/// no way to insert a single one of them.
+#[derive(Insertable, Debug)]
+#[diesel(table_name = dependencies, check_for_backend(diesel::pg::Pg))]
+pub struct NewDependency<'a> {
+    pub version_id: i32,
+    pub crate_id: i32,
+    pub req: &'a str,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<&'a str>,
+    pub target: Option<&'a str>,
+    /// `None` uses the default kind of the `dependencies` table.
+    pub kind: Option<DependencyKind>,
+    pub explicit_name: Option<&'a str>,
+}
+
+impl NewDependency<'_> {
+    /// Inserts all dependencies of a version with a single `INSERT` statement.
+    ///
+    /// The requirement strings are interned in the `dependency_requirements`
+    /// table first, and referenced by the inserted dependencies.
+    pub fn insert_all(deps: &[Self], conn: &mut PgConnection) -> QueryResult<usize> {
+        if deps.is_empty() {
+            return Ok(0);
+        }
+
+        let reqs = deps.iter().map(|dep| dep.req).collect::<BTreeSet<_>>();
+        let requirement_ids = intern_requirements(&reqs, conn)?;
+
+        let rows = deps
+            .iter()
+            .map(|dep| {
+                let requirement_id = requirement_ids[dep.req];
+                (dep, dependencies::requirement_id.eq(requirement_id))
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(dependencies::table)
+            .values(rows)
+            .execute(conn)
+    }
+}
+
+/// Returns the ids of the given requirement strings in the
+/// `dependency_requirements` table, inserting the ones that don't exist yet.
+///
+/// The requirements are inserted in a fixed order, so that concurrent
+/// publishes with overlapping requirements can't deadlock.
+fn intern_requirements(
+    reqs: &BTreeSet<&str>,
+    conn: &mut PgConnection,
+) -> QueryResult<HashMap<String, i32>> {
+    let new_requirements = reqs
+        .iter()
+        .map(|req| dependency_requirements::req.eq(*req))
+        .collect::<Vec<_>>();
+
+    diesel::insert_into(dependency_requirements::table)
+        .values(new_requirements)
+        .on_conflict(dependency_requirements::req)
+        .do_nothing()
+        .execute(conn)?;
+
+    dependency_requirements::table
+        .filter(dependency_requirements::req.eq_any(reqs))
+        .select((dependency_requirements::req, dependency_requirements::id))
+        .load::<(String, i32)>(conn)
+        .map(|rows| rows.into_iter().collect())
+}
+
+/// A dependency on a crate that is hosted on another registry.
+///
+/// These can't be stored in the `dependencies` table, since the crate does
+/// not exist on this registry.
+#[derive(Identifiable, Associations, Debug, Queryable)]
+#[diesel(
+    table_name = cross_registry_dependencies,
+    check_for_backend(diesel::pg::Pg),
+    belongs_to(Version),
+)]
+pub struct CrossRegistryDependency {
+    pub id: i32,
+    pub version_id: i32,
+    pub registry: String,
+    pub name: String,
+    pub req: String,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    pub target: Option<String>,
+    pub kind: DependencyKind,
+    pub explicit_name: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = cross_registry_dependencies, check_for_backend(diesel::pg::Pg))]
+pub struct NewCrossRegistryDependency<'a> {
+    pub version_id: i32,
+    pub registry: &'a str,
+    pub name: &'a str,
+    pub req: &'a str,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<&'a str>,
+    pub target: Option<&'a str>,
+    /// `None` uses the default kind of the `cross_registry_dependencies` table.
+    pub kind: Option<DependencyKind>,
+    pub explicit_name: Option<&'a str>,
+}
+
+impl NewCrossRegistryDependency<'_> {
+    /// Inserts all cross-registry dependencies of a version with a single
+    /// `INSERT` statement.
+    pub fn insert_all(deps: &[Self], conn: &mut PgConnection) -> QueryResult<usize> {
+        if deps.is_empty() {
+            return Ok(0);
+        }
+
+        diesel::insert_into(cross_registry_dependencies::table)
+            .values(deps)
+            .execute(conn)
+    }
 }
 
 #[derive(Debug, QueryableByName)]
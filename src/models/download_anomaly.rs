@@ -0,0 +1,137 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Date, Double, Integer};
+
+use crate::models::Crate;
+use crate::schema::{crates, download_anomalies};
+
+/// A day on which a crate was downloaded unusually often compared to its
+/// baseline.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = download_anomalies,
+    primary_key(crate_id, date),
+    belongs_to(Crate),
+)]
+pub struct DownloadAnomaly {
+    pub crate_id: i32,
+    pub date: NaiveDate,
+    pub downloads: i64,
+    pub baseline_mean: f64,
+    pub baseline_stddev: f64,
+    pub excluded_downloads: i64,
+    pub created_at: NaiveDateTime,
+}
+
+/// The downloads of a crate on a single day, compared to its baseline.
+#[derive(Debug, Clone, QueryableByName)]
+pub struct DownloadBaseline {
+    #[diesel(sql_type = Integer)]
+    pub crate_id: i32,
+    #[diesel(sql_type = BigInt)]
+    pub downloads: i64,
+    #[diesel(sql_type = Double)]
+    pub baseline_mean: f64,
+    #[diesel(sql_type = Double)]
+    pub baseline_stddev: f64,
+}
+
+impl DownloadBaseline {
+    /// Returns the downloads of all crates that were downloaded at least
+    /// `min_downloads` times on `date`, together with the average and
+    /// standard deviation of their daily downloads in the `baseline_days`
+    /// days before.
+    ///
+    /// Crates that are younger than `baseline_days` or that were not
+    /// downloaded at all in that period are skipped, since there is nothing
+    /// to compare their downloads with.
+    pub fn for_date(
+        date: NaiveDate,
+        baseline_days: i32,
+        min_downloads: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<Self>> {
+        diesel::sql_query(include_str!("download_baselines.sql"))
+            .bind::<Date, _>(date)
+            .bind::<Integer, _>(baseline_days)
+            .bind::<BigInt, _>(min_downloads)
+            .load(conn)
+    }
+
+    /// Returns how many standard deviations the downloads are above the
+    /// baseline.
+    ///
+    /// The standard deviation is at least the square root of the mean, which
+    /// is the expected noise of random downloads, so that crates with very
+    /// steady downloads are not flagged for small increases.
+    pub fn score(&self) -> f64 {
+        let stddev = self.baseline_stddev.max(self.baseline_mean.sqrt()).max(1.0);
+
+        (self.downloads as f64 - self.baseline_mean) / stddev
+    }
+}
+
+impl DownloadAnomaly {
+    /// Records an anomaly, unless it was already recorded before.
+    ///
+    /// Returns `false` if the anomaly was already recorded.
+    pub fn record(
+        baseline: &DownloadBaseline,
+        date_: NaiveDate,
+        excluded_downloads_: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<bool> {
+        use crate::schema::download_anomalies::dsl::*;
+
+        let inserted = diesel::insert_into(download_anomalies)
+            .values((
+                crate_id.eq(baseline.crate_id),
+                date.eq(date_),
+                downloads.eq(baseline.downloads),
+                baseline_mean.eq(baseline.baseline_mean),
+                baseline_stddev.eq(baseline.baseline_stddev),
+                excluded_downloads.eq(excluded_downloads_),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Returns all anomalies since `since`, together with the name of the
+    /// crate, most recent first.
+    pub fn since(since: NaiveDate, conn: &mut PgConnection) -> QueryResult<Vec<(Self, String)>> {
+        download_anomalies::table
+            .inner_join(crates::table)
+            .filter(download_anomalies::date.ge(since))
+            .select((download_anomalies::all_columns, crates::name))
+            .order((
+                download_anomalies::date.desc(),
+                download_anomalies::downloads.desc(),
+            ))
+            .load(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline(downloads: i64, mean: f64, stddev: f64) -> DownloadBaseline {
+        DownloadBaseline {
+            crate_id: 1,
+            downloads,
+            baseline_mean: mean,
+            baseline_stddev: stddev,
+        }
+    }
+
+    #[test]
+    fn score() {
+        assert_eq!(baseline(500, 20.0, 10.0).score(), 48.0);
+        assert_eq!(baseline(100, 100.0, 10.0).score(), 0.0);
+
+        // Steady downloads are compared with the expected noise
+        assert_eq!(baseline(10_100, 10_000.0, 0.0).score(), 1.0);
+    }
+}
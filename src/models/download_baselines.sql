@@ -0,0 +1,32 @@
+-- The downloads of all crates that were downloaded at least `$3` times on
+-- day `$1`, together with the average and standard deviation of their daily
+-- downloads in the `$2` days before. Days without downloads count as zero.
+--
+-- Crates that were created within the baseline period or that have no
+-- downloads in it are skipped, since their baseline would be zero and all of
+-- their downloads would look anomalous.
+WITH daily_downloads AS (
+    SELECT versions.crate_id, version_downloads.date, SUM(version_downloads.downloads) AS downloads
+    FROM version_downloads
+    INNER JOIN versions ON versions.id = version_downloads.version_id
+    WHERE version_downloads.date BETWEEN $1 - $2 AND $1
+    GROUP BY versions.crate_id, version_downloads.date
+), baselines AS (
+    SELECT crate_id,
+        SUM(downloads)::float8 / $2 AS mean,
+        SUM(downloads::float8 * downloads::float8) / $2 AS mean_of_squares
+    FROM daily_downloads
+    WHERE date < $1
+    GROUP BY crate_id
+)
+SELECT daily_downloads.crate_id,
+    daily_downloads.downloads::int8 AS downloads,
+    baselines.mean AS baseline_mean,
+    SQRT(GREATEST(baselines.mean_of_squares - baselines.mean * baselines.mean, 0)) AS baseline_stddev
+FROM daily_downloads
+INNER JOIN baselines ON baselines.crate_id = daily_downloads.crate_id
+INNER JOIN crates ON crates.id = daily_downloads.crate_id
+WHERE daily_downloads.date = $1
+    AND daily_downloads.downloads >= $3
+    AND crates.created_at < $1 - $2
+ORDER BY daily_downloads.crate_id;
@@ -0,0 +1,240 @@
+use chrono::{Datelike, Months, NaiveDate};
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+
+use crate::models::VersionDownload;
+use crate::schema::version_downloads;
+
+/// A partition of the `version_downloads` table.
+///
+/// The table is partitioned by month, with one partition per month
+/// (`version_downloads_2023_10`) except for `version_downloads_legacy`, which
+/// covers all dates before the table was partitioned.
+///
+/// Downloads for dates without a monthly partition are stored in the
+/// [`DEFAULT_PARTITION`], which is not listed as a partition itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadsPartition {
+    pub name: String,
+    /// First date of the partition, or `None` if it covers all earlier dates.
+    pub from: Option<NaiveDate>,
+    /// First date after the partition.
+    pub to: NaiveDate,
+}
+
+/// Name of the partition that stores the downloads of dates that are not
+/// covered by any monthly partition.
+pub const DEFAULT_PARTITION: &str = "version_downloads_default";
+
+impl DownloadsPartition {
+    /// Returns all partitions of the `version_downloads` table, ordered by
+    /// date.
+    pub fn all(conn: &mut PgConnection) -> QueryResult<Vec<Self>> {
+        #[derive(QueryableByName)]
+        struct Partition {
+            #[diesel(sql_type = Text)]
+            name: String,
+            #[diesel(sql_type = Text)]
+            bound: String,
+        }
+
+        let partitions: Vec<Partition> = diesel::sql_query(
+            "SELECT child.relname::text AS name, pg_get_expr(child.relpartbound, child.oid) AS bound \
+             FROM pg_inherits \
+             JOIN pg_class parent ON parent.oid = pg_inherits.inhparent \
+             JOIN pg_class child ON child.oid = pg_inherits.inhrelid \
+             WHERE parent.relname = 'version_downloads'",
+        )
+        .load(conn)?;
+
+        let mut partitions = partitions
+            .into_iter()
+            .filter(|partition| partition.bound != "DEFAULT")
+            .filter_map(|partition| {
+                let Some((from, to)) = parse_bound(&partition.bound) else {
+                    warn!(name = %partition.name, bound = %partition.bound, "Unexpected partition bound");
+                    return None;
+                };
+
+                Some(Self {
+                    name: partition.name,
+                    from,
+                    to,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        partitions.sort_by_key(|partition| partition.to);
+        Ok(partitions)
+    }
+
+    /// Creates the partition for the month of `month`.
+    ///
+    /// Downloads of the month that were already stored in the
+    /// [`DEFAULT_PARTITION`] are moved to the new partition, since the
+    /// partition could not be attached otherwise.
+    pub fn create(month: NaiveDate, conn: &mut PgConnection) -> QueryResult<Self> {
+        let from = first_of_month(month);
+        let to = from + Months::new(1);
+        let partition = Self {
+            name: format!("version_downloads_{}", from.format("%Y_%m")),
+            from: Some(from),
+            to,
+        };
+
+        let name = partition.quoted_name();
+        conn.transaction(|conn| {
+            diesel::sql_query(format!(
+                "CREATE TABLE {name} (LIKE version_downloads INCLUDING DEFAULTS)"
+            ))
+            .execute(conn)?;
+
+            let moved = diesel::sql_query(format!(
+                "WITH moved AS ( \
+                     DELETE FROM {DEFAULT_PARTITION} WHERE date >= '{from}' AND date < '{to}' \
+                     RETURNING version_id, downloads, counted, date, processed \
+                 ) \
+                 INSERT INTO {name} (version_id, downloads, counted, date, processed) \
+                 SELECT version_id, downloads, counted, date, processed FROM moved"
+            ))
+            .execute(conn)?;
+            if moved > 0 {
+                warn!(name = %partition.name, moved, "Moved downloads out of the default partition");
+            }
+
+            diesel::sql_query(format!(
+                "ALTER TABLE version_downloads ATTACH PARTITION {name} FOR VALUES FROM ('{from}') TO ('{to}')"
+            ))
+            .execute(conn)?;
+
+            Ok(partition)
+        })
+    }
+
+    /// Returns the first day with downloads in the partition, if any.
+    pub fn first_date(&self, conn: &mut PgConnection) -> QueryResult<Option<NaiveDate>> {
+        self.query()
+            .select(diesel::dsl::min(version_downloads::date))
+            .get_result(conn)
+    }
+
+    /// Returns whether the partition contains downloads that were not added
+    /// to the download counts of the versions and crates yet.
+    pub fn has_unprocessed_downloads(&self, conn: &mut PgConnection) -> QueryResult<bool> {
+        let unprocessed = self.query().filter(version_downloads::processed.eq(false));
+        diesel::select(diesel::dsl::exists(unprocessed)).get_result(conn)
+    }
+
+    /// Returns the downloads of a single day of the partition.
+    pub fn downloads_on(
+        &self,
+        date: NaiveDate,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<VersionDownload>> {
+        self.query()
+            .filter(version_downloads::date.eq(date))
+            .order(version_downloads::version_id)
+            .load(conn)
+    }
+
+    /// Detaches the partition from the `version_downloads` table, and drops
+    /// it.
+    ///
+    /// The downloads of each version in the partition are added to the
+    /// `archived_version_downloads` table first, so that the download counts
+    /// can still be recomputed from the remaining daily rows.
+    pub fn detach_and_drop(&self, conn: &mut PgConnection) -> QueryResult<()> {
+        let name = self.quoted_name();
+        conn.transaction(|conn| {
+            diesel::sql_query(format!(
+                "INSERT INTO archived_version_downloads (version_id, downloads, counted) \
+                 SELECT version_id, SUM(downloads), SUM(counted) FROM {name} GROUP BY version_id \
+                 ON CONFLICT (version_id) DO UPDATE SET \
+                     downloads = archived_version_downloads.downloads + EXCLUDED.downloads, \
+                     counted = archived_version_downloads.counted + EXCLUDED.counted"
+            ))
+            .execute(conn)?;
+
+            diesel::sql_query(format!(
+                "ALTER TABLE version_downloads DETACH PARTITION {name}"
+            ))
+            .execute(conn)?;
+            diesel::sql_query(format!("DROP TABLE {name}")).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    fn query(&self) -> version_downloads::BoxedQuery<'static, diesel::pg::Pg> {
+        let mut query = version_downloads::table
+            .filter(version_downloads::date.lt(self.to))
+            .into_boxed();
+
+        if let Some(from) = self.from {
+            query = query.filter(version_downloads::date.ge(from));
+        }
+
+        query
+    }
+
+    fn quoted_name(&self) -> String {
+        format!("\"{}\"", self.name.replace('"', "\"\""))
+    }
+}
+
+/// Returns the first day of the month of `date`.
+pub fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// Parses a range partition bound like
+/// `FOR VALUES FROM ('2023-10-01') TO ('2023-11-01')`, where the lower bound
+/// can also be `MINVALUE`.
+fn parse_bound(bound: &str) -> Option<(Option<NaiveDate>, NaiveDate)> {
+    let bound = bound.strip_prefix("FOR VALUES FROM (")?.strip_suffix(')')?;
+    let (from, to) = bound.split_once(") TO (")?;
+
+    let parse_date = |value: &str| {
+        let value = value.strip_prefix('\'')?.strip_suffix('\'')?;
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+    };
+
+    let from = match from {
+        "MINVALUE" => None,
+        from => Some(parse_date(from)?),
+    };
+
+    Some((from, parse_date(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn partition_bounds() {
+        let bound = "FOR VALUES FROM ('2023-10-01') TO ('2023-11-01')";
+        assert_eq!(
+            parse_bound(bound),
+            Some((Some(date("2023-10-01")), date("2023-11-01")))
+        );
+
+        let bound = "FOR VALUES FROM (MINVALUE) TO ('2023-10-01')";
+        assert_eq!(parse_bound(bound), Some((None, date("2023-10-01"))));
+
+        assert_eq!(
+            parse_bound("FOR VALUES FROM ('2023-10-01') TO (MAXVALUE)"),
+            None
+        );
+        assert_eq!(parse_bound("DEFAULT"), None);
+    }
+
+    #[test]
+    fn first_of_months() {
+        assert_eq!(first_of_month(date("2023-10-17")), date("2023-10-01"));
+        assert_eq!(first_of_month(date("2023-10-01")), date("2023-10-01"));
+    }
+}
@@ -0,0 +1,58 @@
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use diesel::sql_types::Date;
+
+use crate::models::Crate;
+use crate::schema::features_usage;
+
+/// The number of dependent crates that requested a feature of a crate on a
+/// given day.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = features_usage,
+    primary_key(crate_id, feature, date),
+    belongs_to(Crate),
+)]
+pub struct FeatureUsage {
+    pub crate_id: i32,
+    pub feature: String,
+    pub date: NaiveDate,
+    pub dependents: i32,
+}
+
+impl FeatureUsage {
+    /// Records the feature usage of all crates for `date`, replacing any
+    /// snapshot that was already recorded for that day.
+    ///
+    /// Returns the number of recorded rows.
+    pub fn aggregate(date: NaiveDate, conn: &mut PgConnection) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            diesel::delete(features_usage::table.filter(features_usage::date.eq(date)))
+                .execute(conn)?;
+
+            diesel::sql_query(include_str!("features_usage.sql"))
+                .bind::<Date, _>(date)
+                .execute(conn)
+        })
+    }
+
+    /// Deletes all snapshots that were recorded before `cutoff`.
+    ///
+    /// Returns the number of deleted rows.
+    pub fn prune(cutoff: NaiveDate, conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(features_usage::table.filter(features_usage::date.lt(cutoff))).execute(conn)
+    }
+
+    /// Returns the feature usage of a crate since `since`, ordered by feature
+    /// and date.
+    pub fn for_crate(
+        krate: &Crate,
+        since: NaiveDate,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<Self>> {
+        Self::belonging_to(krate)
+            .filter(features_usage::date.ge(since))
+            .order((features_usage::feature, features_usage::date))
+            .load(conn)
+    }
+}
@@ -0,0 +1,27 @@
+-- Records for day `$1` how many dependent crates request each feature of the
+-- crates they depend on. Only the newest non-yanked version of each dependent
+-- crate and its normal and build dependencies are considered. Dependencies
+-- that use the default features count towards the `default` feature.
+WITH newest_versions AS (
+    SELECT DISTINCT ON (crate_id) id, crate_id
+    FROM versions
+    WHERE NOT yanked
+    ORDER BY crate_id, created_at DESC
+), requested_features AS (
+    SELECT dependencies.crate_id, newest_versions.crate_id AS dependent_id, features.feature
+    FROM dependencies
+    INNER JOIN newest_versions ON newest_versions.id = dependencies.version_id
+    CROSS JOIN LATERAL unnest(
+        CASE WHEN dependencies.default_features
+            THEN array_append(dependencies.features, 'default')
+            ELSE dependencies.features
+        END
+    ) AS features(feature)
+    WHERE dependencies.kind IN (0, 1)
+        AND newest_versions.crate_id <> dependencies.crate_id
+)
+INSERT INTO features_usage (crate_id, feature, date, dependents)
+SELECT crate_id, feature, $1, COUNT(DISTINCT dependent_id)
+FROM requested_features
+WHERE feature <> ''
+GROUP BY crate_id, feature;
@@ -0,0 +1,68 @@
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt};
+
+use crate::models::Version;
+use crate::schema::{crates, version_fingerprints};
+
+/// A fingerprint of the source files of a published crate file, see
+/// `crates_io_tarball::ContentFingerprint`.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = version_fingerprints,
+    primary_key(version_id),
+    belongs_to(Version),
+)]
+pub struct VersionFingerprint {
+    pub version_id: i32,
+    pub crate_id: i32,
+    pub hashes: Vec<i64>,
+}
+
+impl VersionFingerprint {
+    pub fn record(
+        version_id_: i32,
+        crate_id_: i32,
+        hashes_: &[i64],
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::version_fingerprints::dsl::*;
+
+        diesel::insert_into(version_fingerprints)
+            .values((
+                version_id.eq(version_id_),
+                crate_id.eq(crate_id_),
+                hashes.eq(hashes_),
+            ))
+            .execute(conn)
+    }
+
+    /// Returns up to `limit` fingerprints of versions of other crates that
+    /// share at least one hash with the given fingerprint, along with the
+    /// names of their crates. The versions sharing the most hashes come
+    /// first, so that the most similar ones are not cut off by the limit.
+    pub fn overlapping(
+        crate_id: i32,
+        hashes: &[i64],
+        limit: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<(Self, String)>> {
+        let shared_hashes = sql::<BigInt>(
+            "(SELECT count(*) FROM unnest(version_fingerprints.hashes) AS hash WHERE hash = ANY(",
+        )
+        .bind::<Array<BigInt>, _>(hashes)
+        .sql("))");
+
+        version_fingerprints::table
+            .inner_join(crates::table)
+            .filter(version_fingerprints::crate_id.ne(crate_id))
+            .filter(version_fingerprints::hashes.overlaps_with(hashes))
+            .select((version_fingerprints::all_columns, crates::name))
+            .order((
+                shared_hashes.desc(),
+                version_fingerprints::version_id.desc(),
+            ))
+            .limit(limit)
+            .load(conn)
+    }
+}
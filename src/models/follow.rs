@@ -1,5 +1,25 @@
-use crate::models::User;
-use crate::schema::follows;
+use chrono::NaiveDateTime;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+
+use crate::models::{OwnerKind, User, VersionAction};
+use crate::schema::{crate_owners, crates, follows, teams, users, version_owner_actions, versions};
+use crate::sql::pg_enum;
+
+pg_enum! {
+    pub enum FollowMode {
+        Releases = 0,
+        AllActivity = 1,
+    }
+}
+
+pg_enum! {
+    pub enum FollowChannel {
+        Feed = 0,
+        Email = 1,
+    }
+}
 
 #[derive(Insertable, Queryable, Identifiable, Associations, Clone, Copy, Debug)]
 #[diesel(
@@ -11,4 +31,198 @@ use crate::schema::follows;
 pub struct Follow {
     pub user_id: i32,
     pub crate_id: i32,
+    pub mode: FollowMode,
+    pub channel: FollowChannel,
+}
+
+impl Follow {
+    pub fn new(user_id: i32, crate_id: i32) -> Self {
+        Self {
+            user_id,
+            crate_id,
+            mode: FollowMode::Releases,
+            channel: FollowChannel::Feed,
+        }
+    }
+
+    /// Inserts the follow, or updates the notification settings if the user
+    /// already follows the crate.
+    pub fn save(&self, conn: &mut PgConnection) -> QueryResult<()> {
+        diesel::insert_into(follows::table)
+            .values(self)
+            .on_conflict(follows::table.primary_key())
+            .do_update()
+            .set((
+                follows::mode.eq(self.mode),
+                follows::channel.eq(self.channel),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+/// Something that happened to a crate that is followed by a user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowActivity {
+    pub time: NaiveDateTime,
+    pub crate_name: String,
+    pub event: FollowEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowEvent {
+    Version { num: String, action: VersionAction },
+    OwnerAdded { login: String },
+    OwnerRemoved { login: String },
+}
+
+/// Returns the IDs of the crates that the user follows with the email
+/// channel, optionally limited to the crates followed with `mode`.
+fn emailed_crate_ids(
+    user_id: i32,
+    mode: Option<FollowMode>,
+) -> follows::BoxedQuery<'static, Pg, Integer> {
+    let mut query = follows::table
+        .filter(follows::user_id.eq(user_id))
+        .filter(follows::channel.eq(FollowChannel::Email))
+        .select(follows::crate_id)
+        .into_boxed();
+
+    if let Some(mode) = mode {
+        query = query.filter(follows::mode.eq(mode));
+    }
+
+    query
+}
+
+/// Returns the activity between `since` and `until` of the crates that the
+/// user follows with the email channel, oldest first.
+///
+/// New releases are included for all of these crates. Yanks, unyanks and
+/// ownership changes are only included for the crates that are followed with
+/// [`FollowMode::AllActivity`].
+pub fn follow_activity(
+    user_id: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<FollowActivity>> {
+    let mut activity = Vec::new();
+
+    let releases: Vec<(NaiveDateTime, String, String)> = versions::table
+        .inner_join(crates::table)
+        .filter(versions::crate_id.eq_any(emailed_crate_ids(user_id, None)))
+        .filter(versions::created_at.gt(since))
+        .filter(versions::created_at.le(until))
+        .select((versions::created_at, crates::name, versions::num))
+        .order(versions::created_at)
+        .limit(limit)
+        .load(conn)?;
+
+    activity.extend(releases.into_iter().map(|(time, crate_name, num)| {
+        let action = VersionAction::Publish;
+        let event = FollowEvent::Version { num, action };
+        FollowActivity {
+            time,
+            crate_name,
+            event,
+        }
+    }));
+
+    let all_activity = Some(FollowMode::AllActivity);
+
+    let yanks: Vec<(NaiveDateTime, String, String, VersionAction)> = version_owner_actions::table
+        .inner_join(versions::table.inner_join(crates::table))
+        .filter(versions::crate_id.eq_any(emailed_crate_ids(user_id, all_activity)))
+        .filter(version_owner_actions::action.ne(VersionAction::Publish))
+        .filter(version_owner_actions::time.gt(since))
+        .filter(version_owner_actions::time.le(until))
+        .select((
+            version_owner_actions::time,
+            crates::name,
+            versions::num,
+            version_owner_actions::action,
+        ))
+        .order(version_owner_actions::time)
+        .limit(limit)
+        .load(conn)?;
+
+    activity.extend(yanks.into_iter().map(|(time, crate_name, num, action)| {
+        let event = FollowEvent::Version { num, action };
+        FollowActivity {
+            time,
+            crate_name,
+            event,
+        }
+    }));
+
+    // Owners are soft-deleted, so removals are recognized by the time of the
+    // last update of a deleted owner. Owners that are added back after they
+    // were removed keep their original `created_at` time and are not listed.
+    let added = crate_owners::deleted
+        .eq(false)
+        .and(crate_owners::created_at.gt(since))
+        .and(crate_owners::created_at.le(until));
+    let removed = crate_owners::deleted
+        .eq(true)
+        .and(crate_owners::updated_at.gt(since))
+        .and(crate_owners::updated_at.le(until));
+
+    let user_owner = crate_owners::owner_id
+        .eq(users::id)
+        .and(crate_owners::owner_kind.eq(OwnerKind::User as i32));
+    let team_owner = crate_owners::owner_id
+        .eq(teams::id)
+        .and(crate_owners::owner_kind.eq(OwnerKind::Team as i32));
+
+    type OwnerChange = (
+        NaiveDateTime,
+        NaiveDateTime,
+        bool,
+        String,
+        Option<String>,
+        Option<String>,
+    );
+
+    let owner_changes: Vec<OwnerChange> = crate_owners::table
+        .inner_join(crates::table)
+        .left_join(users::table.on(user_owner))
+        .left_join(teams::table.on(team_owner))
+        .filter(crate_owners::crate_id.eq_any(emailed_crate_ids(user_id, all_activity)))
+        .filter(added.or(removed))
+        .select((
+            crate_owners::created_at,
+            crate_owners::updated_at,
+            crate_owners::deleted,
+            crates::name,
+            users::gh_login.nullable(),
+            teams::login.nullable(),
+        ))
+        .limit(limit)
+        .load(conn)?;
+
+    for (created_at, updated_at, deleted, crate_name, user_login, team_login) in owner_changes {
+        let Some(login) = user_login.or(team_login) else {
+            continue;
+        };
+
+        let (time, event) = if deleted {
+            (updated_at, FollowEvent::OwnerRemoved { login })
+        } else {
+            (created_at, FollowEvent::OwnerAdded { login })
+        };
+
+        activity.push(FollowActivity {
+            time,
+            crate_name,
+            event,
+        });
+    }
+
+    activity.sort_by_key(|activity| activity.time);
+    activity.truncate(limit as usize);
+
+    Ok(activity)
 }
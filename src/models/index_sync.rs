@@ -0,0 +1,121 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::Crate;
+use crate::schema::{crates, index_sync_times, versions};
+
+/// The indexes that crate metadata is synced to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    Git,
+    Sparse,
+}
+
+impl IndexKind {
+    /// Returns the type of the background job that syncs crates to the index.
+    pub fn job_type(self) -> &'static str {
+        match self {
+            IndexKind::Git => "sync_to_git_index",
+            IndexKind::Sparse => "sync_to_sparse_index",
+        }
+    }
+}
+
+/// When the index file of a crate was last synced to the git and sparse
+/// indexes.
+#[derive(Queryable, Identifiable, Debug, Clone, Copy)]
+#[diesel(table_name = index_sync_times, primary_key(crate_id))]
+pub struct IndexSyncTime {
+    pub crate_id: i32,
+    pub git_synced_at: Option<NaiveDateTime>,
+    pub sparse_synced_at: Option<NaiveDateTime>,
+}
+
+/// The newest version that is visible in the database or in an index.
+#[derive(Queryable, Debug, Clone)]
+pub struct VisibleVersion {
+    pub crate_name: String,
+    pub num: String,
+    pub published_at: NaiveDateTime,
+}
+
+impl IndexSyncTime {
+    pub fn for_crate(crate_id: i32, conn: &mut PgConnection) -> QueryResult<Option<Self>> {
+        index_sync_times::table
+            .find(crate_id)
+            .first(conn)
+            .optional()
+    }
+
+    /// Records that the index file of the crate was synced with the data as
+    /// of `synced_at`. Does nothing if the crate does not exist (anymore).
+    pub fn record(
+        crate_name: &str,
+        kind: IndexKind,
+        synced_at: NaiveDateTime,
+        conn: &mut PgConnection,
+    ) -> QueryResult<()> {
+        let crate_id: Option<i32> = Crate::by_exact_name(crate_name)
+            .select(crates::id)
+            .first(conn)
+            .optional()?;
+
+        let Some(crate_id) = crate_id else {
+            return Ok(());
+        };
+
+        let insert = diesel::insert_into(index_sync_times::table)
+            .values(index_sync_times::crate_id.eq(crate_id))
+            .on_conflict(index_sync_times::crate_id)
+            .do_update();
+
+        match kind {
+            IndexKind::Git => insert
+                .set(index_sync_times::git_synced_at.eq(synced_at))
+                .execute(conn)?,
+            IndexKind::Sparse => insert
+                .set(index_sync_times::sparse_synced_at.eq(synced_at))
+                .execute(conn)?,
+        };
+
+        Ok(())
+    }
+}
+
+/// Returns the most recently published version in the database.
+pub fn newest_version(conn: &mut PgConnection) -> QueryResult<Option<VisibleVersion>> {
+    versions::table
+        .inner_join(crates::table)
+        .select((crates::name, versions::num, versions::created_at))
+        .order(versions::created_at.desc())
+        .first(conn)
+        .optional()
+}
+
+/// Returns the most recently published version that was included in the last
+/// sync of its crate to the given index.
+pub fn newest_synced_version(
+    kind: IndexKind,
+    conn: &mut PgConnection,
+) -> QueryResult<Option<VisibleVersion>> {
+    let query = versions::table
+        .inner_join(crates::table.inner_join(index_sync_times::table))
+        .select((crates::name, versions::num, versions::created_at))
+        .order(versions::created_at.desc())
+        .into_boxed();
+
+    let query = match kind {
+        IndexKind::Git => query.filter(
+            versions::created_at
+                .nullable()
+                .le(index_sync_times::git_synced_at),
+        ),
+        IndexKind::Sparse => query.filter(
+            versions::created_at
+                .nullable()
+                .le(index_sync_times::sparse_synced_at),
+        ),
+    };
+
+    query.first(conn).optional()
+}
@@ -9,10 +9,11 @@ use url::Url;
 
 use crate::app::App;
 use crate::controllers::helpers::pagination::*;
+use crate::email::Emails;
 use crate::models::version::TopVersions;
 use crate::models::{
-    CrateOwner, CrateOwnerInvitation, Dependency, NewCrateOwnerInvitationOutcome, Owner, OwnerKind,
-    ReverseDependency, User, Version,
+    CrateOwner, CrateOwnerInvitation, CrossRegistryDependency, Dependency,
+    NewCrateOwnerInvitationOutcome, Owner, OwnerKind, ReverseDependency, User, Version,
 };
 use crate::util::errors::{cargo_err, AppResult};
 
@@ -46,6 +47,8 @@ pub struct Crate {
     pub documentation: Option<String>,
     pub repository: Option<String>,
     pub max_upload_size: Option<i32>,
+    pub first_version_published_at: Option<NaiveDateTime>,
+    pub last_activity_at: Option<NaiveDateTime>,
 }
 
 /// We literally never want to select `textsearchable_index_col`
@@ -61,6 +64,8 @@ type AllColumns = (
     crates::documentation,
     crates::repository,
     crates::max_upload_size,
+    crates::first_version_published_at,
+    crates::last_activity_at,
 );
 
 pub const ALL_COLUMNS: AllColumns = (
@@ -74,6 +79,8 @@ pub const ALL_COLUMNS: AllColumns = (
     crates::documentation,
     crates::repository,
     crates::max_upload_size,
+    crates::first_version_published_at,
+    crates::last_activity_at,
 );
 
 pub const MAX_NAME_LENGTH: usize = 64;
@@ -102,6 +109,27 @@ pub struct NewCrate<'a> {
     pub max_upload_size: Option<i32>,
 }
 
+/// Validates a `homepage`, `documentation` or `repository` URL of a crate.
+pub(crate) fn validate_url(url: Option<&str>, field: &str) -> AppResult<()> {
+    let url = match url {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+
+    // Manually check the string, as `Url::parse` may normalize relative URLs
+    // making it difficult to ensure that both slashes are present.
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(cargo_err(&format_args!(
+            "URL for field `{field}` must begin with http:// or https:// (url: {url})"
+        )));
+    }
+
+    // Ensure the entire URL parses as well
+    Url::parse(url)
+        .map_err(|_| cargo_err(&format_args!("`{field}` is not a valid url: `{url}`")))?;
+    Ok(())
+}
+
 impl<'a> NewCrate<'a> {
     pub fn create_or_update(
         self,
@@ -119,7 +147,7 @@ impl<'a> NewCrate<'a> {
             // first so we know whether to add an owner
             if let Some(krate) = self.save_new_crate(conn, uploader)? {
                 if let Some(rate_limit) = rate_limit {
-                    rate_limit.check_rate_limit(uploader, conn)?;
+                    rate_limit.check_rate_limit(uploader, self.repository, conn)?;
                 }
                 return Ok(krate);
             }
@@ -134,26 +162,6 @@ impl<'a> NewCrate<'a> {
     }
 
     fn validate(&self) -> AppResult<()> {
-        fn validate_url(url: Option<&str>, field: &str) -> AppResult<()> {
-            let url = match url {
-                Some(s) => s,
-                None => return Ok(()),
-            };
-
-            // Manually check the string, as `Url::parse` may normalize relative URLs
-            // making it difficult to ensure that both slashes are present.
-            if !url.starts_with("http://") && !url.starts_with("https://") {
-                return Err(cargo_err(&format_args!(
-                    "URL for field `{field}` must begin with http:// or https:// (url: {url})"
-                )));
-            }
-
-            // Ensure the entire URL parses as well
-            Url::parse(url)
-                .map_err(|_| cargo_err(&format_args!("`{field}` is not a valid url: `{url}`")))?;
-            Ok(())
-        }
-
         validate_url(self.homepage, "homepage")?;
         validate_url(self.documentation, "documentation")?;
         validate_url(self.repository, "repository")?;
@@ -342,13 +350,17 @@ impl Crate {
         Ok(users.chain(teams).collect())
     }
 
+    /// Invites a user or adds a team as an owner of the crate.
+    ///
+    /// Returns the message for the response, and the invitation email to send
+    /// once the invitation has been committed to the database.
     pub fn owner_add(
         &self,
         app: &App,
         conn: &mut PgConnection,
         req_user: &User,
         login: &str,
-    ) -> AppResult<String> {
+    ) -> AppResult<(String, Option<OwnerInviteEmail>)> {
         use diesel::insert_into;
 
         let owner = Owner::find_or_create_by_login(app, conn, req_user, login)?;
@@ -359,27 +371,27 @@ impl Crate {
                 let config = &app.config;
                 match CrateOwnerInvitation::create(user.id, req_user.id, self.id, conn, config)? {
                     NewCrateOwnerInvitationOutcome::InviteCreated { plaintext_token } => {
-                        if let Ok(Some(email)) = user.verified_email(conn) {
-                            // Swallow any error. Whether or not the email is sent, the invitation
-                            // entry will be created in the database and the user will see the
-                            // invitation when they visit https://crates.io/me/pending-invites/.
-                            let _ = app.emails.send_owner_invite(
-                                &email,
-                                &req_user.gh_login,
-                                &self.name,
-                                &plaintext_token,
-                            );
-                        }
-
-                        Ok(format!(
+                        let email = user.verified_email(conn).ok().flatten();
+                        let email = email.map(|email| OwnerInviteEmail {
+                            email,
+                            inviter: req_user.gh_login.clone(),
+                            crate_name: self.name.clone(),
+                            token: plaintext_token,
+                        });
+
+                        let msg = format!(
                             "user {} has been invited to be an owner of crate {}",
                             user.gh_login, self.name
-                        ))
+                        );
+                        Ok((msg, email))
+                    }
+                    NewCrateOwnerInvitationOutcome::AlreadyExists => {
+                        let msg = format!(
+                            "user {} already has a pending invitation to be an owner of crate {}",
+                            user.gh_login, self.name
+                        );
+                        Ok((msg, None))
                     }
-                    NewCrateOwnerInvitationOutcome::AlreadyExists => Ok(format!(
-                        "user {} already has a pending invitation to be an owner of crate {}",
-                        user.gh_login, self.name
-                    )),
                 }
             }
             // Teams are added as owners immediately
@@ -397,11 +409,12 @@ impl Crate {
                     .set(crate_owners::deleted.eq(false))
                     .execute(conn)?;
 
-                Ok(format!(
+                let msg = format!(
                     "team {} has been added as an owner of crate {}",
                     owner.login(),
                     self.name
-                ))
+                );
+                Ok((msg, None))
             }
         }
     }
@@ -462,10 +475,16 @@ impl Crate {
 
         let deps = deps.grouped_by(&versions);
 
+        let cross_registry_deps: Vec<CrossRegistryDependency> =
+            CrossRegistryDependency::belonging_to(&versions).load(conn)?;
+
+        let cross_registry_deps = cross_registry_deps.grouped_by(&versions);
+
         versions
             .into_iter()
             .zip(deps)
-            .map(|(version, deps)| {
+            .zip(cross_registry_deps)
+            .map(|((version, deps), cross_registry_deps)| {
                 let mut deps = deps
                     .into_iter()
                     .map(|(dep, name)| {
@@ -488,10 +507,30 @@ impl Crate {
                             kind: Some(dep.kind.into()),
                             package,
                             target: dep.target,
+                            registry: None,
                         }
                     })
                     .collect::<Vec<_>>();
 
+                deps.extend(cross_registry_deps.into_iter().map(|dep| {
+                    let (name, package) = match dep.explicit_name {
+                        Some(explicit_name) => (explicit_name, Some(dep.name)),
+                        None => (dep.name, None),
+                    };
+
+                    crates_io_index::Dependency {
+                        name,
+                        req: dep.req,
+                        features: dep.features,
+                        optional: dep.optional,
+                        default_features: dep.default_features,
+                        kind: Some(dep.kind.into()),
+                        package,
+                        target: dep.target,
+                        registry: Some(dep.registry),
+                    }
+                }));
+
                 deps.sort();
 
                 let features: BTreeMap<String, Vec<String>> =
@@ -528,6 +567,26 @@ impl Crate {
     }
 }
 
+/// An ownership invitation email, which is only sent after the transaction
+/// that created the invitation has been committed, so that invitations that
+/// were rolled back don't reach the invited user.
+#[derive(Debug)]
+pub struct OwnerInviteEmail {
+    email: String,
+    inviter: String,
+    crate_name: String,
+    token: String,
+}
+
+impl OwnerInviteEmail {
+    pub fn send(&self, emails: &Emails) {
+        // Swallow any error. Whether or not the email is sent, the invitation
+        // entry is in the database and the user will see the invitation when
+        // they visit https://crates.io/me/pending-invites/.
+        let _ = emails.send_owner_invite(&self.email, &self.inviter, &self.crate_name, &self.token);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::models::{Crate, NewCrate};
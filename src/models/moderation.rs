@@ -0,0 +1,47 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::models::Crate;
+use crate::schema::moderation_queue;
+
+/// A crate that was flagged automatically and needs to be reviewed by the
+/// crates.io team.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(table_name = moderation_queue, belongs_to(Crate))]
+pub struct ModerationFlag {
+    pub id: i32,
+    pub crate_id: i32,
+    pub version_id: Option<i32>,
+    pub reason: String,
+    pub details: Value,
+    pub created_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+impl ModerationFlag {
+    /// Returns all flags that were not reviewed yet, oldest first.
+    pub fn unresolved(conn: &mut PgConnection) -> QueryResult<Vec<Self>> {
+        moderation_queue::table
+            .filter(moderation_queue::resolved_at.is_null())
+            .order(moderation_queue::created_at)
+            .load(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = moderation_queue, check_for_backend(diesel::pg::Pg))]
+pub struct NewModerationFlag<'a> {
+    pub crate_id: i32,
+    pub version_id: Option<i32>,
+    pub reason: &'a str,
+    pub details: Value,
+}
+
+impl NewModerationFlag<'_> {
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<ModerationFlag> {
+        diesel::insert_into(moderation_queue::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
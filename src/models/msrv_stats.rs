@@ -0,0 +1,53 @@
+use chrono::NaiveDate;
+use diesel::dsl::max;
+use diesel::prelude::*;
+use diesel::sql_types::{Date, Integer};
+
+use crate::schema::msrv_stats;
+
+/// The number of recently published versions that declared a `rust-version`
+/// on a given day.
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = msrv_stats, primary_key(date, rust_version))]
+pub struct MsrvStat {
+    pub date: NaiveDate,
+    /// The major and minor version, e.g. `1.60`, or an empty string for
+    /// versions without a `rust-version`.
+    pub rust_version: String,
+    pub version_count: i32,
+    pub crate_count: i32,
+}
+
+impl MsrvStat {
+    /// Records the `rust-version` distribution of the versions published in
+    /// the `days` days before `date`, replacing any snapshot that was
+    /// already recorded for that day.
+    ///
+    /// Returns the number of recorded rows.
+    pub fn aggregate(date: NaiveDate, days: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            diesel::delete(msrv_stats::table.filter(msrv_stats::date.eq(date))).execute(conn)?;
+
+            diesel::sql_query(include_str!("msrv_stats.sql"))
+                .bind::<Date, _>(date)
+                .bind::<Integer, _>(days)
+                .execute(conn)
+        })
+    }
+
+    /// Returns the most recent snapshot, or an empty list if no snapshot was
+    /// recorded yet.
+    pub fn latest(conn: &mut PgConnection) -> QueryResult<Vec<Self>> {
+        let date: Option<NaiveDate> = msrv_stats::table
+            .select(max(msrv_stats::date))
+            .get_result(conn)?;
+
+        let Some(date) = date else {
+            return Ok(Vec::new());
+        };
+
+        msrv_stats::table
+            .filter(msrv_stats::date.eq(date))
+            .load(conn)
+    }
+}
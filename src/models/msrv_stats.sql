@@ -0,0 +1,17 @@
+-- Records for day `$1` how many of the non-yanked versions that were
+-- published in the `$2` days before declare each `rust-version`. Versions
+-- are truncated to the major and minor version, since patch versions are
+-- rarely relevant for an MSRV, and versions without a `rust-version` are
+-- counted with an empty string.
+INSERT INTO msrv_stats (date, rust_version, version_count, crate_count)
+SELECT $1, rust_version, COUNT(*), COUNT(DISTINCT crate_id)
+FROM (
+    SELECT
+        crate_id,
+        COALESCE(substring(rust_version FROM '^\d+\.\d+'), rust_version, '') AS rust_version
+    FROM versions
+    WHERE NOT yanked
+        AND created_at >= $1 - make_interval(days => $2)
+        AND created_at < $1 + 1
+) AS recent_versions
+GROUP BY rust_version;
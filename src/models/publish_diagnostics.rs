@@ -0,0 +1,67 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::models::User;
+use crate::schema::{publish_diagnostics, users};
+
+/// The context of a crate file that was rejected on publish, which is kept
+/// for a short time so that support requests can be debugged without asking
+/// for the crate file.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone, Serialize)]
+#[diesel(
+    table_name = publish_diagnostics,
+    primary_key(token),
+    belongs_to(User),
+)]
+pub struct PublishDiagnostics {
+    pub token: String,
+    pub user_id: i32,
+    pub crate_name: String,
+    pub version: String,
+    pub error: String,
+    pub context: Value,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+impl PublishDiagnostics {
+    /// Returns the diagnostics with the given token, unless they have
+    /// expired, together with the login of the user that tried to publish.
+    pub fn find(token: &str, conn: &mut PgConnection) -> QueryResult<Option<(Self, String)>> {
+        publish_diagnostics::table
+            .inner_join(users::table)
+            .filter(publish_diagnostics::token.eq(token))
+            .filter(publish_diagnostics::expires_at.gt(now))
+            .select((publish_diagnostics::all_columns, users::gh_login))
+            .first(conn)
+            .optional()
+    }
+
+    /// Deletes all expired diagnostics, returning how many were deleted.
+    pub fn prune(conn: &mut PgConnection) -> QueryResult<usize> {
+        let expired = publish_diagnostics::table.filter(publish_diagnostics::expires_at.le(now));
+        diesel::delete(expired).execute(conn)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = publish_diagnostics, check_for_backend(diesel::pg::Pg))]
+pub struct NewPublishDiagnostics {
+    pub token: String,
+    pub user_id: i32,
+    pub crate_name: String,
+    pub version: String,
+    pub error: String,
+    pub context: Value,
+    pub expires_at: NaiveDateTime,
+}
+
+impl NewPublishDiagnostics {
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::insert_into(publish_diagnostics::table)
+            .values(self)
+            .execute(conn)
+    }
+}
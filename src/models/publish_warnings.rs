@@ -0,0 +1,35 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::models::Version;
+use crate::schema::version_publish_warnings;
+
+/// The warnings that were returned to the publisher of a version, see
+/// `crate::views::PublishWarning`. Versions that were published without
+/// warnings have no entry.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = version_publish_warnings,
+    primary_key(version_id),
+    belongs_to(Version),
+)]
+pub struct VersionPublishWarnings {
+    pub version_id: i32,
+    pub warnings: Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl VersionPublishWarnings {
+    pub fn record(
+        version_id_: i32,
+        warnings_: Value,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::version_publish_warnings::dsl::*;
+
+        diesel::insert_into(version_publish_warnings)
+            .values((version_id.eq(version_id_), warnings.eq(warnings_)))
+            .execute(conn)
+    }
+}
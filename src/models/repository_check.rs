@@ -0,0 +1,138 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{BigInt, Integer, Text};
+use std::io::Write;
+
+use crate::models::Crate;
+use crate::schema::repository_checks;
+
+/// Whether the repository that a crate links to still exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AsExpression, FromSqlRow, Serialize)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum RepositoryStatus {
+    Available,
+    /// The repository still exists, but is read-only, which usually means
+    /// that the project is no longer maintained.
+    Archived,
+    /// The repository was deleted or made private.
+    NotFound,
+}
+
+impl RepositoryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Available => "available",
+            Self::Archived => "archived",
+            Self::NotFound => "not_found",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for RepositoryStatus {
+    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for RepositoryStatus {
+    fn from_sql(bytes: diesel::pg::PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "available" => Ok(Self::Available),
+            "archived" => Ok(Self::Archived),
+            "not_found" => Ok(Self::NotFound),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+/// The result of the latest check of the repository of a crate.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = repository_checks,
+    primary_key(crate_id),
+    belongs_to(Crate),
+)]
+pub struct RepositoryCheck {
+    pub crate_id: i32,
+    /// The repository URL of the crate at the time of the check. If it
+    /// differs from the current URL, the result is outdated.
+    pub repository: String,
+    pub status: RepositoryStatus,
+    /// The URL that the repository redirects to, if it was renamed or
+    /// transferred to another owner.
+    pub redirected_to: Option<String>,
+    pub checked_at: NaiveDateTime,
+}
+
+/// A crate whose repository is due to be checked.
+#[derive(Debug, QueryableByName)]
+pub struct RepositoryCheckCandidate {
+    #[diesel(sql_type = Integer)]
+    pub crate_id: i32,
+    #[diesel(sql_type = Text)]
+    pub repository: String,
+}
+
+impl RepositoryCheck {
+    /// Returns the result of the latest check of the current repository of
+    /// a crate, or `None` if it has not been checked yet.
+    pub fn for_crate(krate: &Crate, conn: &mut PgConnection) -> QueryResult<Option<Self>> {
+        let Some(repository) = &krate.repository else {
+            return Ok(None);
+        };
+
+        Self::belonging_to(krate)
+            .filter(repository_checks::repository.eq(repository))
+            .first(conn)
+            .optional()
+    }
+
+    /// Returns up to `limit` crates whose repository was never checked, has
+    /// changed since the last check, or was last checked more than
+    /// `recheck_days` days ago.
+    pub fn candidates(
+        recheck_days: i32,
+        limit: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<RepositoryCheckCandidate>> {
+        diesel::sql_query(include_str!("repository_check_candidates.sql"))
+            .bind::<Integer, _>(recheck_days)
+            .bind::<BigInt, _>(limit)
+            .load(conn)
+    }
+
+    /// Stores the result of a check, replacing the result of any previous
+    /// check of the same crate.
+    pub fn record(
+        crate_id_: i32,
+        repository_: &str,
+        status_: RepositoryStatus,
+        redirected_to_: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::repository_checks::dsl::*;
+        use diesel::dsl::now;
+
+        diesel::insert_into(repository_checks)
+            .values((
+                crate_id.eq(crate_id_),
+                repository.eq(repository_),
+                status.eq(status_),
+                redirected_to.eq(redirected_to_),
+            ))
+            .on_conflict(crate_id)
+            .do_update()
+            .set((
+                repository.eq(repository_),
+                status.eq(status_),
+                redirected_to.eq(redirected_to_),
+                checked_at.eq(now),
+            ))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,15 @@
+-- Crates whose repository should be checked: crates with an HTTP(S)
+-- repository URL that was never checked, that changed since the last check,
+-- or that was last checked more than `$1` days ago. The oldest checks come
+-- first.
+SELECT crates.id AS crate_id, crates.repository
+FROM crates
+LEFT JOIN repository_checks ON repository_checks.crate_id = crates.id
+WHERE crates.repository ~* '^https?://'
+    AND (
+        repository_checks.crate_id IS NULL
+        OR repository_checks.repository <> crates.repository
+        OR repository_checks.checked_at < now() - $1 * INTERVAL '1 day'
+    )
+ORDER BY repository_checks.checked_at NULLS FIRST, crates.id
+LIMIT $2;
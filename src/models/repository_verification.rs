@@ -0,0 +1,157 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::User;
+use crate::schema::repository_verifications;
+use crate::sql::lower;
+use crate::util::token::generate_secure_alphanumeric_string;
+
+/// Path of the file in the default branch of the repository that has to
+/// contain the challenge.
+pub const CHALLENGE_FILE: &str = ".crates-io-verification";
+
+/// Prefix of the tag that can be pushed to the repository instead of the
+/// challenge file.
+pub const CHALLENGE_TAG_PREFIX: &str = "crates-io-verification-";
+
+const CHALLENGE_LENGTH: usize = 32;
+
+/// A challenge for a user to prove control of a GitHub repository.
+///
+/// Users that have not published any crates yet are subject to stricter rate
+/// limits for publishing new crates, unless they have verified a repository.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = repository_verifications,
+    primary_key(user_id),
+    belongs_to(User),
+)]
+pub struct RepositoryVerification {
+    pub user_id: i32,
+    pub repository: String,
+    pub challenge: String,
+    pub created_at: NaiveDateTime,
+    pub verified_at: Option<NaiveDateTime>,
+}
+
+impl RepositoryVerification {
+    pub fn find(user_id: i32, conn: &mut PgConnection) -> QueryResult<Option<Self>> {
+        repository_verifications::table
+            .find(user_id)
+            .first(conn)
+            .optional()
+    }
+
+    /// Checks whether the user verified control of the repository, which has
+    /// to be in the `owner/name` form of [`parse_github_repository`].
+    pub fn is_verified(
+        user_id: i32,
+        repository: &str,
+        conn: &mut PgConnection,
+    ) -> QueryResult<bool> {
+        // GitHub treats the owner and name of repositories case-insensitively
+        let query = repository_verifications::table
+            .filter(repository_verifications::user_id.eq(user_id))
+            .filter(lower(repository_verifications::repository).eq(repository.to_lowercase()))
+            .filter(repository_verifications::verified_at.is_not_null());
+
+        diesel::select(diesel::dsl::exists(query)).get_result(conn)
+    }
+
+    /// Issues a new challenge for the repository, replacing any previous
+    /// challenge or verification of the user.
+    pub fn start(user_id: i32, repository: &str, conn: &mut PgConnection) -> QueryResult<Self> {
+        let challenge = generate_secure_alphanumeric_string(CHALLENGE_LENGTH);
+
+        diesel::insert_into(repository_verifications::table)
+            .values((
+                repository_verifications::user_id.eq(user_id),
+                repository_verifications::repository.eq(repository),
+                repository_verifications::challenge.eq(&challenge),
+            ))
+            .on_conflict(repository_verifications::user_id)
+            .do_update()
+            .set((
+                repository_verifications::repository.eq(repository),
+                repository_verifications::challenge.eq(&challenge),
+                repository_verifications::created_at.eq(diesel::dsl::now),
+                repository_verifications::verified_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+    }
+
+    pub fn mark_verified(&self, conn: &mut PgConnection) -> QueryResult<Self> {
+        diesel::update(self)
+            .set(repository_verifications::verified_at.eq(diesel::dsl::now.nullable()))
+            .get_result(conn)
+    }
+
+    /// The tag that can be pushed to the repository to pass the challenge.
+    pub fn challenge_tag(&self) -> String {
+        format!("{CHALLENGE_TAG_PREFIX}{}", self.challenge)
+    }
+
+    /// Splits the repository into its owner and name.
+    pub fn owner_and_name(&self) -> (&str, &str) {
+        self.repository
+            .split_once('/')
+            .unwrap_or((&self.repository, ""))
+    }
+}
+
+/// Parses a GitHub repository URL like `https://github.com/owner/name` into
+/// the `owner/name` form.
+pub fn parse_github_repository(url: &str) -> Option<String> {
+    let path = url
+        .trim()
+        .strip_prefix("https://github.com/")
+        .or_else(|| url.trim().strip_prefix("http://github.com/"))?;
+
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    let (owner, name) = path.split_once('/')?;
+    let is_valid = |part: &str| {
+        !part.is_empty()
+            && part != "."
+            && part != ".."
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    };
+
+    if !is_valid(owner) || !is_valid(name) {
+        return None;
+    }
+
+    Some(format!("{owner}/{name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_repository_urls() {
+        let parse = parse_github_repository;
+        assert_some_eq!(
+            parse("https://github.com/rust-lang/crates.io"),
+            "rust-lang/crates.io"
+        );
+        assert_some_eq!(
+            parse("https://github.com/rust-lang/cargo/"),
+            "rust-lang/cargo"
+        );
+        assert_some_eq!(
+            parse("https://github.com/rust-lang/cargo.git"),
+            "rust-lang/cargo"
+        );
+        assert_some_eq!(parse(" http://github.com/foo_bar/baz-1 "), "foo_bar/baz-1");
+
+        assert_none!(parse("https://gitlab.com/rust-lang/cargo"));
+        assert_none!(parse("https://github.com/rust-lang"));
+        assert_none!(parse("https://github.com/rust-lang/cargo/tree/master"));
+        assert_none!(parse("https://github.com/rust-lang/.."));
+        assert_none!(parse("https://github.com//cargo"));
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::Version;
+use crate::schema::version_reproducibility;
+
+/// The result of comparing the files of a published crate file with the files
+/// of its source repository at the corresponding git tag.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = version_reproducibility,
+    primary_key(version_id),
+    belongs_to(Version),
+)]
+pub struct VersionReproducibility {
+    pub version_id: i32,
+    pub reproducible: bool,
+    pub git_tag: String,
+    pub checked_at: NaiveDateTime,
+}
+
+impl VersionReproducibility {
+    /// Returns the result of the latest reproducibility check of a version,
+    /// or `None` if the version has not been checked yet.
+    pub fn for_version(version: &Version, conn: &mut PgConnection) -> QueryResult<Option<Self>> {
+        Self::belonging_to(version).first(conn).optional()
+    }
+
+    /// Stores the result of a reproducibility check, replacing the result of
+    /// any previous check of the same version.
+    pub fn record(
+        version_id_: i32,
+        reproducible_: bool,
+        git_tag_: &str,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::version_reproducibility::dsl::*;
+        use diesel::dsl::now;
+
+        diesel::insert_into(version_reproducibility)
+            .values((
+                version_id.eq(version_id_),
+                reproducible.eq(reproducible_),
+                git_tag.eq(git_tag_),
+            ))
+            .on_conflict(version_id)
+            .do_update()
+            .set((
+                reproducible.eq(reproducible_),
+                git_tag.eq(git_tag_),
+                checked_at.eq(now),
+            ))
+            .execute(conn)
+    }
+}
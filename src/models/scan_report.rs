@@ -0,0 +1,72 @@
+use chrono::NaiveDateTime;
+use crates_io_tarball::scanner::ScanReport;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::models::Version;
+use crate::schema::{crates, version_scan_reports, versions};
+
+/// The suspicious files of a published crate file, see
+/// `crates_io_tarball::scanner::ScanReport`. Versions without findings have
+/// no report.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = version_scan_reports,
+    primary_key(version_id),
+    belongs_to(Version),
+)]
+pub struct VersionScanReport {
+    pub version_id: i32,
+    pub findings: Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl VersionScanReport {
+    pub fn record(
+        version_id_: i32,
+        report: &ScanReport,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::version_scan_reports::dsl::*;
+
+        diesel::insert_into(version_scan_reports)
+            .values((
+                version_id.eq(version_id_),
+                findings.eq(json!(report.findings)),
+            ))
+            .execute(conn)
+    }
+
+    /// Deserializes the stored findings, returning `None` if they were
+    /// written in a format that is no longer understood.
+    pub fn report(&self) -> Option<ScanReport> {
+        let findings = serde_json::from_value(self.findings.clone()).ok()?;
+        Some(ScanReport { findings })
+    }
+
+    /// Returns all reports created since `since`, optionally only for the
+    /// crate with the given name, together with the crate name and version
+    /// number, most recent first.
+    pub fn since(
+        since: NaiveDateTime,
+        crate_name: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<(Self, String, String)>> {
+        let mut query = version_scan_reports::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .filter(version_scan_reports::created_at.ge(since))
+            .select((
+                version_scan_reports::all_columns,
+                crates::name,
+                versions::num,
+            ))
+            .order(version_scan_reports::created_at.desc())
+            .into_boxed();
+
+        if let Some(crate_name) = crate_name {
+            query = query.filter(crates::name.eq(crate_name));
+        }
+
+        query.load(conn)
+    }
+}
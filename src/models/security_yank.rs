@@ -0,0 +1,89 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::Version;
+use crate::schema::security_yanks;
+
+/// Prefix of the IDs of the OSV records that are exported for security yanks.
+pub const OSV_ID_PREFIX: &str = "CRATESIO-";
+
+/// A version that was yanked for security reasons.
+///
+/// Security yanks are exported as OSV records, so that vulnerability scanners
+/// can pick them up. Unyanking the version withdraws the record instead of
+/// deleting it, so that scanners learn about the change too.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(table_name = security_yanks, belongs_to(Version))]
+pub struct SecurityYank {
+    pub id: i32,
+    pub version_id: i32,
+    pub advisory_url: String,
+    pub published_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+    pub withdrawn_at: Option<NaiveDateTime>,
+}
+
+impl SecurityYank {
+    /// Records that the version was yanked for security reasons, updating the
+    /// advisory link and reinstating the record if it was withdrawn before.
+    pub fn record(
+        version_id: i32,
+        advisory_url: &str,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Self> {
+        diesel::insert_into(security_yanks::table)
+            .values((
+                security_yanks::version_id.eq(version_id),
+                security_yanks::advisory_url.eq(advisory_url),
+            ))
+            .on_conflict(security_yanks::version_id)
+            .do_update()
+            .set((
+                security_yanks::advisory_url.eq(advisory_url),
+                security_yanks::modified_at.eq(diesel::dsl::now),
+                security_yanks::withdrawn_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+    }
+
+    /// Withdraws the record of the version, if there is one.
+    pub fn withdraw(version_id: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        let query = security_yanks::table
+            .filter(security_yanks::version_id.eq(version_id))
+            .filter(security_yanks::withdrawn_at.is_null());
+
+        diesel::update(query)
+            .set((
+                security_yanks::modified_at.eq(diesel::dsl::now),
+                security_yanks::withdrawn_at.eq(diesel::dsl::now.nullable()),
+            ))
+            .execute(conn)
+    }
+
+    pub fn osv_id(&self) -> String {
+        format!("{OSV_ID_PREFIX}{}", self.id)
+    }
+
+    /// Returns the database ID from an OSV record ID like `CRATESIO-42`.
+    pub fn parse_osv_id(osv_id: &str) -> Option<i32> {
+        osv_id
+            .strip_prefix(OSV_ID_PREFIX)?
+            .parse()
+            .ok()
+            .filter(|id| *id > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecurityYank;
+
+    #[test]
+    fn parse_osv_id() {
+        assert_some_eq!(SecurityYank::parse_osv_id("CRATESIO-42"), 42);
+        assert_none!(SecurityYank::parse_osv_id("CRATESIO-0"));
+        assert_none!(SecurityYank::parse_osv_id("CRATESIO-foo"));
+        assert_none!(SecurityYank::parse_osv_id("RUSTSEC-2023-0001"));
+        assert_none!(SecurityYank::parse_osv_id("42"));
+    }
+}
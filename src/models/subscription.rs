@@ -0,0 +1,182 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+
+use crate::models::{Category, User};
+use crate::schema::*;
+use crate::sql::pg_enum;
+
+#[derive(Insertable, Queryable, Identifiable, Associations, Clone, Copy, Debug)]
+#[diesel(
+    table_name = keyword_subscriptions,
+    check_for_backend(diesel::pg::Pg),
+    primary_key(user_id, keyword_id),
+    belongs_to(User),
+)]
+pub struct KeywordSubscription {
+    pub user_id: i32,
+    pub keyword_id: i32,
+}
+
+#[derive(Insertable, Queryable, Identifiable, Associations, Clone, Copy, Debug)]
+#[diesel(
+    table_name = category_subscriptions,
+    check_for_backend(diesel::pg::Pg),
+    primary_key(user_id, category_id),
+    belongs_to(User),
+)]
+pub struct CategorySubscription {
+    pub user_id: i32,
+    pub category_id: i32,
+}
+
+/// Returns the IDs of the crates that match the keyword and category
+/// subscriptions of the user.
+///
+/// `category_ids` are the IDs returned by [`subscribed_category_ids()`].
+pub fn subscribed_crate_ids(
+    user_id: i32,
+    category_ids: Vec<i32>,
+) -> (
+    crates_keywords::BoxedQuery<'static, Pg, Integer>,
+    crates_categories::BoxedQuery<'static, Pg, Integer>,
+) {
+    let keyword_ids = keyword_subscriptions::table
+        .filter(keyword_subscriptions::user_id.eq(user_id))
+        .select(keyword_subscriptions::keyword_id);
+
+    let by_keyword = crates_keywords::table
+        .filter(crates_keywords::keyword_id.eq_any(keyword_ids))
+        .select(crates_keywords::crate_id)
+        .into_boxed();
+
+    let by_category = crates_categories::table
+        .filter(crates_categories::category_id.eq_any(category_ids))
+        .select(crates_categories::crate_id)
+        .into_boxed();
+
+    (by_keyword, by_category)
+}
+
+/// Returns the IDs of the categories that the user is subscribed to,
+/// including all of their subcategories.
+pub fn subscribed_category_ids(user_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<i32>> {
+    let categories: Vec<Category> = category_subscriptions::table
+        .inner_join(categories::table)
+        .filter(category_subscriptions::user_id.eq(user_id))
+        .select(categories::all_columns)
+        .load(conn)?;
+
+    let mut ids = Vec::new();
+    for category in categories {
+        ids.extend(category.with_subcategories(conn)?.iter().map(|c| c.id));
+    }
+
+    ids.sort_unstable();
+    ids.dedup();
+
+    Ok(ids)
+}
+
+/// A release that is listed in the digest of a user.
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct DigestEntry {
+    pub crate_name: String,
+    pub version: String,
+    pub description: Option<String>,
+    /// Whether the crate was created since the previous digest.
+    pub new_crate: bool,
+}
+
+/// Returns the releases that were published between `since` and `until` and
+/// match the subscriptions of the user, oldest first.
+pub fn digest_entries(
+    user_id: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> QueryResult<Vec<DigestEntry>> {
+    let category_ids = subscribed_category_ids(user_id, conn)?;
+    let (by_keyword, by_category) = subscribed_crate_ids(user_id, category_ids);
+
+    versions::table
+        .inner_join(crates::table)
+        .filter(versions::created_at.gt(since))
+        .filter(versions::created_at.le(until))
+        .filter(versions::yanked.eq(false))
+        .filter(
+            crates::id
+                .eq_any(by_keyword)
+                .or(crates::id.eq_any(by_category)),
+        )
+        .order(versions::created_at)
+        .select((
+            crates::name,
+            versions::num,
+            crates::description,
+            crates::created_at.gt(since),
+        ))
+        .limit(limit)
+        .load(conn)
+}
+
+pg_enum! {
+    pub enum DigestFrequency {
+        Never = 0,
+        Daily = 1,
+        Weekly = 2,
+    }
+}
+
+impl DigestFrequency {
+    /// Returns the time between two digests, or `None` if no digests are sent.
+    pub fn period(self) -> Option<Duration> {
+        match self {
+            DigestFrequency::Never => None,
+            DigestFrequency::Daily => Some(Duration::days(1)),
+            DigestFrequency::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Clone, Copy, Debug)]
+#[diesel(
+    table_name = digest_preferences,
+    check_for_backend(diesel::pg::Pg),
+    primary_key(user_id)
+)]
+pub struct DigestPreference {
+    pub user_id: i32,
+    pub frequency: DigestFrequency,
+    pub last_sent_at: Option<NaiveDateTime>,
+}
+
+impl DigestPreference {
+    /// Returns the digest preference of the user, or the default preference
+    /// of a weekly digest if the user did not change it.
+    pub fn for_user(user_id: i32, conn: &mut PgConnection) -> QueryResult<Self> {
+        let preference = digest_preferences::table
+            .find(user_id)
+            .first(conn)
+            .optional()?;
+
+        Ok(preference.unwrap_or(Self {
+            user_id,
+            frequency: DigestFrequency::Weekly,
+            last_sent_at: None,
+        }))
+    }
+
+    pub fn save(&self, conn: &mut PgConnection) -> QueryResult<()> {
+        diesel::insert_into(digest_preferences::table)
+            .values(self)
+            .on_conflict(digest_preferences::user_id)
+            .do_update()
+            .set(self)
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+
+use crate::models::Crate;
+use crate::schema::support_windows;
+use crate::sql::pg_enum;
+
+pg_enum! {
+    pub enum SupportStatus {
+        Supported = 0,
+        SecurityFixesOnly = 1,
+        EndOfLife = 2,
+    }
+}
+
+impl SupportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Supported => "supported",
+            Self::SecurityFixesOnly => "security-fixes-only",
+            Self::EndOfLife => "end-of-life",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s {
+            "supported" => Some(Self::Supported),
+            "security-fixes-only" => Some(Self::SecurityFixesOnly),
+            "end-of-life" => Some(Self::EndOfLife),
+            _ => None,
+        }
+    }
+}
+
+/// The support status of a semver-compatible release line of a crate, as
+/// declared by the owners of the crate.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = support_windows,
+    primary_key(crate_id, major_version),
+    belongs_to(Crate),
+)]
+pub struct SupportWindow {
+    pub crate_id: i32,
+    pub major_version: String,
+    pub status: SupportStatus,
+    pub end_of_life_date: Option<NaiveDate>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl SupportWindow {
+    /// Returns the support windows of all release lines of a crate that
+    /// were declared by its owners.
+    pub fn for_crate(krate: &Crate, conn: &mut PgConnection) -> QueryResult<Vec<Self>> {
+        Self::belonging_to(krate)
+            .order(support_windows::major_version)
+            .load(conn)
+    }
+
+    /// Stores the support window of a release line, replacing any previously
+    /// declared support window of the same release line.
+    pub fn upsert(
+        crate_id_: i32,
+        major_version_: &str,
+        status_: SupportStatus,
+        end_of_life_date_: Option<NaiveDate>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Self> {
+        use crate::schema::support_windows::dsl::*;
+        use diesel::dsl::now;
+
+        diesel::insert_into(support_windows)
+            .values((
+                crate_id.eq(crate_id_),
+                major_version.eq(major_version_),
+                status.eq(status_),
+                end_of_life_date.eq(end_of_life_date_),
+            ))
+            .on_conflict((crate_id, major_version))
+            .do_update()
+            .set((
+                status.eq(status_),
+                end_of_life_date.eq(end_of_life_date_),
+                updated_at.eq(now),
+            ))
+            .get_result(conn)
+    }
+
+    pub fn delete(
+        crate_id_: i32,
+        major_version_: &str,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(support_windows::table.find((crate_id_, major_version_))).execute(conn)
+    }
+}
+
+/// Returns the semver-compatible release line of a version, i.e. `1` for
+/// `1.2.3`, `0.4` for `0.4.1` and `0.0.3` for `0.0.3`.
+pub fn major_version(version: &semver::Version) -> String {
+    match (version.major, version.minor) {
+        (0, 0) => format!("0.0.{}", version.patch),
+        (0, minor) => format!("0.{minor}"),
+        (major, _) => major.to_string(),
+    }
+}
+
+/// Parses and normalizes the name of a semver-compatible release line.
+///
+/// Returns `None` if the input does not describe a release line, e.g. `0`
+/// or `1.2`.
+pub fn parse_major_version(input: &str) -> Option<String> {
+    let parts = input
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match parts[..] {
+        [major] if major > 0 => Some(major.to_string()),
+        [0, minor] if minor > 0 => Some(format!("0.{minor}")),
+        [0, 0, patch] => Some(format!("0.0.{patch}")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn major_versions() {
+        let major_version = |v: &str| major_version(&semver::Version::parse(v).unwrap());
+        assert_eq!(major_version("1.2.3"), "1");
+        assert_eq!(major_version("12.0.0-beta.1"), "12");
+        assert_eq!(major_version("0.4.1"), "0.4");
+        assert_eq!(major_version("0.0.3"), "0.0.3");
+    }
+
+    #[test]
+    fn parse_major_versions() {
+        assert_eq!(parse_major_version("1").as_deref(), Some("1"));
+        assert_eq!(parse_major_version("01").as_deref(), Some("1"));
+        assert_eq!(parse_major_version("0.4").as_deref(), Some("0.4"));
+        assert_eq!(parse_major_version("0.0.3").as_deref(), Some("0.0.3"));
+        assert_eq!(parse_major_version("0"), None);
+        assert_eq!(parse_major_version("1.2"), None);
+        assert_eq!(parse_major_version("0.4.1"), None);
+        assert_eq!(parse_major_version("1.x"), None);
+        assert_eq!(parse_major_version(""), None);
+    }
+}
@@ -89,6 +89,17 @@ impl ApiToken {
         .or_else(|_| tokens.select(ApiToken::as_select()).first(conn))
         .map_err(Into::into)
     }
+
+    /// Returns whether the crate scopes of the token allow access to the
+    /// given crate. Tokens without crate scopes allow access to all crates.
+    pub fn allows_crate(&self, crate_name: &str) -> bool {
+        match &self.crate_scopes {
+            Some(scopes) if !scopes.is_empty() => {
+                scopes.iter().any(|scope| scope.matches(crate_name))
+            }
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug)]
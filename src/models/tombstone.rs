@@ -0,0 +1,69 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::schema::tombstones;
+use crate::sql::canon_crate_name;
+
+/// A deleted crate or version.
+///
+/// The names of deleted crates and the numbers of deleted versions can only
+/// be published again after a cooldown, so that a freed name can't be taken
+/// over right away by someone who impersonates the previous crate.
+#[derive(Queryable, Identifiable, Debug, Clone)]
+pub struct Tombstone {
+    pub id: i32,
+    pub crate_name: String,
+    /// The deleted version, or `None` if the whole crate was deleted.
+    pub version: Option<String>,
+    pub deleted_at: NaiveDateTime,
+}
+
+impl Tombstone {
+    /// Records the deletion of a crate, or of one of its versions.
+    pub fn record(
+        crate_name: &str,
+        version: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        diesel::insert_into(tombstones::table)
+            .values((
+                tombstones::crate_name.eq(crate_name),
+                tombstones::version.eq(version),
+            ))
+            .execute(conn)
+    }
+
+    /// Returns the latest deletion since `since` that prevents publishing
+    /// `version` of the crate `crate_name`.
+    ///
+    /// Deletions of whole crates are only taken into account if the crate
+    /// doesn't exist (anymore), since the owners of an existing crate are
+    /// the ones that published it again after the cooldown.
+    pub fn find_blocking(
+        crate_name: &str,
+        version: &str,
+        crate_exists: bool,
+        since: NaiveDateTime,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Option<Self>> {
+        let mut query = tombstones::table
+            .filter(canon_crate_name(tombstones::crate_name).eq(canon_crate_name(crate_name)))
+            .filter(tombstones::deleted_at.gt(since))
+            .into_boxed();
+
+        query = if crate_exists {
+            query.filter(tombstones::version.eq(version))
+        } else {
+            query.filter(
+                tombstones::version
+                    .is_null()
+                    .or(tombstones::version.eq(version)),
+            )
+        };
+
+        query
+            .order(tombstones::deleted_at.desc())
+            .first(conn)
+            .optional()
+    }
+}
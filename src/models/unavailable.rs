@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::Version;
+use crate::schema::unavailable_versions;
+
+/// A version whose crate file is missing or corrupted in the storage, and
+/// could not be restored from a mirror.
+///
+/// Downloads of these versions fail with an explanation, instead of a
+/// redirect to a file that the CDN doesn't have.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[diesel(
+    table_name = unavailable_versions,
+    primary_key(version_id),
+    belongs_to(Version),
+)]
+pub struct UnavailableVersion {
+    pub version_id: i32,
+    pub reason: String,
+    pub marked_at: NaiveDateTime,
+}
+
+impl UnavailableVersion {
+    /// Marks the crate file of a version as unavailable, or updates the
+    /// reason if it was marked before.
+    pub fn mark(version_id_: i32, reason_: &str, conn: &mut PgConnection) -> QueryResult<usize> {
+        use crate::schema::unavailable_versions::dsl::*;
+
+        diesel::insert_into(unavailable_versions)
+            .values((version_id.eq(version_id_), reason.eq(reason_)))
+            .on_conflict(version_id)
+            .do_update()
+            .set(reason.eq(reason_))
+            .execute(conn)
+    }
+
+    /// Records that the crate file of a version is available again.
+    pub fn remove(version_id_: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(unavailable_versions::table.find(version_id_)).execute(conn)
+    }
+}
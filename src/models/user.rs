@@ -1,13 +1,14 @@
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::app::App;
 use crate::email::Emails;
 use crate::util::errors::AppResult;
 
-use crate::models::{ApiToken, Crate, CrateOwner, Email, NewEmail, Owner, OwnerKind, Rights};
-use crate::schema::{crate_owners, emails, users};
+use crate::models::{ApiToken, Crate, CrateOwner, Email, NewEmail, Owner, OwnerKind, Rights, Team};
+use crate::schema::{crate_owners, crates, emails, teams, users};
 
 /// The model representing a row in the `users` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable, AsChangeset)]
@@ -20,6 +21,8 @@ pub struct User {
     pub gh_id: i32,
     pub account_lock_reason: Option<String>,
     pub account_lock_until: Option<NaiveDateTime>,
+    pub sessions_revoked_at: Option<NaiveDateTime>,
+    pub is_admin: bool,
 }
 
 /// Represents a new user record insertable to the `users` table
@@ -109,6 +112,17 @@ impl<'a> NewUser<'a> {
     }
 }
 
+/// A crate that a user can publish new versions of, see
+/// [`User::publishable_crates`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublishableCrate {
+    pub id: i32,
+    pub name: String,
+    /// The login of the owning team that grants the user publish rights, or
+    /// `None` if the user is a direct owner of the crate.
+    pub team: Option<String>,
+}
+
 impl User {
     pub fn find(conn: &mut PgConnection, id: i32) -> QueryResult<User> {
         users::table.find(id).first(conn)
@@ -160,6 +174,72 @@ impl User {
         Ok(best)
     }
 
+    /// Returns all crates that the user can publish new versions of, ordered
+    /// by name.
+    ///
+    /// This is the bulk version of [`User::rights`]: crates can be published
+    /// by their direct owners and by active members of an owning team. The
+    /// membership of each owning team is only looked up once.
+    pub fn publishable_crates(
+        &self,
+        app: &App,
+        conn: &mut PgConnection,
+    ) -> AppResult<Vec<PublishableCrate>> {
+        let owned: Vec<(i32, String)> = CrateOwner::by_owner_kind(OwnerKind::User)
+            .inner_join(crates::table)
+            .filter(crate_owners::owner_id.eq(self.id))
+            .select((crates::id, crates::name))
+            .load(conn)?;
+
+        let mut publishable = owned
+            .into_iter()
+            .map(|(id, name)| {
+                let krate = PublishableCrate {
+                    id,
+                    name: name.clone(),
+                    team: None,
+                };
+                (name, krate)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let team_owned: Vec<(i32, String, Team)> = crate_owners::table
+            .inner_join(crates::table)
+            .inner_join(teams::table)
+            .filter(crate_owners::deleted.eq(false))
+            .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
+            .select((crates::id, crates::name, teams::all_columns))
+            .order(teams::login)
+            .load(conn)?;
+
+        let mut memberships = HashMap::new();
+        for (id, name, team) in team_owned {
+            if publishable.contains_key(&name) {
+                continue;
+            }
+
+            let is_member = match memberships.get(&team.id) {
+                Some(&is_member) => is_member,
+                None => {
+                    let is_member = team.contains_user(app, self)?;
+                    memberships.insert(team.id, is_member);
+                    is_member
+                }
+            };
+
+            if is_member {
+                let krate = PublishableCrate {
+                    id,
+                    name: name.clone(),
+                    team: Some(team.login),
+                };
+                publishable.insert(name, krate);
+            }
+        }
+
+        Ok(publishable.into_values().collect())
+    }
+
     /// Queries the database for the verified emails
     /// belonging to a given user
     pub fn verified_email(&self, conn: &mut PgConnection) -> QueryResult<Option<String>> {
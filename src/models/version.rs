@@ -8,6 +8,7 @@ use crate::util::errors::{cargo_err, AppResult};
 use crate::models::{Crate, Dependency, User};
 use crate::schema::*;
 use crate::sql::split_part;
+use crate::util::source_links::VersionSource;
 
 // Queryable has a custom implementation below
 #[derive(Clone, Identifiable, Associations, Debug, Queryable, Deserialize, Serialize)]
@@ -27,6 +28,9 @@ pub struct Version {
     pub checksum: String,
     pub links: Option<String>,
     pub rust_version: Option<String>,
+    pub dirty_worktree: bool,
+    pub commit_sha: Option<String>,
+    pub source_url: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -41,6 +45,9 @@ pub struct NewVersion {
     checksum: String,
     links: Option<String>,
     rust_version: Option<String>,
+    dirty_worktree: bool,
+    commit_sha: Option<String>,
+    source_url: Option<String>,
 }
 
 /// The highest version (semver order) and the most recently updated version.
@@ -105,18 +112,21 @@ impl Version {
             .load(conn)
     }
 
+    /// Records that the README of the version was rendered, along with the
+    /// reason if a placeholder was uploaded instead.
     pub fn record_readme_rendering(
         version_id_: i32,
+        error_: Option<&str>,
         conn: &mut PgConnection,
     ) -> QueryResult<usize> {
         use crate::schema::readme_renderings::dsl::*;
         use diesel::dsl::now;
 
         diesel::insert_into(readme_renderings)
-            .values(version_id.eq(version_id_))
+            .values((version_id.eq(version_id_), error.eq(error_)))
             .on_conflict(version_id)
             .do_update()
-            .set(rendered_at.eq(now))
+            .set((rendered_at.eq(now), error.eq(error_)))
             .execute(conn)
     }
 
@@ -143,8 +153,14 @@ impl NewVersion {
         checksum: String,
         links: Option<String>,
         rust_version: Option<String>,
+        dirty_worktree: bool,
+        source: VersionSource,
     ) -> AppResult<Self> {
         let features = serde_json::to_value(features)?;
+        let VersionSource {
+            commit_sha,
+            source_url,
+        } = source;
 
         let mut new_version = NewVersion {
             crate_id,
@@ -156,6 +172,9 @@ impl NewVersion {
             checksum,
             links,
             rust_version,
+            dirty_worktree,
+            commit_sha,
+            source_url,
         };
 
         new_version.validate_license(license_file)?;
@@ -208,14 +227,7 @@ impl NewVersion {
 }
 
 fn validate_license_expr(s: &str) -> AppResult<()> {
-    pub const PARSE_MODE: spdx::ParseMode = spdx::ParseMode {
-        allow_lower_case_operators: false,
-        allow_slash_as_or_operator: true,
-        allow_imprecise_license_names: false,
-        allow_postfix_plus_on_gpl: true,
-    };
-
-    spdx::Expression::parse_mode(s, PARSE_MODE).map_err(|_| {
+    crates_io_tarball::validate_license_expr(s).map_err(|_| {
         cargo_err("unknown or invalid license expression; see http://opensource.org/licenses for options, and http://spdx.org/licenses/ for their identifiers")
     })?;
 
@@ -0,0 +1,25 @@
+-- Versions of dead crates, whose crate files can be moved to the archive
+-- storage tier: the version and all other versions of the crate are yanked,
+-- and the version has not been downloaded for at least `$1` days.
+SELECT versions.id AS version_id, crates.name AS crate_name, versions.num
+FROM versions
+INNER JOIN crates ON crates.id = versions.crate_id
+WHERE versions.yanked
+    AND versions.created_at < CURRENT_DATE - $1 * INTERVAL '1 day'
+    AND NOT EXISTS (
+        SELECT 1 FROM versions AS other_versions
+        WHERE other_versions.crate_id = versions.crate_id
+            AND NOT other_versions.yanked
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM version_downloads
+        WHERE version_downloads.version_id = versions.id
+            AND version_downloads.date > CURRENT_DATE - $1 * INTERVAL '1 day'
+            AND version_downloads.downloads > 0
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM version_archives
+        WHERE version_archives.version_id = versions.id
+    )
+ORDER BY versions.id
+LIMIT $2;
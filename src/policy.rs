@@ -0,0 +1,308 @@
+//! Publish policies of the registry
+//!
+//! Registry operators can enforce additional rules on published crates, e.g.
+//! that crates in the `cryptography` category must link to their repository.
+//! The rules are read from a policy file on startup and are evaluated by the
+//! publish endpoint, which rejects crates that violate any of them.
+//!
+//! The policy file is a TOML file like this:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[rules]]
+//! name = "cryptography-repository"
+//! categories = ["cryptography"]
+//! require = ["repository"]
+//!
+//! [[rules]]
+//! name = "no-std-dependencies"
+//! categories = ["no-std"]
+//! deny-dependency-categories = ["command-line-interface"]
+//! ```
+
+use crate::env_optional;
+use crate::schema::{categories, crates, crates_categories};
+use crate::views::EncodableCrateUpload;
+use anyhow::{bail, Context};
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// The only version of the policy file format that is currently supported.
+const POLICY_FILE_VERSION: u32 = 1;
+
+/// The rules that published crates have to comply with.
+#[derive(Debug, Default)]
+pub struct PublishPolicy {
+    rules: Vec<PolicyRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    version: u32,
+    #[serde(default)]
+    rules: Vec<PolicyRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct PolicyRule {
+    /// Identifies the rule in the errors of the publish endpoint.
+    name: String,
+    /// The rule only applies to crates with one of these names. If neither
+    /// `crates` nor `categories` are set, the rule applies to all crates.
+    #[serde(default)]
+    crates: Vec<String>,
+    /// The rule only applies to crates in one of these categories or their
+    /// subcategories.
+    #[serde(default)]
+    categories: Vec<String>,
+    /// Metadata fields that have to be set.
+    #[serde(default)]
+    require: Vec<RequiredField>,
+    /// Categories whose crates may not be used as dependencies.
+    #[serde(default)]
+    deny_dependency_categories: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RequiredField {
+    Description,
+    Documentation,
+    Homepage,
+    License,
+    Readme,
+    Repository,
+}
+
+/// A rule of the [`PublishPolicy`] that a published crate does not comply
+/// with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub rule: String,
+    pub message: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (rule `{}`)", self.message, self.rule)
+    }
+}
+
+impl PublishPolicy {
+    /// Reads the policy file at the path in the `PUBLISH_POLICY_FILE`
+    /// environment variable, or returns an empty policy if it is not set.
+    pub fn from_environment() -> Self {
+        let Some(path) = env_optional::<String>("PUBLISH_POLICY_FILE") else {
+            return Self::default();
+        };
+
+        std::fs::read_to_string(&path)
+            .context("failed to read the policy file")
+            .and_then(|contents| Self::parse(&contents))
+            .unwrap_or_else(|error| panic!("invalid PUBLISH_POLICY_FILE `{path}`: {error:#}"))
+    }
+
+    /// Parses the contents of a policy file.
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let file: PolicyFile = toml::from_str(contents)?;
+        if file.version != POLICY_FILE_VERSION {
+            bail!("unsupported policy file version {}", file.version);
+        }
+
+        Ok(Self { rules: file.rules })
+    }
+
+    /// Returns all violations of the policy by the crate that is being
+    /// published.
+    pub fn evaluate(
+        &self,
+        krate: &EncodableCrateUpload,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<PolicyViolation>> {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(krate))
+            .collect::<Vec<_>>();
+
+        let needs_dependency_categories = rules
+            .iter()
+            .any(|rule| !rule.deny_dependency_categories.is_empty());
+        let dependency_categories = if needs_dependency_categories {
+            load_dependency_categories(krate, conn)?
+        } else {
+            BTreeSet::new()
+        };
+
+        let mut violations = Vec::new();
+        for rule in rules {
+            for field in &rule.require {
+                if !field.is_set(krate) {
+                    violations.push(
+                        rule.violation(format!("the `{}` field must be set", field.as_str())),
+                    );
+                }
+            }
+
+            let denied = dependency_categories.iter().filter(|(_, slug)| {
+                let denied = &rule.deny_dependency_categories;
+                denied.iter().any(|pattern| category_matches(pattern, slug))
+            });
+            for (dependency, slug) in denied {
+                violations.push(rule.violation(format!(
+                    "the dependency `{dependency}` is in the `{slug}` category, which is not allowed"
+                )));
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+impl PolicyRule {
+    fn applies_to(&self, krate: &EncodableCrateUpload) -> bool {
+        if self.crates.is_empty() && self.categories.is_empty() {
+            return true;
+        }
+
+        self.crates.iter().any(|name| krate.name.as_str() == name)
+            || krate.categories.iter().any(|slug| {
+                let categories = &self.categories;
+                categories
+                    .iter()
+                    .any(|pattern| category_matches(pattern, slug))
+            })
+    }
+
+    fn violation(&self, message: String) -> PolicyViolation {
+        PolicyViolation {
+            rule: self.name.clone(),
+            message,
+        }
+    }
+}
+
+impl RequiredField {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Description => "description",
+            Self::Documentation => "documentation",
+            Self::Homepage => "homepage",
+            Self::License => "license",
+            Self::Readme => "readme",
+            Self::Repository => "repository",
+        }
+    }
+
+    fn is_set(&self, krate: &EncodableCrateUpload) -> bool {
+        fn present(value: &Option<String>) -> bool {
+            value
+                .as_deref()
+                .is_some_and(|value| !value.trim().is_empty())
+        }
+
+        match self {
+            Self::Description => present(&krate.description),
+            Self::Documentation => present(&krate.documentation),
+            Self::Homepage => present(&krate.homepage),
+            Self::License => present(&krate.license) || present(&krate.license_file),
+            Self::Readme => present(&krate.readme),
+            Self::Repository => present(&krate.repository),
+        }
+    }
+}
+
+/// Returns whether the category `slug` is the category `pattern` or one of
+/// its subcategories, like `cryptography::cryptocurrencies`.
+fn category_matches(pattern: &str, slug: &str) -> bool {
+    slug.strip_prefix(pattern)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Loads the categories of all crates.io dependencies of the crate, as pairs
+/// of crate name and category slug, without duplicates.
+fn load_dependency_categories(
+    krate: &EncodableCrateUpload,
+    conn: &mut PgConnection,
+) -> QueryResult<BTreeSet<(String, String)>> {
+    let names = krate
+        .deps
+        .iter()
+        .filter(|dep| dep.registry.is_none())
+        .map(|dep| dep.name.as_str())
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        return Ok(BTreeSet::new());
+    }
+
+    let categories = crates_categories::table
+        .inner_join(crates::table)
+        .inner_join(categories::table)
+        .filter(crates::name.eq_any(names))
+        .select((crates::name, categories::slug))
+        .load::<(String, String)>(conn)?;
+
+    Ok(categories.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let policy = PublishPolicy::parse(
+            r#"
+version = 1
+
+[[rules]]
+name = "cryptography-repository"
+categories = ["cryptography"]
+require = ["repository", "license"]
+
+[[rules]]
+name = "no-cli-dependencies"
+crates = ["foo"]
+deny-dependency-categories = ["command-line-interface"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(policy.rules.len(), 2);
+
+        assert_ok!(PublishPolicy::parse("version = 1"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let error = assert_err!(PublishPolicy::parse("version = 2"));
+        assert_eq!(error.to_string(), "unsupported policy file version 2");
+
+        assert_err!(PublishPolicy::parse(""));
+        assert_err!(PublishPolicy::parse(
+            "version = 1\n[[rules]]\nname = \"foo\"\nrequire = [\"unknown\"]"
+        ));
+        assert_err!(PublishPolicy::parse(
+            "version = 1\n[[rules]]\nname = \"foo\"\ndeny-git = true"
+        ));
+    }
+
+    #[test]
+    fn category_patterns() {
+        assert!(category_matches("cryptography", "cryptography"));
+        assert!(category_matches(
+            "cryptography",
+            "cryptography::cryptocurrencies"
+        ));
+        assert!(!category_matches("cryptography", "cryptographyx"));
+        assert!(!category_matches(
+            "cryptography::cryptocurrencies",
+            "cryptography"
+        ));
+    }
+}
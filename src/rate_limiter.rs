@@ -1,11 +1,16 @@
 use chrono::{NaiveDateTime, Utc};
-use diesel::data_types::PgInterval;
+use diesel::dsl::exists;
 use diesel::prelude::*;
-use diesel::sql_types::Interval;
+use diesel::upsert::excluded;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::schema::{publish_limit_buckets, publish_rate_overrides};
-use crate::sql::{date_part, floor, greatest, interval_part, least, pg_enum};
+use crate::models::repository_verification::parse_github_repository;
+use crate::models::RepositoryVerification;
+use crate::schema::{publish_limit_buckets, publish_rate_overrides, versions};
+use crate::sql::pg_enum;
 use crate::util::errors::{AppResult, TooManyRequests};
 
 pg_enum! {
@@ -14,10 +19,24 @@ pg_enum! {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Token bucket rate limiter for publishing new crates.
+///
+/// The buckets of the users that published recently are kept in memory, and
+/// are periodically checkpointed to the `publish_limit_buckets` table, see
+/// [`RateLimiter::checkpoint`]. Buckets that are not in memory are loaded from
+/// the table, so the remaining budget of a user is not reset when the
+/// application is restarted or redeployed.
+#[derive(Debug, Clone)]
 pub struct RateLimiter {
     pub rate: Duration,
     pub burst: i32,
+    /// The burst for users that have not published any versions yet, unless
+    /// they have verified control of a repository. If not set, newcomers get
+    /// the same burst as everyone else.
+    pub newcomer_burst: Option<i32>,
+    /// How often the buckets in memory are written to the database
+    pub checkpoint_interval: Duration,
+    buckets: Arc<Mutex<HashMap<i32, CachedBucket>>>,
 }
 
 impl Default for RateLimiter {
@@ -32,16 +51,40 @@ impl Default for RateLimiter {
             .parse()
             .ok()
             .unwrap_or(5);
+        let newcomer_burst = dotenvy::var("WEB_NEWCOMER_PKG_RATE_LIMIT_BURST")
+            .unwrap_or_default()
+            .parse()
+            .ok();
+        let checkpoint_seconds = dotenvy::var("WEB_NEW_PKG_RATE_LIMIT_CHECKPOINT_SECONDS")
+            .unwrap_or_default()
+            .parse()
+            .ok()
+            .unwrap_or(10);
+
+        let mut rate_limiter = Self::new(Duration::from_secs(60) * minutes, burst, newcomer_burst);
+        rate_limiter.checkpoint_interval = Duration::from_secs(checkpoint_seconds);
+        rate_limiter
+    }
+}
+
+impl RateLimiter {
+    pub fn new(rate: Duration, burst: i32, newcomer_burst: Option<i32>) -> Self {
         Self {
-            rate: Duration::from_secs(60) * minutes,
+            rate,
             burst,
+            newcomer_burst,
+            checkpoint_interval: Duration::from_secs(10),
+            buckets: Default::default(),
         }
     }
-}
 
-impl RateLimiter {
-    pub fn check_rate_limit(&self, uploader: i32, conn: &mut PgConnection) -> AppResult<()> {
-        let bucket = self.take_token(uploader, Utc::now().naive_utc(), conn)?;
+    pub fn check_rate_limit(
+        &self,
+        uploader: i32,
+        repository: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> AppResult<()> {
+        let bucket = self.take_token(uploader, repository, Utc::now().naive_utc(), conn)?;
         if bucket.tokens >= 1 {
             Ok(())
         } else {
@@ -62,14 +105,13 @@ impl RateLimiter {
     fn take_token(
         &self,
         uploader: i32,
+        repository: Option<&str>,
         now: NaiveDateTime,
         conn: &mut PgConnection,
     ) -> QueryResult<Bucket> {
-        use self::publish_limit_buckets::dsl::*;
-
         let performed_action = LimitedAction::PublishNew;
 
-        let burst: i32 = publish_rate_overrides::table
+        let overridden_burst: Option<i32> = publish_rate_overrides::table
             .find((uploader, performed_action))
             .filter(
                 publish_rate_overrides::expires_at
@@ -78,43 +120,127 @@ impl RateLimiter {
             )
             .select(publish_rate_overrides::burst)
             .first(conn)
-            .optional()?
-            .unwrap_or(self.burst);
-
-        // Interval division is poorly defined in general (what is 1 month / 30 days?)
-        // However, for the intervals we're dealing with, it is always well
-        // defined, so we convert to an f64 of seconds to represent this.
-        let tokens_to_add = floor(
-            (date_part("epoch", now) - date_part("epoch", last_refill))
-                / interval_part("epoch", self.refill_rate()),
-        );
-
-        diesel::insert_into(publish_limit_buckets)
-            .values((
-                user_id.eq(uploader),
-                action.eq(performed_action),
-                tokens.eq(burst),
-                last_refill.eq(now),
-            ))
-            .on_conflict((user_id, action))
-            .do_update()
-            .set((
-                tokens.eq(least(burst, greatest(0, tokens - 1) + tokens_to_add)),
-                last_refill
-                    .eq(last_refill + self.refill_rate().into_sql::<Interval>() * tokens_to_add),
-            ))
-            .get_result(conn)
+            .optional()?;
+
+        let burst = match (overridden_burst, self.newcomer_burst) {
+            (Some(burst), _) => burst,
+            (None, Some(newcomer_burst)) if is_unverified_newcomer(uploader, repository, conn)? => {
+                newcomer_burst
+            }
+            (None, _) => self.burst,
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        let cached = match buckets.entry(uploader) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let bucket = publish_limit_buckets::table
+                    .find((uploader, performed_action))
+                    .first::<Bucket>(conn)
+                    .optional()?;
+
+                let Some(bucket) = bucket else {
+                    let bucket = Bucket {
+                        user_id: uploader,
+                        tokens: burst,
+                        last_refill: now,
+                        action: performed_action,
+                    };
+                    entry.insert(CachedBucket {
+                        bucket,
+                        dirty: true,
+                    });
+                    return Ok(bucket);
+                };
+
+                entry.insert(CachedBucket {
+                    bucket,
+                    dirty: false,
+                })
+            }
+        };
+
+        let bucket = &mut cached.bucket;
+        let rate = self.rate.as_micros() as i64;
+        let elapsed = (now - bucket.last_refill).num_microseconds();
+        let tokens_to_add = match elapsed {
+            Some(elapsed) if rate > 0 => elapsed.div_euclid(rate),
+            _ => 0,
+        };
+
+        let tokens = i64::from((bucket.tokens - 1).max(0)) + tokens_to_add;
+        bucket.tokens = tokens.min(i64::from(burst)) as i32;
+        // `last_refill` is only advanced by whole multiples of the rate, so
+        // that partial refills are not lost
+        bucket.last_refill += chrono::Duration::microseconds(rate * tokens_to_add);
+        cached.dirty = true;
+
+        Ok(*bucket)
     }
 
-    fn refill_rate(&self) -> PgInterval {
-        use diesel::dsl::*;
-        (self.rate.as_millis() as i64).milliseconds()
+    /// Writes the buckets that changed since the last checkpoint to the
+    /// `publish_limit_buckets` table, and returns how many were written.
+    ///
+    /// All buckets are removed from memory, so that they are loaded from the
+    /// table again, including the changes of other server instances, and so
+    /// that the buckets of inactive users don't pile up. This also happens if
+    /// writing fails, e.g. because one of the users was deleted in the
+    /// meantime, so that a single bucket can't block all future checkpoints.
+    pub fn checkpoint(&self, conn: &mut PgConnection) -> QueryResult<usize> {
+        use self::publish_limit_buckets::dsl::*;
+
+        // The lock is held while writing, so that a bucket can't be loaded
+        // from the table before its changes are written.
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        let changed = std::mem::take(&mut *buckets)
+            .into_values()
+            .filter(|cached| cached.dirty)
+            .map(|cached| cached.bucket)
+            .collect::<Vec<_>>();
+
+        if !changed.is_empty() {
+            diesel::insert_into(publish_limit_buckets)
+                .values(&changed)
+                .on_conflict((user_id, action))
+                .do_update()
+                .set((
+                    tokens.eq(excluded(tokens)),
+                    last_refill.eq(excluded(last_refill)),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(changed.len())
     }
 }
 
+/// Newcomers are users that have not published any versions yet. They can
+/// skip the stricter rate limit by verifying control of the repository of the
+/// crate that they are publishing.
+fn is_unverified_newcomer(
+    uploader: i32,
+    repository: Option<&str>,
+    conn: &mut PgConnection,
+) -> QueryResult<bool> {
+    let published_versions = versions::table.filter(versions::published_by.eq(uploader));
+    let has_published: bool = diesel::select(exists(published_versions)).get_result(conn)?;
+    if has_published {
+        return Ok(false);
+    }
+
+    let Some(repository) = repository.and_then(parse_github_repository) else {
+        return Ok(true);
+    };
+
+    Ok(!RepositoryVerification::is_verified(
+        uploader,
+        &repository,
+        conn,
+    )?)
+}
+
 #[derive(Queryable, Insertable, Debug, PartialEq, Clone, Copy)]
 #[diesel(table_name = publish_limit_buckets, check_for_backend(diesel::pg::Pg))]
-#[allow(dead_code)] // Most fields only read in tests
 struct Bucket {
     user_id: i32,
     tokens: i32,
@@ -122,6 +248,13 @@ struct Bucket {
     action: LimitedAction,
 }
 
+#[derive(Debug)]
+struct CachedBucket {
+    bucket: Bucket,
+    /// Whether the bucket changed since it was loaded or last checkpointed
+    dirty: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,11 +266,8 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
-        let bucket = rate.take_token(new_user(conn, "user1")?, now, conn)?;
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
+        let bucket = rate.take_token(new_user(conn, "user1")?, None, now, conn)?;
         let expected = Bucket {
             user_id: bucket.user_id,
             tokens: 10,
@@ -146,11 +276,8 @@ mod tests {
         };
         assert_eq!(expected, bucket);
 
-        let rate = RateLimiter {
-            rate: Duration::from_millis(50),
-            burst: 20,
-        };
-        let bucket = rate.take_token(new_user(conn, "user2")?, now, conn)?;
+        let rate = RateLimiter::new(Duration::from_millis(50), 20, None);
+        let bucket = rate.take_token(new_user(conn, "user2")?, None, now, conn)?;
         let expected = Bucket {
             user_id: bucket.user_id,
             tokens: 20,
@@ -166,12 +293,9 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user_bucket(conn, 5, now)?.user_id;
-        let bucket = rate.take_token(user_id, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
         let expected = Bucket {
             user_id,
             tokens: 4,
@@ -187,13 +311,10 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user_bucket(conn, 5, now)?.user_id;
         let refill_time = now + chrono::Duration::seconds(2);
-        let bucket = rate.take_token(user_id, refill_time, conn)?;
+        let bucket = rate.take_token(user_id, None, refill_time, conn)?;
         let expected = Bucket {
             user_id,
             tokens: 6,
@@ -213,13 +334,10 @@ mod tests {
             NaiveDateTime::parse_from_str("2019-03-19T21:11:24.620401", "%Y-%m-%dT%H:%M:%S%.f")
                 .unwrap();
 
-        let rate = RateLimiter {
-            rate: Duration::from_millis(100),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_millis(100), 10, None);
         let user_id = new_user_bucket(conn, 5, now)?.user_id;
         let refill_time = now + chrono::Duration::milliseconds(300);
-        let bucket = rate.take_token(user_id, refill_time, conn)?;
+        let bucket = rate.take_token(user_id, None, refill_time, conn)?;
         let expected = Bucket {
             user_id,
             tokens: 7,
@@ -235,12 +353,14 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_millis(100),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_millis(100), 10, None);
         let user_id = new_user_bucket(conn, 5, now)?.user_id;
-        let bucket = rate.take_token(user_id, now + chrono::Duration::milliseconds(250), conn)?;
+        let bucket = rate.take_token(
+            user_id,
+            None,
+            now + chrono::Duration::milliseconds(250),
+            conn,
+        )?;
         let expected_refill_time = now + chrono::Duration::milliseconds(200);
         let expected = Bucket {
             user_id,
@@ -257,12 +377,9 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user_bucket(conn, 1, now)?.user_id;
-        let bucket = rate.take_token(user_id, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
         let expected = Bucket {
             user_id,
             tokens: 0,
@@ -271,7 +388,7 @@ mod tests {
         };
         assert_eq!(expected, bucket);
 
-        let bucket = rate.take_token(user_id, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
         assert_eq!(expected, bucket);
         Ok(())
     }
@@ -281,13 +398,10 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user_bucket(conn, 0, now)?.user_id;
         let refill_time = now + chrono::Duration::seconds(1);
-        let bucket = rate.take_token(user_id, refill_time, conn)?;
+        let bucket = rate.take_token(user_id, None, refill_time, conn)?;
         let expected = Bucket {
             user_id,
             tokens: 1,
@@ -304,13 +418,10 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user_bucket(conn, 8, now)?.user_id;
         let refill_time = now + chrono::Duration::seconds(4);
-        let bucket = rate.take_token(user_id, refill_time, conn)?;
+        let bucket = rate.take_token(user_id, None, refill_time, conn)?;
         let expected = Bucket {
             user_id,
             tokens: 10,
@@ -323,14 +434,86 @@ mod tests {
     }
 
     #[test]
-    fn override_is_used_instead_of_global_burst_if_present() -> QueryResult<()> {
+    fn buckets_are_only_written_to_the_database_on_checkpoint() -> QueryResult<()> {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
+        let user_id = new_user(conn, "user1")?;
+        rate.take_token(user_id, None, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
+        assert_eq!(9, bucket.tokens);
+
+        let stored = publish_limit_buckets::table
+            .find((user_id, LimitedAction::PublishNew))
+            .first::<Bucket>(conn)
+            .optional()?;
+        assert_eq!(None, stored);
+
+        assert_eq!(1, rate.checkpoint(conn)?);
+        let stored = publish_limit_buckets::table
+            .find((user_id, LimitedAction::PublishNew))
+            .first::<Bucket>(conn)?;
+        assert_eq!(bucket, stored);
+
+        // Nothing changed since the last checkpoint
+        assert_eq!(0, rate.checkpoint(conn)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bucket_state_is_kept_when_the_limiter_is_recreated() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
+        let user_id = new_user(conn, "user1")?;
+        rate.take_token(user_id, None, now, conn)?;
+        rate.take_token(user_id, None, now, conn)?;
+        rate.checkpoint(conn)?;
+        drop(rate);
+
+        // Simulate a restart of the application by using a fresh limiter,
+        // which has to load the bucket from the database
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
+        let bucket = rate.take_token(user_id, None, now, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 8,
+            last_refill: now,
+            action: LimitedAction::PublishNew,
         };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn buckets_are_reloaded_after_checkpoint() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
+        let user_id = new_user_bucket(conn, 5, now)?.user_id;
+        rate.take_token(user_id, None, now, conn)?;
+        rate.checkpoint(conn)?;
+
+        // Changes of other server instances are picked up after a checkpoint
+        diesel::update(publish_limit_buckets::table)
+            .filter(publish_limit_buckets::user_id.eq(user_id))
+            .set(publish_limit_buckets::tokens.eq(1))
+            .execute(conn)?;
+
+        let bucket = rate.take_token(user_id, None, now, conn)?;
+        assert_eq!(0, bucket.tokens);
+        Ok(())
+    }
+
+    #[test]
+    fn override_is_used_instead_of_global_burst_if_present() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user(conn, "user1")?;
         let other_user_id = new_user(conn, "user2")?;
 
@@ -341,8 +524,8 @@ mod tests {
             ))
             .execute(conn)?;
 
-        let bucket = rate.take_token(user_id, now, conn)?;
-        let other_bucket = rate.take_token(other_user_id, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
+        let other_bucket = rate.take_token(other_user_id, None, now, conn)?;
 
         assert_eq!(20, bucket.tokens);
         assert_eq!(10, other_bucket.tokens);
@@ -354,10 +537,7 @@ mod tests {
         let conn = &mut pg_connection();
         let now = now();
 
-        let rate = RateLimiter {
-            rate: Duration::from_secs(1),
-            burst: 10,
-        };
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, None);
         let user_id = new_user(conn, "user1")?;
         let other_user_id = new_user(conn, "user2")?;
 
@@ -369,8 +549,8 @@ mod tests {
             ))
             .execute(conn)?;
 
-        let bucket = rate.take_token(user_id, now, conn)?;
-        let other_bucket = rate.take_token(other_user_id, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
+        let other_bucket = rate.take_token(other_user_id, None, now, conn)?;
 
         assert_eq!(20, bucket.tokens);
         assert_eq!(10, other_bucket.tokens);
@@ -381,8 +561,8 @@ mod tests {
             .filter(publish_rate_overrides::user_id.eq(user_id))
             .execute(conn)?;
 
-        let bucket = rate.take_token(user_id, now, conn)?;
-        let other_bucket = rate.take_token(other_user_id, now, conn)?;
+        let bucket = rate.take_token(user_id, None, now, conn)?;
+        let other_bucket = rate.take_token(other_user_id, None, now, conn)?;
 
         // The number of tokens of user_id is 10 and not 9 because when the new burst limit is
         // lower than the amount of available tokens, the number of available tokens is reset to
@@ -393,6 +573,45 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn newcomer_burst_is_used_unless_repository_is_verified() -> QueryResult<()> {
+        use crate::schema::repository_verifications;
+
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = RateLimiter::new(Duration::from_secs(1), 10, Some(2));
+        let newcomer_id = new_user(conn, "user1")?;
+        let verified_id = new_user(conn, "user2")?;
+        let other_repository_id = new_user(conn, "user3")?;
+
+        for user_id in [verified_id, other_repository_id] {
+            diesel::insert_into(repository_verifications::table)
+                .values((
+                    repository_verifications::user_id.eq(user_id),
+                    repository_verifications::repository.eq("User2/repo"),
+                    repository_verifications::challenge.eq("challenge"),
+                    repository_verifications::verified_at.eq(now),
+                ))
+                .execute(conn)?;
+        }
+
+        let repository = Some("https://github.com/user2/repo");
+        let bucket = rate.take_token(newcomer_id, repository, now, conn)?;
+        let verified_bucket = rate.take_token(verified_id, repository, now, conn)?;
+
+        // The verified repository doesn't help with crates of other repositories
+        let other_repository = Some("https://github.com/user3/repo");
+        let other_repository_bucket =
+            rate.take_token(other_repository_id, other_repository, now, conn)?;
+
+        assert_eq!(2, bucket.tokens);
+        assert_eq!(10, verified_bucket.tokens);
+        assert_eq!(2, other_repository_bucket.tokens);
+
+        Ok(())
+    }
+
     fn new_user(conn: &mut PgConnection, gh_login: &str) -> QueryResult<i32> {
         use crate::models::NewUser;
 
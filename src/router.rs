@@ -23,7 +23,8 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/owners",
             get(krate::owners::owners)
                 .put(krate::owners::add_owners)
-                .delete(krate::owners::remove_owners),
+                .delete(krate::owners::remove_owners)
+                .patch(krate::owners::batch_modify_owners),
         )
         .route(
             "/api/v1/crates/:crate_id/:version/yank",
@@ -37,6 +38,14 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/:version/download",
             get(version::downloads::download),
         )
+        .route(
+            "/api/v1/crates/:crate_id/download",
+            get(version::downloads::download_matching),
+        )
+        .route("/api/v1/changes", get(version::changes::index))
+        .route("/api/v1/changes/yanks", get(version::changes::yanks))
+        .route("/api/v1/osv", get(osv::index))
+        .route("/api/v1/osv/:osv_id", get(osv::show))
         // Routes that appear to be unused
         .route("/api/v1/versions", get(version::deprecated::index))
         .route(
@@ -44,7 +53,10 @@ pub fn build_axum_router(state: AppState) -> Router {
             get(version::deprecated::show_by_id),
         )
         // Routes used by the frontend
-        .route("/api/v1/crates/:crate_id", get(krate::metadata::show))
+        .route(
+            "/api/v1/crates/:crate_id",
+            get(krate::metadata::show).patch(krate::update::update),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version",
             get(version::metadata::show),
@@ -53,6 +65,18 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/:version/readme",
             get(krate::metadata::readme),
         )
+        .route(
+            "/api/v1/crates/:crate_id/:version/readme/previous",
+            get(version::readme::previous),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/:version/readme/restore",
+            put(version::readme::restore),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/:version/license-text",
+            get(krate::metadata::license_text),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/dependencies",
             get(version::metadata::dependencies),
@@ -61,14 +85,38 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/:version/downloads",
             get(version::downloads::downloads),
         )
+        .route(
+            "/api/v1/crates/:crate_id/:version/reproducibility",
+            get(version::metadata::reproducibility),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/:version/archive",
+            get(version::metadata::archive),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/authors",
             get(version::metadata::authors),
         )
+        .route(
+            "/api/v1/crates/:crate_id/badge.json",
+            get(krate::badge::badge),
+        )
         .route(
             "/api/v1/crates/:crate_id/downloads",
             get(krate::downloads::downloads),
         )
+        .route(
+            "/api/v1/crates/:crate_id/downloads/total",
+            get(krate::downloads::total_downloads),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/download_lines",
+            get(krate::downloads::download_lines),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/feature_usage",
+            get(krate::feature_usage::feature_usage),
+        )
         .route(
             "/api/v1/crates/:crate_id/versions",
             get(krate::metadata::versions),
@@ -81,6 +129,10 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/following",
             get(krate::follow::following),
         )
+        .route(
+            "/api/v1/crates/:crate_id/owner_invitations/:login/resend",
+            put(krate::owners::resend_invitation),
+        )
         .route(
             "/api/v1/crates/:crate_id/owner_team",
             get(krate::owners::owner_team),
@@ -93,10 +145,36 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/reverse_dependencies",
             get(krate::metadata::reverse_dependencies),
         )
+        .route(
+            "/api/v1/crates/:crate_id/support_windows",
+            get(krate::support_windows::list),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/support_windows/:major_version",
+            put(krate::support_windows::update).delete(krate::support_windows::delete),
+        )
         .route("/api/v1/keywords", get(keyword::index))
         .route("/api/v1/keywords/:keyword_id", get(keyword::show))
-        .route("/api/v1/categories", get(category::index))
-        .route("/api/v1/categories/:category_id", get(category::show))
+        .route(
+            "/api/v1/keywords/:keyword_id/subscribe",
+            put(subscription::subscribe_keyword).delete(subscription::unsubscribe_keyword),
+        )
+        .route(
+            "/api/v1/categories",
+            get(category::index).post(category::create),
+        )
+        .route(
+            "/api/v1/categories/:category_id",
+            get(category::show).patch(category::update),
+        )
+        .route(
+            "/api/v1/categories/:category_id/merge",
+            put(category::merge),
+        )
+        .route(
+            "/api/v1/categories/:category_id/subscribe",
+            put(subscription::subscribe_category).delete(subscription::unsubscribe_category),
+        )
         .route("/api/v1/category_slugs", get(category::slugs))
         .route(
             "/api/v1/users/:user_id",
@@ -106,6 +184,30 @@ pub fn build_axum_router(state: AppState) -> Router {
         .route("/api/v1/teams/:team_id", get(team::show_team))
         .route("/api/v1/me", get(user::me::me))
         .route("/api/v1/me/updates", get(user::me::updates))
+        .route(
+            "/api/v1/me/publishable-crates",
+            get(user::me::publishable_crates),
+        )
+        .route(
+            "/api/v1/me/follows",
+            get(krate::follow::list).put(krate::follow::bulk_update),
+        )
+        .route(
+            "/api/v1/me/subscriptions",
+            get(subscription::list).put(subscription::update),
+        )
+        .route(
+            "/api/v1/me/subscriptions/updates",
+            get(subscription::updates),
+        )
+        .route(
+            "/api/v1/me/repository_verification",
+            get(user::repository_verification::show).put(user::repository_verification::start),
+        )
+        .route(
+            "/api/v1/me/repository_verification/verify",
+            put(user::repository_verification::verify),
+        )
         .route("/api/v1/me/tokens", get(token::list).put(token::new))
         .route("/api/v1/me/tokens/:id", delete(token::revoke))
         .route("/api/v1/tokens/current", delete(token::revoke_current))
@@ -126,6 +228,10 @@ pub fn build_axum_router(state: AppState) -> Router {
             put(user::me::update_email_notifications),
         )
         .route("/api/v1/summary", get(krate::metadata::summary))
+        .route(
+            "/api/v1/publish_diagnostics/:token",
+            get(krate::publish_diagnostics::show),
+        )
         .route(
             "/api/v1/confirm/:email_token",
             put(user::me::confirm_user_email),
@@ -138,6 +244,8 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/site_metadata",
             get(site_metadata::show_deployed_sha),
         )
+        .route("/api/v1/stats/msrv", get(stats::msrv))
+        .route("/api/v1/status/propagation", get(status::propagation))
         // Session management
         .route("/api/private/session/begin", get(user::session::begin))
         .route(
@@ -17,6 +17,60 @@ pub mod sql_types {
     pub use diesel_full_text_search::Tsvector;
 }
 
+diesel::table! {
+    /// Accounts that were put into compromise mode by the crates.io team. New publishes from these accounts are held until the compromise is resolved.
+    account_compromises (id) {
+        /// The `id` column of the `account_compromises` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `account_compromises` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The crates.io team member that put the account into compromise mode
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        admin_id -> Int4,
+        /// The `reason` column of the `account_compromises` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Varchar,
+        /// Number of API tokens that were revoked
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        revoked_tokens -> Int4,
+        /// Number of recently published versions that were added to the moderation queue
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        flagged_versions -> Int4,
+        /// The `created_at` column of the `account_compromises` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// Point in time at which the compromise was resolved, or NULL if new publishes are still held
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     /// Representation of the `api_tokens` table.
     ///
@@ -77,6 +131,84 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Sums of the daily downloads of versions in the `version_downloads` partitions that were archived and dropped, so that the download counts can still be recomputed
+    archived_version_downloads (version_id) {
+        /// The `version_id` column of the `archived_version_downloads` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// Sum of the `downloads` column of the dropped rows
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int8,
+        /// Sum of the `counted` column of the dropped rows
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        counted -> Int8,
+    }
+}
+
+diesel::table! {
+    /// Security relevant events like logins, token anomalies and admin actions, which are shipped to an external audit sink
+    audit_events (id) {
+        /// The `id` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// Kind of the event, e.g. `login` or `admin.delete_crate`
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// User that caused the event, if any
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Nullable<Int4>,
+        /// API token that was used, if any
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        api_token_id -> Nullable<Int4>,
+        /// IP address of the client, as reported by the `X-Real-Ip` header
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ip_address -> Nullable<Varchar>,
+        /// Additional event specific information
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        details -> Jsonb,
+        /// The `created_at` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// Time at which the event was shipped to the audit sink, or NULL if it has not been shipped yet
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        exported_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     /// Representation of the `background_jobs` table.
     ///
@@ -124,6 +256,8 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         priority -> Int2,
+        /// Name of the queue of the job. Each queue is processed by its own runner, so that slow jobs don't hold up the jobs of other queues.
+        queue -> Varchar,
     }
 }
 
@@ -197,6 +331,54 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
+        /// Time at which the category was deprecated, or NULL. Deprecated categories can no longer be assigned to crates.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deprecated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Slugs of renamed or merged categories, and the categories that they now point to
+    category_redirects (old_slug) {
+        /// Previous slug of the category
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        old_slug -> Varchar,
+        /// Category that the old slug redirects to
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        category_id -> Int4,
+        /// The `created_at` column of the `category_redirects` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Categories that users want to be notified about when crates in them, or in their subcategories, are published
+    category_subscriptions (user_id, category_id) {
+        /// The `user_id` column of the `category_subscriptions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `category_id` column of the `category_subscriptions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        category_id -> Int4,
     }
 }
 
@@ -241,6 +423,8 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         token_generated_at -> Nullable<Timestamp>,
+        /// Point in time at which the invited user and the inviter were notified that the invitation expired
+        expiry_notified_at -> Nullable<Timestamp>,
     }
 }
 
@@ -380,6 +564,10 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         max_upload_size -> Nullable<Int4>,
+        /// Point in time at which the oldest existing version of the crate was published, maintained by the `trigger_versions_update_crate_activity` trigger
+        first_version_published_at -> Nullable<Timestamp>,
+        /// Point in time at which a version of the crate was last published, yanked or unyanked, maintained by the `trigger_versions_update_crate_activity` trigger
+        last_activity_at -> Nullable<Timestamp>,
     }
 }
 
@@ -423,6 +611,70 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Dependencies of versions on crates that are hosted on other registries
+    cross_registry_dependencies (id) {
+        /// The `id` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `version_id` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The index URL of the registry that hosts the dependency
+        registry -> Varchar,
+        /// The name of the crate on the other registry
+        name -> Varchar,
+        /// The `req` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        req -> Varchar,
+        /// The `optional` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        optional -> Bool,
+        /// The `default_features` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        default_features -> Bool,
+        /// The `features` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        features -> Array<Text>,
+        /// The `target` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target -> Nullable<Varchar>,
+        /// The `kind` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Int4,
+        /// The `explicit_name` column of the `cross_registry_dependencies` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        explicit_name -> Nullable<Varchar>,
+    }
+}
+
 diesel::table! {
     /// Representation of the `dependencies` table.
     ///
@@ -488,97 +740,97 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         explicit_name -> Nullable<Varchar>,
+        /// The interned `req` of the dependency. NULL for dependencies that were inserted before the requirements were interned, until they are backfilled
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        requirement_id -> Nullable<Int4>,
     }
 }
 
 diesel::table! {
-    /// Representation of the `emails` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    emails (id) {
-        /// The `id` column of the `emails` table.
+    /// Version requirement strings of dependencies, which are stored once and referenced by the `dependencies` table, since most crates use the same few requirements
+    dependency_requirements (id) {
+        /// The `id` column of the `dependency_requirements` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `user_id` column of the `emails` table.
-        ///
-        /// Its SQL type is `Int4`.
-        ///
-        /// (Automatically generated by Diesel.)
-        user_id -> Int4,
-        /// The `email` column of the `emails` table.
+        /// The `req` column of the `dependency_requirements` table.
         ///
         /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        email -> Varchar,
-        /// The `verified` column of the `emails` table.
+        req -> Varchar,
+    }
+}
+
+diesel::table! {
+    /// How often users receive the email digest of their keyword and category subscriptions. Users without a row receive a weekly digest.
+    digest_preferences (user_id) {
+        /// The `user_id` column of the `digest_preferences` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        verified -> Bool,
-        /// The `token` column of the `emails` table.
+        user_id -> Int4,
+        /// 0 = never, 1 = daily, 2 = weekly
         ///
-        /// Its SQL type is `Text`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        token -> Text,
-        /// The `token_generated_at` column of the `emails` table.
+        frequency -> Int4,
+        /// Time up to which publishes were included in the last digest
         ///
         /// Its SQL type is `Nullable<Timestamp>`.
         ///
         /// (Automatically generated by Diesel.)
-        token_generated_at -> Nullable<Timestamp>,
+        last_sent_at -> Nullable<Timestamp>,
     }
 }
 
 diesel::table! {
-    /// Representation of the `follows` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    follows (user_id, crate_id) {
-        /// The `user_id` column of the `follows` table.
+    /// Days on which a crate was downloaded unusually often compared to its baseline, e.g. because of artificial inflation
+    download_anomalies (crate_id, date) {
+        /// The `crate_id` column of the `download_anomalies` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        user_id -> Int4,
-        /// The `crate_id` column of the `follows` table.
+        crate_id -> Int4,
+        /// The `date` column of the `download_anomalies` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Date`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
-    }
-}
-
-diesel::table! {
-    /// Representation of the `keywords` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    keywords (id) {
-        /// The `id` column of the `keywords` table.
+        date -> Date,
+        /// Number of downloads of all versions of the crate on that day
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Int8`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int4,
-        /// The `keyword` column of the `keywords` table.
+        downloads -> Int8,
+        /// Average number of daily downloads of the crate in the days before
         ///
-        /// Its SQL type is `Text`.
+        /// Its SQL type is `Float8`.
         ///
         /// (Automatically generated by Diesel.)
-        keyword -> Text,
-        /// The `crates_cnt` column of the `keywords` table.
+        baseline_mean -> Float8,
+        /// Standard deviation of the daily downloads of the crate in the days before
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Float8`.
         ///
         /// (Automatically generated by Diesel.)
-        crates_cnt -> Int4,
-        /// The `created_at` column of the `keywords` table.
+        baseline_stddev -> Float8,
+        /// Number of downloads above the baseline that were subtracted from the public download counts of the crate
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        excluded_downloads -> Int8,
+        /// The `created_at` column of the `download_anomalies` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
@@ -588,18 +840,396 @@ diesel::table! {
 }
 
 diesel::table! {
-    /// Representation of the `metadata` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    metadata (total_downloads) {
-        /// The `total_downloads` column of the `metadata` table.
+    /// Random sample of the download redirects, which is pruned after 30 days
+    download_redirect_samples (id) {
+        /// The `id` column of the `download_redirect_samples` table.
         ///
         /// Its SQL type is `Int8`.
         ///
         /// (Automatically generated by Diesel.)
-        total_downloads -> Int8,
-    }
-}
+        id -> Int8,
+        /// Crate name as requested by the client, which might not be canonical
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Varchar,
+        /// The `version` column of the `download_redirect_samples` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version -> Varchar,
+        /// Coarse class of the `User-Agent` header, e.g. `cargo/1.72` or `browser`
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent_class -> Varchar,
+        /// Host that the client was redirected to, or `archive` if the file was served from the archive storage
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        cdn -> Varchar,
+        /// Fraction of the download redirects that were sampled at the time, to extrapolate totals
+        ///
+        /// Its SQL type is `Float8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sample_rate -> Float8,
+        /// The `sampled_at` column of the `download_redirect_samples` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sampled_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `emails` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    emails (id) {
+        /// The `id` column of the `emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `email` column of the `emails` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Varchar,
+        /// The `verified` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified -> Bool,
+        /// The `token` column of the `emails` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token -> Text,
+        /// The `token_generated_at` column of the `emails` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_generated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Background jobs of queues with a retry limit that failed too often, and are not retried anymore
+    failed_background_jobs (id) {
+        /// ID of the job in the `background_jobs` table
+        id -> Int8,
+        /// The `job_type` column of the `failed_background_jobs` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        job_type -> Text,
+        /// The `data` column of the `failed_background_jobs` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        data -> Jsonb,
+        /// The `queue` column of the `failed_background_jobs` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        queue -> Varchar,
+        /// Number of failed attempts to run the job
+        retries -> Int4,
+        /// Point in time at which the job was enqueued
+        created_at -> Timestamp,
+        /// Point in time at which the job failed for the last time
+        failed_at -> Timestamp,
+        /// Error of the last failed attempt to run the job
+        error -> Text,
+    }
+}
+
+diesel::table! {
+    /// Daily snapshots of how many dependent crates request each feature of a crate
+    features_usage (crate_id, feature, date) {
+        /// The `crate_id` column of the `features_usage` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// Name of the feature, or `default` for dependents that use the default features
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        feature -> Varchar,
+        /// Date of the snapshot
+        ///
+        /// Its SQL type is `Date`.
+        ///
+        /// (Automatically generated by Diesel.)
+        date -> Date,
+        /// Number of crates whose newest non-yanked version requests the feature in a normal or build dependency on the crate
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        dependents -> Int4,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `follows` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    follows (user_id, crate_id) {
+        /// The `user_id` column of the `follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `crate_id` column of the `follows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// 0 = new releases only, 1 = all activity, including yanks and ownership changes
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        mode -> Int4,
+        /// 0 = dashboard feed only, 1 = also included in the email digest
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        channel -> Int4,
+    }
+}
+
+diesel::table! {
+    /// When the index files of crates were last synced to the git and sparse indexes
+    index_sync_times (crate_id) {
+        /// The `crate_id` column of the `index_sync_times` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// Time at which the index data was read for the last successful push to the git index
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        git_synced_at -> Nullable<Timestamp>,
+        /// Time at which the index data was read for the last successful upload to the sparse index, including the CDN invalidation
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sparse_synced_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Keywords that users want to be notified about when crates with them are published
+    keyword_subscriptions (user_id, keyword_id) {
+        /// The `user_id` column of the `keyword_subscriptions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `keyword_id` column of the `keyword_subscriptions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword_id -> Int4,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `keywords` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    keywords (id) {
+        /// The `id` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `keyword` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword -> Text,
+        /// The `crates_cnt` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crates_cnt -> Int4,
+        /// The `created_at` column of the `keywords` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `metadata` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    metadata (total_downloads) {
+        /// The `total_downloads` column of the `metadata` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        total_downloads -> Int8,
+    }
+}
+
+diesel::table! {
+    /// Crates that were flagged automatically and need to be reviewed by the crates.io team
+    moderation_queue (id) {
+        /// The `id` column of the `moderation_queue` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `moderation_queue` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The version that caused the crate to be flagged, if any
+        version_id -> Nullable<Int4>,
+        /// Machine readable reason for the flag, e.g. `duplicate_content`
+        reason -> Varchar,
+        /// Reason specific details, e.g. the crate with similar content
+        details -> Jsonb,
+        /// The `created_at` column of the `moderation_queue` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// Point in time at which the flag was reviewed, or NULL if it still needs to be reviewed
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Daily snapshots of the `rust-version` values that recently published versions declare
+    msrv_stats (date, rust_version) {
+        /// Date of the snapshot
+        ///
+        /// Its SQL type is `Date`.
+        ///
+        /// (Automatically generated by Diesel.)
+        date -> Date,
+        /// The declared `rust-version`, truncated to the major and minor version, or an empty string for versions that do not declare one
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        rust_version -> Varchar,
+        /// Number of non-yanked versions published in the period before the snapshot that declare the `rust-version`
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_count -> Int4,
+        /// Number of distinct crates of these versions
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_count -> Int4,
+    }
+}
+
+diesel::table! {
+    /// Context of crate files that were rejected on publish, kept for a short time to debug support requests
+    publish_diagnostics (token) {
+        /// Random token that is included in the publish error, with which the crates.io team can look up the context
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token -> Varchar,
+        /// The user that tried to publish the crate file
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `crate_name` column of the `publish_diagnostics` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Varchar,
+        /// The `version` column of the `publish_diagnostics` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version -> Varchar,
+        /// The error message that was returned to the user
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        error -> Varchar,
+        /// Summary of the rejected crate file, like its manifest and the first entries of the archive
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        context -> Jsonb,
+        /// The `created_at` column of the `publish_diagnostics` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// Point in time after which the context is deleted
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+    }
+}
 
 diesel::table! {
     /// Representation of the `publish_limit_buckets` table.
@@ -682,6 +1312,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         rendered_at -> Timestamp,
+        /// Reason why the README could not be rendered, in which case a placeholder was uploaded instead
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        error -> Nullable<Varchar>,
     }
 }
 
@@ -703,6 +1339,78 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Results of the periodic checks whether the repositories that crates link to still exist
+    repository_checks (crate_id) {
+        /// The `crate_id` column of the `repository_checks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The repository URL of the crate at the time of the check
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository -> Varchar,
+        /// Either `available`, `archived` or `not_found`
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// URL that the repository redirects to if it was renamed or transferred, or NULL otherwise
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redirected_to -> Nullable<Varchar>,
+        /// Time of the latest check
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        checked_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Repositories that users have proven control of, which exempts them from the stricter rate limits for newcomers
+    repository_verifications (user_id) {
+        /// The `user_id` column of the `repository_verifications` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// GitHub repository in the `owner/name` form
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository -> Varchar,
+        /// Random value that has to be pushed to the repository, either as the content of the `.crates-io-verification` file or as the `crates-io-verification-<challenge>` tag
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        challenge -> Varchar,
+        /// Time at which the challenge was issued
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// Time at which the challenge was found in the repository, or NULL if it has not been verified yet
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     /// Representation of the `reserved_crate_names` table.
     ///
@@ -717,6 +1425,86 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Versions that were yanked for security reasons, which are exported as OSV records
+    security_yanks (id) {
+        /// The `id` column of the `security_yanks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `version_id` column of the `security_yanks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// Link to the security advisory that was given when the version was yanked
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        advisory_url -> Varchar,
+        /// Time at which the version was first yanked for security reasons
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        published_at -> Timestamp,
+        /// Time at which the record was last changed
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        modified_at -> Timestamp,
+        /// Time at which the version was unyanked, if it is no longer considered affected
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        withdrawn_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Popular crates with a single owner, whose owner is asked to add another owner
+    single_owner_nudges (crate_id) {
+        /// The crate that only has a single owner
+        crate_id -> Int4,
+        /// The only owner of the crate
+        user_id -> Int4,
+        /// Point in time at which the crate was first found to only have a single owner
+        flagged_at -> Timestamp,
+        /// Point in time at which the owner was last reminded by email, or NULL if no email was sent yet
+        emailed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Support status of the semver-compatible release lines of a crate, as declared by its owners
+    support_windows (crate_id, major_version) {
+        /// The `crate_id` column of the `support_windows` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The semver-compatible release line, e.g. `1` for `1.x.y` or `0.4` for `0.4.x`
+        major_version -> Varchar,
+        /// 0 = supported, 1 = security fixes only, 2 = end of life
+        status -> Int4,
+        /// Date at which the release line stops or stopped receiving any fixes
+        end_of_life_date -> Nullable<Date>,
+        /// The `updated_at` column of the `support_windows` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     /// Representation of the `teams` table.
     ///
@@ -761,6 +1549,40 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Deleted crates and versions, whose names can only be published again after a cooldown
+    tombstones (id) {
+        /// The `id` column of the `tombstones` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// Name of the deleted crate, or of the crate of the deleted version
+        crate_name -> Varchar,
+        /// Number of the deleted version, or NULL if the whole crate was deleted
+        version -> Nullable<Varchar>,
+        /// Point in time at which the crate or version was deleted
+        deleted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Versions whose crate files are missing or corrupted in the storage and could not be repaired
+    unavailable_versions (version_id) {
+        /// The `version_id` column of the `unavailable_versions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// Why the crate file is unavailable, e.g. `missing` or `empty`
+        reason -> Varchar,
+        /// Point in time at which the version was marked as unavailable
+        marked_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     /// Representation of the `users` table.
     ///
@@ -814,13 +1636,41 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         account_lock_until -> Nullable<Timestamp>,
+        /// Browser sessions of the user that were started before this point in time are no longer valid
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sessions_revoked_at -> Nullable<Timestamp>,
+        /// Whether the user is a member of the crates.io team and may use the admin endpoints of the API
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_admin -> Bool,
     }
 }
 
 diesel::table! {
-    /// Representation of the `version_downloads` table.
-    ///
-    /// (Automatically generated by Diesel.)
+    /// Versions whose crate files were moved to the archive storage tier
+    version_archives (version_id) {
+        /// The `version_id` column of the `version_archives` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// Point in time at which the crate file was copied to the archive storage tier
+        archived_at -> Timestamp,
+        /// Point in time at which the crate file was requested to be restored, or NULL if no restore was requested yet
+        restore_requested_at -> Nullable<Timestamp>,
+        /// Point in time at which the crate file was deleted from its regular location, or NULL if it was only copied to the archive storage tier so far
+        original_deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    /// Number of downloads per version and day, partitioned by month. Old partitions are archived to the storage and detached by the `manage_downloads_partitions` background job.
     version_downloads (version_id, date) {
         /// The `version_id` column of the `version_downloads` table.
         ///
@@ -896,6 +1746,78 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         time -> Timestamp,
+        /// Increasing in the order in which the rows were committed, used for paging through the change feed
+        seq -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    /// Fingerprints of the source files of published crate files, used to detect crates with duplicate content
+    version_fingerprints (version_id) {
+        /// The `version_id` column of the `version_fingerprints` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The crate of the version, to exclude other versions of the same crate when looking for duplicates
+        crate_id -> Int4,
+        /// The smallest hashes of the normalized file contents, in ascending order
+        hashes -> Array<Int8>,
+    }
+}
+
+diesel::table! {
+    /// Warnings that were returned to the publisher of a version, for versions that were published with warnings
+    version_publish_warnings (version_id) {
+        /// The `version_id` column of the `version_publish_warnings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The warnings, as a JSON array of objects with a `kind` and a `message` field
+        warnings -> Jsonb,
+        /// Point in time at which the version was published
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Results of comparing the files of a published crate file with its source repository
+    version_reproducibility (version_id) {
+        /// The `version_id` column of the `version_reproducibility` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// TRUE if all files of the crate file match the files in the repository
+        reproducible -> Bool,
+        /// The git tag that the crate file was compared against
+        git_tag -> Varchar,
+        /// The `checked_at` column of the `version_reproducibility` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        checked_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Files of published crate files that look like compiled binaries or otherwise do not belong in a source package, see `crates_io_tarball::scanner`
+    version_scan_reports (version_id) {
+        /// The `version_id` column of the `version_scan_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// The suspicious files, as a JSON array of objects with a `path` and a `kind` field
+        findings -> Jsonb,
+        /// Point in time at which the crate file was scanned
+        created_at -> Timestamp,
     }
 }
 
@@ -989,6 +1911,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         rust_version -> Nullable<Varchar>,
+        /// Whether the `.cargo_vcs_info.json` file of the crate file reported uncommitted changes in the git working tree, in which case the recorded commit does not match the published source
+        dirty_worktree -> Bool,
+        /// The hash of the git commit that the crate file was packaged from, according to its `.cargo_vcs_info.json` file
+        commit_sha -> Nullable<Varchar>,
+        /// Link to the source code of the version at `commit_sha` in the repository of the crate at the time of publishing, if the repository is hosted on a supported site
+        source_url -> Nullable<Varchar>,
     }
 }
 
@@ -1012,8 +1940,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Versions whose crate file was found to contain no README that could be rendered, so that the `repair_readmes` job does not download it again
+    versions_without_readme (version_id) {
+        /// The `version_id` column of the `versions_without_readme` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        version_id -> Int4,
+        /// Point in time at which the crate file was checked
+        checked_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(account_compromises -> users (user_id));
 diesel::joinable!(api_tokens -> users (user_id));
+diesel::joinable!(archived_version_downloads -> versions (version_id));
+diesel::joinable!(audit_events -> api_tokens (api_token_id));
+diesel::joinable!(audit_events -> users (user_id));
 diesel::joinable!(badges -> crates (crate_id));
+diesel::joinable!(category_redirects -> categories (category_id));
+diesel::joinable!(category_subscriptions -> categories (category_id));
+diesel::joinable!(category_subscriptions -> users (user_id));
 diesel::joinable!(crate_owner_invitations -> crates (crate_id));
 diesel::joinable!(crate_owners -> crates (crate_id));
 diesel::joinable!(crate_owners -> teams (owner_id));
@@ -1022,47 +1971,102 @@ diesel::joinable!(crates_categories -> categories (category_id));
 diesel::joinable!(crates_categories -> crates (crate_id));
 diesel::joinable!(crates_keywords -> crates (crate_id));
 diesel::joinable!(crates_keywords -> keywords (keyword_id));
+diesel::joinable!(cross_registry_dependencies -> versions (version_id));
 diesel::joinable!(dependencies -> crates (crate_id));
+diesel::joinable!(dependencies -> dependency_requirements (requirement_id));
 diesel::joinable!(dependencies -> versions (version_id));
+diesel::joinable!(digest_preferences -> users (user_id));
+diesel::joinable!(download_anomalies -> crates (crate_id));
 diesel::joinable!(emails -> users (user_id));
+diesel::joinable!(features_usage -> crates (crate_id));
 diesel::joinable!(follows -> crates (crate_id));
 diesel::joinable!(follows -> users (user_id));
+diesel::joinable!(index_sync_times -> crates (crate_id));
+diesel::joinable!(keyword_subscriptions -> keywords (keyword_id));
+diesel::joinable!(keyword_subscriptions -> users (user_id));
+diesel::joinable!(moderation_queue -> crates (crate_id));
+diesel::joinable!(moderation_queue -> versions (version_id));
+diesel::joinable!(publish_diagnostics -> users (user_id));
 diesel::joinable!(publish_limit_buckets -> users (user_id));
 diesel::joinable!(publish_rate_overrides -> users (user_id));
 diesel::joinable!(readme_renderings -> versions (version_id));
 diesel::joinable!(recent_crate_downloads -> crates (crate_id));
+diesel::joinable!(repository_checks -> crates (crate_id));
+diesel::joinable!(repository_verifications -> users (user_id));
+diesel::joinable!(security_yanks -> versions (version_id));
+diesel::joinable!(single_owner_nudges -> crates (crate_id));
+diesel::joinable!(single_owner_nudges -> users (user_id));
+diesel::joinable!(support_windows -> crates (crate_id));
+diesel::joinable!(unavailable_versions -> versions (version_id));
+diesel::joinable!(version_archives -> versions (version_id));
 diesel::joinable!(version_downloads -> versions (version_id));
+diesel::joinable!(version_fingerprints -> crates (crate_id));
+diesel::joinable!(version_fingerprints -> versions (version_id));
 diesel::joinable!(version_owner_actions -> api_tokens (api_token_id));
 diesel::joinable!(version_owner_actions -> users (user_id));
 diesel::joinable!(version_owner_actions -> versions (version_id));
+diesel::joinable!(version_publish_warnings -> versions (version_id));
+diesel::joinable!(version_reproducibility -> versions (version_id));
+diesel::joinable!(version_scan_reports -> versions (version_id));
 diesel::joinable!(versions -> crates (crate_id));
 diesel::joinable!(versions -> users (published_by));
 diesel::joinable!(versions_published_by -> versions (version_id));
+diesel::joinable!(versions_without_readme -> versions (version_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    account_compromises,
     api_tokens,
+    archived_version_downloads,
+    audit_events,
     background_jobs,
     badges,
     categories,
+    category_redirects,
+    category_subscriptions,
     crate_owner_invitations,
     crate_owners,
     crates,
     crates_categories,
     crates_keywords,
+    cross_registry_dependencies,
     dependencies,
+    dependency_requirements,
+    digest_preferences,
+    download_anomalies,
+    download_redirect_samples,
     emails,
+    failed_background_jobs,
+    features_usage,
     follows,
+    index_sync_times,
+    keyword_subscriptions,
     keywords,
     metadata,
+    moderation_queue,
+    msrv_stats,
+    publish_diagnostics,
     publish_limit_buckets,
     publish_rate_overrides,
     readme_renderings,
     recent_crate_downloads,
+    repository_checks,
+    repository_verifications,
     reserved_crate_names,
+    security_yanks,
+    single_owner_nudges,
+    support_windows,
     teams,
+    tombstones,
+    unavailable_versions,
     users,
+    version_archives,
     version_downloads,
+    version_fingerprints,
     version_owner_actions,
+    version_publish_warnings,
+    version_reproducibility,
+    version_scan_reports,
     versions,
     versions_published_by,
+    versions_without_readme,
 );
@@ -2,6 +2,8 @@ use diesel::sql_types::{Date, Double, Integer, Interval, SingleValue, Text, Time
 
 sql_function!(#[aggregate] fn array_agg<T: SingleValue>(x: T) -> Array<T>);
 sql_function!(fn canon_crate_name(x: Text) -> Text);
+sql_function!(fn hashtext(x: Text) -> Integer);
+sql_function!(fn pg_try_advisory_xact_lock(key1: Integer, key2: Integer) -> Bool);
 sql_function!(fn to_char(a: Date, b: Text) -> Text);
 sql_function!(fn lower(x: Text) -> Text);
 sql_function!(fn date_part(x: Text, y: Timestamp) -> Double);
@@ -13,6 +15,7 @@ sql_function!(fn floor(x: Double) -> Integer);
 sql_function!(fn greatest<T: SingleValue>(x: T, y: T) -> T);
 sql_function!(fn least<T: SingleValue>(x: T, y: T) -> T);
 sql_function!(fn split_part(string: Text, delimiter: Text, n: Integer) -> Text);
+sql_function!(fn random_string(length: Integer) -> Text);
 
 macro_rules! pg_enum {
     (
@@ -1,34 +1,80 @@
 mod arc_store;
+mod cas;
+mod replication;
+mod retry;
+mod signed_urls;
 
 use crate::env;
 use crate::storage::arc_store::ArcStore;
+use crate::storage::replication::{FileKind, Replica, Replication};
+pub use crate::storage::replication::{ReplicaState, ReplicaStatus, ReplicatedFile};
+use crate::storage::retry::{client_retry_config, RetryStore, UploadPolicy};
+pub use crate::storage::retry::{RetryConfig, StorageUnavailable};
+use crate::storage::signed_urls::UrlSigner;
+use crate::util::deadline;
 use anyhow::Context;
-use futures_util::{StreamExt, TryStreamExt};
-use http::header::CACHE_CONTROL;
+use brotli::enc::BrotliEncoderParams;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::stream;
+use futures_util::{Stream, StreamExt, TryStreamExt};
+use http::header::{ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING};
 use http::{HeaderMap, HeaderValue};
 use hyper::body::Bytes;
-use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
 use object_store::local::LocalFileSystem;
 use object_store::memory::InMemory;
 use object_store::path::Path;
 use object_store::prefix::PrefixStore;
-use object_store::{ClientOptions, ObjectStore, Result};
+use object_store::{
+    ClientOptions, ObjectMeta, ObjectStore, Result, RetryConfig as ClientRetryConfig,
+};
 use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
 use std::fs;
+use std::future::Future;
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufRead, AsyncWriteExt};
+use tokio_util::io::StreamReader;
 
+const PREFIX_ARCHIVE: &str = "archive";
+const PREFIX_CAS: &str = "cas";
 const PREFIX_CRATES: &str = "crates";
+const PREFIX_DRY_RUNS: &str = "dry-runs";
+const PREFIX_LICENSE_TEXTS: &str = "license-texts";
 const PREFIX_READMES: &str = "readmes";
+const PREFIX_README_HISTORY: &str = "readme-history";
+/// The directories of the primary storage that contain a directory for each
+/// crate, e.g. `readmes/{name}`.
+const CRATE_DIRECTORIES: [&str; 6] = [
+    PREFIX_CRATES,
+    "archive/crates",
+    "cas/manifests/crates",
+    PREFIX_READMES,
+    PREFIX_README_HISTORY,
+    PREFIX_LICENSE_TEXTS,
+];
 const DEFAULT_REGION: &str = "us-west-1";
 const CONTENT_TYPE_CRATE: &str = "application/gzip";
 const CONTENT_TYPE_DB_DUMP: &str = "application/gzip";
 const CONTENT_TYPE_INDEX: &str = "text/plain";
+const CONTENT_TYPE_LICENSE_TEXTS: &str = "application/json";
 const CONTENT_TYPE_README: &str = "text/html";
 const CACHE_CONTROL_IMMUTABLE: &str = "public,max-age=31536000,immutable";
 const CACHE_CONTROL_INDEX: &str = "public,max-age=600";
 const CACHE_CONTROL_README: &str = "public,max-age=604800";
+const INDEX_BATCH_CONCURRENCY: usize = 32;
+/// Number of minutes after which the files of a publish dry run are deleted
+/// by [`Storage::delete_expired_dry_runs`].
+pub const DRY_RUN_EXPIRATION_MINUTES: i64 = 60;
 
 type StdPath = std::path::Path;
 
@@ -36,13 +82,44 @@ type StdPath = std::path::Path;
 pub struct StorageConfig {
     backend: StorageBackend,
     pub cdn_prefix: Option<String>,
+    /// Whether brotli and gzip compressed variants of readmes and index files
+    /// are stored next to the uncompressed files.
+    pub compressed_variants: bool,
+    /// Prefix of all object keys (e.g. `staging`), so that multiple
+    /// environments can share the same buckets.
+    pub key_prefix: Option<String>,
+    /// Whether archived crate files are split into content-addressed blobs
+    /// that are shared between versions, see the `cas` module.
+    pub deduplicated_archive: bool,
+    /// Whether crate files may only be downloaded via time-limited signed
+    /// URLs, e.g. for private registries whose bucket is not public.
+    pub require_signed_urls: bool,
+    /// How uploads of crate files and readmes to cloud storage backends are
+    /// retried, see the `retry` module.
+    pub upload_retries: RetryConfig,
 }
 
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum StorageBackend {
-    S3 { default: S3Config, index: S3Config },
-    LocalFileSystem { path: PathBuf },
+    S3 {
+        default: S3Config,
+        index: S3Config,
+        /// Buckets that crate files, readmes and index files are mirrored
+        /// to, e.g. in another region for disaster recovery.
+        replicas: Vec<S3Config>,
+    },
+    Azure {
+        default: AzureConfig,
+        index: AzureConfig,
+    },
+    Gcs {
+        default: GcsConfig,
+        index: GcsConfig,
+    },
+    LocalFileSystem {
+        path: PathBuf,
+    },
     InMemory,
 }
 
@@ -54,15 +131,43 @@ pub struct S3Config {
     secret_key: SecretString,
 }
 
+#[derive(Debug)]
+pub struct AzureConfig {
+    account: String,
+    access_key: SecretString,
+    container: String,
+}
+
+#[derive(Debug)]
+pub struct GcsConfig {
+    bucket: String,
+    /// Path to the JSON key file of the service account.
+    service_account_path: String,
+}
+
 impl StorageConfig {
     pub fn in_memory() -> Self {
         Self {
             backend: StorageBackend::InMemory,
             cdn_prefix: None,
+            compressed_variants: false,
+            key_prefix: None,
+            deduplicated_archive: false,
+            require_signed_urls: false,
+            upload_retries: RetryConfig::default(),
         }
     }
 
     pub fn from_environment() -> Self {
+        let compressed_variants = dotenvy::var("STORAGE_COMPRESSED_VARIANTS").is_ok();
+        let deduplicated_archive = dotenvy::var("STORAGE_DEDUPLICATED_ARCHIVE").is_ok();
+        let require_signed_urls = dotenvy::var("STORAGE_REQUIRE_SIGNED_URLS").is_ok();
+        let upload_retries = RetryConfig::from_environment();
+        let key_prefix = dotenvy::var("STORAGE_KEY_PREFIX")
+            .ok()
+            .map(|prefix| prefix.trim_matches('/').to_string())
+            .filter(|prefix| !prefix.is_empty());
+
         if let Ok(bucket) = dotenvy::var("S3_BUCKET") {
             let region = dotenvy::var("S3_REGION").ok();
             let cdn_prefix = dotenvy::var("S3_CDN").ok();
@@ -83,15 +188,99 @@ impl StorageConfig {
             let index = S3Config {
                 bucket: index_bucket,
                 region: index_region,
+                access_key: access_key.clone(),
+                secret_key: secret_key.clone(),
+            };
+
+            let replicas = dotenvy::var("S3_REPLICA_BUCKETS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|replica| !replica.is_empty())
+                .map(|replica| {
+                    let (bucket, region) = match replica.split_once(':') {
+                        Some((bucket, region)) => (bucket, Some(region.to_string())),
+                        None => (replica, None),
+                    };
+
+                    S3Config {
+                        bucket: bucket.to_string(),
+                        region,
+                        access_key: access_key.clone(),
+                        secret_key: secret_key.clone(),
+                    }
+                })
+                .collect();
+
+            let backend = StorageBackend::S3 {
+                default,
+                index,
+                replicas,
+            };
+
+            return Self {
+                backend,
+                cdn_prefix,
+                compressed_variants,
+                key_prefix,
+                deduplicated_archive,
+                require_signed_urls,
+                upload_retries,
+            };
+        }
+
+        if let Ok(account) = dotenvy::var("AZURE_STORAGE_ACCOUNT") {
+            let access_key: SecretString = env("AZURE_STORAGE_ACCESS_KEY").into();
+            let cdn_prefix = dotenvy::var("AZURE_CDN").ok();
+
+            let default = AzureConfig {
+                account: account.clone(),
+                access_key: access_key.clone(),
+                container: env("AZURE_STORAGE_CONTAINER"),
+            };
+
+            let index = AzureConfig {
+                account,
                 access_key,
-                secret_key,
+                container: env("AZURE_STORAGE_INDEX_CONTAINER"),
+            };
+
+            let backend = StorageBackend::Azure { default, index };
+
+            return Self {
+                backend,
+                cdn_prefix,
+                compressed_variants,
+                key_prefix,
+                deduplicated_archive,
+                require_signed_urls,
+                upload_retries,
+            };
+        }
+
+        if let Ok(service_account_path) = dotenvy::var("GOOGLE_SERVICE_ACCOUNT") {
+            let cdn_prefix = dotenvy::var("GCS_CDN").ok();
+
+            let default = GcsConfig {
+                bucket: env("GCS_BUCKET"),
+                service_account_path: service_account_path.clone(),
+            };
+
+            let index = GcsConfig {
+                bucket: env("GCS_INDEX_BUCKET"),
+                service_account_path,
             };
 
-            let backend = StorageBackend::S3 { default, index };
+            let backend = StorageBackend::Gcs { default, index };
 
             return Self {
                 backend,
                 cdn_prefix,
+                compressed_variants,
+                key_prefix,
+                deduplicated_archive,
+                require_signed_urls,
+                upload_retries,
             };
         }
 
@@ -106,20 +295,181 @@ impl StorageConfig {
         Self {
             backend,
             cdn_prefix: None,
+            compressed_variants,
+            key_prefix,
+            deduplicated_archive,
+            require_signed_urls,
+            upload_retries,
+        }
+    }
+}
+
+/// The encodings of the pre-compressed variants of readmes and index files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Brotli,
+    Gzip,
+}
+
+impl ContentEncoding {
+    const ALL: [Self; 2] = [Self::Brotli, Self::Gzip];
+
+    /// The file extension that is appended to the path of the uncompressed
+    /// file, e.g. `readmes/foo/foo-1.0.0.html.br`.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gz",
+        }
+    }
+
+    fn header_value(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+        }
+    }
+
+    /// Picks the preferred encoding that is accepted according to the
+    /// `Accept-Encoding` header of a request. Brotli is preferred over gzip,
+    /// since it compresses text better.
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let accepted = headers
+            .get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|coding| {
+                let mut parts = coding.split(';').map(str::trim);
+                let name = parts.next()?;
+                let rejected = parts.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q <= 0.0)
+                });
+                (!rejected).then_some(name)
+            })
+            .collect::<Vec<_>>();
+
+        Self::ALL.into_iter().find(|encoding| {
+            accepted.iter().any(|name| {
+                name.eq_ignore_ascii_case(encoding.header_value())
+                    || (*encoding == Self::Gzip && name.eq_ignore_ascii_case("x-gzip"))
+            })
+        })
+    }
+
+    fn compress(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Brotli => {
+                let mut output = Vec::new();
+                let params = BrotliEncoderParams::default();
+                brotli::BrotliCompress(&mut &bytes[..], &mut output, &params)?;
+                Ok(output)
+            }
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// A file in the storage that belongs to a crate, as listed by
+/// [`Storage::list_all_for_crate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateObject {
+    pub path: Path,
+    pub kind: CrateObjectKind,
+    pub last_modified: DateTime<Utc>,
+}
+
+impl CrateObject {
+    /// Returns the file whose copies in the replica buckets have to be
+    /// updated after this file was changed, if it is replicated at all.
+    pub fn replicated_file(&self) -> Option<ReplicatedFile> {
+        let path = &self.path;
+        if self.kind == CrateObjectKind::Index {
+            return Some(ReplicatedFile::new(FileKind::Index, path));
+        }
+
+        if path.prefix_matches(&Path::from(PREFIX_CRATES)) {
+            Some(ReplicatedFile::new(FileKind::Crate, path))
+        } else if path.prefix_matches(&Path::from(PREFIX_READMES)) {
+            Some(ReplicatedFile::new(FileKind::Readme, path))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrateObjectKind {
+    /// The index file of the crate, or one of its compressed variants.
+    Index,
+    /// A crate file, readme or deduplication manifest of a version.
+    Version(String),
+    /// A file in one of the directories of the crate whose name doesn't
+    /// contain a version.
+    Unknown,
+}
+
+/// The stores that the compressed variants of a file are uploaded to. They
+/// are separate from the store of the uncompressed file, so that the
+/// `Content-Encoding` of the variants is set correctly.
+struct VariantStores {
+    brotli: Box<dyn ObjectStore>,
+    gzip: Box<dyn ObjectStore>,
+    /// Whether clients can be redirected to the variants directly. Otherwise
+    /// the variants are served by content negotiation on the location of the
+    /// uncompressed file, like the local file system storage in development.
+    direct_locations: bool,
+}
+
+impl VariantStores {
+    /// Uses the same store for all variants, for backends that don't support
+    /// object metadata like the `Content-Encoding`.
+    fn shared(store: &ArcStore, direct_locations: bool) -> Self {
+        Self {
+            brotli: Box::new(store.clone()),
+            gzip: Box::new(store.clone()),
+            direct_locations,
+        }
+    }
+
+    fn get(&self, encoding: ContentEncoding) -> &dyn ObjectStore {
+        match encoding {
+            ContentEncoding::Brotli => &self.brotli,
+            ContentEncoding::Gzip => &self.gzip,
         }
     }
 }
 
 pub struct Storage {
     cdn_prefix: Option<String>,
+    key_prefix: Option<Path>,
 
     store: Box<dyn ObjectStore>,
     crate_upload_store: Box<dyn ObjectStore>,
     readme_upload_store: Box<dyn ObjectStore>,
+    license_upload_store: Box<dyn ObjectStore>,
     db_dump_upload_store: Box<dyn ObjectStore>,
 
     index_store: Box<dyn ObjectStore>,
     index_upload_store: Box<dyn ObjectStore>,
+
+    readme_variant_stores: Option<VariantStores>,
+    index_variant_stores: Option<VariantStores>,
+
+    replication: Option<Replication>,
+
+    deduplicated_archive: bool,
+
+    /// Signs the URLs of crate files, if they may only be downloaded via
+    /// signed URLs.
+    url_signer: Option<UrlSigner>,
 }
 
 impl Storage {
@@ -129,41 +479,84 @@ impl Storage {
 
     pub fn from_config(config: &StorageConfig) -> Self {
         let cdn_prefix = config.cdn_prefix.clone();
+        let compressed_variants = config.compressed_variants;
+        let deduplicated_archive = config.deduplicated_archive;
+        let require_signed_urls = config.require_signed_urls;
+        let key_prefix = config.key_prefix.as_deref().map(Path::from);
+        let prefix = key_prefix.as_ref();
 
         match &config.backend {
-            StorageBackend::S3 { default, index } => {
-                let options = ClientOptions::default();
-                let store = build_s3(default, options);
+            StorageBackend::S3 {
+                default,
+                index,
+                replicas,
+            } => {
+                if cdn_prefix.is_none() {
+                    panic!("Missing S3_CDN environment variable");
+                }
+
+                let mut storage = Self::from_buckets(
+                    |options, retry| build_s3(default, options, retry, prefix),
+                    |options, retry| build_s3(index, options, retry, prefix),
+                    cdn_prefix,
+                    key_prefix.clone(),
+                    compressed_variants,
+                    deduplicated_archive,
+                    config.upload_retries,
+                );
 
-                let options = client_options(CONTENT_TYPE_CRATE, CACHE_CONTROL_IMMUTABLE);
-                let crate_upload_store = build_s3(default, options);
+                storage.replication = (!replicas.is_empty()).then(|| {
+                    let replicas = replicas
+                        .iter()
+                        .map(|replica| Replica::s3(replica, prefix))
+                        .collect();
 
-                let options = client_options(CONTENT_TYPE_README, CACHE_CONTROL_README);
-                let readme_upload_store = build_s3(default, options);
+                    Replication::new(replicas)
+                });
 
-                let options =
-                    ClientOptions::default().with_default_content_type(CONTENT_TYPE_DB_DUMP);
-                let db_dump_upload_store = build_s3(default, options);
+                storage.url_signer = require_signed_urls.then(|| UrlSigner::s3(default));
 
-                let options = ClientOptions::default();
-                let index_store = build_s3(index, options);
+                storage
+            }
 
-                let options = client_options(CONTENT_TYPE_INDEX, CACHE_CONTROL_INDEX);
-                let index_upload_store = build_s3(index, options);
+            StorageBackend::Azure { default, index } => {
+                if require_signed_urls {
+                    panic!("Signed URLs are not supported by the Azure storage backend");
+                }
 
                 if cdn_prefix.is_none() {
-                    panic!("Missing S3_CDN environment variable");
+                    panic!("Missing AZURE_CDN environment variable");
                 }
 
-                Self {
-                    store: Box::new(store),
-                    crate_upload_store: Box::new(crate_upload_store),
-                    readme_upload_store: Box::new(readme_upload_store),
-                    db_dump_upload_store: Box::new(db_dump_upload_store),
+                Self::from_buckets(
+                    |options, retry| build_azure(default, options, retry, prefix),
+                    |options, retry| build_azure(index, options, retry, prefix),
                     cdn_prefix,
-                    index_store: Box::new(index_store),
-                    index_upload_store: Box::new(index_upload_store),
+                    key_prefix.clone(),
+                    compressed_variants,
+                    deduplicated_archive,
+                    config.upload_retries,
+                )
+            }
+
+            StorageBackend::Gcs { default, index } => {
+                if require_signed_urls {
+                    panic!("Signed URLs are not supported by the GCS storage backend");
+                }
+
+                if cdn_prefix.is_none() {
+                    panic!("Missing GCS_CDN environment variable");
                 }
+
+                Self::from_buckets(
+                    |options, retry| build_gcs(default, options, retry, prefix),
+                    |options, retry| build_gcs(index, options, retry, prefix),
+                    cdn_prefix,
+                    key_prefix.clone(),
+                    compressed_variants,
+                    deduplicated_archive,
+                    config.upload_retries,
+                )
             }
 
             StorageBackend::LocalFileSystem { path } => {
@@ -183,188 +576,1323 @@ impl Storage {
                     .context("Failed to initialize local file system storage")
                     .unwrap();
 
-                let store = ArcStore::new(local);
-                let index_store = ArcStore::new(local_index);
+                let store = with_key_prefix(ArcStore::new(local), prefix);
+                let index_store = with_key_prefix(ArcStore::new(local_index), prefix);
+
+                let readme_variant_stores =
+                    compressed_variants.then(|| VariantStores::shared(&store, false));
+                let index_variant_stores =
+                    compressed_variants.then(|| VariantStores::shared(&index_store, false));
 
                 Self {
                     store: Box::new(store.clone()),
                     crate_upload_store: Box::new(store.clone()),
                     readme_upload_store: Box::new(store.clone()),
+                    license_upload_store: Box::new(store.clone()),
                     db_dump_upload_store: Box::new(store),
                     cdn_prefix,
+                    key_prefix,
                     index_store: Box::new(index_store.clone()),
                     index_upload_store: Box::new(index_store),
+                    readme_variant_stores,
+                    index_variant_stores,
+                    replication: None,
+                    deduplicated_archive,
+                    url_signer: require_signed_urls.then(UrlSigner::local),
                 }
             }
 
             StorageBackend::InMemory => {
                 warn!("Using in-memory file storage");
-                let store = ArcStore::new(InMemory::new());
+                let store = with_key_prefix(ArcStore::new(InMemory::new()), prefix);
+                let index_store = ArcStore::new(PrefixStore::new(store.clone(), "index"));
+
+                // The in-memory store is never served to clients, so it
+                // behaves like the S3 store here.
+                let readme_variant_stores =
+                    compressed_variants.then(|| VariantStores::shared(&store, true));
+                let index_variant_stores =
+                    compressed_variants.then(|| VariantStores::shared(&index_store, true));
 
                 Self {
                     store: Box::new(store.clone()),
                     crate_upload_store: Box::new(store.clone()),
                     readme_upload_store: Box::new(store.clone()),
+                    license_upload_store: Box::new(store.clone()),
                     db_dump_upload_store: Box::new(store.clone()),
                     cdn_prefix,
-                    index_store: Box::new(PrefixStore::new(store.clone(), "index")),
-                    index_upload_store: Box::new(PrefixStore::new(store, "index")),
+                    key_prefix,
+                    index_store: Box::new(index_store.clone()),
+                    index_upload_store: Box::new(index_store),
+                    readme_variant_stores,
+                    index_variant_stores,
+                    replication: None,
+                    deduplicated_archive,
+                    url_signer: require_signed_urls.then(UrlSigner::local),
                 }
             }
         }
     }
 
+    /// Sets up the stores of a cloud storage backend, with one bucket for
+    /// crate files, readmes and database dumps, and one for the index.
+    ///
+    /// The `Content-Type`, `Cache-Control` and `Content-Encoding` of uploaded
+    /// objects are set by the default headers of the `ClientOptions` that the
+    /// stores are built with, which all supported backends store as object
+    /// metadata.
+    fn from_buckets(
+        default: impl Fn(ClientOptions, ClientRetryConfig) -> Box<dyn ObjectStore>,
+        index: impl Fn(ClientOptions, ClientRetryConfig) -> Box<dyn ObjectStore>,
+        cdn_prefix: Option<String>,
+        key_prefix: Option<Path>,
+        compressed_variants: bool,
+        deduplicated_archive: bool,
+        upload_retries: RetryConfig,
+    ) -> Self {
+        let store = default(ClientOptions::default(), ClientRetryConfig::default());
+
+        // The crate, readme and license text uploads share one circuit
+        // breaker, since they go to the same bucket.
+        let policy = Arc::new(UploadPolicy::new(upload_retries));
+        let upload_store = |options: ClientOptions| {
+            let store = default(options, client_retry_config());
+            Box::new(RetryStore::new(store, policy.clone()))
+        };
+
+        let options = client_options(CONTENT_TYPE_CRATE, CACHE_CONTROL_IMMUTABLE);
+        let crate_upload_store = upload_store(options);
+
+        let options = client_options(CONTENT_TYPE_README, CACHE_CONTROL_README);
+        let readme_upload_store = upload_store(options);
+
+        // The license texts are extracted from the crate file, which never
+        // changes after it was published.
+        let options = client_options(CONTENT_TYPE_LICENSE_TEXTS, CACHE_CONTROL_IMMUTABLE);
+        let license_upload_store = upload_store(options);
+
+        let options = ClientOptions::default().with_default_content_type(CONTENT_TYPE_DB_DUMP);
+        let db_dump_upload_store = default(options, ClientRetryConfig::default());
+
+        let index_store = index(ClientOptions::default(), ClientRetryConfig::default());
+
+        let options = client_options(CONTENT_TYPE_INDEX, CACHE_CONTROL_INDEX);
+        let index_upload_store = index(options, ClientRetryConfig::default());
+
+        let readme_variant_stores = compressed_variants
+            .then(|| build_variants(&default, CONTENT_TYPE_README, CACHE_CONTROL_README));
+        let index_variant_stores = compressed_variants
+            .then(|| build_variants(&index, CONTENT_TYPE_INDEX, CACHE_CONTROL_INDEX));
+
+        Self {
+            store,
+            crate_upload_store,
+            readme_upload_store,
+            license_upload_store,
+            db_dump_upload_store,
+            cdn_prefix,
+            key_prefix,
+            index_store,
+            index_upload_store,
+            readme_variant_stores,
+            index_variant_stores,
+            replication: None,
+            deduplicated_archive,
+            url_signer: None,
+        }
+    }
+
     /// Returns the URL of an uploaded crate's version archive.
     ///
     /// The function doesn't check for the existence of the file.
     pub fn crate_location(&self, name: &str, version: &str) -> String {
-        apply_cdn_prefix(&self.cdn_prefix, &crate_file_path(name, version)).replace('+', "%2B")
+        self.location(&crate_file_path(name, version))
+    }
+
+    /// Whether crate files may only be downloaded via the URLs of
+    /// [`Storage::presigned_crate_url`], instead of [`Storage::crate_location`].
+    pub fn requires_signed_urls(&self) -> bool {
+        self.url_signer.is_some()
+    }
+
+    /// Returns a URL of an uploaded crate's version archive that is only
+    /// valid for `ttl`.
+    ///
+    /// S3 URLs point to the bucket instead of the CDN, since only S3 can
+    /// check their signatures. Fails if signed URLs are not enabled.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn presigned_crate_url(
+        &self,
+        name: &str,
+        version: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<String> {
+        let signer = self
+            .url_signer
+            .as_ref()
+            .context("Signed URLs are not enabled")?;
+
+        let path = self.encoded_path(&crate_file_path(name, version));
+        signer.sign(&path, ttl, SystemTime::now())
+    }
+
+    /// Checks whether a request for a file of the local file system storage
+    /// may be served.
+    ///
+    /// If signed URLs are required, crate files are only served with a valid
+    /// signature in the `query`. Archived and deduplicated crate files are
+    /// never served then, since their URLs are not signed.
+    pub fn verify_local_request(&self, path: &str, query: Option<&str>) -> bool {
+        let Some(signer) = &self.url_signer else {
+            return true;
+        };
+
+        let path = path.trim_start_matches('/').replace('+', "%2B");
+        let unprefixed = match &self.key_prefix {
+            Some(prefix) => path
+                .strip_prefix(prefix.as_ref())
+                .and_then(|path| path.strip_prefix('/')),
+            None => Some(path.as_str()),
+        };
+
+        let protected = [PREFIX_CRATES, PREFIX_ARCHIVE, PREFIX_CAS];
+        let is_protected = unprefixed
+            .and_then(|path| path.split('/').next())
+            .is_some_and(|first| protected.contains(&first));
+
+        !is_protected || signer.verify(&path, query, SystemTime::now())
+    }
+
+    /// Returns the URL of the license texts of an uploaded crate version.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn license_texts_location(&self, name: &str, version: &str) -> String {
+        self.location(&license_texts_path(name, version))
     }
 
     /// Returns the URL of an uploaded crate's version readme.
     ///
     /// The function doesn't check for the existence of the file.
     pub fn readme_location(&self, name: &str, version: &str) -> String {
-        apply_cdn_prefix(&self.cdn_prefix, &readme_path(name, version)).replace('+', "%2B")
+        self.location(&readme_path(name, version))
+    }
+
+    /// Returns the URL of the crate file that was uploaded by the publish dry
+    /// run `id`.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn dry_run_crate_location(&self, id: &str, name: &str, version: &str) -> String {
+        self.location(&dry_run_path(id, &crate_file_path(name, version)))
+    }
+
+    /// Returns the URL of the readme that was rendered by the publish dry run
+    /// `id`.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn dry_run_readme_location(&self, id: &str, name: &str, version: &str) -> String {
+        self.location(&dry_run_path(id, &readme_path(name, version)))
+    }
+
+    /// Returns the URL of the compressed variant of an uploaded crate's
+    /// version readme, or `None` if clients can't be redirected to the
+    /// compressed variants.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn compressed_readme_location(
+        &self,
+        name: &str,
+        version: &str,
+        encoding: ContentEncoding,
+    ) -> Option<String> {
+        let stores = self.readme_variant_stores.as_ref()?;
+        if !stores.direct_locations {
+            return None;
+        }
+
+        let path = variant_path(&readme_path(name, version), encoding);
+        Some(self.location(&path))
+    }
+
+    /// Returns the paths of the files of a version that are served via the
+    /// CDN, including the key prefix, so that they can be purged from the
+    /// CDN caches after the files were deleted.
+    pub fn cdn_paths(&self, name: &str, version: &str) -> Vec<String> {
+        let readme = readme_path(name, version);
+        let variants = ContentEncoding::ALL.map(|encoding| variant_path(&readme, encoding));
+
+        [
+            crate_file_path(name, version),
+            license_texts_path(name, version),
+        ]
+        .into_iter()
+        .chain([readme])
+        .chain(variants)
+        .map(|path| self.encoded_path(&path))
+        .collect()
+    }
+
+    /// Returns the paths of the index file of a crate and of its compressed
+    /// variants that are served via the CDN, including the key prefix.
+    pub fn index_file_cdn_paths(&self, name: &str) -> Vec<String> {
+        let path = index_file_path(name);
+
+        let mut paths = vec![self.encoded_path(&path)];
+        if self.index_variant_stores.is_some() {
+            for encoding in ContentEncoding::ALL {
+                paths.push(self.encoded_path(&variant_path(&path, encoding)));
+            }
+        }
+
+        paths
+    }
+
+    /// Returns the path of a database dump that is served via the CDN,
+    /// including the key prefix.
+    pub fn db_dump_cdn_path(&self, target: &str) -> String {
+        self.encoded_path(&target.into())
+    }
+
+    pub fn has_compressed_variants(&self) -> bool {
+        self.readme_variant_stores.is_some()
     }
 
     #[instrument(skip(self))]
     pub async fn delete_all_crate_files(&self, name: &str) -> Result<()> {
         let prefix = format!("{PREFIX_CRATES}/{name}").into();
-        self.delete_all_with_prefix(&prefix).await
+        self.delete_all_with_prefix(&prefix).await?;
+
+        let prefix = format!("{PREFIX_ARCHIVE}/{PREFIX_CRATES}/{name}").into();
+        self.delete_all_with_prefix(&prefix).await?;
+
+        // The blobs of deduplicated crate files may be shared with other
+        // crates, so they are only deleted once they are not referenced
+        // anymore.
+        let prefix = format!("{PREFIX_CAS}/manifests/{PREFIX_CRATES}/{name}").into();
+        let manifest_paths: Vec<Path> = self
+            .store
+            .list(Some(&prefix))
+            .await?
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await?;
+        for manifest_path in manifest_paths {
+            self.delete_deduplicated(&manifest_path).await?;
+        }
+
+        Ok(())
     }
 
     #[instrument(skip(self))]
     pub async fn delete_all_readmes(&self, name: &str) -> Result<()> {
         let prefix = format!("{PREFIX_READMES}/{name}").into();
+        self.delete_all_with_prefix(&prefix).await?;
+
+        let prefix = format!("{PREFIX_README_HISTORY}/{name}").into();
+        self.delete_all_with_prefix(&prefix).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn delete_all_license_texts(&self, name: &str) -> Result<()> {
+        let prefix = format!("{PREFIX_LICENSE_TEXTS}/{name}").into();
         self.delete_all_with_prefix(&prefix).await
     }
 
     #[instrument(skip(self))]
     pub async fn delete_crate_file(&self, name: &str, version: &str) -> Result<()> {
         let path = crate_file_path(name, version);
-        self.store.delete(&path).await
+        within_deadline(self.store.delete(&path)).await
     }
 
     #[instrument(skip(self))]
     pub async fn delete_readme(&self, name: &str, version: &str) -> Result<()> {
         let path = readme_path(name, version);
-        self.store.delete(&path).await
+        within_deadline(self.store.delete(&path)).await?;
+
+        if self.readme_variant_stores.is_some() {
+            for encoding in ContentEncoding::ALL {
+                let path = variant_path(&path, encoding);
+                within_deadline(delete_if_exists(&self.store, &path)).await?;
+            }
+        }
+
+        let path = previous_readme_path(name, version);
+        within_deadline(delete_if_exists(&self.store, &path)).await
     }
 
     #[instrument(skip(self))]
-    pub async fn upload_crate_file(&self, name: &str, version: &str, bytes: Bytes) -> Result<()> {
+    pub async fn delete_license_texts(&self, name: &str, version: &str) -> Result<()> {
+        let path = license_texts_path(name, version);
+        within_deadline(delete_if_exists(&self.store, &path)).await
+    }
+
+    /// Downloads a crate file from the configured backend.
+    ///
+    /// The contents are streamed, so only the request itself is subject to
+    /// the storage deadline, not reading the returned reader.
+    #[instrument(skip(self))]
+    pub async fn download_crate_file(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<impl AsyncBufRead + Send + Unpin> {
         let path = crate_file_path(name, version);
-        self.crate_upload_store.put(&path, bytes).await
+        self.download(&path).await
     }
 
+    /// Downloads a whole crate file into memory, from the archive storage
+    /// tier if it isn't found at its regular location.
+    ///
+    /// Unlike with [`Storage::download_crate_file`], the storage deadline
+    /// applies to reading the contents too, so a stalled download can't
+    /// block the caller forever.
     #[instrument(skip(self))]
-    pub async fn upload_readme(&self, name: &str, version: &str, bytes: Bytes) -> Result<()> {
-        let path = readme_path(name, version);
-        self.readme_upload_store.put(&path, bytes).await
+    pub async fn read_crate_file(&self, name: &str, version: &str) -> Result<Bytes> {
+        let path = crate_file_path(name, version);
+        match within_deadline(async { self.store.get(&path).await?.bytes().await }).await {
+            Err(object_store::Error::NotFound { .. }) => {
+                within_deadline(self.download_archived_crate_file(name, version)).await
+            }
+            result => result,
+        }
     }
 
+    /// Returns the size of a crate file in bytes, or `None` if the crate file
+    /// doesn't exist. Archived crate files are not taken into account.
     #[instrument(skip(self))]
-    pub async fn sync_index(&self, name: &str, content: Option<String>) -> Result<()> {
-        let path = crates_io_index::Repository::relative_index_file_for_url(name).into();
-        if let Some(content) = content {
-            self.index_upload_store.put(&path, content.into()).await
-        } else {
-            self.index_store.delete(&path).await
+    pub async fn crate_file_size(&self, name: &str, version: &str) -> Result<Option<usize>> {
+        let path = crate_file_path(name, version);
+        match within_deadline(self.store.head(&path)).await {
+            Ok(meta) => Ok(Some(meta.size)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(error) => Err(error),
         }
     }
 
+    /// Lists the names of all crates that have files in the storage,
+    /// including deleted crates whose files were left behind.
+    ///
+    /// Index files are named after the lowercase crate name, so a crate with
+    /// uppercase letters in its name can appear twice.
     #[instrument(skip(self))]
-    pub async fn upload_db_dump(&self, target: &str, local_path: &StdPath) -> anyhow::Result<()> {
-        let store = &self.db_dump_upload_store;
+    pub async fn list_crate_names(&self) -> Result<BTreeSet<String>> {
+        let mut names = BTreeSet::new();
+        for directory in CRATE_DIRECTORIES {
+            let prefix = Path::from(directory);
+            let result = within_deadline(self.store.list_with_delimiter(Some(&prefix))).await?;
+            let directories = result.common_prefixes.iter();
+            names.extend(directories.filter_map(|path| path.filename().map(String::from)));
+        }
 
-        // Open the local tarball file
-        let mut local_file = File::open(local_path).await?;
+        // Crate names can't contain dots, which skips the compressed variants
+        // and the `config.json` file of the index.
+        let mut index_files = within_deadline(self.index_store.list(None)).await?;
+        while let Some(meta) = index_files.try_next().await? {
+            if let Some(name) = meta.location.filename().filter(|name| !name.contains('.')) {
+                names.insert(name.to_string());
+            }
+        }
 
-        // Set up a multipart upload
-        let path = target.into();
-        let (id, mut writer) = store.put_multipart(&path).await?;
+        Ok(names)
+    }
 
-        // Upload file contents
-        if let Err(error) = tokio::io::copy(&mut local_file, &mut writer).await {
-            // Abort the upload if something failed
-            store.abort_multipart(&path, &id).await?;
-            return Err(error.into());
+    /// Lists all files of the crate `name`: its crate files (including
+    /// archived and deduplicated ones), readmes, previous readmes and its
+    /// index file, each with their compressed variants.
+    ///
+    /// This doesn't consult the database, so it also finds the files of
+    /// deleted crates and versions.
+    #[instrument(skip(self))]
+    pub async fn list_all_for_crate(&self, name: &str) -> Result<Vec<CrateObject>> {
+        let mut objects = Vec::new();
+        for directory in CRATE_DIRECTORIES {
+            let prefix = Path::from(format!("{directory}/{name}"));
+            let mut metas = within_deadline(self.store.list(Some(&prefix))).await?;
+            while let Some(meta) = metas.try_next().await? {
+                let kind = match parse_version_file_name(name, &meta.location) {
+                    Some(version) => CrateObjectKind::Version(version),
+                    None => CrateObjectKind::Unknown,
+                };
+
+                objects.push(CrateObject {
+                    path: meta.location,
+                    kind,
+                    last_modified: meta.last_modified,
+                });
+            }
         }
 
-        // ... or finalize upload
-        writer.shutdown().await?;
-
-        Ok(())
-    }
+        let path = index_file_path(name);
+        let variants = ContentEncoding::ALL.map(|encoding| variant_path(&path, encoding));
+        for path in std::iter::once(path).chain(variants) {
+            match within_deadline(self.index_store.head(&path)).await {
+                Ok(meta) => objects.push(CrateObject {
+                    path,
+                    kind: CrateObjectKind::Index,
+                    last_modified: meta.last_modified,
+                }),
+                Err(object_store::Error::NotFound { .. }) => {}
+                Err(error) => return Err(error),
+            }
+        }
 
-    /// This should only be used for assertions in the test suite!
-    pub fn as_inner(&self) -> &dyn ObjectStore {
-        &self.store
+        Ok(objects)
     }
 
-    async fn delete_all_with_prefix(&self, prefix: &Path) -> Result<()> {
-        let objects = self.store.list(Some(prefix)).await?;
-        let locations = objects.map(|meta| meta.map(|m| m.location)).boxed();
+    /// Deletes a file that was listed by [`Storage::list_all_for_crate`].
+    ///
+    /// Its copies in the replica buckets are deleted by the replication job
+    /// of [`CrateObject::replicated_file`].
+    #[instrument(skip(self))]
+    pub async fn delete_crate_object(&self, object: &CrateObject) -> Result<()> {
+        let path = &object.path;
+        if object.kind == CrateObjectKind::Index {
+            within_deadline(delete_if_exists(&self.index_store, path)).await?;
+            return Ok(());
+        }
 
-        self.store
-            .delete_stream(locations)
-            .try_collect::<Vec<_>>()
-            .await?;
+        if path.prefix_matches(&Path::from(PREFIX_CAS)) {
+            return within_deadline(self.delete_deduplicated(path)).await;
+        }
 
-        Ok(())
+        within_deadline(delete_if_exists(&self.store, path)).await
     }
-}
-
-fn client_options(content_type: &str, cache_control: &'static str) -> ClientOptions {
-    let mut headers = HeaderMap::new();
-    headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
 
-    ClientOptions::default()
-        .with_default_content_type(content_type)
-        .with_default_headers(headers)
-}
+    /// Lists the blobs of deduplicated crate files that are not referenced
+    /// by any manifest, e.g. because deleting a manifest was interrupted.
+    ///
+    /// References of manifests that don't exist anymore don't count, unless
+    /// they were recorded after `cutoff`, since the manifest is only stored
+    /// after its references.
+    #[instrument(skip(self))]
+    pub async fn list_unreferenced_blobs(&self, cutoff: DateTime<Utc>) -> Result<Vec<ObjectMeta>> {
+        let mut unreferenced = Vec::new();
+        let mut blobs = within_deadline(self.store.list(Some(&cas::blobs_prefix()))).await?;
+        while let Some(blob) = blobs.try_next().await? {
+            let Some(hash) = cas::blob_hash(&blob.location) else {
+                continue;
+            };
+            if self.live_blob_references(hash, cutoff).await?.is_empty() {
+                unreferenced.push(blob);
+            }
+        }
 
-fn build_s3(config: &S3Config, client_options: ClientOptions) -> AmazonS3 {
-    AmazonS3Builder::new()
-        .with_region(config.region.as_deref().unwrap_or(DEFAULT_REGION))
-        .with_bucket_name(&config.bucket)
-        .with_access_key_id(&config.access_key)
-        .with_secret_access_key(config.secret_key.expose_secret())
-        .with_client_options(client_options)
-        .build()
-        .context("Failed to initialize S3 code")
-        .unwrap()
-}
+        Ok(unreferenced)
+    }
 
-fn crate_file_path(name: &str, version: &str) -> Path {
-    format!("{PREFIX_CRATES}/{name}/{name}-{version}.crate").into()
-}
+    /// Deletes a blob that was listed by [`Storage::list_unreferenced_blobs`]
+    /// together with the stale references of deleted manifests, unless it
+    /// was referenced again in the meantime.
+    ///
+    /// Returns whether the blob was deleted.
+    #[instrument(skip(self))]
+    pub async fn delete_unreferenced_blob(
+        &self,
+        path: &Path,
+        cutoff: DateTime<Utc>,
+    ) -> Result<bool> {
+        let Some(hash) = cas::blob_hash(path) else {
+            return Ok(false);
+        };
+
+        let prefix = cas::references_prefix(hash);
+        let references: Vec<ObjectMeta> = within_deadline(self.store.list(Some(&prefix)))
+            .await?
+            .try_collect()
+            .await?;
+        let live = self.live_blob_references(hash, cutoff).await?;
+        for reference in references {
+            if !live.contains(&reference.location) {
+                within_deadline(delete_if_exists(&self.store, &reference.location)).await?;
+            }
+        }
 
-fn readme_path(name: &str, version: &str) -> Path {
-    format!("{PREFIX_READMES}/{name}/{name}-{version}.html").into()
-}
+        within_deadline(self.release_blob(hash)).await
+    }
 
-fn apply_cdn_prefix(cdn_prefix: &Option<String>, path: &Path) -> String {
-    match cdn_prefix {
-        Some(cdn_prefix) if !cdn_prefix.starts_with("https://") => {
-            format!("https://{cdn_prefix}/{path}")
+    /// Returns the references of a blob whose manifest exists, or that were
+    /// recorded after `cutoff`.
+    async fn live_blob_references(&self, hash: &str, cutoff: DateTime<Utc>) -> Result<Vec<Path>> {
+        let prefix = cas::references_prefix(hash);
+        let mut references = within_deadline(self.store.list(Some(&prefix))).await?;
+
+        let mut live = Vec::new();
+        while let Some(reference) = references.try_next().await? {
+            let manifest_path = cas::referencing_manifest_path(&reference.location);
+            let is_live = match manifest_path {
+                _ if reference.last_modified > cutoff => true,
+                Some(manifest_path) => match self.store.head(&manifest_path).await {
+                    Ok(_) => true,
+                    Err(object_store::Error::NotFound { .. }) => false,
+                    Err(error) => return Err(error),
+                },
+                None => false,
+            };
+            if is_live {
+                live.push(reference.location);
+            }
         }
-        Some(cdn_prefix) => format!("{cdn_prefix}/{path}"),
-        None => format!("/{path}"),
+
+        Ok(live)
     }
-}
+
+    /// Calculates the SHA256 checksum of a crate file, in the hex encoding
+    /// that is used for the `checksum` column of the `versions` table.
+    ///
+    /// The file is streamed from the backend, so only the request itself is
+    /// subject to the storage deadline.
+    #[instrument(skip(self))]
+    pub async fn crate_file_checksum(&self, name: &str, version: &str) -> Result<String> {
+        let path = crate_file_path(name, version);
+        sha256_checksum(&self.store, &path).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upload_crate_file(&self, name: &str, version: &str, bytes: Bytes) -> Result<()> {
+        let path = crate_file_path(name, version);
+        within_deadline(self.crate_upload_store.put(&path, bytes)).await
+    }
+
+    /// Copies a crate file to the archive storage tier.
+    ///
+    /// Archived files are stored below the `archive/` prefix, which is
+    /// expected to be transitioned to a cheaper storage class (e.g. S3
+    /// Glacier Instant Retrieval) by a lifecycle rule of the bucket. They are
+    /// not available via the CDN.
+    ///
+    /// If the archive is deduplicated, the crate file is stored as blobs
+    /// below the `cas/` prefix instead, if it can be reproduced from them.
+    ///
+    /// The crate file at its regular location is kept, so that downloads
+    /// that are still redirected there keep working until it is deleted
+    /// with [`Storage::delete_crate_file`].
+    #[instrument(skip(self))]
+    pub async fn archive_crate_file(&self, name: &str, version: &str) -> Result<()> {
+        let path = crate_file_path(name, version);
+        if self.deduplicated_archive && self.archive_deduplicated(name, version).await? {
+            return Ok(());
+        }
+
+        let archive_path = archived_crate_file_path(name, version);
+        match self.store.copy(&path, &archive_path).await {
+            // The crate file was archived and deleted before
+            Err(object_store::Error::NotFound { .. })
+                if self.store.head(&archive_path).await.is_ok() =>
+            {
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Moves a crate file from the archive storage tier back to its regular
+    /// location.
+    #[instrument(skip(self))]
+    pub async fn restore_crate_file(&self, name: &str, version: &str) -> Result<()> {
+        let path = crate_file_path(name, version);
+
+        // Deduplicated crate files are restored even if the archive is not
+        // deduplicated anymore.
+        let manifest_path = deduplicated_crate_file_path(name, version);
+        if let Some(bytes) = self.reassemble_crate_file(&manifest_path).await? {
+            self.crate_upload_store.put(&path, bytes).await?;
+            return self.delete_deduplicated(&manifest_path).await;
+        }
+
+        let archive_path = archived_crate_file_path(name, version);
+        self.move_file(&archive_path, &path).await
+    }
+
+    /// Downloads a crate file from the archive storage tier, reassembling it
+    /// from its blobs if it was deduplicated.
+    #[instrument(skip(self))]
+    pub async fn download_archived_crate_file(&self, name: &str, version: &str) -> Result<Bytes> {
+        let manifest_path = deduplicated_crate_file_path(name, version);
+        if let Some(bytes) = self.reassemble_crate_file(&manifest_path).await? {
+            return Ok(bytes);
+        }
+
+        let path = archived_crate_file_path(name, version);
+        self.store.get(&path).await?.bytes().await
+    }
+
+    /// Checks whether the rendered readme of a crate version exists.
+    #[instrument(skip(self))]
+    pub async fn readme_exists(&self, name: &str, version: &str) -> Result<bool> {
+        let path = readme_path(name, version);
+        match within_deadline(self.store.head(&path)).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Downloads the rendered readme of a crate version, see
+    /// [`Storage::download_crate_file`].
+    #[instrument(skip(self))]
+    pub async fn download_readme(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<impl AsyncBufRead + Send + Unpin> {
+        let path = readme_path(name, version);
+        self.download(&path).await
+    }
+
+    /// Uploads the rendered readme of a crate version.
+    ///
+    /// If a readme was rendered before, it is kept below the
+    /// `readme-history/` prefix, replacing any older one, so that a readme
+    /// broken by a bad renderer deploy can be restored with
+    /// [`Storage::restore_previous_readme`].
+    #[instrument(skip(self))]
+    pub async fn upload_readme(&self, name: &str, version: &str, bytes: Bytes) -> Result<()> {
+        let path = readme_path(name, version);
+        let previous_path = previous_readme_path(name, version);
+        match within_deadline(self.store.copy(&path, &previous_path)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+            Err(error) => return Err(error),
+        }
+
+        if let Some(stores) = &self.readme_variant_stores {
+            within_deadline(upload_variants(stores, &path, &bytes)).await?;
+        }
+
+        within_deadline(self.readme_upload_store.put(&path, bytes)).await
+    }
+
+    /// Uploads the license and copyright files of a crate version, as a JSON
+    /// document, so that they can be fetched without the whole crate file.
+    #[instrument(skip(self))]
+    pub async fn upload_license_texts(
+        &self,
+        name: &str,
+        version: &str,
+        bytes: Bytes,
+    ) -> Result<()> {
+        let path = license_texts_path(name, version);
+        within_deadline(self.license_upload_store.put(&path, bytes)).await
+    }
+
+    /// Uploads the crate file and the rendered readme of a publish dry run
+    /// below the `dry-runs/{id}/` prefix, instead of their regular location.
+    ///
+    /// The files are deleted again by [`Storage::delete_expired_dry_runs`].
+    #[instrument(skip(self, crate_file, readme))]
+    pub async fn upload_dry_run(
+        &self,
+        id: &str,
+        name: &str,
+        version: &str,
+        crate_file: Bytes,
+        readme: Option<Bytes>,
+    ) -> Result<()> {
+        let path = dry_run_path(id, &crate_file_path(name, version));
+        within_deadline(self.crate_upload_store.put(&path, crate_file)).await?;
+
+        if let Some(readme) = readme {
+            let path = dry_run_path(id, &readme_path(name, version));
+            within_deadline(self.readme_upload_store.put(&path, readme)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the files of all publish dry runs that were uploaded more than
+    /// [`DRY_RUN_EXPIRATION_MINUTES`] before `now`, and returns the number of
+    /// deleted files.
+    #[instrument(skip(self))]
+    pub async fn delete_expired_dry_runs(&self, now: DateTime<Utc>) -> Result<usize> {
+        let cutoff = now - chrono::Duration::minutes(DRY_RUN_EXPIRATION_MINUTES);
+
+        let prefix = Path::from(PREFIX_DRY_RUNS);
+        let objects = within_deadline(self.store.list(Some(&prefix))).await?;
+        let expired = objects
+            .try_filter(|meta| std::future::ready(meta.last_modified < cutoff))
+            .map_ok(|meta| meta.location)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let count = expired.len();
+        let locations = stream::iter(expired.into_iter().map(Ok)).boxed();
+        self.store
+            .delete_stream(locations)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Downloads the readme that was replaced by the latest upload of the
+    /// rendered readme of a crate version.
+    #[instrument(skip(self))]
+    pub async fn download_previous_readme(&self, name: &str, version: &str) -> Result<Bytes> {
+        let path = previous_readme_path(name, version);
+        within_deadline(async { self.store.get(&path).await?.bytes().await }).await
+    }
+
+    /// Replaces the rendered readme of a crate version with the previous one.
+    ///
+    /// The replaced readme becomes the previous one in turn, so a restore can
+    /// be undone by restoring again.
+    #[instrument(skip(self))]
+    pub async fn restore_previous_readme(&self, name: &str, version: &str) -> Result<()> {
+        let bytes = self.download_previous_readme(name, version).await?;
+        self.upload_readme(name, version, bytes).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sync_index(&self, name: &str, content: Option<String>) -> Result<()> {
+        let path = index_file_path(name);
+        if let Some(content) = content {
+            let bytes = Bytes::from(content);
+            if let Some(stores) = &self.index_variant_stores {
+                within_deadline(upload_variants(stores, &path, &bytes)).await?;
+            }
+
+            within_deadline(self.index_upload_store.put(&path, bytes)).await?;
+        } else {
+            within_deadline(self.index_store.delete(&path)).await?;
+
+            if self.index_variant_stores.is_some() {
+                for encoding in ContentEncoding::ALL {
+                    let path = variant_path(&path, encoding);
+                    within_deadline(delete_if_exists(&self.index_store, &path)).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Syncs the index files of many crates like [`Storage::sync_index`],
+    /// with a bounded number of uploads in flight at once.
+    ///
+    /// The result of each crate is returned as soon as its upload finishes,
+    /// so the order of the results doesn't match the order of `files`, and a
+    /// failed upload doesn't stop the remaining ones.
+    pub fn sync_index_batch<'a>(
+        &'a self,
+        files: impl Iterator<Item = (String, Option<String>)> + 'a,
+    ) -> impl Stream<Item = (String, Result<()>)> + 'a {
+        stream::iter(files)
+            .map(move |(name, content)| async move {
+                let result = self.sync_index(&name, content).await;
+                (name, result)
+            })
+            .buffer_unordered(INDEX_BATCH_CONCURRENCY)
+    }
+
+    /// Whether files are mirrored to replica buckets, which is done by the
+    /// `replicate_file` background job, see [`Storage::replicate_file`].
+    pub fn has_replicas(&self) -> bool {
+        self.replication.is_some()
+    }
+
+    /// Copies a file from the primary storage to the replica buckets, or
+    /// deletes it from them if it doesn't exist in the primary storage
+    /// anymore. Does nothing if no replicas are configured.
+    #[instrument(skip(self))]
+    pub async fn replicate_file(&self, file: &ReplicatedFile) -> Result<()> {
+        let Some(replication) = &self.replication else {
+            return Ok(());
+        };
+
+        let primary = match file.kind {
+            FileKind::Index => &self.index_store,
+            FileKind::Crate | FileKind::Readme => &self.store,
+        };
+
+        replication.replicate(primary, file).await
+    }
+
+    /// Compares the checksums of the crate file of a version in the replica
+    /// buckets with the one in the primary storage.
+    ///
+    /// Returns an empty list if no replicas are configured, and an error if
+    /// the crate file doesn't exist in the primary storage.
+    #[instrument(skip(self))]
+    pub async fn verify_replication(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Vec<ReplicaStatus>> {
+        let Some(replication) = &self.replication else {
+            return Ok(Vec::new());
+        };
+
+        let path = crate_file_path(name, version);
+        replication
+            .verify(&self.store, FileKind::Crate, &path)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upload_db_dump(&self, target: &str, local_path: &StdPath) -> anyhow::Result<()> {
+        self.upload_gzip_file(&target.into(), local_path).await
+    }
+
+    /// Uploads the gzip compressed CSV export of a detached partition of the
+    /// `version_downloads` table to the archive.
+    #[instrument(skip(self))]
+    pub async fn upload_downloads_archive(
+        &self,
+        partition: &str,
+        local_path: &StdPath,
+    ) -> anyhow::Result<()> {
+        let path = downloads_archive_path(partition);
+        self.upload_gzip_file(&path, local_path).await
+    }
+
+    async fn upload_gzip_file(&self, path: &Path, local_path: &StdPath) -> anyhow::Result<()> {
+        let store = &self.db_dump_upload_store;
+
+        // Open the local file
+        let mut local_file = File::open(local_path).await?;
+
+        // Set up a multipart upload
+        let (id, mut writer) = store.put_multipart(path).await?;
+
+        // Upload file contents
+        if let Err(error) = tokio::io::copy(&mut local_file, &mut writer).await {
+            // Abort the upload if something failed
+            store.abort_multipart(path, &id).await?;
+            return Err(error.into());
+        }
+
+        // ... or finalize upload
+        writer.shutdown().await?;
+
+        Ok(())
+    }
+
+    async fn download(&self, path: &Path) -> Result<impl AsyncBufRead + Send + Unpin> {
+        let result = within_deadline(self.store.get(path)).await?;
+        Ok(StreamReader::new(result.into_stream()))
+    }
+
+    /// This should only be used for assertions in the test suite!
+    pub fn as_inner(&self) -> &dyn ObjectStore {
+        &self.store
+    }
+
+    /// Returns the URL of a file, including the key prefix of the objects.
+    fn location(&self, path: &Path) -> String {
+        let path = self.prefixed_path(path);
+        apply_cdn_prefix(&self.cdn_prefix, &path).replace('+', "%2B")
+    }
+
+    /// Returns the path of a file including the key prefix of the objects,
+    /// with `+` encoded like in [`Storage::location`].
+    fn encoded_path(&self, path: &Path) -> String {
+        self.prefixed_path(path).to_string().replace('+', "%2B")
+    }
+
+    fn prefixed_path(&self, path: &Path) -> Path {
+        match &self.key_prefix {
+            Some(prefix) => prefix.parts().chain(path.parts()).collect(),
+            None => path.clone(),
+        }
+    }
+
+    /// Moves a file within the same store.
+    ///
+    /// If the source file does not exist, but the target file does, the file
+    /// is assumed to have been moved already, so that retrying a partially
+    /// failed move succeeds.
+    async fn move_file(&self, from: &Path, to: &Path) -> Result<()> {
+        match self.store.copy(from, to).await {
+            Err(object_store::Error::NotFound { .. }) if self.store.head(to).await.is_ok() => {
+                return Ok(())
+            }
+            result => result?,
+        }
+
+        self.store.delete(from).await
+    }
+
+    /// Stores the deduplicated form of a crate file.
+    ///
+    /// Returns `false` if the crate file doesn't exist or can't be
+    /// reproduced from its blobs, in which case it has to be archived as a
+    /// plain copy.
+    async fn archive_deduplicated(&self, name: &str, version: &str) -> Result<bool> {
+        let path = crate_file_path(name, version);
+        let manifest_path = deduplicated_crate_file_path(name, version);
+
+        let bytes = match self.store.get(&path).await {
+            Ok(result) => result.bytes().await?,
+            // The crate file was archived before
+            Err(object_store::Error::NotFound { .. }) => {
+                return Ok(self.store.head(&manifest_path).await.is_ok());
+            }
+            Err(error) => return Err(error),
+        };
+
+        let Some(deduplicated) = cas::deduplicate(&bytes) else {
+            info!(%name, %version, "Crate file can't be deduplicated, archiving a copy");
+            return Ok(false);
+        };
+
+        for (hash, blob) in deduplicated.blobs {
+            // The reference is recorded before the blob is checked, so that
+            // a concurrent `release_blob` either sees the reference, or
+            // deletes the blob before it is checked here.
+            let reference_path = cas::reference_path(&hash, &manifest_path)
+                .expect("manifests are stored below the manifests prefix");
+            self.store.put(&reference_path, Bytes::new()).await?;
+
+            // Blobs never change, so existing ones don't have to be uploaded
+            // again
+            let blob_path = cas::blob_path(&hash);
+            match self.store.head(&blob_path).await {
+                Ok(_) => {}
+                Err(object_store::Error::NotFound { .. }) => {
+                    self.store.put(&blob_path, blob).await?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        // The manifest is only stored once all of its blobs exist
+        self.store
+            .put(&manifest_path, deduplicated.manifest)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Reassembles a deduplicated crate file, or returns `None` if there is
+    /// no manifest at `manifest_path`.
+    async fn reassemble_crate_file(&self, manifest_path: &Path) -> Result<Option<Bytes>> {
+        let manifest = match self.store.get(manifest_path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        cas::reassemble(&self.store, &manifest).await.map(Some)
+    }
+
+    /// Deletes the manifest of a deduplicated crate file, and the blobs that
+    /// are not referenced by any other manifest anymore.
+    ///
+    /// The manifest is deleted before its references, so that an
+    /// interrupted deletion only leaves unused blobs behind, which are
+    /// deleted by [`Storage::delete_unreferenced_blob`], and never a
+    /// manifest without its blobs.
+    async fn delete_deduplicated(&self, manifest_path: &Path) -> Result<()> {
+        let manifest = match self.store.get(manifest_path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        let hashes = cas::blob_hashes(&manifest)?;
+
+        self.store.delete(manifest_path).await?;
+
+        for hash in hashes {
+            if let Some(reference_path) = cas::reference_path(&hash, manifest_path) {
+                delete_if_exists(&self.store, &reference_path).await?;
+            }
+            self.release_blob(&hash).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a blob if no manifest references it anymore, and returns
+    /// whether it was deleted.
+    ///
+    /// A crate file that is archived concurrently records its reference
+    /// before it checks whether the blob exists. So the references are
+    /// checked again after the blob was deleted, and the blob is put back if
+    /// a reference was recorded in the meantime.
+    async fn release_blob(&self, hash: &str) -> Result<bool> {
+        if self.has_blob_references(hash).await? {
+            return Ok(false);
+        }
+
+        let blob_path = cas::blob_path(hash);
+        let blob = match self.store.get(&blob_path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(false),
+            Err(error) => return Err(error),
+        };
+        self.store.delete(&blob_path).await?;
+
+        if self.has_blob_references(hash).await? {
+            self.store.put(&blob_path, blob).await?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    async fn has_blob_references(&self, hash: &str) -> Result<bool> {
+        let prefix = cas::references_prefix(hash);
+        let mut references = self.store.list(Some(&prefix)).await?;
+        Ok(references.try_next().await?.is_some())
+    }
+
+    async fn delete_all_with_prefix(&self, prefix: &Path) -> Result<()> {
+        let objects = self.store.list(Some(prefix)).await?;
+        let locations = objects.map(|meta| meta.map(|m| m.location)).boxed();
+
+        self.store
+            .delete_stream(locations)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Uploads the compressed variants of a file. They are uploaded before the
+/// uncompressed file, so that clients never see an uncompressed file without
+/// its variants.
+async fn upload_variants(stores: &VariantStores, path: &Path, bytes: &[u8]) -> Result<()> {
+    for encoding in ContentEncoding::ALL {
+        let compressed =
+            encoding
+                .compress(bytes)
+                .map_err(|error| object_store::Error::Generic {
+                    store: "compression",
+                    source: Box::new(error),
+                })?;
+
+        let path = variant_path(path, encoding);
+        stores.get(encoding).put(&path, compressed.into()).await?;
+    }
+
+    Ok(())
+}
+
+/// Calculates the SHA256 checksum of a file in hex encoding. The file is
+/// streamed, so only the request itself is subject to the storage deadline.
+async fn sha256_checksum(store: &dyn ObjectStore, path: &Path) -> Result<String> {
+    let result = within_deadline(store.get(path)).await?;
+    let mut stream = result.into_stream();
+
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.try_next().await? {
+        hasher.update(&chunk);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+async fn delete_if_exists(store: &dyn ObjectStore, path: &Path) -> Result<()> {
+    match store.delete(path).await {
+        Err(object_store::Error::NotFound { .. }) => Ok(()),
+        result => result,
+    }
+}
+
+/// Cancels the storage operation if the deadline of the current request passes
+/// before it completes.
+async fn within_deadline<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    deadline::within_deadline(future)
+        .await
+        .map_err(|error| object_store::Error::Generic {
+            store: "deadline",
+            source: Box::new(error),
+        })?
+}
+
+fn client_options(content_type: &str, cache_control: &'static str) -> ClientOptions {
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+
+    ClientOptions::default()
+        .with_default_content_type(content_type)
+        .with_default_headers(headers)
+}
+
+fn build_variants(
+    build: impl Fn(ClientOptions, ClientRetryConfig) -> Box<dyn ObjectStore>,
+    content_type: &str,
+    cache_control: &'static str,
+) -> VariantStores {
+    let options = variant_client_options(content_type, cache_control, ContentEncoding::Brotli);
+    let brotli = build(options, ClientRetryConfig::default());
+
+    let options = variant_client_options(content_type, cache_control, ContentEncoding::Gzip);
+    let gzip = build(options, ClientRetryConfig::default());
+
+    VariantStores {
+        brotli,
+        gzip,
+        direct_locations: true,
+    }
+}
+
+fn variant_client_options(
+    content_type: &str,
+    cache_control: &'static str,
+    encoding: ContentEncoding,
+) -> ClientOptions {
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    let content_encoding = HeaderValue::from_static(encoding.header_value());
+    headers.insert(CONTENT_ENCODING, content_encoding);
+
+    ClientOptions::default()
+        .with_default_content_type(content_type)
+        .with_default_headers(headers)
+}
+
+fn build_s3(
+    config: &S3Config,
+    client_options: ClientOptions,
+    retry_config: ClientRetryConfig,
+    key_prefix: Option<&Path>,
+) -> Box<dyn ObjectStore> {
+    let store = AmazonS3Builder::new()
+        .with_region(config.region.as_deref().unwrap_or(DEFAULT_REGION))
+        .with_bucket_name(&config.bucket)
+        .with_access_key_id(&config.access_key)
+        .with_secret_access_key(config.secret_key.expose_secret())
+        .with_client_options(client_options)
+        .with_retry(retry_config)
+        .build()
+        .context("Failed to initialize S3 code")
+        .unwrap();
+
+    boxed_with_key_prefix(store, key_prefix)
+}
+
+fn build_azure(
+    config: &AzureConfig,
+    client_options: ClientOptions,
+    retry_config: ClientRetryConfig,
+    key_prefix: Option<&Path>,
+) -> Box<dyn ObjectStore> {
+    let store = MicrosoftAzureBuilder::new()
+        .with_account(&config.account)
+        .with_access_key(config.access_key.expose_secret())
+        .with_container_name(&config.container)
+        .with_client_options(client_options)
+        .with_retry(retry_config)
+        .build()
+        .context("Failed to initialize Azure Blob Storage code")
+        .unwrap();
+
+    boxed_with_key_prefix(store, key_prefix)
+}
+
+fn build_gcs(
+    config: &GcsConfig,
+    client_options: ClientOptions,
+    retry_config: ClientRetryConfig,
+    key_prefix: Option<&Path>,
+) -> Box<dyn ObjectStore> {
+    let store = GoogleCloudStorageBuilder::new()
+        .with_service_account_path(&config.service_account_path)
+        .with_bucket_name(&config.bucket)
+        .with_client_options(client_options)
+        .with_retry(retry_config)
+        .build()
+        .context("Failed to initialize Google Cloud Storage code")
+        .unwrap();
+
+    boxed_with_key_prefix(store, key_prefix)
+}
+
+/// Like [`with_key_prefix`], but for the stores of the cloud backends.
+fn boxed_with_key_prefix<T: ObjectStore>(
+    store: T,
+    key_prefix: Option<&Path>,
+) -> Box<dyn ObjectStore> {
+    match key_prefix {
+        Some(prefix) => Box::new(PrefixStore::new(store, prefix.clone())),
+        None => Box::new(store),
+    }
+}
+
+/// Wraps the store in a `PrefixStore`, if a key prefix is configured.
+fn with_key_prefix(store: ArcStore, key_prefix: Option<&Path>) -> ArcStore {
+    match key_prefix {
+        Some(prefix) => ArcStore::new(PrefixStore::new(store, prefix.clone())),
+        None => store,
+    }
+}
+
+fn index_file_path(name: &str) -> Path {
+    crates_io_index::Repository::relative_index_file_for_url(name).into()
+}
+
+fn crate_file_path(name: &str, version: &str) -> Path {
+    format!("{PREFIX_CRATES}/{name}/{name}-{version}.crate").into()
+}
+
+/// Extracts the version from the name of a file of the crate `name`, like
+/// `{name}-{version}.crate` or `{name}-{version}.html.br`.
+fn parse_version_file_name(name: &str, path: &Path) -> Option<String> {
+    const SUFFIXES: [&str; 5] = [".crate", ".json", ".html", ".html.br", ".html.gz"];
+
+    let version = path.filename()?.strip_prefix(name)?.strip_prefix('-')?;
+    SUFFIXES
+        .iter()
+        .find_map(|suffix| version.strip_suffix(suffix))
+        .filter(|version| !version.is_empty())
+        .map(String::from)
+}
+
+fn archived_crate_file_path(name: &str, version: &str) -> Path {
+    format!("{PREFIX_ARCHIVE}/{PREFIX_CRATES}/{name}/{name}-{version}.crate").into()
+}
+
+fn downloads_archive_path(partition: &str) -> Path {
+    format!("{PREFIX_ARCHIVE}/version-downloads/{partition}.csv.gz").into()
+}
+
+fn deduplicated_crate_file_path(name: &str, version: &str) -> Path {
+    format!("{PREFIX_CAS}/manifests/{PREFIX_CRATES}/{name}/{name}-{version}.json").into()
+}
+
+fn license_texts_path(name: &str, version: &str) -> Path {
+    format!("{PREFIX_LICENSE_TEXTS}/{name}/{name}-{version}.json").into()
+}
+
+fn dry_run_path(id: &str, path: &Path) -> Path {
+    format!("{PREFIX_DRY_RUNS}/{id}/{path}").into()
+}
+
+fn readme_path(name: &str, version: &str) -> Path {
+    format!("{PREFIX_READMES}/{name}/{name}-{version}.html").into()
+}
+
+fn previous_readme_path(name: &str, version: &str) -> Path {
+    format!("{PREFIX_README_HISTORY}/{name}/{name}-{version}.html").into()
+}
+
+fn variant_path(path: &Path, encoding: ContentEncoding) -> Path {
+    format!("{path}.{}", encoding.extension()).into()
+}
+
+fn apply_cdn_prefix(cdn_prefix: &Option<String>, path: &Path) -> String {
+    match cdn_prefix {
+        Some(cdn_prefix) if !cdn_prefix.starts_with("https://") => {
+            format!("https://{cdn_prefix}/{path}")
+        }
+        Some(cdn_prefix) => format!("{cdn_prefix}/{path}"),
+        None => format!("/{path}"),
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use hyper::body::Bytes;
     use tempfile::NamedTempFile;
+    use tokio::io::AsyncReadExt;
 
     pub async fn prepare() -> Storage {
         let storage = Storage::from_config(&StorageConfig::in_memory());
 
         let files_to_create = vec![
+            "archive/crates/foo/foo-0.1.0.crate",
             "crates/bar/bar-2.0.0.crate",
             "crates/foo/foo-1.0.0.crate",
             "crates/foo/foo-1.2.3.crate",
@@ -420,6 +1948,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn cdn_paths() {
+        let mut config = StorageConfig::in_memory();
+        config.key_prefix = Some("staging".to_string());
+        let storage = Storage::from_config(&config);
+
+        assert_eq!(
+            storage.cdn_paths("foo", "1.2.3+bar"),
+            vec![
+                "staging/crates/foo/foo-1.2.3%2Bbar.crate",
+                "staging/license-texts/foo/foo-1.2.3%2Bbar.json",
+                "staging/readmes/foo/foo-1.2.3%2Bbar.html",
+                "staging/readmes/foo/foo-1.2.3%2Bbar.html.br",
+                "staging/readmes/foo/foo-1.2.3%2Bbar.html.gz",
+            ]
+        );
+    }
+
     #[test]
     fn cdn_prefix() {
         assert_eq!(apply_cdn_prefix(&None, &"foo".into()), "/foo");
@@ -435,15 +1981,131 @@ mod tests {
             "https://fastly-static.crates.io/foo"
         );
 
-        assert_eq!(
-            apply_cdn_prefix(&Some("static.crates.io".to_string()), &"/foo/bar".into()),
-            "https://static.crates.io/foo/bar"
-        );
+        assert_eq!(
+            apply_cdn_prefix(&Some("static.crates.io".to_string()), &"/foo/bar".into()),
+            "https://static.crates.io/foo/bar"
+        );
+
+        assert_eq!(
+            apply_cdn_prefix(&Some("static.crates.io/".to_string()), &"/foo/bar".into()),
+            "https://static.crates.io//foo/bar"
+        );
+    }
+
+    #[tokio::test]
+    async fn key_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = StorageConfig {
+            backend: StorageBackend::LocalFileSystem {
+                path: dir.path().to_path_buf(),
+            },
+            cdn_prefix: None,
+            compressed_variants: false,
+            key_prefix: Some("staging".to_string()),
+            deduplicated_archive: false,
+            require_signed_urls: false,
+            upload_retries: RetryConfig::default(),
+        };
+
+        let storage = Storage::from_config(&config);
+
+        assert_eq!(
+            storage.crate_location("foo", "1.2.3"),
+            "/staging/crates/foo/foo-1.2.3.crate"
+        );
+        assert_eq!(
+            storage.readme_location("foo", "1.2.3"),
+            "/staging/readmes/foo/foo-1.2.3.html"
+        );
+
+        // CDN invalidations use the same prefixed paths as the uploads
+        assert_eq!(storage.index_file_cdn_paths("foo"), vec!["staging/3/f/foo"]);
+        assert_eq!(
+            storage.db_dump_cdn_path("db-dump.tar.gz"),
+            "staging/db-dump.tar.gz"
+        );
+
+        let bytes = Bytes::from_static(b"foo");
+        storage
+            .upload_crate_file("foo", "1.2.3", bytes)
+            .await
+            .unwrap();
+        storage.sync_index("foo", Some("{}".into())).await.unwrap();
+
+        let path = dir.path().join("staging/crates/foo/foo-1.2.3.crate");
+        assert_eq!(fs::read(path).unwrap(), b"foo");
+        let path = dir.path().join("index/staging/3/f/foo");
+        assert_eq!(fs::read(path).unwrap(), b"{}");
+
+        // The prefix is transparent to the users of the storage
+        let mut reader = storage.download_crate_file("foo", "1.2.3").await.unwrap();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, b"foo");
+    }
+
+    #[tokio::test]
+    async fn list_all_for_crate() {
+        let storage = prepare().await;
+        storage.sync_index("foo", Some("foo".into())).await.unwrap();
+        let path = "readmes/foo/foo-1.2.3.html.br".into();
+        storage.store.put(&path, Bytes::new()).await.unwrap();
+        let path = "readmes/foo/README.md".into();
+        storage.store.put(&path, Bytes::new()).await.unwrap();
+
+        let names = storage.list_crate_names().await.unwrap();
+        assert_eq!(names.into_iter().collect::<Vec<_>>(), vec!["bar", "foo"]);
+
+        let objects = storage.list_all_for_crate("foo").await.unwrap();
+        let objects = objects
+            .into_iter()
+            .map(|object| (object.path.to_string(), object.kind))
+            .collect::<Vec<_>>();
+
+        let version = |version: &str| CrateObjectKind::Version(version.into());
+        let expected = vec![
+            ("crates/foo/foo-1.0.0.crate".into(), version("1.0.0")),
+            ("crates/foo/foo-1.2.3.crate".into(), version("1.2.3")),
+            (
+                "archive/crates/foo/foo-0.1.0.crate".into(),
+                version("0.1.0"),
+            ),
+            ("readmes/foo/README.md".into(), CrateObjectKind::Unknown),
+            ("readmes/foo/foo-1.0.0.html".into(), version("1.0.0")),
+            ("readmes/foo/foo-1.2.3.html".into(), version("1.2.3")),
+            ("readmes/foo/foo-1.2.3.html.br".into(), version("1.2.3")),
+            ("3/f/foo".into(), CrateObjectKind::Index),
+        ];
+        assert_eq!(objects, expected);
+
+        for object in storage.list_all_for_crate("foo").await.unwrap() {
+            storage.delete_crate_object(&object).await.unwrap();
+        }
+
+        let expected = vec!["crates/bar/bar-2.0.0.crate", "readmes/bar/bar-2.0.0.html"];
+        assert_eq!(stored_files(&storage.store).await, expected);
+    }
+
+    #[tokio::test]
+    async fn checksum() {
+        let storage = Storage::from_config(&StorageConfig::in_memory());
+
+        let bytes = Bytes::from_static(b"hello world");
+        storage
+            .upload_crate_file("foo", "1.2.3", bytes)
+            .await
+            .unwrap();
 
         assert_eq!(
-            apply_cdn_prefix(&Some("static.crates.io/".to_string()), &"/foo/bar".into()),
-            "https://static.crates.io//foo/bar"
+            storage.crate_file_checksum("foo", "1.2.3").await.unwrap(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
         );
+
+        assert!(matches!(
+            storage.crate_file_checksum("foo", "2.0.0").await,
+            Err(object_store::Error::NotFound { .. })
+        ));
     }
 
     #[tokio::test]
@@ -468,6 +2130,7 @@ mod tests {
         storage.delete_all_readmes("foo").await.unwrap();
 
         let expected_files = vec![
+            "archive/crates/foo/foo-0.1.0.crate",
             "crates/bar/bar-2.0.0.crate",
             "crates/foo/foo-1.0.0.crate",
             "crates/foo/foo-1.2.3.crate",
@@ -483,6 +2146,7 @@ mod tests {
         storage.delete_crate_file("foo", "1.2.3").await.unwrap();
 
         let expected_files = vec![
+            "archive/crates/foo/foo-0.1.0.crate",
             "crates/bar/bar-2.0.0.crate",
             "crates/foo/foo-1.0.0.crate",
             "readmes/bar/bar-2.0.0.html",
@@ -499,6 +2163,7 @@ mod tests {
         storage.delete_readme("foo", "1.2.3").await.unwrap();
 
         let expected_files = vec![
+            "archive/crates/foo/foo-0.1.0.crate",
             "crates/bar/bar-2.0.0.crate",
             "crates/foo/foo-1.0.0.crate",
             "crates/foo/foo-1.2.3.crate",
@@ -508,6 +2173,43 @@ mod tests {
         assert_eq!(stored_files(&storage.store).await, expected_files);
     }
 
+    #[tokio::test]
+    async fn download_crate_file() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        s.upload_crate_file("foo", "1.2.3", Bytes::from_static(b"foo"))
+            .await
+            .unwrap();
+
+        let mut reader = s.download_crate_file("foo", "1.2.3").await.unwrap();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, b"foo");
+
+        assert!(s.download_crate_file("foo", "1.0.0").await.is_err());
+
+        let bytes = s.read_crate_file("foo", "1.2.3").await.unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"foo"));
+        assert!(s.read_crate_file("foo", "1.0.0").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn download_readme() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        s.upload_readme("foo", "1.2.3", Bytes::from_static(b"<p>foo</p>"))
+            .await
+            .unwrap();
+
+        let mut reader = s.download_readme("foo", "1.2.3").await.unwrap();
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).await.unwrap();
+        assert_eq!(contents, "<p>foo</p>");
+
+        let error = s.download_readme("foo", "1.0.0").await.err().unwrap();
+        assert!(matches!(error, object_store::Error::NotFound { .. }));
+    }
+
     #[tokio::test]
     async fn upload_crate_file() {
         let s = Storage::from_config(&StorageConfig::in_memory());
@@ -530,6 +2232,234 @@ mod tests {
         assert_eq!(stored_files(&s.store).await, expected_files);
     }
 
+    #[tokio::test]
+    async fn crate_file_size() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        s.upload_crate_file("foo", "1.0.0", Bytes::from_static(b"foo"))
+            .await
+            .unwrap();
+        s.upload_crate_file("foo", "1.0.1", Bytes::new())
+            .await
+            .unwrap();
+
+        assert_eq!(s.crate_file_size("foo", "1.0.0").await.unwrap(), Some(3));
+        assert_eq!(s.crate_file_size("foo", "1.0.1").await.unwrap(), Some(0));
+        assert_eq!(s.crate_file_size("foo", "1.0.2").await.unwrap(), None);
+
+        // Archived crate files are not found
+        s.archive_crate_file("foo", "1.0.0").await.unwrap();
+        s.delete_crate_file("foo", "1.0.0").await.unwrap();
+        assert_eq!(s.crate_file_size("foo", "1.0.0").await.unwrap(), None);
+    }
+
+    #[test]
+    fn presigned_crate_urls() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+        let ttl = std::time::Duration::from_secs(60);
+        assert!(!s.requires_signed_urls());
+        assert!(s.presigned_crate_url("foo", "1.0.0", ttl).is_err());
+        assert!(s.verify_local_request("/crates/foo/foo-1.0.0.crate", None));
+
+        let config = StorageConfig {
+            key_prefix: Some("staging".to_string()),
+            require_signed_urls: true,
+            ..StorageConfig::in_memory()
+        };
+        let s = Storage::from_config(&config);
+        assert!(s.requires_signed_urls());
+
+        let url = s.presigned_crate_url("foo", "1.0.0+bar", ttl).unwrap();
+        let (path, query) = url.split_once('?').unwrap();
+        assert_eq!(path, "/staging/crates/foo/foo-1.0.0%2Bbar.crate");
+        assert!(s.verify_local_request(path, Some(query)));
+        assert!(!s.verify_local_request(path, None));
+
+        let other = "/staging/crates/foo/foo-1.0.1.crate";
+        assert!(!s.verify_local_request(other, Some(query)));
+        let archived = "/staging/archive/crates/foo/foo-1.0.0%2Bbar.crate";
+        assert!(!s.verify_local_request(archived, Some(query)));
+
+        // Other files don't need signatures
+        assert!(s.verify_local_request("/staging/readmes/foo/foo-1.0.0.html", None));
+        assert!(s.verify_local_request("/index/3/f/foo", None));
+    }
+
+    #[tokio::test]
+    async fn archive_crate_file() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        s.upload_crate_file("foo", "1.2.3", Bytes::from_static(b"foo"))
+            .await
+            .unwrap();
+
+        s.archive_crate_file("foo", "1.2.3").await.unwrap();
+
+        // The original is kept until it is deleted explicitly
+        let expected_files = vec![
+            "archive/crates/foo/foo-1.2.3.crate",
+            "crates/foo/foo-1.2.3.crate",
+        ];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        s.delete_crate_file("foo", "1.2.3").await.unwrap();
+
+        let expected_files = vec!["archive/crates/foo/foo-1.2.3.crate"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        // Archiving the file again is a no-op
+        s.archive_crate_file("foo", "1.2.3").await.unwrap();
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        assert!(s.download_crate_file("foo", "1.2.3").await.is_err());
+        let bytes = s
+            .download_archived_crate_file("foo", "1.2.3")
+            .await
+            .unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"foo"));
+
+        // Reading the whole crate file falls back to the archive
+        let bytes = s.read_crate_file("foo", "1.2.3").await.unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"foo"));
+
+        s.restore_crate_file("foo", "1.2.3").await.unwrap();
+
+        let expected_files = vec!["crates/foo/foo-1.2.3.crate"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        // Restoring the file again is a no-op
+        s.restore_crate_file("foo", "1.2.3").await.unwrap();
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        assert!(s.archive_crate_file("foo", "1.0.0").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn archive_crate_file_deduplicated() {
+        let config = StorageConfig {
+            deduplicated_archive: true,
+            ..StorageConfig::in_memory()
+        };
+        let s = Storage::from_config(&config);
+
+        let data = (0..4096).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let v1 = crates_io_tarball::TarballBuilder::new("foo", "1.0.0")
+            .add_file("foo-1.0.0/data.bin", &data)
+            .build();
+        let v2 = crates_io_tarball::TarballBuilder::new("foo", "1.1.0")
+            .add_file("foo-1.1.0/data.bin", &data)
+            .build();
+
+        for (version, bytes) in [("1.0.0", &v1), ("1.1.0", &v2)] {
+            let bytes = Bytes::copy_from_slice(bytes);
+            s.upload_crate_file("foo", version, bytes).await.unwrap();
+            s.archive_crate_file("foo", version).await.unwrap();
+            s.delete_crate_file("foo", version).await.unwrap();
+        }
+
+        // Crate files that can't be reproduced are archived as a copy
+        s.upload_crate_file("foo", "2.0.0", Bytes::from_static(b"foo"))
+            .await
+            .unwrap();
+        s.archive_crate_file("foo", "2.0.0").await.unwrap();
+        s.delete_crate_file("foo", "2.0.0").await.unwrap();
+
+        let files = stored_files(&s.store).await;
+        assert_eq!(files.len(), 6);
+        assert!(files[0].starts_with("archive/crates/foo/foo-2.0.0.crate"));
+        assert!(files[1].starts_with("cas/blobs/"));
+        assert_eq!(files[2], "cas/manifests/crates/foo/foo-1.0.0.json");
+        assert_eq!(files[3], "cas/manifests/crates/foo/foo-1.1.0.json");
+
+        // Each manifest references the shared blob
+        let hash = files[1].rsplit('/').next().unwrap();
+        let references = format!("cas/references/{hash}/crates/foo");
+        assert_eq!(files[4], format!("{references}/foo-1.0.0.json"));
+        assert_eq!(files[5], format!("{references}/foo-1.1.0.json"));
+
+        // Archiving the file again is a no-op
+        s.archive_crate_file("foo", "1.0.0").await.unwrap();
+        assert_eq!(stored_files(&s.store).await, files);
+
+        let bytes = s.download_archived_crate_file("foo", "1.1.0").await;
+        assert_eq!(bytes.unwrap(), v2);
+        let bytes = s.download_archived_crate_file("foo", "2.0.0").await;
+        assert_eq!(bytes.unwrap(), Bytes::from_static(b"foo"));
+        let bytes = s.read_crate_file("foo", "1.0.0").await;
+        assert_eq!(bytes.unwrap(), v1);
+
+        s.restore_crate_file("foo", "1.0.0").await.unwrap();
+        s.restore_crate_file("foo", "2.0.0").await.unwrap();
+
+        let mut reader = s.download_crate_file("foo", "1.0.0").await.unwrap();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, v1);
+
+        // The blobs are kept for the other manifest
+        let expected_files = vec![
+            files[1].as_str(),
+            "cas/manifests/crates/foo/foo-1.1.0.json",
+            files[5].as_str(),
+            "crates/foo/foo-1.0.0.crate",
+            "crates/foo/foo-2.0.0.crate",
+        ];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        // Blobs are deleted together with their last reference
+        s.delete_all_crate_files("foo").await.unwrap();
+        assert!(stored_files(&s.store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn delete_unreferenced_blobs() {
+        let config = StorageConfig {
+            deduplicated_archive: true,
+            ..StorageConfig::in_memory()
+        };
+        let s = Storage::from_config(&config);
+
+        let data = (0..4096).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        for name in ["foo", "bar"] {
+            let bytes = crates_io_tarball::TarballBuilder::new(name, "1.0.0")
+                .add_file(&format!("{name}-1.0.0/data.bin"), &data)
+                .build();
+            s.upload_crate_file(name, "1.0.0", bytes.into())
+                .await
+                .unwrap();
+            s.archive_crate_file(name, "1.0.0").await.unwrap();
+            s.delete_crate_file(name, "1.0.0").await.unwrap();
+        }
+
+        // The blob is shared between the crates
+        s.delete_all_crate_files("foo").await.unwrap();
+        let bytes = s.download_archived_crate_file("bar", "1.0.0").await;
+        assert_ok!(bytes);
+
+        // An interrupted deletion leaves a stale reference behind
+        let manifest_path = deduplicated_crate_file_path("bar", "1.0.0");
+        s.store.delete(&manifest_path).await.unwrap();
+
+        let files = stored_files(&s.store).await;
+        assert_eq!(files.len(), 2);
+        assert!(files[0].starts_with("cas/blobs/"));
+        assert!(files[1].starts_with("cas/references/"));
+
+        // Recent references are kept, since their manifest may not have
+        // been stored yet
+        let cutoff = Utc::now() - chrono::Duration::hours(1);
+        assert!(s.list_unreferenced_blobs(cutoff).await.unwrap().is_empty());
+
+        let cutoff = Utc::now() + chrono::Duration::hours(1);
+        let blobs = s.list_unreferenced_blobs(cutoff).await.unwrap();
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].location.as_ref(), files[0]);
+
+        let deleted = s.delete_unreferenced_blob(&blobs[0].location, cutoff).await;
+        assert!(deleted.unwrap());
+        assert!(stored_files(&s.store).await.is_empty());
+    }
+
     #[tokio::test]
     async fn upload_readme() {
         let s = Storage::from_config(&StorageConfig::in_memory());
@@ -551,6 +2481,219 @@ mod tests {
         assert_eq!(stored_files(&s.store).await, expected_files);
     }
 
+    #[tokio::test]
+    async fn license_texts() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        let bytes = Bytes::from_static(b"{}");
+        s.upload_license_texts("foo", "1.2.3", bytes.clone())
+            .await
+            .unwrap();
+        s.upload_license_texts("foo", "2.0.0", bytes).await.unwrap();
+
+        let expected_files = vec![
+            "license-texts/foo/foo-1.2.3.json",
+            "license-texts/foo/foo-2.0.0.json",
+        ];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        let objects = s.list_all_for_crate("foo").await.unwrap();
+        let kind = &objects[0].kind;
+        assert_eq!(kind, &CrateObjectKind::Version("1.2.3".into()));
+
+        s.delete_license_texts("foo", "1.2.3").await.unwrap();
+        let expected_files = vec!["license-texts/foo/foo-2.0.0.json"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        s.delete_all_license_texts("foo").await.unwrap();
+        assert!(stored_files(&s.store).await.is_empty());
+
+        assert_eq!(
+            s.license_texts_location("foo", "1.2.3"),
+            "/license-texts/foo/foo-1.2.3.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_runs() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        let crate_file = Bytes::from_static(b"crate");
+        let readme = Bytes::from_static(b"readme");
+        s.upload_dry_run("abc", "foo", "1.2.3", crate_file.clone(), Some(readme))
+            .await
+            .unwrap();
+        s.upload_dry_run("def", "foo", "1.2.3", crate_file, None)
+            .await
+            .unwrap();
+
+        let expected_files = vec![
+            "dry-runs/abc/crates/foo/foo-1.2.3.crate",
+            "dry-runs/abc/readmes/foo/foo-1.2.3.html",
+            "dry-runs/def/crates/foo/foo-1.2.3.crate",
+        ];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        // Dry runs never show up as files of the crate
+        assert!(s.list_all_for_crate("foo").await.unwrap().is_empty());
+
+        assert_eq!(
+            s.dry_run_crate_location("abc", "foo", "1.2.3"),
+            "/dry-runs/abc/crates/foo/foo-1.2.3.crate"
+        );
+        assert_eq!(
+            s.dry_run_readme_location("abc", "foo", "1.2.3"),
+            "/dry-runs/abc/readmes/foo/foo-1.2.3.html"
+        );
+
+        let deleted = s.delete_expired_dry_runs(Utc::now()).await.unwrap();
+        assert_eq!(deleted, 0);
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        let later = Utc::now() + chrono::Duration::minutes(DRY_RUN_EXPIRATION_MINUTES + 1);
+        let deleted = s.delete_expired_dry_runs(later).await.unwrap();
+        assert_eq!(deleted, 3);
+        assert!(stored_files(&s.store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn readme_history() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        s.upload_readme("foo", "1.2.3", Bytes::from_static(b"good"))
+            .await
+            .unwrap();
+        let expected_files = vec!["readmes/foo/foo-1.2.3.html"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+        assert!(s.download_previous_readme("foo", "1.2.3").await.is_err());
+
+        s.upload_readme("foo", "1.2.3", Bytes::from_static(b"bad"))
+            .await
+            .unwrap();
+        let expected_files = vec![
+            "readme-history/foo/foo-1.2.3.html",
+            "readmes/foo/foo-1.2.3.html",
+        ];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+        let previous = s.download_previous_readme("foo", "1.2.3").await.unwrap();
+        assert_eq!(previous, "good");
+
+        s.restore_previous_readme("foo", "1.2.3").await.unwrap();
+        let path = "readmes/foo/foo-1.2.3.html".into();
+        let current = s.store.get(&path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(current, "good");
+        let previous = s.download_previous_readme("foo", "1.2.3").await.unwrap();
+        assert_eq!(previous, "bad");
+
+        s.delete_readme("foo", "1.2.3").await.unwrap();
+        assert!(stored_files(&s.store).await.is_empty());
+
+        s.upload_readme("foo", "1.0.0", Bytes::from_static(b"good"))
+            .await
+            .unwrap();
+        s.upload_readme("foo", "1.0.0", Bytes::from_static(b"bad"))
+            .await
+            .unwrap();
+        s.delete_all_readmes("foo").await.unwrap();
+        assert!(stored_files(&s.store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn upload_readme_with_compressed_variants() {
+        let config = StorageConfig {
+            compressed_variants: true,
+            ..StorageConfig::in_memory()
+        };
+        let s = Storage::from_config(&config);
+
+        let bytes = Bytes::from_static(b"<p>hello world</p>");
+        s.upload_readme("foo", "1.2.3", bytes).await.unwrap();
+
+        let expected_files = vec![
+            "readmes/foo/foo-1.2.3.html",
+            "readmes/foo/foo-1.2.3.html.br",
+            "readmes/foo/foo-1.2.3.html.gz",
+        ];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        let path = "readmes/foo/foo-1.2.3.html.br".into();
+        let compressed = s.store.get(&path).await.unwrap().bytes().await.unwrap();
+        let mut decompressed = Vec::new();
+        brotli::BrotliDecompress(&mut &compressed[..], &mut decompressed).unwrap();
+        assert_eq!(decompressed, b"<p>hello world</p>");
+
+        let path = "readmes/foo/foo-1.2.3.html.gz".into();
+        let compressed = s.store.get(&path).await.unwrap().bytes().await.unwrap();
+        let mut decompressed = Vec::new();
+        let mut decoder = flate2::read::GzDecoder::new(&compressed[..]);
+        std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+        assert_eq!(decompressed, b"<p>hello world</p>");
+
+        s.delete_readme("foo", "1.2.3").await.unwrap();
+        assert!(stored_files(&s.store).await.is_empty());
+    }
+
+    #[test]
+    fn compressed_readme_location() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+        assert_none!(s.compressed_readme_location("foo", "1.2.3", ContentEncoding::Brotli));
+
+        let config = StorageConfig {
+            compressed_variants: true,
+            ..StorageConfig::in_memory()
+        };
+        let s = Storage::from_config(&config);
+        assert_some_eq!(
+            s.compressed_readme_location("foo", "1.2.3+bar", ContentEncoding::Brotli),
+            "/readmes/foo/foo-1.2.3%2Bbar.html.br"
+        );
+        assert_some_eq!(
+            s.compressed_readme_location("foo", "1.2.3", ContentEncoding::Gzip),
+            "/readmes/foo/foo-1.2.3.html.gz"
+        );
+    }
+
+    #[test]
+    fn negotiate_content_encoding() {
+        fn negotiate(accept_encoding: &str) -> Option<ContentEncoding> {
+            let mut headers = HeaderMap::new();
+            let value = HeaderValue::from_str(accept_encoding).unwrap();
+            headers.insert(ACCEPT_ENCODING, value);
+            ContentEncoding::negotiate(&headers)
+        }
+
+        assert_none!(ContentEncoding::negotiate(&HeaderMap::new()));
+        assert_none!(negotiate("identity"));
+        assert_some_eq!(negotiate("gzip, deflate, br"), ContentEncoding::Brotli);
+        assert_some_eq!(negotiate("gzip"), ContentEncoding::Gzip);
+        assert_some_eq!(negotiate("x-gzip"), ContentEncoding::Gzip);
+        assert_some_eq!(negotiate("br;q=0, gzip;q=0.5"), ContentEncoding::Gzip);
+        assert_none!(negotiate("br;q=0, gzip;q=0"));
+    }
+
+    #[tokio::test]
+    async fn sync_index_with_compressed_variants() {
+        let config = StorageConfig {
+            compressed_variants: true,
+            ..StorageConfig::in_memory()
+        };
+        let s = Storage::from_config(&config);
+
+        let content = "foo".to_string();
+        s.sync_index("foo", Some(content)).await.unwrap();
+
+        let expected_files = vec!["index/3/f/foo", "index/3/f/foo.br", "index/3/f/foo.gz"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+
+        // The variants are purged from the CDN together with the index file
+        let expected_paths = vec!["3/f/foo", "3/f/foo.br", "3/f/foo.gz"];
+        assert_eq!(s.index_file_cdn_paths("foo"), expected_paths);
+
+        s.sync_index("foo", None).await.unwrap();
+
+        assert!(stored_files(&s.store).await.is_empty());
+    }
+
     #[tokio::test]
     async fn sync_index() {
         let s = Storage::from_config(&StorageConfig::in_memory());
@@ -568,6 +2711,151 @@ mod tests {
         assert!(stored_files(&s.store).await.is_empty());
     }
 
+    #[tokio::test]
+    async fn sync_index_batch() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+        s.sync_index("baz", Some("baz".into())).await.unwrap();
+
+        let files = vec![
+            ("foo".to_string(), Some("foo".to_string())),
+            ("bar".to_string(), Some("bar".to_string())),
+            ("baz".to_string(), None),
+        ];
+        let mut results = s
+            .sync_index_batch(files.into_iter())
+            .collect::<Vec<_>>()
+            .await;
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let names = results
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["bar", "baz", "foo"]);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        let expected_files = vec!["index/3/b/bar", "index/3/f/foo"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+    }
+
+    #[tokio::test]
+    async fn replication() {
+        let mut s = Storage::from_config(&StorageConfig::in_memory());
+        let replicas = vec![Replica::in_memory("dr-1"), Replica::in_memory("dr-2")];
+        s.replication = Some(Replication::new(replicas));
+        assert!(s.has_replicas());
+
+        let files = [
+            ReplicatedFile::crate_file("foo", "1.0.0"),
+            ReplicatedFile::readme("foo", "1.0.0"),
+            ReplicatedFile::index_file("foo"),
+        ];
+
+        s.upload_crate_file("foo", "1.0.0", Bytes::from_static(b"foo"))
+            .await
+            .unwrap();
+        s.upload_readme("foo", "1.0.0", Bytes::from_static(b"<p>foo</p>"))
+            .await
+            .unwrap();
+        s.sync_index("foo", Some("{}".into())).await.unwrap();
+
+        // The replicas are only updated by the replication jobs
+        let replicas = s.replication.as_ref().unwrap().replicas();
+        for replica in replicas {
+            assert!(stored_files(replica.as_inner()).await.is_empty());
+        }
+
+        for file in &files {
+            s.replicate_file(file).await.unwrap();
+        }
+
+        let expected_files = vec![
+            "crates/foo/foo-1.0.0.crate",
+            "index/3/f/foo",
+            "readmes/foo/foo-1.0.0.html",
+        ];
+        for replica in replicas {
+            assert_eq!(stored_files(replica.as_inner()).await, expected_files);
+        }
+
+        let statuses = s.verify_replication("foo", "1.0.0").await.unwrap();
+        let states = statuses
+            .iter()
+            .map(|status| (&*status.replica, &status.state));
+        assert_eq!(
+            states.collect::<Vec<_>>(),
+            vec![
+                ("dr-1", &ReplicaState::InSync),
+                ("dr-2", &ReplicaState::InSync)
+            ]
+        );
+
+        // Files that were deleted from the primary storage are deleted from
+        // the replicas too
+        s.sync_index("foo", None).await.unwrap();
+        s.replicate_file(&files[2]).await.unwrap();
+        for replica in replicas {
+            let files = stored_files(replica.as_inner()).await;
+            assert!(!files.contains(&"index/3/f/foo".to_string()));
+        }
+
+        // Files with the same size but a different content are detected
+        let path = crate_file_path("foo", "1.0.0");
+        replicas[0].as_inner().delete(&path).await.unwrap();
+        let bytes = Bytes::from_static(b"bar");
+        replicas[1].as_inner().put(&path, bytes).await.unwrap();
+
+        let statuses = s.verify_replication("foo", "1.0.0").await.unwrap();
+        let states = statuses
+            .iter()
+            .map(|status| (&*status.replica, &status.state));
+        assert_eq!(
+            states.collect::<Vec<_>>(),
+            vec![
+                ("dr-1", &ReplicaState::Missing),
+                (
+                    "dr-2",
+                    &ReplicaState::ChecksumMismatch {
+                        primary: hex::encode(Sha256::digest(b"foo")),
+                        replica: hex::encode(Sha256::digest(b"bar")),
+                    }
+                ),
+            ]
+        );
+
+        assert!(s.verify_replication("foo", "2.0.0").await.is_err());
+    }
+
+    #[test]
+    fn replicated_files_of_crate_objects() {
+        let object = |path: &str, kind| CrateObject {
+            path: Path::from(path),
+            kind,
+            last_modified: Utc::now(),
+        };
+
+        let version = || CrateObjectKind::Version("1.0.0".into());
+        let index = object("3/f/foo", CrateObjectKind::Index);
+        let crate_file = object("crates/foo/foo-1.0.0.crate", version());
+        let readme = object("readmes/foo/foo-1.0.0.html", version());
+        let previous_readme = object("readme-history/foo/foo-1.0.0.html", version());
+
+        let index_file = ReplicatedFile::index_file("foo");
+        assert_eq!(index.replicated_file(), Some(index_file));
+        let replicated = ReplicatedFile::crate_file("foo", "1.0.0");
+        assert_eq!(crate_file.replicated_file(), Some(replicated));
+        let replicated = ReplicatedFile::readme("foo", "1.0.0");
+        assert_eq!(readme.replicated_file(), Some(replicated));
+        assert_eq!(previous_readme.replicated_file(), None);
+    }
+
+    #[tokio::test]
+    async fn verify_replication_without_replicas() {
+        let s = prepare().await;
+        let statuses = s.verify_replication("foo", "1.0.0").await.unwrap();
+        assert!(statuses.is_empty());
+    }
+
     #[tokio::test]
     async fn upload_db_dump() {
         let s = Storage::from_config(&StorageConfig::in_memory());
@@ -581,4 +2869,17 @@ mod tests {
         let expected_files = vec![target];
         assert_eq!(stored_files(&s.store).await, expected_files);
     }
+
+    #[tokio::test]
+    async fn upload_downloads_archive() {
+        let s = Storage::from_config(&StorageConfig::in_memory());
+
+        let file = NamedTempFile::new().unwrap();
+        s.upload_downloads_archive("version_downloads_2023_01", file.path())
+            .await
+            .unwrap();
+
+        let expected_files = vec!["archive/version-downloads/version_downloads_2023_01.csv.gz"];
+        assert_eq!(stored_files(&s.store).await, expected_files);
+    }
 }
@@ -0,0 +1,447 @@
+//! Content-addressed storage of archived crate files.
+//!
+//! Consecutive versions of a crate usually share most of their files. Instead
+//! of a copy of the whole crate file, the archive storage tier can store each
+//! file of the tarball once, as a blob below `cas/blobs/` that is keyed by the
+//! SHA256 hash of its contents, plus a small manifest per version that lists
+//! the blobs and everything else that is needed to reassemble the crate file.
+//!
+//! Cargo verifies the checksum of downloaded crate files, so a crate file is
+//! only deduplicated if it can be reassembled byte for byte, which requires
+//! recompressing the tarball exactly like it was compressed originally. This
+//! depends on the deflate implementation that the crate file was compressed
+//! with, so crate files that can't be reproduced with the `flate2` backend of
+//! this application are archived as a plain copy instead.
+//!
+//! Only the archive tier is deduplicated, since its files are served by the
+//! application instead of the CDN, which couldn't reassemble them. Crate
+//! files that are served via the CDN are always stored as plain copies, so
+//! the savings are limited to versions that were archived, and
+//! `crate_location` never has to fall back to another object.
+//!
+//! Blobs are shared between manifests, so they are reference counted: for
+//! each blob of a manifest, an empty marker object is stored below
+//! `cas/references/{hash}/`, mirroring the path of the manifest below
+//! `cas/manifests/`. Deleting a manifest deletes its markers, and a blob is
+//! deleted once no markers are left.
+
+use super::PREFIX_CAS;
+use base64::{engine::general_purpose, Engine};
+use flate2::read::GzDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use hyper::body::Bytes;
+use object_store::path::Path;
+use object_store::{ObjectStore, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// The compression levels that are tried to reproduce a crate file. Cargo
+/// compresses crate files with the best compression, while most other tools
+/// use the default level.
+const LEVELS: [u32; 2] = [9, 6];
+
+/// File contents smaller than this are kept in the manifest, since a blob
+/// would take more space than it saves.
+const MIN_BLOB_SIZE: usize = 1024;
+
+/// How many blobs are downloaded at once to reassemble a crate file.
+const MAX_CONCURRENT_DOWNLOADS: usize = 16;
+
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// Everything that is needed to reassemble a crate file from its blobs.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    /// The base64 encoded gzip header of the crate file, which includes
+    /// optional fields like the file name.
+    gzip_header: String,
+    /// The compression level that reproduces the crate file.
+    level: u32,
+    /// The uncompressed tarball, in order.
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Segment {
+    /// Base64 encoded bytes, e.g. the tar headers and small files.
+    Inline(String),
+    /// The SHA256 hash of a blob.
+    Blob(String),
+}
+
+impl Manifest {
+    /// Returns the hashes of the blobs of the manifest, without duplicates.
+    fn blob_hashes(&self) -> Vec<&String> {
+        let mut hashes = self
+            .segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Blob(hash) => Some(hash),
+                Segment::Inline(_) => None,
+            })
+            .collect::<Vec<_>>();
+        hashes.sort();
+        hashes.dedup();
+        hashes
+    }
+}
+
+/// A crate file that was split into a manifest and blobs.
+pub(super) struct Deduplicated {
+    /// The JSON encoded manifest.
+    pub(super) manifest: Bytes,
+    /// The blobs that are referenced by the manifest, with their hashes.
+    pub(super) blobs: Vec<(String, Bytes)>,
+}
+
+/// Splits a crate file into a manifest and blobs, or returns `None` if the
+/// crate file can't be reassembled exactly from them.
+pub(super) fn deduplicate(crate_file: &[u8]) -> Option<Deduplicated> {
+    let header_len = gzip_header_len(crate_file)?;
+
+    let mut tarball = Vec::new();
+    GzDecoder::new(crate_file).read_to_end(&mut tarball).ok()?;
+
+    let level = LEVELS
+        .into_iter()
+        .find(|&level| compress(&crate_file[..header_len], &tarball, level) == crate_file)?;
+
+    let mut segments = Vec::new();
+    let mut blobs = HashMap::new();
+    for (range, is_blob) in split_tarball(&tarball)? {
+        let bytes = &tarball[range];
+        if is_blob {
+            let hash = hex::encode(Sha256::digest(bytes));
+            blobs.insert(hash.clone(), Bytes::copy_from_slice(bytes));
+            segments.push(Segment::Blob(hash));
+        } else {
+            segments.push(Segment::Inline(general_purpose::STANDARD.encode(bytes)));
+        }
+    }
+
+    let manifest = Manifest {
+        gzip_header: general_purpose::STANDARD.encode(&crate_file[..header_len]),
+        level,
+        segments,
+    };
+
+    // Guards against mistakes in splitting the tarball, which would otherwise
+    // only be noticed when the crate file is restored.
+    if assemble(&manifest, &blobs)? != crate_file {
+        return None;
+    }
+
+    let blobs = blobs.into_iter().collect();
+    let manifest = serde_json::to_vec(&manifest).ok()?.into();
+    Some(Deduplicated { manifest, blobs })
+}
+
+/// Reassembles a crate file from its manifest and the blobs in `store`.
+pub(super) async fn reassemble(store: &dyn ObjectStore, manifest: &[u8]) -> Result<Bytes> {
+    let manifest: Manifest = serde_json::from_slice(manifest).map_err(generic_error)?;
+
+    let blobs: HashMap<_, _> = stream::iter(manifest.blob_hashes())
+        .map(|hash| async move {
+            let bytes = store.get(&blob_path(hash)).await?.bytes().await?;
+            Ok::<_, object_store::Error>((hash.clone(), bytes))
+        })
+        .buffer_unordered(MAX_CONCURRENT_DOWNLOADS)
+        .try_collect()
+        .await?;
+
+    let crate_file = assemble(&manifest, &blobs)
+        .ok_or_else(|| generic_error("crate file manifest is corrupted"))?;
+
+    Ok(crate_file.into())
+}
+
+/// Returns the hashes of the blobs that a JSON encoded manifest references.
+pub(super) fn blob_hashes(manifest: &[u8]) -> Result<Vec<String>> {
+    let manifest: Manifest = serde_json::from_slice(manifest).map_err(generic_error)?;
+    Ok(manifest.blob_hashes().into_iter().cloned().collect())
+}
+
+pub(super) fn blob_path(hash: &str) -> Path {
+    format!("{PREFIX_CAS}/blobs/{}/{hash}", &hash[..2]).into()
+}
+
+/// Returns the hash of the blob at `path`.
+pub(super) fn blob_hash(path: &Path) -> Option<&str> {
+    path.filename()
+}
+
+pub(super) fn blobs_prefix() -> Path {
+    format!("{PREFIX_CAS}/blobs").into()
+}
+
+/// Returns the path of the marker that records that the manifest at
+/// `manifest_path` references the blob `hash`.
+pub(super) fn reference_path(hash: &str, manifest_path: &Path) -> Option<Path> {
+    let parts = manifest_path.prefix_match(&manifests_prefix())?;
+    Some(references_prefix(hash).parts().chain(parts).collect())
+}
+
+/// Returns the path of the manifest that a reference marker belongs to.
+pub(super) fn referencing_manifest_path(reference_path: &Path) -> Option<Path> {
+    let mut parts = reference_path.prefix_match(&Path::from(PREFIX_CAS))?;
+    let (Some(_references), Some(_hash)) = (parts.next(), parts.next()) else {
+        return None;
+    };
+    Some(manifests_prefix().parts().chain(parts).collect())
+}
+
+pub(super) fn references_prefix(hash: &str) -> Path {
+    format!("{PREFIX_CAS}/references/{hash}").into()
+}
+
+fn manifests_prefix() -> Path {
+    format!("{PREFIX_CAS}/manifests").into()
+}
+
+fn assemble(manifest: &Manifest, blobs: &HashMap<String, Bytes>) -> Option<Vec<u8>> {
+    let mut tarball = Vec::new();
+    for segment in &manifest.segments {
+        match segment {
+            Segment::Inline(data) => tarball.extend(general_purpose::STANDARD.decode(data).ok()?),
+            Segment::Blob(hash) => tarball.extend_from_slice(blobs.get(hash)?),
+        }
+    }
+
+    let gzip_header = general_purpose::STANDARD
+        .decode(&manifest.gzip_header)
+        .ok()?;
+
+    Some(compress(&gzip_header, &tarball, manifest.level))
+}
+
+/// Compresses a tarball into a gzip file with the given header.
+fn compress(gzip_header: &[u8], tarball: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(gzip_header.to_vec(), Compression::new(level));
+    encoder.write_all(tarball).unwrap();
+    let mut gzip = encoder.finish().unwrap();
+
+    let mut crc = Crc::new();
+    crc.update(tarball);
+    gzip.extend(crc.sum().to_le_bytes());
+    gzip.extend(crc.amount().to_le_bytes());
+    gzip
+}
+
+/// Returns the length of the header of a gzip file, including the optional
+/// fields.
+fn gzip_header_len(data: &[u8]) -> Option<usize> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.get(..3)? != [0x1f, 0x8b, 8] {
+        return None;
+    }
+
+    let flags = *data.get(3)?;
+    let mut len = 10;
+    if flags & FEXTRA != 0 {
+        let extra_len = u16::from_le_bytes([*data.get(len)?, *data.get(len + 1)?]);
+        len += 2 + extra_len as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            len += data.get(len..)?.iter().position(|&byte| byte == 0)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        len += 2;
+    }
+
+    (len <= data.len()).then_some(len)
+}
+
+/// Splits an uncompressed tarball into consecutive ranges, which are marked
+/// as blobs if they are the contents of a regular file that is large enough.
+fn split_tarball(tarball: &[u8]) -> Option<Vec<(std::ops::Range<usize>, bool)>> {
+    let mut ranges = Vec::new();
+    let mut inline_start = 0;
+    let mut pos = 0;
+
+    while let Some(header) = tarball.get(pos..pos + TAR_BLOCK_SIZE) {
+        // The archive ends with blocks of zeros, which are kept inline
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+
+        let size = entry_size(&header[124..136])?;
+        let is_file = matches!(header[156], b'0' | b'\0' | b'7');
+        pos += TAR_BLOCK_SIZE;
+
+        if is_file && size >= MIN_BLOB_SIZE {
+            if tarball.len() < pos + size {
+                return None;
+            }
+
+            ranges.push((inline_start..pos, false));
+            ranges.push((pos..pos + size, true));
+            inline_start = pos + size;
+        }
+
+        pos += (size + TAR_BLOCK_SIZE - 1) / TAR_BLOCK_SIZE * TAR_BLOCK_SIZE;
+    }
+
+    ranges.push((inline_start..tarball.len(), false));
+    Some(ranges)
+}
+
+/// Parses the size field of a tar header, which is either an octal number or,
+/// for large files, a big-endian binary number marked by the highest bit.
+fn entry_size(field: &[u8]) -> Option<usize> {
+    if field[0] & 0x80 != 0 {
+        let size = field[1..].iter().try_fold(0usize, |size, &byte| {
+            size.checked_mul(256)?.checked_add(byte as usize)
+        })?;
+        return Some(size);
+    }
+
+    let digits = std::str::from_utf8(field).ok()?;
+    let digits = digits.trim_matches(|c| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+
+    usize::from_str_radix(digits, 8).ok()
+}
+
+fn generic_error(
+    error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> object_store::Error {
+    object_store::Error::Generic {
+        store: "cas",
+        source: error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crates_io_tarball::TarballBuilder;
+    use object_store::memory::InMemory;
+
+    /// Contents that are large enough to be stored as a blob.
+    fn contents(seed: u8) -> Vec<u8> {
+        (0..4096).map(|i| (i % 251) as u8 ^ seed).collect()
+    }
+
+    async fn store_blobs(store: &InMemory, deduplicated: &Deduplicated) {
+        for (hash, bytes) in &deduplicated.blobs {
+            store.put(&blob_path(hash), bytes.clone()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn roundtrip() {
+        let crate_file = TarballBuilder::new("foo", "1.0.0")
+            .add_file("foo-1.0.0/src/lib.rs", b"pub fn foo() {}")
+            .add_file("foo-1.0.0/src/data.bin", &contents(0))
+            .add_file("foo-1.0.0/src/more.bin", &contents(1))
+            .add_file("foo-1.0.0/src/copy.bin", &contents(0))
+            .build();
+
+        let deduplicated = deduplicate(&crate_file).unwrap();
+        assert_eq!(deduplicated.blobs.len(), 2);
+
+        let store = InMemory::new();
+        store_blobs(&store, &deduplicated).await;
+
+        let reassembled = reassemble(&store, &deduplicated.manifest).await.unwrap();
+        assert_eq!(reassembled, crate_file);
+
+        let mut hashes = deduplicated
+            .blobs
+            .iter()
+            .map(|(hash, _)| hash.clone())
+            .collect::<Vec<_>>();
+        hashes.sort();
+        assert_eq!(blob_hashes(&deduplicated.manifest).unwrap(), hashes);
+
+        // Missing blobs can't be replaced
+        let (hash, _) = &deduplicated.blobs[0];
+        store.delete(&blob_path(hash)).await.unwrap();
+        assert!(reassemble(&store, &deduplicated.manifest).await.is_err());
+    }
+
+    #[test]
+    fn shared_blobs() {
+        let v1 = TarballBuilder::new("foo", "1.0.0")
+            .add_file("foo-1.0.0/src/data.bin", &contents(0))
+            .build_gzip_with_level(flate2::Compression::best());
+        let v2 = TarballBuilder::new("foo", "1.1.0")
+            .add_file("foo-1.1.0/src/data.bin", &contents(0))
+            .add_file("foo-1.1.0/src/more.bin", &contents(1))
+            .build_gzip_with_level(flate2::Compression::best());
+
+        let v1 = deduplicate(&v1).unwrap();
+        let v2 = deduplicate(&v2).unwrap();
+
+        let (v1_hash, _) = &v1.blobs[0];
+        assert!(v2.blobs.iter().any(|(hash, _)| hash == v1_hash));
+        assert!(blob_path(v1_hash).as_ref().starts_with("cas/blobs/"));
+    }
+
+    #[test]
+    fn reference_paths() {
+        let manifest_path = Path::from("cas/manifests/crates/foo/foo-1.0.0.json");
+        let reference = reference_path("abcd", &manifest_path).unwrap();
+        assert_eq!(
+            reference.as_ref(),
+            "cas/references/abcd/crates/foo/foo-1.0.0.json"
+        );
+        assert_eq!(referencing_manifest_path(&reference), Some(manifest_path));
+
+        assert_eq!(
+            reference_path("abcd", &Path::from("crates/foo/foo-1.0.0.crate")),
+            None
+        );
+        assert_eq!(referencing_manifest_path(&Path::from("cas/blobs")), None);
+    }
+
+    #[test]
+    fn unreproducible_crate_files() {
+        // Compression levels that are not tried
+        let crate_file = TarballBuilder::new("foo", "1.0.0")
+            .add_file("foo-1.0.0/src/data.bin", &contents(0))
+            .build_gzip_with_level(flate2::Compression::none());
+        assert!(deduplicate(&crate_file).is_none());
+
+        // Trailing garbage
+        let mut crate_file = TarballBuilder::new("foo", "1.0.0")
+            .add_file("foo-1.0.0/src/data.bin", &contents(0))
+            .build();
+        crate_file.extend(b"garbage");
+        assert!(deduplicate(&crate_file).is_none());
+
+        assert!(deduplicate(b"").is_none());
+        assert!(deduplicate(b"not a crate file").is_none());
+    }
+
+    #[test]
+    fn gzip_headers() {
+        let mut header = vec![0x1f, 0x8b, 8, 0x08, 0, 0, 0, 0, 0, 255];
+        header.extend(b"foo-1.0.0.crate\0");
+        assert_eq!(gzip_header_len(&header), Some(header.len()));
+
+        assert_eq!(gzip_header_len(&header[..header.len() - 1]), None);
+        assert_eq!(gzip_header_len(&[0x28, 0xb5, 0x2f, 0xfd]), None);
+    }
+
+    #[test]
+    fn entry_sizes() {
+        assert_eq!(entry_size(b"00000001750\0"), Some(1000));
+        assert_eq!(entry_size(b"     1750 \0\0"), Some(1000));
+        assert_eq!(entry_size(b"\0\0\0\0\0\0\0\0\0\0\0\0"), Some(0));
+        assert_eq!(entry_size(b"\x80\0\0\0\0\0\0\0\0\0\x03\xe8"), Some(1000));
+        assert_eq!(entry_size(b"0000000175x\0"), None);
+    }
+}
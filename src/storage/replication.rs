@@ -0,0 +1,223 @@
+//! Mirroring of crate files, readmes and index files to replica buckets, e.g.
+//! in a second region for disaster recovery.
+//!
+//! Writes to the primary storage stay synchronous. Once the database change
+//! that belongs to a write is committed, a `replicate_file` background job
+//! copies the file from the primary storage to the replicas, or deletes it
+//! from them if it doesn't exist in the primary storage anymore. Since the
+//! jobs always copy the current state of the primary storage, they can run
+//! in any order, and failed jobs are retried by the background worker, so an
+//! outage of a replica never fails a publish. Replicas are audited with
+//! [`Storage::verify_replication`](super::Storage::verify_replication).
+
+use super::arc_store::ArcStore;
+use super::{build_s3, client_options, sha256_checksum, S3Config};
+use super::{crate_file_path, index_file_path, readme_path};
+use super::{CACHE_CONTROL_IMMUTABLE, CACHE_CONTROL_INDEX, CACHE_CONTROL_README};
+use super::{CONTENT_TYPE_CRATE, CONTENT_TYPE_INDEX, CONTENT_TYPE_README};
+use hyper::body::Bytes;
+use object_store::path::Path;
+use object_store::prefix::PrefixStore;
+use object_store::{ObjectStore, Result};
+
+/// The kinds of files that are replicated, which are uploaded with different
+/// `Content-Type` and `Cache-Control` headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    Crate,
+    Readme,
+    Index,
+}
+
+/// A file of the primary storage that is mirrored to the replicas, which is
+/// also the payload of the `replicate_file` background job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicatedFile {
+    pub(super) kind: FileKind,
+    pub(super) path: String,
+}
+
+impl ReplicatedFile {
+    pub fn crate_file(name: &str, version: &str) -> Self {
+        Self::new(FileKind::Crate, &crate_file_path(name, version))
+    }
+
+    pub fn readme(name: &str, version: &str) -> Self {
+        Self::new(FileKind::Readme, &readme_path(name, version))
+    }
+
+    pub fn index_file(name: &str) -> Self {
+        Self::new(FileKind::Index, &index_file_path(name))
+    }
+
+    pub(super) fn new(kind: FileKind, path: &Path) -> Self {
+        let path = path.to_string();
+        Self { kind, path }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub(super) fn parsed_path(&self) -> Result<Path> {
+        Path::parse(&self.path).map_err(|source| object_store::Error::InvalidPath { source })
+    }
+}
+
+/// Whether a file of the primary storage has been replicated correctly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicaState {
+    InSync,
+    /// The file doesn't exist in the replica.
+    Missing,
+    /// The file in the replica has a different SHA256 checksum than in the
+    /// primary storage, e.g. because a later upload was not replicated yet.
+    ChecksumMismatch {
+        primary: String,
+        replica: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaStatus {
+    /// The name of the replica bucket.
+    pub replica: String,
+    pub state: ReplicaState,
+}
+
+#[derive(Clone)]
+pub(super) struct Replica {
+    name: String,
+    crate_store: ArcStore,
+    readme_store: ArcStore,
+    /// Index files are stored below the `index/` prefix of the replica
+    /// bucket, instead of in a separate bucket like in the primary storage.
+    index_store: ArcStore,
+}
+
+impl Replica {
+    pub(super) fn s3(config: &S3Config, key_prefix: Option<&Path>) -> Self {
+        let options = client_options(CONTENT_TYPE_CRATE, CACHE_CONTROL_IMMUTABLE);
+        let crate_store = ArcStore::new(build_s3(config, options, key_prefix));
+
+        let options = client_options(CONTENT_TYPE_README, CACHE_CONTROL_README);
+        let readme_store = ArcStore::new(build_s3(config, options, key_prefix));
+
+        let options = client_options(CONTENT_TYPE_INDEX, CACHE_CONTROL_INDEX);
+        let index_store = build_s3(config, options, key_prefix);
+        let index_store = ArcStore::new(PrefixStore::new(index_store, "index"));
+
+        Self {
+            name: config.bucket.clone(),
+            crate_store,
+            readme_store,
+            index_store,
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn in_memory(name: &str) -> Self {
+        let store = ArcStore::new(object_store::memory::InMemory::new());
+        let index_store = ArcStore::new(PrefixStore::new(store.clone(), "index"));
+
+        Self {
+            name: name.into(),
+            crate_store: store.clone(),
+            readme_store: store,
+            index_store,
+        }
+    }
+
+    /// This should only be used for assertions in the test suite!
+    #[cfg(test)]
+    pub(super) fn as_inner(&self) -> &dyn ObjectStore {
+        &self.crate_store
+    }
+
+    fn store(&self, kind: FileKind) -> &ArcStore {
+        match kind {
+            FileKind::Crate => &self.crate_store,
+            FileKind::Readme => &self.readme_store,
+            FileKind::Index => &self.index_store,
+        }
+    }
+
+    /// Uploads the file, or deletes it if `bytes` is `None`.
+    async fn write(&self, kind: FileKind, path: &Path, bytes: Option<Bytes>) -> Result<()> {
+        let store = self.store(kind);
+        match bytes {
+            Some(bytes) => store.put(path, bytes).await,
+            None => match store.delete(path).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                Err(error) => Err(error),
+            },
+        }
+    }
+}
+
+pub(super) struct Replication {
+    replicas: Vec<Replica>,
+}
+
+impl Replication {
+    pub(super) fn new(replicas: Vec<Replica>) -> Self {
+        Self { replicas }
+    }
+
+    #[cfg(test)]
+    pub(super) fn replicas(&self) -> &[Replica] {
+        &self.replicas
+    }
+
+    /// Copies the file from the `primary` storage to all replicas, or
+    /// deletes it from them if it doesn't exist in the `primary` storage.
+    pub(super) async fn replicate(
+        &self,
+        primary: &dyn ObjectStore,
+        file: &ReplicatedFile,
+    ) -> Result<()> {
+        let path = file.parsed_path()?;
+        let bytes = match primary.get(&path).await {
+            Ok(result) => Some(result.bytes().await?),
+            Err(object_store::Error::NotFound { .. }) => None,
+            Err(error) => return Err(error),
+        };
+
+        for replica in &self.replicas {
+            replica.write(file.kind, &path, bytes.clone()).await?;
+            debug!(replica = %replica.name, %path, "Replicated file");
+        }
+
+        Ok(())
+    }
+
+    /// Compares the checksum of the file in all replicas with the one of the
+    /// file in the `primary` storage, which has to exist.
+    pub(super) async fn verify(
+        &self,
+        primary: &dyn ObjectStore,
+        kind: FileKind,
+        path: &Path,
+    ) -> Result<Vec<ReplicaStatus>> {
+        let primary_checksum = sha256_checksum(primary, path).await?;
+
+        let mut statuses = Vec::with_capacity(self.replicas.len());
+        for replica in &self.replicas {
+            let state = match sha256_checksum(replica.store(kind), path).await {
+                Ok(checksum) if checksum == primary_checksum => ReplicaState::InSync,
+                Ok(checksum) => ReplicaState::ChecksumMismatch {
+                    primary: primary_checksum.clone(),
+                    replica: checksum,
+                },
+                Err(object_store::Error::NotFound { .. }) => ReplicaState::Missing,
+                Err(error) => return Err(error),
+            };
+
+            let replica = replica.name.clone();
+            statuses.push(ReplicaStatus { replica, state });
+        }
+
+        Ok(statuses)
+    }
+}
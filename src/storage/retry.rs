@@ -0,0 +1,602 @@
+//! Retries of uploads to cloud storage backends, and a circuit breaker that
+//! pauses uploads while the backend keeps failing.
+//!
+//! Transient errors (server errors, timeouts and connection errors) are
+//! retried with a jittered exponential backoff, so that they don't fail a
+//! publish. If uploads still fail after all attempts several times in a row,
+//! the backend is assumed to be down and further uploads fail immediately
+//! with [`StorageUnavailable`] for a while, instead of tying up publish
+//! requests with retries that are unlikely to succeed.
+//!
+//! The HTTP clients of object_store retry requests on their own, so their
+//! retries are disabled for the upload stores, see [`client_retry_config`].
+//! Multipart uploads can't be replayed, so they are not retried, but they
+//! are still counted by the circuit breaker.
+
+use crate::env_optional;
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use hyper::body::Bytes;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore, Result,
+    RetryConfig as ClientRetryConfig,
+};
+use rand::Rng;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWrite;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    /// How often an upload is attempted before its error is returned.
+    pub max_attempts: u32,
+    /// The delay before the first retry, which is doubled for every further
+    /// retry. The actual delays are picked randomly between half of that and
+    /// the full delay, so that concurrent uploads don't retry in lockstep.
+    pub initial_backoff: Duration,
+    /// After how many consecutive failed uploads the circuit breaker opens.
+    pub failure_threshold: u32,
+    /// How long uploads fail immediately once the circuit breaker is open.
+    pub open_duration: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn from_environment() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_attempts: env_optional("STORAGE_UPLOAD_MAX_ATTEMPTS")
+                .unwrap_or(default.max_attempts)
+                .max(1),
+            initial_backoff: env_optional("STORAGE_UPLOAD_INITIAL_BACKOFF_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_backoff),
+            failure_threshold: env_optional("STORAGE_CIRCUIT_BREAKER_THRESHOLD")
+                .unwrap_or(default.failure_threshold)
+                .max(1),
+            open_duration: env_optional("STORAGE_CIRCUIT_BREAKER_OPEN_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.open_duration),
+        }
+    }
+}
+
+/// The error of uploads while the circuit breaker is open.
+///
+/// It is wrapped in an [`object_store::Error::Generic`], which can be checked
+/// with [`StorageUnavailable::is`].
+#[derive(Debug)]
+pub struct StorageUnavailable;
+
+impl StorageUnavailable {
+    pub fn is(error: &object_store::Error) -> bool {
+        match error {
+            object_store::Error::Generic { source, .. } => source.is::<Self>(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for StorageUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("uploads to the storage are paused after repeated failures")
+    }
+}
+
+impl std::error::Error for StorageUnavailable {}
+
+impl From<StorageUnavailable> for object_store::Error {
+    fn from(error: StorageUnavailable) -> Self {
+        Self::Generic {
+            store: "circuit_breaker",
+            source: Box::new(error),
+        }
+    }
+}
+
+/// The retry policy and circuit breaker, which are shared by all upload
+/// stores of a storage.
+#[derive(Debug)]
+pub(super) struct UploadPolicy {
+    config: RetryConfig,
+    breaker: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    /// Set while the circuit breaker is open. Once this time has passed,
+    /// uploads are attempted again, and the first one that fails opens the
+    /// circuit breaker again right away.
+    open_until: Option<Instant>,
+}
+
+impl UploadPolicy {
+    pub(super) fn new(config: RetryConfig) -> Self {
+        Self {
+            config,
+            breaker: Mutex::default(),
+        }
+    }
+
+    /// Runs an upload, retrying it if it fails with a transient error.
+    pub(super) async fn run<F, Fut>(&self, location: &Path, upload: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        self.check()?;
+
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            let error = match upload().await {
+                Ok(()) => {
+                    self.record_success();
+                    return Ok(());
+                }
+                Err(error) if !is_transient(&error) => return Err(error),
+                Err(error) => error,
+            };
+
+            if attempt >= self.config.max_attempts {
+                error!(%location, %error, attempt, "Failed to upload file, giving up");
+                self.record_failure();
+                return Err(error);
+            }
+
+            warn!(%location, %error, attempt, "Failed to upload file, retrying");
+            let delay = rand::thread_rng().gen_range(backoff / 2..=backoff);
+            tokio::time::sleep(delay).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    fn check(&self) -> Result<(), StorageUnavailable> {
+        let state = self.breaker.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if Instant::now() < open_until => Err(StorageUnavailable),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_success(&self) {
+        *self.breaker.lock().unwrap() = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.breaker.lock().unwrap();
+        state.consecutive_failures += 1;
+
+        let half_open = state.open_until.is_some();
+        if half_open || state.consecutive_failures >= self.config.failure_threshold {
+            let failures = state.consecutive_failures;
+            error!(failures, "Opening the circuit breaker of storage uploads");
+            state.open_until = Some(Instant::now() + self.config.open_duration);
+        }
+    }
+}
+
+/// The retry config of the HTTP clients of the upload stores, which disables
+/// the retries of object_store, so that they don't multiply the attempts of
+/// the [`RetryStore`] around them.
+pub(super) fn client_retry_config() -> ClientRetryConfig {
+    ClientRetryConfig {
+        max_retries: 0,
+        ..Default::default()
+    }
+}
+
+/// Server errors, timeouts and connection errors of requests to the backend,
+/// as opposed to client errors like denied requests, missing files or
+/// invalid paths, which fail the same way when they are retried.
+///
+/// object_store doesn't expose the HTTP errors of its clients, so the source
+/// chain of the error is searched for the underlying `reqwest` or I/O error.
+fn is_transient(error: &object_store::Error) -> bool {
+    let object_store::Error::Generic { source, .. } = error else {
+        return false;
+    };
+
+    let mut source: Option<&(dyn StdError + 'static)> = Some(&**source);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            if error.is_timeout() || error.is_connect() {
+                return true;
+            }
+            if let Some(status) = error.status() {
+                return status.is_server_error();
+            }
+        }
+        if let Some(error) = error.downcast_ref::<io::Error>() {
+            if is_transient_io_kind(error.kind()) {
+                return true;
+            }
+        }
+        source = error.source();
+    }
+
+    false
+}
+
+fn is_transient_io_kind(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::TimedOut
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+/// Like [`is_transient`], for the errors of multipart uploads, which wrap the
+/// errors of the store in I/O errors.
+fn is_transient_io(error: &io::Error) -> bool {
+    match error.get_ref() {
+        Some(inner) => match inner.downcast_ref::<object_store::Error>() {
+            Some(inner) => is_transient(inner),
+            None => is_transient_io_kind(error.kind()),
+        },
+        None => is_transient_io_kind(error.kind()),
+    }
+}
+
+/// Uploads files with the [`UploadPolicy`]. All other operations are passed
+/// through to the inner store.
+#[derive(Debug, Clone)]
+pub(super) struct RetryStore {
+    inner: Arc<dyn ObjectStore>,
+    policy: Arc<UploadPolicy>,
+}
+
+impl RetryStore {
+    pub(super) fn new(inner: Box<dyn ObjectStore>, policy: Arc<UploadPolicy>) -> Self {
+        let inner = Arc::from(inner);
+        Self { inner, policy }
+    }
+}
+
+impl fmt::Display for RetryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RetryStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for RetryStore {
+    async fn put(&self, location: &Path, bytes: Bytes) -> Result<()> {
+        let upload = || self.inner.put(location, bytes.clone());
+        self.policy.run(location, upload).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.policy.check()?;
+
+        let (id, inner) = match self.inner.put_multipart(location).await {
+            Ok(upload) => upload,
+            Err(error) => {
+                if is_transient(&error) {
+                    self.policy.record_failure();
+                }
+                return Err(error);
+            }
+        };
+
+        let writer = BreakerWriter {
+            inner,
+            policy: self.policy.clone(),
+        };
+
+        Ok((id, Box::new(writer)))
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn append(&self, location: &Path) -> Result<Box<dyn AsyncWrite + Unpin + Send>> {
+        self.inner.append(location).await
+    }
+
+    async fn get(&self, location: &Path) -> Result<GetResult> {
+        self.inner.get(location).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        self.inner.get_range(location, range).await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+        self.inner.get_ranges(location, ranges).await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn delete_stream<'a>(
+        &'a self,
+        locations: BoxStream<'a, Result<Path>>,
+    ) -> BoxStream<'a, Result<Path>> {
+        self.inner.delete_stream(locations)
+    }
+
+    async fn list(&self, prefix: Option<&Path>) -> Result<BoxStream<'_, Result<ObjectMeta>>> {
+        self.inner.list(prefix).await
+    }
+
+    async fn list_with_offset(
+        &self,
+        prefix: Option<&Path>,
+        offset: &Path,
+    ) -> Result<BoxStream<'_, Result<ObjectMeta>>> {
+        self.inner.list_with_offset(prefix, offset).await
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+
+    async fn rename_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename_if_not_exists(from, to).await
+    }
+}
+
+/// The writer of a multipart upload, which reports its outcome to the
+/// circuit breaker once it is shut down.
+struct BreakerWriter {
+    inner: Box<dyn AsyncWrite + Unpin + Send>,
+    policy: Arc<UploadPolicy>,
+}
+
+impl BreakerWriter {
+    fn record<T>(&self, result: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if let Poll::Ready(Err(error)) = &result {
+            if is_transient_io(error) {
+                self.policy.record_failure();
+            }
+        }
+        result
+    }
+}
+
+impl AsyncWrite for BreakerWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.record(result)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_flush(cx);
+        self.record(result)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_shutdown(cx);
+        if let Poll::Ready(Ok(())) = result {
+            self.policy.record_success();
+        }
+        self.record(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(open_duration: Duration) -> UploadPolicy {
+        UploadPolicy::new(RetryConfig {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            failure_threshold: 2,
+            open_duration,
+        })
+    }
+
+    fn transient_error() -> object_store::Error {
+        object_store::Error::Generic {
+            store: "test",
+            source: Box::new(io::Error::from(ErrorKind::ConnectionReset)),
+        }
+    }
+
+    /// Runs an upload that fails with a transient error `failures` times,
+    /// and returns the result and the number of attempts.
+    async fn upload(policy: &UploadPolicy, failures: u32) -> (Result<()>, u32) {
+        let attempts = AtomicU32::new(0);
+        let result = policy
+            .run(&Path::from("foo"), || async {
+                if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                    Err(transient_error())
+                } else {
+                    Ok(())
+                }
+            })
+            .await;
+
+        (result, attempts.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn retries() {
+        let policy = policy(Duration::from_secs(3600));
+
+        let (result, attempts) = upload(&policy, 2).await;
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+
+        let (result, attempts) = upload(&policy, 3).await;
+        assert!(!StorageUnavailable::is(&result.unwrap_err()));
+        assert_eq!(attempts, 3);
+
+        // Permanent errors are not retried
+        let permanent_errors = [
+            || object_store::Error::NotImplemented,
+            || object_store::Error::Generic {
+                store: "test",
+                source: "access denied".into(),
+            },
+        ];
+        for permanent_error in permanent_errors {
+            let attempts = AtomicU32::new(0);
+            let result = policy
+                .run(&Path::from("foo"), || async {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(permanent_error())
+                })
+                .await;
+            assert!(result.is_err());
+            assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(is_transient(&transient_error()));
+        assert!(!is_transient(&object_store::Error::NotFound {
+            path: "foo".into(),
+            source: Box::new(io::Error::from(ErrorKind::NotFound)),
+        }));
+
+        // The I/O errors of multipart uploads wrap the errors of the store
+        let error = io::Error::new(ErrorKind::Other, transient_error());
+        assert!(is_transient_io(&error));
+        assert!(is_transient_io(&io::Error::from(ErrorKind::TimedOut)));
+        assert!(!is_transient_io(&io::Error::from(
+            ErrorKind::PermissionDenied
+        )));
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker() {
+        let policy = policy(Duration::from_secs(3600));
+
+        // Successful uploads reset the count of failures
+        assert!(upload(&policy, 3).await.0.is_err());
+        assert!(upload(&policy, 0).await.0.is_ok());
+        assert!(upload(&policy, 3).await.0.is_err());
+
+        // The second failure in a row opens the circuit breaker
+        assert!(upload(&policy, 3).await.0.is_err());
+        let (result, attempts) = upload(&policy, 0).await;
+        assert!(StorageUnavailable::is(&result.unwrap_err()));
+        assert_eq!(attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn half_open_circuit_breaker() {
+        let policy = policy(Duration::ZERO);
+        assert!(upload(&policy, 3).await.0.is_err());
+        assert!(upload(&policy, 3).await.0.is_err());
+        assert!(policy.breaker.lock().unwrap().open_until.is_some());
+
+        // Once the circuit breaker is half-open, a single failure opens it
+        // again, and a single success closes it.
+        assert!(upload(&policy, 3).await.0.is_err());
+        assert_eq!(policy.breaker.lock().unwrap().consecutive_failures, 3);
+        assert!(upload(&policy, 0).await.0.is_ok());
+        assert!(policy.breaker.lock().unwrap().open_until.is_none());
+    }
+
+    /// A multipart upload whose writes fail with a reset connection.
+    struct ResetWriter;
+
+    impl AsyncWrite for ResetWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(io::Error::from(ErrorKind::ConnectionReset)))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn multipart_uploads() {
+        use tokio::io::AsyncWriteExt;
+
+        let policy = Arc::new(policy(Duration::from_secs(3600)));
+        let writer = |inner: Box<dyn AsyncWrite + Unpin + Send>| BreakerWriter {
+            inner,
+            policy: policy.clone(),
+        };
+
+        assert!(writer(Box::new(ResetWriter))
+            .write_all(b"foo")
+            .await
+            .is_err());
+        let mut upload = writer(Box::new(tokio::io::sink()));
+        upload.write_all(b"foo").await.unwrap();
+        upload.shutdown().await.unwrap();
+        assert_eq!(policy.breaker.lock().unwrap().consecutive_failures, 0);
+
+        assert!(writer(Box::new(ResetWriter))
+            .write_all(b"foo")
+            .await
+            .is_err());
+        assert!(writer(Box::new(ResetWriter))
+            .write_all(b"foo")
+            .await
+            .is_err());
+        assert!(policy.check().is_err());
+    }
+}
@@ -4,7 +4,7 @@ SELECT c.id, c.category, c.slug, c.description,
     FROM categories as c2
     WHERE c2.slug = c.slug
     OR c2.slug LIKE c.slug || '::%'
-  ), 0) as crates_cnt, c.created_at
+  ), 0) as crates_cnt, c.created_at, c.deprecated_at
 FROM categories as c
 WHERE c.category ILIKE $1 || '::%'
 AND c.category NOT ILIKE $1 || '::%::%'
@@ -24,6 +24,11 @@ pub struct Runner {
     thread_pool: ThreadPool,
     environment: Arc<Option<Environment>>,
     job_start_timeout: Duration,
+    /// Queue whose jobs are run, or `None` to run the jobs of all queues.
+    queue: Option<&'static str>,
+    /// Number of failed attempts after which a job is moved to the
+    /// `failed_background_jobs` table, or `None` to retry jobs forever.
+    max_retries: Option<i32>,
 }
 
 impl Runner {
@@ -41,9 +46,19 @@ impl Runner {
             thread_pool: ThreadPool::new(5),
             environment,
             job_start_timeout: Duration::from_secs(job_start_timeout),
+            queue: None,
+            max_retries: None,
         }
     }
 
+    /// Only runs the jobs of the `queue`, and gives up on jobs once they
+    /// failed `max_retries` times.
+    pub fn with_queue(mut self, queue: &'static str, max_retries: Option<i32>) -> Self {
+        self.queue = Some(queue);
+        self.max_retries = max_retries;
+        self
+    }
+
     #[cfg(test)]
     fn internal_test_runner(environment: Option<Environment>, url: String) -> Self {
         let connection_pool = r2d2::Pool::builder()
@@ -54,6 +69,8 @@ impl Runner {
             thread_pool: ThreadPool::new(2),
             environment: Arc::new(environment),
             job_start_timeout: Duration::from_secs(10),
+            queue: None,
+            max_retries: None,
         }
     }
 
@@ -63,6 +80,8 @@ impl Runner {
             thread_pool: ThreadPool::new(1),
             environment: Arc::new(Some(environment)),
             job_start_timeout: Duration::from_secs(5),
+            queue: None,
+            max_retries: None,
         }
     }
 
@@ -126,6 +145,8 @@ impl Runner {
 
         // The connection may not be `Send` so we need to clone the pool instead
         let pool = self.connection_pool.clone();
+        let queue = self.queue;
+        let max_retries = self.max_retries;
         self.thread_pool.execute(move || {
             let conn = &mut *match pool.get() {
                 Ok(conn) => conn,
@@ -137,7 +158,7 @@ impl Runner {
             };
 
             let job_run_result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
-                let job = match storage::find_next_unlocked_job(conn).optional() {
+                let job = match storage::find_next_unlocked_job(conn, queue).optional() {
                     Ok(Some(j)) => {
                         let _ = sender.send(Event::Working);
                         j
@@ -202,7 +223,7 @@ impl Runner {
                     Ok(_) => storage::delete_successful_job(conn, job_id)?,
                     Err(e) => {
                         eprintln!("Job {job_id} failed to run: {e}");
-                        storage::update_failed_job(conn, job_id);
+                        storage::update_failed_job(conn, job_id, &e.to_string(), max_retries);
                     }
                 }
                 Ok(())
@@ -400,6 +421,54 @@ mod tests {
         assert_eq!(1, tries);
     }
 
+    #[test]
+    fn jobs_of_other_queues_are_not_run() {
+        let _guard = TestGuard::lock();
+        let runner = runner().with_queue("readmes", None);
+        create_dummy_job(&runner);
+
+        let (sender, receiver) = sync_channel(1);
+        runner.get_single_job(sender, |_, _| panic!("job of another queue was run"));
+        runner.wait_for_jobs().unwrap();
+
+        assert!(matches!(receiver.recv().unwrap(), Event::NoJobAvailable));
+    }
+
+    #[test]
+    fn jobs_are_moved_to_failed_jobs_after_max_retries() {
+        use crate::schema::failed_background_jobs;
+        use diesel::dsl::{now, IntervalDsl};
+
+        let _guard = TestGuard::lock();
+        let runner = runner().with_queue("default", Some(2));
+        let job_id = create_dummy_job(&runner).id;
+
+        runner.get_single_job(dummy_sender(), |_, _| Err("first".into()));
+        runner.wait_for_jobs().unwrap();
+
+        let conn = &mut *runner.connection().unwrap();
+        diesel::update(background_jobs.find(job_id))
+            .set(last_retry.eq(now - 1.day()))
+            .execute(conn)
+            .unwrap();
+
+        runner.get_single_job(dummy_sender(), |_, _| Err("second".into()));
+        runner.wait_for_jobs().unwrap();
+
+        let remaining_jobs = background_jobs.count().get_result(conn);
+        assert_eq!(Ok(0), remaining_jobs);
+
+        let failed_jobs = failed_background_jobs::table
+            .select((
+                failed_background_jobs::id,
+                failed_background_jobs::retries,
+                failed_background_jobs::error,
+            ))
+            .load::<(i64, i32, String)>(conn)
+            .unwrap();
+        assert_eq!(failed_jobs, vec![(job_id, 2, "second".to_string())]);
+    }
+
     // Since these tests deal with behavior concerning multiple connections
     // running concurrently, they have to run outside of a transaction.
     // Therefore we can't run more than one at a time.
@@ -418,7 +487,7 @@ mod tests {
 
     impl<'a> Drop for TestGuard<'a> {
         fn drop(&mut self) {
-            diesel::sql_query("TRUNCATE TABLE background_jobs")
+            diesel::sql_query("TRUNCATE TABLE background_jobs, failed_background_jobs")
                 .execute(&mut *runner().connection().unwrap())
                 .unwrap();
         }
@@ -1,7 +1,7 @@
 use diesel::dsl::now;
 use diesel::pg::Pg;
 use diesel::prelude::*;
-use diesel::sql_types::{Bool, Integer, Interval};
+use diesel::sql_types::{Bool, Integer, Interval, Text};
 use diesel::{delete, update};
 
 use crate::schema::{self, background_jobs};
@@ -22,15 +22,25 @@ fn retriable() -> Box<dyn BoxableExpression<background_jobs::table, Pg, SqlType
     Box::new(last_retry.lt(now - 1.minute().into_sql::<Interval>() * power(2, retries)))
 }
 
-/// Finds the next job that is unlocked, and ready to be retried. If a row is
-/// found, it will be locked.
-pub(super) fn find_next_unlocked_job(conn: &mut PgConnection) -> QueryResult<BackgroundJob> {
+/// Finds the next job of the `job_queue` (or of any queue if `None`) that is
+/// unlocked, and ready to be retried. If a row is found, it will be locked.
+pub(super) fn find_next_unlocked_job(
+    conn: &mut PgConnection,
+    job_queue: Option<&str>,
+) -> QueryResult<BackgroundJob> {
     use schema::background_jobs::dsl::*;
 
-    background_jobs
+    let mut query = background_jobs
         .select((id, job_type, data))
         .filter(retriable())
         .order((priority.desc(), id))
+        .into_boxed();
+
+    if let Some(job_queue) = job_queue {
+        query = query.filter(queue.eq(job_queue));
+    }
+
+    query
         .for_update()
         .skip_locked()
         .first::<BackgroundJob>(conn)
@@ -56,12 +66,65 @@ pub(super) fn delete_successful_job(conn: &mut PgConnection, job_id: i64) -> Que
 
 /// Marks that we just tried and failed to run a job.
 ///
+/// Jobs that failed `max_retries` times are moved to the
+/// `failed_background_jobs` table instead, so that they are not retried
+/// anymore.
+///
 /// Ignores any database errors that may have occurred. If the DB has gone away,
 /// we assume that just trying again with a new connection will succeed.
-pub(super) fn update_failed_job(conn: &mut PgConnection, job_id: i64) {
+pub(super) fn update_failed_job(
+    conn: &mut PgConnection,
+    job_id: i64,
+    error: &str,
+    max_retries: Option<i32>,
+) {
     use schema::background_jobs::dsl::*;
 
-    let _ = update(background_jobs.find(job_id))
+    let result = update(background_jobs.find(job_id))
         .set((retries.eq(retries + 1), last_retry.eq(now)))
-        .execute(conn);
+        .returning(retries)
+        .get_result::<i32>(conn);
+
+    if let (Ok(job_retries), Some(max_retries)) = (result, max_retries) {
+        if job_retries >= max_retries {
+            let _ = move_to_failed_jobs(conn, job_id, error);
+        }
+    }
+}
+
+fn move_to_failed_jobs(conn: &mut PgConnection, job_id: i64, error: &str) -> QueryResult<()> {
+    use schema::failed_background_jobs;
+
+    conn.transaction(|conn| {
+        let job = background_jobs::table.find(job_id).select((
+            background_jobs::id,
+            background_jobs::job_type,
+            background_jobs::data,
+            background_jobs::queue,
+            background_jobs::retries,
+            background_jobs::created_at,
+            error.into_sql::<Text>(),
+        ));
+
+        diesel::insert_into(failed_background_jobs::table)
+            .values(job)
+            .into_columns((
+                failed_background_jobs::id,
+                failed_background_jobs::job_type,
+                failed_background_jobs::data,
+                failed_background_jobs::queue,
+                failed_background_jobs::retries,
+                failed_background_jobs::created_at,
+                failed_background_jobs::error,
+            ))
+            .execute(conn)?;
+
+        delete(background_jobs::table.find(job_id)).execute(conn)?;
+
+        warn!(
+            job_id,
+            error, "Job failed too often and will not be retried"
+        );
+        Ok(())
+    })
 }
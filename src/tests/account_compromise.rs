@@ -0,0 +1,115 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use chrono::NaiveDateTime;
+use crates_io::admin::account_compromise::{resolve_compromise, start_compromise_mode};
+use crates_io::models::ModerationFlag;
+use crates_io::schema::{audit_events, users};
+use diesel::prelude::*;
+use http::StatusCode;
+
+const HOLD_MESSAGE: &str = "Publishing from this account is on hold because it may have been \
+    compromised. Please contact help@crates.io for further information.";
+
+const ACCOUNT_HOLD_MESSAGE: &str = "This account is on hold because it may have been \
+    compromised. Please contact help@crates.io for further information.";
+
+#[test]
+fn account_compromise_workflow() {
+    let (app, _, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+
+    app.db(|conn| {
+        let user = user.as_model();
+        assert_ok!(start_compromise_mode(
+            user,
+            admin.as_model(),
+            "phishing",
+            30,
+            conn
+        ));
+
+        // An account can only be compromised once at a time
+        assert_err!(start_compromise_mode(
+            user,
+            admin.as_model(),
+            "phishing",
+            30,
+            conn
+        ));
+    });
+
+    // Existing API tokens and browser sessions are revoked
+    let response = token.publish_crate(PublishBuilder::new("foo", "1.1.0"));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = user.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // New publishes are held, even with new API tokens
+    let new_token = user.db_new_token("new");
+    let response = new_token.publish_crate(PublishBuilder::new("foo", "1.1.0"));
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": HOLD_MESSAGE }] })
+    );
+
+    // No owners can be added, even with new API tokens
+    let response = new_token.add_named_owner("foo", "admin");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": ACCOUNT_HOLD_MESSAGE }] })
+    );
+
+    // No API tokens can be created, even with a new browser session
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::sessions_revoked_at.eq(None::<NaiveDateTime>))
+            .execute(conn)
+            .unwrap();
+    });
+    let response = user.put::<()>(
+        "/api/v1/me/tokens",
+        br#"{ "api_token": { "name": "bar" } }"#,
+    );
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": ACCOUNT_HOLD_MESSAGE }] })
+    );
+
+    // Recently published versions are flagged for review
+    let flags = app.db(|conn| ModerationFlag::unresolved(conn).unwrap());
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].reason, "account_compromise");
+    assert_eq!(flags[0].details["reason"], "phishing");
+
+    app.run_pending_background_jobs();
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(
+        emails[0].subject,
+        "Your crates.io account may have been compromised"
+    );
+
+    app.db(|conn| {
+        assert_ok!(resolve_compromise(user.as_model(), admin.as_model(), conn));
+
+        // The resolution is recorded for the admin
+        let actor: Option<i32> = audit_events::table
+            .filter(audit_events::kind.eq("admin.account_compromise.resolve"))
+            .select(audit_events::user_id)
+            .first(conn)
+            .unwrap();
+        assert_eq!(actor, Some(admin.as_model().id));
+    });
+
+    new_token
+        .publish_crate(PublishBuilder::new("foo", "1.1.0"))
+        .good();
+}
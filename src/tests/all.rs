@@ -25,6 +25,7 @@ use std::{
 
 use diesel::prelude::*;
 
+mod account_compromise;
 mod account_lock;
 mod authentication;
 mod blocked_routes;
@@ -32,6 +33,7 @@ mod builders;
 mod categories;
 mod dump_db;
 mod github_secret_scanning;
+mod import_registry;
 mod krate;
 mod middleware;
 mod models;
@@ -1,8 +1,10 @@
+use crates_io::models::DependencyKind;
 use crates_io::views::krate_publish as u;
 
 /// A builder for constructing a dependency of another crate.
 pub struct DependencyBuilder {
     explicit_name_in_toml: Option<u::EncodableDependencyName>,
+    kind: Option<DependencyKind>,
     name: String,
     registry: Option<String>,
     version_req: u::EncodableCrateVersionReq,
@@ -13,6 +15,7 @@ impl DependencyBuilder {
     pub fn new(name: &str) -> Self {
         DependencyBuilder {
             explicit_name_in_toml: None,
+            kind: None,
             name: name.to_string(),
             registry: None,
             version_req: u::EncodableCrateVersionReq("> 0".to_string()),
@@ -31,6 +34,12 @@ impl DependencyBuilder {
         self
     }
 
+    /// Set the kind of this dependency, e.g. to make it a dev-dependency.
+    pub fn kind(mut self, kind: DependencyKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
     /// Set the version requirement for this dependency.
     ///
     /// # Panics
@@ -52,7 +61,7 @@ impl DependencyBuilder {
             features: Vec::new(),
             version_req: self.version_req,
             target: None,
-            kind: None,
+            kind: self.kind,
             explicit_name_in_toml: self.explicit_name_in_toml,
             registry: self.registry,
         }
@@ -19,6 +19,8 @@ pub struct PublishBuilder {
     license: Option<String>,
     license_file: Option<String>,
     readme: Option<String>,
+    repository: Option<String>,
+    badges: BTreeMap<String, serde_json::Value>,
     tarball: Vec<u8>,
     version: semver::Version,
     features: BTreeMap<u::EncodableFeatureName, Vec<u::EncodableFeature>>,
@@ -38,6 +40,8 @@ impl PublishBuilder {
             license: Some("MIT".to_string()),
             license_file: None,
             readme: None,
+            repository: None,
+            badges: BTreeMap::new(),
             tarball: TarballBuilder::new(krate_name, version).build(),
             version: semver::Version::parse(version).unwrap(),
             features: BTreeMap::new(),
@@ -98,6 +102,12 @@ impl PublishBuilder {
         self
     }
 
+    /// Set the repository URL of this crate
+    pub fn repository(mut self, repository: &str) -> Self {
+        self.repository = Some(repository.to_string());
+        self
+    }
+
     /// Add a keyword to this crate.
     pub fn keyword(mut self, keyword: &str) -> Self {
         self.keywords.push(keyword.into());
@@ -123,6 +133,12 @@ impl PublishBuilder {
         self
     }
 
+    /// Add a badge from the `[badges]` section of the manifest
+    pub fn badge(mut self, name: &str, attributes: serde_json::Value) -> Self {
+        self.badges.insert(name.to_string(), attributes);
+        self
+    }
+
     // Adds a feature.
     pub fn feature(mut self, name: &str, values: &[&str]) -> Self {
         let values = values
@@ -156,8 +172,9 @@ impl PublishBuilder {
             ),
             license: self.license,
             license_file: self.license_file,
-            repository: None,
+            repository: self.repository,
             links: None,
+            badges: self.badges,
         };
 
         (serde_json::to_string(&new_crate).unwrap(), self.tarball)
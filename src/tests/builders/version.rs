@@ -2,6 +2,7 @@ use crates_io::{
     models::{Crate, NewVersion, Version},
     schema::{dependencies, versions},
     util::errors::AppResult,
+    util::source_links::VersionSource,
 };
 use std::collections::BTreeMap;
 
@@ -112,6 +113,8 @@ impl<'a> VersionBuilder<'a> {
             self.checksum,
             self.links,
             self.rust_version,
+            false,
+            VersionSource::default(),
         )?
         .save(connection, "someone@example.com")?;
 
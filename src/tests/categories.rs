@@ -64,23 +64,33 @@ fn sync_adds_new_categories() {
 }
 
 #[test]
-fn sync_removes_missing_categories() {
+fn sync_keeps_existing_categories() {
     let conn = &mut pg_connection();
 
     ::crates_io::boot::categories::sync_with_connection(ALGORITHMS_AND_SUCH, conn).unwrap();
-    ::crates_io::boot::categories::sync_with_connection(ALGORITHMS, conn).unwrap();
+    ::crates_io::boot::categories::sync_with_connection(ALGORITHMS_AND_ANOTHER, conn).unwrap();
 
     let categories = select_slugs(conn);
-    assert_eq!(categories, vec!["algorithms"]);
+    assert_eq!(
+        categories,
+        vec!["algorithms", "algorithms::such", "another"]
+    );
 }
 
 #[test]
-fn sync_adds_and_removes() {
+fn sync_skips_redirected_slugs() {
+    use crates_io::models::Category;
+
     let conn = &mut pg_connection();
 
     ::crates_io::boot::categories::sync_with_connection(ALGORITHMS_AND_SUCH, conn).unwrap();
-    ::crates_io::boot::categories::sync_with_connection(ALGORITHMS_AND_ANOTHER, conn).unwrap();
+
+    let such: Category = Category::by_slug("algorithms::such").first(conn).unwrap();
+    such.rename("algorithms::other", "Algorithms::Other", conn)
+        .unwrap();
+
+    ::crates_io::boot::categories::sync_with_connection(ALGORITHMS_AND_SUCH, conn).unwrap();
 
     let categories = select_slugs(conn);
-    assert_eq!(categories, vec!["algorithms", "another"]);
+    assert_eq!(categories, vec!["algorithms", "algorithms::other"]);
 }
@@ -0,0 +1,65 @@
+use crate::util::TestApp;
+use crates_io::admin::import_registry::{find_or_create_user, github_user_id, SourceOwner};
+use oauth2::AccessToken;
+
+fn source_owner(login: &str) -> SourceOwner {
+    SourceOwner {
+        login: login.into(),
+        kind: Some("user".into()),
+        name: None,
+        avatar: None,
+    }
+}
+
+#[test]
+fn owners_are_matched_by_github_id() {
+    let (app, _) = TestApp::init().empty();
+    let user = app.db_new_user("foo");
+    let user = user.as_model();
+
+    app.db(|conn| {
+        // The GitHub account was renamed, but it is still the same user
+        let owner = source_owner("bar");
+        let found = find_or_create_user(&owner, Some(user.gh_id), conn).unwrap();
+        assert_eq!(found.id, user.id);
+    });
+}
+
+#[test]
+fn renamed_accounts_do_not_take_over_users() {
+    let (app, _) = TestApp::init().empty();
+    let user = app.db_new_user("foo");
+    let user = user.as_model();
+
+    app.db(|conn| {
+        // A different GitHub account now uses a case-variant of the login
+        let owner = source_owner("Foo");
+        let created = find_or_create_user(&owner, Some(user.gh_id + 1000), conn).unwrap();
+        assert_ne!(created.id, user.id);
+        assert_eq!(created.gh_id, user.gh_id + 1000);
+        assert_eq!(created.gh_login, "Foo");
+
+        // Owners without a GitHub account are never matched by their login
+        let owner = source_owner("foo");
+        let created = find_or_create_user(&owner, None, conn).unwrap();
+        assert_ne!(created.id, user.id);
+        assert_eq!(created.gh_id, -1);
+        assert_eq!(created.gh_login, "foo");
+    });
+}
+
+#[test]
+fn github_ids_are_looked_up_by_login() {
+    let (app, _) = TestApp::init().empty();
+    let github = &*app.as_inner().github;
+    let token = AccessToken::new("token".into());
+
+    assert_eq!(
+        github_user_id(github, "user-one-team", &token).unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        github_user_id(github, "unknown-user", &token).unwrap(),
+        None
+    );
+}
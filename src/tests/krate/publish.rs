@@ -1,15 +1,21 @@
-use crate::builders::{CrateBuilder, DependencyBuilder, PublishBuilder};
+use crate::builders::{CrateBuilder, DependencyBuilder, PublishBuilder, VersionBuilder};
 use crate::new_category;
 use crate::util::insta::assert_yaml_snapshot;
 use crate::util::{RequestHelper, TestApp};
+use crates_io::background_jobs::Job;
 use crates_io::controllers::krate::publish::{
     missing_metadata_error_message, MISSING_RIGHTS_ERROR_MESSAGE,
 };
 use crates_io::models::krate::MAX_NAME_LENGTH;
-use crates_io::schema::{api_tokens, emails, versions_published_by};
-use crates_io::views::GoodCrate;
+use crates_io::models::DependencyKind;
+use crates_io::policy::PublishPolicy;
+use crates_io::schema::{
+    api_tokens, dependencies, dependency_requirements, emails, publish_diagnostics, tombstones,
+    users, version_publish_warnings, versions_published_by,
+};
+use crates_io::views::{GoodCrate, PublishWarningKind};
 use crates_io_tarball::TarballBuilder;
-use diesel::{delete, update, ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{delete, update, Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
 use flate2::Compression;
 use http::StatusCode;
 use std::collections::BTreeMap;
@@ -149,6 +155,62 @@ fn new_krate() {
     });
 }
 
+#[test]
+fn new_krate_records_stage_durations() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    let crate_to_publish = PublishBuilder::new("foo_stages", "1.0.0").readme("hello world");
+    user.publish_crate(crate_to_publish).good();
+
+    let metric = &app.as_inner().instance_metrics.publish_stage_duration;
+    for stage in [
+        "parse_body",
+        "validate_tarball",
+        "insert_version",
+        "enqueue_readme",
+        "upload_crate",
+        "enqueue_index_sync",
+    ] {
+        let sample_count = metric.with_label_values(&[stage]).get_sample_count();
+        assert_eq!(sample_count, 1, "unexpected sample count for stage {stage}");
+    }
+}
+
+#[test]
+fn new_krate_dry_run() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    let crate_to_publish = PublishBuilder::new("foo_dry_run", "1.0.0").readme("hello world");
+    let response =
+        user.put::<GoodCrate>("/api/v1/crates/new?dry_run=true", &crate_to_publish.body());
+    let json = response.good();
+
+    assert_eq!(json.krate.name, "foo_dry_run");
+    let index_entry = json.index_entry.unwrap();
+    assert_eq!(index_entry.name, "foo_dry_run");
+    assert_eq!(index_entry.vers, "1.0.0");
+
+    // The crate file and the rendered readme are only uploaded to the shadow
+    // namespace of the dry run
+    let dry_run = json.dry_run.unwrap();
+    assert_eq!(dry_run.readme_error, None);
+    let files = app.stored_files();
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().all(|file| file.starts_with("dry-runs/")));
+    assert!(files[0].ends_with("/crates/foo_dry_run/foo_dry_run-1.0.0.crate"));
+    assert!(dry_run.crate_file.ends_with(&files[0]));
+    assert!(files[1].ends_with("/readmes/foo_dry_run/foo_dry_run-1.0.0.html"));
+    assert!(dry_run.readme.unwrap().ends_with(&files[1]));
+
+    // Nothing else of the dry run may be persisted, and its files are kept
+    // until they expired
+    app.db(|conn| Job::expire_publish_dry_runs().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+    assert_eq!(app.stored_files(), files);
+    user.get::<()>("/api/v1/crates/foo_dry_run")
+        .assert_not_found();
+}
+
 #[test]
 fn new_krate_with_token() {
     let (app, _, _, token) = TestApp::full().with_token();
@@ -253,7 +315,52 @@ fn new_krate_with_dependency() {
 
     assert_eq!(dependencies.len(), 1);
     assert_eq!(dependencies[0].crate_id, "foo-dep");
-    assert_eq!(dependencies[0].req, "1.0.0");
+    assert_eq!(dependencies[0].req, "^1.0.0");
+}
+
+#[test]
+fn dependency_requirements_are_interned() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo-dep", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("bar-dep", user.as_model().id).expect_build(conn);
+    });
+
+    let crate_to_publish = PublishBuilder::new("first", "1.0.0")
+        .dependency(DependencyBuilder::new("foo-dep").version_req("^1.0.0"))
+        .dependency(DependencyBuilder::new("bar-dep").version_req("^1.0.0"));
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("second", "1.0.0")
+        .dependency(DependencyBuilder::new("foo-dep").version_req("^1.0.0"))
+        .dependency(DependencyBuilder::new("bar-dep").version_req("^2.0.0"));
+    token.publish_crate(crate_to_publish).good();
+
+    app.db(|conn| {
+        let requirements = dependency_requirements::table
+            .select(dependency_requirements::req)
+            .order(dependency_requirements::req)
+            .load::<String>(conn)
+            .unwrap();
+        assert_eq!(requirements, vec!["^1.0.0", "^2.0.0"]);
+
+        // Every dependency references the requirement it was published with
+        let mismatched = dependencies::table
+            .inner_join(dependency_requirements::table)
+            .filter(dependencies::req.ne(dependency_requirements::req))
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap();
+        assert_eq!(mismatched, 0);
+
+        let unreferenced = dependencies::table
+            .filter(dependencies::requirement_id.is_null())
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap();
+        assert_eq!(unreferenced, 0);
+    });
 }
 
 #[test]
@@ -284,7 +391,7 @@ fn new_krate_with_broken_dependency_requirement() {
 
     assert_eq!(
         response,
-        json!({"errors": [{"detail": "invalid upload request: invalid value: string \"broken\", expected a valid version req at line 1 column 136"}]})
+        json!({"errors": [{"detail": "invalid version requirement `broken` for dependency `foo-dep`: unexpected character 'b' while parsing major version number"}]})
     );
 
     assert!(app.stored_files().is_empty());
@@ -345,6 +452,52 @@ fn reject_new_crate_with_alternative_registry_dependency() {
     assert!(app.stored_files().is_empty());
 }
 
+#[test]
+fn new_crate_with_allowed_alternative_registry_dependency() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.cross_registry.allowed_registries =
+                vec!["https://server.example/path/to/registry".into()];
+        })
+        .with_token();
+
+    let dependency =
+        DependencyBuilder::new("dep").registry("https://server.example/path/to/registry/");
+
+    let crate_to_publish =
+        PublishBuilder::new("depends-on-alt-registry", "1.0.0").dependency(dependency);
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(json.warnings.other, Vec::<String>::new());
+
+    let crates = app.crates_from_index_head("depends-on-alt-registry");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].deps.len(), 1);
+    assert_eq!(crates[0].deps[0].name, "dep");
+    assert_eq!(
+        crates[0].deps[0].registry.as_deref(),
+        Some("https://server.example/path/to/registry/")
+    );
+}
+
+#[test]
+fn new_crate_with_unknown_alternative_registry_dependency_warning() {
+    let (_, _, _, token) = TestApp::full()
+        .with_config(|config| config.cross_registry.warn_on_unknown = true)
+        .with_token();
+
+    let dependency =
+        DependencyBuilder::new("dep").registry("https://server.example/path/to/registry");
+
+    let crate_to_publish =
+        PublishBuilder::new("depends-on-alt-registry", "1.0.0").dependency(dependency);
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(
+        json.warnings.other,
+        vec!["dependency `dep` is hosted on the unknown registry `https://server.example/path/to/registry`, \
+            so this crate can only be installed by users with access to that registry"]
+    );
+}
+
 #[test]
 fn new_krate_with_wildcard_dependency() {
     let (app, _, user, token) = TestApp::full().with_token();
@@ -674,6 +827,65 @@ fn new_krate_with_readme_and_plus_version() {
     assert_eq!(app.stored_files(), expected_files);
 }
 
+#[test]
+fn new_krate_with_license_files() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\nlicense-file = \"LICENSE-MIT\"")
+        .add_file("foo-1.0.0/COPYRIGHT", b"Copyright foo")
+        .add_file("foo-1.0.0/LICENSE-MIT", b"MIT License")
+        .add_file("foo-1.0.0/src/license.rs", b"pub fn foo() {}")
+        .build();
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0")
+        .unset_license()
+        .license_file("LICENSE-MIT")
+        .tarball(tarball);
+    token.publish_crate(crate_to_publish).good();
+
+    let expected_files = vec![
+        "crates/foo/foo-1.0.0.crate",
+        "index/3/f/foo",
+        "license-texts/foo/foo-1.0.0.json",
+    ];
+    assert_eq!(app.stored_files(), expected_files);
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let store = app.as_inner().storage.as_inner();
+    let bytes = rt.block_on(async {
+        let path = "license-texts/foo/foo-1.0.0.json".into();
+        store.get(&path).await.unwrap().bytes().await.unwrap()
+    });
+    let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "license": null,
+            "files": [
+                { "path": "LICENSE-MIT", "contents": "MIT License" },
+                { "path": "COPYRIGHT", "contents": "Copyright foo" },
+            ],
+        })
+    );
+}
+
+#[test]
+fn new_krate_with_plus_version_and_stripped_package_directory() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .add_file("foo-1.0.0/src/lib.rs", b"pub fn foo() {}")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0+foo").tarball(tarball);
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(json.krate.max_version, "1.0.0+foo");
+
+    let expected_files = vec!["crates/foo/foo-1.0.0+foo.crate", "index/3/f/foo"];
+    assert_eq!(app.stored_files(), expected_files);
+}
+
 #[test]
 fn new_krate_without_any_email_fails() {
     let (app, _, _, token) = TestApp::full().with_token();
@@ -739,6 +951,25 @@ fn publish_records_an_audit_action() {
     assert_eq!(action.user.id, token.as_model().user_id);
 }
 
+#[test]
+fn publish_normalizes_metadata_urls() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("docscrate", "0.1.0")
+        .documentation("http://docs.rs/docscrate?utm_source=readme&utm_medium=badge");
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(
+        json.krate.documentation,
+        Some("https://docs.rs/docscrate".to_owned())
+    );
+
+    let json = anon.show_crate("docscrate");
+    assert_eq!(
+        json.krate.documentation,
+        Some("https://docs.rs/docscrate".to_owned())
+    );
+}
+
 #[test]
 fn publish_after_removing_documentation() {
     let (app, anon, user, token) = TestApp::full().with_token();
@@ -844,6 +1075,372 @@ fn ignored_categories() {
     assert_eq!(json.krate.name, "foo_ignored_cat");
     assert_eq!(json.krate.max_version, "1.0.0");
     assert_eq!(json.warnings.invalid_categories, vec!["bar"]);
+    assert_eq!(json.warnings.details.len(), 1);
+    assert_eq!(
+        json.warnings.details[0].kind,
+        PublishWarningKind::InvalidCategory
+    );
+}
+
+#[test]
+fn publish_warnings() {
+    let (_, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.max_unpack_size = 10_000;
+            config.large_file_warning_size = 100;
+        })
+        .with_token();
+
+    let tarball = TarballBuilder::new("foo_warnings", "1.0.0")
+        .add_file("foo_warnings-1.0.0/src/lib.rs", b"pub fn foo() {}")
+        .add_file("foo_warnings-1.0.0/data.bin", &[0; 200])
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo_warnings", "1.0.0")
+        .tarball(tarball)
+        .keyword("foo")
+        .keyword("FOO")
+        .documentation("https://rust-ci.org/foo/foo_warnings/doc/foo_warnings/")
+        .badge("maintenance", json!({ "status": "actively-developed" }));
+    let json = token.publish_crate(crate_to_publish).good();
+
+    let details = json
+        .warnings
+        .details
+        .iter()
+        .map(|warning| (warning.kind, warning.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        details,
+        vec![
+            (
+                PublishWarningKind::DuplicateKeyword,
+                "the keyword `FOO` is a duplicate and was ignored"
+            ),
+            (
+                PublishWarningKind::DeprecatedField,
+                "the `maintenance` badge of the `[badges]` manifest section was ignored, \
+                 because badges are no longer displayed on crates.io"
+            ),
+            (
+                PublishWarningKind::LargeFile,
+                "the file `data.bin` is 200 bytes large, consider excluding it from the \
+                 package if it is not needed to build the crate"
+            ),
+            (
+                PublishWarningKind::BlockedDocumentationUrl,
+                "the documentation URL `https://rust-ci.org/foo/foo_warnings/doc/foo_warnings/` \
+                 points to a host that is known not to host documentation, so it will not be \
+                 displayed"
+            ),
+        ]
+    );
+
+    // Older versions of cargo only display the `other` warnings
+    let other = details
+        .iter()
+        .map(|(_, message)| *message)
+        .collect::<Vec<_>>();
+    assert_eq!(json.warnings.other, other);
+}
+
+#[test]
+fn publish_warnings_for_yanked_lockfile_dependencies() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("bar_locked", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0").yanked(true))
+            .version("1.0.1")
+            .expect_build(conn);
+    });
+
+    let lockfile = br#"version = 3
+
+[[package]]
+name = "bar_locked"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "baz_locked"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "foo_locked"
+version = "1.0.0"
+dependencies = ["bar_locked", "baz_locked"]
+"#;
+
+    let tarball = TarballBuilder::new("foo_locked", "1.0.0")
+        .add_file("foo_locked-1.0.0/Cargo.lock", lockfile)
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo_locked", "1.0.0").tarball(tarball);
+    let json = token.publish_crate(crate_to_publish).good();
+
+    let details = json
+        .warnings
+        .details
+        .iter()
+        .map(|warning| (warning.kind, warning.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        details,
+        vec![(
+            PublishWarningKind::YankedDependency,
+            "the `Cargo.lock` file pins `bar_locked` to the yanked version 1.0.0, \
+             consider updating it with `cargo update -p bar_locked`"
+        )]
+    );
+}
+
+#[test]
+fn publish_warnings_for_dependency_graph() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("bar_graph", user.as_model().id)
+            .version("1.0.0")
+            .version("2.0.0")
+            .expect_build(conn);
+    });
+
+    let crate_to_publish = PublishBuilder::new("foo_graph", "1.0.0")
+        .dependency(DependencyBuilder::new("bar_graph").version_req("^1"))
+        .dependency(
+            DependencyBuilder::new("bar_graph")
+                .version_req("^2")
+                .kind(DependencyKind::Dev),
+        );
+    let json = token.publish_crate(crate_to_publish).good();
+
+    let details = json
+        .warnings
+        .details
+        .iter()
+        .map(|warning| (warning.kind, warning.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        details,
+        vec![(
+            PublishWarningKind::ConflictingDependency,
+            "the dependency `bar_graph` is declared with incompatible version requirements, \
+             `^1` in `dependencies` and `^2` in `dev-dependencies`, so two different versions \
+             of it end up in the dependency graph"
+        )]
+    );
+
+    let crate_to_publish = PublishBuilder::new("foo_graph", "1.0.1").dependency(
+        DependencyBuilder::new("foo_graph")
+            .version_req("^1")
+            .kind(DependencyKind::Dev),
+    );
+    let json = token.publish_crate(crate_to_publish).good();
+
+    let details = json
+        .warnings
+        .details
+        .iter()
+        .map(|warning| (warning.kind, warning.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        details,
+        vec![(
+            PublishWarningKind::SelfDevDependency,
+            "the crate has a dev-dependency on itself (`foo_graph = \"^1\"`), which creates a \
+             cycle in the dependency graph that tools resolving dev-dependencies may fail on"
+        )]
+    );
+
+    // The warnings are stored with the versions
+    let stored: i64 = app.db(|conn| {
+        version_publish_warnings::table
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(stored, 2);
+}
+
+#[test]
+fn publish_warning_for_dirty_worktree() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    let sha = "0123456789abcdef0123456789abcdef01234567";
+    let vcs_info = format!(r#"{{"git": {{"sha1": "{sha}", "dirty": true}}, "path_in_vcs": ""}}"#);
+    let tarball = TarballBuilder::new("foo_dirty", "1.0.0")
+        .add_file("foo_dirty-1.0.0/.cargo_vcs_info.json", vcs_info.as_bytes())
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo_dirty", "1.0.0").tarball(tarball);
+    let json = token.publish_crate(crate_to_publish).good();
+
+    let details = json
+        .warnings
+        .details
+        .iter()
+        .map(|warning| (warning.kind, warning.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        details,
+        vec![(
+            PublishWarningKind::DirtyWorktree,
+            "the crate was packaged from a git working tree with uncommitted changes, so its \
+             contents may differ from commit `0123456789abcdef0123456789abcdef01234567`; \
+             consider publishing from a clean checkout"
+        )]
+    );
+    assert!(
+        anon.show_version("foo_dirty", "1.0.0")
+            .version
+            .dirty_worktree
+    );
+
+    // Clean working trees are not reported
+    let vcs_info = format!(r#"{{"git": {{"sha1": "{sha}"}}, "path_in_vcs": ""}}"#);
+    let tarball = TarballBuilder::new("foo_dirty", "1.0.1")
+        .add_file("foo_dirty-1.0.1/.cargo_vcs_info.json", vcs_info.as_bytes())
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo_dirty", "1.0.1").tarball(tarball);
+    let json = token.publish_crate(crate_to_publish).good();
+    assert!(json.warnings.details.is_empty());
+    assert!(
+        !anon
+            .show_version("foo_dirty", "1.0.1")
+            .version
+            .dirty_worktree
+    );
+}
+
+#[test]
+fn publish_records_commit_sha() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    let sha = "0123456789abcdef0123456789abcdef01234567";
+    let vcs_info = format!(r#"{{"git": {{"sha1": "{sha}"}}, "path_in_vcs": "crates/foo"}}"#);
+    let tarball = TarballBuilder::new("foo_sha", "1.0.0")
+        .add_file("foo_sha-1.0.0/.cargo_vcs_info.json", vcs_info.as_bytes())
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo_sha", "1.0.0")
+        .repository("https://github.com/foo/bar.git")
+        .tarball(tarball);
+    token.publish_crate(crate_to_publish).good();
+
+    let version = anon.show_version("foo_sha", "1.0.0").version;
+    assert_eq!(version.commit_sha.as_deref(), Some(sha));
+    assert_eq!(
+        version.source_url.unwrap(),
+        format!("https://github.com/foo/bar/tree/{sha}/crates/foo")
+    );
+
+    // The commit is recorded even if the repository is not supported
+    let tarball = TarballBuilder::new("foo_sha", "1.0.1")
+        .add_file("foo_sha-1.0.1/.cargo_vcs_info.json", vcs_info.as_bytes())
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo_sha", "1.0.1")
+        .repository("https://example.com/foo/bar")
+        .tarball(tarball);
+    token.publish_crate(crate_to_publish).good();
+
+    let version = anon.show_version("foo_sha", "1.0.1").version;
+    assert_eq!(version.commit_sha.as_deref(), Some(sha));
+    assert_eq!(version.source_url, None);
+
+    // Crates without `.cargo_vcs_info.json` file have neither
+    let crate_to_publish = PublishBuilder::new("foo_sha", "1.0.2");
+    token.publish_crate(crate_to_publish).good();
+
+    let version = anon.show_version("foo_sha", "1.0.2").version;
+    assert_eq!(version.commit_sha, None);
+    assert_eq!(version.source_url, None);
+}
+
+#[test]
+fn publish_policy_required_fields() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.publish_policy = PublishPolicy::parse(
+                r#"
+version = 1
+
+[[rules]]
+name = "crypto-repository"
+categories = ["cryptography"]
+require = ["repository", "homepage"]
+"#,
+            )
+            .unwrap();
+        })
+        .with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_policy", "1.0.0").category("cryptography");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the crate does not comply with the publish policy of this registry: the `repository` field must be set (rule `crypto-repository`); the `homepage` field must be set (rule `crypto-repository`)" }] })
+    );
+    assert!(app.stored_files().is_empty());
+
+    // Crates in other categories are not affected by the rule
+    let crate_to_publish = PublishBuilder::new("foo_policy", "1.0.0").category("cat1");
+    token.publish_crate(crate_to_publish).good();
+}
+
+#[test]
+fn publish_policy_denied_dependency_categories() {
+    let (app, _, user, token) = TestApp::full()
+        .with_config(|config| {
+            config.publish_policy = PublishPolicy::parse(
+                r#"
+version = 1
+
+[[rules]]
+name = "no-cryptography"
+crates = ["foo_policy"]
+deny-dependency-categories = ["cryptography"]
+"#,
+            )
+            .unwrap();
+        })
+        .with_token();
+
+    app.db(|conn| {
+        new_category("Cryptography", "cryptography", "Crypto crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category(
+            "Cryptocurrencies",
+            "cryptography::cryptocurrencies",
+            "Coins",
+        )
+        .create_or_update(conn)
+        .unwrap();
+
+        CrateBuilder::new("bar_coin", user.as_model().id)
+            .category("cryptography::cryptocurrencies")
+            .expect_build(conn);
+        CrateBuilder::new("baz_plain", user.as_model().id).expect_build(conn);
+    });
+
+    let crate_to_publish = PublishBuilder::new("foo_policy", "1.0.0")
+        .dependency(DependencyBuilder::new("bar_coin"))
+        .dependency(DependencyBuilder::new("baz_plain"));
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the crate does not comply with the publish policy of this registry: the dependency `bar_coin` is in the `cryptography::cryptocurrencies` category, which is not allowed (rule `no-cryptography`)" }] })
+    );
+
+    let crate_to_publish =
+        PublishBuilder::new("foo_policy", "1.0.0").dependency(DependencyBuilder::new("baz_plain"));
+    token.publish_crate(crate_to_publish).good();
 }
 
 #[test]
@@ -889,19 +1486,9 @@ fn license_and_description_required() {
 fn new_krate_tarball_with_hard_links() {
     let (app, _, _, token) = TestApp::full().with_token();
 
-    let tarball = {
-        let mut builder = TarballBuilder::new("foo", "1.1.0");
-
-        let mut header = tar::Header::new_gnu();
-        assert_ok!(header.set_path("foo-1.1.0/bar"));
-        header.set_size(0);
-        header.set_entry_type(tar::EntryType::hard_link());
-        assert_ok!(header.set_link_name("foo-1.1.0/another"));
-        header.set_cksum();
-        assert_ok!(builder.as_mut().append(&header, &[][..]));
-
-        builder.build()
-    };
+    let tarball = TarballBuilder::new("foo", "1.1.0")
+        .add_hardlink("foo-1.1.0/bar", "foo-1.1.0/another")
+        .build();
 
     let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
 
@@ -916,11 +1503,132 @@ fn new_krate_tarball_with_hard_links() {
 }
 
 #[test]
-fn tarball_between_default_axum_limit_and_max_upload_size() {
-    let max_upload_size = 5 * 1024 * 1024;
-    let (app, _, _, token) = TestApp::full()
-        .with_config(|config| {
-            config.max_upload_size = max_upload_size;
+fn new_krate_tarball_with_symlinks() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.1.0")
+        .add_symlink("foo-1.1.0/bar", "/etc/passwd")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "unexpected symlink or hard link found: foo-1.1.0/bar" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn new_krate_rejected_tarball_keeps_diagnostics() {
+    let (app, anon, user, token) = TestApp::full()
+        .with_config(|config| {
+            config.publish_diagnostics_retention = Some(Duration::from_secs(24 * 60 * 60));
+        })
+        .with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.1.0")
+        .add_raw_manifest(b"[package]\nname = \"foo\"\n")
+        .add_symlink("foo-1.1.0/bar", "/etc/passwd")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = response.into_json();
+    let detail = json["errors"][0]["detail"].as_str().unwrap();
+    assert!(detail.starts_with("unexpected symlink or hard link found: foo-1.1.0/bar\n\n"));
+    assert!(detail.contains("kept for 24 hours"));
+
+    let reference = detail.split('`').nth(1).unwrap();
+    let url = format!("/api/v1/publish_diagnostics/{reference}");
+
+    anon.get::<()>(&url).assert_forbidden();
+    user.get::<()>(&url).assert_forbidden();
+
+    app.db(|conn| {
+        update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = user.get::<()>(&url).into_json();
+    assert_eq!(json["user"], "foo");
+    let diagnostics = &json["diagnostics"];
+    assert_eq!(diagnostics["crate_name"], "foo");
+    assert_eq!(diagnostics["version"], "1.1.0");
+    assert_eq!(
+        diagnostics["error"],
+        "unexpected symlink or hard link found: foo-1.1.0/bar"
+    );
+
+    let summary = &diagnostics["context"]["tarball"];
+    assert_eq!(summary["compression"], "gzip");
+    assert_eq!(summary["manifest"], "[package]\nname = \"foo\"\n");
+    assert_eq!(
+        summary["entries"][1],
+        json!({ "path": "foo-1.1.0/bar", "kind": "symlink", "size": 0 })
+    );
+
+    user.get::<()>("/api/v1/publish_diagnostics/unknown")
+        .assert_not_found();
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn new_krate_rejected_tarball_without_diagnostics() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.1.0")
+        .add_symlink("foo-1.1.0/bar", "/etc/passwd")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
+    token.publish_crate(crate_to_publish);
+
+    let count: i64 = app.db(|conn| publish_diagnostics::table.count().get_result(conn).unwrap());
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn new_krate_tarball_with_malformed_header() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    // The size of the header doesn't match the content and its checksum is
+    // never set
+    let mut header = tar::Header::new_gnu();
+    assert_ok!(header.set_path("foo-1.1.0/src/lib.rs"));
+    header.set_size(1000);
+
+    let tarball = TarballBuilder::new("foo", "1.1.0")
+        .add_raw_manifest(b"[package]")
+        .add_entry_with_header(&header, b"pub fn foo() {}")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "uploaded tarball is malformed or too large when decompressed" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_between_default_axum_limit_and_max_upload_size() {
+    let max_upload_size = 5 * 1024 * 1024;
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.max_upload_size = max_upload_size;
             config.max_unpack_size = max_upload_size;
         })
         .with_token();
@@ -938,7 +1646,7 @@ fn tarball_between_default_axum_limit_and_max_upload_size() {
         assert_ok!(builder.as_mut().append(&header, data));
 
         // We explicitly disable compression to be able to influence the final tarball size
-        builder.build_with_compression(Compression::none())
+        builder.build_gzip_with_level(Compression::none())
     };
 
     let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
@@ -975,7 +1683,7 @@ fn tarball_bigger_than_max_upload_size() {
         assert_ok!(builder.as_mut().append(&header, data));
 
         // We explicitly disable compression to be able to influence the final tarball size
-        builder.build_with_compression(Compression::none())
+        builder.build_gzip_with_level(Compression::none())
     };
 
     let crate_to_publish = PublishBuilder::new("foo", "1.1.0").tarball(tarball);
@@ -990,6 +1698,393 @@ fn tarball_bigger_than_max_upload_size() {
     assert!(app.stored_files().is_empty());
 }
 
+#[test]
+fn tarball_with_too_many_entries() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_unpack_entries = 2)
+        .with_token();
+
+    let files = [
+        ("foo-1.0.0/a", b"a" as &[_]),
+        ("foo-1.0.0/b", b"b" as &[_]),
+        ("foo-1.0.0/c", b"c" as &[_]),
+    ];
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").files(&files);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "uploaded tarball contains more than 2 entries" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_too_large_file() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_unpack_size = 10_000)
+        .with_token();
+
+    // The file size limit is raised to the upload size limit of 3000 bytes
+    let files = [("foo-1.0.0/big", &[b'a'; 5000] as &[_])];
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").files(&files);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the file `foo-1.0.0/big` is larger than the maximum file size of 3000 bytes" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_duplicate_paths() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let files = [
+        ("foo-1.0.0/src/lib.rs", b"pub fn foo() {}" as &[_]),
+        ("foo-1.0.0/src/lib.rs", b"pub fn bar() {}" as &[_]),
+    ];
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").files(&files);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "duplicate path found: foo-1.0.0/src/lib.rs" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_case_insensitive_duplicate_paths() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.case_insensitive_tarball_paths = true)
+        .with_token();
+
+    let files = [
+        ("foo-1.0.0/README.md", b"foo" as &[_]),
+        ("foo-1.0.0/readme.md", b"bar" as &[_]),
+    ];
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").files(&files);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "duplicate path found: foo-1.0.0/readme.md" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_invalid_readme_encoding() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let files = [
+        (
+            "foo-1.0.0/Cargo.toml",
+            b"[package]\nname = \"foo\"\nreadme = \"README.md\"\n" as &[_],
+        ),
+        ("foo-1.0.0/README.md", b"# f\xf6o" as &[_]),
+    ];
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").files(&files);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the file `foo-1.0.0/README.md` has an invalid encoding: invalid UTF-8 at byte offset 3" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_denied_files() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.deny_tarball_executables = true;
+            config.denied_tarball_paths = vec!["*.exe".into()];
+        })
+        .with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .add_file("foo-1.0.0/bin/tool", b"\x7fELF\x02\x01\x01")
+        .build();
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the file `foo-1.0.0/bin/tool` was rejected: ELF binaries are not allowed" }] })
+    );
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .add_file("foo-1.0.0/setup.exe", b"")
+        .build();
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the file `foo-1.0.0/setup.exe` was rejected: the path matches the denied pattern `*.exe`" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_invalid_lockfile() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .add_file("foo-1.0.0/Cargo.lock", b"[[package]]\nname = \"bar\"\n")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    // A malformed lockfile doesn't prevent the publish
+    let json = token.publish_crate(crate_to_publish).good();
+    let [warning] = &json.warnings.details[..] else {
+        panic!("unexpected warnings: {:?}", json.warnings.details);
+    };
+    assert_eq!(warning.kind, PublishWarningKind::InvalidLockfile);
+    assert!(warning.message.starts_with(
+        "the `Cargo.lock` file could not be parsed, so its pinned dependencies were not checked: "
+    ));
+
+    assert_eq!(app.stored_files().len(), 2);
+}
+
+#[test]
+fn tarball_with_invalid_license_expression() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\nlicense = \"MIT OR Apache 2.0\"")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the `license` field `MIT OR Apache 2.0` is not a valid SPDX license expression: unknown term `Apache`" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_invalid_features() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\n[features]\ndefault = [\"std\"]\nstd = [\"log/std\"]")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid feature in `Cargo.toml`: feature `std` includes `log/std`, but `log` is not a dependency" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_inherited_rust_version() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\nrust-version = { workspace = true }")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "`package.rust-version` uses workspace inheritance, which is not supported in crate files; publish with cargo 1.64 or newer, which replaces inherited values with the values of the workspace" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn tarball_with_too_many_features() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.manifest_limits.max_features = 1)
+        .with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\n[features]\ndefault = [\"std\"]\nstd = []")
+        .build();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the manifest declares 2 features, but at most 1 are allowed" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn concurrent_publish_is_rejected() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    // Hold the publish lock of the crate on another connection, like a
+    // publish of the crate that is still in progress would.
+    let database_url =
+        dotenvy::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set to run tests");
+    let mut conn = PgConnection::establish(&database_url).unwrap();
+    let lock = "pg_advisory_lock(1, hashtext(canon_crate_name('foo_concurrent')))";
+    diesel::sql_query(format!("SELECT {lock}"))
+        .execute(&mut conn)
+        .unwrap();
+
+    let response = token.publish_crate(PublishBuilder::new("foo_concurrent", "1.0.0"));
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "another version of `foo_concurrent` is being published right now, please try again once it has been published" }] })
+    );
+
+    diesel::sql_query("SELECT pg_advisory_unlock_all()")
+        .execute(&mut conn)
+        .unwrap();
+
+    token
+        .publish_crate(PublishBuilder::new("foo_concurrent", "1.0.0"))
+        .good();
+}
+
+#[test]
+fn metadata_with_too_many_features_and_dependencies() {
+    let (app, _, user, token) = TestApp::full()
+        .with_config(|config| {
+            config.manifest_limits.max_features = 1;
+            config.manifest_limits.max_dependencies = 1;
+        })
+        .with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("dep-a", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("dep-b", user.as_model().id).expect_build(conn);
+    });
+
+    // The limits apply to the metadata, even if the manifest in the crate
+    // file stays within them
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0")
+        .feature("default", &["std"])
+        .feature("std", &[]);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the manifest declares 2 features, but at most 1 are allowed" }] })
+    );
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0")
+        .dependency(DependencyBuilder::new("dep-a"))
+        .dependency(DependencyBuilder::new("dep-b"));
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the manifest declares 2 dependencies, but at most 1 are allowed" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn zstd_tarball_rejected_by_default() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .build_zstd();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "zstd compressed crate files are not accepted by this registry" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
+#[test]
+fn zstd_tarball_accepted_if_configured() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.allowed_compressions = vec![
+                crates_io_tarball::Compression::Gzip,
+                crates_io_tarball::Compression::Zstd,
+            ];
+        })
+        .with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .build_zstd();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(json.krate.name, "foo");
+    assert_eq!(json.krate.max_version, "1.0.0");
+
+    assert_eq!(app.stored_files().len(), 2);
+}
+
+#[test]
+fn tarball_with_unknown_compression() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]")
+        .build_unzipped();
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "uploaded tarball uses an unknown compression format" }] })
+    );
+
+    assert!(app.stored_files().is_empty());
+}
+
 #[test]
 fn publish_new_crate_rate_limited() {
     let (app, anon, _, token) = TestApp::full()
@@ -1185,3 +2280,166 @@ fn boolean_readme() {
     let json = response.into_json();
     assert_some_eq!(json["version"]["rust_version"].as_str(), "1.69");
 }
+
+#[test]
+fn new_krate_with_duplicate_content_is_flagged() {
+    use crates_io::models::ModerationFlag;
+
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = |name: &str, prefix: &str| {
+        let mut builder = TarballBuilder::new(name, "1.0.0");
+        for i in 0..8 {
+            let path = format!("{name}-1.0.0/src/module_{i}.rs");
+            let content = format!("pub fn {prefix}_{i}() -> usize {{\n    {i}\n}}\n");
+            builder = builder.add_file(&path, content.as_bytes());
+        }
+        builder.build()
+    };
+
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball("foo", "original"));
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("bar", "1.0.0").tarball(tarball("bar", "original"));
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("baz", "1.0.0").tarball(tarball("baz", "different"));
+    token.publish_crate(crate_to_publish).good();
+
+    let flags = app.db(|conn| ModerationFlag::unresolved(conn).unwrap());
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].reason, "duplicate_content");
+    assert_eq!(flags[0].details["similar_crate"], "foo");
+    assert_eq!(flags[0].details["similarity"], 1.0);
+}
+
+#[test]
+fn new_krate_with_binaries_is_scanned() {
+    use crates_io::models::VersionScanReport;
+
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_file("foo-1.0.0/src/lib.rs", b"pub fn foo() {}")
+        .add_file("foo-1.0.0/bin/tool", b"\x7fELF\x02\x01\x01")
+        .build();
+    let crate_to_publish = PublishBuilder::new("foo", "1.0.0").tarball(tarball);
+    token.publish_crate(crate_to_publish).good();
+
+    // Crate files without findings don't get a report
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0"))
+        .good();
+
+    let since = chrono::NaiveDateTime::MIN;
+    let reports = app.db(|conn| VersionScanReport::since(since, None, conn).unwrap());
+    assert_eq!(reports.len(), 1);
+
+    let (report, crate_name, version) = &reports[0];
+    assert_eq!(crate_name, "foo");
+    assert_eq!(version, "1.0.0");
+    assert_eq!(
+        report.findings,
+        json!([{ "path": "bin/tool", "kind": "binary", "format": "ELF" }])
+    );
+}
+
+#[test]
+fn unusual_version_numbers_are_reported() {
+    use crates_io::models::ModerationFlag;
+
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "0.3.1"))
+        .good();
+
+    let json = token
+        .publish_crate(PublishBuilder::new("foo", "0.3.0"))
+        .good();
+    let kinds = json
+        .warnings
+        .details
+        .iter()
+        .map(|warning| warning.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, vec![PublishWarningKind::LowerVersion]);
+    assert!(app
+        .db(|conn| ModerationFlag::unresolved(conn).unwrap())
+        .is_empty());
+
+    let json = token
+        .publish_crate(PublishBuilder::new("foo", "9.0.0"))
+        .good();
+    assert_eq!(json.warnings.details.len(), 1);
+    assert_eq!(
+        json.warnings.details[0].kind,
+        PublishWarningKind::VersionJump
+    );
+    assert_eq!(
+        json.warnings.details[0].message,
+        "the version `9.0.0` is an unusually large jump from the highest published version \
+         `0.3.1`, make sure that the version number is not a typo"
+    );
+
+    let flags = app.db(|conn| ModerationFlag::unresolved(conn).unwrap());
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].reason, "version_jump");
+    assert_eq!(flags[0].details["highest_version"], "0.3.1");
+}
+
+#[test]
+fn deleted_names_have_a_cooldown() {
+    use chrono::Utc;
+    use crates_io::models::Tombstone;
+
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.deleted_name_cooldown = Duration::from_secs(30 * 24 * 60 * 60);
+        })
+        .with_token();
+
+    app.db(|conn| {
+        Tombstone::record("foo", None, conn).unwrap();
+        Tombstone::record("bar", Some("1.0.0"), conn).unwrap();
+    });
+
+    let json = token
+        .publish_crate(PublishBuilder::new("FOO", "2.0.0"))
+        .into_json();
+    let detail = json["errors"][0]["detail"].as_str().unwrap();
+    assert!(detail.starts_with("the crate `foo` was deleted at "));
+
+    let json = token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0"))
+        .into_json();
+    let detail = json["errors"][0]["detail"].as_str().unwrap();
+    assert!(detail.starts_with("version `1.0.0` of crate `bar` was deleted at "));
+
+    // Only the deleted version of `bar` is blocked, not the whole crate.
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.1"))
+        .good();
+
+    let json = token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0"))
+        .into_json();
+    assert!(json["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("can only be published again after"));
+
+    app.db(|conn| {
+        update(tombstones::table)
+            .set(tombstones::deleted_at.eq(Utc::now().naive_utc() - chrono::Duration::days(31)))
+            .execute(conn)
+            .unwrap();
+    });
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "2.0.0"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0"))
+        .good();
+}
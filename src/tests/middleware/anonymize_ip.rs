@@ -0,0 +1,64 @@
+use crate::util::{RequestHelper, TestApp};
+use crates_io::config::IpAnonymization;
+use crates_io::schema::audit_events;
+use diesel::prelude::*;
+use http::{Method, StatusCode};
+
+static NEW_TOKEN: &[u8] = br#"{ "api_token": { "name": "bar" } }"#;
+
+#[test]
+fn audit_events_store_the_truncated_ip() {
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| config.ip_anonymization = IpAnonymization::Truncate)
+        .with_user();
+
+    let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ip_address: Option<String> = app.db(|conn| {
+        audit_events::table
+            .filter(audit_events::kind.eq("token.create"))
+            .select(audit_events::ip_address)
+            .first(conn)
+            .unwrap()
+    });
+    assert_some_eq!(ip_address, "127.0.0.0");
+}
+
+#[test]
+fn audit_events_store_the_hashed_ip() {
+    let salt = "secret".to_string();
+    let anonymization = IpAnonymization::Hash { salt };
+    let expected = anonymization.anonymize("127.0.0.1");
+
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| config.ip_anonymization = anonymization)
+        .with_user();
+
+    let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ip_address: Option<String> = app.db(|conn| {
+        audit_events::table
+            .filter(audit_events::kind.eq("token.create"))
+            .select(audit_events::ip_address)
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(ip_address, expected);
+    assert_ne!(ip_address.as_deref(), Some("127.0.0.1"));
+}
+
+#[test]
+fn blocked_traffic_uses_the_raw_ip() {
+    let (_, anon) = TestApp::init()
+        .with_config(|config| {
+            config.ip_anonymization = IpAnonymization::Truncate;
+            config.blocked_traffic = vec![("X-Real-Ip".into(), vec!["127.0.0.1".into()])];
+        })
+        .empty();
+
+    let req = anon.request_builder(Method::GET, "/api/v1/summary");
+    let resp = anon.run::<()>(req);
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
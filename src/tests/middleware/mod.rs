@@ -1 +1,2 @@
+mod anonymize_ip;
 mod head;
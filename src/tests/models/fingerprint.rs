@@ -0,0 +1,39 @@
+use crate::builders::CrateBuilder;
+use crate::TestApp;
+use crates_io::models::{Crate, VersionFingerprint};
+use crates_io::schema::versions;
+use diesel::prelude::*;
+
+fn record(krate: &Crate, hashes: &[i64], conn: &mut PgConnection) {
+    let version_id: i32 = versions::table
+        .filter(versions::crate_id.eq(krate.id))
+        .select(versions::id)
+        .first(conn)
+        .unwrap();
+
+    VersionFingerprint::record(version_id, krate.id, hashes, conn).unwrap();
+}
+
+#[test]
+fn overlapping_orders_by_shared_hashes() {
+    let (app, _, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let similar = CrateBuilder::new("similar", user.id).expect_build(conn);
+        let distant = CrateBuilder::new("distant", user.id).expect_build(conn);
+        let new = CrateBuilder::new("new", user.id).expect_build(conn);
+
+        record(&similar, &[1, 2, 3, 4], conn);
+        // Recorded later, so it would come first if ordered by version
+        record(&distant, &[1, 10, 11, 12], conn);
+        record(&new, &[1, 2, 3, 5], conn);
+
+        let candidates = VersionFingerprint::overlapping(new.id, &[1, 2, 3, 5], 1, conn).unwrap();
+        let names = candidates
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["similar"]);
+    });
+}
@@ -1 +1,2 @@
+mod fingerprint;
 mod krate;
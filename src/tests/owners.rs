@@ -2,11 +2,13 @@ use crate::{
     add_team_to_crate,
     builders::{CrateBuilder, PublishBuilder},
     new_team,
-    util::{MockAnonymousUser, MockCookieUser, MockTokenUser, RequestHelper, Response},
+    util::{
+        MockAnonymousUser, MockCookieUser, MockRequestExt, MockTokenUser, RequestHelper, Response,
+    },
     TestApp,
 };
 use crates_io::{
-    models::Crate,
+    models::{Crate, CrateOwnerInvitation},
     views::{
         EncodableCrateOwnerInvitation, EncodableCrateOwnerInvitationV1, EncodableOwner,
         EncodablePublicUser, InvitationResponse,
@@ -17,7 +19,7 @@ use crates_io::{
 use chrono::{Duration, Utc};
 use crates_io::models::token::{CrateScope, EndpointScope};
 use diesel::prelude::*;
-use http::StatusCode;
+use http::{Method, StatusCode};
 
 #[derive(Deserialize)]
 struct TeamResponse {
@@ -264,6 +266,78 @@ fn modify_multiple_owners() {
     assert_eq!(app.db(|conn| krate.owners(conn).unwrap()).len(), 3);
 }
 
+/// Verify that adding and removing owners in a batch is applied atomically.
+#[test]
+fn batch_modify_owners() {
+    let (app, _, user, token) = TestApp::init().with_token();
+    let username = &user.as_model().gh_login;
+
+    let krate =
+        app.db(|conn| CrateBuilder::new("owners_batch", user.as_model().id).expect_build(conn));
+
+    create_and_add_owner(&app, &token, "user2", &krate);
+    let user3 = app.db_new_user("user3");
+
+    let sent_emails = || app.as_inner().emails.mails_in_memory().unwrap().len();
+    let emails_before = sent_emails();
+
+    let batch = |body: serde_json::Value| {
+        let mut request =
+            token.request_builder(Method::PATCH, "/api/v1/crates/owners_batch/owners");
+        request.with_body(body.to_string().as_bytes());
+        let response = token.run::<()>(request);
+        assert_eq!(response.status(), StatusCode::OK);
+        response.into_json()
+    };
+
+    // A single failing entry causes none of the changes to be applied.
+    let json = batch(json!({ "add": ["user3", username], "remove": ["user2"] }));
+    assert_eq!(
+        json,
+        json!({
+            "ok": false,
+            "results": [
+                {
+                    "owner": "user3",
+                    "action": "add",
+                    "ok": true,
+                    "msg": "user user3 has been invited to be an owner of crate owners_batch",
+                },
+                {
+                    "owner": username,
+                    "action": "add",
+                    "ok": false,
+                    "msg": format!("`{username}` is already an owner"),
+                },
+                {
+                    "owner": "user2",
+                    "action": "remove",
+                    "ok": true,
+                    "msg": "`user2` has been removed as an owner of crate owners_batch",
+                },
+            ],
+        })
+    );
+    assert_eq!(app.db(|conn| krate.owners(conn).unwrap()).len(), 2);
+    // The invitation was rolled back, so it must not have been sent either.
+    assert_eq!(sent_emails(), emails_before);
+
+    // Removing all individual owners is not allowed.
+    let json = batch(json!({ "remove": [username, "user2"] }));
+    assert_eq!(
+        json,
+        json!({ "errors": [{ "detail": "cannot remove all individual owners of a crate. Team member don't have permission to modify owners, so at least one individual owner is required." }] })
+    );
+    assert_eq!(app.db(|conn| krate.owners(conn).unwrap()).len(), 2);
+
+    // Without failures all changes are applied.
+    let json = batch(json!({ "add": ["user3"], "remove": ["user2"] }));
+    assert_eq!(json["ok"], true);
+    assert_eq!(app.db(|conn| krate.owners(conn).unwrap()).len(), 1);
+    assert_eq!(user3.list_invitations().crate_owner_invitations.len(), 1);
+    assert_eq!(sent_emails(), emails_before + 1);
+}
+
 #[test]
 fn owner_change_via_cookie() {
     let (app, _, cookie) = TestApp::full().with_user();
@@ -749,7 +823,10 @@ fn expire_invitation(app: &TestApp, crate_id: i32) {
         let created_at = (Utc::now() - Duration::days(expiration)).naive_utc();
 
         diesel::update(crate_owner_invitations::table)
-            .set(crate_owner_invitations::created_at.eq(created_at))
+            .set((
+                crate_owner_invitations::created_at.eq(created_at),
+                crate_owner_invitations::token_generated_at.eq(created_at),
+            ))
             .filter(crate_owner_invitations::crate_id.eq(crate_id))
             .execute(conn)
             .expect("failed to override the creation time");
@@ -846,6 +923,126 @@ fn test_accept_expired_invitation_by_mail() {
     assert_eq!(json.users.len(), 1);
 }
 
+#[test]
+fn expired_invitations_notify_invitee_and_inviter() {
+    use crates_io::background_jobs::Job;
+
+    let (app, _, owner, owner_token) = TestApp::full().with_token();
+    let owner = owner.as_model();
+    app.db_new_user("demo_user");
+    let krate = app.db(|conn| CrateBuilder::new("demo_crate", owner.id).expect_build(conn));
+
+    owner_token.add_user_owner("demo_crate", "demo_user");
+    expire_invitation(&app, krate.id);
+
+    let expiration_days = app.as_inner().config.ownership_invitations_expiration_days;
+    let enqueue_job = || {
+        app.db(|conn| {
+            Job::expire_ownership_invitations(expiration_days)
+                .enqueue(conn)
+                .unwrap();
+        })
+    };
+
+    enqueue_job();
+    app.run_pending_background_jobs();
+
+    let mails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(mails.len(), 3);
+    assert_eq!(mails[1].subject, "Crate ownership invitation expired");
+    assert!(mails[1].body.contains("The invitation from foo"));
+    assert_eq!(mails[2].subject, "Crate ownership invitation expired");
+    assert!(mails[2].body.contains("Your invitation for demo_user"));
+
+    // Running the job again does not send the notifications a second time
+    enqueue_job();
+    app.run_pending_background_jobs();
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 3);
+}
+
+#[test]
+fn resend_expired_invitation() {
+    let (app, anon, owner, owner_token) = TestApp::init().with_token();
+    let owner = owner.as_model();
+    let invited_user = app.db_new_user("demo_user");
+    let krate = app.db(|conn| CrateBuilder::new("demo_crate", owner.id).expect_build(conn));
+
+    owner_token.add_user_owner("demo_crate", "demo_user");
+    let old_token = extract_token_from_invite_email(&app.as_inner().emails);
+
+    let url = "/api/v1/crates/demo_crate/owner_invitations/demo_user/resend";
+
+    // Pending invitations can't be re-sent
+    let response = owner_token.put::<()>(url, &[]);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the invitation has not expired yet" }] })
+    );
+
+    expire_invitation(&app, krate.id);
+
+    // Only owners can re-send invitations
+    let response = invited_user.put::<()>(url, &[]);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to modify owners" }] })
+    );
+
+    let response = owner_token.put::<()>(url, &[]);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({
+            "msg": "user demo_user has been invited again to be an owner of crate demo_crate",
+            "ok": true,
+        })
+    );
+
+    let mails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(mails.len(), 2);
+    assert_eq!(mails[1].subject, "Crate ownership invitation");
+    assert!(!mails[1].body.contains(&old_token));
+
+    // The renewed invitation and its token count as new
+    let invitation = app.db(|conn| {
+        let user_id = invited_user.as_model().id;
+        CrateOwnerInvitation::find_by_id(user_id, krate.id, conn).unwrap()
+    });
+    assert!(!invitation.is_expired(&app.as_inner().config));
+    let token_age = Utc::now().naive_utc() - invitation.token_created_at.unwrap();
+    assert!(token_age < Duration::minutes(1));
+
+    // The old token can no longer be used, but the invitation is pending again
+    let response = anon.try_accept_ownership_invitation_by_token::<()>(&old_token);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    assert_eq!(
+        invited_user
+            .list_invitations()
+            .crate_owner_invitations
+            .len(),
+        1
+    );
+    invited_user.accept_ownership_invitation(&krate.name, krate.id);
+
+    let json = anon.show_crate_owners("demo_crate");
+    assert_eq!(json.users.len(), 2);
+}
+
+#[test]
+fn resend_missing_invitation() {
+    let (app, _, owner, owner_token) = TestApp::init().with_token();
+    let owner = owner.as_model();
+    app.db_new_user("demo_user");
+    app.db(|conn| CrateBuilder::new("demo_crate", owner.id).expect_build(conn));
+
+    let url = "/api/v1/crates/demo_crate/owner_invitations/demo_user/resend";
+    let response = owner_token.put::<()>(url, &[]);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[test]
 fn inactive_users_dont_get_invitations() {
     use crates_io::models::NewUser;
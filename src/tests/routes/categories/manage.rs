@@ -0,0 +1,206 @@
+use crate::builders::CrateBuilder;
+use crate::new_category;
+use crate::util::{MockCookieUser, MockRequestExt, RequestHelper, Response, TestApp};
+use crates_io::models::{Category, Crate};
+use crates_io::schema::users;
+use diesel::prelude::*;
+use http::{Method, StatusCode};
+use serde_json::Value;
+
+fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn send<T: RequestHelper>(user: &T, method: Method, url: &str, body: Value) -> Response<Value> {
+    let mut request = user.request_builder(method, url);
+    request.with_body(body.to_string().as_bytes());
+    user.run(request)
+}
+
+#[test]
+fn only_admins_can_manage_categories() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let body = json!({ "category": { "slug": "foo", "name": "Foo" } });
+
+    let response = send(&anon, Method::POST, "/api/v1/categories", body.clone());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(&user, Method::POST, "/api/v1/categories", body.clone());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    make_admin(&app, &user);
+    let response = send(&user, Method::POST, "/api/v1/categories", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json()["category"]["slug"], "foo");
+}
+
+#[test]
+fn create_subcategory() {
+    let (app, _, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    let body = json!({ "category": { "slug": "foo::bar", "name": "Bar" } });
+    let response = send(&user, Method::POST, "/api/v1/categories", body.clone());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "parent category `foo` does not exist" }] })
+    );
+
+    app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap()
+    });
+
+    let response = send(&user, Method::POST, "/api/v1/categories", body.clone());
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json()["category"]["slug"], "foo::bar");
+
+    let category: Category = app.db(|conn| Category::by_slug("foo::bar").first(conn).unwrap());
+    assert_eq!(category.category, "Foo::Bar");
+
+    let response = send(&user, Method::POST, "/api/v1/categories", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({ "category": { "slug": "Foo Bar", "name": "Foo Bar" } });
+    let response = send(&user, Method::POST, "/api/v1/categories", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn rename_moves_subcategories_and_keeps_redirects() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    let krate = app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Foo::Bar", "foo::bar", "")
+            .create_or_update(conn)
+            .unwrap();
+
+        CrateBuilder::new("foo_crate", user.as_model().id)
+            .category("foo::bar")
+            .expect_build(conn)
+    });
+
+    let body = json!({ "category": { "slug": "baz", "name": "Baz", "description": "Baz crates" } });
+    let response = send(&user, Method::PATCH, "/api/v1/categories/foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let json = response.into_json();
+    assert_eq!(json["category"]["slug"], "baz");
+    assert_eq!(json["category"]["description"], "Baz crates");
+
+    let json = anon.show_category("baz::bar");
+    assert_eq!(json.category.category, "Bar");
+    assert_eq!(json.category.crates_cnt, 1);
+
+    anon.get::<()>("/api/v1/categories/foo::bar")
+        .assert_redirect_ends_with("/api/v1/categories/baz::bar");
+
+    // Old slugs still work when publishing
+    let invalid = app.db(|conn| Category::update_crate(conn, &krate, &["foo"]).unwrap());
+    assert!(invalid.is_empty());
+    assert_eq!(anon.show_category("baz").category.crates_cnt, 1);
+
+    // A category can't be moved into its own subcategory
+    let body = json!({ "category": { "slug": "baz::bar::foo" } });
+    let response = send(&user, Method::PATCH, "/api/v1/categories/baz", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn deprecated_categories_cannot_be_added() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    let krate = app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        CrateBuilder::new("foo_crate", user.as_model().id).expect_build(conn)
+    });
+
+    let body = json!({ "category": { "deprecated": true } });
+    let response = send(&user, Method::PATCH, "/api/v1/categories/foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let invalid = app.db(|conn| Category::update_crate(conn, &krate, &["foo"]).unwrap());
+    assert_eq!(invalid, vec!["foo"]);
+    assert_eq!(anon.show_category("foo").category.crates_cnt, 0);
+
+    let body = json!({ "category": { "deprecated": false } });
+    let response = send(&user, Method::PATCH, "/api/v1/categories/foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let invalid = app.db(|conn| Category::update_crate(conn, &krate, &["foo"]).unwrap());
+    assert!(invalid.is_empty());
+}
+
+#[test]
+fn merge_moves_crates() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Bar", "bar", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Baz", "baz", "")
+            .create_or_update(conn)
+            .unwrap();
+
+        CrateBuilder::new("one", user.as_model().id)
+            .category("foo")
+            .expect_build(conn);
+        CrateBuilder::new("two", user.as_model().id)
+            .category("foo")
+            .category("bar")
+            .expect_build(conn);
+    });
+
+    // Rename `baz` to `qux`, so that there is a redirect pointing to `baz`
+    let body = json!({ "category": { "slug": "qux", "name": "Qux" } });
+    let response = send(&user, Method::PATCH, "/api/v1/categories/baz", body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = json!({ "into": "foo" });
+    let response = send(&user, Method::PUT, "/api/v1/categories/foo/merge", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({ "into": "bar" });
+    let response = send(&user, Method::PUT, "/api/v1/categories/foo/merge", body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_eq!(anon.show_category("bar").category.crates_cnt, 2);
+    anon.get::<()>("/api/v1/categories/foo")
+        .assert_redirect_ends_with("/api/v1/categories/bar");
+
+    let body = json!({ "into": "bar" });
+    let response = send(&user, Method::PUT, "/api/v1/categories/qux/merge", body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    anon.get::<()>("/api/v1/categories/baz")
+        .assert_redirect_ends_with("/api/v1/categories/bar");
+
+    let categories: Vec<String> = app.db(|conn| {
+        let krate: Crate = Crate::by_name("two").first(conn).unwrap();
+        crates_io::models::CrateCategory::belonging_to(&krate)
+            .inner_join(crates_io::schema::categories::table)
+            .select(crates_io::schema::categories::slug)
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(categories, vec!["bar"]);
+}
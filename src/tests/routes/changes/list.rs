@@ -0,0 +1,161 @@
+use crate::builders::PublishBuilder;
+use crate::routes::crates::versions::yank_unyank::YankRequestHelper;
+use crate::util::{RequestHelper, TestApp, TestDatabase};
+use crates_io::models::{insert_version_owner_action, VersionAction};
+use crates_io::schema::{crates, versions};
+use diesel::prelude::*;
+use http::StatusCode;
+use secrecy::ExposeSecret;
+use serde_json::Value;
+
+fn changes(json: &Value) -> Vec<(String, String, String, bool)> {
+    json["changes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| {
+            (
+                change["crate"].as_str().unwrap().to_string(),
+                change["num"].as_str().unwrap().to_string(),
+                change["action"].as_str().unwrap().to_string(),
+                change["yanked"].as_bool().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn list_changes() {
+    // The sequence numbers are assigned on commit, so the test can't run
+    // inside of a single transaction.
+    let (_, anon, _, token) = TestApp::full()
+        .with_database(TestDatabase::SlowRealPool { replica: false })
+        .with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0"))
+        .good();
+    token.yank("foo", "1.0.0").good();
+    token.unyank("foo", "1.0.0").good();
+    token.yank("bar", "1.0.0").good();
+
+    let json = anon.get::<Value>("/api/v1/changes").good();
+    assert_eq!(
+        changes(&json),
+        vec![
+            ("foo".into(), "1.0.0".into(), "publish".into(), false),
+            ("bar".into(), "1.0.0".into(), "publish".into(), false),
+            ("foo".into(), "1.0.0".into(), "yank".into(), true),
+            ("foo".into(), "1.0.0".into(), "unyank".into(), false),
+            ("bar".into(), "1.0.0".into(), "yank".into(), true),
+        ]
+    );
+
+    let json = anon.get::<Value>("/api/v1/changes/yanks").good();
+    assert_eq!(
+        changes(&json),
+        vec![
+            ("foo".into(), "1.0.0".into(), "yank".into(), true),
+            ("foo".into(), "1.0.0".into(), "unyank".into(), false),
+            ("bar".into(), "1.0.0".into(), "yank".into(), true),
+        ]
+    );
+
+    // Mirrors continue polling with the sequence number of the last change
+    let json = anon.get::<Value>("/api/v1/changes/yanks?per_page=2").good();
+    assert_eq!(json["changes"].as_array().unwrap().len(), 2);
+    let next_since = json["meta"]["next_since"].as_i64().unwrap();
+
+    let url = format!("/api/v1/changes/yanks?since={next_since}");
+    let json = anon.get::<Value>(&url).good();
+    assert_eq!(
+        changes(&json),
+        vec![("bar".into(), "1.0.0".into(), "yank".into(), true)]
+    );
+
+    let next_since = json["meta"]["next_since"].as_i64().unwrap();
+    let url = format!("/api/v1/changes/yanks?since={next_since}");
+    let json = anon.get::<Value>(&url).good();
+    assert_eq!(changes(&json), vec![]);
+    assert_eq!(json["meta"]["next_since"].as_i64().unwrap(), next_since);
+
+    let json = anon
+        .get::<Value>("/api/v1/changes?since_time=2999-01-01T00:00:00Z")
+        .good();
+    assert_eq!(changes(&json), vec![]);
+}
+
+#[test]
+fn changes_are_listed_in_commit_order() {
+    let (app, anon, user, token) = TestApp::full()
+        .with_database(TestDatabase::SlowRealPool { replica: false })
+        .with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0"))
+        .good();
+
+    let foo_version_id: i32 = app.db(|conn| {
+        versions::table
+            .inner_join(crates::table)
+            .filter(crates::name.eq("foo"))
+            .select(versions::id)
+            .first(conn)
+            .unwrap()
+    });
+
+    // A yank of `foo` that is committed only after the yank of `bar`, even
+    // though it was inserted first
+    let database_url = app.as_inner().config.db.primary.url.expose_secret();
+    let mut conn = PgConnection::establish(database_url).unwrap();
+    diesel::sql_query("BEGIN").execute(&mut conn).unwrap();
+    let user_id = user.as_model().id;
+    insert_version_owner_action(
+        &mut conn,
+        foo_version_id,
+        user_id,
+        None,
+        VersionAction::Yank,
+    )
+    .unwrap();
+
+    token.yank("bar", "1.0.0").good();
+
+    let json = anon.get::<Value>("/api/v1/changes/yanks").good();
+    assert_eq!(
+        changes(&json),
+        vec![("bar".into(), "1.0.0".into(), "yank".into(), true)]
+    );
+
+    diesel::sql_query("COMMIT").execute(&mut conn).unwrap();
+
+    // The yank of `foo` is not skipped by clients that already saw `bar`
+    let next_since = json["meta"]["next_since"].as_i64().unwrap();
+    let url = format!("/api/v1/changes/yanks?since={next_since}");
+    let json = anon.get::<Value>(&url).good();
+    assert_eq!(
+        changes(&json),
+        vec![("foo".into(), "1.0.0".into(), "yank".into(), true)]
+    );
+}
+
+#[test]
+fn invalid_parameters() {
+    let (_, anon) = TestApp::init().empty();
+
+    for query in [
+        "since=abc",
+        "since_time=yesterday",
+        "per_page=0",
+        "per_page=1001",
+    ] {
+        let response = anon.get::<()>(&format!("/api/v1/changes?{query}"));
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use http::{header, StatusCode};
+use serde_json::Value;
+
+#[test]
+fn badges() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_badge", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .license(Some("MIT"))
+                    .rust_version("1.60"),
+            )
+            .version(VersionBuilder::new("1.1.0").license(Some("MIT OR Apache-2.0")))
+            .version(VersionBuilder::new("1.2.0").yanked(true))
+            .version(VersionBuilder::new("2.0.0-beta.1"))
+            .downloads(12_345)
+            .expect_build(conn);
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_badge/badge.json");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CACHE_CONTROL],
+        "public, max-age=3600"
+    );
+    assert_eq!(
+        response.into_json(),
+        json!({ "schemaVersion": 1, "label": "crates.io", "message": "v1.1.0", "color": "blue" })
+    );
+
+    let badge = |kind: &str| {
+        let json: Value = anon
+            .get_with_query(
+                "/api/v1/crates/foo_badge/badge.json",
+                &format!("type={kind}"),
+            )
+            .good();
+        (json["label"].clone(), json["message"].clone())
+    };
+
+    assert_eq!(badge("version"), (json!("crates.io"), json!("v1.1.0")));
+    assert_eq!(badge("downloads"), (json!("downloads"), json!("12k")));
+    assert_eq!(
+        badge("license"),
+        (json!("license"), json!("MIT OR Apache-2.0"))
+    );
+    assert_eq!(badge("msrv"), (json!("rust-version"), json!("unknown")));
+
+    let response = anon.get_with_query::<()>("/api/v1/crates/foo_badge/badge.json", "type=foo");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    anon.get::<()>("/api/v1/crates/unknown/badge.json")
+        .assert_not_found();
+}
@@ -1,7 +1,9 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
 use chrono::{Duration, Utc};
+use crates_io::schema::download_redirect_samples;
 use crates_io::views::EncodableVersionDownload;
+use diesel::prelude::*;
 use http::StatusCode;
 
 #[derive(Deserialize)]
@@ -81,3 +83,149 @@ fn download() {
     assert_dl_count(&anon, "FOO_DOWNLOAD/1.0.0", Some(&query), 2);
     assert_dl_count(&anon, "FOO_DOWNLOAD", Some(&query), 2);
 }
+
+#[test]
+fn download_redirects_are_sampled() {
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| config.download_sample_rate = 1.0)
+        .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_download", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_download/1.0.0/download");
+    assert_eq!(response.status(), StatusCode::FOUND);
+    let response = anon.get::<()>("/api/v1/crates/foo_download/download?req=^1");
+    assert_eq!(response.status(), StatusCode::FOUND);
+
+    let inserted = app
+        .as_inner()
+        .download_sampler
+        .persist(app.as_inner())
+        .unwrap();
+    assert_eq!(inserted, 2);
+
+    let samples: Vec<(String, String, String, String)> = app.db(|conn| {
+        download_redirect_samples::table
+            .select((
+                download_redirect_samples::crate_name,
+                download_redirect_samples::version,
+                download_redirect_samples::user_agent_class,
+                download_redirect_samples::cdn,
+            ))
+            .order(download_redirect_samples::id)
+            .load(conn)
+            .unwrap()
+    });
+
+    let expected = (
+        "foo_download".into(),
+        "1.0.0".into(),
+        "other".into(),
+        "local".into(),
+    );
+    assert_eq!(samples, vec![expected.clone(), expected]);
+}
+
+#[test]
+fn download_lines() {
+    use crates_io::schema::{version_downloads, versions};
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_lines", user.id)
+            .version(VersionBuilder::new("0.1.0"))
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.0.1").yanked(true))
+            .version(VersionBuilder::new("1.1.0"))
+            .expect_build(conn);
+
+        let today = Utc::now().date_naive();
+        let old = today - Duration::days(100);
+        let downloads = [
+            ("0.1.0", today, 10),
+            ("1.0.0", today, 20),
+            ("1.0.1", today, 30),
+            ("1.1.0", today, 40),
+            ("1.1.0", old, 1000),
+        ];
+        for (num, date, downloads) in downloads {
+            let version_id: i32 = versions::table
+                .filter(versions::num.eq(num))
+                .select(versions::id)
+                .first(conn)
+                .unwrap();
+
+            diesel::insert_into(version_downloads::table)
+                .values((
+                    version_downloads::version_id.eq(version_id),
+                    version_downloads::date.eq(date),
+                    version_downloads::downloads.eq(downloads),
+                ))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    let json: serde_json::Value = anon.get("/api/v1/crates/foo_lines/download_lines").good();
+    assert_eq!(
+        json,
+        json!({
+            "lines": [
+                { "line": "1.1", "downloads": 40, "yanked_downloads": 0, "share": 0.4 },
+                { "line": "1.0", "downloads": 50, "yanked_downloads": 30, "share": 0.5 },
+                { "line": "0.1", "downloads": 10, "yanked_downloads": 0, "share": 0.1 },
+            ],
+            "meta": { "days": 90, "total_downloads": 100, "yanked_downloads": 30 },
+        })
+    );
+
+    anon.get::<()>("/api/v1/crates/unknown/download_lines")
+        .assert_not_found();
+}
+
+#[test]
+fn total_downloads() {
+    use crates_io::schema::crates;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_total", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .downloads(42)
+            .expect_build(conn);
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_total/downloads/total");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[http::header::CACHE_CONTROL],
+        "public, max-age=300"
+    );
+    assert_eq!(response.into_json(), json!({ "downloads": 42 }));
+
+    // The totals are cached until they expire
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("foo_total")))
+            .set(crates::downloads.eq(100))
+            .execute(conn)
+            .unwrap();
+    });
+    let json: serde_json::Value = anon.get("/api/v1/crates/foo_total/downloads/total").good();
+    assert_eq!(json, json!({ "downloads": 42 }));
+
+    // Non-canonical names are cached separately
+    let json: serde_json::Value = anon.get("/api/v1/crates/FOO-TOTAL/downloads/total").good();
+    assert_eq!(json, json!({ "downloads": 100 }));
+
+    anon.get::<()>("/api/v1/crates/unknown/downloads/total")
+        .assert_not_found();
+}
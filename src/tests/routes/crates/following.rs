@@ -1,5 +1,8 @@
 use crate::builders::CrateBuilder;
 use crate::util::{RequestHelper, TestApp};
+use crate::OkBool;
+use http::StatusCode;
+use serde_json::Value;
 
 #[test]
 fn diesel_not_found_results_in_404() {
@@ -25,3 +28,89 @@ fn disallow_api_token_auth_for_get_crate_following_status() {
         .get(&format!("/api/v1/crates/{a_crate}/following"))
         .assert_forbidden();
 }
+
+#[test]
+fn follow_with_notification_settings() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_following", user.as_model().id).expect_build(conn);
+    });
+
+    let body = json!({ "mode": "all_activity", "channel": "email" });
+    user.put::<OkBool>(
+        "/api/v1/crates/foo_following/follow",
+        body.to_string().as_bytes(),
+    )
+    .good();
+
+    let json = user
+        .get::<Value>("/api/v1/crates/foo_following/following")
+        .good();
+    assert_eq!(
+        json,
+        json!({
+            "following": true,
+            "settings": { "mode": "all_activity", "channel": "email" },
+        })
+    );
+
+    // Following again without settings keeps the current settings
+    user.put::<OkBool>("/api/v1/crates/foo_following/follow", b"")
+        .good();
+
+    let json = user
+        .get::<Value>("/api/v1/crates/foo_following/following")
+        .good();
+    assert_eq!(json["settings"]["channel"], "email");
+
+    let body = json!({ "mode": "everything" });
+    let response = user.put::<()>(
+        "/api/v1/crates/foo_following/follow",
+        body.to_string().as_bytes(),
+    );
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn bulk_update_follows() {
+    let (app, _, user) = TestApp::init().with_user();
+    let user_id = user.as_model().id;
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user_id).expect_build(conn);
+        CrateBuilder::new("bar", user_id).expect_build(conn);
+        CrateBuilder::new("baz", user_id).expect_build(conn);
+    });
+
+    user.put::<OkBool>("/api/v1/crates/baz/follow", b"").good();
+
+    let body = json!({
+        "follow": [
+            { "crate": "foo", "mode": "all_activity", "channel": "email" },
+            { "crate": "bar" },
+        ],
+        "unfollow": ["baz"],
+    });
+    user.put::<OkBool>("/api/v1/me/follows", body.to_string().as_bytes())
+        .good();
+
+    let json = user.get::<Value>("/api/v1/me/follows").good();
+    assert_eq!(
+        json,
+        json!({
+            "follows": [
+                { "crate": "bar", "mode": "releases", "channel": "feed" },
+                { "crate": "foo", "mode": "all_activity", "channel": "email" },
+            ]
+        })
+    );
+
+    // Unknown crates abort the whole update
+    let body = json!({ "follow": [{ "crate": "baz" }, { "crate": "missing" }] });
+    let response = user.put::<()>("/api/v1/me/follows", body.to_string().as_bytes());
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let json = user.get::<Value>("/api/v1/me/follows").good();
+    assert_eq!(json["follows"].as_array().unwrap().len(), 2);
+}
@@ -408,6 +408,52 @@ fn ignore_exact_match_on_queries_with_sort() {
     assert_eq!(json.crates[2].name, "foo_sort");
 }
 
+#[test]
+fn sorting_by_activity_dates() {
+    use chrono::{Duration, Utc};
+    use crates_io::schema::versions;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let days_ago = |days| Utc::now().naive_utc() - Duration::days(days);
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("idle_sort", user.id)
+            .version(VersionBuilder::new("1.0.0").created_at(days_ago(10)))
+            .expect_build(conn);
+
+        CrateBuilder::new("active_sort", user.id)
+            .version(VersionBuilder::new("1.0.0").created_at(days_ago(30)))
+            .version(VersionBuilder::new("2.0.0").created_at(days_ago(2)))
+            .expect_build(conn);
+
+        // The first version of a crate can be older than the crate itself,
+        // e.g. for crates that were imported from another registry
+        update(crates::table)
+            .set(crates::created_at.eq(now))
+            .execute(conn)
+            .unwrap();
+
+        update(versions::table.filter(versions::crate_id.eq(krate.id)))
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = anon.search("sort=new");
+    assert_eq!(json.crates[0].name, "idle_sort");
+    assert_eq!(json.crates[1].name, "active_sort");
+    let first_published_at = json.crates[1].first_published_at.unwrap();
+    assert_eq!(first_published_at.date(), days_ago(30).date());
+    let last_activity_at = json.crates[1].last_activity_at.unwrap();
+    assert_eq!(last_activity_at.date(), days_ago(2).date());
+
+    // Yanking a version counts as activity
+    let json = anon.search("sort=recently-updated");
+    assert_eq!(json.crates[0].name, "idle_sort");
+    assert_eq!(json.crates[1].name, "active_sort");
+}
+
 #[test]
 fn multiple_ids() {
     let (app, anon, user) = TestApp::init().with_user();
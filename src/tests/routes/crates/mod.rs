@@ -1,3 +1,4 @@
+mod badge;
 pub mod downloads;
 mod following;
 mod list;
@@ -5,4 +6,6 @@ mod new;
 pub mod owners;
 mod read;
 mod reverse_dependencies;
+mod support_windows;
+mod update;
 pub mod versions;
@@ -116,6 +116,54 @@ fn show_minimal() {
     assert!(json.keywords.is_none());
 }
 
+#[test]
+fn show_repository_status() {
+    use crates_io::models::{RepositoryCheck, RepositoryStatus};
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_archived", user.id)
+            .repository("https://github.com/foo/foo_archived")
+            .expect_build(conn);
+
+        RepositoryCheck::record(
+            krate.id,
+            "https://github.com/foo/foo_archived",
+            RepositoryStatus::Archived,
+            Some("https://github.com/bar/foo_archived"),
+            conn,
+        )
+        .unwrap();
+
+        // The check of the previous repository URL is outdated
+        let krate = CrateBuilder::new("foo_moved", user.id)
+            .repository("https://github.com/foo/foo_moved")
+            .expect_build(conn);
+
+        RepositoryCheck::record(
+            krate.id,
+            "https://github.com/foo/foo_old",
+            RepositoryStatus::NotFound,
+            None,
+            conn,
+        )
+        .unwrap();
+    });
+
+    let json = anon.show_crate("foo_archived");
+    let status = json.krate.repository_status.unwrap();
+    assert_eq!(status.status, "archived");
+    assert_some_eq!(status.redirected_to, "https://github.com/bar/foo_archived");
+
+    let json = anon.show_crate("foo_moved");
+    assert_none!(json.krate.repository_status);
+
+    let json = anon.show_crate_minimal("foo_archived");
+    assert_some!(json.krate.repository_status);
+}
+
 #[test]
 fn version_size() {
     let (_, _, user) = TestApp::full().with_user();
@@ -0,0 +1,177 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{MockRequestExt, RequestHelper, Response, TestApp};
+use http::{Method, StatusCode};
+use serde_json::Value;
+
+fn put_support_window<T: RequestHelper>(
+    user: &T,
+    crate_name: &str,
+    major_version: &str,
+    body: Value,
+) -> Response<Value> {
+    let url = format!("/api/v1/crates/{crate_name}/support_windows/{major_version}");
+    let mut request = user.request_builder(Method::PUT, &url);
+    request.with_body(body.to_string().as_bytes());
+    user.run(request)
+}
+
+#[test]
+fn declare_support_windows() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .version("0.4.0")
+            .version("1.0.0")
+            .expect_build(conn);
+    });
+
+    let body = json!({ "support_window": { "status": "security-fixes-only" } });
+    let response = put_support_window(&token, "foo", "0.4", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = response.into_json();
+    assert_eq!(json["support_window"]["major_version"], "0.4");
+    assert_eq!(json["support_window"]["status"], "security-fixes-only");
+    assert_eq!(json["support_window"]["end_of_life_date"], Value::Null);
+
+    let body = json!({ "support_window": { "status": "supported" } });
+    put_support_window(&user, "foo", "01", body).good();
+
+    let json = anon
+        .get::<()>("/api/v1/crates/foo/support_windows")
+        .into_json();
+    let windows = json["support_windows"].as_array().unwrap();
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[0]["major_version"], "0.4");
+    assert_eq!(windows[1]["major_version"], "1");
+    assert_eq!(windows[1]["status"], "supported");
+
+    // Declaring the support window of a release line again replaces it
+    let body =
+        json!({ "support_window": { "status": "end-of-life", "end_of_life_date": "2023-01-31" } });
+    put_support_window(&token, "foo", "0.4", body).good();
+
+    let json = anon
+        .get::<()>("/api/v1/crates/foo/support_windows")
+        .into_json();
+    assert_eq!(json["support_windows"][0]["status"], "end-of-life");
+    assert_eq!(json["support_windows"][0]["end_of_life_date"], "2023-01-31");
+
+    let response = token.delete::<()>("/api/v1/crates/foo/support_windows/0.4");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let json = anon
+        .get::<()>("/api/v1/crates/foo/support_windows")
+        .into_json();
+    assert_eq!(json["support_windows"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn reject_invalid_support_windows() {
+    let (app, _, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    let body = json!({ "support_window": { "status": "supported" } });
+    let response = put_support_window(&token, "foo", "1.2", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid major version: 1.2" }] })
+    );
+
+    let body = json!({ "support_window": { "status": "unmaintained" } });
+    let response = put_support_window(&token, "foo", "1", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid status: unmaintained" }] })
+    );
+
+    let body = json!({ "support_window": { "status": "end-of-life" } });
+    let response = put_support_window(&token, "foo", "1", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = token.delete::<()>("/api/v1/crates/foo/support_windows/1.2");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid major version: 1.2" }] })
+    );
+}
+
+#[test]
+fn support_windows_require_ownership() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let another_user = app.db_new_user("bar");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    let body = json!({ "support_window": { "status": "supported" } });
+    let response = put_support_window(&another_user, "foo", "1", body.clone());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = put_support_window(&anon, "foo", "1", body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = another_user.delete::<()>("/api/v1/crates/foo/support_windows/1");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn reverse_dependencies_include_support_warnings() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id)
+            .version("1.0.0")
+            .version("2.0.0")
+            .expect_build(conn);
+        CrateBuilder::new("c2", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .expect_build(conn);
+    });
+
+    let url = "/api/v1/crates/c1/reverse_dependencies";
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json["support_warnings"], json!([]));
+
+    // The dependency of `c2` resolves to `c1` version 2.0.0, so the end of
+    // life of the `1` release line does not affect it.
+    let body =
+        json!({ "support_window": { "status": "end-of-life", "end_of_life_date": "2020-01-01" } });
+    put_support_window(&token, "c1", "1", body).good();
+
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json["support_warnings"], json!([]));
+
+    let body = json!({ "support_window": { "status": "security-fixes-only" } });
+    put_support_window(&token, "c1", "2", body).good();
+
+    let json = anon.get::<()>(url).into_json();
+    let warnings = json["support_warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["dependency_id"], json["dependencies"][0]["id"]);
+    assert_eq!(warnings[0]["major_version"], "2");
+    assert_eq!(warnings[0]["status"], "security-fixes-only");
+    assert_eq!(
+        warnings[0]["message"],
+        "the `2` release line of `c1` only receives security fixes"
+    );
+
+    let body = json!({
+        "support_window": { "status": "security-fixes-only", "end_of_life_date": "2020-01-01" }
+    });
+    put_support_window(&token, "c1", "2", body).good();
+
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(
+        json["support_warnings"][0]["message"],
+        "the `2` release line of `c1` stopped receiving security fixes on 2020-01-01"
+    );
+}
@@ -0,0 +1,164 @@
+use crate::builders::CrateBuilder;
+use crate::new_category;
+use crate::util::{MockRequestExt, RequestHelper, Response, TestApp};
+use http::{Method, StatusCode};
+use serde_json::Value;
+
+fn patch_crate<T: RequestHelper>(user: &T, crate_name: &str, body: Value) -> Response<Value> {
+    let url = format!("/api/v1/crates/{crate_name}");
+    let mut request = user.request_builder(Method::PATCH, &url);
+    request.with_body(body.to_string().as_bytes());
+    user.run(request)
+}
+
+#[test]
+fn update_keywords_and_categories() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Category 2", "cat2", "Category 2 crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        CrateBuilder::new("foo", user.as_model().id)
+            .keyword("tpyo")
+            .category("cat1")
+            .expect_build(conn);
+    });
+
+    let body = json!({ "crate": { "keywords": ["typo", "fixed"] } });
+    let response = patch_crate(&token, "foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({
+            "crate": {
+                "keywords": ["fixed", "typo"],
+                "categories": ["cat1"],
+                "homepage": null,
+                "documentation": null,
+                "repository": null,
+            }
+        })
+    );
+
+    let body = json!({ "crate": { "categories": ["cat2"] } });
+    let response = patch_crate(&user, "foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({
+            "crate": {
+                "keywords": ["fixed", "typo"],
+                "categories": ["cat2"],
+                "homepage": null,
+                "documentation": null,
+                "repository": null,
+            }
+        })
+    );
+
+    let json = anon.show_crate("foo");
+    let mut keywords = json.krate.keywords.unwrap();
+    keywords.sort();
+    assert_eq!(keywords, ["fixed", "typo"]);
+    assert_eq!(json.krate.categories.unwrap(), ["cat2"]);
+}
+
+#[test]
+fn update_links() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .keyword("kw")
+            .expect_build(conn);
+    });
+
+    let body = json!({
+        "crate": {
+            "homepage": "https://example.com/?utm_source=crates.io",
+            "repository": "http://www.github.com/foo/bar.git",
+        }
+    });
+    let response = patch_crate(&token, "foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({
+            "crate": {
+                "keywords": ["kw"],
+                "categories": [],
+                "homepage": "https://example.com/",
+                "documentation": null,
+                "repository": "https://github.com/foo/bar",
+            }
+        })
+    );
+
+    let json = anon.show_crate("foo");
+    assert_eq!(json.krate.homepage.unwrap(), "https://example.com/");
+    assert_eq!(json.krate.repository.unwrap(), "https://github.com/foo/bar");
+
+    let body = json!({ "crate": { "documentation": "docs.rs/foo" } });
+    let response = patch_crate(&token, "foo", body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "URL for field `documentation` must begin with http:// or https:// (url: docs.rs/foo)" }] })
+    );
+}
+
+#[test]
+fn update_rejects_invalid_metadata() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .keyword("kw")
+            .expect_build(conn);
+    });
+
+    let body = json!({ "crate": { "keywords": ["a", "b", "c", "d", "e", "f"] } });
+    let response = patch_crate(&token, "foo", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({ "crate": { "keywords": ["áccênts"] } });
+    let response = patch_crate(&token, "foo", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({ "crate": { "keywords": ["new"], "categories": ["missing"] } });
+    let response = patch_crate(&token, "foo", body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "unknown categories: missing" }] })
+    );
+
+    // None of the changes have been applied
+    let json = anon.show_crate("foo");
+    assert_eq!(json.krate.keywords.unwrap(), ["kw"]);
+}
+
+#[test]
+fn update_requires_ownership() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let another_user = app.db_new_user("bar");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    let body = json!({ "crate": { "keywords": ["kw"] } });
+    let response = patch_crate(&another_user, "foo", body.clone());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = patch_crate(&anon, "foo", body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = anon.show_crate("foo");
+    assert_eq!(json.krate.keywords.unwrap(), Vec::<String>::new());
+}
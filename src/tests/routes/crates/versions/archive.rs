@@ -0,0 +1,157 @@
+use crate::builders::PublishBuilder;
+use crate::util::{MockRequestExt, RequestHelper, TestApp};
+use crates_io::background_jobs::Job;
+use crates_io::models::Crate;
+use crates_io::schema::{crates, version_archives, versions};
+use diesel::prelude::*;
+use http::{header, Method, StatusCode};
+
+fn crate_files(app: &TestApp) -> Vec<String> {
+    app.stored_files()
+        .into_iter()
+        .filter(|path| path.contains("crates/"))
+        .collect()
+}
+
+#[test]
+fn archive_and_restore_crate_file() {
+    let (app, anon, user) = TestApp::full().with_user();
+
+    user.publish_crate(PublishBuilder::new("foo_dead", "1.0.0"))
+        .good();
+    user.publish_crate(PublishBuilder::new("foo_alive", "1.0.0"))
+        .good();
+
+    // Yank all versions of `foo_dead` and pretend that they were published
+    // a long time ago.
+    app.db(|conn| {
+        let three_years_ago = chrono::Utc::now().naive_utc() - chrono::Duration::days(3 * 365);
+        diesel::update(versions::table)
+            .set(versions::created_at.eq(three_years_ago))
+            .execute(conn)
+            .unwrap();
+
+        let foo_dead = Crate::by_name("foo_dead")
+            .select(crates::id)
+            .first::<i32>(conn)
+            .unwrap();
+        diesel::update(versions::table.filter(versions::crate_id.eq(foo_dead)))
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+
+        Job::archive_versions(730, 100).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    // The original is kept for a while, since the download endpoint might
+    // still redirect to it
+    let expected_files = vec![
+        "archive/crates/foo_dead/foo_dead-1.0.0.crate",
+        "crates/foo_alive/foo_alive-1.0.0.crate",
+        "crates/foo_dead/foo_dead-1.0.0.crate",
+    ];
+    assert_eq!(crate_files(&app), expected_files);
+
+    app.db(|conn| {
+        let two_hours_ago = chrono::Utc::now().naive_utc() - chrono::Duration::hours(2);
+        diesel::update(version_archives::table)
+            .set(version_archives::archived_at.eq(two_hours_ago))
+            .execute(conn)
+            .unwrap();
+
+        Job::archive_versions(730, 100).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    let expected_files = vec![
+        "archive/crates/foo_dead/foo_dead-1.0.0.crate",
+        "crates/foo_alive/foo_alive-1.0.0.crate",
+    ];
+    assert_eq!(crate_files(&app), expected_files);
+
+    let url = "/api/v1/crates/foo_dead/1.0.0/archive";
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json["archive"]["status"], "archived");
+    assert!(json["archive"]["restore_requested_at"].is_null());
+
+    let json = anon
+        .get::<()>("/api/v1/crates/foo_alive/1.0.0/archive")
+        .into_json();
+    assert_eq!(json, json!({ "archive": null }));
+
+    // Downloads of archived versions are served directly and request a
+    // restore of the crate file.
+    let response = anon.get::<()>("/api/v1/crates/foo_dead/1.0.0/download");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json["archive"]["status"], "restoring");
+    assert!(json["archive"]["restore_requested_at"].is_string());
+
+    let mut request = anon.request_builder(Method::GET, "/api/v1/crates/foo_dead/1.0.0/download");
+    request.header(header::ACCEPT, "application/json");
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    app.run_pending_background_jobs();
+
+    let expected_files = vec![
+        "crates/foo_alive/foo_alive-1.0.0.crate",
+        "crates/foo_dead/foo_dead-1.0.0.crate",
+    ];
+    assert_eq!(crate_files(&app), expected_files);
+
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json, json!({ "archive": null }));
+
+    anon.get::<()>("/api/v1/crates/foo_dead/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo_dead/foo_dead-1.0.0.crate");
+}
+
+#[test]
+fn archived_crate_files_are_served_by_unconditional_redirects() {
+    let (app, anon, user) = TestApp::full()
+        .with_config(|config| {
+            config.force_unconditional_redirects = true;
+        })
+        .with_user();
+
+    user.publish_crate(PublishBuilder::new("foo_dead", "1.0.0"))
+        .good();
+
+    app.db(|conn| {
+        let three_years_ago = chrono::Utc::now().naive_utc() - chrono::Duration::days(3 * 365);
+        diesel::update(versions::table)
+            .set((
+                versions::created_at.eq(three_years_ago),
+                versions::yanked.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+
+        Job::archive_versions(730, 100).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    // The original is still there, so the download is redirected to it
+    anon.get::<()>("/api/v1/crates/foo_dead/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo_dead/foo_dead-1.0.0.crate");
+
+    app.db(|conn| {
+        let two_hours_ago = chrono::Utc::now().naive_utc() - chrono::Duration::hours(2);
+        diesel::update(version_archives::table)
+            .set(version_archives::archived_at.eq(two_hours_ago))
+            .execute(conn)
+            .unwrap();
+
+        Job::archive_versions(730, 100).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    // Without the database, the storage tells that the file was archived
+    let response = anon.get::<()>("/api/v1/crates/foo_dead/1.0.0/download");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+}
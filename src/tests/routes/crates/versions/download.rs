@@ -1,5 +1,6 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
-use crate::util::{RequestHelper, TestApp};
+use crate::util::{MockRequestExt, RequestHelper, TestApp};
+use http::{header, Method, StatusCode};
 
 #[test]
 fn download_nonexistent_version_of_existing_crate_404s() {
@@ -98,6 +99,66 @@ fn download_caches_version_id() {
     downloads::assert_dl_count(&anon, "other/1.0.0", None, 2);
 }
 
+#[test]
+fn download_unavailable_version() {
+    use crates_io::models::UnavailableVersion;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let version = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_lost", user.id)
+            .version(VersionBuilder::new("1.1.0"))
+            .expect_build(conn);
+
+        let version = VersionBuilder::new("1.0.0").expect_build(krate.id, user.id, conn);
+        UnavailableVersion::mark(version.id, "missing", conn).unwrap();
+        version
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_lost/1.0.0/download");
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "The crate file of foo_lost v1.0.0 is missing or corrupted in the storage of this registry and could not be restored." }] })
+    );
+
+    // Other versions are not affected
+    anon.get::<()>("/api/v1/crates/foo_lost/1.1.0/download")
+        .assert_redirect_ends_with("/crates/foo_lost/foo_lost-1.1.0.crate");
+
+    app.db(|conn| UnavailableVersion::remove(version.id, conn).unwrap());
+    anon.get::<()>("/api/v1/crates/foo_lost/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo_lost/foo_lost-1.0.0.crate");
+}
+
+#[test]
+fn download_with_signed_urls() {
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| config.storage.require_signed_urls = true)
+        .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_signed", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_signed/1.0.0/download");
+    assert_eq!(response.status(), StatusCode::FOUND);
+
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    let (path, query) = location.split_once('?').unwrap();
+    assert_eq!(path, "/crates/foo_signed/foo_signed-1.0.0.crate");
+    assert!(query.starts_with("expires="));
+    assert!(query.contains("&signature="));
+
+    let response = anon.get::<()>("/api/v1/crates/foo_signed/download?req=^1");
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert!(location.contains("&signature="));
+}
+
 #[test]
 fn download_with_build_metadata() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -114,4 +175,72 @@ fn download_with_build_metadata() {
 
     anon.get::<()>("/api/v1/crates/foo/1.0.0+bar/readme")
         .assert_redirect_ends_with("/readmes/foo/foo-1.0.0%2Bbar.html");
+
+    anon.get::<()>("/api/v1/crates/foo/1.0.0+bar/license-text")
+        .assert_redirect_ends_with("/license-texts/foo/foo-1.0.0%2Bbar.json");
+}
+
+#[test]
+fn readme_with_compressed_variants() {
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| config.storage.compressed_variants = true)
+        .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let mut req = anon.request_builder(Method::GET, "/api/v1/crates/foo/1.0.0/readme");
+    req.header(header::ACCEPT_ENCODING, "gzip, deflate, br");
+    let response = anon.run::<()>(req);
+    response.assert_redirect_ends_with("/readmes/foo/foo-1.0.0.html.br");
+    assert_eq!(response.headers()[header::VARY], "Accept-Encoding");
+
+    let mut req = anon.request_builder(Method::GET, "/api/v1/crates/foo/1.0.0/readme");
+    req.header(header::ACCEPT_ENCODING, "gzip");
+    let response = anon.run::<()>(req);
+    response.assert_redirect_ends_with("/readmes/foo/foo-1.0.0.html.gz");
+
+    anon.get::<()>("/api/v1/crates/foo/1.0.0/readme")
+        .assert_redirect_ends_with("/readmes/foo/foo-1.0.0.html");
+}
+
+#[test]
+fn download_matching_version_requirement() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_download", user.id)
+            .version(VersionBuilder::new("1.1.0"))
+            .version(VersionBuilder::new("1.2.0"))
+            .version(VersionBuilder::new("1.3.0").yanked(true))
+            .version(VersionBuilder::new("2.0.0-beta.1"))
+            .expect_build(conn);
+    });
+
+    anon.get::<()>("/api/v1/crates/foo_download/download?req=1")
+        .assert_redirect_ends_with("/crates/foo_download/foo_download-1.2.0.crate");
+
+    anon.get::<()>("/api/v1/crates/foo-download/download?req=%3D1.1.0")
+        .assert_redirect_ends_with("/crates/foo_download/foo_download-1.1.0.crate");
+
+    // Yanked versions and pre-releases are only used if explicitly requested
+    anon.get::<()>("/api/v1/crates/foo_download/download?req=%3E%3D1.3.0")
+        .assert_not_found();
+
+    anon.get::<()>("/api/v1/crates/foo_download/download?req=2.0.0-beta")
+        .assert_redirect_ends_with("/crates/foo_download/foo_download-2.0.0-beta.1.crate");
+
+    let response = anon.get::<()>("/api/v1/crates/foo_download/download?req=foo");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = anon.get::<()>("/api/v1/crates/foo_download/download");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    anon.get::<()>("/api/v1/crates/bar_download/download?req=1")
+        .assert_not_found();
 }
@@ -1,5 +1,8 @@
+mod archive;
 mod authors;
 pub mod dependencies;
 pub mod download;
 mod read;
+mod readme;
+mod reproducibility;
 pub mod yank_unyank;
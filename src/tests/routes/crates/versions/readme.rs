@@ -0,0 +1,61 @@
+use crate::builders::PublishBuilder;
+use crate::util::{MockCookieUser, RequestHelper, TestApp};
+use crate::OkBool;
+use crates_io::background_jobs::Job;
+use crates_io::schema::{users, versions};
+use diesel::prelude::*;
+
+fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn rerender_readme(app: &TestApp, text: &str) {
+    app.db(|conn| {
+        let version_id = versions::table.select(versions::id).first(conn).unwrap();
+
+        Job::render_and_upload_readme(version_id, text.into(), "README.md".into(), None, None)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn restore_previous_readme() {
+    let (app, anon, user) = TestApp::full().with_user();
+
+    user.publish_crate(PublishBuilder::new("foo", "1.0.0").readme("good readme"))
+        .good();
+
+    let previous_url = "/api/v1/crates/foo/1.0.0/readme/previous";
+    let restore_url = "/api/v1/crates/foo/1.0.0/readme/restore";
+
+    anon.get::<()>(previous_url).assert_forbidden();
+    user.get::<()>(previous_url).assert_forbidden();
+    user.put::<()>(restore_url, b"").assert_forbidden();
+
+    make_admin(&app, &user);
+
+    // Nothing has been re-rendered yet
+    user.get::<()>(previous_url).assert_not_found();
+    user.put::<()>(restore_url, b"").assert_not_found();
+
+    rerender_readme(&app, "bad readme");
+
+    let previous = user.get::<()>(previous_url).into_text();
+    assert!(previous.contains("good readme"));
+
+    assert!(user.put::<OkBool>(restore_url, b"").good().ok);
+
+    let files = app.stored_files();
+    assert!(files.contains(&"readme-history/foo/foo-1.0.0.html".to_string()));
+
+    // The replaced readme is kept, so the restore can be undone
+    let previous = user.get::<()>(previous_url).into_text();
+    assert!(previous.contains("bad readme"));
+}
@@ -0,0 +1,29 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use crates_io::models::VersionReproducibility;
+
+#[test]
+fn reproducibility() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let version = app.db(|conn| {
+        let krate = CrateBuilder::new("foo_reproducible", user.id).expect_build(conn);
+        VersionBuilder::new("1.0.0").expect_build(krate.id, user.id, conn)
+    });
+
+    let url = "/api/v1/crates/foo_reproducible/1.0.0/reproducibility";
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json, json!({ "reproducibility": null }));
+
+    app.db(|conn| VersionReproducibility::record(version.id, false, "v1.0.0", conn).unwrap());
+
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json["reproducibility"]["reproducible"], false);
+    assert_eq!(json["reproducibility"]["git_tag"], "v1.0.0");
+
+    app.db(|conn| VersionReproducibility::record(version.id, true, "v1.0.0", conn).unwrap());
+
+    let json = anon.get::<()>(url).into_json();
+    assert_eq!(json["reproducibility"]["reproducible"], true);
+}
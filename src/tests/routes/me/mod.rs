@@ -1,4 +1,7 @@
 mod email_notifications;
 pub mod get;
+mod publishable_crates;
+mod repository_verification;
+mod subscriptions;
 pub mod tokens;
 mod updates;
@@ -0,0 +1,100 @@
+use crate::builders::CrateBuilder;
+use crate::util::{RequestHelper, TestApp};
+use crates_io::models::token::{CrateScope, EndpointScope};
+use serde_json::Value;
+
+const URL: &str = "/api/v1/me/publishable-crates";
+
+#[test]
+fn anonymous_user_is_forbidden() {
+    let (_, anon) = TestApp::init().empty();
+    anon.get::<()>(URL).assert_forbidden();
+}
+
+fn publishable_crates(user: &impl RequestHelper) -> Vec<(String, Option<String>)> {
+    let json = user.get::<Value>(URL).good();
+    json["crates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|krate| {
+            let name = krate["name"].as_str().unwrap().to_string();
+            (name, krate["team"].as_str().map(String::from))
+        })
+        .collect()
+}
+
+#[test]
+fn owned_and_team_owned_crates() {
+    let (app, _) = TestApp::init().empty();
+    let user_on_both_teams = app.db_new_user("user-all-teams");
+    let token_on_both_teams = user_on_both_teams.db_new_token("arbitrary token name");
+    let user_on_one_team = app.db_new_user("user-one-team");
+
+    app.db(|conn| {
+        let owner_id = user_on_both_teams.as_model().id;
+        CrateBuilder::new("foo_team_owned", owner_id).expect_build(conn);
+        CrateBuilder::new("foo_core_owned", owner_id).expect_build(conn);
+        CrateBuilder::new("foo_other", owner_id).expect_build(conn);
+        CrateBuilder::new("foo_owned", user_on_one_team.as_model().id).expect_build(conn);
+    });
+
+    token_on_both_teams
+        .add_named_owner("foo_team_owned", "github:test-org:all")
+        .good();
+    token_on_both_teams
+        .add_named_owner("foo_core_owned", "github:test-org:core")
+        .good();
+
+    // The team memberships are looked up per user in the mocked GitHub API
+    assert_eq!(
+        publishable_crates(&user_on_one_team),
+        vec![
+            ("foo_owned".into(), None),
+            ("foo_team_owned".into(), Some("github:test-org:all".into())),
+        ]
+    );
+    assert_eq!(
+        publishable_crates(&user_on_both_teams),
+        vec![
+            ("foo_core_owned".into(), None),
+            ("foo_other".into(), None),
+            ("foo_team_owned".into(), None),
+        ]
+    );
+}
+
+#[test]
+fn token_scopes_are_applied() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        let owner_id = user.as_model().id;
+        CrateBuilder::new("foo", owner_id).expect_build(conn);
+        CrateBuilder::new("foo-bar", owner_id).expect_build(conn);
+        CrateBuilder::new("bar", owner_id).expect_build(conn);
+    });
+
+    let json = user.db_new_token("legacy").get::<Value>(URL).good();
+    assert_eq!(json["crates"].as_array().unwrap().len(), 3);
+
+    let crate_scopes = vec![CrateScope::try_from("foo*").unwrap()];
+    let token = user.db_new_scoped_token(
+        "scoped",
+        Some(crate_scopes),
+        Some(vec![EndpointScope::PublishUpdate]),
+        None,
+    );
+    let json = token.get::<Value>(URL).good();
+    let names = json["crates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|krate| krate["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["foo", "foo-bar"]);
+
+    // Tokens that can't publish updates can't publish to any existing crate
+    let token = user.db_new_scoped_token("yank", None, Some(vec![EndpointScope::Yank]), None);
+    token.get::<()>(URL).assert_forbidden();
+}
@@ -0,0 +1,192 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use crates_io::schema::repository_verifications;
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+use std::time::Duration;
+
+const URL: &str = "/api/v1/me/repository_verification";
+const VERIFY_URL: &str = "/api/v1/me/repository_verification/verify";
+
+/// Replaces the random challenge with the one found in the mock repositories.
+fn set_challenge(app: &TestApp, user_id: i32, challenge: &str) {
+    app.db(|conn| {
+        diesel::update(repository_verifications::table.find(user_id))
+            .set(repository_verifications::challenge.eq(challenge))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+#[test]
+fn repository_verification_requires_login() {
+    let (_, anon) = TestApp::init().empty();
+    anon.get::<()>(URL).assert_forbidden();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-file" }"#;
+    anon.put::<()>(URL, body).assert_forbidden();
+    anon.put::<()>(VERIFY_URL, b"").assert_forbidden();
+}
+
+#[test]
+fn start_verification() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let json: Value = user.get(URL).good();
+    assert_eq!(json, json!({ "repository_verification": null }));
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-file.git" }"#;
+    let json: Value = user.put(URL, body).good();
+    let verification = &json["repository_verification"];
+    assert_eq!(verification["repository"], "user-one-team/with-file");
+    assert_eq!(verification["challenge_file"], ".crates-io-verification");
+    assert_eq!(verification["verified_at"], Value::Null);
+
+    let challenge = verification["challenge"].as_str().unwrap();
+    assert_eq!(challenge.len(), 32);
+    assert_eq!(
+        verification["challenge_tag"],
+        format!("crates-io-verification-{challenge}")
+    );
+
+    let json: Value = user.get(URL).good();
+    assert_eq!(json["repository_verification"]["challenge"], challenge);
+
+    // Starting again issues a new challenge
+    let json: Value = user.put(URL, body).good();
+    assert_ne!(json["repository_verification"]["challenge"], challenge);
+}
+
+#[test]
+fn start_verification_with_invalid_repository() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "repository": "https://gitlab.com/user-one-team/with-file" }"#;
+    let response = user.put::<()>(URL, body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid repository: https://gitlab.com/user-one-team/with-file, only GitHub repositories like `https://github.com/owner/name` are supported" }] })
+    );
+}
+
+#[test]
+fn verify_without_challenge() {
+    let (_, _, user) = TestApp::init().with_user();
+    user.put::<()>(VERIFY_URL, b"").assert_not_found();
+}
+
+#[test]
+fn verify_with_challenge_file() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-file" }"#;
+    user.put::<Value>(URL, body).good();
+    set_challenge(&app, user.as_model().id, "mock-file-challenge");
+
+    let json: Value = user.put(VERIFY_URL, b"").good();
+    assert!(json["repository_verification"]["verified_at"].is_string());
+
+    let json: Value = user.get(URL).good();
+    assert!(json["repository_verification"]["verified_at"].is_string());
+}
+
+#[test]
+fn verify_with_challenge_tag() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-tag" }"#;
+    user.put::<Value>(URL, body).good();
+    set_challenge(&app, user.as_model().id, "mock-tag-challenge");
+
+    let json: Value = user.put(VERIFY_URL, b"").good();
+    assert!(json["repository_verification"]["verified_at"].is_string());
+}
+
+#[test]
+fn verify_with_wrong_challenge() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-file" }"#;
+    user.put::<Value>(URL, body).good();
+    set_challenge(&app, user.as_model().id, "other-challenge");
+
+    let response = user.put::<()>(VERIFY_URL, b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the challenge was not found in user-one-team/with-file: add a `.crates-io-verification` file containing `other-challenge` to the default branch, or push the `crates-io-verification-other-challenge` tag" }] })
+    );
+
+    let json: Value = user.get(URL).good();
+    assert_eq!(json["repository_verification"]["verified_at"], Value::Null);
+}
+
+#[test]
+fn verify_with_unknown_repository() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/missing" }"#;
+    user.put::<Value>(URL, body).good();
+    set_challenge(&app, user.as_model().id, "mock-file-challenge");
+
+    let response = user.put::<()>(VERIFY_URL, b"");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn newcomers_are_rate_limited() {
+    let (_, _, _, token) = TestApp::full()
+        .with_publish_rate_limit(Duration::from_secs(60), 5)
+        .with_config(|config| config.rate_limiter.newcomer_burst = Some(1))
+        .with_token();
+
+    let crate_to_publish = PublishBuilder::new("newcomer1", "1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("newcomer2", "1.0.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn verified_newcomers_are_not_rate_limited() {
+    let (app, _, user, token) = TestApp::full()
+        .with_publish_rate_limit(Duration::from_secs(60), 5)
+        .with_config(|config| config.rate_limiter.newcomer_burst = Some(1))
+        .with_token();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-file" }"#;
+    user.put::<Value>(URL, body).good();
+    set_challenge(&app, user.as_model().id, "mock-file-challenge");
+    user.put::<Value>(VERIFY_URL, b"").good();
+
+    let repository = "https://github.com/user-one-team/with-file";
+    let crate_to_publish = PublishBuilder::new("newcomer1", "1.0.0").repository(repository);
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("newcomer2", "1.0.0").repository(repository);
+    token.publish_crate(crate_to_publish).good();
+}
+
+#[test]
+fn verification_only_applies_to_crates_of_the_repository() {
+    let (app, _, user, token) = TestApp::full()
+        .with_publish_rate_limit(Duration::from_secs(60), 5)
+        .with_config(|config| config.rate_limiter.newcomer_burst = Some(1))
+        .with_token();
+
+    let body = br#"{ "repository": "https://github.com/user-one-team/with-file" }"#;
+    user.put::<Value>(URL, body).good();
+    set_challenge(&app, user.as_model().id, "mock-file-challenge");
+    user.put::<Value>(VERIFY_URL, b"").good();
+
+    let repository = "https://github.com/user-one-team/with-tag";
+    let crate_to_publish = PublishBuilder::new("newcomer1", "1.0.0").repository(repository);
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("newcomer2", "1.0.0").repository(repository);
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
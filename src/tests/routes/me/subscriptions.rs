@@ -0,0 +1,140 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::new_category;
+use crate::util::{RequestHelper, TestApp};
+use crate::OkBool;
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn api_token_cannot_manage_subscriptions() {
+    let (_, _, _, token) = TestApp::init().with_token();
+    token.get("/api/v1/me/subscriptions").assert_forbidden();
+    token
+        .get("/api/v1/me/subscriptions/updates")
+        .assert_forbidden();
+}
+
+#[test]
+fn subscribe_and_unsubscribe() {
+    let (app, _, user) = TestApp::init().with_user();
+    let user_id = user.as_model().id;
+    app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        CrateBuilder::new("foo_crate", user_id)
+            .keyword("bar")
+            .expect_build(conn);
+    });
+
+    let json = user.get::<Value>("/api/v1/me/subscriptions").good();
+    assert_eq!(
+        json,
+        json!({
+            "keywords": [],
+            "categories": [],
+            "digest": { "frequency": "weekly", "last_sent_at": null },
+        })
+    );
+
+    user.put::<OkBool>("/api/v1/keywords/bar/subscribe", b"")
+        .good();
+    user.put::<OkBool>("/api/v1/keywords/bar/subscribe", b"")
+        .good();
+    user.put::<OkBool>("/api/v1/categories/foo/subscribe", b"")
+        .good();
+
+    let response = user.put::<()>("/api/v1/keywords/missing/subscribe", b"");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = user.put::<()>("/api/v1/categories/missing/subscribe", b"");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let json = user.get::<Value>("/api/v1/me/subscriptions").good();
+    assert_eq!(json["keywords"], json!(["bar"]));
+    assert_eq!(json["categories"], json!(["foo"]));
+
+    user.delete::<OkBool>("/api/v1/keywords/bar/subscribe")
+        .good();
+    user.delete::<OkBool>("/api/v1/categories/foo/subscribe")
+        .good();
+
+    let json = user.get::<Value>("/api/v1/me/subscriptions").good();
+    assert_eq!(json["keywords"], json!([]));
+    assert_eq!(json["categories"], json!([]));
+}
+
+#[test]
+fn update_digest_frequency() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let body = json!({ "digest": { "frequency": "daily" } });
+    user.put::<OkBool>("/api/v1/me/subscriptions", body.to_string().as_bytes())
+        .good();
+
+    let json = user.get::<Value>("/api/v1/me/subscriptions").good();
+    assert_eq!(json["digest"]["frequency"], "daily");
+
+    let body = json!({ "digest": { "frequency": "hourly" } });
+    let response = user.put::<()>("/api/v1/me/subscriptions", body.to_string().as_bytes());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn updates() {
+    let (app, _, user) = TestApp::init().with_user();
+    let user_id = user.as_model().id;
+    app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Foo::Bar", "foo::bar", "")
+            .create_or_update(conn)
+            .unwrap();
+
+        CrateBuilder::new("by_keyword", user_id)
+            .keyword("baz")
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+        CrateBuilder::new("by_subcategory", user_id)
+            .category("foo::bar")
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .expect_build(conn);
+        CrateBuilder::new("unrelated", user_id)
+            .keyword("other")
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let json = user.get::<Value>("/api/v1/me/subscriptions/updates").good();
+    assert_eq!(json["versions"], json!([]));
+
+    user.put::<OkBool>("/api/v1/keywords/baz/subscribe", b"")
+        .good();
+    user.put::<OkBool>("/api/v1/categories/foo/subscribe", b"")
+        .good();
+
+    let json = user.get::<Value>("/api/v1/me/subscriptions/updates").good();
+    let mut crates = json["versions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| {
+            format!(
+                "{}@{}",
+                v["crate"].as_str().unwrap(),
+                v["num"].as_str().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    crates.sort();
+    assert_eq!(
+        crates,
+        [
+            "by_keyword@1.0.0",
+            "by_subcategory@1.0.0",
+            "by_subcategory@1.1.0"
+        ]
+    );
+    assert_eq!(json["meta"]["more"], false);
+}
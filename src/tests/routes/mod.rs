@@ -13,11 +13,14 @@
 
 pub mod categories;
 pub mod category_slugs;
+pub mod changes;
 pub mod crates;
 pub mod keywords;
 pub mod me;
 pub mod metrics;
+pub mod osv;
 pub mod session;
+pub mod status;
 pub mod summary;
 pub mod users;
 pub mod versions;
@@ -0,0 +1,174 @@
+use crate::builders::PublishBuilder;
+use crate::routes::crates::versions::yank_unyank::YankRequestHelper;
+use crate::util::{RequestHelper, TestApp};
+use crate::OkBool;
+use crates_io::schema::security_yanks;
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+const ADVISORY: &str = "https://rustsec.org/advisories/RUSTSEC-2023-0001.html";
+
+#[test]
+fn security_yank_is_exported() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.1.0"))
+        .good();
+
+    // Regular yanks are not exported
+    token.yank("foo", "1.0.0").good();
+
+    let json = anon.get::<Value>("/api/v1/osv").good();
+    assert_eq!(json, json!({ "vulns": [], "meta": { "next_page": null } }));
+
+    let url = format!("/api/v1/crates/foo/1.1.0/yank?reason=security&advisory={ADVISORY}");
+    token.delete::<OkBool>(&url).good();
+    app.run_pending_background_jobs();
+
+    let json = anon.get::<Value>("/api/v1/osv").good();
+    let vulns = json["vulns"].as_array().unwrap();
+    assert_eq!(vulns.len(), 1);
+
+    let vuln = &vulns[0];
+    let id = vuln["id"].as_str().unwrap();
+    assert!(id.starts_with("CRATESIO-"));
+    assert_eq!(
+        vuln["affected"],
+        json!([{
+            "package": { "ecosystem": "crates.io", "name": "foo", "purl": "pkg:cargo/foo" },
+            "versions": ["1.1.0"],
+        }])
+    );
+    assert_eq!(
+        vuln["references"],
+        json!([{ "type": "ADVISORY", "url": ADVISORY }])
+    );
+    assert!(vuln["modified"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(vuln.get("withdrawn"), None);
+
+    let json = anon.get::<Value>(&format!("/api/v1/osv/{id}")).good();
+    assert_eq!(&json, vuln);
+
+    // Unyanking withdraws the record
+    token.unyank("foo", "1.1.0").good();
+
+    let json = anon.get::<Value>(&format!("/api/v1/osv/{id}")).good();
+    assert!(json["withdrawn"].is_string());
+
+    let json = anon
+        .get::<Value>("/api/v1/osv?since=2100-01-01T00:00:00Z")
+        .good();
+    assert_eq!(json, json!({ "vulns": [], "meta": { "next_page": null } }));
+}
+
+#[test]
+fn pagination_with_equal_modification_times() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    for version in ["1.0.0", "1.1.0", "1.2.0"] {
+        token
+            .publish_crate(PublishBuilder::new("foo", version))
+            .good();
+
+        let url = format!("/api/v1/crates/foo/{version}/yank?reason=security&advisory={ADVISORY}");
+        token.delete::<OkBool>(&url).good();
+    }
+    app.run_pending_background_jobs();
+
+    app.db(|conn| {
+        diesel::update(security_yanks::table)
+            .set(security_yanks::modified_at.eq(diesel::dsl::now))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let mut url = "/api/v1/osv?per_page=2".to_string();
+    let mut versions = Vec::new();
+    loop {
+        let json = anon.get::<Value>(&url).good();
+        let vulns = json["vulns"].as_array().unwrap();
+        if vulns.is_empty() {
+            assert_eq!(json["meta"]["next_page"], Value::Null);
+            break;
+        }
+
+        versions.extend(
+            vulns
+                .iter()
+                .map(|vuln| vuln["affected"][0]["versions"][0].clone()),
+        );
+        url = format!("/api/v1/osv{}", json["meta"]["next_page"].as_str().unwrap());
+    }
+    assert_eq!(versions, ["1.0.0", "1.1.0", "1.2.0"]);
+
+    // The link of the last page returns records that are modified later
+    token.unyank("foo", "1.1.0").good();
+
+    let json = anon.get::<Value>(&url).good();
+    let vulns = json["vulns"].as_array().unwrap();
+    assert_eq!(vulns.len(), 1);
+    assert!(vulns[0]["withdrawn"].is_string());
+}
+
+#[test]
+fn advisory_for_already_yanked_version() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+    token.yank("foo", "1.0.0").good();
+
+    let url = format!("/api/v1/crates/foo/1.0.0/yank?reason=security&advisory={ADVISORY}");
+    token.delete::<OkBool>(&url).good();
+
+    let json = anon.get::<Value>("/api/v1/osv").good();
+    assert_eq!(json["vulns"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn invalid_security_yanks() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+
+    let assert_error = |query: &str, detail: &str| {
+        let url = format!("/api/v1/crates/foo/1.0.0/yank?{query}");
+        let response = token.delete::<OkBool>(&url);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.into_json(),
+            json!({ "errors": [{ "detail": detail }] })
+        );
+    };
+
+    assert_error(
+        "reason=security",
+        "yanking for security reasons requires an `advisory` link",
+    );
+    assert_error(
+        "reason=security&advisory=ftp://example.com",
+        "invalid `advisory` link, expected an http(s) URL: ftp://example.com",
+    );
+    assert_error(
+        &format!("advisory={ADVISORY}"),
+        "the `advisory` parameter can only be used with `reason=security`",
+    );
+    assert_error("reason=broken", "unknown yank reason: broken");
+}
+
+#[test]
+fn unknown_osv_record() {
+    let (_, anon) = TestApp::init().empty();
+
+    anon.get::<()>("/api/v1/osv/CRATESIO-1").assert_not_found();
+    anon.get::<()>("/api/v1/osv/RUSTSEC-2023-0001")
+        .assert_not_found();
+}
@@ -0,0 +1,53 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn propagation_without_crates() {
+    let (_, anon) = TestApp::init().empty();
+
+    let json = anon.get::<Value>("/api/v1/status/propagation").good();
+    assert_eq!(json["database"], Value::Null);
+    assert_eq!(json["git_index"]["newest_version"], Value::Null);
+    assert_eq!(json["git_index"]["pending_syncs"], 0);
+    assert_eq!(json["crate"], Value::Null);
+
+    let response = anon.get::<()>("/api/v1/status/propagation?crate=missing");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn propagation_of_new_release() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("serde", "1.0.0").body();
+    let response = token.put::<()>("/api/v1/crates/new", &body);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let url = "/api/v1/status/propagation?crate=serde";
+    let json = anon.get::<Value>(url).good();
+    assert_eq!(json["database"]["crate"], "serde");
+    assert_eq!(json["database"]["num"], "1.0.0");
+    for index in ["git_index", "sparse_index"] {
+        assert_eq!(json[index]["newest_version"], Value::Null);
+        assert_eq!(json[index]["pending_syncs"], 1);
+        assert_eq!(json["crate"][index]["synced_at"], Value::Null);
+        assert_eq!(json["crate"][index]["up_to_date"], false);
+        assert_eq!(json["crate"][index]["sync_pending"], true);
+    }
+
+    app.run_pending_background_jobs();
+
+    let json = anon.get::<Value>(url).good();
+    assert_eq!(json["crate"]["newest_version"], "1.0.0");
+    for index in ["git_index", "sparse_index"] {
+        assert_eq!(json[index]["newest_version"]["crate"], "serde");
+        assert_eq!(json[index]["newest_version"]["num"], "1.0.0");
+        assert_eq!(json[index]["lag_seconds"], 0);
+        assert_eq!(json[index]["pending_syncs"], 0);
+        assert!(json["crate"][index]["synced_at"].is_string());
+        assert_eq!(json["crate"][index]["up_to_date"], true);
+        assert_eq!(json["crate"][index]["sync_pending"], false);
+    }
+}
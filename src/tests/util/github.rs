@@ -1,7 +1,7 @@
 use crates_io::controllers::github::secret_scanning::GitHubPublicKey;
 use crates_io::github::{
-    GitHubClient, GitHubOrgMembership, GitHubOrganization, GitHubTeam, GitHubTeamMembership,
-    GithubUser,
+    GitHubClient, GitHubOrgMembership, GitHubOrganization, GitHubRef, GitHubTeam,
+    GitHubTeamMembership, GithubUser,
 };
 use crates_io::util::errors::{not_found, AppResult};
 use oauth2::AccessToken;
@@ -52,6 +52,20 @@ pub(crate) const MOCK_GITHUB_DATA: MockData = MockData {
             is_current: true,
         },
     ],
+    repositories: &[
+        MockRepository {
+            owner: "user-one-team",
+            name: "with-file",
+            files: &[(".crates-io-verification", "mock-file-challenge\n")],
+            tags: &[],
+        },
+        MockRepository {
+            owner: "user-one-team",
+            name: "with-tag",
+            files: &[],
+            tags: &["v1.0.0", "crates-io-verification-mock-tag-challenge"],
+        },
+    ],
 };
 
 pub(crate) struct MockGitHubClient {
@@ -62,6 +76,14 @@ impl MockGitHubClient {
     pub(crate) fn new(data: &'static MockData) -> Self {
         Self { data }
     }
+
+    fn repository(&self, owner: &str, repo: &str) -> AppResult<&'static MockRepository> {
+        self.data
+            .repositories
+            .iter()
+            .find(|repository| repository.owner == owner && repository.name == repo)
+            .ok_or_else(not_found)
+    }
 }
 
 impl GitHubClient for MockGitHubClient {
@@ -76,6 +98,22 @@ impl GitHubClient for MockGitHubClient {
         })
     }
 
+    fn user_by_login(&self, login: &str, _auth: &AccessToken) -> AppResult<GithubUser> {
+        let user = self
+            .data
+            .users
+            .iter()
+            .find(|user| user.login.eq_ignore_ascii_case(login))
+            .ok_or_else(not_found)?;
+        Ok(GithubUser {
+            id: user.id,
+            login: user.login.into(),
+            name: Some(user.name.into()),
+            email: Some(user.email.into()),
+            avatar_url: Some(format!("https://avatars.example.com/{}", user.id)),
+        })
+    }
+
     fn org_by_name(&self, org_name: &str, _auth: &AccessToken) -> AppResult<GitHubOrganization> {
         let org = self
             .data
@@ -172,12 +210,47 @@ impl GitHubClient for MockGitHubClient {
     fn public_keys(&self, _username: &str, _password: &str) -> AppResult<Vec<GitHubPublicKey>> {
         Ok(self.data.public_keys.iter().map(Into::into).collect())
     }
+
+    fn repository_file(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        _auth: &AccessToken,
+    ) -> AppResult<String> {
+        let (_, content) = self
+            .repository(owner, repo)?
+            .files
+            .iter()
+            .find(|(file, _)| *file == path)
+            .ok_or_else(not_found)?;
+        Ok(content.to_string())
+    }
+
+    fn repository_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        _auth: &AccessToken,
+    ) -> AppResult<GitHubRef> {
+        let tag = self
+            .repository(owner, repo)?
+            .tags
+            .iter()
+            .find(|name| **name == tag)
+            .ok_or_else(not_found)?;
+        Ok(GitHubRef {
+            name: format!("refs/tags/{tag}"),
+        })
+    }
 }
 
 pub(crate) struct MockData {
     orgs: &'static [MockOrg],
     users: &'static [MockUser],
     public_keys: &'static [MockPublicKey],
+    repositories: &'static [MockRepository],
 }
 
 struct MockUser {
@@ -200,6 +273,13 @@ struct MockTeam {
     members: &'static [&'static str],
 }
 
+struct MockRepository {
+    owner: &'static str,
+    name: &'static str,
+    files: &'static [(&'static str, &'static str)],
+    tags: &'static [&'static str],
+}
+
 struct MockPublicKey {
     key_identifier: &'static str,
     key: &'static str,
@@ -1,11 +1,15 @@
 use super::{MockAnonymousUser, MockCookieUser, MockTokenUser};
 use crate::record;
 use crate::util::{chaosproxy::ChaosProxy, fresh_schema::FreshSchema};
-use crates_io::config::{self, BalanceCapacityConfig, Base, DatabasePools, DbPoolConfig};
+use crates_io::config::{
+    self, BalanceCapacityConfig, Base, CrossRegistryConfig, DatabasePools, DbPoolConfig,
+    DuplicateContentConfig, IpAnonymization, VersionPolicyConfig, WatchdogConfig,
+};
 use crates_io::storage::StorageConfig;
 use crates_io::{background_jobs::Environment, env, App, Emails, Env};
 use crates_io_index::testing::UpstreamIndex;
 use crates_io_index::{Credentials, Repository as WorkerRepository, RepositoryConfig};
+use crates_io_tarball::Compression;
 use std::{rc::Rc, sync::Arc, time::Duration};
 
 use crate::util::github::{MockGitHubClient, MOCK_GITHUB_DATA};
@@ -263,6 +267,8 @@ impl TestAppBuilder {
                 None,
                 None,
                 app.storage.clone(),
+                app.emails.clone(),
+                None,
             );
 
             Some(Runner::test_runner(
@@ -396,6 +402,16 @@ fn simple_config() -> config::Server {
         gh_client_secret: ClientSecret::new(dotenvy::var("GH_CLIENT_SECRET").unwrap_or_default()),
         max_upload_size: 3000,
         max_unpack_size: 2000,
+        max_unpack_entries: 100,
+        max_unpack_file_size: 2000,
+        case_insensitive_tarball_paths: false,
+        deny_tarball_executables: false,
+        denied_tarball_paths: vec![],
+        large_file_warning_size: 2000,
+        manifest_limits: Default::default(),
+        allowed_compressions: vec![Compression::Gzip],
+        publish_diagnostics_retention: None,
+        deleted_name_cooldown: Duration::ZERO,
         rate_limiter: Default::default(),
         new_version_rate_limit: Some(10),
         blocked_traffic: Default::default(),
@@ -406,6 +422,7 @@ fn simple_config() -> config::Server {
         domain_name: "crates.io".into(),
         allowed_origins: Default::default(),
         downloads_persist_interval_ms: 1000,
+        download_sample_rate: 0.0,
         ownership_invitations_expiration_days: 30,
         metrics_authorization_token: None,
         use_test_database_pool: true,
@@ -414,8 +431,17 @@ fn simple_config() -> config::Server {
         blocked_routes: HashSet::new(),
         version_id_cache_size: 10000,
         version_id_cache_ttl: Duration::from_secs(5 * 60),
+        crate_downloads_cache_size: 10000,
+        crate_downloads_cache_ttl: Duration::from_secs(5 * 60),
         cdn_user_agent: "Amazon CloudFront".to_string(),
         balance_capacity,
+        cross_registry: CrossRegistryConfig::default(),
+        duplicate_content: DuplicateContentConfig::default(),
+        version_policy: VersionPolicyConfig::default(),
+        publish_policy: Default::default(),
+        request_budget: None,
+        ip_anonymization: IpAnonymization::Disabled,
+        watchdog: WatchdogConfig::default(),
 
         // The frontend code is not needed for the backend tests.
         serve_dist: false,
@@ -438,7 +464,7 @@ fn build_app(config: config::Server, proxy: Option<String>) -> (Arc<App>, axum::
 
     // Use the in-memory email backend for all tests, allowing tests to analyze the emails sent by
     // the application. This will also prevent cluttering the filesystem.
-    app.emails = Emails::new_in_memory();
+    app.emails = Arc::new(Emails::new_in_memory());
 
     // Use a custom mock for the GitHub client, allowing to define the GitHub users and
     // organizations without actually having to create GitHub accounts.
@@ -1,6 +1,8 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::TestApp;
 use crates_io::models::Version;
+use crates_io::schema::readme_renderings;
+use diesel::prelude::*;
 
 #[test]
 fn record_rerendered_readme_time() {
@@ -11,7 +13,20 @@ fn record_rerendered_readme_time() {
         let c = CrateBuilder::new("foo_authors", user.id).expect_build(conn);
         let version = VersionBuilder::new("1.0.0").expect_build(c.id, user.id, conn);
 
-        Version::record_readme_rendering(version.id, conn).unwrap();
-        Version::record_readme_rendering(version.id, conn).unwrap();
+        Version::record_readme_rendering(version.id, Some("timed out"), conn).unwrap();
+        let error: Option<String> = readme_renderings::table
+            .find(version.id)
+            .select(readme_renderings::error)
+            .first(conn)
+            .unwrap();
+        assert_eq!(error.as_deref(), Some("timed out"));
+
+        Version::record_readme_rendering(version.id, None, conn).unwrap();
+        let error: Option<String> = readme_renderings::table
+            .find(version.id)
+            .select(readme_renderings::error)
+            .first(conn)
+            .unwrap();
+        assert_none!(error);
     });
 }
@@ -0,0 +1,123 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use crates_io::background_jobs::Job;
+use crates_io::schema::{crates, unavailable_versions, versions};
+use diesel::prelude::*;
+use http::StatusCode;
+use hyper::body::Bytes;
+use object_store::path::Path;
+
+fn unavailable_versions(app: &TestApp) -> Vec<(String, String)> {
+    app.db(|conn| {
+        unavailable_versions::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .select((versions::num, unavailable_versions::reason))
+            .order(versions::num)
+            .load(conn)
+            .unwrap()
+    })
+}
+
+#[test]
+fn verify_checksums_marks_corrupted_files() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.1.0"))
+        .good();
+
+    let store = app.as_inner().storage.as_inner();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let corrupted_path = Path::from("crates/foo/foo-1.1.0.crate");
+    let original = rt.block_on(async {
+        let original = store.get(&corrupted_path).await.unwrap().bytes().await;
+
+        store
+            .put(&corrupted_path, Bytes::from_static(b"corrupted"))
+            .await
+            .unwrap();
+
+        original.unwrap()
+    });
+
+    // Mismatches don't fail the job, so that a single broken file doesn't
+    // make the job retry the verification of its batch. Every batch
+    // enqueues the next one, until all versions are verified.
+    app.db(|conn| Job::verify_checksums(None, 0, 1).enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    assert_eq!(
+        unavailable_versions(&app),
+        vec![("1.1.0".to_string(), "checksum_mismatch".to_string())]
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo/1.1.0/download");
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "The crate file of foo v1.1.0 is missing or corrupted in the storage of this registry and could not be restored." }] })
+    );
+    anon.get::<()>("/api/v1/crates/foo/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo/foo-1.0.0.crate");
+
+    // Once the crate file is repaired, the version is available again
+    rt.block_on(store.put(&corrupted_path, original)).unwrap();
+
+    app.db(|conn| {
+        Job::verify_checksums(Some("foo".into()), 0, 100)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    assert!(unavailable_versions(&app).is_empty());
+    anon.get::<()>("/api/v1/crates/foo/1.1.0/download")
+        .assert_redirect_ends_with("/crates/foo/foo-1.1.0.crate");
+}
+
+#[test]
+fn verify_checksums_resumes_after_version_id() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.1.0"))
+        .good();
+
+    let store = app.as_inner().storage.as_inner();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        for path in ["crates/foo/foo-1.0.0.crate", "crates/foo/foo-1.1.0.crate"] {
+            let path = Path::from(path);
+            store
+                .put(&path, Bytes::from_static(b"corrupted"))
+                .await
+                .unwrap();
+        }
+    });
+
+    // Only the versions after the given one are verified
+    let first_version_id: i32 = app.db(|conn| {
+        versions::table
+            .select(versions::id)
+            .order(versions::id)
+            .first(conn)
+            .unwrap()
+    });
+    app.db(|conn| {
+        Job::verify_checksums(None, first_version_id, 100)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(
+        unavailable_versions(&app),
+        vec![("1.1.0".to_string(), "checksum_mismatch".to_string())]
+    );
+}
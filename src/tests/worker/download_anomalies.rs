@@ -0,0 +1,171 @@
+use crate::builders::CrateBuilder;
+use crate::util::TestApp;
+use chrono::{Duration, NaiveDate, Utc};
+use crates_io::background_jobs::Job;
+use crates_io::models::{Crate, DownloadAnomaly};
+use crates_io::schema::{crates, version_downloads, versions};
+use diesel::prelude::*;
+
+fn add_downloads(krate: &Crate, date: NaiveDate, downloads: i32, conn: &mut PgConnection) {
+    let version_id: i32 = versions::table
+        .filter(versions::crate_id.eq(krate.id))
+        .select(versions::id)
+        .first(conn)
+        .unwrap();
+
+    diesel::insert_into(version_downloads::table)
+        .values((
+            version_downloads::version_id.eq(version_id),
+            version_downloads::date.eq(date),
+            version_downloads::downloads.eq(downloads),
+            version_downloads::counted.eq(downloads),
+            version_downloads::processed.eq(true),
+        ))
+        .execute(conn)
+        .unwrap();
+
+    diesel::update(versions::table.find(version_id))
+        .set(versions::downloads.eq(versions::downloads + downloads))
+        .execute(conn)
+        .unwrap();
+}
+
+/// Backdates the creation of a crate, so that it is old enough to have a
+/// download baseline.
+fn set_created_at(krate: &Crate, days_ago: i64, conn: &mut PgConnection) {
+    diesel::update(crates::table.find(krate.id))
+        .set(crates::created_at.eq(Utc::now().naive_utc() - Duration::days(days_ago)))
+        .execute(conn)
+        .unwrap();
+}
+
+fn crate_downloads(crate_name: &str, conn: &mut PgConnection) -> i32 {
+    crates::table
+        .filter(crates::name.eq(crate_name))
+        .select(crates::downloads)
+        .first(conn)
+        .unwrap()
+}
+
+fn version_downloads(crate_name: &str, conn: &mut PgConnection) -> (i32, i32, i32) {
+    let yesterday = Utc::now().date_naive() - Duration::days(1);
+
+    versions::table
+        .inner_join(crates::table)
+        .inner_join(version_downloads::table)
+        .filter(crates::name.eq(crate_name))
+        .filter(version_downloads::date.eq(yesterday))
+        .select((
+            versions::downloads,
+            version_downloads::downloads,
+            version_downloads::counted,
+        ))
+        .first(conn)
+        .unwrap()
+}
+
+#[test]
+fn detect_download_anomalies() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user = user.as_model();
+
+    let yesterday = Utc::now().date_naive() - Duration::days(1);
+
+    app.db(|conn| {
+        let steady = CrateBuilder::new("steady", user.id)
+            .downloads(11_000)
+            .expect_build(conn);
+        let inflated = CrateBuilder::new("inflated", user.id)
+            .downloads(102_000)
+            .expect_build(conn);
+        set_created_at(&steady, 10, conn);
+        set_created_at(&inflated, 10, conn);
+
+        for days in 2..=3 {
+            let date = Utc::now().date_naive() - Duration::days(days);
+            add_downloads(&steady, date, 1_000, conn);
+            add_downloads(&inflated, date, 1_000, conn);
+        }
+
+        add_downloads(&steady, yesterday, 1_050, conn);
+        add_downloads(&inflated, yesterday, 100_000, conn);
+
+        Job::detect_download_anomalies(2, 100, 6.0, true)
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let anomalies = app.db(|conn| DownloadAnomaly::since(yesterday, conn).unwrap());
+    assert_eq!(anomalies.len(), 1);
+
+    let (anomaly, crate_name) = &anomalies[0];
+    assert_eq!(crate_name, "inflated");
+    assert_eq!(anomaly.date, yesterday);
+    assert_eq!(anomaly.downloads, 100_000);
+    assert_eq!(anomaly.baseline_mean, 1_000.0);
+    assert_eq!(anomaly.excluded_downloads, 99_000);
+
+    let downloads = app.db(|conn| crate_downloads("inflated", conn));
+    assert_eq!(downloads, 3_000);
+
+    // The excluded downloads are removed from the version and its daily
+    // downloads too
+    let downloads = app.db(|conn| version_downloads("inflated", conn));
+    assert_eq!(downloads, (3_000, 1_000, 1_000));
+    let downloads = app.db(|conn| version_downloads("steady", conn));
+    assert_eq!(downloads, (3_050, 1_050, 1_050));
+
+    // Running the job again does not exclude the downloads twice
+    app.db(|conn| {
+        Job::detect_download_anomalies(2, 100, 6.0, true)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    let downloads = app.db(|conn| crate_downloads("inflated", conn));
+    assert_eq!(downloads, 3_000);
+
+    let downloads = app.db(|conn| version_downloads("inflated", conn));
+    assert_eq!(downloads, (3_000, 1_000, 1_000));
+}
+
+#[test]
+fn new_crates_are_not_scored() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user = user.as_model();
+
+    let yesterday = Utc::now().date_naive() - Duration::days(1);
+
+    app.db(|conn| {
+        // Published within the baseline period
+        let new = CrateBuilder::new("new", user.id)
+            .downloads(100_000)
+            .expect_build(conn);
+        set_created_at(&new, 2, conn);
+        add_downloads(&new, yesterday, 100_000, conn);
+
+        // Old enough, but without any downloads in the baseline period
+        let dormant = CrateBuilder::new("dormant", user.id)
+            .downloads(100_000)
+            .expect_build(conn);
+        set_created_at(&dormant, 10, conn);
+        add_downloads(&dormant, yesterday, 100_000, conn);
+
+        Job::detect_download_anomalies(7, 100, 6.0, true)
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let anomalies = app.db(|conn| DownloadAnomaly::since(yesterday, conn).unwrap());
+    assert!(anomalies.is_empty());
+
+    let downloads = app.db(|conn| version_downloads("new", conn));
+    assert_eq!(downloads, (100_000, 100_000, 100_000));
+    let downloads = app.db(|conn| version_downloads("dormant", conn));
+    assert_eq!(downloads, (100_000, 100_000, 100_000));
+}
@@ -0,0 +1,196 @@
+use crate::builders::CrateBuilder;
+use crate::util::TestApp;
+use chrono::{Datelike, Months, NaiveDate, Utc};
+use crates_io::background_jobs::Job;
+use crates_io::models::downloads_partition::first_of_month;
+use crates_io::models::DownloadsPartition;
+use crates_io::schema::{archived_version_downloads, version_downloads, versions};
+use diesel::prelude::*;
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+#[test]
+fn creates_partitions_ahead_of_time() {
+    let (app, _) = TestApp::full().empty();
+
+    let next_year = first_of_month(Utc::now().date_naive()) + Months::new(12);
+    let partitions = app.db(|conn| DownloadsPartition::all(conn).unwrap());
+    assert!(partitions.iter().all(|partition| partition.to <= next_year));
+
+    app.db(|conn| {
+        Job::manage_downloads_partitions(12, 6, true)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert_eq!(
+        app.db(|conn| DownloadsPartition::all(conn).unwrap()),
+        partitions
+    );
+
+    app.db(|conn| {
+        Job::manage_downloads_partitions(12, 6, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    let partitions = app.db(|conn| DownloadsPartition::all(conn).unwrap());
+    let last = partitions.last().unwrap();
+    assert_eq!(
+        last.name,
+        format!("version_downloads_{}", next_year.format("%Y_%m"))
+    );
+    assert_eq!(last.from, Some(next_year));
+    assert!(partitions
+        .windows(2)
+        .all(|pair| pair[0].to == pair[1].from.unwrap()));
+
+    // Running the job again doesn't create any more partitions
+    app.db(|conn| {
+        Job::manage_downloads_partitions(12, 6, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert_eq!(
+        app.db(|conn| DownloadsPartition::all(conn).unwrap()),
+        partitions
+    );
+}
+
+#[test]
+fn archives_and_drops_old_partitions() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user = user.as_model();
+
+    let old_month = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    let version_id = app.db(|conn| {
+        let krate = CrateBuilder::new("foo", user.id).expect_build(conn);
+        let version_id: i32 = versions::table
+            .filter(versions::crate_id.eq(krate.id))
+            .select(versions::id)
+            .first(conn)
+            .unwrap();
+
+        // The legacy partition covers all old dates, so it is replaced with
+        // partitions for an old month and the current month.
+        diesel::sql_query(
+            "ALTER TABLE version_downloads DETACH PARTITION version_downloads_legacy",
+        )
+        .execute(conn)
+        .unwrap();
+        DownloadsPartition::create(old_month, conn).unwrap();
+        DownloadsPartition::create(Utc::now().date_naive(), conn).unwrap();
+
+        for (day, downloads) in [(5, 10), (6, 20)] {
+            diesel::insert_into(version_downloads::table)
+                .values((
+                    version_downloads::version_id.eq(version_id),
+                    version_downloads::date.eq(old_month.with_day(day).unwrap()),
+                    version_downloads::downloads.eq(downloads),
+                    version_downloads::counted.eq(downloads),
+                    version_downloads::processed.eq(true),
+                ))
+                .execute(conn)
+                .unwrap();
+        }
+
+        version_id
+    });
+
+    app.db(|conn| {
+        Job::manage_downloads_partitions(0, 6, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    let partitions = app.db(|conn| DownloadsPartition::all(conn).unwrap());
+    let names = partitions
+        .iter()
+        .map(|partition| partition.name.as_str())
+        .collect::<Vec<_>>();
+    assert!(!names.contains(&"version_downloads_2020_01"));
+
+    // The sums of the dropped rows are kept in the database
+    let archived: (i32, i64, i64) = app.db(|conn| {
+        archived_version_downloads::table
+            .select((
+                archived_version_downloads::version_id,
+                archived_version_downloads::downloads,
+                archived_version_downloads::counted,
+            ))
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(archived, (version_id, 30, 30));
+
+    // ... and the rows themselves in the storage
+    let path = "archive/version-downloads/version_downloads_2020_01.csv.gz";
+    assert!(app.stored_files().contains(&path.to_string()));
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let store = app.as_inner().storage.as_inner();
+    let bytes = rt.block_on(async {
+        let path = path.into();
+        store.get(&path).await.unwrap().bytes().await.unwrap()
+    });
+
+    let mut csv = String::new();
+    GzDecoder::new(&*bytes).read_to_string(&mut csv).unwrap();
+    assert_eq!(
+        csv,
+        format!(
+            "version_id,downloads,counted,date,processed\n\
+             {version_id},10,10,2020-01-05,true\n\
+             {version_id},20,20,2020-01-06,true\n"
+        )
+    );
+}
+
+#[test]
+fn moves_downloads_out_of_the_default_partition() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user = user.as_model();
+
+    let next_year = first_of_month(Utc::now().date_naive()) + Months::new(12);
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo", user.id).expect_build(conn);
+        let version_id: i32 = versions::table
+            .filter(versions::crate_id.eq(krate.id))
+            .select(versions::id)
+            .first(conn)
+            .unwrap();
+
+        // There is no partition for next year yet, so the downloads are
+        // stored in the default partition instead of being rejected
+        diesel::insert_into(version_downloads::table)
+            .values((
+                version_downloads::version_id.eq(version_id),
+                version_downloads::date.eq(next_year),
+                version_downloads::downloads.eq(5),
+            ))
+            .execute(conn)
+            .unwrap();
+
+        Job::manage_downloads_partitions(12, 6, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    let partition: String = app.db(|conn| {
+        version_downloads::table
+            .filter(version_downloads::date.eq(next_year))
+            .select(diesel::dsl::sql::<diesel::sql_types::Text>(
+                "tableoid::regclass::text",
+            ))
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(
+        partition,
+        format!("version_downloads_{}", next_year.format("%Y_%m"))
+    );
+}
@@ -0,0 +1,172 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use chrono::{Duration, Utc};
+use crates_io::background_jobs::Job;
+use crates_io::models::{Crate, DependencyKind};
+use crates_io::schema::{dependencies, features_usage, versions};
+use diesel::prelude::*;
+use serde_json::Value;
+
+/// Updates the dependency of a version of `dependent` on `krate`.
+fn set_dependency(
+    conn: &mut PgConnection,
+    dependent: &Crate,
+    num: &str,
+    krate: &Crate,
+    default_features: bool,
+    features: &[&str],
+    kind: DependencyKind,
+) {
+    let version_id: i32 = versions::table
+        .filter(versions::crate_id.eq(dependent.id))
+        .filter(versions::num.eq(num))
+        .select(versions::id)
+        .first(conn)
+        .unwrap();
+
+    diesel::update(dependencies::table)
+        .filter(dependencies::version_id.eq(version_id))
+        .filter(dependencies::crate_id.eq(krate.id))
+        .set((
+            dependencies::default_features.eq(default_features),
+            dependencies::features.eq(features),
+            dependencies::kind.eq(kind),
+        ))
+        .execute(conn)
+        .unwrap();
+}
+
+#[test]
+fn aggregate_feature_usage() {
+    let (app, anon, user) = TestApp::full().with_user();
+    let user = user.as_model();
+
+    let yesterday = Utc::now().naive_utc() - Duration::days(1);
+
+    app.db(|conn| {
+        let serde = CrateBuilder::new("serde", user.id).expect_build(conn);
+
+        let uses_defaults = CrateBuilder::new("uses-defaults", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&serde, None))
+            .expect_build(conn);
+        set_dependency(
+            conn,
+            &uses_defaults,
+            "1.0.0",
+            &serde,
+            true,
+            &["derive"],
+            DependencyKind::Normal,
+        );
+
+        // Only the newest version of a dependent is considered
+        let changed = CrateBuilder::new("changed", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .dependency(&serde, None)
+                    .created_at(yesterday),
+            )
+            .version(VersionBuilder::new("1.1.0").dependency(&serde, None))
+            .expect_build(conn);
+        set_dependency(
+            conn,
+            &changed,
+            "1.0.0",
+            &serde,
+            true,
+            &["derive"],
+            DependencyKind::Normal,
+        );
+        set_dependency(
+            conn,
+            &changed,
+            "1.1.0",
+            &serde,
+            false,
+            &["derive", "rc"],
+            DependencyKind::Build,
+        );
+
+        // Dev dependencies don't affect users of the dependent
+        let dev_only = CrateBuilder::new("dev-only", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&serde, None))
+            .expect_build(conn);
+        set_dependency(
+            conn,
+            &dev_only,
+            "1.0.0",
+            &serde,
+            true,
+            &["rc"],
+            DependencyKind::Dev,
+        );
+
+        // Snapshots older than the retention period are pruned
+        diesel::insert_into(features_usage::table)
+            .values((
+                features_usage::crate_id.eq(serde.id),
+                features_usage::feature.eq("std"),
+                features_usage::date.eq(Utc::now().date_naive() - Duration::days(400)),
+                features_usage::dependents.eq(1),
+            ))
+            .execute(conn)
+            .unwrap();
+
+        Job::aggregate_feature_usage(365).enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let old_snapshots: i64 = app.db(|conn| {
+        features_usage::table
+            .filter(features_usage::feature.eq("std"))
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(old_snapshots, 0);
+
+    let json: Value = anon.get("/api/v1/crates/serde/feature_usage").good();
+    let today = Utc::now().date_naive().to_string();
+    assert_eq!(
+        json,
+        json!({
+            "features": {
+                "default": [{ "date": today, "dependents": 1 }],
+                "derive": [{ "date": today, "dependents": 2 }],
+                "rc": [{ "date": today, "dependents": 1 }],
+            }
+        })
+    );
+
+    // Running the aggregation again on the same day replaces the snapshot
+    app.db(|conn| {
+        diesel::update(versions::table)
+            .filter(versions::num.eq("1.1.0"))
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+
+        Job::aggregate_feature_usage(365).enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let json: Value = anon.get("/api/v1/crates/serde/feature_usage").good();
+    assert_eq!(
+        json,
+        json!({
+            "features": {
+                "default": [{ "date": today, "dependents": 2 }],
+                "derive": [{ "date": today, "dependents": 2 }],
+            }
+        })
+    );
+}
+
+#[test]
+fn feature_usage_of_unknown_crate() {
+    let (_, anon) = TestApp::init().empty();
+    anon.get::<()>("/api/v1/crates/unknown/feature_usage")
+        .assert_not_found();
+}
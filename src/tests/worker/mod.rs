@@ -1 +1,10 @@
+mod checksums;
+mod download_anomalies;
+mod downloads_partitions;
+mod feature_usage;
 mod git;
+mod msrv_stats;
+mod orphaned_files;
+mod readmes;
+mod single_owner_nudges;
+mod subscription_digests;
@@ -0,0 +1,68 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use chrono::{Duration, Utc};
+use crates_io::background_jobs::Job;
+use serde_json::Value;
+
+#[test]
+fn aggregate_msrv_stats() {
+    let (app, anon, user) = TestApp::full().with_user();
+    let user = user.as_model();
+
+    let json: Value = anon.get("/api/v1/stats/msrv").good();
+    assert_eq!(
+        json,
+        json!({
+            "date": null,
+            "rust_versions": [],
+            "meta": { "total_versions": 0, "versions_without_rust_version": 0 },
+        })
+    );
+
+    let long_ago = Utc::now().naive_utc() - Duration::days(200);
+
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.id)
+            .version(VersionBuilder::new("1.0.0").rust_version("1.60"))
+            .version(VersionBuilder::new("1.1.0").rust_version("1.60.1"))
+            .expect_build(conn);
+        CrateBuilder::new("bar", user.id)
+            .version(VersionBuilder::new("1.0.0").rust_version("1.56"))
+            .expect_build(conn);
+        CrateBuilder::new("baz", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+        CrateBuilder::new("old", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .rust_version("1.40")
+                    .created_at(long_ago),
+            )
+            .expect_build(conn);
+        CrateBuilder::new("yanked", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .rust_version("1.70")
+                    .yanked(true),
+            )
+            .expect_build(conn);
+
+        Job::aggregate_msrv_stats(90).enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let json: Value = anon.get("/api/v1/stats/msrv").good();
+    let today = Utc::now().date_naive().to_string();
+    assert_eq!(
+        json,
+        json!({
+            "date": today,
+            "rust_versions": [
+                { "rust_version": "1.60", "versions": 2, "crates": 1, "share": 0.5 },
+                { "rust_version": "1.56", "versions": 1, "crates": 1, "share": 0.25 },
+            ],
+            "meta": { "total_versions": 4, "versions_without_rust_version": 1 },
+        })
+    );
+}
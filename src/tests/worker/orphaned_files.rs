@@ -0,0 +1,97 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use crates_io::background_jobs::Job;
+use hyper::body::Bytes;
+use object_store::path::Path;
+
+#[test]
+fn sweep_orphaned_files() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0"))
+        .good();
+
+    let storage = &app.as_inner().storage;
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let store = storage.as_inner();
+        for path in [
+            "cas/blobs/ab/abcd",
+            "cas/references/abcd/crates/gone/gone-1.0.0.json",
+            "crates/foo/foo-0.9.0.crate",
+            "readmes/foo/foo-0.9.0.html",
+            "readmes/foo/README.md",
+            "readmes/gone/gone-1.0.0.html",
+        ] {
+            store.put(&Path::from(path), Bytes::new()).await.unwrap();
+        }
+
+        storage.sync_index("gone", Some("{}".into())).await.unwrap();
+    });
+
+    let expected_files = vec![
+        "cas/blobs/ab/abcd",
+        "cas/references/abcd/crates/gone/gone-1.0.0.json",
+        "crates/foo/foo-0.9.0.crate",
+        "crates/foo/foo-1.0.0.crate",
+        "index/3/f/foo",
+        "index/go/ne/gone",
+        "readmes/foo/README.md",
+        "readmes/foo/foo-0.9.0.html",
+        "readmes/gone/gone-1.0.0.html",
+    ];
+    assert_eq!(app.stored_files(), expected_files);
+
+    // Recently modified files might belong to a publish in progress
+    app.db(|conn| {
+        Job::sweep_orphaned_files(None, 6, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert_eq!(app.stored_files(), expected_files);
+
+    app.db(|conn| {
+        Job::sweep_orphaned_files(None, 0, true)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert_eq!(app.stored_files(), expected_files);
+
+    app.db(|conn| {
+        Job::sweep_orphaned_files(Some("gone".into()), 0, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    // Blobs are only swept together with all crates
+    let expected_files = vec![
+        "cas/blobs/ab/abcd",
+        "cas/references/abcd/crates/gone/gone-1.0.0.json",
+        "crates/foo/foo-0.9.0.crate",
+        "crates/foo/foo-1.0.0.crate",
+        "index/3/f/foo",
+        "readmes/foo/README.md",
+        "readmes/foo/foo-0.9.0.html",
+    ];
+    assert_eq!(app.stored_files(), expected_files);
+
+    app.db(|conn| {
+        Job::sweep_orphaned_files(None, 0, false)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    // Unexpected files are only reported, and blobs whose references
+    // belong to manifests that don't exist anymore are deleted
+    let expected_files = vec![
+        "crates/foo/foo-1.0.0.crate",
+        "index/3/f/foo",
+        "readmes/foo/README.md",
+    ];
+    assert_eq!(app.stored_files(), expected_files);
+}
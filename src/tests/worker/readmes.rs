@@ -0,0 +1,119 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use crates_io::background_jobs::Job;
+use crates_io::schema::{crates, versions, versions_without_readme};
+use crates_io_tarball::TarballBuilder;
+use diesel::prelude::*;
+
+fn readme_files(app: &TestApp) -> Vec<String> {
+    app.stored_files()
+        .into_iter()
+        .filter(|path| path.starts_with("readmes/"))
+        .collect()
+}
+
+#[test]
+fn repair_missing_readmes() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    // The README of `foo` was never rendered, e.g. because the publish
+    // request did not include it in its metadata.
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\nreadme = \"README.md\"\n")
+        .add_file("foo-1.0.0/README.md", b"# foo")
+        .build();
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0").tarball(tarball))
+        .good();
+
+    // The manifest of `bar` opts out of a README
+    let tarball = TarballBuilder::new("bar", "1.0.0")
+        .add_raw_manifest(b"[package]\nreadme = false\n")
+        .build();
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0").tarball(tarball))
+        .good();
+
+    assert!(readme_files(&app).is_empty());
+
+    app.db(|conn| {
+        let two_hours_ago = chrono::Utc::now().naive_utc() - chrono::Duration::hours(2);
+        diesel::update(versions::table)
+            .set(versions::created_at.eq(two_hours_ago))
+            .execute(conn)
+            .unwrap();
+
+        Job::repair_readmes(7, 100).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(readme_files(&app), vec!["readmes/foo/foo-1.0.0.html"]);
+
+    // The crate file of `bar` is not downloaded again by the next runs
+    let without_readme = app.db(|conn| {
+        versions_without_readme::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .select(crates::name)
+            .load::<String>(conn)
+            .unwrap()
+    });
+    assert_eq!(without_readme, vec!["bar"]);
+}
+
+#[test]
+fn pending_readme_renders() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0").readme("# foo"))
+        .good();
+
+    app.db(|conn| {
+        let version_id: i32 = versions::table.select(versions::id).first(conn).unwrap();
+        let pending = Job::pending_readme_renders(conn).unwrap();
+        assert_eq!(pending.into_iter().collect::<Vec<_>>(), vec![version_id]);
+    });
+
+    app.run_pending_background_jobs();
+
+    app.db(|conn| assert!(Job::pending_readme_renders(conn).unwrap().is_empty()));
+}
+
+#[test]
+fn repair_readmes_past_rendered_versions() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let tarball = TarballBuilder::new("foo", "1.0.0")
+        .add_raw_manifest(b"[package]\nreadme = \"README.md\"\n")
+        .add_file("foo-1.0.0/README.md", b"# foo")
+        .build();
+    token
+        .publish_crate(PublishBuilder::new("foo", "1.0.0").tarball(tarball))
+        .good();
+
+    // `bar` is published later and its README is rendered as usual
+    token
+        .publish_crate(PublishBuilder::new("bar", "1.0.0").readme("# bar"))
+        .good();
+    app.run_pending_background_jobs();
+
+    assert_eq!(readme_files(&app), vec!["readmes/bar/bar-1.0.0.html"]);
+
+    app.db(|conn| {
+        let two_hours_ago = chrono::Utc::now().naive_utc() - chrono::Duration::hours(2);
+        diesel::update(versions::table)
+            .set(versions::created_at.eq(two_hours_ago))
+            .execute(conn)
+            .unwrap();
+
+        // The newest version is fine, so the missing README of `foo` is
+        // only found past the first batch
+        Job::repair_readmes(7, 1).enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(
+        readme_files(&app),
+        vec!["readmes/bar/bar-1.0.0.html", "readmes/foo/foo-1.0.0.html"]
+    );
+}
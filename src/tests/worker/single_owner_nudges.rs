@@ -0,0 +1,66 @@
+use crate::builders::CrateBuilder;
+use crate::util::TestApp;
+use crate::{add_team_to_crate, new_team};
+use crates_io::background_jobs::Job;
+
+#[test]
+fn nudge_single_owner_crates() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_model = user.as_model();
+
+    let popular = app.db(|conn| {
+        let popular = CrateBuilder::new("popular", user_model.id)
+            .downloads(2_000_000)
+            .expect_build(conn);
+        CrateBuilder::new("unpopular", user_model.id)
+            .downloads(10)
+            .expect_build(conn);
+        let shared = CrateBuilder::new("shared", user_model.id)
+            .downloads(2_000_000)
+            .expect_build(conn);
+
+        let team = new_team("github:test-org:core")
+            .create_or_update(conn)
+            .unwrap();
+        add_team_to_crate(&team, &shared, user_model, conn).unwrap();
+
+        popular
+    });
+
+    let run_job = || {
+        let job = Job::nudge_single_owner_crates(1_000_000, 180, true);
+        app.db(|conn| job.enqueue(conn).unwrap());
+        app.run_pending_background_jobs();
+    };
+
+    let flagged_crates = || {
+        user.show_me()
+            .owned_crates
+            .into_iter()
+            .filter(|krate| krate.needs_backup_owner)
+            .map(|krate| krate.name)
+            .collect::<Vec<_>>()
+    };
+
+    run_job();
+    assert_eq!(flagged_crates(), vec!["popular"]);
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].body.contains("- popular"));
+    assert!(!emails[0].body.contains("shared"));
+
+    // The owner is only reminded again after the reminder period
+    run_job();
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 1);
+
+    app.db(|conn| {
+        let team = new_team("github:test-org:backup")
+            .create_or_update(conn)
+            .unwrap();
+        add_team_to_crate(&team, &popular, user_model, conn).unwrap();
+    });
+
+    run_job();
+    assert!(flagged_crates().is_empty());
+}
@@ -0,0 +1,108 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use crate::OkBool;
+use chrono::{Duration, Utc};
+use crates_io::background_jobs::Job;
+use crates_io::models::DigestPreference;
+use crates_io::schema::{crates, versions};
+use diesel::prelude::*;
+
+#[test]
+fn send_subscription_digests() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    let two_weeks_ago = (Utc::now() - Duration::weeks(2)).naive_utc();
+    app.db(|conn| {
+        let old = CrateBuilder::new("old_crate", user_id)
+            .keyword("async")
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+
+        // Releases from before the digest period are not included
+        diesel::update(versions::table.filter(versions::crate_id.eq(old.id)))
+            .set(versions::created_at.eq(two_weeks_ago))
+            .execute(conn)
+            .unwrap();
+        diesel::update(crates::table.find(old.id))
+            .set(crates::created_at.eq(two_weeks_ago))
+            .execute(conn)
+            .unwrap();
+
+        CrateBuilder::new("new_crate", user_id)
+            .keyword("async")
+            .description("A new crate")
+            .version(VersionBuilder::new("0.1.0"))
+            .expect_build(conn);
+    });
+
+    user.put::<OkBool>("/api/v1/keywords/async/subscribe", b"")
+        .good();
+
+    app.db(|conn| Job::send_subscription_digests(50).enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].subject, "Your crates.io digest");
+    assert!(emails[0].body.contains("new_crate 0.1.0 (new crate)"));
+    assert!(emails[0].body.contains("A new crate"));
+    assert!(!emails[0].body.contains("old_crate"));
+
+    let preference = app.db(|conn| DigestPreference::for_user(user_id, conn).unwrap());
+    assert!(preference.last_sent_at.is_some());
+
+    // The next digest is only sent after a week
+    app.db(|conn| Job::send_subscription_digests(50).enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+    assert_eq!(app.as_inner().emails.mails_in_memory().unwrap().len(), 1);
+}
+
+#[test]
+fn follow_activity_in_digest() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    app.db(|conn| {
+        CrateBuilder::new("followed", user_id)
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .expect_build(conn);
+        CrateBuilder::new("releases_only", user_id)
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .expect_build(conn);
+        CrateBuilder::new("feed_only", user_id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let body = json!({
+        "follow": [
+            { "crate": "followed", "mode": "all_activity", "channel": "email" },
+            { "crate": "releases_only", "mode": "releases", "channel": "email" },
+            { "crate": "feed_only", "mode": "all_activity", "channel": "feed" },
+        ]
+    });
+    user.put::<OkBool>("/api/v1/me/follows", body.to_string().as_bytes())
+        .good();
+
+    user.delete::<OkBool>("/api/v1/crates/followed/1.0.0/yank")
+        .good();
+    user.delete::<OkBool>("/api/v1/crates/releases_only/1.0.0/yank")
+        .good();
+
+    app.db(|conn| Job::send_subscription_digests(50).enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert_eq!(emails.len(), 1);
+
+    let body = &emails[0].body;
+    assert!(body.contains("followed 1.1.0 was published"));
+    assert!(body.contains("followed 1.0.0 was yanked"));
+    assert!(body.contains("foo was added as an owner of followed"));
+    assert!(body.contains("releases_only 1.1.0 was published"));
+    assert!(!body.contains("releases_only 1.0.0 was yanked"));
+    assert!(!body.contains("feed_only"));
+}
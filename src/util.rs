@@ -5,17 +5,21 @@ pub use self::io_util::{read_fill, read_le_u32};
 pub use self::request_helpers::*;
 
 mod bytes_request;
+pub mod deadline;
 pub mod errors;
 mod io_util;
 mod request_helpers;
 pub mod rfc3339;
+pub mod source_links;
 pub mod token;
 pub mod tracing;
+pub mod url_normalization;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Maximums {
     pub max_upload_size: u64,
     pub max_unpack_size: u64,
+    pub max_unpack_file_size: u64,
 }
 
 impl Maximums {
@@ -23,12 +27,15 @@ impl Maximums {
         krate_max_upload: Option<i32>,
         app_max_upload: u64,
         app_max_unpack: u64,
+        app_max_unpack_file_size: u64,
     ) -> Maximums {
         let max_upload_size = krate_max_upload.map(|m| m as u64).unwrap_or(app_max_upload);
         let max_unpack_size = cmp::max(app_max_unpack, max_upload_size);
+        let max_unpack_file_size = cmp::max(app_max_unpack_file_size, max_upload_size);
         Maximums {
             max_upload_size,
             max_unpack_size,
+            max_unpack_file_size,
         }
     }
 }
@@ -0,0 +1,117 @@
+//! Request-scoped deadlines.
+//!
+//! If `WEB_REQUEST_BUDGET_MS` is configured, the `request_deadline` middleware
+//! attaches a [`Deadline`] to each request. The deadline is available as a
+//! task-local in the async part of the request handling, and is carried over
+//! into the blocking part by `conduit_compat()`, where it is stored in a
+//! thread-local for the duration of the closure.
+//!
+//! The database pool uses the remaining time of the deadline to limit how long
+//! it waits for a connection and to lower the `statement_timeout` of the
+//! connection, and the storage layer uses it to cancel slow operations. This
+//! ensures that a slow client or a stuck backend can't block a worker thread
+//! beyond the configured budget.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::time::error::Elapsed;
+
+tokio::task_local! {
+    static TASK_DEADLINE: Deadline;
+}
+
+thread_local! {
+    static THREAD_DEADLINE: Cell<Option<Deadline>> = Cell::new(None);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Creates a deadline that expires after the given `budget`.
+    pub fn after(budget: Duration) -> Self {
+        Self(Instant::now() + budget)
+    }
+
+    /// Returns the deadline of the request that is currently being handled,
+    /// if any.
+    pub fn current() -> Option<Self> {
+        TASK_DEADLINE
+            .try_with(|deadline| *deadline)
+            .ok()
+            .or_else(|| THREAD_DEADLINE.with(Cell::get))
+    }
+
+    /// Returns the time left until the deadline, or `None` if the deadline has
+    /// already passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Runs the future with this deadline as the current deadline.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        TASK_DEADLINE.scope(self, future).await
+    }
+
+    /// Runs the blocking closure with the given deadline as the current
+    /// deadline of this thread.
+    pub fn enter<R>(deadline: Option<Self>, f: impl FnOnce() -> R) -> R {
+        struct Reset(Option<Deadline>);
+
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                THREAD_DEADLINE.with(|cell| cell.set(self.0));
+            }
+        }
+
+        let _reset = Reset(THREAD_DEADLINE.with(|cell| cell.replace(deadline)));
+        f()
+    }
+}
+
+/// Cancels the future if the current deadline passes before it completes.
+///
+/// If there is no current deadline the future is run without a timeout.
+pub async fn within_deadline<F: Future>(future: F) -> Result<F::Output, Elapsed> {
+    match Deadline::current() {
+        Some(deadline) => tokio::time::timeout_at(deadline.0.into(), future).await,
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn deadline_is_carried_into_blocking_threads() {
+        assert_eq!(Deadline::current(), None);
+
+        let deadline = Deadline::after(Duration::from_secs(60));
+        let current = deadline
+            .scope(async {
+                let current = Deadline::current();
+                tokio::task::spawn_blocking(move || Deadline::enter(current, Deadline::current))
+                    .await
+                    .unwrap()
+            })
+            .await;
+
+        assert_eq!(current, Some(deadline));
+        assert_eq!(Deadline::current(), None);
+    }
+
+    #[tokio::test]
+    async fn expired_deadline_cancels_futures() {
+        let deadline = Deadline::after(Duration::ZERO);
+        assert_eq!(deadline.remaining(), None);
+
+        let result = deadline.scope(within_deadline(std::future::pending::<()>()));
+        assert!(result.await.is_err());
+
+        assert_eq!(within_deadline(async { 42 }).await, Ok(42));
+    }
+}
@@ -20,19 +20,21 @@ use std::fmt;
 
 use axum::Extension;
 use chrono::NaiveDateTime;
-use diesel::result::Error as DieselError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use http::StatusCode;
 use tokio::task::JoinError;
 
 use crate::db::PoolError;
 use crate::middleware::log_request::{CauseField, ErrorField};
+use crate::util::deadline::Deadline;
 
 mod json;
 
 pub use json::TOKEN_FORMAT_ERROR;
 pub(crate) use json::{
-    InsecurelyGeneratedTokenRevoked, MetricsDisabled, NotFound, OwnershipInvitationExpired,
-    ReadOnlyMode, RouteBlocked, TooManyRequests,
+    CrateFileUnavailable, DeadlineExceeded, InsecurelyGeneratedTokenRevoked, MetricsDisabled,
+    NotFound, OwnershipInvitationExpired, PublishInProgress, ReadOnlyMode, RouteBlocked,
+    TooManyRequests,
 };
 
 pub type BoxedAppError = Box<dyn AppError>;
@@ -63,6 +65,10 @@ pub fn account_locked(reason: &str, until: Option<NaiveDateTime>) -> BoxedAppErr
     })
 }
 
+pub fn account_compromised() -> BoxedAppError {
+    Box::new(json::AccountCompromised)
+}
+
 pub fn forbidden() -> BoxedAppError {
     Box::new(json::Forbidden)
 }
@@ -212,6 +218,31 @@ impl From<DieselError> for BoxedAppError {
             {
                 Box::new(ReadOnlyMode)
             }
+            DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => {
+                Box::new(TransactionConflict(err))
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, ref info)
+                if info
+                    .constraint_name()
+                    .map_or(false, |name| CONFLICTING_CONSTRAINTS.contains(&name)) =>
+            {
+                Box::new(TransactionConflict(err))
+            }
+            // Diesel maps `deadlock_detected` (40P01) to `Unknown` and doesn't
+            // expose the SQLSTATE, so the message has to be checked instead.
+            DieselError::DatabaseError(DatabaseErrorKind::Unknown, ref info)
+                if info.message() == "deadlock detected" =>
+            {
+                Box::new(TransactionConflict(err))
+            }
+            // Diesel doesn't expose a kind for `query_canceled` (57014), but the
+            // `statement_timeout` is derived from the request deadline, so an
+            // unclassified error after the deadline has passed is the timeout.
+            DieselError::DatabaseError(DatabaseErrorKind::Unknown, _)
+                if Deadline::current().map_or(false, |deadline| deadline.remaining().is_none()) =>
+            {
+                Box::new(DeadlineExceeded)
+            }
             _ => Box::new(err),
         }
     }
@@ -239,6 +270,8 @@ impl From<PoolError> for BoxedAppError {
     fn from(err: PoolError) -> BoxedAppError {
         match err {
             PoolError::UnhealthyPool => service_unavailable("Service unavailable"),
+            PoolError::DeadlineExceeded => Box::new(DeadlineExceeded),
+            PoolError::Query(err) => err.into(),
             _ => Box::new(err),
         }
     }
@@ -280,6 +313,34 @@ impl From<JoinError> for BoxedAppError {
     }
 }
 
+// =============================================================================
+// Conflicts between concurrent transactions
+
+/// Unique constraints that concurrent transactions can violate when they
+/// replace the same rows, e.g. two updates of the keywords of a crate that
+/// both delete and then re-insert the `crates_keywords` rows.
+const CONFLICTING_CONSTRAINTS: &[&str] = &["crates_keywords_pkey", "crates_categories_pkey"];
+
+/// A serialization failure, a deadlock or a violation of one of the
+/// `CONFLICTING_CONSTRAINTS` that aborted a database transaction.
+///
+/// These errors are caused by concurrent transactions and usually go away
+/// when the transaction is retried, see `controllers::helpers::with_transaction`.
+#[derive(Debug)]
+pub(crate) struct TransactionConflict(DieselError);
+
+impl fmt::Display for TransactionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl AppError for TransactionConflict {
+    fn response(&self) -> axum::response::Response {
+        self.0.response()
+    }
+}
+
 // =============================================================================
 // Internal error for use with `chain_error`
 
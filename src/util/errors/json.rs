@@ -62,6 +62,22 @@ impl fmt::Display for ReadOnlyMode {
     }
 }
 
+#[derive(Debug)]
+pub(crate) struct DeadlineExceeded;
+
+impl AppError for DeadlineExceeded {
+    fn response(&self) -> Response {
+        let detail = "The request took too long to process. Please try again later.";
+        json_error(detail, StatusCode::GATEWAY_TIMEOUT)
+    }
+}
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "Request deadline exceeded".fmt(f)
+    }
+}
+
 // The following structs wrap owned data and provide a custom message to the user
 
 #[derive(Debug)]
@@ -76,6 +92,10 @@ pub(crate) struct ServiceUnavailable(pub(super) String);
 pub(crate) struct TooManyRequests {
     pub retry_after: NaiveDateTime,
 }
+#[derive(Debug)]
+pub(crate) struct PublishInProgress {
+    pub crate_name: String,
+}
 
 impl AppError for Ok {
     fn response(&self) -> Response {
@@ -153,6 +173,23 @@ impl fmt::Display for TooManyRequests {
     }
 }
 
+impl AppError for PublishInProgress {
+    fn response(&self) -> Response {
+        let detail = format!(
+            "another version of `{}` is being published right now, please try again \
+             once it has been published",
+            self.crate_name
+        );
+        json_error(&detail, StatusCode::CONFLICT)
+    }
+}
+
+impl fmt::Display for PublishInProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "Concurrent publish of the same crate".fmt(f)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct InsecurelyGeneratedTokenRevoked;
 
@@ -223,6 +260,23 @@ impl fmt::Display for AccountLocked {
     }
 }
 
+#[derive(Debug)]
+pub(super) struct AccountCompromised;
+
+impl AppError for AccountCompromised {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::FORBIDDEN)
+    }
+}
+
+impl fmt::Display for AccountCompromised {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "This account is on hold because it may have been compromised. \
+         Please contact help@crates.io for further information."
+            .fmt(f)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct OwnershipInvitationExpired {
     pub(crate) crate_name: String,
@@ -245,6 +299,29 @@ impl fmt::Display for OwnershipInvitationExpired {
     }
 }
 
+#[derive(Debug)]
+pub(crate) struct CrateFileUnavailable {
+    pub(crate) crate_name: String,
+    pub(crate) version: String,
+}
+
+impl AppError for CrateFileUnavailable {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::GONE)
+    }
+}
+
+impl fmt::Display for CrateFileUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The crate file of {} v{} is missing or corrupted in the storage of this registry \
+             and could not be restored.",
+            self.crate_name, self.version
+        )
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct MetricsDisabled;
 
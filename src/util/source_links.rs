@@ -0,0 +1,175 @@
+//! Links to the source code of a version in its repository.
+//!
+//! Cargo records the commit that a crate was packaged from in the
+//! `.cargo_vcs_info.json` file of the crate file. Together with the
+//! `repository` URL of the crate, this is enough to link to the exact source
+//! of a version on the common code hosting sites, which all use a different
+//! URL scheme for browsing a repository at a commit.
+
+use crates_io_tarball::CargoVcsInfo;
+use url::Url;
+
+/// The commit that a version was packaged from, and a link to its source.
+#[derive(Debug, Default)]
+pub struct VersionSource {
+    pub commit_sha: Option<String>,
+    pub source_url: Option<String>,
+}
+
+impl VersionSource {
+    /// Reads the commit from the `.cargo_vcs_info.json` file of a crate file,
+    /// and links to it if the `repository` of the crate is supported.
+    pub fn new(repository: Option<&str>, vcs_info: Option<&CargoVcsInfo>) -> Self {
+        let Some(commit_sha) = vcs_info.and_then(|info| info.commit_sha()) else {
+            return Self::default();
+        };
+
+        let path_in_vcs = vcs_info.map(|info| info.path_in_vcs.as_str());
+        let source_url = repository
+            .and_then(|repository| source_url(repository, commit_sha, path_in_vcs.unwrap_or("")));
+
+        Self {
+            commit_sha: Some(commit_sha.to_string()),
+            source_url,
+        }
+    }
+}
+
+/// Returns a link to the directory `path_in_vcs` of the `repository` at the
+/// commit `commit_sha`.
+///
+/// Returns `None` if the repository is not hosted on one of the supported
+/// sites, or if its URL doesn't point to a repository.
+pub fn source_url(repository: &str, commit_sha: &str, path_in_vcs: &str) -> Option<String> {
+    let url = Url::parse(repository).ok()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+
+    let host = url.host_str()?;
+    let segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    // The commit and path are provided by the publisher, so they are added
+    // as percent-encoded segments, with `.` and `..` segments dropped.
+    let path = path_in_vcs
+        .split('/')
+        .filter(|segment| !matches!(*segment, "" | "." | ".."))
+        .collect::<Vec<_>>();
+    let link = |repo: String, before_commit: &[&str], before_path: &[&str]| {
+        let mut url = Url::parse(&format!("https://{host}/{repo}")).ok()?;
+        let mut url_segments = url.path_segments_mut().ok()?;
+        url_segments.extend(before_commit).push(commit_sha);
+        if !path.is_empty() {
+            url_segments.extend(before_path).extend(&path);
+        }
+        drop(url_segments);
+        Some(url.into())
+    };
+
+    match host {
+        "github.com" => link(repo_path(&segments, 2)?, &["tree"], &[]),
+        // Projects can be nested in groups, and everything after `-` is a
+        // page of the project.
+        "gitlab.com" => {
+            let end = segments.iter().position(|s| *s == "-");
+            let repo = repo_path(&segments[..end.unwrap_or(segments.len())], usize::MAX)?;
+            link(repo, &["-", "tree"], &[])
+        }
+        "codeberg.org" => link(repo_path(&segments, 2)?, &["src", "commit"], &[]),
+        "bitbucket.org" => link(repo_path(&segments, 2)?, &["src"], &[]),
+        "git.sr.ht" => link(repo_path(&segments, 2)?, &["tree"], &["item"]),
+        _ => None,
+    }
+}
+
+/// Returns the path of the repository, which consists of at least two and
+/// at most `max_len` segments, without the `.git` suffix.
+fn repo_path(segments: &[&str], max_len: usize) -> Option<String> {
+    if segments.len() < 2 {
+        return None;
+    }
+
+    let segments = &segments[..segments.len().min(max_len)];
+    let path = segments.join("/");
+    Some(path.strip_suffix(".git").map(Into::into).unwrap_or(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::source_url;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn github() {
+        assert_eq!(
+            source_url("https://github.com/rust-lang/crates.io", SHA, "").unwrap(),
+            format!("https://github.com/rust-lang/crates.io/tree/{SHA}")
+        );
+        assert_eq!(
+            source_url("https://github.com/rust-lang/cargo.git", SHA, "crates/home").unwrap(),
+            format!("https://github.com/rust-lang/cargo/tree/{SHA}/crates/home")
+        );
+        assert_eq!(
+            source_url(
+                "https://github.com/rust-lang/cargo/tree/master/crates",
+                SHA,
+                ""
+            )
+            .unwrap(),
+            format!("https://github.com/rust-lang/cargo/tree/{SHA}")
+        );
+    }
+
+    #[test]
+    fn gitlab() {
+        assert_eq!(
+            source_url("https://gitlab.com/group/subgroup/project", SHA, "foo").unwrap(),
+            format!("https://gitlab.com/group/subgroup/project/-/tree/{SHA}/foo")
+        );
+        assert_eq!(
+            source_url("https://gitlab.com/owner/project/-/tree/main", SHA, "").unwrap(),
+            format!("https://gitlab.com/owner/project/-/tree/{SHA}")
+        );
+    }
+
+    #[test]
+    fn other_hosts() {
+        assert_eq!(
+            source_url("https://codeberg.org/owner/repo", SHA, "foo").unwrap(),
+            format!("https://codeberg.org/owner/repo/src/commit/{SHA}/foo")
+        );
+        assert_eq!(
+            source_url("https://bitbucket.org/owner/repo/", SHA, "").unwrap(),
+            format!("https://bitbucket.org/owner/repo/src/{SHA}")
+        );
+        assert_eq!(
+            source_url("https://git.sr.ht/~owner/repo", SHA, "foo").unwrap(),
+            format!("https://git.sr.ht/~owner/repo/tree/{SHA}/item/foo")
+        );
+    }
+
+    #[test]
+    fn path_in_vcs_is_encoded() {
+        assert_eq!(
+            source_url("https://github.com/owner/repo", SHA, "/foo bar/./b%z#?/").unwrap(),
+            format!("https://github.com/owner/repo/tree/{SHA}/foo%20bar/b%25z%23%3F")
+        );
+        assert_eq!(
+            source_url("https://github.com/owner/repo", SHA, "../../other").unwrap(),
+            format!("https://github.com/owner/repo/tree/{SHA}/other")
+        );
+    }
+
+    #[test]
+    fn unsupported_repositories() {
+        assert_eq!(source_url("https://example.com/owner/repo", SHA, ""), None);
+        assert_eq!(source_url("http://github.com/owner/repo", SHA, ""), None);
+        assert_eq!(source_url("https://github.com/owner", SHA, ""), None);
+        assert_eq!(source_url("git@github.com:owner/repo.git", SHA, ""), None);
+        assert_eq!(source_url("not a url", SHA, ""), None);
+    }
+}
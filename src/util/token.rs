@@ -75,7 +75,7 @@ impl ExposeSecret<String> for PlainToken {
     }
 }
 
-fn generate_secure_alphanumeric_string(len: usize) -> String {
+pub(crate) fn generate_secure_alphanumeric_string(len: usize) -> String {
     const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
 
     OsRng
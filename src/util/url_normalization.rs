@@ -0,0 +1,187 @@
+//! Normalization of the URLs that are stored as crate metadata.
+//!
+//! Crate authors often copy URLs from their browser, which may include
+//! tracking query parameters or outdated forms of the URL. These are cleaned
+//! up before the URLs are stored, so that they are displayed consistently on
+//! the website and in the API.
+
+use url::Url;
+
+/// Query parameters that are only used to track the origin of a visit.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "igshid", "yclid", "_ga", "_gl",
+];
+
+/// Query parameter prefixes that are only used to track the origin of a visit.
+const TRACKING_PARAM_PREFIXES: &[&str] = &["utm_", "pk_"];
+
+/// Hosts that are known to serve all of their content via HTTPS.
+const HTTPS_HOSTS: &[&str] = &[
+    "github.com",
+    "gitlab.com",
+    "bitbucket.org",
+    "codeberg.org",
+    "git.sr.ht",
+    "docs.rs",
+    "crates.io",
+    "lib.rs",
+];
+
+/// Normalizes a `homepage`, `repository` or `documentation` URL.
+///
+/// - tracking query parameters are removed
+/// - `http` is upgraded to `https` for hosts known to support it
+/// - GitHub URLs are canonicalized to `https://github.com/{owner}/{repo}`
+///
+/// URLs that can't be parsed or don't use the `http` or `https` scheme are
+/// returned unchanged, so that the validation of the metadata can reject them
+/// with the usual error messages. URLs that don't need any normalization are
+/// returned unchanged as well, instead of the serialization of the parsed URL
+/// (e.g. without an added trailing slash).
+pub fn normalize_url(input: &str) -> String {
+    let mut url = match Url::parse(input) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => return input.to_string(),
+    };
+
+    let mut changed = strip_tracking_params(&mut url);
+
+    if url.host_str() == Some("www.github.com") {
+        changed |= url.set_host(Some("github.com")).is_ok();
+    }
+
+    let host = url.host_str().unwrap_or_default();
+    if url.scheme() == "http" && HTTPS_HOSTS.contains(&host) {
+        changed |= url.set_scheme("https").is_ok();
+    }
+
+    if url.host_str() == Some("github.com") {
+        changed |= canonicalize_github_path(&mut url);
+    }
+
+    if changed {
+        url.to_string()
+    } else {
+        input.to_string()
+    }
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    TRACKING_PARAMS.contains(&name.as_str())
+        || TRACKING_PARAM_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// Returns `true` if any query parameters were removed.
+fn strip_tracking_params(url: &mut Url) -> bool {
+    let params = url.query_pairs().collect::<Vec<_>>();
+    if !params.iter().any(|(name, _)| is_tracking_param(name)) {
+        return false;
+    }
+
+    let params = params
+        .into_iter()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    true
+}
+
+/// Removes the `.git` suffix and trailing slashes from GitHub repository
+/// URLs, e.g. `https://github.com/rust-lang/crates.io.git/`.
+///
+/// Returns `true` if the path was changed.
+fn canonicalize_github_path(url: &mut Url) -> bool {
+    let segments = match url.path_segments() {
+        Some(segments) => segments.filter(|s| !s.is_empty()).collect::<Vec<_>>(),
+        None => return false,
+    };
+
+    let [owner, repo] = segments[..] else {
+        return false;
+    };
+
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+    let path = format!("/{owner}/{repo}");
+    if url.path() == path {
+        return false;
+    }
+
+    url.set_path(&path);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_url;
+
+    #[test]
+    fn strips_tracking_params() {
+        assert_eq!(
+            normalize_url("https://example.com/?utm_source=foo&utm_medium=bar"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            normalize_url("https://example.com/docs?page=2&fbclid=abc&UTM_CAMPAIGN=x"),
+            "https://example.com/docs?page=2"
+        );
+        assert_eq!(
+            normalize_url("https://example.com/docs?page=2#section"),
+            "https://example.com/docs?page=2#section"
+        );
+    }
+
+    #[test]
+    fn upgrades_known_hosts_to_https() {
+        assert_eq!(normalize_url("http://docs.rs/foo"), "https://docs.rs/foo");
+        assert_eq!(
+            normalize_url("http://example.com/foo"),
+            "http://example.com/foo"
+        );
+    }
+
+    #[test]
+    fn keeps_urls_without_changes() {
+        assert_eq!(normalize_url("http://foo.rs"), "http://foo.rs");
+        assert_eq!(
+            normalize_url("https://example.com/Foo%20Bar?a=b"),
+            "https://example.com/Foo%20Bar?a=b"
+        );
+        assert_eq!(
+            normalize_url("https://github.com/rust-lang/crates.io"),
+            "https://github.com/rust-lang/crates.io"
+        );
+    }
+
+    #[test]
+    fn canonicalizes_github_urls() {
+        assert_eq!(
+            normalize_url("http://www.github.com/rust-lang/crates.io.git"),
+            "https://github.com/rust-lang/crates.io"
+        );
+        assert_eq!(
+            normalize_url("https://github.com/rust-lang/crates.io/"),
+            "https://github.com/rust-lang/crates.io"
+        );
+        assert_eq!(
+            normalize_url("https://github.com/rust-lang/crates.io/tree/main/src"),
+            "https://github.com/rust-lang/crates.io/tree/main/src"
+        );
+    }
+
+    #[test]
+    fn keeps_invalid_urls() {
+        assert_eq!(normalize_url("not a url"), "not a url");
+        assert_eq!(normalize_url("ftp://example.com"), "ftp://example.com");
+        assert_eq!(normalize_url("github.com/foo/bar"), "github.com/foo/bar");
+    }
+}
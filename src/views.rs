@@ -1,12 +1,15 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use crates_io_tarball::LicenseInfo;
 use secrecy::ExposeSecret;
 use url::Url;
 
 use crate::github;
+use crate::models::repository_verification::CHALLENGE_FILE;
 use crate::models::{
     ApiToken, Category, Crate, CrateOwnerInvitation, CreatedApiToken, Dependency, DependencyKind,
-    Keyword, Owner, ReverseDependency, Team, TopVersions, User, Version, VersionDownload,
-    VersionOwnerAction,
+    Keyword, Owner, PublishableCrate, RepositoryCheck, RepositoryVerification, ReverseDependency,
+    SecurityYank, SupportWindow, Team, TopVersions, User, Version, VersionArchive, VersionDownload,
+    VersionOwnerAction, VersionReproducibility,
 };
 use crate::util::rfc3339;
 
@@ -209,6 +212,12 @@ pub struct EncodableCrate {
     pub badges: Option<Vec<()>>,
     #[serde(with = "rfc3339")]
     pub created_at: NaiveDateTime,
+    /// When the oldest existing version was published, if there are versions
+    #[serde(with = "rfc3339::option")]
+    pub first_published_at: Option<NaiveDateTime>,
+    /// When a version was last published, yanked or unyanked
+    #[serde(with = "rfc3339::option")]
+    pub last_activity_at: Option<NaiveDateTime>,
     // NOTE: Used by shields.io, altering `downloads` requires a PR with shields.io
     pub downloads: i64,
     pub recent_downloads: Option<i64>,
@@ -222,6 +231,10 @@ pub struct EncodableCrate {
     pub repository: Option<String>,
     pub links: EncodableCrateLinks,
     pub exact_match: bool,
+    /// The result of the latest check of the repository, only present on
+    /// the crate page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_status: Option<EncodableRepositoryStatus>,
 }
 
 impl EncodableCrate {
@@ -245,6 +258,8 @@ impl EncodableCrate {
             homepage,
             documentation,
             repository,
+            first_version_published_at,
+            last_activity_at,
             ..
         } = krate;
         let versions_link = match versions {
@@ -286,6 +301,8 @@ impl EncodableCrate {
             name: name.clone(),
             updated_at,
             created_at,
+            first_published_at: first_version_published_at,
+            last_activity_at,
             downloads,
             recent_downloads,
             versions,
@@ -308,6 +325,7 @@ impl EncodableCrate {
                 owner_user: Some(format!("/api/v1/crates/{name}/owner_user")),
                 reverse_dependencies: format!("/api/v1/crates/{name}/reverse_dependencies"),
             },
+            repository_status: None,
         }
     }
 
@@ -359,6 +377,15 @@ impl EncodableCrate {
     }
 }
 
+/// Returns `true` if the host of the documentation URL is known not to host
+/// documentation, in which case the URL is not displayed.
+pub fn is_blocked_documentation_url(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(domain_is_blocked))
+        .unwrap_or(false)
+}
+
 fn domain_is_blocked(domain: &str) -> bool {
     DOCUMENTATION_BLOCKLIST
         .iter()
@@ -490,6 +517,26 @@ pub struct OwnedCrate {
     pub id: i32,
     pub name: String,
     pub email_notifications: bool,
+    /// Whether the crate is popular, but the user is its only owner, and
+    /// should add another owner.
+    pub needs_backup_owner: bool,
+}
+
+/// A crate that the authenticated user can publish new versions of.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodablePublishableCrate {
+    pub id: i32,
+    pub name: String,
+    /// The owning team that grants the publish rights, or `None` for crates
+    /// that the user owns directly.
+    pub team: Option<String>,
+}
+
+impl From<PublishableCrate> for EncodablePublishableCrate {
+    fn from(krate: PublishableCrate) -> Self {
+        let PublishableCrate { id, name, team } = krate;
+        Self { id, name, team }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -607,6 +654,14 @@ pub struct EncodableVersion {
     pub audit_actions: Vec<EncodableAuditAction>,
     pub checksum: String,
     pub rust_version: Option<String>,
+    /// Whether the crate was packaged from a git working tree with
+    /// uncommitted changes, according to its `.cargo_vcs_info.json` file.
+    pub dirty_worktree: bool,
+    /// The git commit the crate was packaged from.
+    pub commit_sha: Option<String>,
+    /// Link to the source code of the version in its repository at
+    /// `commit_sha`.
+    pub source_url: Option<String>,
 }
 
 impl EncodableVersion {
@@ -628,6 +683,9 @@ impl EncodableVersion {
             crate_size,
             checksum,
             rust_version,
+            dirty_worktree,
+            commit_sha,
+            source_url,
             ..
         } = version;
 
@@ -653,6 +711,9 @@ impl EncodableVersion {
             crate_size,
             checksum,
             rust_version,
+            dirty_worktree,
+            commit_sha,
+            source_url,
             published_by: published_by.map(User::into),
             audit_actions: audit_actions
                 .into_iter()
@@ -673,18 +734,355 @@ pub struct EncodableVersionLinks {
     pub authors: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableRepositoryStatus {
+    /// `available`, `archived` or `not_found`
+    pub status: String,
+    pub redirected_to: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub checked_at: NaiveDateTime,
+}
+
+impl From<RepositoryCheck> for EncodableRepositoryStatus {
+    fn from(check: RepositoryCheck) -> Self {
+        Self {
+            status: check.status.as_str().to_string(),
+            redirected_to: check.redirected_to,
+            checked_at: check.checked_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableVersionReproducibility {
+    pub reproducible: bool,
+    pub git_tag: String,
+    #[serde(with = "rfc3339")]
+    pub checked_at: NaiveDateTime,
+}
+
+impl From<VersionReproducibility> for EncodableVersionReproducibility {
+    fn from(reproducibility: VersionReproducibility) -> Self {
+        Self {
+            reproducible: reproducibility.reproducible,
+            git_tag: reproducibility.git_tag,
+            checked_at: reproducibility.checked_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableVersionArchive {
+    /// `restoring` if a restore of the crate file was requested, `archived`
+    /// otherwise.
+    pub status: String,
+    #[serde(with = "rfc3339")]
+    pub archived_at: NaiveDateTime,
+    #[serde(with = "rfc3339::option")]
+    pub restore_requested_at: Option<NaiveDateTime>,
+}
+
+impl From<VersionArchive> for EncodableVersionArchive {
+    fn from(archive: VersionArchive) -> Self {
+        let status = match archive.restore_requested_at {
+            Some(_) => "restoring",
+            None => "archived",
+        };
+
+        Self {
+            status: status.to_string(),
+            archived_at: archive.archived_at,
+            restore_requested_at: archive.restore_requested_at,
+        }
+    }
+}
+
+/// The license and copyright files of a version, as stored next to its
+/// crate file and served by the `license-text` endpoint.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableLicenseTexts {
+    /// The `license` expression of the manifest.
+    pub license: Option<String>,
+    /// The file that the `license-file` field of the manifest references
+    /// comes first, followed by the other license files in the order of the
+    /// crate file.
+    pub files: Vec<EncodableLicenseText>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableLicenseText {
+    /// Path of the file relative to the package root, e.g. `LICENSE-MIT`.
+    pub path: String,
+    pub contents: String,
+}
+
+impl From<LicenseInfo> for EncodableLicenseTexts {
+    fn from(info: LicenseInfo) -> Self {
+        let mut files: Vec<EncodableLicenseText> = Vec::new();
+        for file in info.license_file.into_iter().chain(info.files) {
+            let path = file.path.to_string_lossy().into_owned();
+            if files.iter().all(|existing| existing.path != path) {
+                let contents = file.contents;
+                files.push(EncodableLicenseText { path, contents });
+            }
+        }
+
+        Self {
+            license: info.expression,
+            files,
+        }
+    }
+}
+
+/// A change of a published version, as listed by the change feed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableVersionChange {
+    /// Sequence number of the change, which increases in the order in which
+    /// the changes were committed, to be used as the `since` parameter of the
+    /// next request.
+    pub seq: i64,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub num: String,
+    pub action: String,
+    /// The yanked state of the version after this change.
+    pub yanked: bool,
+    #[serde(with = "rfc3339")]
+    pub time: NaiveDateTime,
+}
+
+/// A vulnerability record in the [OSV format](https://ossf.github.io/osv-schema/)
+/// for a version that was yanked for security reasons.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OsvRecord {
+    pub schema_version: String,
+    pub id: String,
+    pub modified: String,
+    pub published: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdrawn: Option<String>,
+    pub summary: String,
+    pub details: String,
+    pub affected: Vec<OsvAffected>,
+    pub references: Vec<OsvReference>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OsvAffected {
+    pub package: OsvPackage,
+    pub versions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OsvPackage {
+    pub ecosystem: String,
+    pub name: String,
+    pub purl: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OsvReference {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub url: String,
+}
+
+impl OsvRecord {
+    pub fn from(yank: SecurityYank, crate_name: String, num: String) -> Self {
+        // OSV requires UTC timestamps with a `Z` suffix.
+        fn timestamp(time: NaiveDateTime) -> String {
+            DateTime::<Utc>::from_utc(time, Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+        }
+
+        Self {
+            schema_version: "1.5.0".to_string(),
+            id: yank.osv_id(),
+            modified: timestamp(yank.modified_at),
+            published: timestamp(yank.published_at),
+            withdrawn: yank.withdrawn_at.map(timestamp),
+            summary: format!("{crate_name} {num} was yanked for security reasons"),
+            details: format!(
+                "Version {num} of the `{crate_name}` crate was yanked from crates.io by its \
+                 owners for security reasons. See the advisory for more details."
+            ),
+            affected: vec![OsvAffected {
+                package: OsvPackage {
+                    ecosystem: "crates.io".to_string(),
+                    purl: format!("pkg:cargo/{crate_name}"),
+                    name: crate_name,
+                },
+                versions: vec![num],
+            }],
+            references: vec![OsvReference {
+                kind: "ADVISORY".to_string(),
+                url: yank.advisory_url,
+            }],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableSupportWindow {
+    pub major_version: String,
+    pub status: String,
+    pub end_of_life_date: Option<NaiveDate>,
+    #[serde(with = "rfc3339")]
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<SupportWindow> for EncodableSupportWindow {
+    fn from(window: SupportWindow) -> Self {
+        Self {
+            major_version: window.major_version,
+            status: window.status.as_str().to_string(),
+            end_of_life_date: window.end_of_life_date,
+            updated_at: window.updated_at,
+        }
+    }
+}
+
+/// The repository verification of the current user, including the
+/// instructions how the challenge can be passed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableRepositoryVerification {
+    pub repository: String,
+    pub challenge: String,
+    /// File in the default branch of the repository that has to contain the
+    /// challenge.
+    pub challenge_file: String,
+    /// Tag that can be pushed to the repository instead of the challenge file.
+    pub challenge_tag: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339::option")]
+    pub verified_at: Option<NaiveDateTime>,
+}
+
+impl From<RepositoryVerification> for EncodableRepositoryVerification {
+    fn from(verification: RepositoryVerification) -> Self {
+        Self {
+            challenge_tag: verification.challenge_tag(),
+            challenge_file: CHALLENGE_FILE.to_string(),
+            repository: verification.repository,
+            challenge: verification.challenge,
+            created_at: verification.created_at,
+            verified_at: verification.verified_at,
+        }
+    }
+}
+
+/// A warning about a reverse dependency that depends on a release line that
+/// does not receive all fixes anymore.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableSupportWarning {
+    pub dependency_id: i32,
+    pub major_version: String,
+    pub status: String,
+    pub end_of_life_date: Option<NaiveDate>,
+    pub message: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GoodCrate {
     #[serde(rename = "crate")]
     pub krate: EncodableCrate,
     pub warnings: PublishWarnings,
+    /// The index entry that would have been written, only present for dry runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_entry: Option<crates_io_index::Crate>,
+    /// Where the files of a dry run were uploaded to, only present for dry runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<EncodablePublishDryRun>,
 }
 
+/// The files of a publish dry run, which are uploaded to a shadow namespace
+/// instead of their regular location and deleted once they expired.
 #[derive(Serialize, Deserialize, Debug)]
+pub struct EncodablePublishDryRun {
+    /// The URL of the uploaded crate file.
+    pub crate_file: String,
+    /// The URL of the rendered readme, if the crate has one.
+    pub readme: Option<String>,
+    /// Why the readme could not be rendered, in which case a placeholder was
+    /// uploaded instead.
+    pub readme_error: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PublishWarnings {
     pub invalid_categories: Vec<String>,
     pub invalid_badges: Vec<String>,
     pub other: Vec<String>,
+    /// All warnings of the publish, including the ones above. The other
+    /// fields are only kept for older versions of cargo, which only display
+    /// their contents.
+    #[serde(default)]
+    pub details: Vec<PublishWarning>,
+}
+
+impl PublishWarnings {
+    pub fn add(&mut self, kind: PublishWarningKind, message: String) {
+        self.other.push(message.clone());
+        self.details.push(PublishWarning { kind, message });
+    }
+
+    pub fn add_invalid_category(&mut self, category: String) {
+        let message = format!(
+            "`{category}` is not a valid category slug and was ignored, \
+             see https://crates.io/category_slugs for the list of valid slugs"
+        );
+        self.details.push(PublishWarning {
+            kind: PublishWarningKind::InvalidCategory,
+            message,
+        });
+        self.invalid_categories.push(category);
+    }
+}
+
+/// A problem with a published crate that did not prevent the publish.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PublishWarning {
+    pub kind: PublishWarningKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishWarningKind {
+    /// A category that does not exist was ignored.
+    InvalidCategory,
+    /// A keyword that only differs in case from another keyword was ignored.
+    DuplicateKeyword,
+    /// A dependency is hosted on a registry that is not known to crates.io.
+    UnknownRegistry,
+    /// The documentation URL points to a host that is known not to host
+    /// documentation, so it is not displayed.
+    BlockedDocumentationUrl,
+    /// A file in the tarball is unusually large.
+    LargeFile,
+    /// The crate uses a manifest field that crates.io no longer supports.
+    DeprecatedField,
+    /// The `Cargo.lock` file of the crate pins a dependency to a version that
+    /// has been yanked.
+    YankedDependency,
+    /// The `Cargo.lock` file of the crate could not be parsed.
+    InvalidLockfile,
+    /// The version is lower than an existing version of the same release
+    /// series.
+    LowerVersion,
+    /// The version is an unusually large jump from the highest existing
+    /// version.
+    VersionJump,
+    /// The crate was packaged from a git working tree with uncommitted
+    /// changes.
+    DirtyWorktree,
+    /// The same crate is declared as more than one dependency, with version
+    /// requirements that no single version satisfies.
+    ConflictingDependency,
+    /// The crate declares a dev-dependency on itself.
+    SelfDevDependency,
 }
 
 #[cfg(test)]
@@ -777,6 +1175,9 @@ mod tests {
             crate_size: Some(1234),
             checksum: String::new(),
             rust_version: None,
+            dirty_worktree: false,
+            commit_sha: None,
+            source_url: None,
             published_by: None,
             audit_actions: vec![EncodableAuditAction {
                 action: "publish".to_string(),
@@ -820,6 +1221,10 @@ mod tests {
                 .unwrap()
                 .and_hms_opt(14, 23, 12)
                 .unwrap(),
+            first_published_at: NaiveDate::from_ymd_opt(2017, 1, 6)
+                .unwrap()
+                .and_hms_opt(14, 23, 12),
+            last_activity_at: None,
             downloads: 0,
             recent_downloads: None,
             max_version: "".to_string(),
@@ -838,6 +1243,7 @@ mod tests {
                 reverse_dependencies: "".to_string(),
             },
             exact_match: false,
+            repository_status: None,
         };
         let json = serde_json::to_string(&crt).unwrap();
         assert_some!(json
@@ -846,6 +1252,10 @@ mod tests {
         assert_some!(json
             .as_str()
             .find(r#""created_at":"2017-01-06T14:23:12+00:00""#));
+        assert_some!(json
+            .as_str()
+            .find(r#""first_published_at":"2017-01-06T14:23:12+00:00""#));
+        assert_some!(json.as_str().find(r#""last_activity_at":null"#));
     }
 
     #[test]
@@ -32,6 +32,10 @@ pub struct EncodableCrateUpload {
     pub repository: Option<String>,
     #[serde(default)]
     pub links: Option<String>,
+    /// The `[badges]` section of the manifest, which is no longer displayed
+    /// on crates.io.
+    #[serde(default)]
+    pub badges: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(PartialEq, Eq, Hash, Serialize, Debug, Deref)]
@@ -40,7 +44,9 @@ pub struct EncodableCrateName(pub String);
 pub struct EncodableDependencyName(pub String);
 #[derive(Debug, Deref)]
 pub struct EncodableCrateVersion(pub semver::Version);
-#[derive(Debug, Deref)]
+/// The version requirement of a dependency. It's only parsed by the publish
+/// endpoint, so that errors can name the dependency.
+#[derive(Deserialize, Debug, Deref)]
 pub struct EncodableCrateVersionReq(pub String);
 #[derive(Serialize, Debug, Deref, Default)]
 pub struct EncodableKeywordList(pub Vec<EncodableKeyword>);
@@ -163,20 +169,6 @@ impl<'de> Deserialize<'de> for EncodableCrateVersion {
     }
 }
 
-impl<'de> Deserialize<'de> for EncodableCrateVersionReq {
-    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<EncodableCrateVersionReq, D::Error> {
-        let s = String::deserialize(d)?;
-        match semver::VersionReq::parse(&s) {
-            Ok(_) => Ok(EncodableCrateVersionReq(s)),
-            Err(..) => {
-                let value = de::Unexpected::Str(&s);
-                let expected = "a valid version req";
-                Err(de::Error::invalid_value(value, &expected))
-            }
-        }
-    }
-}
-
 impl<'de> Deserialize<'de> for EncodableKeywordList {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<EncodableKeywordList, D::Error> {
         let inner = <Vec<EncodableKeyword> as Deserialize<'de>>::deserialize(d)?;
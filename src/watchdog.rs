@@ -0,0 +1,227 @@
+//! Watch the resources of the server process to mitigate slow leaks and
+//! saturation between deploys.
+//!
+//! The watchdog periodically checks:
+//!
+//! * the growth of the resident memory since the server was started,
+//! * how long it takes until a no-op task on the blocking thread pool starts
+//!   running, which grows when the pool is saturated,
+//! * how much later than scheduled the watchdog itself is woken up, which
+//!   grows when the async event loop is stalled by blocking code.
+//!
+//! The values are exported as instance metrics. If a check exceeds its
+//! threshold for `WATCHDOG_RESTART_AFTER` consecutive times, [`run`] returns
+//! so that the server can shut down gracefully and be restarted by the
+//! process manager.
+
+use crate::config::WatchdogConfig;
+use crate::App;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The values observed by a single check of the watchdog.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    memory_rss: Option<u64>,
+    blocking_delay: Duration,
+    event_loop_stall: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    MemoryGrowth,
+    BlockingDelay,
+    EventLoopStall,
+}
+
+impl Check {
+    fn as_str(self) -> &'static str {
+        match self {
+            Check::MemoryGrowth => "memory_growth",
+            Check::BlockingDelay => "blocking_delay",
+            Check::EventLoopStall => "event_loop_stall",
+        }
+    }
+}
+
+impl Sample {
+    /// Returns the checks whose threshold is exceeded by this sample.
+    fn exceeded(&self, config: &WatchdogConfig, baseline_rss: Option<u64>) -> Vec<Check> {
+        let mut exceeded = Vec::new();
+
+        let memory_growth = self
+            .memory_rss
+            .zip(baseline_rss)
+            .map(|(rss, baseline)| rss.saturating_sub(baseline));
+        if let Some((growth, max)) = memory_growth.zip(config.max_memory_growth) {
+            if growth > max {
+                exceeded.push(Check::MemoryGrowth);
+            }
+        }
+
+        if let Some(max) = config.max_blocking_delay {
+            if self.blocking_delay > max {
+                exceeded.push(Check::BlockingDelay);
+            }
+        }
+
+        if let Some(max) = config.max_event_loop_stall {
+            if self.event_loop_stall > max {
+                exceeded.push(Check::EventLoopStall);
+            }
+        }
+
+        exceeded
+    }
+}
+
+/// Runs the watchdog on the current tokio runtime.
+///
+/// This function only returns if a restart of the server is required.
+pub async fn run(app: Arc<App>) {
+    let config = app.config.watchdog.clone();
+    let metrics = &app.instance_metrics;
+
+    let baseline_rss = memory_rss();
+    let mut consecutive = 0;
+
+    loop {
+        let sample = sample(config.interval).await;
+
+        if let Some(rss) = sample.memory_rss {
+            metrics.process_memory_rss_bytes.set(rss as i64);
+        }
+        metrics
+            .watchdog_blocking_delay
+            .observe(sample.blocking_delay.as_secs_f64());
+        metrics
+            .watchdog_event_loop_stall
+            .observe(sample.event_loop_stall.as_secs_f64());
+
+        let exceeded = sample.exceeded(&config, baseline_rss);
+        if exceeded.is_empty() {
+            consecutive = 0;
+            continue;
+        }
+
+        for check in &exceeded {
+            metrics
+                .watchdog_thresholds_exceeded_total
+                .with_label_values(&[check.as_str()])
+                .inc();
+        }
+
+        consecutive += 1;
+        warn!(
+            ?sample,
+            ?baseline_rss,
+            ?exceeded,
+            consecutive,
+            "Watchdog thresholds exceeded"
+        );
+
+        if config.restart_after.is_some_and(|max| consecutive >= max) {
+            error!(
+                ?exceeded,
+                consecutive, "Watchdog requests a restart of the server"
+            );
+            return;
+        }
+    }
+}
+
+async fn sample(interval: Duration) -> Sample {
+    let start = Instant::now();
+    tokio::time::sleep(interval).await;
+    let event_loop_stall = start.elapsed().saturating_sub(interval);
+
+    // If the blocking pool is saturated, the probe might not run for a long
+    // time, so the delay is capped at the check interval.
+    let start = Instant::now();
+    let probe = tokio::task::spawn_blocking(|| ());
+    let _ = tokio::time::timeout(interval, probe).await;
+    let blocking_delay = start.elapsed();
+
+    Sample {
+        memory_rss: memory_rss(),
+        blocking_delay,
+        event_loop_stall,
+    }
+}
+
+/// Returns the resident set size of the process in bytes, or `None` on
+/// platforms without `/proc`.
+fn memory_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim();
+
+    kilobytes
+        .parse::<u64>()
+        .ok()
+        .map(|kilobytes| kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn sample(memory_rss: u64, blocking_delay_ms: u64, event_loop_stall_ms: u64) -> Sample {
+        Sample {
+            memory_rss: Some(memory_rss),
+            blocking_delay: Duration::from_millis(blocking_delay_ms),
+            event_loop_stall: Duration::from_millis(event_loop_stall_ms),
+        }
+    }
+
+    #[test]
+    fn parse_vm_rss_from_proc_status() {
+        let status = "Name:\tserver\nVmPeak:\t  204800 kB\nVmRSS:\t  102400 kB\nThreads:\t8\n";
+        assert_some_eq!(parse_vm_rss(status), 100 * MB);
+        assert_none!(parse_vm_rss("Name:\tserver\n"));
+    }
+
+    #[test]
+    fn thresholds_are_optional() {
+        let config = WatchdogConfig::default();
+        let sample = sample(1000 * MB, 10_000, 10_000);
+        assert_eq!(sample.exceeded(&config, Some(MB)), vec![]);
+    }
+
+    #[test]
+    fn exceeded_thresholds() {
+        let config = WatchdogConfig {
+            max_memory_growth: Some(100 * MB),
+            max_blocking_delay: Some(Duration::from_millis(500)),
+            max_event_loop_stall: Some(Duration::from_millis(100)),
+            ..WatchdogConfig::default()
+        };
+
+        let healthy = sample(150 * MB, 10, 5);
+        assert_eq!(healthy.exceeded(&config, Some(100 * MB)), vec![]);
+
+        let leaking = sample(250 * MB, 10, 5);
+        assert_eq!(
+            leaking.exceeded(&config, Some(100 * MB)),
+            vec![Check::MemoryGrowth]
+        );
+        assert_eq!(leaking.exceeded(&config, None), vec![]);
+
+        let saturated = sample(150 * MB, 1000, 500);
+        assert_eq!(
+            saturated.exceeded(&config, Some(100 * MB)),
+            vec![Check::BlockingDelay, Check::EventLoopStall]
+        );
+    }
+}
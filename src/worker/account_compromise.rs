@@ -0,0 +1,44 @@
+//! Notify users that their account was put into compromise mode.
+//!
+//! The notification is only sent to verified email addresses, since the
+//! account settings may have been changed by an attacker.
+
+use crate::background_jobs::Environment;
+use crate::models::{AccountCompromise, User};
+use crate::schema::account_compromises;
+use crate::swirl::PerformError;
+use anyhow::anyhow;
+use diesel::prelude::*;
+
+#[instrument(skip_all, fields(compromise_id = %compromise_id))]
+pub fn perform_notify_account_compromise(
+    conn: &mut PgConnection,
+    env: &Environment,
+    compromise_id: i32,
+) -> Result<(), PerformError> {
+    let compromise: AccountCompromise =
+        account_compromises::table.find(compromise_id).first(conn)?;
+
+    let user = User::find(conn, compromise.user_id)?;
+
+    let Some(email) = user.verified_email(conn)? else {
+        warn!(
+            user_id = %user.id,
+            "Account without verified email address was put into compromise mode",
+        );
+        return Ok(());
+    };
+
+    env.emails()
+        .send_account_compromise_notification(
+            &email,
+            &user.gh_login,
+            compromise.revoked_tokens,
+            compromise.flagged_versions,
+        )
+        .map_err(|error| anyhow!("Failed to send email notification: {error}"))?;
+
+    info!(user_id = %user.id, "Sent account compromise notification");
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Move crate files of dead crates to the archive storage tier, and restore
+//! them once they are requested again.
+//!
+//! Only versions of crates whose versions are all yanked and that have not
+//! been downloaded for a long time are archived. The files are not deleted,
+//! so that the download endpoint can still serve them from the archive tier.
+//!
+//! A crate file is copied to the archive tier and recorded as archived
+//! first, and only deleted from its regular location by a later run of the
+//! job, once `DELETE_ORIGINAL_AFTER_MINUTES` have passed. Until then, the
+//! download endpoint can still redirect to the regular location: it caches
+//! the `version_id` of popular downloads without checking whether they are
+//! archived, and an unconditional redirect doesn't check it either.
+
+use crate::background_jobs::{Environment, Job};
+use crate::models::VersionArchive;
+use crate::storage::ReplicatedFile;
+use crate::swirl::PerformError;
+use anyhow::Context;
+use diesel::prelude::*;
+
+/// How long a crate file is kept at its regular location after it was
+/// copied to the archive tier. This has to be longer than the
+/// `version_id_cache_ttl` of the download endpoint (5 minutes by default).
+const DELETE_ORIGINAL_AFTER_MINUTES: i32 = 60;
+
+#[instrument(skip_all)]
+pub fn perform_archive_versions(
+    conn: &mut PgConnection,
+    env: &Environment,
+    idle_days: i32,
+    batch_size: i64,
+) -> Result<(), PerformError> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let pending = VersionArchive::pending_original_deletions(
+        DELETE_ORIGINAL_AFTER_MINUTES,
+        batch_size,
+        conn,
+    )?;
+
+    info!(
+        count = pending.len(),
+        "Deleting archived crate files from their regular location"
+    );
+
+    for (version_id, crate_name, vers) in pending {
+        conn.transaction::<_, PerformError, _>(|conn| {
+            // A restore moves the archived copy back, so the original must
+            // not be deleted once a restore was requested.
+            if VersionArchive::lock_for_original_deletion(version_id, conn)? {
+                match rt.block_on(env.storage.delete_crate_file(&crate_name, &vers)) {
+                    Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                    Err(error) => return Err(error.into()),
+                }
+                VersionArchive::record_original_deletion(version_id, conn)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    let candidates = VersionArchive::candidates(idle_days, batch_size, conn)?;
+
+    info!(
+        count = candidates.len(),
+        "Copying crate files to the archive storage tier"
+    );
+
+    for candidate in candidates {
+        let crate_name = &candidate.crate_name;
+        let vers = &candidate.num;
+
+        rt.block_on(env.storage.archive_crate_file(crate_name, vers))?;
+        VersionArchive::record(candidate.version_id, conn)?;
+
+        info!(%crate_name, %vers, "Archived crate file");
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(krate.name))]
+pub fn perform_restore_crate_file(
+    conn: &mut PgConnection,
+    env: &Environment,
+    version_id: i32,
+) -> Result<(), PerformError> {
+    use crate::schema::*;
+
+    let (crate_name, vers): (String, String) = versions::table
+        .find(version_id)
+        .inner_join(crates::table)
+        .select((crates::name, versions::num))
+        .first(conn)?;
+
+    tracing::Span::current().record("krate.name", tracing::field::display(&crate_name));
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    rt.block_on(env.storage.restore_crate_file(&crate_name, &vers))?;
+    VersionArchive::remove(version_id, conn)?;
+
+    let file = ReplicatedFile::crate_file(&crate_name, &vers);
+    Job::enqueue_replication(&env.storage, file, conn)?;
+
+    info!(%crate_name, %vers, "Restored crate file from the archive storage tier");
+
+    Ok(())
+}
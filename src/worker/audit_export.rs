@@ -0,0 +1,284 @@
+//! Ship audit events to an external audit sink, e.g. a SIEM.
+//!
+//! The sink is configured with the following environment variables:
+//!
+//! - `AUDIT_EXPORT_URL`: URL that the events are sent to. If not set, events
+//!   are not exported, and only pruned after the retention period.
+//! - `AUDIT_EXPORT_FORMAT`: `webhook` (default) to send each batch as a
+//!   `{"events": [...]}` JSON document, or `kafka-rest` to send the events as
+//!   records to the `/topics/<topic>` endpoint of a Kafka REST proxy.
+//! - `AUDIT_EXPORT_KAFKA_TOPIC`: Kafka topic of the events. Required for the
+//!   `kafka-rest` format.
+//! - `AUDIT_EXPORT_TOKEN`: Optional token that is sent as a bearer token in
+//!   the `Authorization` header.
+//!
+//! Events are sent in batches in the order in which they were recorded, and
+//! are marked as exported once the sink accepted them. If the sink rejects a
+//! batch, the remaining events stay queued for the next run, so that a slow or
+//! unavailable sink delays the export instead of losing events. Events are
+//! only dropped once they are older than the retention period.
+
+use crate::background_jobs::Environment;
+use crate::models::AuditEvent;
+use crate::schema::audit_events;
+use crate::swirl::PerformError;
+use crate::util::rfc3339;
+use anyhow::Context;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use http::header;
+use reqwest::blocking::Client;
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::Value;
+
+const CONTENT_TYPE_KAFKA_JSON: &str = "application/vnd.kafka.json.v2+json";
+
+#[derive(Clone, Debug)]
+pub struct AuditSink {
+    url: String,
+    format: AuditSinkFormat,
+    token: Option<SecretString>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AuditSinkFormat {
+    Webhook,
+    KafkaRest { topic: String },
+}
+
+impl AuditSink {
+    pub fn from_environment() -> Option<Self> {
+        let url = dotenvy::var("AUDIT_EXPORT_URL").ok()?;
+
+        let format = match dotenvy::var("AUDIT_EXPORT_FORMAT").as_deref() {
+            Err(_) | Ok("webhook") => AuditSinkFormat::Webhook,
+            Ok("kafka-rest") => AuditSinkFormat::KafkaRest {
+                topic: dotenvy::var("AUDIT_EXPORT_KAFKA_TOPIC")
+                    .expect("missing AUDIT_EXPORT_KAFKA_TOPIC"),
+            },
+            Ok(format) => panic!("invalid AUDIT_EXPORT_FORMAT: {format}"),
+        };
+
+        let token = dotenvy::var("AUDIT_EXPORT_TOKEN").ok().map(Into::into);
+
+        Some(Self { url, format, token })
+    }
+
+    /// Sends a batch of events to the sink.
+    #[instrument(skip_all, fields(count = events.len()))]
+    fn send(&self, client: &Client, events: &[ExportedEvent<'_>]) -> anyhow::Result<()> {
+        let url = self.request_url();
+        let body = self.request_body(events);
+
+        let mut request = client.post(&url);
+        if matches!(self.format, AuditSinkFormat::KafkaRest { .. }) {
+            request = request.header(header::CONTENT_TYPE, CONTENT_TYPE_KAFKA_JSON);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token.expose_secret());
+        }
+
+        // `json()` keeps the content type that was set above
+        request
+            .json(&body)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("failed to send audit events to {url}"))?;
+
+        Ok(())
+    }
+
+    fn request_url(&self) -> String {
+        match &self.format {
+            AuditSinkFormat::Webhook => self.url.clone(),
+            AuditSinkFormat::KafkaRest { topic } => {
+                format!("{}/topics/{topic}", self.url.trim_end_matches('/'))
+            }
+        }
+    }
+
+    fn request_body(&self, events: &[ExportedEvent<'_>]) -> Value {
+        match self.format {
+            AuditSinkFormat::Webhook => json!({ "events": events }),
+            AuditSinkFormat::KafkaRest { .. } => {
+                let records = events
+                    .iter()
+                    .map(|event| json!({ "key": event.id.to_string(), "value": event }))
+                    .collect::<Vec<_>>();
+
+                json!({ "records": records })
+            }
+        }
+    }
+}
+
+/// The representation of an audit event that is sent to the sink.
+#[derive(Debug, Serialize)]
+struct ExportedEvent<'a> {
+    id: i64,
+    kind: &'a str,
+    user_id: Option<i32>,
+    user_login: Option<&'a str>,
+    api_token_id: Option<i32>,
+    ip_address: Option<&'a str>,
+    details: &'a Value,
+    #[serde(with = "rfc3339")]
+    created_at: NaiveDateTime,
+}
+
+impl<'a> ExportedEvent<'a> {
+    fn new(event: &'a AuditEvent, user_login: Option<&'a str>) -> Self {
+        Self {
+            id: event.id,
+            kind: &event.kind,
+            user_id: event.user_id,
+            user_login,
+            api_token_id: event.api_token_id,
+            ip_address: event.ip_address.as_deref(),
+            details: &event.details,
+            created_at: event.created_at,
+        }
+    }
+}
+
+#[instrument(skip_all)]
+pub fn perform_export_audit_events(
+    conn: &mut PgConnection,
+    env: &Environment,
+    batch_size: i64,
+    max_batches: i64,
+    retention_days: i32,
+) -> Result<(), PerformError> {
+    if let Some(sink) = env.audit_sink() {
+        export_events(conn, env.http_client(), sink, batch_size, max_batches)?;
+    }
+
+    let cutoff = Utc::now().naive_utc() - Duration::days(retention_days.into());
+    let pruned: Vec<Option<NaiveDateTime>> =
+        diesel::delete(audit_events::table.filter(audit_events::created_at.lt(cutoff)))
+            .returning(audit_events::exported_at)
+            .get_results(conn)?;
+
+    let not_exported = pruned.iter().filter(|exported_at| exported_at.is_none());
+    let not_exported = not_exported.count();
+    if not_exported > 0 && env.audit_sink().is_some() {
+        warn!(
+            not_exported,
+            "Dropped audit events that were not exported within the retention period"
+        );
+    }
+
+    info!(pruned = pruned.len(), "Pruned old audit events");
+
+    Ok(())
+}
+
+fn export_events(
+    conn: &mut PgConnection,
+    client: &Client,
+    sink: &AuditSink,
+    batch_size: i64,
+    max_batches: i64,
+) -> Result<(), PerformError> {
+    let mut exported = 0;
+
+    for _ in 0..max_batches {
+        let events = AuditEvent::unexported(batch_size, conn)?;
+        if events.is_empty() {
+            break;
+        }
+
+        let batch = events
+            .iter()
+            .map(|(event, user_login)| ExportedEvent::new(event, user_login.as_deref()))
+            .collect::<Vec<_>>();
+
+        if let Err(error) = sink.send(client, &batch) {
+            // Keep the progress of the previous batches, and try again with
+            // the remaining events on the next run.
+            if exported == 0 {
+                return Err(error.into());
+            }
+
+            warn!(exported, ?error, "Stopped exporting audit events");
+            return Ok(());
+        }
+
+        let ids = batch.iter().map(|event| event.id).collect::<Vec<_>>();
+        AuditEvent::mark_exported(&ids, conn)?;
+        exported += ids.len();
+
+        if (events.len() as i64) < batch_size {
+            break;
+        }
+    }
+
+    info!(exported, "Exported audit events");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> AuditEvent {
+        AuditEvent {
+            id: 42,
+            kind: "login".into(),
+            user_id: Some(1),
+            api_token_id: None,
+            ip_address: Some("127.0.0.1".into()),
+            details: json!({}),
+            created_at: NaiveDateTime::from_timestamp_opt(1_600_000_000, 0).unwrap(),
+            exported_at: None,
+        }
+    }
+
+    fn sink(format: AuditSinkFormat) -> AuditSink {
+        AuditSink {
+            url: "https://audit.example.com/".into(),
+            format,
+            token: None,
+        }
+    }
+
+    #[test]
+    fn webhook_request() {
+        let sink = sink(AuditSinkFormat::Webhook);
+        let event = event();
+        let events = [ExportedEvent::new(&event, Some("foo"))];
+
+        assert_eq!(sink.request_url(), "https://audit.example.com/");
+        assert_eq!(
+            sink.request_body(&events),
+            json!({
+                "events": [{
+                    "id": 42,
+                    "kind": "login",
+                    "user_id": 1,
+                    "user_login": "foo",
+                    "api_token_id": null,
+                    "ip_address": "127.0.0.1",
+                    "details": {},
+                    "created_at": "2020-09-13T12:26:40+00:00",
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn kafka_rest_request() {
+        let topic = "audit".to_string();
+        let sink = sink(AuditSinkFormat::KafkaRest { topic });
+        let event = event();
+        let events = [ExportedEvent::new(&event, None)];
+
+        assert_eq!(sink.request_url(), "https://audit.example.com/topics/audit");
+
+        let body = sink.request_body(&events);
+        assert_eq!(body["records"][0]["key"], "42");
+        assert_eq!(body["records"][0]["value"]["kind"], "login");
+        assert_eq!(body["records"][0]["value"]["user_login"], Value::Null);
+    }
+}
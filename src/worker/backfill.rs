@@ -0,0 +1,101 @@
+//! Backfill large tables in small batches, instead of in a single statement
+//! within a migration.
+//!
+//! Every batch is committed on its own, so that the row locks of a batch are
+//! released before the next batch starts, and a failed job only loses the
+//! progress of a single batch. The `filter` of the backfill must exclude the
+//! rows that were already backfilled, otherwise the same rows are updated
+//! over and over again.
+//!
+//! Rows that are locked by other transactions are skipped by a batch, so a
+//! short or even empty batch doesn't mean that the backfill is done. It only
+//! finishes once no rows match the `filter` anymore.
+
+use crate::background_jobs::{BatchedBackfillJob, Job};
+use crate::swirl::PerformError;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use std::thread;
+use std::time::Duration;
+
+/// Pause between two batches, to give replicas and autovacuum time to keep up.
+const PAUSE_BETWEEN_BATCHES: Duration = Duration::from_millis(100);
+
+#[instrument(skip_all, fields(table = %job.table))]
+pub fn perform_batched_backfill(
+    conn: &mut PgConnection,
+    job: &BatchedBackfillJob,
+) -> Result<(), PerformError> {
+    if !is_identifier(&job.table) {
+        return Err(format!("Invalid table name: {}", job.table).into());
+    }
+
+    let table = &job.table;
+    let query = format!(
+        "UPDATE {table} SET {set} WHERE ctid = ANY(ARRAY(\
+            SELECT ctid FROM {table} WHERE {filter} LIMIT {batch_size} FOR UPDATE SKIP LOCKED\
+        ))",
+        set = job.set,
+        filter = job.filter,
+        batch_size = job.batch_size,
+    );
+
+    let remaining = format!(
+        "EXISTS (SELECT 1 FROM {table} WHERE {filter})",
+        filter = job.filter,
+    );
+
+    let mut updated = 0;
+    for _ in 0..job.max_batches {
+        let rows = diesel::sql_query(&query).execute(conn)?;
+        updated += rows;
+
+        let has_remaining = (rows as i64) == job.batch_size
+            || diesel::select(sql::<Bool>(&remaining)).get_result(conn)?;
+        if !has_remaining {
+            info!(updated, "Finished backfill");
+            return Ok(());
+        }
+
+        thread::sleep(PAUSE_BETWEEN_BATCHES);
+    }
+
+    // Continue in a new job, so that other jobs are not starved by a long
+    // running backfill.
+    info!(updated, "Continuing backfill in a new job");
+    Job::batched_backfill(
+        job.table.clone(),
+        job.set.clone(),
+        job.filter.clone(),
+        job.batch_size,
+        job.max_batches,
+    )
+    .enqueue(conn)?;
+
+    Ok(())
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert!(is_identifier("versions"));
+        assert!(is_identifier("version_downloads"));
+        assert!(is_identifier("_foo2"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("2foo"));
+        assert!(!is_identifier("versions; DROP TABLE crates"));
+        assert!(!is_identifier("public.versions"));
+    }
+}
@@ -0,0 +1,112 @@
+//! Verify that the crate files in the storage still match the checksums that
+//! were recorded in the database when they were published.
+//!
+//! The CDN serves whatever is in the object store, so this is the only way to
+//! notice crate files that were silently corrupted or tampered with. Cargo
+//! would reject such files, but only after users ran into the error.
+//!
+//! Versions with mismatching crate files are marked as unavailable, so that
+//! their downloads fail with an explanation, and have to be investigated
+//! manually. The mark is removed again once the crate file matches.
+//!
+//! The versions are verified in batches in the order of their IDs. Every job
+//! verifies a single batch and enqueues the next one with the ID of the last
+//! verified version, within the same transaction as the marks of the batch.
+//! A failed or interrupted job therefore resumes at its own batch instead of
+//! starting over with the first version.
+
+use crate::background_jobs::{Environment, Job, VerifyChecksumsJob};
+use crate::models::UnavailableVersion;
+use crate::swirl::PerformError;
+use anyhow::Context;
+use diesel::dsl::{exists, not};
+use diesel::prelude::*;
+
+/// The reason that versions with mismatching crate files are marked as
+/// unavailable with.
+const CHECKSUM_MISMATCH: &str = "checksum_mismatch";
+
+#[instrument(
+    skip_all,
+    fields(krate.name = job.crate_name.as_deref(), after_version_id = job.after_version_id)
+)]
+pub fn perform_verify_checksums(
+    conn: &mut PgConnection,
+    env: &Environment,
+    job: &VerifyChecksumsJob,
+) -> Result<(), PerformError> {
+    use crate::schema::*;
+
+    // Archived crate files are not stored at their regular location anymore
+    let mut query = versions::table
+        .inner_join(crates::table)
+        .left_join(unavailable_versions::table)
+        .filter(versions::id.gt(job.after_version_id))
+        .filter(not(exists(
+            version_archives::table.filter(version_archives::version_id.eq(versions::id)),
+        )))
+        .select((
+            versions::id,
+            crates::name,
+            versions::num,
+            versions::checksum,
+            unavailable_versions::reason.nullable(),
+        ))
+        .order(versions::id)
+        .limit(job.batch_size)
+        .into_boxed();
+
+    if let Some(crate_name) = &job.crate_name {
+        query = query.filter(crates::name.eq(crate_name));
+    }
+
+    let batch: Vec<(i32, String, String, String, Option<String>)> = query.load(conn)?;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let mut mismatches = 0;
+    let mut failed = 0;
+
+    for (version_id, name, vers, expected, unavailable_reason) in &batch {
+        // A single unreadable file shouldn't abort the verification of all
+        // other crate files.
+        let actual = match rt.block_on(env.storage.crate_file_checksum(name, vers)) {
+            Ok(actual) => actual,
+            Err(error) => {
+                warn!(%name, %vers, %error, "Failed to calculate checksum of crate file");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let is_marked = unavailable_reason.as_deref() == Some(CHECKSUM_MISMATCH);
+        if actual != *expected {
+            error!(%name, %vers, %expected, %actual, "Checksum mismatch of crate file");
+            mismatches += 1;
+
+            if !is_marked {
+                UnavailableVersion::mark(*version_id, CHECKSUM_MISMATCH, conn)?;
+            }
+        } else if is_marked {
+            info!(%name, %vers, "Crate file matches its checksum again");
+            UnavailableVersion::remove(*version_id, conn)?;
+        }
+    }
+
+    let checked = batch.len() - failed;
+    info!(checked, mismatches, failed, "Verified batch of crate files");
+
+    if batch.len() as i64 == job.batch_size {
+        if let Some((last_version_id, ..)) = batch.last() {
+            Job::verify_checksums(job.crate_name.clone(), *last_version_id, job.batch_size)
+                .enqueue(conn)?;
+        }
+    } else {
+        info!("Verified all crate files");
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use crate::models::PublishDiagnostics;
+use crate::schema::download_redirect_samples;
 use crate::swirl::PerformError;
+use diesel::dsl::{now, IntervalDsl};
 /// Run daily database maintenance tasks
 ///
 /// By default PostgreSQL will run an auto-vacuum when 20% of the tuples in a table are dead.
@@ -8,11 +11,28 @@ use crate::swirl::PerformError;
 /// We only need to keep 90 days of entries in `version_downloads`. Once we have a mechanism to
 /// archive daily download counts and drop historical data, we can drop this task and rely on
 /// auto-vacuum again.
-use diesel::{sql_query, PgConnection, RunQueryDsl};
+use diesel::{prelude::*, sql_query};
+
+/// Number of days after which sampled download redirects are deleted.
+const DOWNLOAD_SAMPLES_RETENTION_DAYS: i32 = 30;
 
 pub(crate) fn perform_daily_db_maintenance(conn: &mut PgConnection) -> Result<(), PerformError> {
+    let deleted = prune_download_redirect_samples(conn)?;
+    info!("Deleted {deleted} expired download redirect samples");
+
+    let deleted = PublishDiagnostics::prune(conn)?;
+    info!("Deleted {deleted} expired publish diagnostics");
+
     info!("Running VACUUM on version_downloads table");
     sql_query("VACUUM version_downloads;").execute(conn)?;
     info!("Finished running VACUUM on version_downloads table");
     Ok(())
 }
+
+fn prune_download_redirect_samples(conn: &mut PgConnection) -> QueryResult<usize> {
+    let cutoff = now - DOWNLOAD_SAMPLES_RETENTION_DAYS.days();
+    let expired =
+        download_redirect_samples::table.filter(download_redirect_samples::sampled_at.lt(cutoff));
+
+    diesel::delete(expired).execute(conn)
+}
@@ -0,0 +1,180 @@
+//! Detect crates that were downloaded unusually often, e.g. because their
+//! download counts were inflated artificially.
+//!
+//! The downloads of each crate on the previous day are compared with the
+//! average daily downloads of the crate in the days before. Anomalies are
+//! recorded for review, and their downloads above the baseline can optionally
+//! be excluded from the public download counts of the crate.
+//!
+//! Excluded downloads are removed from the daily `version_downloads` rows of
+//! the crate too, spread over its versions in proportion to their downloads
+//! on that day, so that the version counts, the daily counts and the crate
+//! counts keep adding up.
+
+use crate::models::{DownloadAnomaly, DownloadBaseline};
+use crate::schema::{crates, metadata, version_downloads, versions};
+use crate::swirl::PerformError;
+use chrono::{Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+
+#[instrument(skip_all)]
+pub fn perform_detect_download_anomalies(
+    conn: &mut PgConnection,
+    baseline_days: i32,
+    min_downloads: i64,
+    threshold: f64,
+    exclude: bool,
+) -> Result<(), PerformError> {
+    // The downloads of the current day are still being counted
+    let date = Utc::now().date_naive() - Duration::days(1);
+
+    let anomalies = DownloadBaseline::for_date(date, baseline_days, min_downloads, conn)?
+        .into_iter()
+        .filter(|baseline| baseline.score() >= threshold)
+        .collect::<Vec<_>>();
+
+    info!(%date, count = anomalies.len(), "Recording download anomalies");
+
+    for anomaly in anomalies {
+        let excluded_downloads = if exclude {
+            (anomaly.downloads - anomaly.baseline_mean.round() as i64).max(0)
+        } else {
+            0
+        };
+
+        conn.transaction::<_, PerformError, _>(|conn| {
+            // The downloads of anomalies that were recorded before have
+            // already been excluded.
+            if !DownloadAnomaly::record(&anomaly, date, excluded_downloads, conn)? {
+                return Ok(());
+            }
+
+            if excluded_downloads > 0 {
+                exclude_downloads(anomaly.crate_id, date, excluded_downloads, conn)?;
+            }
+
+            info!(
+                crate_id = anomaly.crate_id,
+                downloads = anomaly.downloads,
+                baseline = anomaly.baseline_mean,
+                excluded_downloads,
+                "Recorded download anomaly"
+            );
+
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Removes `excluded` downloads of a crate on `date` from its daily
+/// `version_downloads` rows, and the part of them that was already counted
+/// from the totals of its versions and of the crate.
+///
+/// Downloads that were not counted yet are simply never counted by the
+/// `update_downloads` job.
+fn exclude_downloads(
+    crate_id: i32,
+    date: NaiveDate,
+    excluded: i64,
+    conn: &mut PgConnection,
+) -> Result<(), PerformError> {
+    let rows: Vec<(i32, i32, i32)> = version_downloads::table
+        .inner_join(versions::table)
+        .filter(versions::crate_id.eq(crate_id))
+        .filter(version_downloads::date.eq(date))
+        .select((
+            version_downloads::version_id,
+            version_downloads::downloads,
+            version_downloads::counted,
+        ))
+        .order(version_downloads::version_id)
+        .for_update()
+        .load(conn)?;
+
+    let downloads = rows.iter().map(|row| i64::from(row.1)).collect::<Vec<_>>();
+    let shares = distribute(excluded, &downloads);
+
+    let mut uncounted = 0;
+    for ((version_id, downloads, counted), share) in rows.into_iter().zip(shares) {
+        if share == 0 {
+            continue;
+        }
+
+        let downloads = downloads - i32::try_from(share)?;
+        let new_counted = counted.min(downloads);
+
+        diesel::update(version_downloads::table.find((version_id, date)))
+            .set((
+                version_downloads::downloads.eq(downloads),
+                version_downloads::counted.eq(new_counted),
+            ))
+            .execute(conn)?;
+
+        let version_uncounted = counted - new_counted;
+        if version_uncounted > 0 {
+            diesel::update(versions::table.find(version_id))
+                .set(versions::downloads.eq(versions::downloads - version_uncounted))
+                .execute(conn)?;
+
+            uncounted += version_uncounted;
+        }
+    }
+
+    if uncounted > 0 {
+        diesel::update(crates::table.find(crate_id))
+            .set(crates::downloads.eq(crates::downloads - uncounted))
+            .execute(conn)?;
+
+        diesel::update(metadata::table)
+            .set(metadata::total_downloads.eq(metadata::total_downloads - i64::from(uncounted)))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Splits `excluded` over rows with the given downloads in proportion to
+/// them, without taking more from a row than it has.
+fn distribute(excluded: i64, downloads: &[i64]) -> Vec<i64> {
+    let total = downloads.iter().sum::<i64>();
+    let excluded = excluded.min(total);
+    if total == 0 {
+        return vec![0; downloads.len()];
+    }
+
+    let mut shares = downloads
+        .iter()
+        .map(|downloads| (i128::from(excluded) * i128::from(*downloads) / i128::from(total)) as i64)
+        .collect::<Vec<_>>();
+
+    // Hand out what rounding down left over, one download per row
+    let mut remainder = excluded - shares.iter().sum::<i64>();
+    for (share, downloads) in shares.iter_mut().zip(downloads) {
+        if remainder == 0 {
+            break;
+        }
+        if *share < *downloads {
+            *share += 1;
+            remainder -= 1;
+        }
+    }
+
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribute() {
+        assert_eq!(distribute(0, &[]), Vec::<i64>::new());
+        assert_eq!(distribute(10, &[0, 0]), vec![0, 0]);
+        assert_eq!(distribute(99_000, &[100_000]), vec![99_000]);
+        assert_eq!(distribute(50, &[75, 25]), vec![38, 12]);
+        assert_eq!(distribute(10, &[1, 1, 1]), vec![1, 1, 1]);
+        assert_eq!(distribute(2, &[1, 1, 1]), vec![1, 1, 0]);
+    }
+}
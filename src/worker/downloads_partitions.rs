@@ -0,0 +1,130 @@
+//! Create and rotate the monthly partitions of the `version_downloads` table.
+//!
+//! Partitions are created a few months ahead of time. Downloads for a date
+//! without a monthly partition end up in the default partition, and are moved
+//! to the monthly partition once it is created. Partitions whose downloads are
+//! older than the retention period are exported to the storage as a gzip
+//! compressed CSV file, and then detached and dropped, which is much cheaper
+//! than deleting the rows and vacuuming the table afterwards.
+
+use crate::background_jobs::Environment;
+use crate::models::downloads_partition::first_of_month;
+use crate::models::DownloadsPartition;
+use crate::swirl::PerformError;
+use anyhow::Context;
+use chrono::{Months, Utc};
+use diesel::PgConnection;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{BufWriter, Write};
+
+/// The download counts of the last 90 days are shown on the website, and
+/// summed up for the `recent_crate_downloads` view.
+const MIN_RETENTION_MONTHS: u32 = 4;
+
+#[instrument(skip_all)]
+pub fn perform_manage_downloads_partitions(
+    conn: &mut PgConnection,
+    env: &Environment,
+    months_ahead: u32,
+    retention_months: u32,
+    dry_run: bool,
+) -> Result<(), PerformError> {
+    if retention_months < MIN_RETENTION_MONTHS {
+        let message =
+            format!("The retention period must be at least {MIN_RETENTION_MONTHS} months");
+        return Err(message.into());
+    }
+
+    let this_month = first_of_month(Utc::now().date_naive());
+    let partitions = DownloadsPartition::all(conn)?;
+
+    let mut created = 0;
+    let covered_until = partitions.iter().map(|partition| partition.to).max();
+    for months in 0..=months_ahead {
+        let month = this_month + Months::new(months);
+        if covered_until.map_or(false, |to| month < to) {
+            continue;
+        }
+
+        if dry_run {
+            info!(%month, "Would create partition");
+        } else {
+            let partition = DownloadsPartition::create(month, conn)?;
+            info!(name = %partition.name, "Created partition");
+        }
+        created += 1;
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let mut archived = 0;
+    let cutoff = this_month - Months::new(retention_months);
+    for partition in partitions.iter().filter(|partition| partition.to <= cutoff) {
+        if partition.has_unprocessed_downloads(conn)? {
+            warn!(name = %partition.name, "Skipping partition with unprocessed downloads");
+            continue;
+        }
+
+        if dry_run {
+            info!(name = %partition.name, "Would archive partition");
+            archived += 1;
+            continue;
+        }
+
+        let rows = archive_partition(conn, env, &rt, partition)?;
+        partition.detach_and_drop(conn)?;
+        info!(name = %partition.name, rows, "Archived and detached partition");
+        archived += 1;
+    }
+
+    info!(
+        created,
+        archived, dry_run, "Managed version_downloads partitions"
+    );
+
+    Ok(())
+}
+
+/// Exports the downloads of a partition to a CSV file, and uploads it to the
+/// storage. Returns the number of exported rows.
+fn archive_partition(
+    conn: &mut PgConnection,
+    env: &Environment,
+    rt: &tokio::runtime::Runtime,
+    partition: &DownloadsPartition,
+) -> Result<usize, PerformError> {
+    let file = tempfile::NamedTempFile::new()?;
+    let mut writer = GzEncoder::new(BufWriter::new(file.as_file()), Compression::default());
+    writeln!(writer, "version_id,downloads,counted,date,processed")?;
+
+    let mut rows = 0;
+    let mut date = partition.first_date(conn)?;
+    while let Some(day) = date.filter(|day| *day < partition.to) {
+        for download in partition.downloads_on(day, conn)? {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                download.version_id,
+                download.downloads,
+                download.counted,
+                download.date,
+                download.processed
+            )?;
+            rows += 1;
+        }
+        date = day.succ_opt();
+    }
+
+    writer.finish()?.flush()?;
+
+    let future = env
+        .storage
+        .upload_downloads_archive(&partition.name, file.path());
+    rt.block_on(future)?;
+
+    Ok(rows)
+}
@@ -37,7 +37,7 @@ pub fn perform_dump_db(
     info!("Database dump tarball uploaded");
 
     info!("Invalidating CDN caches");
-    invalidate_caches(env, &target_name);
+    invalidate_caches(env, &storage.db_dump_cdn_path(&target_name));
 
     Ok(())
 }
@@ -253,15 +253,15 @@ impl Drop for DumpTarball {
     }
 }
 
-fn invalidate_caches(env: &Environment, target_name: &str) {
+fn invalidate_caches(env: &Environment, path: &str) {
     if let Some(cloudfront) = env.cloudfront() {
-        if let Err(error) = cloudfront.invalidate(env.http_client(), target_name) {
+        if let Err(error) = cloudfront.invalidate(env.http_client(), path) {
             warn!("failed to invalidate CloudFront cache: {}", error);
         }
     }
 
     if let Some(fastly) = env.fastly() {
-        if let Err(error) = fastly.invalidate(env.http_client(), target_name) {
+        if let Err(error) = fastly.invalidate(env.http_client(), path) {
             warn!("failed to invalidate Fastly cache: {}", error);
         }
     }
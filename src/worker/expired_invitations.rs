@@ -0,0 +1,74 @@
+//! Notify invited users and their inviters about crate ownership invitations
+//! that expired without being accepted.
+//!
+//! Expired invitations are kept in the database, so that the crate owners can
+//! re-send them. Each invitation is only notified about once, unless it is
+//! re-sent and expires again.
+
+use crate::background_jobs::Environment;
+use crate::models::{CrateOwnerInvitation, User};
+use crate::schema::{crate_owner_invitations, crates};
+use crate::swirl::PerformError;
+use chrono::{Duration, Utc};
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+#[instrument(skip_all)]
+pub fn perform_expire_ownership_invitations(
+    conn: &mut PgConnection,
+    env: &Environment,
+    expiration_days: u64,
+) -> Result<(), PerformError> {
+    let cutoff = (Utc::now() - Duration::days(expiration_days as i64)).naive_utc();
+
+    let invitations: Vec<(CrateOwnerInvitation, String)> = crate_owner_invitations::table
+        .inner_join(crates::table)
+        .filter(crate_owner_invitations::created_at.le(cutoff))
+        .filter(crate_owner_invitations::expiry_notified_at.is_null())
+        .select((crate_owner_invitations::all_columns, crates::name))
+        .load(conn)?;
+
+    info!(
+        count = invitations.len(),
+        "Notifying users about expired ownership invitations"
+    );
+
+    for (invitation, crate_name) in invitations {
+        let invitee = User::find(conn, invitation.invited_user_id)?;
+        let inviter = User::find(conn, invitation.invited_by_user_id)?;
+
+        // Failing to send one of the emails should not prevent the other
+        // notifications from being sent, so errors are only logged here.
+        if let Some(email) = invitee.verified_email(conn)? {
+            let result =
+                env.emails()
+                    .send_owner_invite_expired(&email, &inviter.gh_login, &crate_name);
+            if let Err(error) = result {
+                warn!(
+                    %crate_name, user_id = %invitee.id, ?error,
+                    "Failed to send email notification",
+                );
+            }
+        }
+
+        if let Some(email) = inviter.verified_email(conn)? {
+            let result = env.emails().send_owner_invite_expired_to_inviter(
+                &email,
+                &invitee.gh_login,
+                &crate_name,
+            );
+            if let Err(error) = result {
+                warn!(
+                    %crate_name, user_id = %inviter.id, ?error,
+                    "Failed to send email notification",
+                );
+            }
+        }
+
+        diesel::update(&invitation)
+            .set(crate_owner_invitations::expiry_notified_at.eq(now.nullable()))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Record how many dependent crates request each feature of a crate, so that
+//! maintainers can see how a change of their features would affect users.
+//!
+//! A snapshot is recorded for every crate and feature each day, so snapshots
+//! that are older than the retention period are pruned by the same job. The
+//! API only serves the last year of snapshots anyway.
+
+use crate::models::FeatureUsage;
+use crate::swirl::PerformError;
+use chrono::{Duration, Utc};
+use diesel::PgConnection;
+
+#[instrument(skip_all)]
+pub fn perform_aggregate_feature_usage(
+    conn: &mut PgConnection,
+    retention_days: i32,
+) -> Result<(), PerformError> {
+    let date = Utc::now().date_naive();
+
+    info!(%date, "Aggregating feature usage");
+    let count = FeatureUsage::aggregate(date, conn)?;
+    info!(%date, count, "Recorded feature usage");
+
+    let cutoff = date - Duration::days(retention_days.into());
+    let pruned = FeatureUsage::prune(cutoff, conn)?;
+    info!(%cutoff, pruned, "Pruned old feature usage snapshots");
+
+    Ok(())
+}
@@ -1,9 +1,11 @@
-use crate::background_jobs::{Environment, NormalizeIndexJob};
+use crate::background_jobs::{Environment, Job, NormalizeIndexJob};
 use crate::models;
+use crate::models::{IndexKind, IndexSyncTime};
+use crate::storage::ReplicatedFile;
 use crate::swirl::PerformError;
 use anyhow::Context;
 use chrono::Utc;
-use crates_io_index::{Crate, Repository};
+use crates_io_index::Crate;
 use diesel::prelude::*;
 use sentry::Level;
 use std::fs::{self, File};
@@ -19,6 +21,7 @@ pub fn sync_to_git_index(
 ) -> Result<(), PerformError> {
     info!("Syncing to git index");
 
+    let synced_at = Utc::now().naive_utc();
     let new = get_index_data(krate, conn).context("Failed to get index data")?;
 
     let repo = env.lock_index()?;
@@ -50,6 +53,8 @@ pub fn sync_to_git_index(
         _ => debug!("Skipping sync because index is up-to-date"),
     }
 
+    IndexSyncTime::record(krate, IndexKind::Git, synced_at, conn)?;
+
     Ok(())
 }
 
@@ -62,6 +67,7 @@ pub fn sync_to_sparse_index(
 ) -> Result<(), PerformError> {
     info!("Syncing to sparse index");
 
+    let synced_at = Utc::now().naive_utc();
     let content = get_index_data(krate, conn).context("Failed to get index data")?;
 
     let rt = tokio::runtime::Builder::new_current_thread()
@@ -73,15 +79,21 @@ pub fn sync_to_sparse_index(
     let future = env.storage.sync_index(krate, content);
     rt.block_on(future).context("Failed to sync index data")?;
 
-    if let Some(cloudfront) = env.cloudfront() {
-        let path = Repository::relative_index_file_for_url(krate);
+    let file = ReplicatedFile::index_file(krate);
+    Job::enqueue_replication(&env.storage, file, conn)?;
 
-        info!(%path, "Invalidating index file on CloudFront");
-        cloudfront
-            .invalidate(env.http_client(), &path)
-            .context("Failed to invalidate CloudFront")?;
+    if let Some(cloudfront) = env.cloudfront() {
+        // The compressed variants are cached separately by CloudFront.
+        for path in env.storage.index_file_cdn_paths(krate) {
+            info!(%path, "Invalidating index file on CloudFront");
+            cloudfront
+                .invalidate(env.http_client(), &path)
+                .context("Failed to invalidate CloudFront")?;
+        }
     }
 
+    IndexSyncTime::record(krate, IndexKind::Sparse, synced_at, conn)?;
+
     Ok(())
 }
 
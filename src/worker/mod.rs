@@ -3,18 +3,55 @@
 //! the daily database maintenance, but also operations like rendering READMEs
 //! and uploading them to S3.
 
+mod account_compromise;
+mod archive;
+pub mod audit_export;
+mod backfill;
+mod checksums;
 pub mod cloudfront;
 mod daily_db_maintenance;
+mod download_anomalies;
+mod downloads_partitions;
 pub mod dump_db;
+mod expired_invitations;
 pub mod fastly;
+mod feature_usage;
 mod git;
+mod msrv_stats;
+mod orphaned_files;
+mod publish_dry_runs;
 mod readmes;
+mod replication;
+mod repositories;
+mod reproducibility;
+mod single_owner_nudges;
+mod subscription_digests;
 mod update_downloads;
 
+pub(crate) use account_compromise::perform_notify_account_compromise;
+pub(crate) use archive::{perform_archive_versions, perform_restore_crate_file};
+pub(crate) use audit_export::perform_export_audit_events;
+pub(crate) use backfill::perform_batched_backfill;
+pub(crate) use checksums::perform_verify_checksums;
 pub(crate) use daily_db_maintenance::perform_daily_db_maintenance;
+pub(crate) use download_anomalies::perform_detect_download_anomalies;
+pub(crate) use downloads_partitions::perform_manage_downloads_partitions;
 pub(crate) use dump_db::perform_dump_db;
+pub(crate) use expired_invitations::perform_expire_ownership_invitations;
+pub(crate) use feature_usage::perform_aggregate_feature_usage;
 pub(crate) use git::{
     perform_index_squash, perform_normalize_index, sync_to_git_index, sync_to_sparse_index,
 };
-pub(crate) use readmes::perform_render_and_upload_readme;
+pub(crate) use msrv_stats::perform_aggregate_msrv_stats;
+pub(crate) use orphaned_files::perform_sweep_orphaned_files;
+pub(crate) use publish_dry_runs::perform_expire_publish_dry_runs;
+pub(crate) use readmes::{
+    perform_render_and_upload_readme, perform_repair_readmes, render_readme, RenderLimits,
+    RenderedReadme,
+};
+pub(crate) use replication::perform_replicate_file;
+pub(crate) use repositories::perform_check_repositories;
+pub(crate) use reproducibility::perform_verify_reproducibility;
+pub(crate) use single_owner_nudges::perform_nudge_single_owner_crates;
+pub(crate) use subscription_digests::perform_send_subscription_digests;
 pub(crate) use update_downloads::perform_update_downloads;
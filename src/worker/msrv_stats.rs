@@ -0,0 +1,21 @@
+//! Record which `rust-version` recently published versions declare, so that
+//! crate authors can see which MSRVs are common when choosing their own.
+
+use crate::models::MsrvStat;
+use crate::swirl::PerformError;
+use chrono::Utc;
+use diesel::PgConnection;
+
+#[instrument(skip_all)]
+pub fn perform_aggregate_msrv_stats(
+    conn: &mut PgConnection,
+    days: i32,
+) -> Result<(), PerformError> {
+    let date = Utc::now().date_naive();
+
+    info!(%date, days, "Aggregating MSRV statistics");
+    let count = MsrvStat::aggregate(date, days, conn)?;
+    info!(%date, count, "Recorded MSRV statistics");
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! Delete files in the storage that don't belong to any crate or version in
+//! the database anymore.
+//!
+//! Deleting a crate or a version removes its files from the storage only after
+//! the database changes were committed, so a failed storage request leaves
+//! stray crate files, readmes or index files behind. Those are still served by
+//! the CDN, and would be served again if the name was published anew.
+//!
+//! Sweeps of all crates also delete the blobs of deduplicated crate files
+//! that no manifest references anymore.
+
+use crate::background_jobs::{Environment, Job};
+use crate::sql::lower;
+use crate::storage::CrateObjectKind;
+use crate::swirl::PerformError;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use std::collections::{BTreeSet, HashSet};
+
+#[instrument(skip_all, fields(krate.name = crate_name))]
+pub fn perform_sweep_orphaned_files(
+    conn: &mut PgConnection,
+    env: &Environment,
+    crate_name: Option<&str>,
+    min_age_hours: i64,
+    dry_run: bool,
+) -> Result<(), PerformError> {
+    use crate::schema::*;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    // Files are uploaded before the database transaction of a publish is
+    // committed, so recently modified files are never considered orphaned.
+    let cutoff = Utc::now() - Duration::hours(min_age_hours);
+
+    let mut checked = 0;
+    let mut orphaned = 0;
+    let mut unknown = 0;
+    let mut failed = 0;
+
+    rt.block_on(async {
+        let names = match crate_name {
+            Some(name) => BTreeSet::from([name.to_string()]),
+            None => env.storage.list_crate_names().await?,
+        };
+
+        // Crates whose names only differ in case share their index file.
+        let mut index_files = HashSet::new();
+
+        for name in names {
+            let objects = env.storage.list_all_for_crate(&name).await?;
+            if objects.is_empty() {
+                continue;
+            }
+
+            let versions: HashSet<String> = versions::table
+                .inner_join(crates::table)
+                .filter(crates::name.eq(&name))
+                .select(versions::num)
+                .load::<String>(conn)?
+                .into_iter()
+                .collect();
+
+            let indexed_crate = crates::table.filter(lower(crates::name).eq(name.to_lowercase()));
+            let crate_exists: bool = diesel::select(exists(indexed_crate)).get_result(conn)?;
+
+            for object in objects {
+                let is_orphaned = match &object.kind {
+                    CrateObjectKind::Index if !index_files.insert(object.path.clone()) => continue,
+                    CrateObjectKind::Index => !crate_exists,
+                    CrateObjectKind::Version(version) => !versions.contains(version),
+                    CrateObjectKind::Unknown => {
+                        warn!(path = %object.path, "Unexpected file in the crate storage");
+                        unknown += 1;
+                        continue;
+                    }
+                };
+
+                checked += 1;
+                if !is_orphaned || object.last_modified > cutoff {
+                    continue;
+                }
+
+                orphaned += 1;
+                if dry_run {
+                    info!(path = %object.path, "Found orphaned file");
+                    continue;
+                }
+
+                if let Err(error) = env.storage.delete_crate_object(&object).await {
+                    warn!(path = %object.path, %error, "Failed to delete orphaned file");
+                    failed += 1;
+                    continue;
+                }
+
+                info!(path = %object.path, "Deleted orphaned file");
+                if let Some(file) = object.replicated_file() {
+                    Job::enqueue_replication(&env.storage, file, conn)?;
+                }
+            }
+        }
+
+        if crate_name.is_some() {
+            return Ok(());
+        }
+
+        for blob in env.storage.list_unreferenced_blobs(cutoff).await? {
+            if blob.last_modified > cutoff {
+                continue;
+            }
+
+            orphaned += 1;
+            if dry_run {
+                info!(path = %blob.location, "Found unreferenced blob");
+                continue;
+            }
+
+            match env
+                .storage
+                .delete_unreferenced_blob(&blob.location, cutoff)
+                .await
+            {
+                Ok(true) => info!(path = %blob.location, "Deleted unreferenced blob"),
+                Ok(false) => info!(path = %blob.location, "Blob was referenced again"),
+                Err(error) => {
+                    warn!(path = %blob.location, %error, "Failed to delete unreferenced blob");
+                    failed += 1;
+                }
+            }
+        }
+
+        Ok::<_, PerformError>(())
+    })?;
+
+    info!(
+        checked,
+        orphaned, unknown, failed, dry_run, "Swept orphaned files"
+    );
+
+    Ok(())
+}
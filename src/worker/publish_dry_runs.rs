@@ -0,0 +1,20 @@
+//! Delete the files that publish dry runs uploaded to their shadow namespace
+//! in the storage, once they expired.
+
+use crate::background_jobs::Environment;
+use crate::swirl::PerformError;
+use anyhow::Context;
+use chrono::Utc;
+
+#[instrument(skip_all)]
+pub fn perform_expire_publish_dry_runs(env: &Environment) -> Result<(), PerformError> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let deleted = rt.block_on(env.storage.delete_expired_dry_runs(Utc::now()))?;
+    info!(deleted, "Deleted the files of expired publish dry runs");
+
+    Ok(())
+}
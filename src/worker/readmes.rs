@@ -1,12 +1,203 @@
 //! Render README files to HTML.
 
 use crate::swirl::PerformError;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use crates_io_markdown::text_to_html;
-use diesel::PgConnection;
+use crates_io_tarball::{process_tarball, TarballLimits};
+use diesel::{PgConnection, QueryResult};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::background_jobs::Environment;
+use crate::background_jobs::{Environment, Job, PRIORITY_RENDER_README};
+use crate::env_optional;
 use crate::models::Version;
+use crate::storage::ReplicatedFile;
+
+/// Versions that were published more recently than this are skipped by the
+/// repair job, since the README render job enqueued by the publish endpoint
+/// might not have run yet.
+const RENDER_GRACE_PERIOD_HOURS: i64 = 1;
+
+/// Uploaded instead of the rendered README if it could not be rendered
+/// within the [`RenderLimits`].
+const PLACEHOLDER: &str = "<p><em>The README of this crate could not be rendered.</em></p>\n";
+
+/// Execution limits for rendering a single README, so that pathological
+/// Markdown can't stall the background worker.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderLimits {
+    /// Wall-clock time after which the rendering is abandoned.
+    pub timeout: Duration,
+    /// Maximum size of the rendered HTML in bytes.
+    pub max_size: usize,
+    /// Maximum number of render threads that run at the same time, including
+    /// the ones that are left to finish on their own after a timeout.
+    pub max_threads: usize,
+}
+
+impl RenderLimits {
+    /// Reads the limits from the `README_RENDER_TIMEOUT_MS`,
+    /// `README_RENDER_MAX_SIZE` and `README_RENDER_MAX_THREADS` environment
+    /// variables, defaulting to 10 seconds, 5 MiB and 8 threads.
+    pub fn from_environment() -> Self {
+        let timeout = env_optional("README_RENDER_TIMEOUT_MS").unwrap_or(10_000);
+        let max_threads: usize = env_optional("README_RENDER_MAX_THREADS").unwrap_or(8);
+
+        Self {
+            timeout: Duration::from_millis(timeout),
+            max_size: env_optional("README_RENDER_MAX_SIZE").unwrap_or(5 * 1024 * 1024),
+            max_threads: max_threads.max(1),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum RenderError {
+    #[error("rendering took longer than {} ms", .0.as_millis())]
+    Timeout(Duration),
+    #[error("rendered README is {size} bytes large, which exceeds the limit of {max} bytes")]
+    TooLarge { size: usize, max: usize },
+    #[error("renderer panicked")]
+    Panicked,
+    #[error("all render threads are busy")]
+    Busy,
+}
+
+/// The error of [`render_readme`] if all render threads are still busy with
+/// other READMEs once the timeout has passed.
+///
+/// Unlike the other errors this doesn't depend on the README itself, so the
+/// rendering should be retried later instead of storing the placeholder.
+#[derive(Debug, thiserror::Error)]
+#[error("all {0} README render threads are busy")]
+pub struct RenderBusy(usize);
+
+/// The number of running render threads, see [`RenderLimits::max_threads`].
+///
+/// The renderer can't be interrupted, so this is what keeps READMEs that
+/// take forever to render from piling up threads.
+static RENDER_THREADS: RenderThreads = RenderThreads::new();
+
+struct RenderThreads {
+    running: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl RenderThreads {
+    const fn new() -> Self {
+        Self {
+            running: Mutex::new(0),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Waits until fewer than `max` render threads are running, or returns
+    /// `None` if that didn't happen before the `deadline`.
+    fn acquire(&'static self, max: usize, deadline: Instant) -> Option<RenderThreadSlot> {
+        let mut running = self.running.lock().unwrap();
+        while *running >= max {
+            let timeout = deadline.checked_duration_since(Instant::now())?;
+            running = self.finished.wait_timeout(running, timeout).unwrap().0;
+        }
+
+        *running += 1;
+        Some(RenderThreadSlot(self))
+    }
+}
+
+/// Counts as a running render thread until it is dropped at the end of the
+/// thread, even if the renderer panicked.
+struct RenderThreadSlot(&'static RenderThreads);
+
+impl Drop for RenderThreadSlot {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap() -= 1;
+        self.0.finished.notify_one();
+    }
+}
+
+/// A README rendered by [`render_readme`].
+#[derive(Debug)]
+pub struct RenderedReadme {
+    pub html: String,
+    /// Why the README could not be rendered, in which case `html` is a
+    /// placeholder.
+    pub error: Option<String>,
+}
+
+/// Renders a README to HTML within the given `limits`, falling back to a
+/// placeholder if they are exceeded.
+pub fn render_readme(
+    text: &str,
+    readme_path: &str,
+    base_url: Option<&str>,
+    pkg_path_in_vcs: Option<&str>,
+    limits: RenderLimits,
+) -> Result<RenderedReadme, RenderBusy> {
+    match try_render_readme(text, readme_path, base_url, pkg_path_in_vcs, limits) {
+        Ok(html) => Ok(RenderedReadme { html, error: None }),
+        Err(RenderError::Busy) => Err(RenderBusy(limits.max_threads)),
+        Err(error) => {
+            warn!(%readme_path, %error, "Failed to render README");
+
+            Ok(RenderedReadme {
+                html: PLACEHOLDER.to_string(),
+                error: Some(error.to_string()),
+            })
+        }
+    }
+}
+
+fn try_render_readme(
+    text: &str,
+    readme_path: &str,
+    base_url: Option<&str>,
+    pkg_path_in_vcs: Option<&str>,
+    limits: RenderLimits,
+) -> Result<String, RenderError> {
+    let text = text.to_string();
+    let readme_path = readme_path.to_string();
+    let base_url = base_url.map(String::from);
+    let pkg_path_in_vcs = pkg_path_in_vcs.map(String::from);
+
+    // The timeout includes the wait for a free render thread.
+    let deadline = Instant::now() + limits.timeout;
+    let slot = RENDER_THREADS
+        .acquire(limits.max_threads, deadline)
+        .ok_or(RenderError::Busy)?;
+
+    // The renderer can't be interrupted, so on timeout the thread is left to
+    // finish on its own and its result is discarded. It keeps its slot until
+    // then, so that at most `max_threads` of them can pile up.
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _slot = slot;
+        let rendered = text_to_html(
+            &text,
+            &readme_path,
+            base_url.as_deref(),
+            pkg_path_in_vcs.as_deref(),
+        );
+        let _ = sender.send(rendered);
+    });
+
+    let rendered = receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .map_err(|error| match error {
+            RecvTimeoutError::Timeout => RenderError::Timeout(limits.timeout),
+            RecvTimeoutError::Disconnected => RenderError::Panicked,
+        })?;
+
+    if rendered.len() > limits.max_size {
+        let size = rendered.len();
+        let max = limits.max_size;
+        return Err(RenderError::TooLarge { size, max });
+    }
+
+    Ok(rendered)
+}
 
 #[instrument(skip_all, fields(krate.name))]
 pub fn perform_render_and_upload_readme(
@@ -23,13 +214,14 @@ pub fn perform_render_and_upload_readme(
 
     info!(?version_id, "Rendering README");
 
-    let rendered = text_to_html(text, readme_path, base_url, pkg_path_in_vcs);
-    if rendered.is_empty() {
+    let limits = RenderLimits::from_environment();
+    let rendered = render_readme(text, readme_path, base_url, pkg_path_in_vcs, limits)?;
+    if rendered.html.is_empty() {
         return Ok(());
     }
 
     conn.transaction(|conn| {
-        Version::record_readme_rendering(version_id, conn)?;
+        Version::record_readme_rendering(version_id, rendered.error.as_deref(), conn)?;
         let (crate_name, vers): (String, String) = versions::table
             .find(version_id)
             .inner_join(crates::table)
@@ -44,10 +236,280 @@ pub fn perform_render_and_upload_readme(
             .context("Failed to initialize tokio runtime")
             .unwrap();
 
-        let bytes = rendered.into();
+        let bytes = rendered.html.into();
         let future = env.storage.upload_readme(&crate_name, &vers, bytes);
         rt.block_on(future)?;
 
+        let file = ReplicatedFile::readme(&crate_name, &vers);
+        Job::enqueue_replication(&env.storage, file, conn)?;
+
         Ok(())
     })
 }
+
+/// Enqueues README render jobs for recently published versions whose
+/// rendered README is missing from the storage, or which have no
+/// `readme_renderings` entry at all.
+///
+/// The README and its path are read from the stored crate file, so that
+/// versions whose manifest does not declare a README are skipped. Those are
+/// recorded in the `versions_without_readme` table, so that their crate file
+/// is only downloaded once. Versions whose render job is still queued are
+/// skipped too. At most `batch_size` renders are enqueued per run.
+#[instrument(skip_all)]
+pub fn perform_repair_readmes(
+    conn: &mut PgConnection,
+    env: &Environment,
+    lookback_days: i32,
+    batch_size: i64,
+) -> Result<(), PerformError> {
+    use crate::schema::*;
+    use diesel::dsl::{exists, not};
+    use diesel::prelude::*;
+
+    let now = chrono::Utc::now().naive_utc();
+    let published_after = now - chrono::Duration::days(lookback_days.into());
+    let published_before = now - chrono::Duration::hours(RENDER_GRACE_PERIOD_HOURS);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    // Whether a rendered README exists can only be checked against the
+    // storage, so the whole window is paged through until `batch_size`
+    // repairs have been enqueued. Limiting the query itself would never
+    // reach older missing renders if the newest versions are all fine.
+    let pending = Job::pending_readme_renders(conn)?;
+
+    let mut last_id = i32::MAX;
+    let mut checked = 0;
+    let mut enqueued = 0;
+    while enqueued < batch_size {
+        let without_readme = versions_without_readme::table
+            .filter(versions_without_readme::version_id.eq(versions::id));
+
+        let versions: Vec<(i32, String, String, bool)> = versions::table
+            .inner_join(crates::table)
+            .left_join(readme_renderings::table)
+            .filter(not(exists(without_readme)))
+            .filter(versions::created_at.gt(published_after))
+            .filter(versions::created_at.lt(published_before))
+            .filter(versions::id.lt(last_id))
+            .order(versions::id.desc())
+            .limit(batch_size)
+            .select((
+                versions::id,
+                crates::name,
+                versions::num,
+                readme_renderings::version_id.nullable().is_not_null(),
+            ))
+            .load(conn)?;
+
+        let Some(&(id, ..)) = versions.last() else {
+            break;
+        };
+        last_id = id;
+        checked += versions.len();
+
+        for (version_id, crate_name, vers, is_recorded) in versions {
+            if enqueued >= batch_size {
+                break;
+            }
+
+            if pending.contains(&version_id) {
+                continue;
+            }
+
+            if is_recorded && rt.block_on(env.storage.readme_exists(&crate_name, &vers))? {
+                continue;
+            }
+
+            let bytes = match rt.block_on(env.storage.read_crate_file(&crate_name, &vers)) {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    warn!(%crate_name, %vers, %error, "Failed to download crate file");
+                    continue;
+                }
+            };
+
+            // Crate files never change, so versions without a README that
+            // can be read are not checked again.
+            let readme = match read_package_readme(&bytes, &format!("{crate_name}-{vers}")) {
+                Ok(Some(readme)) => readme,
+                Ok(None) => {
+                    record_version_without_readme(version_id, conn)?;
+                    continue;
+                }
+                Err(error) => {
+                    warn!(%crate_name, %vers, %error, "Failed to read README from crate file");
+                    record_version_without_readme(version_id, conn)?;
+                    continue;
+                }
+            };
+
+            info!(%crate_name, %vers, "Enqueueing missing README render");
+
+            Job::render_and_upload_readme(
+                version_id,
+                readme.text,
+                readme.path,
+                readme.repository,
+                readme.pkg_path_in_vcs,
+            )
+            .enqueue_with_priority(conn, PRIORITY_RENDER_README)?;
+
+            enqueued += 1;
+        }
+    }
+
+    info!(checked, enqueued, "Finished repairing READMEs");
+
+    Ok(())
+}
+
+fn record_version_without_readme(version_id: i32, conn: &mut PgConnection) -> QueryResult<()> {
+    use crate::schema::versions_without_readme;
+    use diesel::prelude::*;
+
+    diesel::insert_into(versions_without_readme::table)
+        .values(versions_without_readme::version_id.eq(version_id))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// The README of a crate file, along with the metadata needed to render it.
+struct PackageReadme {
+    text: String,
+    path: String,
+    repository: Option<String>,
+    pkg_path_in_vcs: Option<String>,
+}
+
+/// Reads the README declared by the manifest of a crate file, or the first
+/// of cargo's default README files if the manifest doesn't declare one.
+///
+/// Returns `None` if the manifest opts out of a README, or if the README
+/// file is not part of the crate file.
+fn read_package_readme(bytes: &[u8], pkg_name: &str) -> anyhow::Result<Option<PackageReadme>> {
+    // The crate file was already validated when it was published, so the
+    // limits are not enforced again.
+    let tarball_info = process_tarball(pkg_name, bytes, &TarballLimits::default())
+        .context("Failed to process crate file")?;
+
+    let manifest = tarball_info
+        .manifest
+        .ok_or_else(|| anyhow!("Failed to find Cargo.toml file"))?;
+
+    // The README is looked up by `process_tarball`, with the same path
+    // normalization as the entries of the crate file.
+    let (Some(text), Some(readme_path)) = (tarball_info.readme_contents, tarball_info.readme_path)
+    else {
+        return Ok(None);
+    };
+    let path = readme_path.display().to_string();
+
+    Ok(Some(PackageReadme {
+        text,
+        path,
+        repository: manifest.package.repository,
+        pkg_path_in_vcs: tarball_info.vcs_info.map(|info| info.path_in_vcs),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crates_io_tarball::TarballBuilder;
+
+    const LIMITS: RenderLimits = RenderLimits {
+        timeout: Duration::from_secs(60),
+        max_size: 1024,
+        max_threads: 8,
+    };
+
+    #[test]
+    fn render_within_limits() {
+        let rendered = render_readme("# foo", "README.md", None, None, LIMITS).unwrap();
+        assert!(rendered.html.contains("foo</h1>"));
+        assert_none!(rendered.error);
+    }
+
+    #[test]
+    fn render_too_large() {
+        let text = "foo ".repeat(1000);
+        let rendered = render_readme(&text, "README.md", None, None, LIMITS).unwrap();
+        assert_eq!(rendered.html, PLACEHOLDER);
+        let error = rendered.error.unwrap();
+        assert!(error.ends_with("which exceeds the limit of 1024 bytes"));
+    }
+
+    #[test]
+    fn render_threads() {
+        static THREADS: RenderThreads = RenderThreads::new();
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+        let slot = THREADS.acquire(1, deadline).unwrap();
+        assert!(THREADS.acquire(1, deadline).is_none());
+
+        // A slot is freed once the render thread holding it finishes
+        thread::spawn(move || drop(slot)).join().unwrap();
+        assert!(THREADS.acquire(1, deadline).is_some());
+    }
+
+    #[test]
+    fn package_readme() {
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_file("foo-0.1.0/docs/README.md", b"readme")
+            .add_raw_manifest(
+                b"[package]\nreadme = \"docs/README.md\"\nrepository = \"https://github.com/foo/foo\"\n",
+            )
+            .add_file(
+                "foo-0.1.0/.cargo_vcs_info.json",
+                br#"{"path_in_vcs": "foo"}"#,
+            )
+            .build();
+
+        let readme = read_package_readme(&tarball, "foo-0.1.0").unwrap().unwrap();
+        assert_eq!(readme.text, "readme");
+        assert_eq!(readme.path, "docs/README.md");
+        assert_eq!(
+            readme.repository.as_deref(),
+            Some("https://github.com/foo/foo")
+        );
+        assert_eq!(readme.pkg_path_in_vcs.as_deref(), Some("foo"));
+
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_raw_manifest(b"[package]\n")
+            .add_file("foo-0.1.0/README.txt", b"readme")
+            .build();
+
+        let readme = read_package_readme(&tarball, "foo-0.1.0").unwrap().unwrap();
+        assert_eq!(readme.path, "README.txt");
+    }
+
+    #[test]
+    fn package_without_readme() {
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_raw_manifest(b"[package]\nreadme = false\n")
+            .add_file("foo-0.1.0/README.md", b"readme")
+            .build();
+        assert!(read_package_readme(&tarball, "foo-0.1.0")
+            .unwrap()
+            .is_none());
+
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_raw_manifest(b"[package]\n")
+            .build();
+        assert!(read_package_readme(&tarball, "foo-0.1.0")
+            .unwrap()
+            .is_none());
+
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_file("foo-0.1.0/README.md", b"readme")
+            .build();
+        assert!(read_package_readme(&tarball, "foo-0.1.0").is_err());
+    }
+}
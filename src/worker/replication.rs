@@ -0,0 +1,26 @@
+//! Mirror files of the primary storage to the replica buckets.
+//!
+//! See [`crate::storage::Storage::replicate_file`].
+
+use crate::background_jobs::Environment;
+use crate::storage::ReplicatedFile;
+use crate::swirl::PerformError;
+use anyhow::Context;
+
+#[instrument(skip_all, fields(path = %file.path()))]
+pub fn perform_replicate_file(
+    env: &Environment,
+    file: &ReplicatedFile,
+) -> Result<(), PerformError> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    rt.block_on(env.storage.replicate_file(file))
+        .context("Failed to replicate file")?;
+
+    info!("Replicated file");
+
+    Ok(())
+}
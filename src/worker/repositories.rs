@@ -0,0 +1,444 @@
+//! Periodically check whether the repositories that crates link to still
+//! exist, so that crate pages can point out crates whose repository was
+//! archived or deleted.
+//!
+//! GitHub repositories are looked up via the GitHub API, which also reports
+//! whether a repository is archived and redirects to the new location of
+//! renamed or transferred repositories. For all other hosts, only the
+//! existence of the repository page is checked.
+//!
+//! The URLs of other hosts are provided by publishers, so they are only
+//! fetched if their host resolves to public IP addresses. Redirects are
+//! followed manually, so that the same applies to every hop.
+
+use crate::background_jobs::Environment;
+use crate::models::{RepositoryCheck, RepositoryStatus};
+use crate::swirl::PerformError;
+use diesel::prelude::*;
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use reqwest::{header, StatusCode};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+use url::{Host, Url};
+
+/// How many redirects are followed when checking repositories on hosts
+/// other than GitHub.
+const MAX_REDIRECTS: usize = 5;
+
+/// How long a single request to a host other than GitHub may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct GitHubRepository {
+    full_name: String,
+    html_url: String,
+    archived: bool,
+}
+
+/// The outcome of a check that could be completed.
+#[derive(Debug, PartialEq, Eq)]
+struct CheckResult {
+    status: RepositoryStatus,
+    redirected_to: Option<String>,
+}
+
+#[instrument(skip_all)]
+pub fn perform_check_repositories(
+    conn: &mut PgConnection,
+    env: &Environment,
+    recheck_days: i32,
+    batch_size: i64,
+) -> Result<(), PerformError> {
+    let candidates = RepositoryCheck::candidates(recheck_days, batch_size, conn)?;
+
+    info!(count = candidates.len(), "Checking crate repositories");
+
+    for candidate in candidates {
+        let repository = &candidate.repository;
+
+        // Failed checks are not recorded, so that they are retried by the
+        // next job instead of waiting for `recheck_days`.
+        let result = match check_repository(env.http_client(), repository) {
+            Ok(Some(result)) => result,
+            Ok(None) => {
+                debug!(%repository, "Skipping repository check, unexpected response");
+                continue;
+            }
+            Err(error) => {
+                warn!(%repository, %error, "Failed to check repository");
+                continue;
+            }
+        };
+
+        if result.status != RepositoryStatus::Available || result.redirected_to.is_some() {
+            info!(
+                %repository,
+                status = result.status.as_str(),
+                redirected_to = ?result.redirected_to,
+                "Repository was archived, deleted or moved"
+            );
+        }
+
+        RepositoryCheck::record(
+            candidate.crate_id,
+            repository,
+            result.status,
+            result.redirected_to.as_deref(),
+            conn,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks a single repository URL.
+///
+/// Returns `None` if the response doesn't tell whether the repository
+/// exists, e.g. if the host is down or the API rate limit was exceeded.
+fn check_repository(client: &Client, repository: &str) -> reqwest::Result<Option<CheckResult>> {
+    let Ok(url) = Url::parse(repository) else {
+        return Ok(None);
+    };
+
+    match github_repository(&url) {
+        Some((owner, name)) => check_github_repository(client, &owner, &name),
+        None => check_url(url),
+    }
+}
+
+fn check_github_repository(
+    client: &Client,
+    owner: &str,
+    name: &str,
+) -> reqwest::Result<Option<CheckResult>> {
+    // The API responds with a redirect to the new location of renamed and
+    // transferred repositories, which the client follows.
+    let response = client
+        .get(format!("https://api.github.com/repos/{owner}/{name}"))
+        .header(header::ACCEPT, "application/vnd.github.v3+json")
+        .header(header::USER_AGENT, "crates.io (https://crates.io)")
+        .send()?;
+
+    match classify_status(response.status()) {
+        Some(RepositoryStatus::Available) => {
+            let repository: GitHubRepository = response.json()?;
+            Ok(Some(github_check_result(owner, name, repository)))
+        }
+        status => Ok(status.map(|status| CheckResult {
+            status,
+            redirected_to: None,
+        })),
+    }
+}
+
+fn github_check_result(owner: &str, name: &str, repository: GitHubRepository) -> CheckResult {
+    let status = if repository.archived {
+        RepositoryStatus::Archived
+    } else {
+        RepositoryStatus::Available
+    };
+
+    // GitHub treats repository names case-insensitively
+    let renamed = !repository
+        .full_name
+        .eq_ignore_ascii_case(&format!("{owner}/{name}"));
+
+    CheckResult {
+        status,
+        redirected_to: renamed.then_some(repository.html_url),
+    }
+}
+
+/// Checks a repository URL on a host other than GitHub.
+///
+/// Returns `None` if the URL or any redirect points to a private or reserved
+/// address, or if there are too many redirects.
+fn check_url(mut url: Url) -> reqwest::Result<Option<CheckResult>> {
+    for _ in 0..=MAX_REDIRECTS {
+        let Some(addrs) = public_addrs(&url) else {
+            debug!(%url, "Skipping repository check, URL is not public");
+            return Ok(None);
+        };
+
+        // The client only connects to the addresses that were checked, so
+        // that the host can't resolve to a different address in between.
+        let mut builder = Client::builder()
+            .redirect(Policy::none())
+            .no_proxy()
+            .timeout(REQUEST_TIMEOUT);
+        if let Some(Host::Domain(domain)) = url.host() {
+            builder = builder.resolve_to_addrs(domain, &addrs);
+        }
+
+        let response = builder
+            .build()?
+            .get(url.clone())
+            .header(header::USER_AGENT, "crates.io (https://crates.io)")
+            .send()?;
+
+        if !response.status().is_redirection() {
+            return Ok(
+                classify_status(response.status()).map(|status| CheckResult {
+                    status,
+                    redirected_to: None,
+                }),
+            );
+        }
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| url.join(location).ok());
+        let Some(location) = location else {
+            return Ok(None);
+        };
+        url = location;
+    }
+
+    Ok(None)
+}
+
+/// Returns the addresses that the host of `url` resolves to, or `None` if
+/// it isn't an HTTP(S) URL or any of the addresses isn't public.
+pub(super) fn public_addrs(url: &Url) -> Option<Vec<SocketAddr>> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    let port = url.port_or_known_default()?;
+    let addrs = match url.host()? {
+        Host::Domain(domain) => (domain, port).to_socket_addrs().ok()?.collect(),
+        Host::Ipv4(ip) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+        Host::Ipv6(ip) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+    };
+
+    let is_public = !addrs.is_empty() && addrs.iter().all(|addr| is_public_ip(addr.ip()));
+    is_public.then_some(addrs)
+}
+
+/// Returns whether `ip` is a globally reachable address, i.e. not a
+/// loopback, link-local, private, shared, documentation or otherwise
+/// reserved one.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "this network" (0.0.0.0/8)
+        || a == 0
+        // shared address space (100.64.0.0/10)
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF protocol assignments (192.0.0.0/24)
+        || (a == 192 && b == 0 && c == 0)
+        // benchmarking (198.18.0.0/15)
+        || (a == 198 && (b & 0xfe) == 18)
+        // reserved (240.0.0.0/4)
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // IPv4-compatible addresses (::/96)
+        || ip.segments()[..6].iter().all(|&segment| segment == 0)
+        // unique local addresses (fc00::/7)
+        || (first & 0xfe00) == 0xfc00
+        // link-local and deprecated site-local addresses (fe80::/10, fec0::/10)
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+        // documentation (2001:db8::/32)
+        || (first == 0x2001 && second == 0x0db8))
+}
+
+/// Maps the final status code of a repository lookup to the state of the
+/// repository, or `None` if the status code is inconclusive.
+fn classify_status(status: StatusCode) -> Option<RepositoryStatus> {
+    match status {
+        status if status.is_success() => Some(RepositoryStatus::Available),
+        StatusCode::NOT_FOUND | StatusCode::GONE => Some(RepositoryStatus::NotFound),
+        _ => None,
+    }
+}
+
+/// Returns the owner and name of a repository URL like
+/// `https://github.com/rust-lang/crates.io`, or `None` for other hosts.
+///
+/// Links to subdirectories like `https://github.com/foo/bar/tree/main/baz`
+/// are accepted too, since they are common in workspaces.
+fn github_repository(url: &Url) -> Option<(String, String)> {
+    if !matches!(url.host_str()?, "github.com" | "www.github.com") {
+        return None;
+    }
+
+    let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+    let owner = segments.next()?;
+    let name = segments.next()?;
+    let name = name.strip_suffix(".git").unwrap_or(name);
+
+    Some((owner.to_string(), name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn github(url: &str) -> Option<(String, String)> {
+        github_repository(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn github_repositories() {
+        let expected = Some(("rust-lang".to_string(), "crates.io".to_string()));
+        assert_eq!(github("https://github.com/rust-lang/crates.io"), expected);
+        assert_eq!(github("https://github.com/rust-lang/crates.io/"), expected);
+        assert_eq!(
+            github("https://github.com/rust-lang/crates.io.git"),
+            expected
+        );
+        assert_eq!(
+            github("http://www.github.com/rust-lang/crates.io"),
+            expected
+        );
+        assert_eq!(
+            github("https://github.com/rust-lang/crates.io/tree/main/crates_io_tarball"),
+            expected
+        );
+
+        assert_eq!(github("https://github.com/rust-lang"), None);
+        assert_eq!(github("https://gitlab.com/rust-lang/crates.io"), None);
+        assert_eq!(
+            github("https://github.com.evil.com/rust-lang/crates.io"),
+            None
+        );
+    }
+
+    #[test]
+    fn github_results() {
+        let repository = |full_name: &str, archived| GitHubRepository {
+            full_name: full_name.to_string(),
+            html_url: format!("https://github.com/{full_name}"),
+            archived,
+        };
+
+        assert_eq!(
+            github_check_result("foo", "bar", repository("Foo/Bar", false)),
+            CheckResult {
+                status: RepositoryStatus::Available,
+                redirected_to: None,
+            }
+        );
+        assert_eq!(
+            github_check_result("foo", "bar", repository("foo/bar", true)),
+            CheckResult {
+                status: RepositoryStatus::Archived,
+                redirected_to: None,
+            }
+        );
+        assert_eq!(
+            github_check_result("foo", "bar", repository("baz/bar", false)),
+            CheckResult {
+                status: RepositoryStatus::Available,
+                redirected_to: Some("https://github.com/baz/bar".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn public_ips() {
+        let public = |ip: &str| is_public_ip(ip.parse().unwrap());
+
+        assert!(public("1.1.1.1"));
+        assert!(public("140.82.121.4"));
+        assert!(public("2606:4700:4700::1111"));
+        assert!(public("::ffff:1.1.1.1"));
+
+        for ip in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "192.0.0.1",
+            "198.18.0.1",
+            "192.0.2.1",
+            "224.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::127.0.0.1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "fc00::1",
+            "fd12:3456:789a::1",
+            "fe80::1",
+            "fec0::1",
+            "2001:db8::1",
+            "ff02::1",
+        ] {
+            assert!(!public(ip), "{ip} should not be public");
+        }
+    }
+
+    #[test]
+    fn public_urls() {
+        let addrs = |url: &str| public_addrs(&Url::parse(url).unwrap());
+
+        assert_eq!(
+            addrs("https://1.1.1.1/foo"),
+            Some(vec!["1.1.1.1:443".parse().unwrap()])
+        );
+        assert_eq!(
+            addrs("http://[2606:4700:4700::1111]:8080/"),
+            Some(vec!["[2606:4700:4700::1111]:8080".parse().unwrap()])
+        );
+
+        assert_eq!(addrs("http://127.0.0.1/"), None);
+        assert_eq!(addrs("http://localhost:8888/"), None);
+        assert_eq!(addrs("http://169.254.169.254/latest/meta-data/"), None);
+        assert_eq!(addrs("https://10.1.2.3/"), None);
+        assert_eq!(addrs("http://[::1]/"), None);
+        assert_eq!(addrs("http://[fd00::1]/"), None);
+        assert_eq!(addrs("ftp://1.1.1.1/"), None);
+        assert_eq!(addrs("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(
+            classify_status(StatusCode::OK),
+            Some(RepositoryStatus::Available)
+        );
+        assert_eq!(
+            classify_status(StatusCode::NOT_FOUND),
+            Some(RepositoryStatus::NotFound)
+        );
+        assert_eq!(
+            classify_status(StatusCode::GONE),
+            Some(RepositoryStatus::NotFound)
+        );
+        assert_eq!(classify_status(StatusCode::FORBIDDEN), None);
+        assert_eq!(classify_status(StatusCode::BAD_GATEWAY), None);
+    }
+}
@@ -0,0 +1,434 @@
+//! Verify that published crate files can be reproduced from the source
+//! repository of the crate.
+//!
+//! Instead of running `cargo package` on the repository checkout, which would
+//! require a full Rust toolchain on the background worker, the files of the
+//! crate file are compared directly with the files in the repository at the
+//! git tag of the published version.
+//!
+//! The repository URL is provided by the publisher, so it is only cloned if
+//! the crate file of the version links to the same repository and its host
+//! resolves to public IP addresses. git is restricted to these addresses and
+//! doesn't follow redirects, so that it can't be pointed at internal hosts.
+
+use crate::background_jobs::Environment;
+use crate::models::VersionReproducibility;
+use crate::swirl::PerformError;
+use crate::worker::repositories::public_addrs;
+use anyhow::{anyhow, Context};
+use crates_io_tarball::{CargoVcsInfo, Decoder, Manifest};
+use diesel::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use url::{Host, Url};
+
+/// Files that are generated by `cargo package` and thus can't be compared
+/// with the files in the repository.
+///
+/// The original `Cargo.toml` file is included in the crate file as
+/// `Cargo.toml.orig`, which is compared with `Cargo.toml` in the repository
+/// instead.
+const GENERATED_FILES: &[&str] = &["Cargo.toml", "Cargo.lock", ".cargo_vcs_info.json"];
+
+/// How long cloning a repository may take before it is aborted.
+const CLONE_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[instrument(skip_all, fields(krate.name))]
+pub fn perform_verify_reproducibility(
+    conn: &mut PgConnection,
+    env: &Environment,
+    version_id: i32,
+) -> Result<(), PerformError> {
+    use crate::schema::*;
+
+    let (crate_name, vers, repository): (String, String, Option<String>) = versions::table
+        .find(version_id)
+        .inner_join(crates::table)
+        .select((crates::name, versions::num, crates::repository))
+        .first(conn)?;
+
+    tracing::Span::current().record("krate.name", tracing::field::display(&crate_name));
+
+    let Some(url) = repository.as_deref().and_then(verifiable_repository) else {
+        info!(
+            ?version_id,
+            "Skipping reproducibility check, no verifiable repository URL"
+        );
+        return Ok(());
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to initialize tokio runtime")?;
+
+    let bytes = rt.block_on(env.storage.read_crate_file(&crate_name, &vers))?;
+    let crate_file = read_crate_file(&bytes, &format!("{crate_name}-{vers}"))?;
+
+    // The repository of the crate is taken from its newest version, so older
+    // versions could have been published from a different repository.
+    if crate_file.repository != repository {
+        info!(
+            ?version_id,
+            "Skipping reproducibility check, version links to a different repository"
+        );
+        return Ok(());
+    }
+
+    let Some(path_in_vcs) = crate_file.path_in_vcs else {
+        info!(
+            ?version_id,
+            "Skipping reproducibility check, crate was not packaged from git"
+        );
+        return Ok(());
+    };
+
+    // `path_in_vcs` is provided by the publisher, so it must not point
+    // outside of the repository checkout.
+    let path_in_vcs = Path::new(&path_in_vcs);
+    if !path_in_vcs
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        warn!(
+            ?version_id,
+            ?path_in_vcs,
+            "Skipping reproducibility check, invalid path in git"
+        );
+        return Ok(());
+    }
+
+    let Some(addrs) = public_addrs(&url) else {
+        warn!(
+            ?version_id,
+            %url,
+            "Skipping reproducibility check, repository host is not public"
+        );
+        return Ok(());
+    };
+
+    let tempdir = tempfile::tempdir()?;
+    let checkout = tempdir.path().join("checkout");
+
+    let mut git_tag = None;
+    for tag in tag_candidates(&crate_name, &vers) {
+        if clone_tag(&url, &addrs, &tag, &checkout)? {
+            git_tag = Some(tag);
+            break;
+        }
+    }
+
+    let Some(git_tag) = git_tag else {
+        info!(
+            ?version_id,
+            "Skipping reproducibility check, no matching git tag found"
+        );
+        return Ok(());
+    };
+
+    let mismatches = find_mismatches(&crate_file.digests, &checkout, path_in_vcs)?;
+    if !mismatches.is_empty() {
+        warn!(?version_id, %git_tag, ?mismatches, "Crate file is not reproducible from git");
+    }
+
+    VersionReproducibility::record(version_id, mismatches.is_empty(), &git_tag, conn)?;
+
+    Ok(())
+}
+
+/// Only public repositories that can be cloned via HTTPS are checked.
+fn verifiable_repository(url: &str) -> Option<Url> {
+    Url::parse(url).ok().filter(|url| url.scheme() == "https")
+}
+
+/// Returns the git tag names that are commonly used for a release, in the
+/// order in which they are tried.
+fn tag_candidates(crate_name: &str, vers: &str) -> Vec<String> {
+    vec![
+        format!("v{vers}"),
+        vers.to_string(),
+        format!("{crate_name}-v{vers}"),
+        format!("{crate_name}-{vers}"),
+    ]
+}
+
+/// Clones the given tag of the repository into `path`. Symlinks are checked
+/// out as plain files, so that they can't point outside of the checkout.
+///
+/// git only connects to `addrs`, so that the host can't resolve to a
+/// different address after it was checked, and it only speaks HTTPS without
+/// following redirects.
+///
+/// Returns `false` if the repository or the tag does not exist, or if cloning
+/// takes longer than [`CLONE_TIMEOUT`].
+fn clone_tag(url: &Url, addrs: &[SocketAddr], tag: &str, path: &Path) -> anyhow::Result<bool> {
+    let mut command = Command::new("git");
+    command
+        .args(["-c", "core.symlinks=false"])
+        .args(["-c", "protocol.allow=never"])
+        .args(["-c", "protocol.https.allow=always"])
+        .args(["-c", "http.followRedirects=false"]);
+    if let Some(resolve) = curl_resolve(url, addrs) {
+        command
+            .arg("-c")
+            .arg(format!("http.curloptResolve={resolve}"));
+    }
+
+    let mut child = command
+        .args([
+            "clone",
+            "--quiet",
+            "--depth=1",
+            "--single-branch",
+            "--branch",
+            tag,
+        ])
+        .args(["--", url.as_str()])
+        .arg(path)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_LFS_SKIP_SMUDGE", "1")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run `git clone` command")?;
+
+    let deadline = Instant::now() + CLONE_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status.success());
+        }
+
+        if Instant::now() >= deadline {
+            warn!(%url, %tag, "Aborting `git clone` command after timeout");
+            child.kill()?;
+            child.wait()?;
+            let _ = std::fs::remove_dir_all(path);
+            return Ok(false);
+        }
+
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Returns the `host:port:addresses` entry that pins the host of `url` to
+/// `addrs` in curl, or `None` if the URL already contains an IP address.
+fn curl_resolve(url: &Url, addrs: &[SocketAddr]) -> Option<String> {
+    let Some(Host::Domain(domain)) = url.host() else {
+        return None;
+    };
+
+    let port = url.port_or_known_default()?;
+    let addrs = addrs
+        .iter()
+        .map(|addr| match addr {
+            SocketAddr::V4(addr) => addr.ip().to_string(),
+            SocketAddr::V6(addr) => format!("[{}]", addr.ip()),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Some(format!("{domain}:{port}:{addrs}"))
+}
+
+struct CrateFile {
+    /// The `repository` field of the normalized `Cargo.toml` file.
+    repository: Option<String>,
+    /// The `path_in_vcs` value of the `.cargo_vcs_info.json` file, or `None`
+    /// if the crate was not packaged from a git repository.
+    path_in_vcs: Option<String>,
+    /// SHA256 digests of all non-generated files, keyed by their path
+    /// relative to the package root.
+    digests: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+fn read_crate_file(bytes: &[u8], pkg_name: &str) -> anyhow::Result<CrateFile> {
+    let mut archive = tar::Archive::new(Decoder::new(bytes)?);
+
+    let mut repository = None;
+    let mut path_in_vcs = None;
+    let mut digests = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.into_owned();
+        let path = path
+            .strip_prefix(pkg_name)
+            .map_err(|_| anyhow!("invalid path in crate file: {}", path.display()))?
+            .to_path_buf();
+
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(anyhow!("invalid path in crate file: {}", path.display()));
+        }
+
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+
+        if path == Path::new("Cargo.toml") {
+            let contents = String::from_utf8_lossy(&contents);
+            let manifest = toml::from_str::<Manifest>(&contents).ok();
+            repository = manifest.and_then(|manifest| manifest.package.repository);
+        } else if path == Path::new(".cargo_vcs_info.json") {
+            let contents = String::from_utf8_lossy(&contents);
+            path_in_vcs = Some(CargoVcsInfo::from_contents(&contents)?.path_in_vcs);
+        } else if !GENERATED_FILES.iter().any(|file| path == Path::new(file)) {
+            digests.insert(path, Sha256::digest(&contents).to_vec());
+        }
+    }
+
+    Ok(CrateFile {
+        repository,
+        path_in_vcs,
+        digests,
+    })
+}
+
+/// Returns the paths of all files that are missing from the package directory
+/// at `path_in_vcs` of the repository checkout or have different contents.
+fn find_mismatches<'a>(
+    digests: &'a BTreeMap<PathBuf, Vec<u8>>,
+    checkout: &Path,
+    path_in_vcs: &Path,
+) -> anyhow::Result<Vec<&'a Path>> {
+    let checkout = checkout.canonicalize()?;
+    let package_dir = checkout.join(path_in_vcs);
+
+    let mismatches = digests
+        .iter()
+        .filter(|(path, digest)| {
+            let repo_path = match path.to_str() {
+                Some("Cargo.toml.orig") => package_dir.join("Cargo.toml"),
+                _ => package_dir.join(path),
+            };
+
+            match read_checkout_file(&checkout, &repo_path) {
+                Some(contents) => Sha256::digest(contents).as_slice() != digest.as_slice(),
+                None => true,
+            }
+        })
+        .map(|(path, _)| path.as_path())
+        .collect();
+
+    Ok(mismatches)
+}
+
+/// Reads a file of the repository checkout, unless it can't be read or the
+/// path leads through a symlink or outside of the checkout.
+fn read_checkout_file(checkout: &Path, path: &Path) -> Option<Vec<u8>> {
+    // The checkout path is canonical, so the canonical path of a file only
+    // differs from its path if a symlink was followed.
+    let canonical = path.canonicalize().ok()?;
+    if canonical != path || !canonical.starts_with(checkout) {
+        return None;
+    }
+
+    std::fs::read(canonical).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crates_io_tarball::TarballBuilder;
+
+    #[test]
+    fn verifiable_repositories() {
+        assert_some!(verifiable_repository(
+            "https://github.com/rust-lang/crates.io"
+        ));
+        assert_none!(verifiable_repository(
+            "http://github.com/rust-lang/crates.io"
+        ));
+        assert_none!(verifiable_repository(
+            "git@github.com:rust-lang/crates.io.git"
+        ));
+        assert_none!(verifiable_repository("file:///etc"));
+    }
+
+    #[test]
+    fn curl_resolve_entries() {
+        let addrs: Vec<SocketAddr> = vec![
+            "140.82.121.4:443".parse().unwrap(),
+            "[2606:50c0:8000::154]:443".parse().unwrap(),
+        ];
+
+        let url = Url::parse("https://github.com/rust-lang/crates.io").unwrap();
+        assert_some_eq!(
+            curl_resolve(&url, &addrs),
+            "github.com:443:140.82.121.4,[2606:50c0:8000::154]"
+        );
+
+        let url = Url::parse("https://140.82.121.4:8443/foo").unwrap();
+        assert_none!(curl_resolve(&url, &addrs[..1]));
+    }
+
+    #[test]
+    fn mismatches() {
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_file("foo-0.1.0/Cargo.toml", b"[package]\nname = \"foo\"\n")
+            .add_file("foo-0.1.0/Cargo.toml.orig", b"[package]\nname = \"foo\"")
+            .add_file(
+                "foo-0.1.0/.cargo_vcs_info.json",
+                br#"{"path_in_vcs": "foo"}"#,
+            )
+            .add_file("foo-0.1.0/src/lib.rs", b"pub fn foo() {}")
+            .add_file("foo-0.1.0/src/main.rs", b"fn main() {}")
+            .add_file("foo-0.1.0/README.md", b"foo")
+            .build();
+
+        let crate_file = read_crate_file(&tarball, "foo-0.1.0").unwrap();
+        assert_eq!(crate_file.path_in_vcs.as_deref(), Some("foo"));
+        assert_eq!(crate_file.digests.len(), 4);
+
+        let repo = tempfile::tempdir().unwrap();
+        let path_in_vcs = Path::new("foo");
+        let package_dir = repo.path().join(path_in_vcs);
+        std::fs::create_dir_all(package_dir.join("src")).unwrap();
+        std::fs::write(package_dir.join("Cargo.toml"), "[package]\nname = \"foo\"").unwrap();
+        std::fs::write(package_dir.join("src/lib.rs"), "pub fn foo() {}").unwrap();
+        std::fs::write(package_dir.join("src/main.rs"), "fn main() { }").unwrap();
+
+        let mismatches = find_mismatches(&crate_file.digests, repo.path(), path_in_vcs).unwrap();
+        assert_eq!(
+            mismatches,
+            [Path::new("README.md"), Path::new("src/main.rs")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_followed() {
+        let tarball = TarballBuilder::new("foo", "0.1.0")
+            .add_file("foo-0.1.0/src/lib.rs", b"secret")
+            .add_file("foo-0.1.0/README.md", b"secret")
+            .build();
+        let crate_file = read_crate_file(&tarball, "foo-0.1.0").unwrap();
+
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "secret").unwrap();
+        std::fs::write(outside.path().join("README.md"), "secret").unwrap();
+
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir(repo.path().join("src")).unwrap();
+        let secret = outside.path().join("secret");
+        std::os::unix::fs::symlink(&secret, repo.path().join("src/lib.rs")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), repo.path().join("linked")).unwrap();
+        std::fs::write(repo.path().join("README.md"), "secret").unwrap();
+
+        let mismatches = find_mismatches(&crate_file.digests, repo.path(), Path::new("")).unwrap();
+        assert_eq!(mismatches, [Path::new("src/lib.rs")]);
+
+        let mismatches =
+            find_mismatches(&crate_file.digests, repo.path(), Path::new("linked")).unwrap();
+        assert_eq!(
+            mismatches,
+            [Path::new("README.md"), Path::new("src/lib.rs")]
+        );
+    }
+}
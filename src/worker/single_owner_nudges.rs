@@ -0,0 +1,141 @@
+//! Ask the only owners of popular crates to add another owner.
+//!
+//! Nobody can publish fixes for a crate anymore if its only owner loses access
+//! to their account or steps away from the project. Popular crates that are
+//! owned by a single user are recorded in the `single_owner_nudges` table, so
+//! that the owner sees a hint on their dashboard, and the owner can
+//! additionally be reminded by email every few months.
+
+use crate::background_jobs::Environment;
+use crate::models::{OwnerKind, User};
+use crate::schema::{crate_owners, crates, single_owner_nudges};
+use crate::swirl::PerformError;
+use chrono::{Duration, Utc};
+use diesel::dsl::now;
+use diesel::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[instrument(skip_all)]
+pub fn perform_nudge_single_owner_crates(
+    conn: &mut PgConnection,
+    env: &Environment,
+    min_downloads: i32,
+    reminder_days: i32,
+    send_emails: bool,
+) -> Result<(), PerformError> {
+    let owners: Vec<(i32, i32, i32)> = crate_owners::table
+        .inner_join(crates::table)
+        .filter(crates::downloads.ge(min_downloads))
+        .filter(crate_owners::deleted.eq(false))
+        .select((
+            crate_owners::crate_id,
+            crate_owners::owner_id,
+            crate_owners::owner_kind,
+        ))
+        .load(conn)?;
+
+    let mut owners_by_crate: HashMap<i32, Vec<(i32, i32)>> = HashMap::new();
+    for (crate_id, owner_id, owner_kind) in owners {
+        let owners = owners_by_crate.entry(crate_id).or_default();
+        owners.push((owner_id, owner_kind));
+    }
+
+    // A team owner counts as a backup, since all of its members can publish.
+    let single_owners: HashSet<(i32, i32)> = owners_by_crate
+        .into_iter()
+        .filter_map(|(crate_id, owners)| match owners[..] {
+            [(user_id, kind)] if kind == OwnerKind::User as i32 => Some((crate_id, user_id)),
+            _ => None,
+        })
+        .collect();
+
+    // Crates that were flagged before, but got another owner or were
+    // transferred to a different user since then.
+    let previous: Vec<(i32, i32)> = single_owner_nudges::table
+        .select((single_owner_nudges::crate_id, single_owner_nudges::user_id))
+        .load(conn)?;
+    let resolved: Vec<i32> = previous
+        .into_iter()
+        .filter(|nudge| !single_owners.contains(nudge))
+        .map(|(crate_id, _)| crate_id)
+        .collect();
+
+    diesel::delete(single_owner_nudges::table)
+        .filter(single_owner_nudges::crate_id.eq_any(&resolved))
+        .execute(conn)?;
+
+    let new_nudges = single_owners
+        .iter()
+        .map(|(crate_id, user_id)| {
+            (
+                single_owner_nudges::crate_id.eq(crate_id),
+                single_owner_nudges::user_id.eq(user_id),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let flagged = diesel::insert_into(single_owner_nudges::table)
+        .values(&new_nudges)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    info!(
+        single_owners = single_owners.len(),
+        flagged,
+        resolved = resolved.len(),
+        "Updated single owner crates"
+    );
+
+    if !send_emails {
+        return Ok(());
+    }
+
+    let cutoff = (Utc::now() - Duration::days(reminder_days.into())).naive_utc();
+    let due: Vec<(i32, i32, String)> = single_owner_nudges::table
+        .inner_join(crates::table)
+        .filter(
+            single_owner_nudges::emailed_at
+                .is_null()
+                .or(single_owner_nudges::emailed_at.lt(cutoff)),
+        )
+        .select((
+            single_owner_nudges::user_id,
+            single_owner_nudges::crate_id,
+            crates::name,
+        ))
+        .order(crates::name)
+        .load(conn)?;
+
+    let mut crates_by_user: BTreeMap<i32, Vec<(i32, String)>> = BTreeMap::new();
+    for (user_id, crate_id, crate_name) in due {
+        let crates = crates_by_user.entry(user_id).or_default();
+        crates.push((crate_id, crate_name));
+    }
+
+    let mut emailed = 0;
+    for (user_id, crates) in crates_by_user {
+        let user = User::find(conn, user_id)?;
+        let Some(email) = user.verified_email(conn)? else {
+            continue;
+        };
+
+        let (crate_ids, crate_names): (Vec<_>, Vec<_>) = crates.into_iter().unzip();
+        let result = env
+            .emails()
+            .send_single_owner_nudge(&email, &user.gh_login, &crate_names);
+        if let Err(error) = result {
+            warn!(%user_id, ?error, "Failed to send email notification");
+            continue;
+        }
+
+        diesel::update(single_owner_nudges::table)
+            .filter(single_owner_nudges::crate_id.eq_any(&crate_ids))
+            .set(single_owner_nudges::emailed_at.eq(now.nullable()))
+            .execute(conn)?;
+        emailed += 1;
+    }
+
+    info!(emailed, "Reminded owners of single owner crates");
+
+    Ok(())
+}
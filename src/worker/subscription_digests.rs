@@ -0,0 +1,117 @@
+//! Send users an email digest of the releases that match their keyword and
+//! category subscriptions, and of the activity of the crates that they follow
+//! with the email channel.
+//!
+//! The job is meant to run frequently, e.g. hourly. A user receives a digest
+//! once the period of their digest frequency has passed since the previous
+//! digest, and each digest covers the releases since the previous one. If the
+//! email can't be sent, the releases are included in the next attempt.
+
+use crate::background_jobs::Environment;
+use crate::models::{
+    digest_entries, follow_activity, DigestPreference, FollowChannel, FollowEvent, User,
+    VersionAction,
+};
+use crate::schema::{category_subscriptions, follows, keyword_subscriptions};
+use crate::swirl::PerformError;
+use chrono::Utc;
+use diesel::prelude::*;
+use std::collections::BTreeSet;
+
+#[instrument(skip_all)]
+pub fn perform_send_subscription_digests(
+    conn: &mut PgConnection,
+    env: &Environment,
+    max_entries: i64,
+) -> Result<(), PerformError> {
+    let now = Utc::now().naive_utc();
+
+    let mut user_ids: BTreeSet<i32> = keyword_subscriptions::table
+        .select(keyword_subscriptions::user_id)
+        .distinct()
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    user_ids.extend(
+        category_subscriptions::table
+            .select(category_subscriptions::user_id)
+            .distinct()
+            .load::<i32>(conn)?,
+    );
+
+    user_ids.extend(
+        follows::table
+            .filter(follows::channel.eq(FollowChannel::Email))
+            .select(follows::user_id)
+            .distinct()
+            .load::<i32>(conn)?,
+    );
+
+    let mut sent = 0;
+    for user_id in user_ids {
+        let preference = DigestPreference::for_user(user_id, conn)?;
+        let Some(period) = preference.frequency.period() else {
+            continue;
+        };
+
+        let since = match preference.last_sent_at {
+            Some(last_sent_at) if last_sent_at + period > now => continue,
+            Some(last_sent_at) => last_sent_at,
+            None => now - period,
+        };
+
+        let mut activity = follow_activity(user_id, since, now, max_entries + 1, conn)?;
+        let mut entries = digest_entries(user_id, since, now, max_entries + 1, conn)?;
+
+        // Releases of followed crates are only listed once, as activity of
+        // the followed crate.
+        entries.retain(|entry| {
+            !activity.iter().any(|activity| {
+                activity.crate_name == entry.crate_name
+                    && matches!(
+                        &activity.event,
+                        FollowEvent::Version { num, action: VersionAction::Publish }
+                            if *num == entry.version
+                    )
+            })
+        });
+
+        let truncated = entries.len() as i64 > max_entries || activity.len() as i64 > max_entries;
+        entries.truncate(max_entries as usize);
+        activity.truncate(max_entries as usize);
+
+        if !entries.is_empty() || !activity.is_empty() {
+            let user = User::find(conn, user_id)?;
+            if let Some(email) = user.verified_email(conn)? {
+                let result = env.emails().send_subscription_digest(
+                    &email,
+                    &user.gh_login,
+                    &entries,
+                    &activity,
+                    truncated,
+                );
+
+                // Failing to send one digest should not prevent the other
+                // digests from being sent. The releases are included in the
+                // next attempt instead.
+                if let Err(error) = result {
+                    warn!(%user_id, ?error, "Failed to send subscription digest");
+                    continue;
+                }
+
+                sent += 1;
+            }
+        }
+
+        DigestPreference {
+            last_sent_at: Some(now),
+            ..preference
+        }
+        .save(conn)?;
+    }
+
+    info!(sent, "Sent subscription digests");
+
+    Ok(())
+}
@@ -85,6 +85,7 @@ mod test {
     use crate::email::Emails;
     use crate::models::{Crate, NewCrate, NewUser, NewVersion, User, Version};
     use crate::test_util::pg_connection;
+    use crate::util::source_links::VersionSource;
     use std::collections::BTreeMap;
 
     fn user(conn: &mut PgConnection) -> User {
@@ -111,6 +112,8 @@ mod test {
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             None,
             None,
+            false,
+            VersionSource::default(),
         )
         .unwrap();
         let version = version.save(conn, "someone@example.com").unwrap();